@@ -0,0 +1,13 @@
+import { createHash, timingSafeEqual } from "crypto";
+
+// Check a user's password against the stored salted hash, comparing in
+// constant time so the check does not leak timing information.
+export function verifyPassword(password: string, salt: string, storedHash: string): boolean {
+  const hash = createHash("sha256").update(salt + password).digest();
+  return timingSafeEqual(hash, Buffer.from(storedHash, "hex"));
+}
+
+// Reject sessions whose token has expired.
+export function isSessionValid(expiresAt: number, now: number = Date.now()): boolean {
+  return now < expiresAt;
+}
@@ -0,0 +1,22 @@
+from collections import OrderedDict
+
+
+class LruCache:
+    """Least-recently-used cache that evicts the oldest entry when full."""
+
+    def __init__(self, capacity):
+        self.capacity = capacity
+        self.entries = OrderedDict()
+
+    def get(self, key):
+        # Move the entry to the end so it is evicted last
+        if key not in self.entries:
+            return None
+        self.entries.move_to_end(key)
+        return self.entries[key]
+
+    def put(self, key, value):
+        self.entries[key] = value
+        self.entries.move_to_end(key)
+        if len(self.entries) > self.capacity:
+            self.entries.popitem(last=False)
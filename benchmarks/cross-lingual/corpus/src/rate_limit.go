@@ -0,0 +1,25 @@
+package ratelimit
+
+import "time"
+
+// 令牌桶限流器：按固定速率补充令牌，没有令牌时拒绝请求。
+type TokenBucket struct {
+	capacity int
+	tokens   int
+	refill   time.Duration
+	last     time.Time
+}
+
+// Allow 消耗一个令牌；桶空时返回 false。
+func (b *TokenBucket) Allow(now time.Time) bool {
+	refilled := int(now.Sub(b.last) / b.refill)
+	if refilled > 0 {
+		b.tokens = min(b.capacity, b.tokens+refilled)
+		b.last = now
+	}
+	if b.tokens == 0 {
+		return false
+	}
+	b.tokens--
+	return true
+}
@@ -0,0 +1,21 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Retry a failed HTTP request with exponential backoff, giving up after
+/// `max_attempts` tries.
+pub fn retry_with_backoff<T, E>(max_attempts: u32, mut call: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    let mut delay = Duration::from_millis(100);
+    let mut attempt = 1;
+    loop {
+        match call() {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt >= max_attempts => return Err(err),
+            Err(_) => {
+                // Wait before the next attempt, doubling the delay each time
+                sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Self-contained search bundles: chunk metadata plus a flat vector index in one
+//! byte blob, for query-only hosts such as a browser loading a pre-built index
+//! over HTTP. Nothing here touches the filesystem, so it compiles to wasm32.
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use super::{AnnIndex, SimpleIndex};
+
+const MAGIC: &[u8; 4] = b"CKSB";
+/// Bumped whenever the serialized layout changes.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Location and preview of one indexed chunk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleChunk {
+    /// Forward-slash path relative to the indexed tree
+    pub path: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub language: Option<String>,
+    pub symbol: Option<String>,
+    /// First lines of the chunk
+    pub snippet: String,
+}
+
+/// A chunk matching a query, with its cosine similarity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleHit<'a> {
+    pub chunk: &'a BundleChunk,
+    pub score: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SearchBundle {
+    model: String,
+    chunks: Vec<BundleChunk>,
+    index: SimpleIndex,
+}
+
+impl SearchBundle {
+    /// Bundle `chunks` with their embeddings from `model`. Query vectors must come
+    /// from the same model.
+    pub fn new(model: impl Into<String>, chunks: Vec<(BundleChunk, Vec<f32>)>) -> Result<Self> {
+        let (chunks, vectors): (Vec<_>, Vec<_>) = chunks.into_iter().unzip();
+        Ok(Self {
+            model: model.into(),
+            chunks,
+            index: SimpleIndex::build(&vectors)?,
+        })
+    }
+
+    /// Embedding model the vectors were produced with
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.index.dim
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// The `top_k` chunks closest to the query embedding, best first.
+    pub fn search(&self, query: &[f32], top_k: usize) -> Result<Vec<BundleHit<'_>>> {
+        let hits = self.index.search(query, top_k)?;
+        Ok(hits
+            .into_iter()
+            .filter_map(|(id, score)| {
+                self.chunks
+                    .get(id as usize)
+                    .map(|chunk| BundleHit { chunk, score })
+            })
+            .collect())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&BUNDLE_VERSION.to_le_bytes());
+        bytes.extend(bincode::serialize(self)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+            bail!("Not a ck search bundle. Export one with `ck --export-bundle FILE`.");
+        };
+        let Some((version, payload)) = rest.split_first_chunk::<4>() else {
+            bail!("Truncated ck search bundle");
+        };
+        let version = u32::from_le_bytes(*version);
+        if version != BUNDLE_VERSION {
+            bail!(
+                "Search bundle version {} is not supported (expected {}). Re-export it with this version of ck.",
+                version,
+                BUNDLE_VERSION
+            );
+        }
+        Ok(bincode::deserialize(payload)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(path: &str, line_start: usize) -> BundleChunk {
+        BundleChunk {
+            path: path.to_string(),
+            line_start,
+            line_end: line_start + 2,
+            language: Some("rust".to_string()),
+            symbol: None,
+            snippet: format!("fn at_{}() {{}}", line_start),
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trip_and_search() {
+        let bundle = SearchBundle::new(
+            "BAAI/bge-small-en-v1.5",
+            vec![
+                (chunk("src/a.rs", 1), vec![1.0, 0.0]),
+                (chunk("src/b.rs", 10), vec![0.0, 1.0]),
+            ],
+        )
+        .unwrap();
+
+        let bytes = bundle.to_bytes().unwrap();
+        let loaded = SearchBundle::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.model(), "BAAI/bge-small-en-v1.5");
+        assert_eq!(loaded.dimensions(), 2);
+        assert_eq!(loaded.len(), 2);
+
+        let hits = loaded.search(&[0.1, 0.9], 1).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk, &chunk("src/b.rs", 10));
+        assert!(hits[0].score > 0.9);
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_foreign_and_newer_data() {
+        assert!(SearchBundle::from_bytes(b"not a bundle").is_err());
+
+        let mut bytes = SearchBundle::new("m", Vec::new())
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        bytes[4..8].copy_from_slice(&(BUNDLE_VERSION + 1).to_le_bytes());
+        let err = SearchBundle::from_bytes(&bytes).err().unwrap();
+        assert!(err.to_string().contains("not supported"));
+    }
+}
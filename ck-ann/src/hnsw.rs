@@ -0,0 +1,416 @@
+//! Hierarchical navigable small world graph (Malkov & Yashunin) over cosine
+//! similarity. Vectors are normalized on insert, so distance is `1 - dot`.
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+use std::path::Path;
+
+use crate::AnnIndex;
+
+/// Graph construction and search parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HnswParams {
+    /// Links per node on the upper layers; layer 0 keeps twice as many
+    pub m: usize,
+    /// Candidate list size while inserting; larger builds slower, links better
+    pub ef_construction: usize,
+    /// Candidate list size while searching; larger is slower with higher recall
+    pub ef_search: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+/// A node's distance to the current query, ordered for the heaps below.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored {
+    distance: f32,
+    node: u32,
+}
+
+impl Eq for Scored {}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HnswIndex {
+    params: HnswParams,
+    dim: usize,
+    /// Normalized vectors, `dim` values per node
+    vectors: Vec<f32>,
+    ids: Vec<u32>,
+    /// `links[node][layer]`: neighbors of `node` on each layer it reaches
+    links: Vec<Vec<Vec<u32>>>,
+    entry: Option<u32>,
+    /// State of the xorshift generator drawing node layers, kept so builds are
+    /// reproducible
+    rng: u64,
+}
+
+impl HnswIndex {
+    pub fn new(params: HnswParams) -> Self {
+        Self {
+            params: HnswParams {
+                m: params.m.max(2),
+                ef_construction: params.ef_construction.max(1),
+                ef_search: params.ef_search.max(1),
+            },
+            dim: 0,
+            vectors: Vec::new(),
+            ids: Vec::new(),
+            links: Vec::new(),
+            entry: None,
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    /// Build a graph over `vectors`, identified by their positions.
+    pub fn build_with(vectors: &[Vec<f32>], params: HnswParams) -> Result<Self> {
+        let mut index = Self::new(params);
+        for (id, vector) in vectors.iter().enumerate() {
+            index.insert(id as u32, vector)?;
+        }
+        Ok(index)
+    }
+
+    pub fn params(&self) -> HnswParams {
+        self.params
+    }
+
+    /// Change the search-time candidate list size; the graph is unaffected.
+    pub fn set_ef_search(&mut self, ef_search: usize) {
+        self.params.ef_search = ef_search.max(1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dim
+    }
+
+    /// The stored (normalized) vector of the `n`th inserted node.
+    pub fn vector(&self, n: usize) -> &[f32] {
+        &self.vectors[n * self.dim..(n + 1) * self.dim]
+    }
+
+    /// The id the `n`th inserted node was added under.
+    pub fn id(&self, n: usize) -> u32 {
+        self.ids[n]
+    }
+
+    pub fn insert(&mut self, id: u32, vector: &[f32]) -> Result<()> {
+        if self.dim == 0 {
+            if vector.is_empty() {
+                bail!(
+                    "Embedding vectors are empty. The embedding model returned 0 values per vector. Re-run the command with a supported embedding model or rebuild the index."
+                );
+            }
+            self.dim = vector.len();
+        }
+        if vector.len() != self.dim {
+            bail!(
+                "Embedding size mismatch while building index: expected {} values but received {}. Clean the index (`ck --clean .`) and rebuild with a single model.",
+                self.dim,
+                vector.len()
+            );
+        }
+
+        let node = self.ids.len() as u32;
+        self.vectors.extend(normalized(vector));
+        self.ids.push(id);
+        let level = self.random_level();
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(node);
+            return Ok(());
+        };
+        let top = self.links[entry as usize].len() - 1;
+        let query = self.vector(node as usize).to_vec();
+
+        let mut nearest = vec![self.scored(&query, entry)];
+        for layer in (level + 1..=top).rev() {
+            nearest = self.search_layer(&query, &nearest, 1, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            nearest = self.search_layer(&query, &nearest, self.params.ef_construction, layer);
+            let neighbors = self.select_neighbors(&nearest, self.params.m);
+            self.links[node as usize][layer] = neighbors.clone();
+            for neighbor in neighbors {
+                self.link(neighbor, node, layer);
+            }
+        }
+        if level > top {
+            self.entry = Some(node);
+        }
+        Ok(())
+    }
+
+    /// Top `topk` ids by cosine similarity, exploring `ef` candidates.
+    pub fn search_with_ef(&self, query: &[f32], topk: usize, ef: usize) -> Result<Vec<(u32, f32)>> {
+        let Some(entry) = self.entry else {
+            return Ok(Vec::new());
+        };
+        if query.len() != self.dim {
+            bail!(
+                "Embedding size mismatch during search: this index stores vectors with {expected} values, but the query provided {actual}. Re-run the command with the original model or clean the index (`ck --clean .`) and rebuild with a single model.",
+                expected = self.dim,
+                actual = query.len()
+            );
+        }
+        let query = normalized(query);
+        let mut nearest = vec![self.scored(&query, entry)];
+        for layer in (1..self.links[entry as usize].len()).rev() {
+            nearest = self.search_layer(&query, &nearest, 1, layer);
+        }
+        let nearest = self.search_layer(&query, &nearest, ef.max(topk), 0);
+        Ok(nearest
+            .into_iter()
+            .take(topk)
+            .map(|scored| (self.ids[scored.node as usize], 1.0 - scored.distance))
+            .collect())
+    }
+
+    /// Top `topk` ids by scanning every vector: the ground truth for tuning.
+    pub fn exact_search(&self, query: &[f32], topk: usize) -> Vec<(u32, f32)> {
+        let query = normalized(query);
+        let mut scored: Vec<Scored> = (0..self.len() as u32)
+            .map(|node| self.scored(&query, node))
+            .collect();
+        scored.sort_unstable();
+        scored
+            .into_iter()
+            .take(topk)
+            .map(|scored| (self.ids[scored.node as usize], 1.0 - scored.distance))
+            .collect()
+    }
+
+    fn scored(&self, query: &[f32], node: u32) -> Scored {
+        let dot: f32 = query
+            .iter()
+            .zip(self.vector(node as usize))
+            .map(|(a, b)| a * b)
+            .sum();
+        Scored {
+            distance: 1.0 - dot,
+            node,
+        }
+    }
+
+    /// Layer for a new node: geometric with ratio `1 / m`.
+    fn random_level(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let uniform = ((self.rng >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let level = -uniform.ln() / (self.params.m as f64).ln();
+        (level as usize).min(16)
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.params.m * 2
+        } else {
+            self.params.m
+        }
+    }
+
+    /// Best-first search of one layer from `entry_points`, returning up to `ef`
+    /// nodes closest first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[Scored],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Scored> {
+        let mut visited: HashSet<u32> = entry_points.iter().map(|scored| scored.node).collect();
+        let mut candidates: BinaryHeap<Reverse<Scored>> =
+            entry_points.iter().copied().map(Reverse).collect();
+        let mut found: BinaryHeap<Scored> = entry_points.iter().copied().collect();
+
+        while let Some(Reverse(closest)) = candidates.pop() {
+            if found
+                .peek()
+                .is_some_and(|furthest| closest.distance > furthest.distance && found.len() >= ef)
+            {
+                break;
+            }
+            let Some(neighbors) = self.links[closest.node as usize].get(layer) else {
+                continue;
+            };
+            for &neighbor in neighbors {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let scored = self.scored(query, neighbor);
+                if found.len() < ef || found.peek().is_some_and(|furthest| scored < *furthest) {
+                    candidates.push(Reverse(scored));
+                    found.push(scored);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    /// Neighbor selection heuristic: skip a candidate that is closer to an
+    /// already chosen neighbor than to the new node, so links spread across
+    /// clusters; top up with the closest skipped ones.
+    fn select_neighbors(&self, candidates: &[Scored], m: usize) -> Vec<u32> {
+        let mut chosen: Vec<Scored> = Vec::with_capacity(m);
+        let mut skipped = Vec::new();
+        for &candidate in candidates {
+            if chosen.len() >= m {
+                break;
+            }
+            let vector = self.vector(candidate.node as usize);
+            if chosen
+                .iter()
+                .all(|kept| self.scored(vector, kept.node).distance > candidate.distance)
+            {
+                chosen.push(candidate);
+            } else {
+                skipped.push(candidate);
+            }
+        }
+        chosen.extend(skipped.into_iter().take(m - chosen.len()));
+        chosen.into_iter().map(|scored| scored.node).collect()
+    }
+
+    /// Add a back link from `from` to `to`, pruning `from`'s links to the
+    /// layer's limit.
+    fn link(&mut self, from: u32, to: u32, layer: usize) {
+        self.links[from as usize][layer].push(to);
+        if self.links[from as usize][layer].len() <= self.max_links(layer) {
+            return;
+        }
+        let base = self.vector(from as usize).to_vec();
+        let mut scored: Vec<Scored> = self.links[from as usize][layer]
+            .iter()
+            .map(|&node| self.scored(&base, node))
+            .collect();
+        scored.sort_unstable();
+        self.links[from as usize][layer] = self.select_neighbors(&scored, self.max_links(layer));
+    }
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        vector.to_vec()
+    } else {
+        vector.iter().map(|x| x / norm).collect()
+    }
+}
+
+impl AnnIndex for HnswIndex {
+    fn build(vectors: &[Vec<f32>]) -> Result<Self> {
+        Self::build_with(vectors, HnswParams::default())
+    }
+
+    #[tracing::instrument(name = "ann_search", level = "debug", skip_all, fields(vectors = self.ids.len(), topk))]
+    fn search(&self, query: &[f32], topk: usize) -> Result<Vec<(u32, f32)>> {
+        if self.is_empty() {
+            bail!(
+                "The ANN index is empty. Reindex the repository before running semantic search (`ck --index`)."
+            );
+        }
+        self.search_with_ef(query, topk, self.params.ef_search)
+    }
+
+    fn add(&mut self, id: u32, vector: &[f32]) -> Result<()> {
+        self.insert(id, vector)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let data = bincode::serialize(self)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        Ok(bincode::deserialize(&data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random vectors in a few loose clusters.
+    fn vectors(count: usize, dim: usize) -> Vec<Vec<f32>> {
+        let mut state = 42u64;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+        };
+        (0..count)
+            .map(|i| {
+                (0..dim)
+                    .map(|d| next() + if d == i % 8 { 1.0 } else { 0.0 })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hnsw_recall_against_exact_search() {
+        let data = vectors(2000, 32);
+        let index = HnswIndex::build_with(&data, HnswParams::default()).unwrap();
+        assert_eq!(index.len(), 2000);
+
+        let mut hits = 0;
+        for query in data.iter().step_by(40) {
+            let truth: HashSet<u32> = index
+                .exact_search(query, 10)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            hits += index
+                .search(query, 10)
+                .unwrap()
+                .iter()
+                .filter(|(id, _)| truth.contains(id))
+                .count();
+        }
+        let recall = hits as f32 / (50 * 10) as f32;
+        assert!(recall >= 0.95, "recall {recall}");
+
+        // The narrowest search still returns a neighbor
+        let narrow = index.search_with_ef(&data[7], 1, 1).unwrap();
+        assert_eq!(narrow.len(), 1);
+    }
+}
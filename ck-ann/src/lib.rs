@@ -2,6 +2,11 @@ use anyhow::{Result, bail};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
+mod bundle;
+mod hnsw;
+pub use bundle::{BUNDLE_VERSION, BundleChunk, BundleHit, SearchBundle};
+pub use hnsw::{HnswIndex, HnswParams};
+
 pub trait AnnIndex: Send + Sync {
     fn build(vectors: &[Vec<f32>]) -> Result<Self>
     where
@@ -14,8 +19,11 @@ pub trait AnnIndex: Send + Sync {
         Self: Sized;
 }
 
-pub fn create_index(_backend: Option<&str>) -> Result<Box<dyn AnnIndex>> {
-    Ok(Box::new(SimpleIndex::new()?))
+pub fn create_index(backend: Option<&str>) -> Result<Box<dyn AnnIndex>> {
+    match backend {
+        Some("hnsw") => Ok(Box::new(HnswIndex::new(HnswParams::default()))),
+        _ => Ok(Box::new(SimpleIndex::new()?)),
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -81,6 +89,7 @@ impl AnnIndex for SimpleIndex {
         })
     }
 
+    #[tracing::instrument(name = "ann_search", level = "debug", skip_all, fields(vectors = self.ids.len(), topk))]
     fn search(&self, query: &[f32], topk: usize) -> Result<Vec<(u32, f32)>> {
         if self.dim == 0 {
             bail!(
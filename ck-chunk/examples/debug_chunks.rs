@@ -68,6 +68,7 @@ fn test_striding() {
         max_tokens: 200,    // Very small limit to trigger striding
         stride_overlap: 50, // 25% overlap
         enable_striding: true,
+        ..Default::default()
     };
 
     let code = std::fs::read_to_string("examples/code/large_function.py")
@@ -218,6 +219,7 @@ def very_large_function():
         max_tokens: 8192,     // Nomic model's actual limit
         stride_overlap: 1024, // 12.5% overlap
         enable_striding: true,
+        ..Default::default()
     };
 
     let strided_chunks = chunk_text_with_config(large_code, Some(Language::Python), &config)
@@ -0,0 +1,130 @@
+//! Chunkers for languages and file types ck does not split well itself, such
+//! as protobuf, Terraform or SQL migrations. A downstream crate registers a
+//! [`Chunker`] for a language or an extension with [`register_chunker`]; a
+//! user names a subprocess plugin for one under `"chunkers"` in the model
+//! registry (see [`crate::PluginChunker`]), which takes precedence.
+//! Either replaces ck's own splitting for matching files. Their chunks are
+//! strided like any other, so a chunker may return whole definitions however
+//! long they are. A chunker that fails is reported and the file is chunked
+//! as usual.
+
+use anyhow::Result;
+use ck_core::{ChunkStrategy, Language};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use crate::{Chunk, ChunkConfig, get_model_chunk_config};
+
+/// Splits a file's text into chunks.
+pub trait Chunker: Send + Sync {
+    /// Chunks of `text`, a file's decoded contents, in `language` when ck
+    /// recognizes it. Build them with [`Chunk::from_range`]; columns are
+    /// filled in afterwards.
+    fn chunk(&self, text: &str, language: Option<Language>) -> Result<Vec<Chunk>>;
+}
+
+/// The files a registered chunker applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChunkerKey {
+    Language(Language),
+    /// File extension without the dot, matched in any case
+    Extension(String),
+}
+
+static CHUNKERS: LazyLock<RwLock<HashMap<ChunkerKey, Arc<dyn Chunker>>>> =
+    LazyLock::new(Default::default);
+
+/// Chunk the files `key` matches with `chunker`, replacing any chunker
+/// registered for it before.
+pub fn register_chunker<C: Chunker + 'static>(key: ChunkerKey, chunker: C) {
+    let key = match key {
+        ChunkerKey::Extension(extension) => ChunkerKey::Extension(extension.to_lowercase()),
+        key => key,
+    };
+    CHUNKERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, Arc::new(chunker));
+}
+
+/// The chunker for `path`: one configured for its extension or language in
+/// `configured` (the user's `"chunkers"`), else one registered for its
+/// extension, else for `language`.
+pub fn chunker_for(
+    path: &Path,
+    language: Option<Language>,
+    configured: &BTreeMap<String, String>,
+) -> Option<Arc<dyn Chunker>> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+    let by_name = |name: &str| {
+        configured
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, provider)| provider)
+    };
+    let provider = extension
+        .as_deref()
+        .and_then(by_name)
+        .or_else(|| language.and_then(|language| by_name(&language.to_string())));
+    if let Some(provider) = provider {
+        match provider.strip_prefix(crate::plugin::PLUGIN_PREFIX) {
+            Some(program) => return Some(crate::plugin::shared(program)),
+            None => tracing::warn!(
+                "Ignoring chunker '{}' for {:?}: expected plugin:/path/to/program",
+                provider,
+                path
+            ),
+        }
+    }
+
+    let chunkers = CHUNKERS.read().unwrap_or_else(|e| e.into_inner());
+    extension
+        .and_then(|extension| chunkers.get(&ChunkerKey::Extension(extension)))
+        .or_else(|| language.and_then(|language| chunkers.get(&ChunkerKey::Language(language))))
+        .cloned()
+}
+
+/// Chunk the file at `path` whose contents are `text`: with its custom
+/// chunker when [`chunker_for`] finds one, else by key path for HCL, YAML
+/// and JSON, by statement for SQL and by definition for protobuf, Thrift
+/// and GraphQL under [`ChunkStrategy::Ast`], otherwise as
+/// [`crate::chunk_text_with_strategy`] does.
+pub fn chunk_file(
+    path: &Path,
+    text: &str,
+    language: Option<Language>,
+    model_name: Option<&str>,
+    strategy: ChunkStrategy,
+    configured: &BTreeMap<String, String>,
+) -> Result<Vec<Chunk>> {
+    let (max_tokens, stride_overlap) = get_model_chunk_config(model_name);
+    let config = ChunkConfig {
+        max_tokens,
+        stride_overlap,
+        enable_striding: true,
+        strategy,
+    };
+    if let Some(chunker) = chunker_for(path, language, configured) {
+        match chunker.chunk(text, language) {
+            Ok(chunks) => return crate::finish_chunks(chunks, text, &config),
+            Err(e) => tracing::warn!(
+                "Custom chunker failed for {:?}, chunking it as usual: {}",
+                path,
+                e
+            ),
+        }
+    } else if strategy == ChunkStrategy::Ast
+        && let Some(chunker) = crate::structured::for_path(path)
+    {
+        // Files that do not parse fall through quietly
+        match chunker.chunk(text, language) {
+            Ok(chunks) => return crate::finish_chunks(chunks, text, &config),
+            Err(e) => tracing::debug!("Chunking {:?} as plain text: {}", path, e),
+        }
+    }
+    crate::chunk_text_with_strategy(text, language, model_name, strategy)
+}
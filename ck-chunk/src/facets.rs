@@ -0,0 +1,181 @@
+//! How much of each chunk is code, comments or string literals, for
+//! `--prefer`. Languages with a tree-sitter grammar are classified from their
+//! syntax tree (a bare string statement in Python counts as a docstring);
+//! other C-family languages by a lexical scan of `//`, `/* */` and quotes.
+//! Text without a language, such as Markdown, and PDFs are prose throughout.
+
+use ck_core::{Facet, Facets, Language};
+
+use crate::{ParseableLanguage, tree_sitter_language};
+
+/// Facets of each byte range of `text`, e.g. the spans of its chunks.
+pub fn chunk_facets(
+    text: &str,
+    language: Option<Language>,
+    spans: &[(usize, usize)],
+) -> Vec<Facets> {
+    let classes = classify(text, language);
+    let bytes = text.as_bytes();
+    spans
+        .iter()
+        .map(|&(start, end)| {
+            let end = end.min(bytes.len());
+            let start = start.min(end);
+            let mut counts = [0usize; 3];
+            for (byte, class) in bytes[start..end].iter().zip(&classes[start..end]) {
+                if !byte.is_ascii_whitespace() {
+                    counts[*class as usize] += 1;
+                }
+            }
+            let total = counts.iter().sum::<usize>();
+            if total == 0 {
+                return Facets {
+                    code: 1.0,
+                    ..Facets::default()
+                };
+            }
+            let share = |facet: Facet| counts[facet as usize] as f32 / total as f32;
+            Facets {
+                code: share(Facet::Code),
+                comments: share(Facet::Comments),
+                strings: share(Facet::Strings),
+            }
+        })
+        .collect()
+}
+
+/// The facet of every byte of `text`.
+fn classify(text: &str, language: Option<Language>) -> Vec<Facet> {
+    let mut classes = vec![Facet::Code; text.len()];
+    match language {
+        None | Some(Language::Pdf) => classes.fill(Facet::Comments),
+        Some(language) => match ParseableLanguage::try_from(language) {
+            Ok(parseable) => {
+                if !classify_tree(text, parseable, &mut classes) {
+                    classify_lexically(text, &mut classes);
+                }
+            }
+            Err(_) => classify_lexically(text, &mut classes),
+        },
+    }
+    classes
+}
+
+/// Mark comment and string nodes; `false` when the text can't be parsed.
+fn classify_tree(text: &str, language: ParseableLanguage, classes: &mut [Facet]) -> bool {
+    let mut parser = tree_sitter::Parser::new();
+    let Ok(ts_language) = tree_sitter_language(language) else {
+        return false;
+    };
+    if parser.set_language(&ts_language).is_err() {
+        return false;
+    }
+    let Some(tree) = parser.parse(text, None) else {
+        return false;
+    };
+
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        let kind = node.kind();
+        let facet = if kind.contains("comment") {
+            Some(Facet::Comments)
+        } else if node.is_named() && is_string_kind(kind) {
+            let docstring = language == ParseableLanguage::Python
+                && node
+                    .parent()
+                    .is_some_and(|parent| parent.kind() == "expression_statement");
+            Some(if docstring {
+                Facet::Comments
+            } else {
+                Facet::Strings
+            })
+        } else {
+            None
+        };
+        match facet {
+            Some(facet) => classes[node.start_byte()..node.end_byte()].fill(facet),
+            None => {
+                let mut cursor = node.walk();
+                stack.extend(node.children(&mut cursor));
+            }
+        }
+    }
+    true
+}
+
+fn is_string_kind(kind: &str) -> bool {
+    kind.ends_with("string")
+        || kind.ends_with("string_literal")
+        || kind == "template_string"
+        || kind == "char_literal"
+}
+
+/// `//` and `/* */` comments and quoted strings, for languages without a
+/// grammar here.
+fn classify_lexically(text: &str, classes: &mut [Facet]) {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        let (facet, len) = if rest.starts_with(b"//") {
+            let len = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            (Facet::Comments, len)
+        } else if rest.starts_with(b"/*") {
+            let len = rest[2..]
+                .windows(2)
+                .position(|pair| pair == b"*/")
+                .map_or(rest.len(), |end| end + 4);
+            (Facet::Comments, len)
+        } else if rest[0] == b'"' || rest[0] == b'\'' {
+            let quote = rest[0];
+            let mut end = 1;
+            while end < rest.len() && rest[end] != quote && rest[end] != b'\n' {
+                end += if rest[end] == b'\\' { 2 } else { 1 };
+            }
+            (Facet::Strings, (end + 1).min(rest.len()))
+        } else {
+            i += 1;
+            continue;
+        };
+        classes[i..i + len].fill(facet);
+        i += len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn whole(text: &str, language: Option<Language>) -> Facets {
+        chunk_facets(text, language, &[(0, text.len())])[0]
+    }
+
+    #[test]
+    fn test_facets_from_syntax_tree() {
+        let rust = "// Retries with backoff because the gateway drops requests\nfn retry() { call(\"x\"); }\n";
+        let facets = whole(rust, Some(Language::Rust));
+        assert!(facets.comments > 0.5, "{:?}", facets);
+        assert!(facets.strings > 0.0 && facets.code > 0.0);
+        assert!((facets.code + facets.comments + facets.strings - 1.0).abs() < 1e-5);
+
+        let python = "def charge():\n    \"\"\"Charge the card once per order.\"\"\"\n    return gateway.post(\"/charges\")\n";
+        let facets = whole(python, Some(Language::Python));
+        assert!(facets.comments > facets.strings && facets.strings > 0.0);
+
+        assert_eq!(whole("# Retries\n\nWhy we retry.", None).comments, 1.0);
+    }
+
+    #[test]
+    fn test_lexical_facets_and_weights() {
+        let java = "/* Why */ String s = \"a\\\"b\"; // note\nint x = 1;";
+        let facets = whole(java, Some(Language::Java));
+        assert!(facets.comments > 0.0 && facets.strings > 0.0 && facets.code > 0.0);
+
+        let prose = Facets {
+            comments: 1.0,
+            ..Facets::default()
+        };
+        assert!(prose.weight(Facet::Comments) > 1.0);
+        assert!(prose.weight(Facet::Code) < 1.0);
+    }
+}
@@ -0,0 +1,147 @@
+//! Chunking for interface definitions: protobuf (`.proto`), Thrift
+//! (`.thrift`) and GraphQL (`.graphql`, `.gql`) schemas. Each top-level
+//! definition (message, struct, type, enum, service, operation) is a chunk
+//! named for what it defines, with a `.proto` file's package as its
+//! breadcrumb. Lines between definitions (`syntax`, `import`, `option`,
+//! comments) join the definition after them.
+
+use anyhow::{Result, bail};
+use ck_core::Language;
+
+use crate::structured::{BraceLayout, cover};
+use crate::{Chunk, ChunkType, Chunker};
+
+pub(crate) struct IdlChunker;
+
+/// Keywords opening a definition, and the chunk type of what they define.
+const DEFINITIONS: &[(&str, ChunkType)] = &[
+    // protobuf
+    ("message", ChunkType::Class),
+    ("enum", ChunkType::Class),
+    ("service", ChunkType::Module),
+    // Thrift
+    ("struct", ChunkType::Class),
+    ("union", ChunkType::Class),
+    ("exception", ChunkType::Class),
+    ("typedef", ChunkType::Text),
+    ("const", ChunkType::Text),
+    // GraphQL
+    ("type", ChunkType::Class),
+    ("input", ChunkType::Class),
+    ("interface", ChunkType::Class),
+    ("scalar", ChunkType::Class),
+    ("schema", ChunkType::Module),
+    ("directive", ChunkType::Text),
+    ("query", ChunkType::Function),
+    ("mutation", ChunkType::Function),
+    ("subscription", ChunkType::Function),
+    ("fragment", ChunkType::Function),
+];
+
+impl Chunker for IdlChunker {
+    fn chunk(&self, text: &str, _language: Option<Language>) -> Result<Vec<Chunk>> {
+        let lines: Vec<&str> = text.lines().collect();
+        let layout = BraceLayout::new(&lines)?;
+        let mut package = None;
+        let mut spans = Vec::new();
+        for node in layout.entries(0, lines.len(), 0) {
+            let line = lines[node.first].trim();
+            if let Some(name) = line.strip_prefix("package ") {
+                package = Some(name.trim_end_matches(';').trim().to_string());
+                continue;
+            }
+            if let Some((chunk_type, symbol)) = definition(line) {
+                spans.push((node.first, node.last, (chunk_type, symbol)));
+            }
+        }
+        if spans.is_empty() {
+            bail!("no definitions found");
+        }
+
+        Ok(cover(text, &lines, spans, |_, _| {})
+            .into_iter()
+            .map(|(mut chunk, (chunk_type, symbol))| {
+                chunk.chunk_type = chunk_type;
+                chunk.metadata.symbol = symbol;
+                chunk.metadata.breadcrumb = package.clone();
+                chunk
+            })
+            .collect())
+    }
+}
+
+/// The chunk type and name of the definition opening with `line`, such as
+/// `message UserProfile {`, `service Users extends Base {`,
+/// `extend type Query {` or `query GetUser($id: ID!) {`.
+fn definition(line: &str) -> Option<(ChunkType, Option<String>)> {
+    let mut words = line
+        .split(|c: char| c.is_whitespace() || matches!(c, '{' | '(' | '=' | ':'))
+        .filter(|word| !word.is_empty());
+    let mut keyword = words.next()?;
+    if keyword == "extend" {
+        keyword = words.next()?;
+    }
+    let (_, chunk_type) = DEFINITIONS.iter().find(|(word, _)| *word == keyword)?;
+    let name = match keyword {
+        // `typedef i64 UserId`, `const i32 MAX = 10`
+        "typedef" | "const" => words.nth(1),
+        _ => words.next(),
+    };
+    Some((chunk_type.clone(), name.map(str::to_string)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    fn symbols(path: &str, text: &str) -> Vec<(Option<String>, Option<String>)> {
+        crate::chunk_file(
+            Path::new(path),
+            text,
+            None,
+            None,
+            Default::default(),
+            &BTreeMap::new(),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|chunk| (chunk.metadata.symbol, chunk.metadata.breadcrumb))
+        .collect()
+    }
+
+    #[test]
+    fn test_idl_chunks_by_definition() {
+        let proto = "syntax = \"proto3\";\npackage acme.user.v1;\n\nimport \"google/protobuf/timestamp.proto\";\n\n// A user's public profile\nmessage UserProfile {\n  string name = 1; // shown as {name}\n  message Avatar {\n    string url = 1;\n  }\n  Avatar avatar = 2;\n}\n\nservice Users {\n  rpc GetProfile(GetProfileRequest) returns (UserProfile);\n}\n";
+        let package = Some("acme.user.v1".to_string());
+        assert_eq!(
+            symbols("api/user.proto", proto),
+            [
+                (Some("UserProfile".to_string()), package.clone()),
+                (Some("Users".to_string()), package),
+            ]
+        );
+
+        let graphql = "\"\"\"\nA user\n\"\"\"\ntype User implements Node {\n  id: ID!\n}\n\nextend type Query {\n  me: User\n}\n\nunion Result = User | Error\n\nquery GetUser($id: ID!) {\n  user(id: $id) { id }\n}\n";
+        let names: Vec<_> = symbols("schema.graphql", graphql)
+            .into_iter()
+            .map(|(symbol, _)| symbol.unwrap())
+            .collect();
+        assert_eq!(names, ["User", "Query", "Result", "GetUser"]);
+
+        let thrift = "namespace go acme.user\n\ntypedef i64 UserId\n\nstruct User {\n  1: UserId id\n}\n\nservice UserService extends Base {\n  User get(1: UserId id)\n}\n";
+        let names: Vec<_> = symbols("user.thrift", thrift)
+            .into_iter()
+            .map(|(symbol, _)| symbol.unwrap())
+            .collect();
+        assert_eq!(names, ["UserId", "User", "UserService"]);
+    }
+
+    #[test]
+    fn test_idl_comment_with_multibyte_text() {
+        assert_eq!(
+            symbols("user.proto", "/* 用户 */\nmessage A {\n}\n"),
+            [(Some("A".to_string()), None)]
+        );
+    }
+}
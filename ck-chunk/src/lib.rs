@@ -1,8 +1,18 @@
 use anyhow::Result;
-use ck_core::Span;
+use ck_core::{ChunkStrategy, Span};
 use serde::{Deserialize, Serialize};
 
+mod chunkers;
+mod facets;
+mod idl;
+mod plugin;
 mod query_chunker;
+mod sql;
+mod structured;
+
+pub use chunkers::{Chunker, ChunkerKey, chunk_file, chunker_for, register_chunker};
+pub use facets::chunk_facets;
+pub use plugin::{PLUGIN_PREFIX, PluginChunker};
 
 /// Import token estimation from ck-embed
 pub use ck_embed::TokenEstimator;
@@ -58,6 +68,9 @@ pub struct StrideInfo {
 pub struct ChunkMetadata {
     pub ancestry: Vec<String>,
     pub breadcrumb: Option<String>,
+    /// Name of the definition this chunk covers (function, class, ...), if any
+    #[serde(default)]
+    pub symbol: Option<String>,
     pub leading_trivia: Vec<String>,
     pub trailing_trivia: Vec<String>,
     pub byte_length: usize,
@@ -80,6 +93,7 @@ impl ChunkMetadata {
         Self {
             ancestry,
             breadcrumb,
+            symbol: None,
             leading_trivia,
             trailing_trivia,
             byte_length: text.len(),
@@ -91,6 +105,7 @@ impl ChunkMetadata {
         Self {
             ancestry: Vec::new(),
             breadcrumb: None,
+            symbol: None,
             leading_trivia: Vec::new(),
             trailing_trivia: Vec::new(),
             byte_length: text.len(),
@@ -116,6 +131,54 @@ pub struct Chunk {
     pub metadata: ChunkMetadata,
 }
 
+impl Chunk {
+    /// The chunk of `source` covering the byte range `bytes`, which must
+    /// start and end on character boundaries.
+    pub fn from_range(source: &str, bytes: std::ops::Range<usize>, chunk_type: ChunkType) -> Self {
+        let text = &source[bytes.clone()];
+        let line_start = source[..bytes.start].matches('\n').count() + 1;
+        let line_end = line_start + text.trim_end_matches('\n').matches('\n').count();
+        Self {
+            span: Span {
+                byte_start: bytes.start,
+                byte_end: bytes.end,
+                line_start,
+                line_end,
+                col_start: 0,
+                col_end: 0,
+            },
+            text: text.to_string(),
+            chunk_type,
+            stride_info: None,
+            metadata: ChunkMetadata::from_text(text),
+        }
+    }
+
+    /// Location header prepended to the chunk text before embedding, e.g.
+    /// `src/calc.rs :: Calculator :: fn add`. Gives the model the file and
+    /// enclosing scopes that the chunk body alone often lacks.
+    pub fn context_line(&self, relative_path: &str) -> String {
+        let mut parts = vec![relative_path.to_string()];
+        if let Some(breadcrumb) = &self.metadata.breadcrumb {
+            parts.push(breadcrumb.clone());
+        }
+        if let Some(symbol) = &self.metadata.symbol {
+            let keyword = match self.chunk_type {
+                ChunkType::Function | ChunkType::Method => "fn",
+                ChunkType::Class => "class",
+                ChunkType::Module => "mod",
+                ChunkType::Text => "",
+            };
+            if keyword.is_empty() {
+                parts.push(symbol.clone());
+            } else {
+                parts.push(format!("{} {}", keyword, symbol));
+            }
+        }
+        parts.join(" :: ")
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ChunkType {
     Text,
@@ -203,6 +266,9 @@ pub struct ChunkConfig {
     pub stride_overlap: usize,
     /// Enable striding for chunks that exceed max_tokens
     pub enable_striding: bool,
+    /// How text is split before striding; only `chars` cuts strides
+    /// mid-line
+    pub strategy: ChunkStrategy,
 }
 
 impl Default for ChunkConfig {
@@ -211,6 +277,7 @@ impl Default for ChunkConfig {
             max_tokens: 8192,     // Default to Nomic model limit
             stride_overlap: 1024, // 12.5% overlap
             enable_striding: true,
+            strategy: ChunkStrategy::default(),
         }
     }
 }
@@ -220,6 +287,16 @@ pub fn chunk_text_with_model(
     text: &str,
     language: Option<ck_core::Language>,
     model_name: Option<&str>,
+) -> Result<Vec<Chunk>> {
+    chunk_text_with_strategy(text, language, model_name, ChunkStrategy::default())
+}
+
+/// Model-specific chunking with a project's `chunk_strategy`
+pub fn chunk_text_with_strategy(
+    text: &str,
+    language: Option<ck_core::Language>,
+    model_name: Option<&str>,
+    strategy: ChunkStrategy,
 ) -> Result<Vec<Chunk>> {
     let (target_tokens, overlap_tokens) = get_model_chunk_config(model_name);
 
@@ -228,6 +305,7 @@ pub fn chunk_text_with_model(
         max_tokens: target_tokens,
         stride_overlap: overlap_tokens,
         enable_striding: true,
+        strategy,
     };
 
     chunk_text_with_config_and_model(text, language, &config, model_name)
@@ -241,6 +319,7 @@ pub fn chunk_text_with_config(
     chunk_text_with_config_and_model(text, language, config, None)
 }
 
+#[tracing::instrument(name = "chunk", level = "debug", skip_all, fields(language = ?language, bytes = text.len()))]
 fn chunk_text_with_config_and_model(
     text: &str,
     language: Option<ck_core::Language>,
@@ -254,32 +333,61 @@ fn chunk_text_with_config_and_model(
         config
     );
 
-    let result = match language.map(ParseableLanguage::try_from) {
-        Some(Ok(lang)) => {
+    let result = match (config.strategy, language.map(ParseableLanguage::try_from)) {
+        // One chunk of the whole text, cut into character windows by striding
+        (ChunkStrategy::Chars, _) => Ok(chunk_whole(text)),
+        (ChunkStrategy::Lines, _) => chunk_generic_with_token_config(text, model_name),
+        (ChunkStrategy::Ast, Some(Ok(lang))) => {
             tracing::debug!("Using {} tree-sitter parser", lang);
             chunk_language_with_model(text, lang, model_name)
         }
-        Some(Err(_)) => {
+        (ChunkStrategy::Ast, Some(Err(_))) => {
             tracing::debug!("Language not supported for parsing, using generic chunking strategy");
             chunk_generic_with_token_config(text, model_name)
         }
-        None => {
+        (ChunkStrategy::Ast, None) => {
             tracing::debug!("Using generic chunking strategy");
             chunk_generic_with_token_config(text, model_name)
         }
     };
 
-    let mut chunks = result?;
+    finish_chunks(result?, text, config)
+}
 
+/// Stride `chunks` of `text` as `config` asks and fill in their columns.
+fn finish_chunks(mut chunks: Vec<Chunk>, text: &str, config: &ChunkConfig) -> Result<Vec<Chunk>> {
     // Apply striding if enabled and necessary
     if config.enable_striding {
         chunks = apply_striding(chunks, config)?;
     }
+    for chunk in &mut chunks {
+        chunk.span.fill_columns(text);
+    }
 
     tracing::debug!("Successfully created {} final chunks", chunks.len());
     Ok(chunks)
 }
 
+fn chunk_whole(text: &str) -> Vec<Chunk> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    vec![Chunk {
+        span: Span {
+            byte_start: 0,
+            byte_end: text.len(),
+            line_start: 1,
+            line_end: text.lines().count().max(1),
+            col_start: 0,
+            col_end: 0,
+        },
+        text: text.to_string(),
+        chunk_type: ChunkType::Text,
+        stride_info: None,
+        metadata: ChunkMetadata::from_text(text),
+    }]
+}
+
 fn chunk_generic(text: &str) -> Result<Vec<Chunk>> {
     chunk_generic_with_token_config(text, None)
 }
@@ -343,6 +451,8 @@ fn chunk_generic_with_token_config(text: &str, model_name: Option<&str>) -> Resu
                 byte_end,
                 line_start: i + 1,
                 line_end: end,
+                col_start: 0,
+                col_end: 0,
             },
             text: chunk_text,
             chunk_type: ChunkType::Text,
@@ -514,6 +624,8 @@ fn fill_gaps(mut chunks: Vec<Chunk>, text: &str) -> Vec<Chunk> {
                     byte_end: gap_end,
                     line_start,
                     line_end,
+                    col_start: 0,
+                    col_end: 0,
                 },
                 chunk_type: ChunkType::Text,
                 metadata: ChunkMetadata::from_text(gap_text),
@@ -548,6 +660,8 @@ fn fill_gaps(mut chunks: Vec<Chunk>, text: &str) -> Vec<Chunk> {
                 byte_end: gap_end,
                 line_start,
                 line_end,
+                col_start: 0,
+                col_end: 0,
             },
             chunk_type: ChunkType::Text,
             metadata: ChunkMetadata::from_text(gap_text),
@@ -658,6 +772,8 @@ fn merge_haskell_functions(chunks: Vec<Chunk>, source: &str) -> Vec<Chunk> {
                 byte_end: group_end,
                 line_start,
                 line_end,
+                col_start: 0,
+                col_end: 0,
             },
             text: merged_text,
             chunk_type: ChunkType::Function,
@@ -839,6 +955,7 @@ fn chunk_type_for_node(
         return None;
     }
 
+    #[allow(clippy::collapsible_match)]
     match language {
         ParseableLanguage::Go
             if matches!(node.kind(), "var_declaration" | "const_declaration")
@@ -937,7 +1054,11 @@ pub(crate) fn build_chunk(
     let ancestry = collect_ancestry(target_node, language, source);
     let leading_trivia = segments_to_strings(&leading_segments, source);
     let trailing_trivia = segments_to_strings(&trailing_segments, source);
-    let metadata = ChunkMetadata::from_context(&text, ancestry, leading_trivia, trailing_trivia);
+    let mut metadata =
+        ChunkMetadata::from_context(&text, ancestry, leading_trivia, trailing_trivia);
+    if chunk_type != ChunkType::Text {
+        metadata.symbol = display_name_for_node(target_node, language, source, chunk_type.clone());
+    }
 
     Some(Chunk {
         span: Span {
@@ -945,6 +1066,8 @@ pub(crate) fn build_chunk(
             byte_end,
             line_start: start_row + 1,
             line_end: end_pos.row + 1,
+            col_start: 0,
+            col_end: 0,
         },
         text,
         chunk_type,
@@ -1346,52 +1469,71 @@ fn stride_large_chunk(chunk: Chunk, config: &ChunkConfig) -> Result<Vec<Chunk>>
 
     // Build char to byte index mapping to handle UTF-8 safely
     let char_byte_indices: Vec<(usize, char)> = text.char_indices().collect();
-    // Note: char_count is already calculated above, just reference it here
+    let snap = config.strategy != ChunkStrategy::Chars;
 
-    let mut strided_chunks = Vec::new();
-    let original_chunk_id = format!("{}:{}", chunk.span.byte_start, chunk.span.byte_end);
+    // Stride bounds in chars. Unless cutting by characters, each bound moves
+    // back to where a line, statement or word begins, so a stride never
+    // starts or ends inside an identifier and the overlap repeats whole units
+    let mut bounds = Vec::new();
     let mut start_char_idx = 0;
-    let mut stride_index = 0;
+    loop {
+        let mut end_char_idx = (start_char_idx + window_chars).min(char_count);
+        if snap && end_char_idx < char_count {
+            end_char_idx = unit_boundary(
+                &char_byte_indices,
+                end_char_idx,
+                start_char_idx + window_chars / 2,
+            );
+        }
+        bounds.push((start_char_idx, end_char_idx));
+        if end_char_idx >= char_count {
+            break;
+        }
+        let mut next = end_char_idx
+            .saturating_sub(overlap_chars)
+            .max(start_char_idx + 1);
+        if snap {
+            next = unit_boundary(
+                &char_byte_indices,
+                next,
+                start_char_idx + (next - start_char_idx) / 2,
+            );
+        }
+        start_char_idx = next;
+    }
 
-    // Calculate total number of strides
-    let total_strides = if char_count <= window_chars {
-        1
-    } else {
-        ((char_count - overlap_chars) as f32 / stride_chars as f32).ceil() as usize
+    let mut strided_chunks = Vec::new();
+    let original_chunk_id = format!("{}:{}", chunk.span.byte_start, chunk.span.byte_end);
+    let total_strides = bounds.len();
+    let byte_at = |char_idx: usize| {
+        char_byte_indices
+            .get(char_idx)
+            .map_or(text.len(), |&(byte, _)| byte)
     };
 
-    while start_char_idx < char_count {
-        let end_char_idx = (start_char_idx + window_chars).min(char_count);
-
-        // Get byte positions from char indices
-        let start_byte_pos = char_byte_indices[start_char_idx].0;
-        let end_byte_pos = if end_char_idx < char_count {
-            char_byte_indices[end_char_idx].0
-        } else {
-            text.len()
-        };
-
+    for (stride_index, &(start_char_idx, end_char_idx)) in bounds.iter().enumerate() {
+        let start_byte_pos = byte_at(start_char_idx);
+        let end_byte_pos = byte_at(end_char_idx);
         let stride_text = &text[start_byte_pos..end_byte_pos];
 
-        // Calculate overlap information
-        let overlap_start = if stride_index > 0 { overlap_chars } else { 0 };
-        let overlap_end = if end_char_idx < char_count {
-            overlap_chars
-        } else {
-            0
+        // Chars shared with the neighboring strides
+        let overlap_start = match stride_index.checked_sub(1) {
+            Some(previous) => bounds[previous].1.saturating_sub(start_char_idx),
+            None => 0,
         };
+        let overlap_end = bounds.get(stride_index + 1).map_or(0, |&(next_start, _)| {
+            end_char_idx.saturating_sub(next_start)
+        });
 
         // Calculate span for this stride
         let byte_offset_start = chunk.span.byte_start + start_byte_pos;
         let byte_offset_end = chunk.span.byte_start + end_byte_pos;
 
-        // Estimate line numbers (approximate)
-        let text_before_start = &text[..start_byte_pos];
-        let line_offset_start = text_before_start.lines().count().saturating_sub(1);
+        let line_offset_start = text[..start_byte_pos].matches('\n').count();
         let stride_lines = stride_text.lines().count();
         let metadata = chunk.metadata.with_updated_text(stride_text);
 
-        let stride_chunk = Chunk {
+        strided_chunks.push(Chunk {
             span: Span {
                 byte_start: byte_offset_start,
                 byte_end: byte_offset_end,
@@ -1400,6 +1542,8 @@ fn stride_large_chunk(chunk: Chunk, config: &ChunkConfig) -> Result<Vec<Chunk>>
                 line_end: chunk.span.line_start
                     + line_offset_start
                     + stride_lines.saturating_sub(1),
+                col_start: 0,
+                col_end: 0,
             },
             text: stride_text.to_string(),
             chunk_type: chunk.chunk_type.clone(),
@@ -1411,17 +1555,7 @@ fn stride_large_chunk(chunk: Chunk, config: &ChunkConfig) -> Result<Vec<Chunk>>
                 overlap_end,
             }),
             metadata,
-        };
-
-        strided_chunks.push(stride_chunk);
-
-        // Move to next stride
-        if end_char_idx >= char_count {
-            break;
-        }
-
-        start_char_idx += stride_chars;
-        stride_index += 1;
+        });
     }
 
     tracing::debug!(
@@ -1433,6 +1567,28 @@ fn stride_large_chunk(chunk: Chunk, config: &ChunkConfig) -> Result<Vec<Chunk>>
     Ok(strided_chunks)
 }
 
+/// The char index in `(floor, idx]` where the largest unit begins: a line,
+/// then a sentence or statement, then a word, then anything that does not
+/// split an identifier. `idx` itself when none does.
+fn unit_boundary(chars: &[(usize, char)], idx: usize, floor: usize) -> usize {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let starts: [&dyn Fn(usize) -> bool; 4] = [
+        &|i| chars[i - 1].1 == '\n',
+        &|i| {
+            i >= 2
+                && chars[i - 1].1.is_whitespace()
+                && matches!(chars[i - 2].1, '.' | ';' | '!' | '?' | '{' | '}')
+        },
+        &|i| chars[i - 1].1.is_whitespace(),
+        &|i| !(is_word(chars[i - 1].1) && is_word(chars[i].1)),
+    ];
+    let idx = idx.min(chars.len().saturating_sub(1));
+    starts
+        .iter()
+        .find_map(|starts_unit| ((floor + 1).max(1)..=idx).rev().find(|&i| starts_unit(i)))
+        .unwrap_or(idx)
+}
+
 // Removed duplicate estimate_tokens function - using the one from ck-embed via TokenEstimator
 
 #[cfg(test)]
@@ -1600,6 +1756,43 @@ pub mod utils {
         assert!(chunk_types.contains(&&ChunkType::Class)); // struct
         assert!(chunk_types.contains(&&ChunkType::Module)); // impl and mod
         assert!(chunk_types.contains(&&ChunkType::Function)); // functions
+
+        let add = chunks
+            .iter()
+            .find(|c| c.text.trim_start().starts_with("pub fn add"))
+            .unwrap();
+        assert_eq!(add.metadata.symbol.as_deref(), Some("add"));
+        assert_eq!(add.metadata.breadcrumb.as_deref(), Some("Calculator"));
+        let calculator = chunks
+            .iter()
+            .find(|c| c.chunk_type == ChunkType::Class)
+            .unwrap();
+        assert_eq!(calculator.metadata.symbol.as_deref(), Some("Calculator"));
+        assert_eq!(
+            add.context_line("src/calc.rs"),
+            "src/calc.rs :: Calculator :: fn add"
+        );
+    }
+
+    #[test]
+    fn test_chunk_columns_locate_the_chunk_text() {
+        let rust_code = "struct Point {\n    x: i32,\n}\n\nimpl Point {\n    fn origin() -> Self {\n        Point { x: 0 }\n    }\n}\n";
+        let chunks = chunk_text(rust_code, Some(ck_core::Language::Rust)).unwrap();
+        let lines: Vec<&str> = rust_code.lines().collect();
+
+        let origin = chunks
+            .iter()
+            .find(|c| c.metadata.symbol.as_deref() == Some("origin"))
+            .unwrap();
+        assert_eq!((origin.span.line_start, origin.span.col_start), (6, 5));
+        assert_eq!((origin.span.line_end, origin.span.col_end), (8, 6));
+
+        for chunk in &chunks {
+            let first = &lines[chunk.span.line_start - 1][chunk.span.col_start - 1..];
+            assert!(first.starts_with(chunk.text.lines().next().unwrap_or("")));
+            let last = &lines[chunk.span.line_end - 1][..chunk.span.col_end - 1];
+            assert!(last.ends_with(chunk.text.lines().last().unwrap_or("")));
+        }
     }
 
     #[test]
@@ -2152,6 +2345,8 @@ public class Calculator
                 byte_end: 0,
                 line_start: 1,
                 line_end: 1,
+                col_start: 0,
+                col_end: 0,
             },
             text: String::new(), // Empty text should not panic
             chunk_type: ChunkType::Text,
@@ -2178,6 +2373,8 @@ public class Calculator
                 byte_end: 5,
                 line_start: 1,
                 line_end: 1,
+                col_start: 0,
+                col_end: 0,
             },
             text: "     ".to_string(), // Whitespace that might return 0 tokens
             chunk_type: ChunkType::Text,
@@ -2205,6 +2402,8 @@ public class Calculator
                 byte_end: long_text.len(),
                 line_start: 1,
                 line_end: 50,
+                col_start: 0,
+                col_end: 0,
             },
             text: long_text,
             chunk_type: ChunkType::Text,
@@ -2800,4 +2999,91 @@ end
             behaviour_chunks.len()
         );
     }
+
+    #[test]
+    fn test_strides_cut_between_lines_and_words() {
+        let config = ChunkConfig {
+            max_tokens: 100,
+            stride_overlap: 20,
+            ..Default::default()
+        };
+        let prose = (1..=400)
+            .map(|i| format!("identifier_{}", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let code = (1..=80)
+            .map(|i| format!("let value_{} = compute_{}(input);", i, i))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        for text in [prose, code] {
+            let strides = stride_large_chunk(chunk_whole(&text).remove(0), &config).unwrap();
+            assert!(strides.len() > 1);
+            let line_start = |byte: usize| byte == 0 || text.as_bytes()[byte - 1] == b'\n';
+            let multiline = text.contains('\n');
+            for stride in &strides {
+                let (start, end) = (stride.span.byte_start, stride.span.byte_end);
+                if multiline {
+                    assert!(
+                        line_start(start),
+                        "stride starts mid-line: {:?}",
+                        stride.text
+                    );
+                    assert!(end == text.len() || line_start(end));
+                } else {
+                    assert!(start == 0 || text.as_bytes()[start - 1] == b' ');
+                    assert!(end == text.len() || text.as_bytes()[end - 1] == b' ');
+                }
+                let info = stride.stride_info.as_ref().unwrap();
+                assert_eq!(info.total_strides, strides.len());
+            }
+            assert!(strides[1].stride_info.as_ref().unwrap().overlap_start > 0);
+        }
+
+        // Character windows keep the raw cut
+        let chars = ChunkConfig {
+            strategy: ChunkStrategy::Chars,
+            ..config
+        };
+        let text = "abcdefghij".repeat(200);
+        let strides = stride_large_chunk(chunk_whole(&text).remove(0), &chars).unwrap();
+        assert!(strides.len() > 1);
+    }
+
+    #[test]
+    fn test_chunk_strategy_overrides_the_parser() {
+        let code = "def first():\n    return 1\n\ndef second():\n    return 2\n";
+        let ast = chunk_text_with_strategy(
+            code,
+            Some(ck_core::Language::Python),
+            None,
+            ChunkStrategy::Ast,
+        )
+        .unwrap();
+        assert!(ast.iter().any(|c| c.chunk_type == ChunkType::Function));
+
+        let lines = chunk_text_with_strategy(
+            code,
+            Some(ck_core::Language::Python),
+            None,
+            ChunkStrategy::Lines,
+        )
+        .unwrap();
+        assert!(lines.iter().all(|c| c.chunk_type == ChunkType::Text));
+
+        let chars = chunk_text_with_strategy(
+            code,
+            Some(ck_core::Language::Python),
+            None,
+            ChunkStrategy::Chars,
+        )
+        .unwrap();
+        assert_eq!(chars.len(), 1);
+        assert_eq!(chars[0].text, code);
+        assert!(
+            chunk_text_with_strategy("", None, None, ChunkStrategy::Chars)
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
@@ -0,0 +1,307 @@
+//! Chunkers run out of process. `"chunkers": {"proto": "plugin:/path/to/bin"}`
+//! in the user model registry (`~/.config/ck/models.json`) has ck start that
+//! program and talk to it over stdio, one JSON object per line, like embedding
+//! plugins. Keys are extensions or
+//! language names. Each request carries a file's language, if ck recognizes
+//! it, and its text; the reply lists chunks by 1-based, inclusive lines:
+//!
+//! ```text
+//! > {"language":null,"text":"syntax = \"proto3\";\nmessage User {..."}
+//! < {"chunks":[{"line_start":2,"line_end":9,"kind":"class","symbol":"User"}]}
+//! ```
+//!
+//! `kind` is `function`, `method`, `class`, `module` or anything else for
+//! plain text, and `symbol` and `breadcrumb` are optional. A plugin that
+//! cannot chunk a file answers `{"error":"message"}`, and ck chunks it as
+//! usual, as it does when no answer comes within 30 seconds. A program is
+//! started once per ck process, on first use, and restarted after it fails.
+
+use anyhow::{Result, anyhow, bail};
+use ck_core::Language;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use crate::{Chunk, ChunkType, Chunker};
+
+/// Provider prefix naming a plugin executable.
+pub const PLUGIN_PREFIX: &str = "plugin:";
+
+/// How long a plugin may take to answer for one file.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize)]
+struct ChunkRequest<'a> {
+    language: Option<String>,
+    text: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ChunkResponse {
+    #[serde(default)]
+    chunks: Vec<PluginChunk>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PluginChunk {
+    line_start: usize,
+    line_end: usize,
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    symbol: Option<String>,
+    #[serde(default)]
+    breadcrumb: Option<String>,
+}
+
+/// A running plugin. Its pipes are served by threads of their own, so a
+/// plugin that stops reading or answering costs a timeout, not a hang.
+struct Process {
+    child: Child,
+    requests: Sender<String>,
+    replies: Receiver<std::io::Result<String>>,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A chunker plugin program, started on first use.
+pub struct PluginChunker {
+    program: String,
+    timeout: Duration,
+    process: Mutex<Option<Process>>,
+}
+
+static PLUGINS: LazyLock<Mutex<HashMap<String, Arc<PluginChunker>>>> =
+    LazyLock::new(Default::default);
+
+/// The one chunker this process runs for `program`.
+pub(crate) fn shared(program: &str) -> Arc<dyn Chunker> {
+    PLUGINS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(program.to_string())
+        .or_insert_with(|| Arc::new(PluginChunker::new(program)))
+        .clone()
+}
+
+impl PluginChunker {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            timeout: REPLY_TIMEOUT,
+            process: Mutex::new(None),
+        }
+    }
+
+    /// Give up on an answer after `timeout` instead of 30 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn start(&self) -> Result<Process> {
+        let mut child = Command::new(&self.program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| anyhow!("cannot start chunker plugin '{}': {}", self.program, e))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+        let (requests, pending) = mpsc::channel::<String>();
+        std::thread::spawn(move || {
+            for line in pending {
+                if stdin
+                    .write_all(line.as_bytes())
+                    .and_then(|_| stdin.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        let (answered, replies) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in stdout.lines() {
+                if answered.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Process {
+            child,
+            requests,
+            replies,
+        })
+    }
+
+    fn request(&self, process: &mut Process, line: String) -> Result<ChunkResponse> {
+        if process.requests.send(line).is_err() {
+            bail!("stopped reading requests");
+        }
+        let reply = match process.replies.recv_timeout(self.timeout) {
+            Ok(reply) => reply?,
+            Err(RecvTimeoutError::Timeout) => {
+                bail!("did not answer within {:?}", self.timeout)
+            }
+            Err(RecvTimeoutError::Disconnected) => bail!("exited without answering"),
+        };
+        serde_json::from_str(&reply).map_err(|e| anyhow!("sent an invalid response: {}", e))
+    }
+}
+
+impl Chunker for PluginChunker {
+    fn chunk(&self, text: &str, language: Option<Language>) -> Result<Vec<Chunk>> {
+        let mut line = serde_json::to_string(&ChunkRequest {
+            language: language.map(|language| language.to_string()),
+            text,
+        })?;
+        line.push('\n');
+
+        let mut process = self.process.lock().unwrap_or_else(|e| e.into_inner());
+        let running = match process.take() {
+            Some(running) => running,
+            None => self.start()?,
+        };
+        let running = process.insert(running);
+        let response = match self.request(running, line) {
+            Ok(response) => response,
+            Err(e) => {
+                // Start afresh for the next file
+                *process = None;
+                bail!("Chunker plugin '{}' {}", self.program, e);
+            }
+        };
+        drop(process);
+
+        if let Some(error) = response.error {
+            bail!("Chunker plugin '{}' failed: {}", self.program, error);
+        }
+        let lines = line_offsets(text);
+        let line_count = lines.len() - 1;
+        response
+            .chunks
+            .into_iter()
+            .map(|chunk| {
+                if chunk.line_start == 0
+                    || chunk.line_start > chunk.line_end
+                    || chunk.line_end > line_count
+                {
+                    bail!(
+                        "Chunker plugin '{}' returned lines {}-{} of a {}-line file",
+                        self.program,
+                        chunk.line_start,
+                        chunk.line_end,
+                        line_count
+                    );
+                }
+                let chunk_type = match chunk.kind.as_deref() {
+                    Some("function") => ChunkType::Function,
+                    Some("method") => ChunkType::Method,
+                    Some("class") => ChunkType::Class,
+                    Some("module") => ChunkType::Module,
+                    _ => ChunkType::Text,
+                };
+                let bytes = lines[chunk.line_start - 1]..lines[chunk.line_end];
+                let mut built = Chunk::from_range(text, bytes, chunk_type);
+                built.metadata.symbol = chunk.symbol;
+                built.metadata.breadcrumb = chunk.breadcrumb;
+                Ok(built)
+            })
+            .collect()
+    }
+}
+
+/// Byte offset of the start of each line of `text`, then its length.
+pub(crate) fn line_offsets(text: &str) -> Vec<usize> {
+    let mut offsets = vec![0];
+    offsets.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+    if offsets.last() != Some(&text.len()) {
+        offsets.push(text.len());
+    }
+    offsets
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plugin_chunks_by_lines() {
+        let dir = TempDir::new().unwrap();
+        let program = dir.path().join("chunker.sh");
+        std::fs::write(
+            &program,
+            "#!/bin/sh\nwhile read -r line; do\n  echo '{\"chunks\":[{\"line_start\":2,\"line_end\":4,\"kind\":\"class\",\"symbol\":\"User\"}]}'\ndone\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let text = "syntax = \"proto3\";\nmessage User {\n  string name = 1;\n}\n";
+        let configured = BTreeMap::from([(
+            "proto".to_string(),
+            format!("{PLUGIN_PREFIX}{}", program.display()),
+        )]);
+        let chunks = crate::chunk_file(
+            Path::new("api/user.proto"),
+            text,
+            None,
+            None,
+            Default::default(),
+            &configured,
+        )
+        .unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text, "message User {\n  string name = 1;\n}\n");
+        assert_eq!((chunks[0].span.line_start, chunks[0].span.line_end), (2, 4));
+        assert_eq!(chunks[0].chunk_type, ChunkType::Class);
+        assert_eq!(chunks[0].metadata.symbol.as_deref(), Some("User"));
+
+        // A plugin that cannot start leaves the file to the usual chunking
+        let configured = BTreeMap::from([(
+            "proto".to_string(),
+            format!("{PLUGIN_PREFIX}/nonexistent/chunker"),
+        )]);
+        let chunks = crate::chunk_file(
+            Path::new("api/user.proto"),
+            text,
+            None,
+            None,
+            Default::default(),
+            &configured,
+        )
+        .unwrap();
+        assert_eq!(chunks[0].span.line_start, 1);
+    }
+
+    #[test]
+    fn test_stuck_plugin_times_out() {
+        let dir = TempDir::new().unwrap();
+        let program = dir.path().join("stuck.sh");
+        std::fs::write(&program, "#!/bin/sh\nsleep 60\n").unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let chunker = PluginChunker::new(&program.display().to_string())
+            .with_timeout(Duration::from_millis(200));
+        let started = std::time::Instant::now();
+        let error = chunker.chunk("message User {}\n", None).unwrap_err();
+        assert!(error.to_string().contains("did not answer"), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
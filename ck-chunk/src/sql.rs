@@ -0,0 +1,445 @@
+//! Chunking for SQL scripts and migrations, by statement. Each DDL
+//! statement is a chunk of its own, named for the object it creates: its
+//! symbol is the table, view, index or function name, and an index,
+//! trigger or policy has the table it is on as its breadcrumb. Statements
+//! that change an object (`ALTER`, `DROP`, `INSERT`, `UPDATE`, ...) carry
+//! its name as their breadcrumb, and neighbours on the same object share a
+//! chunk. Short statements on no object, like `BEGIN;` or `SET ...;`, join
+//! the statement after them.
+//!
+//! Statements end at `;` outside strings, comments and `$$` bodies, at a
+//! `GO` line, or at the delimiter a MySQL `DELIMITER` line sets. The body
+//! of a trigger or routine written `BEGIN ... END` is part of its statement.
+
+use anyhow::{Result, bail};
+use ck_core::Language;
+
+use crate::structured::{SPLIT_TOKENS, cover};
+use crate::{Chunk, ChunkType, Chunker, estimate_tokens};
+
+/// Statements on no object shorter than this join their neighbours.
+const STANDALONE_TOKENS: usize = 50;
+
+pub(crate) struct SqlChunker;
+
+/// What a statement defines or touches.
+#[derive(Debug, Clone, PartialEq)]
+struct Object {
+    chunk_type: ChunkType,
+    /// Name of the object a `CREATE` defines
+    symbol: Option<String>,
+    /// Object the statement acts on, or the table a definition is on
+    breadcrumb: Option<String>,
+}
+
+impl Chunker for SqlChunker {
+    fn chunk(&self, text: &str, _language: Option<Language>) -> Result<Vec<Chunk>> {
+        let lines: Vec<&str> = text.lines().collect();
+        let offsets = crate::plugin::line_offsets(text);
+        let line = |offset: usize| offsets.partition_point(|&start| start <= offset) - 1;
+
+        let mut spans: Vec<(usize, usize, Object)> = Vec::new();
+        let mut pending_tokens = 0;
+        for (start, end) in statements(text) {
+            let statement = &text[start..end];
+            let tokens = estimate_tokens(statement);
+            let object = describe(&words(statement));
+            let (first, last) = (line(start), line(end.saturating_sub(1).max(start)));
+            if object.symbol.is_none() && object.breadcrumb.is_none() && tokens < STANDALONE_TOKENS
+            {
+                continue;
+            }
+            if let Some(previous) = spans.last_mut()
+                && object.symbol.is_none()
+                && previous.2.symbol.is_none()
+                && previous.2.breadcrumb == object.breadcrumb
+                && pending_tokens + tokens <= SPLIT_TOKENS
+            {
+                previous.1 = last;
+                pending_tokens += tokens;
+                continue;
+            }
+            spans.push((first, last, object));
+            pending_tokens = tokens;
+        }
+        if spans.is_empty() {
+            bail!("no statements found");
+        }
+
+        // Statements sharing a line keep the first one's name
+        Ok(cover(text, &lines, spans, |_, _| {})
+            .into_iter()
+            .map(|(mut chunk, object)| {
+                chunk.chunk_type = object.chunk_type;
+                chunk.metadata.symbol = object.symbol;
+                chunk.metadata.breadcrumb = object.breadcrumb;
+                chunk
+            })
+            .collect())
+    }
+}
+
+/// Byte ranges of the statements in `text`, from their first token to
+/// their delimiter.
+fn statements(text: &str) -> Vec<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let mut statements = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut delimiter = ";".to_string();
+    // Open BEGIN/CASE blocks in a trigger or routine body
+    let mut depth = 0usize;
+    let mut routine: Option<bool> = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        if i == 0 || bytes[i - 1] == b'\n' {
+            let line_end = text[i..].find('\n').map_or(text.len(), |n| i + n);
+            let line = text[i..line_end].trim();
+            if line.eq_ignore_ascii_case("go") {
+                if let Some(first) = start.take() {
+                    statements.push((first, line_end));
+                }
+                (depth, routine) = (0, None);
+                i = line_end;
+                continue;
+            }
+            if start.is_none()
+                && line
+                    .get(..10)
+                    .is_some_and(|head| head.eq_ignore_ascii_case("delimiter "))
+            {
+                delimiter = line[10..].trim().to_string();
+                i = line_end;
+                continue;
+            }
+        }
+
+        let b = bytes[i];
+        if b.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if bytes[i..].starts_with(b"--") {
+            i = text[i..].find('\n').map_or(text.len(), |n| i + n);
+            continue;
+        }
+        if bytes[i..].starts_with(b"/*") {
+            i = text[i + 2..].find("*/").map_or(text.len(), |n| i + n + 4);
+            continue;
+        }
+        if depth == 0 && bytes[i..].starts_with(delimiter.as_bytes()) {
+            if let Some(first) = start.take() {
+                statements.push((first, i + delimiter.len()));
+            }
+            routine = None;
+            i += delimiter.len();
+            continue;
+        }
+
+        let first = *start.get_or_insert(i);
+        match b {
+            b'\'' | b'"' | b'`' => i = skip_quoted(bytes, i, b),
+            b'[' => i = skip_quoted(bytes, i, b']'),
+            b'$' if delimiter == ";" => match dollar_tag(&text[i..]) {
+                Some(tag) => {
+                    i = text[i + tag.len()..]
+                        .find(tag)
+                        .map_or(text.len(), |n| i + tag.len() + n + tag.len());
+                }
+                None => i += 1,
+            },
+            b if b.is_ascii_alphanumeric() || b == b'_' => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|b| !b.is_ascii_alphanumeric() && *b != b'_')
+                    .map_or(bytes.len(), |n| i + n);
+                let word = &text[i..end];
+                if delimiter == ";"
+                    && ["BEGIN", "CASE", "END"]
+                        .iter()
+                        .any(|w| word.eq_ignore_ascii_case(w))
+                {
+                    let in_routine =
+                        *routine.get_or_insert_with(|| is_routine(&words(&text[first..i])));
+                    if in_routine {
+                        if !word.eq_ignore_ascii_case("END") {
+                            depth += 1;
+                        } else if !words(&text[end..]).first().is_some_and(|next| {
+                            ["IF", "LOOP", "WHILE", "REPEAT", "FOR"]
+                                .iter()
+                                .any(|w| next.eq_ignore_ascii_case(w))
+                        }) {
+                            depth = depth.saturating_sub(1);
+                        }
+                    }
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    if let Some(first) = start {
+        statements.push((first, text.len()));
+    }
+    statements
+}
+
+/// Index just past the quoted text starting at `start`, where a doubled
+/// `close` is an escaped one.
+fn skip_quoted(bytes: &[u8], start: usize, close: u8) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        if bytes[i] == close {
+            if bytes.get(i + 1) == Some(&close) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+/// The `$tag$` opening a dollar-quoted string at the start of `text`.
+fn dollar_tag(text: &str) -> Option<&str> {
+    let close = text[1..].find('$')? + 1;
+    let tag = &text[1..close];
+    (tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !tag.starts_with(|c: char| c.is_ascii_digit()))
+    .then(|| &text[..=close])
+}
+
+/// Whether a statement starting with `words` creates a trigger or routine.
+fn is_routine(words: &[String]) -> bool {
+    words
+        .first()
+        .is_some_and(|word| word.eq_ignore_ascii_case("CREATE"))
+        && words.iter().take(8).any(|word| {
+            ["FUNCTION", "PROCEDURE", "TRIGGER"]
+                .iter()
+                .any(|w| word.eq_ignore_ascii_case(w))
+        })
+}
+
+/// The leading words and names of a statement, without comments and
+/// quotes: `CREATE INDEX "idx" ON public.orders (email)` reads as
+/// `CREATE`, `INDEX`, `idx`, `ON`, `public.orders`, `(`.
+fn words(statement: &str) -> Vec<String> {
+    let bytes = statement.as_bytes();
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() && words.len() < 24 {
+        let b = bytes[i];
+        if b.is_ascii_whitespace() {
+            i += 1;
+        } else if bytes[i..].starts_with(b"--") {
+            i = statement[i..].find('\n').map_or(bytes.len(), |n| i + n);
+        } else if bytes[i..].starts_with(b"/*") {
+            i = statement[i + 2..]
+                .find("*/")
+                .map_or(bytes.len(), |n| i + n + 4);
+        } else if is_name_byte(b) || matches!(b, b'"' | b'`' | b'[') {
+            let mut word = String::new();
+            while i < bytes.len() {
+                match bytes[i] {
+                    quote @ (b'"' | b'`' | b'[') => {
+                        let close = if quote == b'[' { b']' } else { quote };
+                        let end = skip_quoted(bytes, i, close);
+                        word.push_str(
+                            statement
+                                .get(i + 1..end.saturating_sub(1).max(i + 1))
+                                .unwrap_or_default(),
+                        );
+                        i = end;
+                    }
+                    b if is_name_byte(b) => {
+                        word.push(b as char);
+                        i += 1;
+                    }
+                    _ => break,
+                }
+            }
+            words.push(word);
+        } else {
+            words.push((b as char).to_string());
+            i += statement[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    words
+}
+
+fn is_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'$')
+}
+
+/// The object a statement starting with `words` defines or acts on.
+fn describe(words: &[String]) -> Object {
+    let is = |i: usize, keyword: &str| {
+        words
+            .get(i)
+            .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+    };
+    let name_at = |mut i: usize| {
+        for skipped in ["IF", "NOT", "EXISTS", "ONLY", "CONCURRENTLY"] {
+            if is(i, skipped) {
+                i += 1;
+            }
+        }
+        words
+            .get(i)
+            .filter(|word| word.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+            .cloned()
+    };
+    let after = |keyword: &str| {
+        words
+            .iter()
+            .position(|word| word.eq_ignore_ascii_case(keyword))
+            .and_then(|i| name_at(i + 1))
+    };
+    let on = |object: Option<String>| Object {
+        chunk_type: ChunkType::Text,
+        symbol: None,
+        breadcrumb: object,
+    };
+
+    let Some(first) = words.first().map(|word| word.to_ascii_uppercase()) else {
+        return on(None);
+    };
+    match first.as_str() {
+        "CREATE" => {
+            let Some(kind) = words.iter().take(10).position(|word| {
+                OBJECT_KINDS
+                    .iter()
+                    .any(|(kind, _)| word.eq_ignore_ascii_case(kind))
+            }) else {
+                return on(None);
+            };
+            let keyword = words[kind].to_ascii_uppercase();
+            let chunk_type = OBJECT_KINDS
+                .iter()
+                .find(|(kind, _)| *kind == keyword)
+                .map_or(ChunkType::Text, |(_, chunk_type)| chunk_type.clone());
+            let symbol = name_at(kind + 1).filter(|name| !name.eq_ignore_ascii_case("ON"));
+            let breadcrumb = match keyword.as_str() {
+                "INDEX" | "TRIGGER" | "POLICY" | "RULE" => after("ON"),
+                _ => None,
+            };
+            Object {
+                chunk_type,
+                symbol,
+                breadcrumb,
+            }
+        }
+        "ALTER" | "DROP" | "TRUNCATE" | "COMMENT" => {
+            let start = if is(1, "ON") { 2 } else { 1 };
+            let named = if first == "TRUNCATE" && !is(start, "TABLE") {
+                start
+            } else {
+                start + 1
+            };
+            on(name_at(named))
+        }
+        "INSERT" | "REPLACE" => on(after("INTO")),
+        "UPDATE" | "COPY" => on(name_at(1)),
+        "DELETE" => on(after("FROM")),
+        _ => on(None),
+    }
+}
+
+/// Objects a `CREATE` statement is read as defining, and their chunk types.
+const OBJECT_KINDS: &[(&str, ChunkType)] = &[
+    ("TABLE", ChunkType::Class),
+    ("VIEW", ChunkType::Class),
+    ("TYPE", ChunkType::Class),
+    ("DOMAIN", ChunkType::Class),
+    ("FUNCTION", ChunkType::Function),
+    ("PROCEDURE", ChunkType::Function),
+    ("TRIGGER", ChunkType::Function),
+    ("AGGREGATE", ChunkType::Function),
+    ("SCHEMA", ChunkType::Module),
+    ("DATABASE", ChunkType::Module),
+    ("INDEX", ChunkType::Text),
+    ("SEQUENCE", ChunkType::Text),
+    ("EXTENSION", ChunkType::Text),
+    ("POLICY", ChunkType::Text),
+    ("RULE", ChunkType::Text),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    #[test]
+    fn test_migration_chunks_by_statement() {
+        let migration = "\
+BEGIN;
+
+-- Customer orders
+CREATE TABLE IF NOT EXISTS public.orders (
+    id bigserial PRIMARY KEY,
+    email text NOT NULL, -- contact; not unique
+    note text DEFAULT 'a;b'
+);
+
+CREATE UNIQUE INDEX CONCURRENTLY \"idx_orders_email\" ON orders (email);
+ALTER TABLE orders ADD COLUMN total numeric;
+ALTER TABLE orders ALTER COLUMN total SET DEFAULT 0;
+
+CREATE FUNCTION touch() RETURNS trigger AS $$
+BEGIN
+  NEW.updated_at := now();
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER orders_touch AFTER UPDATE ON orders
+BEGIN
+  UPDATE orders SET total = CASE WHEN total IS NULL THEN 0 ELSE total END;
+END;
+
+COMMIT;
+";
+        let chunks = crate::chunk_file(
+            Path::new("migrations/001_orders.sql"),
+            migration,
+            None,
+            None,
+            Default::default(),
+            &BTreeMap::new(),
+        )
+        .unwrap();
+        let names: Vec<_> = chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.metadata.symbol.as_deref(),
+                    chunk.metadata.breadcrumb.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (Some("public.orders"), None),
+                (Some("idx_orders_email"), Some("orders")),
+                (None, Some("orders")),
+                (Some("touch"), None),
+                (Some("orders_touch"), Some("orders")),
+            ]
+        );
+        assert!(chunks[0].text.starts_with("BEGIN;\n\n-- Customer orders\n"));
+        assert_eq!(chunks[0].chunk_type, ChunkType::Class);
+        assert_eq!(chunks[2].span.line_start, 11);
+        assert_eq!(chunks[2].span.line_end, 12);
+        assert_eq!(chunks[3].chunk_type, ChunkType::Function);
+        assert!(chunks[4].text.ends_with("END;\n\nCOMMIT;\n"));
+
+        // T-SQL batches and MySQL delimiters
+        let statements = statements(
+            "CREATE TABLE a (x int)\nGO\nDELIMITER //\nCREATE PROCEDURE p() BEGIN SELECT 1; END //\nDELIMITER ;\nDROP TABLE a;\n",
+        );
+        assert_eq!(statements.len(), 3);
+    }
+}
@@ -0,0 +1,678 @@
+//! Chunking for configuration formats ck has no grammar for: HCL
+//! (Terraform), YAML and JSON. A file splits at its top-level blocks and
+//! keys, and an entry too large for one chunk splits again at its own keys
+//! or elements. Each chunk's breadcrumb is the key path it covers, such as
+//! `spec.template.spec.containers[0]` or `resource.aws_instance.web`, so the
+//! embedding knows which part of the document it holds. Small neighbouring
+//! entries share a chunk under their parent's path. Lines between entries,
+//! such as comments or a parent's `spec:` line, join the entry after them,
+//! and closing brackets the entry before, so the chunks cover the file.
+
+use anyhow::{Result, bail};
+use ck_core::Language;
+use std::path::Path;
+
+use crate::{Chunk, ChunkType, Chunker, estimate_tokens};
+
+/// Entries larger than this split into their children. The smallest model
+/// chunk size, so unrelated blocks share a chunk only when they are small.
+pub(crate) const SPLIT_TOKENS: usize = 400;
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Hcl,
+    Yaml,
+    Json,
+}
+
+pub(crate) struct StructuredChunker(Format);
+
+/// The built-in chunker for `path`'s extension: one of the formats above,
+/// SQL, or an interface definition language.
+pub(crate) fn for_path(path: &Path) -> Option<Box<dyn Chunker>> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    let format = match extension.as_str() {
+        "tf" | "tfvars" | "hcl" => Format::Hcl,
+        "yaml" | "yml" => Format::Yaml,
+        "json" => Format::Json,
+        "sql" => return Some(Box::new(crate::sql::SqlChunker)),
+        "proto" | "thrift" | "graphql" | "gql" => return Some(Box::new(crate::idl::IdlChunker)),
+        _ => return None,
+    };
+    Some(Box::new(StructuredChunker(format)))
+}
+
+/// A key, block or element and the lines it spans (0-based, inclusive).
+pub(crate) struct Node {
+    pub(crate) key: String,
+    pub(crate) first: usize,
+    pub(crate) last: usize,
+    pub(crate) children: Vec<Node>,
+}
+
+impl Chunker for StructuredChunker {
+    fn chunk(&self, text: &str, _language: Option<Language>) -> Result<Vec<Chunk>> {
+        let lines: Vec<&str> = text.lines().collect();
+        let sections = match self.0 {
+            Format::Hcl => vec![hcl(&lines)?],
+            Format::Yaml => yaml(&lines),
+            Format::Json => vec![json(text)?],
+        };
+        let mut spans = Vec::new();
+        for nodes in &sections {
+            split(nodes, "", &lines, &mut spans);
+        }
+        if spans.is_empty() {
+            bail!("no keys or blocks found");
+        }
+
+        Ok(cover(text, &lines, spans, |previous, path| {
+            *previous = common_path(previous, &path)
+        })
+        .into_iter()
+        .map(|(mut chunk, path)| {
+            chunk.metadata.breadcrumb = (!path.is_empty()).then_some(path);
+            chunk
+        })
+        .collect())
+    }
+}
+
+/// Chunks covering all of `text` from `spans` of its `lines` (0-based,
+/// inclusive, in order), each with its span's value. Lines between spans
+/// join the span after them, except closing brackets, which join the one
+/// before. A span whose lines the one before already covers, as in
+/// minified JSON, is folded into it with `merge`.
+pub(crate) fn cover<T>(
+    text: &str,
+    lines: &[&str],
+    spans: Vec<(usize, usize, T)>,
+    merge: impl Fn(&mut T, T),
+) -> Vec<(Chunk, T)> {
+    let mut ranges: Vec<(usize, usize, T)> = Vec::with_capacity(spans.len());
+    let mut next = 0;
+    for (first, last, value) in spans {
+        if last < next {
+            if let Some(previous) = ranges.last_mut() {
+                merge(&mut previous.2, value);
+            }
+            continue;
+        }
+        if let Some(previous) = ranges.last_mut() {
+            while next < first && is_closing(lines[next]) {
+                previous.1 = next;
+                next += 1;
+            }
+        }
+        ranges.push((next, last, value));
+        next = last + 1;
+    }
+    if let Some(last) = ranges.last_mut() {
+        last.1 = lines.len() - 1;
+    }
+
+    let offsets = crate::plugin::line_offsets(text);
+    ranges
+        .into_iter()
+        .map(|(first, last, value)| {
+            let chunk = Chunk::from_range(text, offsets[first]..offsets[last + 1], ChunkType::Text);
+            (chunk, value)
+        })
+        .collect()
+}
+
+/// Chunk spans for `nodes`, the children of the entry at `parent`.
+fn split(nodes: &[Node], parent: &str, lines: &[&str], spans: &mut Vec<(usize, usize, String)>) {
+    struct Pending {
+        first: usize,
+        last: usize,
+        tokens: usize,
+        path: String,
+        count: usize,
+    }
+    fn flush(pending: &mut Option<Pending>, parent: &str, spans: &mut Vec<(usize, usize, String)>) {
+        if let Some(group) = pending.take() {
+            let path = if group.count == 1 {
+                group.path
+            } else {
+                parent.to_string()
+            };
+            spans.push((group.first, group.last, path));
+        }
+    }
+
+    let mut pending: Option<Pending> = None;
+    for node in nodes {
+        let tokens = estimate_tokens(&lines[node.first..=node.last].join("\n"));
+        if tokens > SPLIT_TOKENS && !node.children.is_empty() {
+            flush(&mut pending, parent, spans);
+            split(&node.children, &join(parent, &node.key), lines, spans);
+            continue;
+        }
+        if pending
+            .as_ref()
+            .is_some_and(|group| group.tokens + tokens > SPLIT_TOKENS)
+        {
+            flush(&mut pending, parent, spans);
+        }
+        match &mut pending {
+            Some(group) => {
+                group.last = node.last;
+                group.tokens += tokens;
+                group.count += 1;
+            }
+            None => {
+                pending = Some(Pending {
+                    first: node.first,
+                    last: node.last,
+                    tokens,
+                    path: join(parent, &node.key),
+                    count: 1,
+                })
+            }
+        }
+    }
+    flush(&mut pending, parent, spans);
+}
+
+fn join(parent: &str, key: &str) -> String {
+    if key.is_empty() {
+        parent.to_string()
+    } else if parent.is_empty() || key.starts_with('[') {
+        format!("{parent}{key}")
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+/// The longest path both `a` and `b` lie under.
+fn common_path(a: &str, b: &str) -> String {
+    a.split('.')
+        .zip(b.split('.'))
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// A line holding nothing but closing brackets, like `}` or `],`.
+fn is_closing(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && line.chars().all(|c| matches!(c, '}' | ']' | ')' | ','))
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Entries of each YAML document.
+fn yaml(lines: &[&str]) -> Vec<Vec<Node>> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    for end in 0..=lines.len() {
+        if end < lines.len() && !lines[end].starts_with("---") && !lines[end].starts_with("...") {
+            continue;
+        }
+        if let Some(first) = (start..end).find(|&i| is_yaml_content(lines[i])) {
+            sections.push(yaml_entries(lines, first, end, indent(lines[first])));
+        }
+        start = end + 1;
+    }
+    sections
+}
+
+fn is_yaml_content(line: &str) -> bool {
+    let line = line.trim_start();
+    !line.is_empty() && !line.starts_with('#')
+}
+
+/// `line` from column `col`, reading the dashes of sequence entries before
+/// it as indentation.
+fn yaml_at(line: &str, col: usize) -> Option<&str> {
+    let head = line.get(..col)?;
+    let rest = &line[col..];
+    (head.chars().all(|c| c == ' ' || c == '-') && !rest.is_empty() && !rest.starts_with(' '))
+        .then_some(rest)
+}
+
+/// A mapping entry's key and the value on its line.
+fn yaml_key(text: &str) -> Option<(String, &str)> {
+    let (key, rest) = match text.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let close = text[1..].find(quote)? + 1;
+            (&text[1..close], text[close + 1..].strip_prefix(':')?)
+        }
+        '{' | '[' | '-' | '#' => return None,
+        _ => {
+            let colon = text
+                .match_indices(':')
+                .map(|(i, _)| i)
+                .find(|&i| text[i + 1..].is_empty() || text[i + 1..].starts_with(' '))?;
+            (&text[..colon], &text[colon + 1..])
+        }
+    };
+    Some((key.trim().to_string(), rest.trim()))
+}
+
+/// Whether a value of `value` on the key's line leaves its entries to the
+/// lines below, rather than being a scalar.
+fn opens_block(value: &str) -> bool {
+    value.is_empty()
+        || value.starts_with('#')
+        || (value.starts_with(['&', '!']) && !value.contains(' '))
+}
+
+/// Entries at column `col` in lines `from..to`.
+fn yaml_entries(lines: &[&str], from: usize, to: usize, col: usize) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut index = 0;
+    let mut i = from;
+    while i < to {
+        let Some(text) = yaml_at(lines[i], col).filter(|_| is_yaml_content(lines[i])) else {
+            i += 1;
+            continue;
+        };
+        let is_item = text == "-" || text.starts_with("- ");
+        let (key, value) = if is_item {
+            index += 1;
+            (format!("[{}]", index - 1), text[1..].trim_start())
+        } else {
+            yaml_key(text).unwrap_or((String::new(), text))
+        };
+        let holds_sequence = !is_item && opens_block(value);
+
+        let mut last = i;
+        let mut end = i + 1;
+        while end < to {
+            let line = lines[end];
+            if is_yaml_content(line) {
+                let line_indent = indent(line);
+                let inside = line_indent > col
+                    || (line_indent == col && holds_sequence && line[col..].starts_with('-'));
+                if !inside {
+                    break;
+                }
+                last = end;
+            }
+            end += 1;
+        }
+
+        let below = (i + 1..=last).find(|&j| is_yaml_content(lines[j]));
+        let children = if is_item && !value.is_empty() {
+            let inner = col + (text.len() - value.len());
+            if yaml_key(value).is_some() || value.starts_with("- ") {
+                yaml_entries(lines, i, last + 1, inner)
+            } else {
+                Vec::new()
+            }
+        } else if is_item || holds_sequence {
+            below
+                .map(|j| yaml_entries(lines, j, last + 1, indent(lines[j])))
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        nodes.push(Node {
+            key,
+            first: i,
+            last,
+            children,
+        });
+        i = end;
+    }
+    nodes
+}
+
+/// Entries of a JSON document's top-level object or array.
+fn json(text: &str) -> Result<Vec<Node>> {
+    serde_json::from_str::<serde_json::Value>(text)?;
+    let mut scanner = JsonScanner {
+        text: text.as_bytes(),
+        pos: 0,
+        lines: crate::plugin::line_offsets(text),
+    };
+    Ok(scanner.value())
+}
+
+struct JsonScanner<'a> {
+    text: &'a [u8],
+    pos: usize,
+    /// Byte offset of each line's start
+    lines: Vec<usize>,
+}
+
+impl JsonScanner<'_> {
+    fn line(&self, offset: usize) -> usize {
+        self.lines.partition_point(|&start| start <= offset) - 1
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .text
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip a value, returning its members or elements.
+    fn value(&mut self) -> Vec<Node> {
+        self.skip_whitespace();
+        match self.text.get(self.pos) {
+            Some(b'{') => self.container(b'}', true),
+            Some(b'[') => self.container(b']', false),
+            Some(b'"') => {
+                self.string();
+                Vec::new()
+            }
+            _ => {
+                while self
+                    .text
+                    .get(self.pos)
+                    .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+                {
+                    self.pos += 1;
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    fn string(&mut self) -> String {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(&b) = self.text.get(self.pos) {
+            self.pos += 1;
+            match b {
+                b'\\' => self.pos += 1,
+                b'"' => break,
+                _ => {}
+            }
+        }
+        serde_json::from_slice(&self.text[start..self.pos.min(self.text.len())]).unwrap_or_default()
+    }
+
+    fn container(&mut self, close: u8, is_object: bool) -> Vec<Node> {
+        self.pos += 1;
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.text.get(self.pos) {
+                Some(&b) if b == close => {
+                    self.pos += 1;
+                    return nodes;
+                }
+                Some(b',') => {
+                    self.pos += 1;
+                    continue;
+                }
+                None => return nodes,
+                _ => {}
+            }
+            let first = self.line(self.pos);
+            let key = if is_object {
+                let key = self.string();
+                self.skip_whitespace();
+                self.pos += 1; // ':'
+                key
+            } else {
+                format!("[{}]", nodes.len())
+            };
+            let children = self.value();
+            nodes.push(Node {
+                key,
+                first,
+                last: self.line(self.pos.saturating_sub(1)),
+                children,
+            });
+        }
+    }
+}
+
+/// Top-level blocks and attributes of an HCL file.
+fn hcl(lines: &[&str]) -> Result<Vec<Node>> {
+    Ok(BraceLayout::new(lines)?.entries(0, lines.len(), 0))
+}
+
+/// Lines of a file whose blocks nest in brackets, like HCL or protobuf.
+pub(crate) struct BraceLayout<'a> {
+    lines: &'a [&'a str],
+    depths: Vec<i32>,
+    starts: Vec<bool>,
+}
+
+impl<'a> BraceLayout<'a> {
+    /// Read `lines`, which fails when their brackets do not balance.
+    pub(crate) fn new(lines: &'a [&'a str]) -> Result<Self> {
+        // Bracket depth at the start of each line, and whether the line starts
+        // outside a string, heredoc or comment
+        let mut depths = Vec::with_capacity(lines.len());
+        let mut starts = Vec::with_capacity(lines.len());
+        let mut depth: i32 = 0;
+        let mut in_comment = false;
+        let mut heredoc: Option<&str> = None;
+        for line in lines {
+            depths.push(depth);
+            if let Some(tag) = heredoc {
+                starts.push(false);
+                if line.trim() == tag {
+                    heredoc = None;
+                }
+                continue;
+            }
+            starts.push(!in_comment);
+            let bytes = line.as_bytes();
+            let mut in_string = false;
+            let mut j = 0;
+            while j < bytes.len() {
+                let b = bytes[j];
+                if in_comment {
+                    if bytes[j..].starts_with(b"*/") {
+                        in_comment = false;
+                        j += 1;
+                    }
+                } else if in_string {
+                    match b {
+                        b'\\' => j += 1,
+                        b'"' => in_string = false,
+                        _ => {}
+                    }
+                } else {
+                    match b {
+                        b'"' => in_string = true,
+                        b'#' => break,
+                        b'/' if bytes.get(j + 1) == Some(&b'/') => break,
+                        b'/' if bytes.get(j + 1) == Some(&b'*') => {
+                            in_comment = true;
+                            j += 1;
+                        }
+                        b'{' | b'[' | b'(' => depth += 1,
+                        b'}' | b']' | b')' => depth -= 1,
+                        b'<' if line[j..].starts_with("<<") => {
+                            let tag = line[j + 2..].trim_start_matches('-').trim();
+                            if !tag.is_empty()
+                                && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                            {
+                                heredoc = Some(tag);
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                j += 1;
+            }
+        }
+        if depth != 0 || heredoc.is_some() || in_comment {
+            bail!("unbalanced brackets");
+        }
+        Ok(Self {
+            lines,
+            depths,
+            starts,
+        })
+    }
+
+    fn is_content(&self, i: usize) -> bool {
+        let line = self.lines[i].trim_start();
+        self.starts[i]
+            && !line.is_empty()
+            && !line.starts_with('#')
+            && !line.starts_with("//")
+            && !line.starts_with("/*")
+    }
+
+    fn starts_entry(&self, i: usize, depth: i32) -> bool {
+        self.depths[i] == depth && self.is_content(i) && !is_closing(self.lines[i])
+    }
+
+    /// Entries at bracket depth `depth` in lines `from..to`.
+    pub(crate) fn entries(&self, from: usize, to: usize, depth: i32) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut i = from;
+        while i < to {
+            if !self.starts_entry(i, depth) {
+                i += 1;
+                continue;
+            }
+            let mut last = i;
+            let mut end = i + 1;
+            while end < to && !self.starts_entry(end, depth) {
+                if self.depths[end] > depth && (self.is_content(end) || !self.starts[end]) {
+                    last = end;
+                }
+                end += 1;
+            }
+            let children = if last > i {
+                self.entries(i + 1, last + 1, depth + 1)
+            } else {
+                Vec::new()
+            };
+            nodes.push(Node {
+                key: hcl_key(self.lines[i]),
+                first: i,
+                last,
+                children,
+            });
+            i = end;
+        }
+        nodes
+    }
+}
+
+/// `name` for an attribute `name = ...`, `type.label` for a block
+/// `type "label" {`.
+fn hcl_key(line: &str) -> String {
+    let line = line.trim();
+    let brace = line.find('{').unwrap_or(line.len());
+    match line.find('=') {
+        Some(equals) if equals < brace => line[..equals].trim().trim_matches('"').to_string(),
+        _ => line[..brace]
+            .split_whitespace()
+            .map(|word| word.trim_matches('"'))
+            .collect::<Vec<_>>()
+            .join("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::fmt::Write;
+
+    fn chunk(path: &str, text: &str) -> Vec<Chunk> {
+        crate::chunk_file(
+            Path::new(path),
+            text,
+            None,
+            None,
+            Default::default(),
+            &BTreeMap::new(),
+        )
+        .unwrap()
+    }
+
+    fn breadcrumbs(chunks: &[Chunk]) -> Vec<&str> {
+        chunks
+            .iter()
+            .map(|chunk| chunk.metadata.breadcrumb.as_deref().unwrap_or(""))
+            .collect()
+    }
+
+    fn assert_covers(chunks: &[Chunk], text: &str) {
+        let joined: String = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+        assert_eq!(joined, text);
+    }
+
+    #[test]
+    fn test_config_formats_chunk_by_key_path() {
+        let mut env = String::new();
+        for i in 0..60 {
+            writeln!(
+                env,
+                "            - name: SETTING_{i}\n              value: \"{i}\""
+            )
+            .unwrap();
+        }
+        let manifest = format!(
+            "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: web\nspec:\n  replicas: 2\n  template:\n    spec:\n      containers:\n        - name: web\n          image: web:1.0\n          env:\n{env}---\napiVersion: v1\nkind: Service\n"
+        );
+        let chunks = chunk("deploy/web.yaml", &manifest);
+        assert_covers(&chunks, &manifest);
+        let paths = breadcrumbs(&chunks);
+        assert!(
+            paths.contains(&"spec.template.spec.containers[0].env"),
+            "{paths:?}"
+        );
+        // The small top-level keys before `spec` share one chunk
+        assert_eq!(paths[0], "");
+        assert!(
+            chunks[0]
+                .text
+                .starts_with("apiVersion: apps/v1\nkind: Deployment")
+        );
+        assert_eq!(
+            chunks.last().unwrap().text,
+            "---\napiVersion: v1\nkind: Service\n"
+        );
+
+        let mut tags = String::new();
+        for i in 0..80 {
+            writeln!(tags, "    tag_{i} = \"value-{i}\"").unwrap();
+        }
+        let terraform = format!(
+            "# Web server\nresource \"aws_instance\" \"web\" {{\n  ami = \"ami-123\"\n  tags = {{\n{tags}  }}\n}}\n\nvariable \"region\" {{\n  default = \"us-east-1\"\n}}\n"
+        );
+        let chunks = chunk("main.tf", &terraform);
+        assert_covers(&chunks, &terraform);
+        let paths = breadcrumbs(&chunks);
+        assert!(
+            paths.contains(&"resource.aws_instance.web.tags"),
+            "{paths:?}"
+        );
+        assert!(chunks[0].text.starts_with("# Web server\n"));
+        assert_eq!(*paths.last().unwrap(), "variable.region");
+        assert!(chunks.iter().all(|chunk| !chunk.text.starts_with('}')));
+
+        let mut scripts = String::new();
+        for i in 0..80 {
+            writeln!(
+                scripts,
+                "    \"task-{i}\": \"node scripts/task-{i}.js --verbose\","
+            )
+            .unwrap();
+        }
+        let package = format!(
+            "{{\n  \"name\": \"web\",\n  \"scripts\": {{\n{scripts}    \"last\": \"true\"\n  }}\n}}\n"
+        );
+        let chunks = chunk("package.json", &package);
+        assert_covers(&chunks, &package);
+        assert!(breadcrumbs(&chunks).contains(&"scripts"));
+
+        // Invalid JSON is chunked as plain text
+        let chunks = chunk("broken.json", "{\"name\": \n");
+        assert_eq!(chunks[0].metadata.breadcrumb, None);
+    }
+}
@@ -0,0 +1,145 @@
+//! `ck --ask "<question>"`: retrieve the code that answers a question, then
+//! either stream an answer with `path:line` citations from the configured LLM
+//! or print the stitched excerpts as Markdown for pasting into a chat.
+//! `ck --context-bundle "<query>"` prints such excerpts cut to a token budget.
+
+use anyhow::Result;
+use ck_core::{SearchOptions, SearchResult};
+use std::io::Write;
+
+use crate::progress::StatusReporter;
+
+/// Hits retrieved for a question when `--topk` is not given.
+pub const DEFAULT_ASK_TOP_K: usize = 8;
+
+/// Candidates a context bundle is chosen from when `--topk` is not given.
+pub const DEFAULT_BUNDLE_TOP_K: usize = 50;
+
+/// Token budget of a context bundle when `--budget` is not given.
+pub const DEFAULT_BUNDLE_BUDGET: usize = 8000;
+
+/// Parse a `--budget` value: a token count such as `8000`, `8k`, `8000tokens`
+/// or `8k tokens`.
+pub fn parse_token_budget(value: &str) -> std::result::Result<usize, String> {
+    let invalid = || format!("Invalid budget '{value}'; use a token count such as 8000 or 8k");
+    let lower = value.trim().to_ascii_lowercase();
+    let number = lower
+        .strip_suffix("tokens")
+        .or_else(|| lower.strip_suffix("token"))
+        .unwrap_or(&lower)
+        .trim_end();
+    let (number, scale) = match number.strip_suffix('k') {
+        Some(number) => (number, 1000.0),
+        None => (number, 1.0),
+    };
+    let tokens = number.trim().parse::<f64>().map_err(|_| invalid())? * scale;
+    if !tokens.is_finite() || tokens < 1.0 || tokens >= usize::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(tokens as usize)
+}
+
+async fn retrieve(options: &SearchOptions, status: &StatusReporter) -> Result<Vec<SearchResult>> {
+    let spinner = status.create_spinner("Retrieving context...");
+    let progress = spinner.as_ref().map(|spinner| {
+        let spinner = spinner.clone();
+        Box::new(move |msg: &str| spinner.set_message(msg.to_string()))
+            as ck_engine::SearchProgressCallback
+    });
+    let results = ck_engine::search_enhanced_with_progress(options, progress).await?;
+    status.finish_progress(
+        spinner,
+        &format!("Retrieved {} matches", results.matches.len()),
+    );
+    Ok(results.matches)
+}
+
+/// Print the excerpts for `query` that fit in `budget` tokens. Returns whether
+/// anything was found.
+pub async fn run_bundle(
+    query: &str,
+    mut options: SearchOptions,
+    budget: usize,
+    status: &StatusReporter,
+) -> Result<bool> {
+    options.query = query.to_string();
+    let results = retrieve(&options, status).await?;
+    let context = ck_engine::build_budgeted_context(query, &results, budget);
+    if results.is_empty() {
+        return Ok(false);
+    }
+    if context.excerpts.is_empty() {
+        anyhow::bail!("No match fits in {} tokens; raise --budget", budget);
+    }
+    print!("{}", context);
+    status.info(&format!(
+        "{} excerpts, ~{} of {} tokens",
+        context.excerpts.len(),
+        context.estimated_tokens(),
+        budget
+    ));
+    Ok(true)
+}
+
+/// Answer `question` from the code `options` searches. Returns whether
+/// anything relevant was found.
+pub async fn run(
+    question: &str,
+    mut options: SearchOptions,
+    status: &StatusReporter,
+) -> Result<bool> {
+    options.query = question.to_string();
+    let results = retrieve(&options, status).await?;
+    let context = ck_engine::build_answer_context(question, &results);
+    if context.excerpts.is_empty() {
+        return Ok(false);
+    }
+
+    let configured = ck_models::ProjectConfig::for_path(&options.path)
+        .trusted()
+        .llm
+        .is_configured();
+    if !ck_engine::answer_llm_available(&options.path) {
+        if configured {
+            status.warn(
+                "An LLM is configured but this ck was built without the `remote` feature; printing the context instead",
+            );
+        }
+        print!("{}", context);
+        return Ok(true);
+    }
+
+    let path = options.path.clone();
+    let citations: Vec<String> = context.excerpts.iter().map(|e| e.citation()).collect();
+    tokio::task::spawn_blocking(move || {
+        let mut stdout = std::io::stdout();
+        ck_engine::synthesize_answer(&path, &context, &mut |token| {
+            let _ = write!(stdout, "{}", token);
+            let _ = stdout.flush();
+        })
+    })
+    .await??;
+
+    println!();
+    println!();
+    println!("Sources:");
+    for citation in citations {
+        println!("  {}", citation);
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_token_budget() {
+        assert_eq!(parse_token_budget("8000"), Ok(8000));
+        assert_eq!(parse_token_budget("8000tokens"), Ok(8000));
+        assert_eq!(parse_token_budget("8k tokens"), Ok(8000));
+        assert_eq!(parse_token_budget("1.5K"), Ok(1500));
+        assert!(parse_token_budget("0").is_err());
+        assert!(parse_token_budget("lots").is_err());
+    }
+}
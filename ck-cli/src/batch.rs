@@ -0,0 +1,335 @@
+//! `ck --stdin`: answer a stream of queries in one process.
+//!
+//! Each input line is either a plain query, searched with the options given on
+//! the command line, or a JSON object that overrides them for that query:
+//!
+//! ```json
+//! {"id": 7, "query": "retry with backoff", "mode": "sem", "top_k": 5, "path": "src/"}
+//! ```
+//!
+//! Each query produces one JSON line holding its results (or its error), in
+//! input order. Models stay loaded between queries, so agents issuing dozens of
+//! searches pay the load time once, and queries sent together rerank together.
+
+use anyhow::Result;
+use ck_core::{JsonlSearchResult, Language, SearchOptions};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Per-query overrides; fields left out keep the command-line value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchQuery {
+    /// Echoed back so callers can match responses to requests
+    #[serde(default)]
+    pub id: Option<serde_json::Value>,
+    pub query: String,
+    /// regex, lex, sem, hybrid or sym
+    #[serde(default)]
+    pub mode: Option<String>,
+    /// Search path, relative to the working directory
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    /// Ranked results to skip before the `top_k` returned
+    #[serde(default)]
+    pub offset: Option<usize>,
+    #[serde(default)]
+    pub threshold: Option<f32>,
+    /// Language names as accepted by `--lang`
+    #[serde(default)]
+    pub lang: Option<Vec<String>>,
+    #[serde(default)]
+    pub rerank: Option<bool>,
+    #[serde(default)]
+    pub rerank_model: Option<String>,
+    /// Replaces the query instruction, as `--instruct` does
+    #[serde(default)]
+    pub instruct: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<serde_json::Value>,
+    pub query: String,
+    pub results: Vec<JsonlSearchResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+/// Values given explicitly on the command line, which win over per-mode defaults.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BatchDefaults {
+    pub top_k: Option<usize>,
+    pub threshold: Option<f32>,
+    pub offset: usize,
+}
+
+/// Summary printed on stderr once input ends.
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub queries: usize,
+    pub failed: usize,
+}
+
+/// Parse one input line into the query to run, its options and its offset.
+fn parse_line(
+    line: &str,
+    base: &SearchOptions,
+    defaults: BatchDefaults,
+) -> Result<(BatchQuery, SearchOptions, usize)> {
+    let request = if line.starts_with('{') {
+        serde_json::from_str::<BatchQuery>(line)?
+    } else {
+        BatchQuery {
+            query: line.to_string(),
+            ..Default::default()
+        }
+    };
+
+    let mut options = base.clone();
+    options.query = request.query.clone();
+    if let Some(mode) = &request.mode {
+        options.mode = mode.parse().map_err(anyhow::Error::msg)?;
+        // Re-derive mode defaults unless the command line set them explicitly
+        let (top_k, threshold) = crate::default_limits(&options.mode);
+        options.top_k = defaults.top_k.or(top_k);
+        options.threshold = defaults.threshold.or(threshold);
+    }
+    if let Some(path) = &request.path {
+        options.path = path.clone();
+    }
+    options.top_k = request.top_k.or(options.top_k);
+    options.threshold = request.threshold.or(options.threshold);
+    if let Some(languages) = &request.lang {
+        options.languages = languages
+            .iter()
+            .map(|lang| lang.parse::<Language>().map_err(anyhow::Error::msg))
+            .collect::<Result<_>>()?;
+    }
+    options.rerank = request.rerank.unwrap_or(options.rerank);
+    if request.rerank_model.is_some() {
+        options.rerank_model = request.rerank_model.clone();
+    }
+    if request.instruct.is_some() {
+        options.instruction = request.instruct.clone();
+    }
+    let offset = request.offset.unwrap_or(defaults.offset);
+    options.top_k = options.top_k.map(|limit| limit + offset);
+    Ok((request, options, offset))
+}
+
+/// Most queries answered together; see [`run`].
+const MAX_GROUP: usize = 32;
+
+/// Answer every line of `input` on `output`. Lines that have already arrived
+/// when a query is read are answered with it, so semantic queries that rerank
+/// share reranker batches ([`ck_engine::search_batch`]); a group never waits
+/// for more input, and its queries report the group's `elapsed_ms`.
+pub async fn run<R, W>(
+    input: R,
+    mut output: W,
+    base: &SearchOptions,
+    defaults: BatchDefaults,
+) -> Result<BatchSummary>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut summary = BatchSummary::default();
+    let mut lines = input.lines();
+    while let Some(first) = lines.next_line().await? {
+        let mut group = vec![first];
+        while group.len() < MAX_GROUP {
+            // `next_line` is cancel safe, so a line still in flight is kept
+            match tokio::time::timeout(Duration::ZERO, lines.next_line()).await {
+                Ok(Ok(Some(line))) => group.push(line),
+                Ok(Ok(None)) | Err(_) => break,
+                Ok(Err(e)) => return Err(e.into()),
+            }
+        }
+        let group: Vec<&str> = group
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if group.is_empty() {
+            continue;
+        }
+        summary.queries += group.len();
+        let started = Instant::now();
+
+        let parsed: Vec<_> = group
+            .iter()
+            .map(|line| parse_line(line, base, defaults))
+            .collect();
+        let searches: Vec<SearchOptions> = parsed
+            .iter()
+            .filter_map(|parsed| parsed.as_ref().ok())
+            .map(|(_, options, _)| options.clone())
+            .collect();
+        let mut outcomes = ck_engine::search_batch(&searches).await.into_iter();
+
+        let mut responses = Vec::with_capacity(group.len());
+        for (line, parsed) in group.iter().zip(parsed) {
+            responses.push(match parsed {
+                Ok((request, options, offset)) => {
+                    let outcome = outcomes.next().expect("one outcome per parsed query");
+                    let (results, error) = match outcome {
+                        Ok(results) => (
+                            results
+                                .matches
+                                .iter()
+                                .skip(offset)
+                                .map(|r| {
+                                    let mut hit = JsonlSearchResult::from_search_result(
+                                        r,
+                                        !options.no_snippet,
+                                    );
+                                    if options.explain {
+                                        hit.explain = r.explain.clone();
+                                    }
+                                    hit
+                                })
+                                .collect(),
+                            None,
+                        ),
+                        Err(e) => (Vec::new(), Some(e.to_string())),
+                    };
+                    BatchResponse {
+                        id: request.id,
+                        query: request.query,
+                        results,
+                        error,
+                        elapsed_ms: 0,
+                    }
+                }
+                Err(e) => BatchResponse {
+                    id: None,
+                    query: line.to_string(),
+                    results: Vec::new(),
+                    error: Some(format!("Invalid query line: {}", e)),
+                    elapsed_ms: 0,
+                },
+            });
+        }
+
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        for mut response in responses {
+            if response.error.is_some() {
+                summary.failed += 1;
+            }
+            response.elapsed_ms = elapsed_ms;
+            let mut json = serde_json::to_vec(&response)?;
+            json.push(b'\n');
+            output.write_all(&json).await?;
+        }
+        output.flush().await?;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::SearchMode;
+
+    #[test]
+    fn test_parse_line_applies_overrides() {
+        let base = SearchOptions {
+            mode: SearchMode::Regex,
+            ..SearchOptions::default()
+        };
+
+        let (request, options, _) = parse_line("fn main", &base, BatchDefaults::default()).unwrap();
+        assert_eq!(request.query, "fn main");
+        assert!(matches!(options.mode, SearchMode::Regex));
+        assert_eq!(options.top_k, None);
+
+        let line = r#"{"id": "a", "query": "retry", "mode": "sem", "lang": ["rust"]}"#;
+        let (request, options, _) = parse_line(line, &base, BatchDefaults::default()).unwrap();
+        assert_eq!(request.id, Some(serde_json::json!("a")));
+        assert!(matches!(options.mode, SearchMode::Semantic));
+        assert_eq!(options.top_k, Some(10));
+        assert_eq!(options.threshold, Some(0.6));
+        assert_eq!(options.languages, vec![Language::Rust]);
+
+        // Command-line limits beat mode defaults; per-query limits beat both
+        let line = r#"{"query": "retry", "mode": "sem", "threshold": 0.3}"#;
+        let defaults = BatchDefaults {
+            top_k: Some(3),
+            ..Default::default()
+        };
+        let (_, options, _) = parse_line(line, &base, defaults).unwrap();
+        assert_eq!(options.top_k, Some(3));
+        assert_eq!(options.threshold, Some(0.3));
+
+        // Paging retrieves through the end of the page
+        let line = r#"{"query": "retry", "top_k": 5, "offset": 10}"#;
+        let (_, options, offset) = parse_line(line, &base, defaults).unwrap();
+        assert_eq!((options.top_k, offset), (Some(15), 10));
+
+        let line = r#"{"query": "retry", "instruct": "Find the retry loop"}"#;
+        let (_, options, _) = parse_line(line, &base, defaults).unwrap();
+        assert_eq!(options.instruction.as_deref(), Some("Find the retry loop"));
+
+        assert!(
+            parse_line(
+                r#"{"query": "x", "mode": "fuzzy"}"#,
+                &base,
+                BatchDefaults::default()
+            )
+            .is_err()
+        );
+        assert!(
+            parse_line(
+                r#"{"query": "x", "topk": 3}"#,
+                &base,
+                BatchDefaults::default()
+            )
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_answers_each_line_in_order() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("a.txt"), "alpha\nbeta\n").unwrap();
+        let base = SearchOptions {
+            mode: SearchMode::Regex,
+            path: temp_dir.path().to_path_buf(),
+            ..SearchOptions::default()
+        };
+
+        let input = "alpha\n\n{\"id\": 2, \"query\": \"beta\"}\ngamma\n{\"query\": 1}\n";
+        let mut output = Vec::new();
+        let summary = run(
+            input.as_bytes(),
+            &mut output,
+            &base,
+            BatchDefaults::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.queries, 4);
+        assert_eq!(summary.failed, 1);
+
+        let responses: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["results"].as_array().unwrap().len(), 1);
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["results"][0]["span"]["line_start"], 2);
+        assert_eq!(responses[2]["query"], "gamma");
+        assert!(responses[2]["results"].as_array().unwrap().is_empty());
+        assert!(responses[3]["error"].as_str().unwrap().contains("Invalid"));
+    }
+}
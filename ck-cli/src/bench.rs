@@ -0,0 +1,381 @@
+//! `ck --bench embed|index|search`: throughput and latency of each candidate
+//! embedding model on a corpus, printed as one comparison table.
+//!
+//! Index and search runs work on a temporary copy of the corpus so the user's
+//! own `.ck` index is never rebuilt with a different model.
+
+use anyhow::{Context, Result, bail};
+use ck_core::{FileCollectionOptions, SearchMode, SearchOptions};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::progress::StatusReporter;
+
+/// Chunks per `embed` call in the embed benchmark.
+const EMBED_BATCH: usize = 32;
+/// Results requested per query in the search benchmark.
+const SEARCH_TOP_K: usize = 10;
+
+const DEFAULT_QUERIES: &[&str] = &[
+    "error handling",
+    "parse configuration file",
+    "retry with backoff",
+    "database connection",
+    "authentication and permissions",
+    "read file from disk",
+    "unit tests for the parser",
+    "command line arguments",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BenchKind {
+    /// Chunk the corpus and time embedding batches
+    Embed,
+    /// Time a full index build with embeddings
+    Index,
+    /// Time semantic queries against a freshly built index
+    Search,
+}
+
+impl BenchKind {
+    fn unit(self) -> &'static str {
+        match self {
+            BenchKind::Embed => "chunks",
+            BenchKind::Index => "files",
+            BenchKind::Search => "queries",
+        }
+    }
+}
+
+/// One model's result.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchRow {
+    pub model: String,
+    pub provider: String,
+    pub dimensions: usize,
+    /// Model load (embed) or index build (search) time, excluded from throughput
+    pub setup_ms: f64,
+    pub items: usize,
+    pub unit: &'static str,
+    pub per_sec: f64,
+    /// Per batch (embed) or per query (search); absent for index builds
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+}
+
+pub struct BenchRequest<'a> {
+    pub kind: BenchKind,
+    pub corpus: &'a Path,
+    pub models: Vec<String>,
+    pub queries: Vec<String>,
+    pub file_options: FileCollectionOptions,
+}
+
+/// Nearest-rank percentile of `samples` in milliseconds.
+fn percentile_ms(samples: &mut [Duration], pct: f64) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_unstable();
+    let rank = ((pct / 100.0) * samples.len() as f64).ceil() as usize;
+    let sample = samples[rank.clamp(1, samples.len()) - 1];
+    Some(sample.as_secs_f64() * 1000.0)
+}
+
+fn per_sec(items: usize, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 { items as f64 / secs } else { 0.0 }
+}
+
+fn millis(elapsed: Duration) -> f64 {
+    elapsed.as_secs_f64() * 1000.0
+}
+
+/// Queries from `--bench-queries FILE` (one per line, blank lines and `#`
+/// comments skipped), else a built-in set of typical code-search queries.
+pub fn load_queries(file: Option<&Path>) -> Result<Vec<String>> {
+    let Some(file) = file else {
+        return Ok(DEFAULT_QUERIES.iter().map(|q| q.to_string()).collect());
+    };
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read queries from {}", file.display()))?;
+    let queries: Vec<String> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if queries.is_empty() {
+        bail!("No queries found in {}", file.display());
+    }
+    Ok(queries)
+}
+
+/// Copy the files ck would index (plus `.ckconfig.json`) into a temporary tree.
+pub(crate) fn copy_corpus(corpus: &Path, files: &[PathBuf]) -> Result<tempfile::TempDir> {
+    let temp = tempfile::TempDir::new()?;
+    for file in files {
+        let relative = file.strip_prefix(corpus).unwrap_or(file);
+        let target = temp.path().join(relative);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(file, &target)?;
+    }
+    let config = corpus.join(ck_models::PROJECT_CONFIG_FILE);
+    if config.exists() {
+        std::fs::copy(&config, temp.path().join(ck_models::PROJECT_CONFIG_FILE))?;
+    }
+    Ok(temp)
+}
+
+fn bench_embed(config: &ck_models::ModelConfig, files: &[PathBuf]) -> Result<BenchRow> {
+    let mut texts = Vec::new();
+    for file in files {
+        let Ok(content) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let language = ck_core::Language::from_path(file);
+        let chunks = ck_chunk::chunk_text_with_model(&content, language, Some(&config.name))?;
+        texts.extend(chunks.into_iter().map(|chunk| chunk.text));
+    }
+
+    let load_start = Instant::now();
+    let mut embedder = ck_embed::create_embedder_for_config(config, None)?;
+    let setup = load_start.elapsed();
+
+    let mut latencies = Vec::new();
+    let start = Instant::now();
+    for batch in texts.chunks(EMBED_BATCH) {
+        let batch_start = Instant::now();
+        embedder.embed(batch)?;
+        latencies.push(batch_start.elapsed());
+    }
+    let elapsed = start.elapsed();
+
+    Ok(BenchRow {
+        model: String::new(),
+        provider: config.provider.clone(),
+        dimensions: embedder.dim(),
+        setup_ms: millis(setup),
+        items: texts.len(),
+        unit: BenchKind::Embed.unit(),
+        per_sec: per_sec(texts.len(), elapsed),
+        p50_ms: percentile_ms(&mut latencies, 50.0),
+        p95_ms: percentile_ms(&mut latencies, 95.0),
+    })
+}
+
+pub(crate) async fn build_index(
+    root: &Path,
+    alias: &str,
+    file_options: &FileCollectionOptions,
+) -> Result<ck_index::UpdateStats> {
+    Ok(ck_index::smart_update_index_with_progress(
+        root,
+        true,
+        None,
+        true,
+        file_options,
+        Some(alias),
+    )
+    .await?)
+}
+
+async fn bench_index_or_search(
+    request: &BenchRequest<'_>,
+    alias: &str,
+    config: &ck_models::ModelConfig,
+    files: &[PathBuf],
+) -> Result<BenchRow> {
+    let copy = copy_corpus(request.corpus, files)?;
+    let start = Instant::now();
+    let stats = build_index(copy.path(), alias, &request.file_options).await?;
+    let build = start.elapsed();
+    let dimensions = std::fs::read(copy.path().join(".ck").join("manifest.json"))
+        .ok()
+        .and_then(|data| serde_json::from_slice::<ck_index::IndexManifest>(&data).ok())
+        .and_then(|manifest| manifest.embedding_dimensions)
+        .unwrap_or(config.dimensions);
+
+    if request.kind == BenchKind::Index {
+        return Ok(BenchRow {
+            model: String::new(),
+            provider: config.provider.clone(),
+            dimensions,
+            setup_ms: 0.0,
+            items: stats.files_indexed,
+            unit: BenchKind::Index.unit(),
+            per_sec: per_sec(stats.files_indexed, build),
+            p50_ms: None,
+            p95_ms: None,
+        });
+    }
+
+    let mut latencies = Vec::new();
+    let start = Instant::now();
+    for query in &request.queries {
+        let options = SearchOptions {
+            mode: SearchMode::Semantic,
+            query: query.clone(),
+            path: copy.path().to_path_buf(),
+            top_k: Some(SEARCH_TOP_K),
+            embedding_model: Some(alias.to_string()),
+            respect_gitignore: request.file_options.respect_gitignore,
+            use_ckignore: request.file_options.use_ckignore,
+            exclude_patterns: request.file_options.exclude_patterns.clone(),
+            ..SearchOptions::default()
+        };
+        let query_start = Instant::now();
+        ck_engine::search(&options).await?;
+        latencies.push(query_start.elapsed());
+    }
+    let elapsed = start.elapsed();
+
+    Ok(BenchRow {
+        model: String::new(),
+        provider: config.provider.clone(),
+        dimensions,
+        setup_ms: millis(build),
+        items: request.queries.len(),
+        unit: BenchKind::Search.unit(),
+        per_sec: per_sec(request.queries.len(), elapsed),
+        p50_ms: percentile_ms(&mut latencies, 50.0),
+        p95_ms: percentile_ms(&mut latencies, 95.0),
+    })
+}
+
+/// Run `request.kind` once per model, in order.
+pub async fn run(request: &BenchRequest<'_>, status: &StatusReporter) -> Result<Vec<BenchRow>> {
+    let files = ck_index::collect_files(request.corpus, &request.file_options)?;
+    if files.is_empty() {
+        bail!("No indexable files found in {}", request.corpus.display());
+    }
+
+    let registry = ck_models::ModelRegistry::with_user_models();
+    let mut rows = Vec::new();
+    for model in &request.models {
+        let (alias, config) = registry.resolve(Some(model))?;
+        let spinner = status.create_spinner(&format!("Benchmarking {}...", alias));
+        let row = match request.kind {
+            BenchKind::Embed => bench_embed(&config, &files),
+            BenchKind::Index | BenchKind::Search => {
+                bench_index_or_search(request, &alias, &config, &files).await
+            }
+        };
+        status.finish_progress(spinner, &format!("{} done", alias));
+        rows.push(BenchRow {
+            model: alias,
+            ..row?
+        });
+    }
+    Ok(rows)
+}
+
+/// Render `rows` as an aligned table.
+pub fn format_table(kind: BenchKind, rows: &[BenchRow]) -> String {
+    let setup = match kind {
+        BenchKind::Embed => "load",
+        BenchKind::Index => "",
+        BenchKind::Search => "index",
+    };
+    let latency = match kind {
+        BenchKind::Embed => "per batch",
+        _ => "per query",
+    };
+    let fmt_ms = |ms: Option<f64>| ms.map_or("-".to_string(), |ms| format!("{:.1}ms", ms));
+
+    let mut table = vec![vec![
+        "model".to_string(),
+        "provider".to_string(),
+        "dims".to_string(),
+        kind.unit().to_string(),
+        format!("{}/s", kind.unit()),
+        format!("p50 {}", latency),
+        format!("p95 {}", latency),
+        setup.to_string(),
+    ]];
+    for row in rows {
+        table.push(vec![
+            row.model.clone(),
+            row.provider.clone(),
+            row.dimensions.to_string(),
+            row.items.to_string(),
+            format!("{:.1}", row.per_sec),
+            fmt_ms(row.p50_ms),
+            fmt_ms(row.p95_ms),
+            if setup.is_empty() {
+                String::new()
+            } else {
+                format!("{:.0}ms", row.setup_ms)
+            },
+        ]);
+    }
+    if kind == BenchKind::Index {
+        for line in &mut table {
+            line.truncate(5);
+        }
+    }
+
+    let columns = table[0].len();
+    let widths: Vec<usize> = (0..columns)
+        .map(|col| table.iter().map(|line| line[col].len()).max().unwrap_or(0))
+        .collect();
+    table
+        .iter()
+        .map(|line| {
+            line.iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(col, (cell, width))| {
+                    // Model and provider read left to right; numbers align right
+                    if col < 2 {
+                        format!("{:<width$}", cell)
+                    } else {
+                        format!("{:>width$}", cell)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_uses_nearest_rank() {
+        let mut samples: Vec<Duration> = (1..=20).map(Duration::from_millis).collect();
+        assert_eq!(percentile_ms(&mut samples, 50.0), Some(10.0));
+        assert_eq!(percentile_ms(&mut samples, 95.0), Some(19.0));
+        assert_eq!(percentile_ms(&mut [], 50.0), None);
+    }
+
+    #[test]
+    fn test_table_aligns_and_drops_latency_for_index() {
+        let row = BenchRow {
+            model: "bge-small".to_string(),
+            provider: "fastembed".to_string(),
+            dimensions: 384,
+            setup_ms: 0.0,
+            items: 120,
+            unit: "files",
+            per_sec: 42.5,
+            p50_ms: None,
+            p95_ms: None,
+        };
+        let table = format_table(BenchKind::Index, &[row]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("model"));
+        assert!(lines[0].ends_with("files/s"));
+        assert!(lines[1].ends_with("42.5"));
+        assert!(!table.contains("p50"));
+    }
+}
@@ -0,0 +1,329 @@
+//! `ck --check FILE`: policy rules for pre-commit hooks and CI.
+//!
+//! Each rule is a query with a score threshold and a policy. `must-not-match`
+//! rules fail when anything scores at or above the threshold; `must-match`
+//! rules fail when nothing does:
+//!
+//! ```yaml
+//! threshold: 0.7            # default for rules without their own
+//! rules:
+//!   - name: no-tls-bypass
+//!     query: disable TLS certificate verification
+//!     threshold: 0.75
+//!     message: Certificate checks must stay on
+//!   - name: rate-limited-api
+//!     query: rate limit incoming requests
+//!     policy: must-match
+//!     paths: [src/api]
+//!   - name: no-unwrap-in-handlers
+//!     query: "\\.unwrap\\(\\)"
+//!     mode: regex
+//!     paths: [src/handlers]
+//! ```
+//!
+//! With `--diff`, `must-not-match` rules only see the files the revision range
+//! changed, so a hook flags what the change introduces. `must-match` rules still
+//! check their whole scope, since a change's own files cannot show that
+//! something exists elsewhere.
+
+use anyhow::{Context, Result, bail};
+use ck_core::{FileCollectionOptions, IncludePattern, SearchMode, SearchOptions};
+use console::style;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::progress::StatusReporter;
+
+const DEFAULT_THRESHOLD: f32 = 0.7;
+
+/// Matches reported per rule.
+const MAX_MATCHES: usize = 10;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RulesFile {
+    #[serde(default)]
+    pub threshold: Option<f32>,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Policy {
+    #[default]
+    MustNotMatch,
+    MustMatch,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub policy: Policy,
+    #[serde(default)]
+    pub threshold: Option<f32>,
+    /// sem (default), lex, hybrid, sym or regex
+    #[serde(default)]
+    pub mode: Option<String>,
+    /// Files or directories relative to the checked root; everything when empty
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+    /// Shown when the rule fails
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pass,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Match {
+    pub path: String,
+    pub line: usize,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleReport {
+    pub name: String,
+    pub policy: Policy,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub matches: Vec<Match>,
+}
+
+impl RuleReport {
+    pub fn failed(&self) -> bool {
+        self.outcome == Outcome::Fail
+    }
+}
+
+pub fn load(path: &Path) -> Result<RulesFile> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read rules file {}", path.display()))?;
+    let file: RulesFile = serde_yaml_ng::from_str(&content)
+        .with_context(|| format!("Invalid rules file {}", path.display()))?;
+    if file.rules.is_empty() {
+        bail!("Rules file {} has no rules", path.display());
+    }
+    for rule in &file.rules {
+        crate::eval::mode_or_semantic(rule.mode.as_deref())
+            .with_context(|| format!("Invalid rule '{}'", rule.name))?;
+    }
+    Ok(file)
+}
+
+fn outcome(policy: Policy, matched: bool) -> Outcome {
+    match (policy, matched) {
+        (Policy::MustNotMatch, false) | (Policy::MustMatch, true) => Outcome::Pass,
+        _ => Outcome::Fail,
+    }
+}
+
+/// Check every rule against `root`. `changed` holds the files of a `--diff`
+/// range, when one was given.
+pub async fn run(
+    file: &RulesFile,
+    root: &Path,
+    file_options: &FileCollectionOptions,
+    changed: Option<&[PathBuf]>,
+    status: &StatusReporter,
+) -> Result<Vec<RuleReport>> {
+    let mut reports = Vec::new();
+    for rule in &file.rules {
+        let mode = crate::eval::mode_or_semantic(rule.mode.as_deref())?;
+        let scope: Vec<PathBuf> = rule.paths.iter().map(|p| root.join(p)).collect();
+        let mut include_patterns: Vec<IncludePattern> =
+            crate::path_utils::build_include_patterns(&scope);
+        if let Some(changed) = changed
+            && rule.policy == Policy::MustNotMatch
+        {
+            include_patterns = crate::git_diff::scope_to_changes(changed, &include_patterns);
+            // An empty scope would search everything
+            if include_patterns.is_empty() {
+                reports.push(RuleReport {
+                    name: rule.name.clone(),
+                    policy: rule.policy,
+                    outcome: Outcome::Pass,
+                    message: None,
+                    matches: Vec::new(),
+                });
+                continue;
+            }
+        }
+
+        let spinner = status.create_spinner(&format!("Checking {}...", rule.name));
+        let options = SearchOptions {
+            threshold: match mode {
+                SearchMode::Regex => None,
+                _ => Some(
+                    rule.threshold
+                        .or(file.threshold)
+                        .unwrap_or(DEFAULT_THRESHOLD),
+                ),
+            },
+            mode,
+            query: rule.query.clone(),
+            path: root.to_path_buf(),
+            top_k: Some(MAX_MATCHES),
+            include_patterns,
+            respect_gitignore: file_options.respect_gitignore,
+            use_ckignore: file_options.use_ckignore,
+            exclude_patterns: file_options.exclude_patterns.clone(),
+            ..SearchOptions::default()
+        };
+        let results = ck_engine::search(&options).await?;
+        status.finish_progress(spinner, &format!("{} checked", rule.name));
+
+        let outcome = outcome(rule.policy, !results.is_empty());
+        reports.push(RuleReport {
+            name: rule.name.clone(),
+            policy: rule.policy,
+            outcome,
+            message: (outcome == Outcome::Fail)
+                .then(|| rule.message.clone())
+                .flatten(),
+            matches: results
+                .iter()
+                .take(MAX_MATCHES)
+                .map(|r| {
+                    let path = r.file.strip_prefix(root).unwrap_or(&r.file);
+                    Match {
+                        path: crate::eval::normalize_path(&path.to_string_lossy()),
+                        line: r.span.line_start,
+                        score: r.score,
+                    }
+                })
+                .collect(),
+        });
+    }
+    Ok(reports)
+}
+
+/// One line per rule, followed by its matches, then a summary.
+pub fn format_report(reports: &[RuleReport]) -> String {
+    let width = reports.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    for report in reports {
+        let label = match report.outcome {
+            Outcome::Pass => style("PASS").green().bold(),
+            Outcome::Fail => style("FAIL").red().bold(),
+        };
+        let policy = match report.policy {
+            Policy::MustNotMatch => "must-not-match",
+            Policy::MustMatch => "must-match",
+        };
+        lines.push(format!(
+            "{}  {:<width$}  {:<14}  {} match{}",
+            label,
+            report.name,
+            policy,
+            report.matches.len(),
+            if report.matches.len() == 1 { "" } else { "es" }
+        ));
+        if let Some(message) = &report.message {
+            lines.push(format!("      {}", message));
+        }
+        if report.failed() {
+            for m in &report.matches {
+                lines.push(format!("      {}:{} ({:.3})", m.path, m.line, m.score));
+            }
+        }
+    }
+    let failed = reports.iter().filter(|r| r.failed()).count();
+    lines.push(format!(
+        "\n{} rule{} checked, {} failed",
+        reports.len(),
+        if reports.len() == 1 { "" } else { "s" },
+        failed
+    ));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome_follows_policy() {
+        assert_eq!(outcome(Policy::MustNotMatch, false), Outcome::Pass);
+        assert_eq!(outcome(Policy::MustNotMatch, true), Outcome::Fail);
+        assert_eq!(outcome(Policy::MustMatch, true), Outcome::Pass);
+        assert_eq!(outcome(Policy::MustMatch, false), Outcome::Fail);
+    }
+
+    #[test]
+    fn test_load_rules_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("rules.yaml");
+        std::fs::write(
+            &path,
+            "threshold: 0.8\nrules:\n  - name: no-tls-bypass\n    query: disable TLS verification\n  - name: limiter\n    query: rate limit\n    policy: must-match\n    paths: [src/api]\n",
+        )
+        .unwrap();
+        let file = load(&path).unwrap();
+        assert_eq!(file.threshold, Some(0.8));
+        assert_eq!(file.rules[0].policy, Policy::MustNotMatch);
+        assert_eq!(file.rules[1].policy, Policy::MustMatch);
+        assert_eq!(file.rules[1].paths, vec![PathBuf::from("src/api")]);
+
+        std::fs::write(
+            &path,
+            "rules:\n  - name: x\n    query: y\n    mode: fuzzy\n",
+        )
+        .unwrap();
+        assert!(load(&path).is_err());
+        std::fs::write(&path, "rules: []\n").unwrap();
+        assert!(load(&path).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_regex_rules_fail_on_violations() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(
+            root.join("src/net.rs"),
+            "fn connect() {\n    builder.danger_accept_invalid_certs(true);\n}\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("src/api.rs"), "fn limit() {}\n").unwrap();
+
+        let file: RulesFile = serde_yaml_ng::from_str(
+            "rules:\n  - name: no-invalid-certs\n    query: danger_accept_invalid_certs\n    mode: regex\n    message: keep TLS on\n  - name: api-limits\n    query: fn limit\n    mode: regex\n    policy: must-match\n    paths: [src/api.rs]\n  - name: api-has-no-certs\n    query: danger_accept\n    mode: regex\n    paths: [src/api.rs]\n",
+        )
+        .unwrap();
+        let status = StatusReporter::new(true);
+        let options = FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: Vec::new(),
+            follow_symlinks: false,
+            files_from: None,
+        };
+        let reports = run(&file, root, &options, None, &status).await.unwrap();
+
+        assert_eq!(reports[0].outcome, Outcome::Fail);
+        assert_eq!(reports[0].matches[0].path, "src/net.rs");
+        assert_eq!(reports[0].matches[0].line, 2);
+        assert_eq!(reports[0].message.as_deref(), Some("keep TLS on"));
+        assert_eq!(reports[1].outcome, Outcome::Pass);
+        assert_eq!(reports[2].outcome, Outcome::Pass);
+        assert!(format_report(&reports).ends_with("3 rules checked, 1 failed"));
+
+        // With nothing changed, only must-match rules still search
+        let reports = run(&file, root, &options, Some(&[]), &status)
+            .await
+            .unwrap();
+        assert_eq!(reports[0].outcome, Outcome::Pass);
+        assert!(reports[0].matches.is_empty());
+        assert_eq!(reports[1].outcome, Outcome::Pass);
+    }
+}
@@ -0,0 +1,66 @@
+//! `ck --validate-config`, and the same check of the project config and the
+//! user model registry at startup. A config file that does not parse would
+//! otherwise be replaced by the defaults without a word.
+
+use anyhow::{Result, bail};
+use ck_models::{ConfigIssue, ProjectConfig, Severity, UserModels, validate_file};
+use std::path::{Path, PathBuf};
+
+use crate::progress::StatusReporter;
+
+/// The config files governing `path` that exist, with their issues.
+fn check(path: &Path) -> Result<Vec<(PathBuf, Vec<ConfigIssue>)>> {
+    let mut checked = Vec::new();
+    if let Some(project) = ProjectConfig::find(path) {
+        let issues = validate_file::<ProjectConfig>(&project)?;
+        checked.push((project, issues));
+    }
+    if let Some(models) = ck_models::user_models_path().filter(|models| models.exists()) {
+        let issues = validate_file::<UserModels>(&models)?;
+        checked.push((models, issues));
+    }
+    Ok(checked)
+}
+
+/// Refuse to run with a config file that does not load; warn about keys it
+/// ignores.
+pub fn check_at_startup(path: &Path, status: &StatusReporter) -> Result<()> {
+    for (_, issues) in check(path)? {
+        for issue in &issues {
+            match issue.severity {
+                Severity::Error => bail!(
+                    "Invalid configuration: {}\nRun 'ck --validate-config' to list every problem",
+                    issue
+                ),
+                Severity::Warning => status.warn(&issue.to_string()),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Report every problem in the config files governing `path`. Returns
+/// whether there were none.
+pub fn validate(path: &Path, status: &StatusReporter) -> Result<bool> {
+    let checked = check(path)?;
+    if checked.is_empty() {
+        status.info("No .ckconfig.json or user model registry to check");
+        return Ok(true);
+    }
+    let mut clean = true;
+    for (file, issues) in checked {
+        if issues.is_empty() {
+            status.success(&format!("{} is valid", file.display()));
+            continue;
+        }
+        clean = false;
+        for issue in issues {
+            let severity = match issue.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            println!("{severity}: {issue}");
+        }
+    }
+    Ok(clean)
+}
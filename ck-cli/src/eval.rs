@@ -0,0 +1,439 @@
+//! `ck --eval FILE`: score retrieval against a team's golden queries.
+//!
+//! The eval file lists queries with the files (optionally line ranges) a good
+//! search should return, plus the configurations to compare:
+//!
+//! ```yaml
+//! top_k: 10
+//! configurations:
+//!   - name: baseline
+//!   - name: nomic+rerank
+//!     model: nomic-v1.5
+//!     rerank: true
+//! queries:
+//!   - query: retry an HTTP request with backoff
+//!     expected:
+//!       - src/http/retry.rs
+//!       - path: src/http/client.rs
+//!         lines: 40-85
+//! ```
+//!
+//! Each configuration runs the normal search pipeline and reports mean
+//! recall@k, MRR and nDCG@k. A configuration without `model` searches the
+//! corpus's own index; one with `model` gets a throwaway index in a temporary
+//! copy, as with `--bench`.
+
+use anyhow::{Context, Result, bail};
+use ck_core::{FileCollectionOptions, ScoreTransform, SearchMode, SearchOptions, SearchResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::bench;
+use crate::progress::StatusReporter;
+
+const DEFAULT_TOP_K: usize = 10;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvalFile {
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    #[serde(default)]
+    pub configurations: Vec<EvalConfig>,
+    pub queries: Vec<GoldenQuery>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvalConfig {
+    pub name: String,
+    /// Embedding model alias; the corpus's index model when absent
+    #[serde(default)]
+    pub model: Option<String>,
+    /// sem (default), lex, hybrid or sym
+    #[serde(default)]
+    pub mode: Option<String>,
+    #[serde(default)]
+    pub rerank: bool,
+    #[serde(default)]
+    pub rerank_model: Option<String>,
+    /// sigmoid, min-max or raw
+    #[serde(default)]
+    pub rerank_scores: Option<ScoreTransform>,
+    /// Rerank only this many leading dense candidates
+    #[serde(default)]
+    pub rerank_top: Option<usize>,
+    #[serde(default)]
+    pub threshold: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GoldenQuery {
+    pub query: String,
+    pub expected: Vec<Expected>,
+}
+
+/// A path relative to the corpus root, or a path with a line range.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Expected {
+    Path(String),
+    Span {
+        path: String,
+        #[serde(default)]
+        lines: Option<LineRange>,
+    },
+}
+
+/// `12` or `"10-40"` (inclusive).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum LineRange {
+    Line(usize),
+    Range(String),
+}
+
+impl LineRange {
+    fn bounds(&self) -> Result<(usize, usize)> {
+        match self {
+            LineRange::Line(line) => Ok((*line, *line)),
+            LineRange::Range(range) => {
+                let parse = |s: &str| s.trim().parse::<usize>();
+                let (start, end) = match range.split_once('-') {
+                    Some((start, end)) => (parse(start), parse(end)),
+                    None => (parse(range), parse(range)),
+                };
+                match (start, end) {
+                    (Ok(start), Ok(end)) if start <= end => Ok((start, end)),
+                    _ => bail!("Invalid line range '{}'; use N or START-END", range),
+                }
+            }
+        }
+    }
+}
+
+/// An expected location with its line range resolved.
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    path: String,
+    lines: Option<(usize, usize)>,
+}
+
+impl Target {
+    fn matches(&self, path: &str, line_start: usize, line_end: usize) -> bool {
+        self.path == path
+            && self
+                .lines
+                .is_none_or(|(start, end)| line_start <= end && start <= line_end)
+    }
+}
+
+/// Scores for one query or the mean over a configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Metrics {
+    pub recall: f64,
+    pub mrr: f64,
+    pub ndcg: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryReport {
+    pub query: String,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+    /// Expected locations not found in the top k
+    pub missed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigReport {
+    pub name: String,
+    pub top_k: usize,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+    pub queries: Vec<QueryReport>,
+}
+
+pub(crate) fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    path.trim_start_matches("./").to_string()
+}
+
+fn targets(expected: &[Expected]) -> Result<Vec<Target>> {
+    expected
+        .iter()
+        .map(|expected| match expected {
+            Expected::Path(path) => Ok(Target {
+                path: normalize_path(path),
+                lines: None,
+            }),
+            Expected::Span { path, lines } => Ok(Target {
+                path: normalize_path(path),
+                lines: lines.as_ref().map(LineRange::bounds).transpose()?,
+            }),
+        })
+        .collect()
+}
+
+/// Score ranked hits (`(path, line_start, line_end)`, best first) against the
+/// expected targets with binary relevance. A hit counts for at most one target
+/// and each target is credited once, so duplicate hits are not rewarded.
+fn score(hits: &[(String, usize, usize)], targets: &[Target], k: usize) -> (Metrics, Vec<usize>) {
+    let mut found = vec![false; targets.len()];
+    let mut first_relevant = None;
+    let mut dcg = 0.0;
+    for (rank, (path, start, end)) in hits.iter().take(k).enumerate() {
+        let Some(target) =
+            (0..targets.len()).find(|&i| !found[i] && targets[i].matches(path, *start, *end))
+        else {
+            continue;
+        };
+        found[target] = true;
+        first_relevant.get_or_insert(rank);
+        dcg += 1.0 / (rank as f64 + 2.0).log2();
+    }
+
+    let ideal: f64 = (0..targets.len().min(k))
+        .map(|rank| 1.0 / (rank as f64 + 2.0).log2())
+        .sum();
+    let hits_found = found.iter().filter(|f| **f).count();
+    let metrics = Metrics {
+        recall: if targets.is_empty() {
+            0.0
+        } else {
+            hits_found as f64 / targets.len() as f64
+        },
+        mrr: first_relevant.map_or(0.0, |rank| 1.0 / (rank as f64 + 1.0)),
+        ndcg: if ideal > 0.0 { dcg / ideal } else { 0.0 },
+    };
+    let missed = (0..targets.len()).filter(|&i| !found[i]).collect();
+    (metrics, missed)
+}
+
+fn mean(metrics: &[Metrics]) -> Metrics {
+    if metrics.is_empty() {
+        return Metrics::default();
+    }
+    let n = metrics.len() as f64;
+    Metrics {
+        recall: metrics.iter().map(|m| m.recall).sum::<f64>() / n,
+        mrr: metrics.iter().map(|m| m.mrr).sum::<f64>() / n,
+        ndcg: metrics.iter().map(|m| m.ndcg).sum::<f64>() / n,
+    }
+}
+
+/// A configuration's or rule's search mode, semantic when it names none.
+pub(crate) fn mode_or_semantic(mode: Option<&str>) -> Result<SearchMode> {
+    mode.map_or(Ok(SearchMode::Semantic), |mode| {
+        mode.parse().map_err(anyhow::Error::msg)
+    })
+}
+
+pub fn load(path: &Path) -> Result<EvalFile> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read eval file {}", path.display()))?;
+    let file: EvalFile = serde_yaml_ng::from_str(&content)
+        .with_context(|| format!("Invalid eval file {}", path.display()))?;
+    if file.queries.is_empty() {
+        bail!("Eval file {} has no queries", path.display());
+    }
+    Ok(file)
+}
+
+fn relative_hit(result: &SearchResult, root: &Path) -> (String, usize, usize) {
+    let path = result.file.strip_prefix(root).unwrap_or(&result.file);
+    (
+        normalize_path(&path.to_string_lossy()),
+        result.span.line_start,
+        result.span.line_end,
+    )
+}
+
+/// Run every configuration over every query.
+pub async fn run(
+    file: &EvalFile,
+    corpus: &Path,
+    file_options: &FileCollectionOptions,
+    status: &StatusReporter,
+) -> Result<Vec<ConfigReport>> {
+    let top_k = file.top_k.unwrap_or(DEFAULT_TOP_K);
+    let configurations = if file.configurations.is_empty() {
+        vec![EvalConfig {
+            name: "default".to_string(),
+            ..EvalConfig::default()
+        }]
+    } else {
+        file.configurations.clone()
+    };
+    let golden: Vec<(&str, Vec<Target>)> = file
+        .queries
+        .iter()
+        .map(|q| Ok((q.query.as_str(), targets(&q.expected)?)))
+        .collect::<Result<_>>()?;
+
+    // One throwaway index per model, shared by configurations that use it
+    let files = ck_index::collect_files(corpus, file_options)?;
+    let mut copies: HashMap<String, tempfile::TempDir> = HashMap::new();
+
+    let mut reports = Vec::new();
+    for config in &configurations {
+        let mode = mode_or_semantic(config.mode.as_deref())?;
+        let root: PathBuf = match &config.model {
+            None => corpus.to_path_buf(),
+            Some(model) => {
+                if !copies.contains_key(model) {
+                    let spinner = status.create_spinner(&format!("Building a {} index...", model));
+                    let copy = bench::copy_corpus(corpus, &files)?;
+                    bench::build_index(copy.path(), model, file_options).await?;
+                    status.finish_progress(spinner, &format!("{} index ready", model));
+                    copies.insert(model.clone(), copy);
+                }
+                copies[model].path().to_path_buf()
+            }
+        };
+
+        let spinner = status.create_spinner(&format!("Evaluating {}...", config.name));
+        // Searched as one batch, so reranked configurations share reranker batches
+        let searches: Vec<SearchOptions> = golden
+            .iter()
+            .map(|(query, _)| SearchOptions {
+                mode: mode.clone(),
+                query: query.to_string(),
+                path: root.clone(),
+                top_k: Some(top_k),
+                threshold: config.threshold,
+                rerank: config.rerank,
+                rerank_model: config.rerank_model.clone(),
+                rerank_scores: config.rerank_scores,
+                rerank_top: config.rerank_top,
+                embedding_model: config.model.clone(),
+                respect_gitignore: file_options.respect_gitignore,
+                use_ckignore: file_options.use_ckignore,
+                exclude_patterns: file_options.exclude_patterns.clone(),
+                ..SearchOptions::default()
+            })
+            .collect();
+        let outcomes = ck_engine::search_batch(&searches).await;
+        let mut queries = Vec::new();
+        for ((query, targets), outcome) in golden.iter().zip(outcomes) {
+            let results = outcome?.matches;
+            let hits: Vec<_> = results.iter().map(|r| relative_hit(r, &root)).collect();
+            let (metrics, missed) = score(&hits, targets, top_k);
+            queries.push(QueryReport {
+                query: query.to_string(),
+                metrics,
+                missed: missed.into_iter().map(|i| describe(&targets[i])).collect(),
+            });
+        }
+        status.finish_progress(spinner, &format!("{} done", config.name));
+
+        let per_query: Vec<Metrics> = queries.iter().map(|q| q.metrics).collect();
+        reports.push(ConfigReport {
+            name: config.name.clone(),
+            top_k,
+            metrics: mean(&per_query),
+            queries,
+        });
+    }
+    Ok(reports)
+}
+
+fn describe(target: &Target) -> String {
+    match target.lines {
+        Some((start, end)) if start == end => format!("{}:{}", target.path, start),
+        Some((start, end)) => format!("{}:{}-{}", target.path, start, end),
+        None => target.path.clone(),
+    }
+}
+
+/// Render the mean metrics of each configuration as an aligned table.
+pub fn format_table(reports: &[ConfigReport]) -> String {
+    let k = reports.first().map_or(DEFAULT_TOP_K, |r| r.top_k);
+    let width = reports
+        .iter()
+        .map(|r| r.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("configuration".len());
+    let recall = format!("recall@{}", k);
+    let ndcg = format!("nDCG@{}", k);
+    let mut lines = vec![format!(
+        "{:<width$}  {:>9}  {:>6}  {:>7}",
+        "configuration", recall, "MRR", ndcg
+    )];
+    for report in reports {
+        lines.push(format!(
+            "{:<width$}  {:>9.3}  {:>6.3}  {:>7.3}",
+            report.name, report.metrics.recall, report.metrics.mrr, report.metrics.ndcg
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(path: &str, start: usize, end: usize) -> (String, usize, usize) {
+        (path.to_string(), start, end)
+    }
+
+    #[test]
+    fn test_score_credits_each_target_once() {
+        let targets = targets(&[
+            Expected::Path("src/a.rs".to_string()),
+            Expected::Span {
+                path: "./src/b.rs".to_string(),
+                lines: Some(LineRange::Range("10-20".to_string())),
+            },
+        ])
+        .unwrap();
+        let hits = vec![
+            hit("src/c.rs", 1, 5),
+            hit("src/a.rs", 1, 5),
+            hit("src/a.rs", 8, 9),
+            hit("src/b.rs", 30, 40),
+            hit("src/b.rs", 18, 25),
+        ];
+
+        let (metrics, missed) = score(&hits, &targets, 10);
+        assert_eq!(metrics.recall, 1.0);
+        assert_eq!(metrics.mrr, 0.5);
+        let dcg = 1.0 / 3f64.log2() + 1.0 / 6f64.log2();
+        let ideal = 1.0 + 1.0 / 3f64.log2();
+        assert!((metrics.ndcg - dcg / ideal).abs() < 1e-9);
+        assert!(missed.is_empty());
+
+        // The span hit falls outside k = 3
+        let (metrics, missed) = score(&hits, &targets, 3);
+        assert_eq!(metrics.recall, 0.5);
+        assert_eq!(missed, vec![1]);
+        assert_eq!(describe(&targets[1]), "src/b.rs:10-20");
+    }
+
+    #[test]
+    fn test_load_eval_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("golden.yaml");
+        std::fs::write(
+            &path,
+            "top_k: 5\nconfigurations:\n  - name: lexical\n    mode: lex\nqueries:\n  - query: retry\n    expected:\n      - src/retry.rs\n      - path: src/http.rs\n        lines: 12\n",
+        )
+        .unwrap();
+        let file = load(&path).unwrap();
+        assert_eq!(file.top_k, Some(5));
+        assert_eq!(file.configurations[0].mode.as_deref(), Some("lex"));
+        let targets = targets(&file.queries[0].expected).unwrap();
+        assert_eq!(targets[1].lines, Some((12, 12)));
+
+        std::fs::write(
+            &path,
+            "queries:\n  - query: x\n    expected: [{path: a, lines: 9-3}]\n",
+        )
+        .unwrap();
+        let file = load(&path).unwrap();
+        assert!(super::targets(&file.queries[0].expected).is_err());
+    }
+}
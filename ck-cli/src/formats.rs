@@ -0,0 +1,374 @@
+//! Machine-readable report formats (`--format`): SARIF 2.1.0 for code-scanning
+//! uploads, reviewdog's rdjson for PR annotations, and CSV/TSV tables for
+//! spreadsheets and dataframes.
+
+use ck_core::{SearchMode, SearchOptions, SearchResult};
+use serde_json::{Value, json};
+use std::path::Path;
+
+const TOOL_NAME: &str = "ck";
+const TOOL_URL: &str = "https://github.com/BeaconBay/ck";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// SARIF 2.1.0 log (GitHub code scanning, IDE SARIF viewers)
+    Sarif,
+    /// reviewdog diagnostic format (`reviewdog -f=rdjson`)
+    #[value(alias = "reviewdog")]
+    Rdjson,
+    /// Comma-separated values with a header row (RFC 4180 quoting)
+    Csv,
+    /// Tab-separated values with a header row; tabs, newlines and backslashes
+    /// in fields are backslash-escaped
+    Tsv,
+}
+
+/// Column of a CSV/TSV table (`--columns`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Column {
+    Path,
+    Line,
+    EndLine,
+    #[value(name = "column")]
+    Col,
+    #[value(name = "end-column")]
+    EndCol,
+    Score,
+    Lang,
+    Symbol,
+    Snippet,
+}
+
+/// Columns used when `--columns` is not given.
+pub const DEFAULT_COLUMNS: &[Column] =
+    &[Column::Path, Column::Line, Column::Score, Column::Snippet];
+
+/// Render all results as one document in `format`. An empty result set still
+/// produces a valid document (or a header-only table) so CI steps can consume it
+/// unconditionally.
+pub fn render(
+    format: OutputFormat,
+    results: &[SearchResult],
+    options: &SearchOptions,
+    columns: &[Column],
+) -> String {
+    let columns = if columns.is_empty() {
+        DEFAULT_COLUMNS
+    } else {
+        columns
+    };
+    match format {
+        OutputFormat::Sarif => format!("{:#}", sarif(results, options)),
+        OutputFormat::Rdjson => format!("{:#}", rdjson(results, options)),
+        OutputFormat::Csv => table(results, columns, ',', csv_field),
+        OutputFormat::Tsv => table(results, columns, '\t', tsv_field),
+    }
+}
+
+fn table(
+    results: &[SearchResult],
+    columns: &[Column],
+    separator: char,
+    escape: fn(&str) -> String,
+) -> String {
+    let separator = separator.to_string();
+    let row = |fields: Vec<String>| {
+        fields
+            .iter()
+            .map(|field| escape(field))
+            .collect::<Vec<_>>()
+            .join(&separator)
+    };
+
+    let mut lines = vec![row(columns
+        .iter()
+        .map(|c| column_name(*c).to_string())
+        .collect())];
+    for result in results {
+        lines.push(row(columns.iter().map(|c| cell(result, *c)).collect()));
+    }
+    lines.join("\n")
+}
+
+fn column_name(column: Column) -> &'static str {
+    match column {
+        Column::Path => "path",
+        Column::Line => "line",
+        Column::EndLine => "end_line",
+        Column::Col => "column",
+        Column::EndCol => "end_column",
+        Column::Score => "score",
+        Column::Lang => "lang",
+        Column::Symbol => "symbol",
+        Column::Snippet => "snippet",
+    }
+}
+
+fn cell(result: &SearchResult, column: Column) -> String {
+    match column {
+        Column::Path => result.file.display().to_string(),
+        Column::Line => result.span.line_start.to_string(),
+        Column::EndLine => result.span.line_end.to_string(),
+        Column::Col => known_column(result.span.col_start),
+        Column::EndCol => known_column(result.span.col_end),
+        Column::Score => format!("{:.4}", result.score),
+        Column::Lang => result.lang.map(|l| l.to_string()).unwrap_or_default(),
+        Column::Symbol => result.symbol.clone().unwrap_or_default(),
+        Column::Snippet => result.preview.clone(),
+    }
+}
+
+/// Spans from indexes built before column tracking leave columns unknown (0).
+fn known_column(column: usize) -> String {
+    if column == 0 {
+        String::new()
+    } else {
+        column.to_string()
+    }
+}
+
+/// Quote fields containing separators, quotes or line breaks; embedded quotes are
+/// doubled, so multi-line snippets stay in one cell.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// TSV has no quoting, so control characters are escaped instead.
+fn tsv_field(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn rule_id(mode: &SearchMode) -> &'static str {
+    match mode {
+        SearchMode::Regex => "ck/regex",
+        SearchMode::Lexical => "ck/lexical",
+        SearchMode::Semantic => "ck/semantic",
+        SearchMode::Hybrid => "ck/hybrid",
+        SearchMode::Symbol => "ck/symbol",
+    }
+}
+
+fn message(result: &SearchResult, options: &SearchOptions) -> String {
+    match options.mode {
+        SearchMode::Regex => format!("Matches \"{}\"", options.query),
+        _ => format!("Matches \"{}\" (score {:.3})", options.query, result.score),
+    }
+}
+
+/// Forward-slash path relative to the working directory, as annotation tools
+/// resolve paths against the checkout root.
+fn artifact_path(path: &Path) -> String {
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path);
+    let relative = relative.strip_prefix(".").unwrap_or(relative);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+fn sarif(results: &[SearchResult], options: &SearchOptions) -> Value {
+    let rule = rule_id(&options.mode);
+    let location = |file: &Path, span: &ck_core::Span, snippet: Option<&str>| {
+        let mut region = json!({
+            "startLine": span.line_start,
+            "endLine": span.line_end.max(span.line_start),
+        });
+        if span.col_start > 0 && span.col_end > 0 {
+            region["startColumn"] = json!(span.col_start);
+            region["endColumn"] = json!(span.col_end);
+        }
+        if let Some(snippet) = snippet {
+            region["snippet"] = json!({ "text": snippet });
+        }
+        json!({
+            "physicalLocation": {
+                "artifactLocation": { "uri": artifact_path(file) },
+                "region": region,
+            }
+        })
+    };
+
+    let sarif_results: Vec<Value> = results
+        .iter()
+        .map(|result| {
+            let mut entry = json!({
+                "ruleId": rule,
+                "level": "warning",
+                "message": { "text": message(result, options) },
+                "locations": [location(&result.file, &result.span, Some(&result.preview))],
+                "properties": { "score": result.score },
+            });
+            if !result.duplicates.is_empty() {
+                entry["relatedLocations"] = result
+                    .duplicates
+                    .iter()
+                    .map(|d| location(&d.file, &d.span, None))
+                    .collect();
+            }
+            entry
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": TOOL_NAME,
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": TOOL_URL,
+                    "rules": [{
+                        "id": rule,
+                        "shortDescription": { "text": format!("ck search: {}", options.query) },
+                    }],
+                }
+            },
+            "results": sarif_results,
+        }]
+    })
+}
+
+fn rdjson(results: &[SearchResult], options: &SearchOptions) -> Value {
+    let rule = rule_id(&options.mode);
+    let location = |file: &Path, span: &ck_core::Span| {
+        let mut range = json!({
+            "start": { "line": span.line_start },
+            "end": { "line": span.line_end.max(span.line_start) },
+        });
+        if span.col_start > 0 && span.col_end > 0 {
+            range["start"]["column"] = json!(span.col_start);
+            range["end"]["column"] = json!(span.col_end);
+        }
+        json!({
+            "path": artifact_path(file),
+            "range": range,
+        })
+    };
+
+    let diagnostics: Vec<Value> = results
+        .iter()
+        .map(|result| {
+            let mut diagnostic = json!({
+                "message": message(result, options),
+                "location": location(&result.file, &result.span),
+                "severity": "WARNING",
+                "code": { "value": rule },
+            });
+            if !result.duplicates.is_empty() {
+                diagnostic["related_locations"] = result
+                    .duplicates
+                    .iter()
+                    .map(|d| {
+                        json!({
+                            "message": "Near-identical copy",
+                            "location": location(&d.file, &d.span),
+                        })
+                    })
+                    .collect();
+            }
+            diagnostic
+        })
+        .collect();
+
+    json!({
+        "source": { "name": TOOL_NAME, "url": TOOL_URL },
+        "severity": "WARNING",
+        "diagnostics": diagnostics,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result() -> SearchResult {
+        let span = ck_core::Span {
+            col_start: 5,
+            col_end: 2,
+            ..ck_core::Span::new_unchecked(0, 20, 3, 5)
+        };
+        SearchResult::new("./src/auth.rs", span, 0.8, "let password = \"hunter2\";")
+    }
+
+    #[test]
+    fn test_sarif_and_rdjson_locations() {
+        let options = SearchOptions {
+            mode: SearchMode::Semantic,
+            query: "hard-coded credentials".to_string(),
+            ..Default::default()
+        };
+
+        let sarif = sarif(&[result()], &options);
+        assert_eq!(sarif["version"], "2.1.0");
+        let entry = &sarif["runs"][0]["results"][0];
+        assert_eq!(entry["ruleId"], "ck/semantic");
+        let location = &entry["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/auth.rs");
+        assert_eq!(location["region"]["startLine"], 3);
+        assert_eq!(location["region"]["endLine"], 5);
+        assert_eq!(location["region"]["startColumn"], 5);
+        assert_eq!(location["region"]["endColumn"], 2);
+
+        let rdjson = rdjson(&[result()], &options);
+        let diagnostic = &rdjson["diagnostics"][0];
+        assert_eq!(diagnostic["location"]["path"], "src/auth.rs");
+        assert_eq!(diagnostic["location"]["range"]["start"]["line"], 3);
+        assert_eq!(diagnostic["location"]["range"]["start"]["column"], 5);
+        assert_eq!(diagnostic["location"]["range"]["end"]["column"], 2);
+        assert_eq!(
+            diagnostic["message"],
+            "Matches \"hard-coded credentials\" (score 0.800)"
+        );
+
+        // Columns of spans indexed before they were tracked are left out
+        let mut legacy = result();
+        legacy.span.col_start = 0;
+        legacy.span.col_end = 0;
+        let legacy = super::rdjson(&[legacy], &options);
+        assert!(
+            legacy["diagnostics"][0]["location"]["range"]["start"]
+                .get("column")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_csv_and_tsv_escape_multiline_snippets() {
+        let mut hit = result();
+        hit.preview = "fn connect() {\n    let s = \"a,b\";\t}".to_string();
+        let options = SearchOptions::default();
+
+        let csv = render(OutputFormat::Csv, &[hit.clone()], &options, &[]);
+        assert_eq!(
+            csv,
+            "path,line,score,snippet\n./src/auth.rs,3,0.8000,\"fn connect() {\n    let s = \"\"a,b\"\";\t}\""
+        );
+
+        let tsv = render(
+            OutputFormat::Tsv,
+            &[hit],
+            &options,
+            &[Column::Snippet, Column::EndLine, Column::Col],
+        );
+        assert_eq!(
+            tsv,
+            "snippet\tend_line\tcolumn\nfn connect() {\\n    let s = \"a,b\";\\t}\t5\t5"
+        );
+    }
+}
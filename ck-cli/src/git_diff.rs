@@ -0,0 +1,120 @@
+//! `--diff`: scope a search to the files a git revision range changed.
+
+use anyhow::{Context, Result, bail};
+use ck_core::IncludePattern;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git; --diff needs git on PATH")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Files under `dir`'s repository that `range` changed and that still exist,
+/// as absolute paths. `range` is anything `git diff` accepts: `base..head`,
+/// `base...head`, or a single revision compared with the working tree.
+pub fn changed_files(dir: &Path, range: &str) -> Result<Vec<PathBuf>> {
+    if range.is_empty() || range.starts_with('-') {
+        bail!("Invalid --diff range '{}'; expected BASE..HEAD", range);
+    }
+    let toplevel = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let toplevel = PathBuf::from(String::from_utf8_lossy(&toplevel).trim());
+
+    // Deleted files have nothing left to search
+    let names = git(
+        dir,
+        &["diff", "--name-only", "-z", "--diff-filter=d", range, "--"],
+    )?;
+    Ok(names
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| toplevel.join(String::from_utf8_lossy(name).as_ref()))
+        .filter(|path| path.is_file())
+        .collect())
+}
+
+/// Narrow the search to `changed` files, keeping only those inside the
+/// existing include patterns (the paths given on the command line).
+pub fn scope_to_changes(
+    changed: &[PathBuf],
+    include_patterns: &[IncludePattern],
+) -> Vec<IncludePattern> {
+    let changed = crate::path_utils::build_include_patterns(changed);
+    if include_patterns.is_empty() {
+        return changed;
+    }
+    changed
+        .into_iter()
+        .filter(|file| {
+            include_patterns.iter().any(|pattern| {
+                if pattern.is_dir {
+                    file.path.starts_with(&pattern.path)
+                } else {
+                    file.path == pattern.path
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=ck", "-c", "user.email=ck@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[test]
+    fn test_changed_files_in_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        run_git(root, &["init", "-q"]);
+        fs::create_dir(root.join("src")).unwrap();
+        fs::write(root.join("src/auth.rs"), "fn login() {}\n").unwrap();
+        fs::write(root.join("src/db.rs"), "fn connect() {}\n").unwrap();
+        fs::write(root.join("old.rs"), "fn gone() {}\n").unwrap();
+        run_git(root, &["add", "."]);
+        run_git(root, &["commit", "-qm", "base"]);
+        run_git(root, &["tag", "base"]);
+
+        fs::write(root.join("src/auth.rs"), "fn refresh_token() {}\n").unwrap();
+        fs::remove_file(root.join("old.rs")).unwrap();
+        run_git(root, &["commit", "-qam", "head"]);
+
+        let changed = changed_files(root, "base..HEAD").unwrap();
+        assert_eq!(changed.len(), 1);
+        assert!(changed[0].ends_with("src/auth.rs"));
+
+        // Command-line paths still narrow the scope
+        let src = crate::path_utils::build_include_patterns(&[root.join("src")]);
+        assert_eq!(scope_to_changes(&changed, &src).len(), 1);
+        let db = crate::path_utils::build_include_patterns(&[root.join("src/db.rs")]);
+        assert!(scope_to_changes(&changed, &db).is_empty());
+
+        assert!(changed_files(root, "--output=x").is_err());
+        assert!(changed_files(root, "nosuchrev..HEAD").is_err());
+    }
+}
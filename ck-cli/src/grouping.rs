@@ -0,0 +1,83 @@
+//! `--group-by file`: results collapsed to one entry per file. Each entry is
+//! the file's best-scoring hit, carrying the file's other hits so output can
+//! count them (collapsed) or list them beneath it (`--expand`).
+
+use ck_core::SearchResult;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    /// One entry per file, at its best hit
+    File,
+}
+
+/// A result and the hits grouped under it, best first.
+pub struct Grouped<'a> {
+    pub best: &'a SearchResult,
+    pub more: Vec<&'a SearchResult>,
+}
+
+/// `results` grouped by `group_by`, or each result on its own when `None`.
+/// Groups keep the order in which their files first appear.
+pub fn group(results: &[SearchResult], group_by: Option<GroupBy>) -> Vec<Grouped<'_>> {
+    let Some(GroupBy::File) = group_by else {
+        return results
+            .iter()
+            .map(|best| Grouped {
+                best,
+                more: Vec::new(),
+            })
+            .collect();
+    };
+
+    let mut slots: HashMap<&Path, usize> = HashMap::new();
+    let mut members: Vec<Vec<&SearchResult>> = Vec::new();
+    for result in results {
+        let slot = *slots.entry(result.file.as_path()).or_insert_with(|| {
+            members.push(Vec::new());
+            members.len() - 1
+        });
+        members[slot].push(result);
+    }
+    members
+        .into_iter()
+        .map(|mut hits| {
+            // Stable, so equal scores (regex hits) stay in line order
+            hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+            let best = hits.remove(0);
+            Grouped { best, more: hits }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::Span;
+    use std::path::PathBuf;
+
+    fn hit(file: &str, line: usize, score: f32) -> SearchResult {
+        SearchResult::new(file, Span::new_unchecked(0, 0, line, line), score, "")
+    }
+
+    #[test]
+    fn test_group_by_file_keeps_best_hit_first() {
+        let results = vec![
+            hit("src/auth.rs", 10, 0.9),
+            hit("src/session.rs", 4, 0.8),
+            hit("src/auth.rs", 40, 0.7),
+            hit("src/auth.rs", 88, 0.95),
+        ];
+
+        let groups = group(&results, Some(GroupBy::File));
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].best.span.line_start, 88);
+        let more: Vec<usize> = groups[0].more.iter().map(|r| r.span.line_start).collect();
+        assert_eq!(more, vec![10, 40]);
+        assert_eq!(groups[1].best.file, PathBuf::from("src/session.rs"));
+        assert!(groups[1].more.is_empty());
+
+        assert_eq!(group(&results, None).len(), 4);
+    }
+}
@@ -0,0 +1,137 @@
+//! Terminal colors for plain output: the `--color` choice and per-language
+//! syntax highlighting of snippets, with the matched region emphasized.
+
+use std::fmt::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+const THEME: &str = "base16-ocean.dark";
+
+/// When to color output (`--color`), as in grep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Turn colors on or off for stdout and stderr. `auto` keeps the terminal
+/// detection, which honors `NO_COLOR`, `CLICOLOR` and `CLICOLOR_FORCE`.
+pub fn apply(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Auto => return,
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    console::set_colors_enabled(enabled);
+    console::set_colors_enabled_stderr(enabled);
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> Option<&'static Theme> {
+    static THEME_SET: OnceLock<ThemeSet> = OnceLock::new();
+    let themes = &THEME_SET.get_or_init(ThemeSet::load_defaults).themes;
+    themes.get(THEME).or_else(|| themes.values().next())
+}
+
+/// Syntax for a snippet from `file`, by extension and then by its first line.
+/// Plain text has nothing to color.
+fn syntax_for(file: &Path, text: &str) -> Option<&'static SyntaxReference> {
+    let syntaxes = syntax_set();
+    file.extension()
+        .and_then(|ext| syntaxes.find_syntax_by_extension(&ext.to_string_lossy()))
+        .or_else(|| syntaxes.find_syntax_by_first_line(text))
+        .filter(|syntax| syntax.name != syntaxes.find_syntax_plain_text().name)
+}
+
+/// Color `text` as source code from `file`, emphasizing the byte ranges in
+/// `emphasis` with bold underline. Returns `None` when the language has no
+/// known syntax, so callers can fall back to plain match highlighting.
+pub fn syntax_highlight(text: &str, file: &Path, emphasis: &[Range<usize>]) -> Option<String> {
+    let syntaxes = syntax_set();
+    let mut highlighter = HighlightLines::new(syntax_for(file, text)?, theme()?);
+    let emphasized = |at: usize| emphasis.iter().any(|range| range.contains(&at));
+
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut offset = 0;
+    for line in LinesWithEndings::from(text) {
+        let Ok(regions) = highlighter.highlight_line(line, syntaxes) else {
+            return None;
+        };
+        for (style, region) in regions {
+            let fg = style.foreground;
+            let mut push = |piece: &str, bold: bool| {
+                if piece.is_empty() {
+                    return;
+                }
+                let bold = if bold { "1;4;" } else { "" };
+                let _ = write!(
+                    out,
+                    "\x1b[{}38;2;{};{};{}m{}\x1b[0m",
+                    bold, fg.r, fg.g, fg.b, piece
+                );
+            };
+            // Split the region wherever emphasis starts or stops
+            let mut start = 0;
+            let mut bold = emphasized(offset);
+            for (i, _) in region.char_indices() {
+                if emphasized(offset + i) != bold {
+                    push(&region[start..i], bold);
+                    start = i;
+                    bold = !bold;
+                }
+            }
+            push(&region[start..], bold);
+            offset += region.len();
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syntax_highlight_keeps_text_and_emphasizes_match() {
+        let text = "fn connect() {\n    retry(3);\n}\n";
+        let start = text.find("retry").unwrap();
+        let retry = Range {
+            start,
+            end: start + 5,
+        };
+        let colored = syntax_highlight(text, Path::new("src/net.rs"), &[retry]).unwrap();
+
+        assert!(colored.contains("\x1b[38;2;"));
+        assert!(colored.contains("\x1b[1;4;38;2;"));
+        assert_eq!(console::strip_ansi_codes(&colored), text);
+        let emphasized: String = colored
+            .split("\x1b[1;4;")
+            .skip(1)
+            .map(|piece| {
+                let text = &piece[piece.find('m').unwrap() + 1..];
+                text[..text.find('\x1b').unwrap()].to_string()
+            })
+            .collect();
+        assert_eq!(emphasized, "retry");
+    }
+
+    #[test]
+    fn test_syntax_highlight_needs_a_known_language() {
+        assert!(syntax_highlight("plain words", Path::new("notes.unknownext"), &[]).is_none());
+        assert!(syntax_highlight("plain words", Path::new("notes.txt"), &[]).is_none());
+        // A shebang identifies extensionless scripts
+        assert!(syntax_highlight("#!/bin/bash\necho hi\n", Path::new("deploy"), &[]).is_some());
+    }
+}
@@ -9,10 +9,25 @@ use owo_colors::{OwoColorize, Rgb};
 use regex::RegexBuilder;
 use std::path::{Path, PathBuf};
 
+mod ask;
+mod batch;
+mod bench;
+mod check;
+mod config;
+mod eval;
+mod formats;
+mod git_diff;
+mod grouping;
+mod highlight;
 mod mcp;
 mod mcp_server;
+mod models;
 mod path_utils;
+mod presets;
 mod progress;
+mod resources;
+mod telemetry;
+mod worktree;
 // TUI is now in its own crate: ck-tui
 
 use path_utils::{build_include_patterns, expand_glob_patterns};
@@ -47,6 +62,11 @@ QUICK START EXAMPLES:
     ck --hybrid "error" --limit 10    # Top 10 most relevant results (--limit is alias for --topk)
     ck --hybrid "bug" --threshold 0.02 # Only results with RRF score >= 0.02
     ck --sem "auth" --scores           # Show similarity scores in output
+    ck --hybrid "auth" --explain       # Show why each result ranked where it did
+
+  Symbol lookup (definitions recorded by tree-sitter chunking):
+    ck --sym parse_config              # Exact, fuzzy and camel/snake-insensitive name matches
+    ck --sym "load settings" src/      # Blends name match with semantic similarity of bodies
 
   Index management:
     ck --status .                     # Check index status
@@ -54,8 +74,29 @@ QUICK START EXAMPLES:
     ck --clean-orphans .               # Clean up orphaned files
     ck --clean .                       # Remove entire index
     ck --switch-model nomic-v1.5       # Clean + rebuild with a different embedding model
+    ck --multilingual --index .        # Multilingual default model + CJK-aware lexical search
+    ck --add-model acme --onnx acme/code-encoder --pooling mean  # Register your own ONNX model
+    ck --add-model nomic-q4 --gguf ~/models/nomic-embed-text-v1.5.Q4_K_M.gguf  # ...or GGUF
+    ck --add-model small3 --endpoint https://api.openai.com --endpoint-model text-embedding-3-small  # ...or an API
     ck --add file.rs                   # Add single file to index
+    ck --inspect --neighbors 3 file.rs # Indexed chunks and their nearest neighbors
     ck --index .                       # Optional: pre-build before CI runs
+    ck --index --dry-run .             # Files, chunks, tokens and time a build would take
+    ck --warm .                        # Load index and model so the next search starts hot
+    ck --export-bundle site/ck.bundle .  # Embeddings for in-browser search (ck-wasm)
+
+  Benchmarks (temporary copy of the corpus; your index is untouched):
+    ck --bench embed --bench-models bge-small,nomic-v1.5 src/   # chunks/sec per model
+    ck --bench search --bench-queries queries.txt .            # queries/sec, p50/p95 latency
+    ck --eval golden.yaml .            # recall@k, MRR, nDCG@k for each configuration in the file
+
+  Policy checks (CI, pre-commit):
+    ck --check rules.yaml .                      # Exit 1 if any rule fails
+    ck --check rules.yaml --diff origin/main... . # Only files changed on this branch
+
+  Diagnostics:
+    ck --log-level debug --sem "auth" .              # Verbose logs on stderr
+    ck --trace-chrome trace.json --sem "auth" .      # Span timings for Perfetto/chrome://tracing
 
   JSON output for tools/scripts:
     ck --json --sem "bug fix" src/    # Traditional JSON (single array)
@@ -65,19 +106,40 @@ QUICK START EXAMPLES:
     ck --jsonl "auth" --no-snippet    # Streaming, memory-efficient format
     ck --jsonl --sem "error" src/     # Perfect for LLM/agent consumption
     ck --jsonl --topk 5 --threshold 0.8 "func"  # High-confidence agent results
+    ck --stdin --sem src/ < queries.txt  # Batch: one JSON line per query, models loaded once
     # Why JSONL? Streaming, error-resilient, standard in AI pipelines
 
+  Reports for code-review tooling:
+    ck --sem "hard-coded credentials" --format sarif src/ > ck.sarif
+    ck --sem "TODO debt near auth" --format rdjson . | reviewdog -f=rdjson -reporter=github-pr-review
+
+  Tables for spreadsheets and dataframes:
+    ck --sem "retry logic" --format csv src/ > hits.csv
+    ck "TODO" --format tsv --columns path,line,snippet .
+
   Advanced grep features:
     ck -C 2 "error" src/              # Show 2 lines of context  
     ck -A 3 -B 1 "TODO"              # 3 lines after, 1 before
     ck -w "test" .                    # Match whole words only
     ck -F "log.Error()" .             # Fixed string (no regex)
 
+  Saved searches (presets in .ckconfig.json, shareable with your team):
+    ck --sem --rerank "auth flows" src/ --save-as auth-flows   # Run and save as a preset
+    ck @auth-flows                     # Re-run the saved search
+    ck @auth-flows --topk 3            # Explicit flags override the preset
+
   Model and embedding options:
     ck --index --model nomic-v1.5      # Index with higher-quality model (8k context)
     ck --index --model jina-code       # Index with code-specialized model
+    ck --index --model LARGE --query-model SMALL  # Embed queries with a distilled model
     ck --sem "auth" --rerank           # Enable reranking for better relevance
     ck --sem "login" --rerank-model bge # Use specific reranking model
+    ck --sem "auth" --topk 100 --rerank --rerank-top 20 --rerank-budget 300  # Bound rerank latency
+    ck --sem --hyde "where do we give up retrying uploads"  # Expand with an LLM draft (remote build)
+    ck --sem "upload retries" --top-files 20  # Rank files first, then chunks in the best 20
+    ck --sem "upload retries" --by-file    # One result per file
+    ck --ask "how are uploads retried?" src/  # Answer with citations, or print the context
+    ck --context-bundle "upload retries" --budget 8k src/  # Markdown excerpts for a prompt
 
   AI agent integration (MCP):
     ck --serve                         # Start MCP server for Claude/Cursor integration
@@ -89,12 +151,17 @@ QUICK START EXAMPLES:
   --lex     : BM25 lexical search (auto-indexed before it runs)  
   --sem     : Semantic/embedding search (auto-indexed, defaults: top 10, threshold ≥0.6)
   --hybrid  : Combines regex and semantic (shares the auto-indexing path)
+  --sym     : Symbol lookup over indexed definitions (auto-indexed)
 
 RESULT FILTERING:
   --topk, --limit N : Limit to top N results (default: 10 for semantic search)
+  --offset N        : Skip the first N results (page with --limit)
   --threshold SCORE : Filter by minimum score (default: 0.6 for semantic search)
                       (0.0-1.0 semantic/lexical, 0.01-0.05 hybrid RRF)
   --scores          : Show scores in output [0.950] file:line:match
+  --explain         : Show each result's score breakdown (dense, lexical, rerank,
+                      boosts, fusion path); adds an `explain` object to JSON/JSONL
+  --lang LANG[,LANG]: Only results in these languages (rust, python, ts, ...)
 
 The semantic search understands meaning - searching for "error handling" 
 will find try/catch blocks, error returns, exception handling, etc.
@@ -109,6 +176,12 @@ struct Cli {
     #[arg(short = 'n', long = "line-number", help = "Show line numbers")]
     line_numbers: bool,
 
+    #[arg(
+        long = "column",
+        help = "Show the column of each match after its line number (implies -n)"
+    )]
+    column: bool,
+
     #[arg(long = "no-filename", help = "Suppress filenames in output")]
     no_filenames: bool,
 
@@ -195,6 +268,12 @@ struct Cli {
     #[arg(long = "regex", help = "Regex search mode (default, grep-compatible)")]
     regex: bool,
 
+    #[arg(
+        long = "sym",
+        help = "Symbol lookup - find definitions by fuzzy name match blended with semantic similarity of their bodies"
+    )]
+    symbol: bool,
+
     #[arg(
         long = "topk",
         alias = "limit",
@@ -203,6 +282,14 @@ struct Cli {
     )]
     top_k: Option<usize>,
 
+    #[arg(
+        long = "offset",
+        value_name = "N",
+        default_value_t = 0,
+        help = "Skip the first N ranked results; with --topk/--limit, show results N+1 to N+limit (for paging)"
+    )]
+    offset: usize,
+
     #[arg(
         long = "threshold",
         value_name = "SCORE",
@@ -213,6 +300,12 @@ struct Cli {
     #[arg(long = "scores", help = "Show similarity scores in output")]
     show_scores: bool,
 
+    #[arg(
+        long = "explain",
+        help = "Show how each result's score was computed: dense, lexical and rerank scores, boosts, and the fusion path that selected it"
+    )]
+    explain: bool,
+
     #[arg(long = "json", help = "Output results as JSON for tools/scripts")]
     json: bool,
 
@@ -222,12 +315,75 @@ struct Cli {
     #[arg(long = "jsonl", help = "Output results as JSONL for agent workflows")]
     jsonl: bool,
 
+    #[arg(
+        long = "format",
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with_all = ["json", "json_v1", "jsonl", "files_with_matches", "files_without_matches"],
+        help = "Output results as a report: sarif (code scanning), rdjson (reviewdog), csv or tsv"
+    )]
+    format: Option<formats::OutputFormat>,
+
+    #[arg(
+        long = "columns",
+        value_enum,
+        value_name = "COL[,COL]",
+        value_delimiter = ',',
+        requires = "format",
+        help = "Columns for --format csv/tsv: path, line, end-line, score, lang, symbol, snippet [default: path,line,score,snippet]"
+    )]
+    columns: Vec<formats::Column>,
+
+    #[arg(
+        long = "group-by",
+        value_enum,
+        value_name = "KEY",
+        conflicts_with_all = ["files_with_matches", "files_without_matches"],
+        help = "Collapse results to one per file: its best hit, with a count of the file's other hits"
+    )]
+    group_by: Option<grouping::GroupBy>,
+
+    #[arg(
+        long = "expand",
+        requires = "group_by",
+        help = "With --group-by, list each file's other hits beneath its best one"
+    )]
+    expand: bool,
+
+    #[arg(
+        long = "diff",
+        value_name = "BASE..HEAD",
+        help = "Only search files changed in a git revision range (anything `git diff` accepts, e.g. main..HEAD or main...feature)"
+    )]
+    diff: Option<String>,
+
+    #[arg(
+        long = "color",
+        visible_alias = "colour",
+        value_enum,
+        value_name = "WHEN",
+        default_value = "auto",
+        help = "When to color output: auto (terminals, unless NO_COLOR is set), always or never. Snippets are syntax-highlighted with the match emphasized"
+    )]
+    color: highlight::ColorChoice,
+
     #[arg(long = "no-snippet", help = "Exclude code snippets from JSONL output")]
     no_snippet: bool,
 
     #[arg(long = "reindex", help = "Force index update before searching")]
     reindex: bool,
 
+    #[arg(
+        long = "read-only",
+        conflicts_with_all = [
+            "reindex", "index", "clean", "clean_orphans", "compact", "add", "switch_model",
+            "rebuild_shard", "tune_ann", "metric", "hnsw_m", "ef_construction", "ef_search",
+            "multilingual", "reembed", "index_history", "save_as"
+        ],
+        help = "Search existing indexes without writing anything: no index builds or updates, lock files or usage statistics (for indexes on network shares or owned by others; also with --serve and --tui)"
+    )]
+    read_only: bool,
+
     #[arg(
         long = "exclude",
         value_name = "PATTERN",
@@ -235,6 +391,14 @@ struct Cli {
     )]
     exclude: Vec<String>,
 
+    #[arg(
+        long = "lang",
+        value_name = "LANG",
+        value_delimiter = ',',
+        help = "Only report results in these languages, e.g. --lang rust,python (detected from extension or shebang)"
+    )]
+    lang: Vec<ck_core::Language>,
+
     #[arg(
         long = "no-default-excludes",
         help = "Disable default directory exclusions (like .git, node_modules, etc.)"
@@ -247,6 +411,12 @@ struct Cli {
     #[arg(long = "no-ckignore", help = "Don't respect .ckignore file")]
     no_ckignore: bool,
 
+    #[arg(
+        long = "follow-symlinks",
+        help = "Descend into symlinked directories and include symlinked files; cycles are skipped and a file reached through several links is taken once. By default symlinks are counted but not followed"
+    )]
+    follow_symlinks: bool,
+
     #[arg(
         long = "print-default-ckignore",
         help = "Print the default .ckignore content that ck generates and exit"
@@ -266,6 +436,27 @@ struct Cli {
     )]
     quiet: bool,
 
+    #[arg(
+        long = "log-level",
+        value_name = "LEVEL",
+        help = "Log filter for stderr: error, warn, info, debug, trace, or RUST_LOG-style directives such as 'warn,ck_embed=debug' (overrides RUST_LOG)"
+    )]
+    log_level: Option<String>,
+
+    #[arg(
+        long = "trace-chrome",
+        value_name = "FILE",
+        help = "Record chunking, tokenization, ONNX, vector search and rerank spans as Chrome trace JSON (open in Perfetto or chrome://tracing)"
+    )]
+    trace_chrome: Option<PathBuf>,
+
+    #[arg(
+        long = "trace-otlp",
+        value_name = "URL",
+        help = "Export the same spans to an OTLP/HTTP collector, e.g. http://localhost:4318/v1/traces (requires the `otlp` build feature)"
+    )]
+    trace_otlp: Option<String>,
+
     // Command flags (replacing subcommands)
     #[arg(
         long = "index",
@@ -273,12 +464,75 @@ struct Cli {
     )]
     index: bool,
 
+    #[arg(
+        long = "index-history",
+        help = "Index the project's commit messages, and the pull requests or issues printed by \"history\": {\"command\": ...} in .ckconfig.json, for --history searches. Re-run to pick up new ones"
+    )]
+    index_history: bool,
+
+    #[arg(
+        long = "history",
+        help = "Search the project's history indexed by --index-history (commit messages, pull requests, issues) instead of its files"
+    )]
+    history: bool,
+
+    #[arg(
+        long = "worktree",
+        value_name = "NAME",
+        help = "Run against another git worktree of this repository, named by its branch, directory name or path. Paths given are taken at the same place in that worktree, which is indexed on its own"
+    )]
+    worktree: Option<String>,
+
     #[arg(long = "clean", help = "Clean up search index")]
     clean: bool,
 
     #[arg(long = "clean-orphans", help = "Clean only orphaned index files")]
     clean_orphans: bool,
 
+    #[arg(
+        long = "compact",
+        help = "Reclaim index space: drop deleted files, stale temporary files and journals, and re-link duplicates"
+    )]
+    compact: bool,
+
+    #[arg(
+        long = "rebuild-shard",
+        value_name = "DIR",
+        requires = "index",
+        help = "With --index, re-embed only this top-level directory (shard) and keep the rest of the index; repeatable"
+    )]
+    rebuild_shard: Vec<String>,
+
+    #[arg(
+        long = "dry-run",
+        requires = "index",
+        conflicts_with = "rebuild_shard",
+        help = "With --index, walk, filter and chunk without embedding: show the files, chunks and tokens a build would process, its estimated time and the largest files"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long = "files-from",
+        value_name = "FILE",
+        requires = "index",
+        help = "With --index, index the files listed in FILE (`-` for stdin) instead of walking the directory, one path per line or NUL-separated (git ls-files -z, fd -0); ignore rules and excludes do not apply"
+    )]
+    files_from: Option<PathBuf>,
+
+    #[arg(
+        long = "max-cost",
+        value_name = "USD",
+        help = "Embed with a priced endpoint without asking when the estimated cost is at most USD; refuse when it is more. Applies to --index, --add, searches that refresh the index, and --serve reindexing"
+    )]
+    max_cost: Option<f64>,
+
+    #[arg(
+        short = 'y',
+        long = "yes",
+        help = "Embed with a priced endpoint without asking for confirmation, wherever ck indexes"
+    )]
+    yes: bool,
+
     #[arg(
         long = "switch-model",
         value_name = "NAME",
@@ -287,6 +541,7 @@ struct Cli {
             "index",
             "clean",
             "clean_orphans",
+            "compact",
             "status",
             "status_verbose",
             "add",
@@ -306,27 +561,189 @@ struct Cli {
     #[arg(long = "add", help = "Add a single file to the index")]
     add: bool,
 
+    #[arg(
+        long = "max-memory",
+        value_name = "SIZE",
+        value_parser = resources::parse_size,
+        help = "Approximate memory budget for embedding, such as 2G or 512M: turns off ONNX Runtime's memory arena and embeds smaller batches"
+    )]
+    max_memory: Option<u64>,
+
+    #[arg(
+        long = "nice",
+        help = "Index in the background: lower ck's CPU priority and use half the cores for embedding and chunking"
+    )]
+    nice: bool,
+
+    #[arg(
+        long = "keyfile",
+        value_name = "FILE",
+        help = "Key for encrypted indexes: a new index is created encrypted, and an encrypted one is opened with it [env: CK_INDEX_KEYFILE; or use a passphrase from CK_INDEX_PASSPHRASE]"
+    )]
+    keyfile: Option<PathBuf>,
+
     #[arg(long = "status", help = "Show index status and statistics")]
     status: bool,
 
     #[arg(long = "status-verbose", help = "Show detailed index statistics")]
     status_verbose: bool,
 
+    #[arg(
+        long = "stats",
+        help = "Show local usage statistics for the index: queries run, average latency, cache hit rate, index update time and most-searched paths (with --json, as JSON)"
+    )]
+    stats: bool,
+
     #[arg(long = "status-json", help = "Output index status as JSON")]
     status_json: bool,
 
     #[arg(
         long = "inspect",
-        help = "Show detailed metadata for a specific file (chunks, embeddings, tree-sitter parsing info)"
+        help = "Show how a file was chunked and embedded in the index: chunk boundaries, token counts, symbols and embedding times (falls back to a chunking preview for unindexed files)"
     )]
     inspect: bool,
 
+    #[arg(
+        long = "neighbors",
+        value_name = "N",
+        requires = "inspect",
+        help = "With --inspect, list the N indexed chunks from other files most similar to each chunk"
+    )]
+    neighbors: Option<usize>,
+
     #[arg(
         long = "dump-chunks",
         help = "Visualize chunk boundaries for a file using the same rendering as TUI chunk mode"
     )]
     dump_chunks: bool,
 
+    #[arg(
+        long = "export-bundle",
+        value_name = "FILE",
+        help = "Write the index's embeddings and chunk previews to FILE as a search bundle for in-browser semantic search (ck-wasm)"
+    )]
+    export_bundle: Option<PathBuf>,
+
+    #[arg(
+        long = "export-vectors",
+        value_name = "FILE",
+        help = "Write every embedded chunk's vector to FILE as a NumPy .npy array (float32, one row per chunk), and the rows' chunk ids, paths, lines and symbols to the same name with .jsonl"
+    )]
+    export_vectors: Option<PathBuf>,
+
+    #[arg(
+        long = "export-index",
+        value_name = "FILE",
+        help = "Write every chunk of the index (path, lines, kind, symbol, breadcrumb, language, metadata, text and vector) to FILE as one table: Parquet for .parquet, Arrow IPC for .arrow or .feather. For DuckDB, Polars or Lance; needs the `arrow` build feature"
+    )]
+    export_index: Option<PathBuf>,
+
+    #[arg(
+        long = "coverage",
+        help = "List the files of the project the index leaves out and why: ignored, in a submodule, binary, over a size or chunk limit, extension disabled, not indexed yet, or changed since (--json for every path)"
+    )]
+    coverage: bool,
+
+    #[arg(
+        long = "verify",
+        help = "Check every embedding of the index for failures: all zeros, NaN or infinite values, the wrong length, or the same as the model's output on empty input (--json for every chunk)"
+    )]
+    verify: bool,
+
+    #[arg(
+        long = "reembed",
+        requires = "verify",
+        help = "With --verify, embed the flagged chunks again and rewrite their files' index entries"
+    )]
+    reembed: bool,
+
+    #[arg(
+        long = "map",
+        help = "Print a map of what the codebase is about: chunk embeddings clustered into topics, each labelled by its most distinctive words, with the files it spans (--json for the full map)"
+    )]
+    map: bool,
+
+    #[arg(
+        long = "topics",
+        value_name = "N",
+        requires = "map",
+        help = "Number of topics for --map (default: about the square root of half the chunk count, at most 24)"
+    )]
+    topics: Option<usize>,
+
+    #[arg(
+        long = "llm-labels",
+        requires = "map",
+        help = "Have the LLM configured under \"llm\" in ~/.config/ck/config.json name each --map topic (needs the `remote` build feature)"
+    )]
+    llm_labels: bool,
+
+    #[arg(
+        long = "dupes",
+        help = "Report groups of near-duplicate code: chunks in different files whose embeddings are at least --min-score alike (cosine), closest first"
+    )]
+    dupes: bool,
+
+    #[arg(
+        long = "min-score",
+        value_name = "SCORE",
+        requires = "dupes",
+        help = "Cosine similarity from 0 to 1 at which --dupes reports two chunks as copies (default: 0.95)"
+    )]
+    min_score: Option<f32>,
+
+    #[arg(
+        long = "embedding",
+        value_name = "CHUNK_ID",
+        help = "Print the stored embedding of one chunk as JSON. CHUNK_ID is PATH:START-END as search results report a chunk's lines, or PATH:LINE for the chunk spanning LINE"
+    )]
+    embedding: Option<String>,
+
+    #[arg(
+        long = "bench",
+        value_enum,
+        value_name = "KIND",
+        help = "Benchmark embed, index or search throughput and p50/p95 latency on a corpus, one table row per model. Runs on a temporary copy; your index is untouched"
+    )]
+    bench: Option<bench::BenchKind>,
+
+    #[arg(
+        long = "bench-models",
+        value_name = "MODEL[,MODEL]",
+        value_delimiter = ',',
+        requires = "bench",
+        help = "Models to compare with --bench [default: --model, else the index's model]"
+    )]
+    bench_models: Vec<String>,
+
+    #[arg(
+        long = "bench-queries",
+        value_name = "FILE",
+        requires = "bench",
+        help = "Queries for --bench search, one per line [default: a built-in set]"
+    )]
+    bench_queries: Option<PathBuf>,
+
+    #[arg(
+        long = "eval",
+        value_name = "FILE",
+        help = "Score search quality against golden queries in a YAML file (query -> expected files/lines), reporting recall@k, MRR and nDCG@k per configuration"
+    )]
+    eval: Option<PathBuf>,
+
+    #[arg(
+        long = "check",
+        value_name = "RULES",
+        help = "Check policy rules from a YAML file (query, threshold, must-match/must-not-match) and exit 1 if any fails; combine with --diff for pre-commit hooks"
+    )]
+    check: Option<PathBuf>,
+
+    #[arg(
+        long = "validate-config",
+        help = "Check the project's .ckconfig.json and the user model registry, reporting unknown keys, type errors and invalid values with file, line and key, and exit 1 if there are any"
+    )]
+    validate_config: bool,
+
     // Model selection (index-time only)
     #[arg(
         long = "model",
@@ -335,6 +752,14 @@ struct Cli {
     )]
     model: Option<String>,
 
+    #[arg(
+        long = "query-model",
+        value_name = "MODEL",
+        requires = "index",
+        help = "Embed queries with a smaller model distilled into the index model's vector space; pairs it with the index after checking dimensions and alignment. Only used with --index."
+    )]
+    query_model: Option<String>,
+
     // Search-time enhancement options
     #[arg(
         long = "rerank",
@@ -349,47 +774,376 @@ struct Cli {
     )]
     rerank_model: Option<String>,
 
+    #[arg(
+        long = "rerank-scores",
+        value_name = "TRANSFORM",
+        help = "How reranker logits become scores: sigmoid (absolute 0-1, comparable across rerankers), min-max (0-1 within each result set) or raw (model logits) [default: the model's setting, sigmoid]"
+    )]
+    rerank_scores: Option<ck_core::ScoreTransform>,
+
+    #[arg(
+        long = "rerank-top",
+        value_name = "N",
+        help = "Rerank only the N best dense candidates; the rest follow in dense order (with --rerank)"
+    )]
+    rerank_top: Option<usize>,
+
+    #[arg(
+        long = "rerank-budget",
+        value_name = "MS",
+        help = "Stop reranking after MS milliseconds, including model load; candidates not yet reranked follow in dense order (with --rerank)"
+    )]
+    rerank_budget: Option<u64>,
+
+    #[arg(
+        long = "hyde",
+        help = "Have the LLM configured under \"llm\" in ~/.config/ck/config.json draft a hypothetical snippet answering the query, and search with the average of its vector and the query's; helps vague questions (semantic and hybrid; needs the `remote` build feature)"
+    )]
+    hyde: bool,
+
+    #[arg(
+        long = "instruct",
+        value_name = "TASK",
+        help = "Embed the query after this task description instead of the model's default query instruction, for instruction-tuned models (e.g. \"Represent this question for retrieving corresponding code\"); \"\" embeds the bare query (semantic, hybrid and symbol)"
+    )]
+    instruct: Option<String>,
+
+    #[arg(
+        long = "top-files",
+        value_name = "N",
+        value_parser = clap::value_parser!(usize),
+        help = "Two-stage semantic search: rank files by their mean chunk vector, then score chunks only in the best N files"
+    )]
+    top_files: Option<usize>,
+
+    #[arg(
+        long = "by-file",
+        help = "Semantic search at file granularity: one result per file, scored by the file's mean chunk vector and shown at its best chunk"
+    )]
+    by_file: bool,
+
+    #[arg(
+        long = "normalize-scores",
+        value_name = "SCHEME",
+        default_value = "rank",
+        help = "How hybrid search and searches over several indexes combine scores: rank (reciprocal rank fusion), min-max (each source rescaled to 0-1, averaged) or z-score (each source in standard deviations from its mean, averaged)"
+    )]
+    normalize_scores: ck_core::ScoreNormalization,
+
+    #[arg(
+        long = "prefer",
+        value_name = "FACET",
+        help = "In semantic and hybrid search, favor chunks made mostly of one kind of text: comments (comments, docstrings and prose, for \"why\" questions), code (for \"where is it implemented\") or strings (messages and literals). Moves scores by up to 15% either way; needs an index built with this version"
+    )]
+    prefer: Option<ck_core::Facet>,
+
+    #[arg(
+        long = "as-of",
+        value_name = "GENERATION|DATE",
+        help = "Search a kept snapshot of an earlier index generation instead of the current index: a generation number, or a UTC date (YYYY-MM-DD) or time (YYYY-MM-DDTHH:MM) picking the newest snapshot written by then. Snapshots are kept with \"snapshots\": N in .ckconfig.json; --status-verbose lists them"
+    )]
+    as_of: Option<String>,
+
+    #[arg(
+        long = "blame",
+        help = "Show who last changed each result's lines, and when, from git blame (text, --json and --jsonl output)"
+    )]
+    blame: bool,
+
+    #[arg(
+        long = "owner",
+        value_name = "NAME",
+        help = "Keep only results with lines written by NAME: any part of a git author name or email, ignoring case. Filters the ranked results, so combine with a larger --topk"
+    )]
+    owner: Option<String>,
+
+    #[arg(
+        long = "meta",
+        value_name = "KEY=VALUE",
+        value_parser = parse_meta_filter,
+        help = "Keep only results whose index-time metadata (.ckmeta.json files or the metadata_command) sets KEY to VALUE; repeatable, all must match"
+    )]
+    meta: Vec<(String, String)>,
+
+    #[arg(
+        long = "ask",
+        requires = "pattern",
+        conflicts_with_all = ["stdin", "save_as"],
+        help = "Treat the pattern as a question: retrieve the top matches (default 8, semantic unless another mode is given) and stream an answer with file:line citations from the LLM under \"llm\" in ~/.config/ck/config.json, or without one print the deduplicated excerpts as Markdown to paste into a chat"
+    )]
+    ask: bool,
+
+    #[arg(
+        long = "context-bundle",
+        requires = "pattern",
+        conflicts_with_all = ["stdin", "save_as", "ask"],
+        help = "Print the best matches for the pattern as one Markdown bundle for an LLM prompt: fenced, path-annotated excerpts, deduplicated, spread across files and cut to --budget tokens (semantic unless another mode is given; candidates from --topk, default 50)"
+    )]
+    context_bundle: bool,
+
+    #[arg(
+        long = "budget",
+        value_name = "TOKENS",
+        value_parser = ask::parse_token_budget,
+        requires = "context_bundle",
+        help = "Token budget of --context-bundle, such as 8000, 8k or 8000tokens [default: 8000]"
+    )]
+    budget: Option<usize>,
+
+    #[arg(
+        long = "stdin",
+        conflicts_with = "save_as",
+        help = "Read queries from stdin, one per line (plain text or a JSON object with per-query options), and write one JSON line of results per query; models stay loaded between queries. Positional arguments give the search path"
+    )]
+    stdin: bool,
+
+    #[arg(
+        long = "multilingual",
+        value_name = "BOOL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        help = "Turn multilingual mode on (or off with =false) in the project's .ckconfig.json: new indexes default to the multilingual-e5 model and lexical search splits CJK text into characters. Combine with --index to index right away"
+    )]
+    multilingual: Option<bool>,
+
+    #[arg(
+        long = "metric",
+        value_name = "METRIC",
+        help = "Set how new indexes compare embeddings (cosine, dot or euclidean) in the project's .ckconfig.json; dot and euclidean keep the model's unnormalized vectors. An existing index keeps its metric until it is rebuilt. Combine with --index to index right away"
+    )]
+    metric: Option<ck_models::SimilarityMetric>,
+
+    #[arg(
+        long = "hnsw-m",
+        value_name = "N",
+        help = "Set the HNSW graph's links per node (default 16) in the project's .ckconfig.json; higher improves recall at the cost of memory and build time. Takes effect at the next --index"
+    )]
+    hnsw_m: Option<usize>,
+
+    #[arg(
+        long = "ef-construction",
+        value_name = "N",
+        help = "Set the HNSW graph's build-time candidate list size (default 200) in the project's .ckconfig.json. Takes effect at the next --index"
+    )]
+    ef_construction: Option<usize>,
+
+    #[arg(
+        long = "ef-search",
+        value_name = "N",
+        help = "Set the HNSW graph's query-time candidate list size (default 64) in the project's .ckconfig.json; higher improves recall at the cost of latency"
+    )]
+    ef_search: Option<usize>,
+
+    #[arg(
+        long = "oversample",
+        value_name = "N",
+        help = "On indexes with an HNSW graph, fetch N candidates per requested result and re-score them exactly before reranking (default: hnsw.oversample in .ckconfig.json, 4); 1 keeps only the graph's own top-k"
+    )]
+    oversample: Option<usize>,
+
+    #[arg(
+        long = "tune-ann",
+        help = "Sweep the HNSW graph's ef_search over sampled chunks, measuring recall against exact search, and record the smallest value reaching --recall in .ckconfig.json"
+    )]
+    tune_ann: bool,
+
+    #[arg(
+        long = "warm",
+        help = "Read the index into the page cache, load its HNSW graph and prime the embedding model with a throwaway query, so the next search skips cold-start costs (run on shell start or editor attach)"
+    )]
+    warm: bool,
+
+    #[arg(
+        long = "recall",
+        value_name = "TARGET",
+        default_value_t = 0.95,
+        requires = "tune_ann",
+        help = "Recall@10 target for --tune-ann"
+    )]
+    recall: f32,
+
+    #[arg(
+        long = "add-model",
+        value_name = "ALIAS",
+        requires = "model_source",
+        help = "Register your own ONNX (--onnx), GGUF (--gguf) or OpenAI-compatible endpoint (--endpoint) embedding model under ALIAS in the user model registry, after checking it with a probe embedding; use it with --model ALIAS"
+    )]
+    add_model: Option<String>,
+
+    #[arg(
+        long = "onnx",
+        value_name = "SOURCE",
+        requires = "add_model",
+        group = "model_source",
+        help = "ONNX graph for --add-model: a local file or a Hugging Face reference owner/repo[/file] (default file onnx/model.onnx)"
+    )]
+    onnx: Option<String>,
+
+    #[arg(
+        long = "gguf",
+        value_name = "SOURCE",
+        requires = "add_model",
+        group = "model_source",
+        help = "GGUF model for --add-model, served by a llama.cpp llama-server that ck starts: a local file or owner/repo/file.gguf (needs the llama-server feature)"
+    )]
+    gguf: Option<String>,
+
+    #[arg(
+        long = "endpoint",
+        value_name = "URL",
+        requires = "add_model",
+        group = "model_source",
+        help = "OpenAI-compatible embeddings endpoint for --add-model, such as a hosted API or a llama-server already running; requests go to URL/v1/embeddings (needs the openai feature)"
+    )]
+    endpoint: Option<String>,
+
+    #[arg(
+        long = "endpoint-model",
+        value_name = "NAME",
+        requires = "endpoint",
+        help = "Model name --add-model requests from --endpoint [default: ALIAS]"
+    )]
+    endpoint_model: Option<String>,
+
+    #[arg(
+        long = "tokenizer",
+        value_name = "SOURCE",
+        requires = "add_model",
+        conflicts_with_all = ["gguf", "endpoint"],
+        help = "tokenizer.json for --add-model, local or owner/repo[/file] [default: beside a local model, or in the model's repo]"
+    )]
+    tokenizer: Option<String>,
+
+    #[arg(
+        long = "dims",
+        value_name = "auto|N",
+        default_value = "auto",
+        value_parser = models::parse_dims,
+        requires = "add_model",
+        help = "Embedding width for --add-model; auto measures it from the model"
+    )]
+    dims: usize,
+
+    #[arg(
+        long = "pooling",
+        value_name = "mean|cls",
+        requires = "add_model",
+        conflicts_with_all = ["gguf", "endpoint"],
+        help = "How --add-model pools token states into one vector, for graphs without a pooled output; default: as the model's 1_Pooling/config.json says, else mean"
+    )]
+    pooling: Option<ck_models::Pooling>,
+
+    #[arg(
+        long = "truncation",
+        value_name = "head|tail|middle",
+        default_value = "head",
+        requires = "add_model",
+        conflicts_with_all = ["gguf", "endpoint"],
+        help = "Which part of an input longer than the model's token limit --add-model embeds; middle keeps both ends"
+    )]
+    truncation: ck_models::Truncation,
+
+    #[arg(
+        long = "query-instruction",
+        value_name = "TASK",
+        requires = "add_model",
+        help = "Task description --add-model's model embeds before each query (not documents), for instruction-tuned models, e.g. \"search_query\"; --instruct replaces it per query"
+    )]
+    query_instruction: Option<String>,
+
+    #[arg(
+        long = "document-instruction",
+        value_name = "TASK",
+        requires = "add_model",
+        help = "Task description --add-model's model embeds before each indexed chunk, for instruction-tuned models, e.g. \"search_document\". Changing it re-embeds the index"
+    )]
+    document_instruction: Option<String>,
+
+    #[arg(
+        long = "save-as",
+        value_name = "NAME",
+        requires = "pattern",
+        help = "Save this search (query, mode, paths, filters, top-k, rerank settings) as a named preset in .ckconfig.json; run it later with `ck @NAME`"
+    )]
+    save_as: Option<String>,
+
     // MCP Server mode
     #[arg(
         long = "serve",
         help = "Start MCP server mode for AI agent integration",
         conflicts_with_all = [
-            "pattern", "files", "line_numbers", "no_filenames", "with_filenames",
+            "pattern", "files", "line_numbers", "column", "no_filenames", "with_filenames",
             "files_with_matches", "files_without_matches", "ignore_case", "word_regexp",
             "fixed_strings", "recursive", "context", "after_context", "before_context",
-            "semantic", "lexical", "hybrid", "regex", "top_k", "threshold", "show_scores",
-            "json", "json_v1", "jsonl", "no_snippet", "reindex", "exclude", "no_default_excludes",
-            "no_ignore", "full_section", "index", "clean", "clean_orphans", "switch_model",
-            "force", "add", "status", "status_verbose", "inspect", "dump_chunks", "model", "rerank", "rerank_model", "tui"
+            "semantic", "lexical", "hybrid", "regex", "symbol", "top_k", "threshold", "show_scores", "explain",
+            "json", "json_v1", "jsonl", "format", "columns", "group_by", "expand", "color", "diff", "no_snippet", "reindex", "exclude", "lang", "no_default_excludes",
+            "no_ignore", "full_section", "index", "clean", "clean_orphans", "compact", "switch_model",
+            "force", "add", "status", "status_verbose", "inspect", "dump_chunks", "export_bundle", "export_vectors", "export_index", "coverage", "verify", "reembed", "map", "topics", "llm_labels", "dupes", "min_score", "embedding", "bench", "eval", "check", "model", "query_model", "rerank", "rerank_model", "rerank_scores", "rerank_top", "rerank_budget", "hyde", "instruct", "top_files", "by_file", "normalize_scores", "prefer", "as_of", "history", "index_history", "worktree", "blame", "owner", "meta", "ask", "context_bundle", "budget", "oversample", "save_as", "stdin", "add_model", "multilingual", "metric", "hnsw_m", "ef_construction", "ef_search", "tune_ann", "warm", "tui"
         ]
     )]
     serve: bool,
 
+    #[arg(
+        long = "audit-log",
+        value_name = "FILE",
+        requires = "serve",
+        help = "With --serve, append a JSON line per tool call to FILE: time, client, user, tool, arguments and the files returned"
+    )]
+    audit_log: Option<PathBuf>,
+
+    #[arg(
+        long = "audit-log-max-size",
+        value_name = "SIZE",
+        requires = "audit_log",
+        value_parser = resources::parse_size,
+        help = "Rotate the audit log when it reaches SIZE, such as 50M (default 10M)"
+    )]
+    audit_log_max_size: Option<u64>,
+
+    #[arg(
+        long = "audit-log-keep",
+        value_name = "N",
+        requires = "audit_log",
+        help = "Rotated audit logs to keep as FILE.1 to FILE.N (default 5)"
+    )]
+    audit_log_keep: Option<usize>,
+
+    #[arg(
+        long = "namespaces",
+        value_name = "FILE",
+        requires = "serve",
+        help = "With --serve, host the indexes named in FILE (JSON: name -> path, api_key_env, quota); every tool call must then give a namespace"
+    )]
+    namespaces: Option<PathBuf>,
+
+    #[arg(
+        long = "query-batch-delay",
+        value_name = "MS",
+        requires = "serve",
+        help = "With --serve, let each query wait up to MS milliseconds for concurrent queries on the same model, and embed them in one batch; 0 embeds every query alone [default: 2]"
+    )]
+    query_batch_delay: Option<u64>,
+
     // TUI mode
     #[arg(
         long = "tui",
         help = "Interactive TUI mode - like fzf but semantic. Live search with arrow keys, Tab to switch modes, Enter to open in $EDITOR",
         conflicts_with_all = [
-            "line_numbers", "no_filenames", "with_filenames",
+            "line_numbers", "column", "no_filenames", "with_filenames",
             "files_with_matches", "files_without_matches", "ignore_case", "word_regexp",
             "fixed_strings", "recursive", "context", "after_context", "before_context",
-            "semantic", "lexical", "hybrid", "regex", "top_k", "threshold", "show_scores",
-            "json", "json_v1", "jsonl", "no_snippet", "reindex", "exclude", "no_default_excludes",
-            "no_ignore", "full_section", "index", "clean", "clean_orphans", "switch_model",
-            "force", "add", "status", "status_verbose", "inspect", "dump_chunks", "model", "rerank", "rerank_model", "serve"
+            "semantic", "lexical", "hybrid", "regex", "symbol", "top_k", "threshold", "show_scores", "explain",
+            "json", "json_v1", "jsonl", "format", "columns", "group_by", "expand", "color", "diff", "no_snippet", "reindex", "exclude", "lang", "no_default_excludes",
+            "no_ignore", "full_section", "index", "clean", "clean_orphans", "compact", "switch_model",
+            "force", "add", "status", "status_verbose", "inspect", "dump_chunks", "export_bundle", "export_vectors", "export_index", "coverage", "verify", "reembed", "map", "topics", "llm_labels", "dupes", "min_score", "embedding", "bench", "eval", "check", "model", "query_model", "rerank", "rerank_model", "rerank_scores", "rerank_top", "rerank_budget", "hyde", "instruct", "top_files", "by_file", "normalize_scores", "prefer", "as_of", "history", "index_history", "worktree", "blame", "owner", "meta", "ask", "context_bundle", "budget", "oversample", "save_as", "stdin", "add_model", "multilingual", "metric", "hnsw_m", "ef_construction", "ef_search", "tune_ann", "warm", "serve"
         ]
     )]
     tui: bool,
 }
 
 fn canonicalize_for_comparison(path: &Path) -> PathBuf {
-    if let Ok(canonical) = path.canonicalize() {
-        return canonical;
-    }
-
-    std::env::current_dir()
-        .map(|cwd| cwd.join(path))
-        .unwrap_or_else(|_| path.to_path_buf())
+    ck_core::paths::canonicalize(path)
 }
 
 fn find_search_root(include_patterns: &[IncludePattern]) -> PathBuf {
@@ -448,12 +1202,90 @@ fn find_search_root(include_patterns: &[IncludePattern]) -> PathBuf {
     }
 }
 
+/// `--meta KEY=VALUE`
+fn parse_meta_filter(value: &str) -> std::result::Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE, got '{}'", value)),
+    }
+}
+
+/// The paths given by `--files-from`, read from stdin for `-`.
+fn read_file_list(cli: &Cli) -> Result<Option<Vec<PathBuf>>> {
+    let Some(source) = &cli.files_from else {
+        return Ok(None);
+    };
+    let data = if source.as_os_str() == "-" {
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut data)?;
+        data
+    } else {
+        std::fs::read(source)
+            .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", source.display(), e))?
+    };
+    Ok(Some(ck_index::parse_file_list(&data)))
+}
+
 fn build_exclude_patterns(cli: &Cli) -> Vec<String> {
     // Use the centralized pattern builder from ck-core
     // Note: .ckignore handling is now done by WalkBuilder via the use_ckignore parameter
     ck_core::build_exclude_patterns(&cli.exclude, !cli.no_default_excludes)
 }
 
+/// Decide whether a build may spend money on a priced endpoint, wherever it
+/// runs: `--max-cost` sets a budget, `--yes` accepts any cost, and otherwise
+/// a terminal is asked, once for any cost up to what was accepted. Without
+/// one of the three, ck refuses rather than spend money unattended; the MCP
+/// server never asks, as its stdin carries the protocol.
+fn cost_approver(
+    cli: &Cli,
+) -> impl Fn(&ck_index::CostEstimate) -> std::result::Result<(), String> + Send + Sync + 'static {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    let (max_cost, yes, quiet, can_ask) = (cli.max_cost, cli.yes, cli.quiet, !cli.serve);
+    let accepted = std::sync::Mutex::new(None::<f64>);
+    move |estimate| {
+        let mut accepted = accepted.lock().unwrap_or_else(|e| e.into_inner());
+        if accepted.is_some_and(|accepted| estimate.cost <= accepted) {
+            return Ok(());
+        }
+        StatusReporter::new(quiet).info(&format!("💰 {}", estimate));
+        if let Some(max_cost) = max_cost {
+            if estimate.cost > max_cost {
+                return Err(format!(
+                    "Estimated cost ${:.2} exceeds --max-cost ${:.2}",
+                    estimate.cost, max_cost
+                ));
+            }
+            return Ok(());
+        }
+        if yes {
+            return Ok(());
+        }
+        if !can_ask || !std::io::stdin().is_terminal() {
+            return Err(format!(
+                "Indexing with {} costs money; pass --yes or --max-cost USD to proceed without a prompt",
+                estimate.model
+            ));
+        }
+        eprint!("Proceed? [y/N] ");
+        let mut answer = String::new();
+        let answered = std::io::stderr()
+            .flush()
+            .and_then(|_| std::io::stdin().lock().read_line(&mut answer));
+        match answered {
+            Ok(_) if matches!(answer.trim(), "y" | "Y" | "yes" | "YES" | "Yes") => {
+                *accepted = Some(estimate.cost);
+                Ok(())
+            }
+            Ok(_) => Err("Indexing cancelled".to_string()),
+            Err(e) => Err(format!("Could not read the answer: {}", e)),
+        }
+    }
+}
+
 async fn run_index_workflow(
     status: &StatusReporter,
     path: &Path,
@@ -497,7 +1329,21 @@ async fn run_index_workflow(
     }
 
     let exclude_patterns = build_exclude_patterns(cli);
+    let file_options = ck_core::FileCollectionOptions {
+        respect_gitignore: !cli.no_ignore,
+        use_ckignore: !cli.no_ckignore,
+        exclude_patterns: exclude_patterns.clone(),
+        follow_symlinks: cli.follow_symlinks,
+        // A rebuild keeps indexing the list an earlier build was given
+        files_from: read_file_list(cli)?.or_else(|| ck_index::saved_file_list(path)),
+    };
 
+    // A rebuild asks before the old index is gone, not after
+    if clean_first
+        && let Some(estimate) = ck_index::estimate_cost(path, &file_options, model_config)?
+    {
+        ck_index::approve_cost(&estimate)?;
+    }
     if clean_first {
         let index_dir = path.join(".ck");
         if index_dir.exists() {
@@ -590,11 +1436,6 @@ async fn run_index_workflow(
         (None, None, None, None)
     };
 
-    let file_options = ck_core::FileCollectionOptions {
-        respect_gitignore: !cli.no_ignore,
-        use_ckignore: !cli.no_ckignore,
-        exclude_patterns: exclude_patterns.clone(),
-    };
     let index_future = ck_index::smart_update_index_with_detailed_progress(
         path,
         false,
@@ -619,12 +1460,8 @@ async fn run_index_workflow(
             status.warn("Indexing interrupted by user");
             match (&mut index_future).await {
                 Ok(_) => return Ok(()),
-                Err(err) => {
-                    if err.to_string() == ck_index::INDEX_INTERRUPTED_MSG {
-                        return Ok(());
-                    }
-                    return Err(err);
-                }
+                Err(ck_index::IndexError::Interrupted) => return Ok(()),
+                Err(err) => return Err(err.into()),
             }
         }
     } {
@@ -636,11 +1473,14 @@ async fn run_index_workflow(
             if let Some(pb) = overall_progress_bar.take() {
                 pb.finish_and_clear();
             }
-            return Err(err);
+            return Err(err.into());
         }
     };
 
     let elapsed = start_time.elapsed();
+    if stats.files_indexed > 0 || stats.orphaned_files_removed > 0 {
+        ck_engine::record_index_run(path, elapsed);
+    }
     let files_per_sec = if elapsed.as_secs_f64() > 0.0 {
         stats.files_indexed as f64 / elapsed.as_secs_f64()
     } else {
@@ -677,6 +1517,53 @@ async fn run_index_workflow(
             stats.orphaned_files_removed
         ));
     }
+    let skipped = [
+        (stats.files_skipped_too_large, "over max_file_size"),
+        (
+            stats.files_skipped_by_extension,
+            "with a disabled extension",
+        ),
+        (
+            stats.files_skipped_too_many_chunks,
+            "over max_chunks_per_file",
+        ),
+    ];
+    for (count, rule) in skipped {
+        if count > 0 {
+            status.info(&format!("  ⏭️ {} files skipped {}", count, rule));
+        }
+    }
+    if stats.symlinks_skipped > 0 {
+        status.info(&format!(
+            "  🔗 {} symlinks not followed (use --follow-symlinks)",
+            stats.symlinks_skipped
+        ));
+    }
+    if !stats.encoding_warnings.is_empty() {
+        status.info(&format!(
+            "  🔤 {} files were not clean UTF-8:",
+            stats.encoding_warnings.len()
+        ));
+        for warning in &stats.encoding_warnings {
+            status.info(&format!("     {}", warning));
+        }
+    }
+    if stats.chunks_degenerate > 0 {
+        status.warn(&format!(
+            "{} chunks got degenerate embeddings; run 'ck --verify --reembed'",
+            stats.chunks_degenerate
+        ));
+    }
+    if let Some(spend) = std::fs::read(path.join(".ck").join("manifest.json"))
+        .ok()
+        .and_then(|data| serde_json::from_slice::<ck_index::IndexManifest>(&data).ok())
+        .and_then(|manifest| manifest.embedding_spend)
+    {
+        status.info(&format!(
+            "  💰 ${:.2} spent on {} tokens across all builds",
+            spend.cost, spend.tokens
+        ));
+    }
 
     if clean_first {
         status.info(&format!(
@@ -685,9 +1572,275 @@ async fn run_index_workflow(
         ));
     }
 
+    // Large indexes get an HNSW graph so searches need not scan every chunk
+    let graph_spinner = status.create_spinner("Updating HNSW graph...");
+    let graph = ck_engine::build_ann_index(path, false);
+    status.finish_progress(graph_spinner, "HNSW graph checked");
+    if let Some(nodes) = graph? {
+        status.info(&format!("  🕸️ HNSW graph covers {} chunks", nodes));
+    }
+    if let Some(files) = ck_engine::build_file_vectors(path, false)? {
+        status.info(&format!("  📁 File vectors cover {} files", files));
+    }
+
     Ok(())
 }
 
+/// The scheduled maintenance `ck --serve` runs on this index, for `--status`.
+fn print_schedule(status: &StatusReporter, tasks: &[mcp::schedule::TaskStatus]) {
+    if tasks.is_empty() {
+        return;
+    }
+    let format_time = |unix: i64| {
+        chrono::DateTime::from_timestamp(unix, 0)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_else(|| unix.to_string())
+    };
+    let now = chrono::Local::now().timestamp();
+    status.info("  Scheduled maintenance (run by ck --serve):");
+    for task in tasks {
+        status.info(&format!("    {} ({})", task.task, task.cron));
+        if let Some(error) = &task.error {
+            status.warn(&format!("      Invalid schedule: {}", error));
+            continue;
+        }
+        match &task.last_run {
+            Some(run) => status.info(&format!(
+                "      Last run: {}, {} in {:.1}s: {}",
+                format_time(run.started),
+                if run.ok { "ok" } else { "failed" },
+                run.duration_ms as f64 / 1000.0,
+                run.message
+            )),
+            None => status.info("      Last run: never"),
+        }
+        match task.next_due {
+            Some(due) if due <= now => status.info(&format!(
+                "      Due since {}, waiting for the server to go idle",
+                format_time(due)
+            )),
+            Some(due) => status.info(&format!("      Next run: {}", format_time(due))),
+            None => status.info("      Next run: none, the expression matches no date"),
+        }
+    }
+}
+
+/// Paths listed for each reason by `--coverage`; `--json` lists them all.
+const COVERAGE_PATHS_SHOWN: usize = 20;
+
+fn print_coverage(index_root: &Path, report: &ck_index::CoverageReport) {
+    use console::style;
+
+    let share = 100.0 * report.indexed as f64 / report.files.max(1) as f64;
+    println!(
+        "{}: {} of {} files indexed ({:.0}%)",
+        style(index_root.display()).cyan(),
+        style(report.indexed).green().bold(),
+        report.files,
+        share
+    );
+    let counts = report.counts();
+    for (reason, files) in &counts {
+        println!("  {:>6} {}", files, reason);
+    }
+    for reason in counts.keys() {
+        let paths: Vec<&ck_index::UncoveredPath> = report
+            .uncovered
+            .iter()
+            .filter(|uncovered| uncovered.reason == *reason)
+            .collect();
+        println!("\n{}", style(format!("{}:", reason)).bold());
+        for uncovered in paths.iter().take(COVERAGE_PATHS_SHOWN) {
+            if uncovered.path.ends_with('/') {
+                println!(
+                    "  {} {}",
+                    uncovered.path,
+                    style(format!("({} files)", uncovered.files)).dim()
+                );
+            } else {
+                println!("  {}", uncovered.path);
+            }
+        }
+        if paths.len() > COVERAGE_PATHS_SHOWN {
+            println!(
+                "  {}",
+                style(format!("... {} more", paths.len() - COVERAGE_PATHS_SHOWN)).dim()
+            );
+        }
+    }
+}
+
+/// Flagged chunks listed by `--verify`; `--json` lists them all.
+const VERIFY_CHUNKS_SHOWN: usize = 20;
+
+fn print_verify(report: &ck_index::VerifyReport) {
+    use console::style;
+
+    if report.flagged.is_empty() {
+        println!(
+            "{} {} embeddings look sound",
+            style("✓").green().bold(),
+            report.chunks
+        );
+    } else {
+        println!(
+            "{} of {} embeddings are degenerate:",
+            style(report.flagged.len()).red().bold(),
+            report.chunks
+        );
+        for chunk in report.flagged.iter().take(VERIFY_CHUNKS_SHOWN) {
+            println!(
+                "  {}:{}-{} {}",
+                chunk.path,
+                chunk.line_start,
+                chunk.line_end,
+                style(format!("({})", chunk.anomaly)).dim()
+            );
+        }
+        if report.flagged.len() > VERIFY_CHUNKS_SHOWN {
+            println!(
+                "  {}",
+                style(format!(
+                    "... {} more",
+                    report.flagged.len() - VERIFY_CHUNKS_SHOWN
+                ))
+                .dim()
+            );
+        }
+    }
+    if !report.padding_checked {
+        println!(
+            "{}",
+            style("The model could not be loaded, so chunks were not compared with its output on empty input").dim()
+        );
+    }
+    if report.files_reembedded > 0 {
+        println!(
+            "Re-embedded {} files; {} chunks are still degenerate",
+            report.files_reembedded, report.remaining
+        );
+    } else if !report.flagged.is_empty() {
+        println!("Run with --reembed to embed them again");
+    }
+}
+
+fn print_duplicates(report: &ck_engine::DuplicateReport) {
+    use console::style;
+
+    if report.groups.is_empty() {
+        println!(
+            "No duplicates among {} chunks at similarity {:.2} or above",
+            report.chunks, report.min_score
+        );
+        return;
+    }
+    let copies: usize = report.groups.iter().map(|group| group.chunks.len()).sum();
+    println!(
+        "{} groups of near-duplicate code ({} chunks of {}, similarity {:.2} or above)",
+        style(report.groups.len()).bold(),
+        copies,
+        report.chunks,
+        report.min_score
+    );
+    for (i, group) in report.groups.iter().enumerate() {
+        println!(
+            "\n{} {}",
+            style(format!("Group {}:", i + 1)).cyan().bold(),
+            style(format!(
+                "{} chunks, similarity {:.3}-{:.3}",
+                group.chunks.len(),
+                group.min_score,
+                group.max_score
+            ))
+            .dim()
+        );
+        let width = group.chunks.iter().map(|c| c.id.len()).max().unwrap_or(0);
+        for chunk in &group.chunks {
+            println!(
+                "  {:<width$}  {}{}",
+                chunk.id,
+                style(format!("{:.3}", chunk.score)).dim(),
+                chunk
+                    .symbol
+                    .as_deref()
+                    .map(|symbol| format!("  {}", symbol))
+                    .unwrap_or_default(),
+                width = width
+            );
+        }
+    }
+}
+
+/// Files listed under each topic of `--map`; `--json` lists them all.
+const MAP_FILES_SHOWN: usize = 5;
+
+fn print_topic_map(map: &ck_engine::TopicMap) {
+    use console::style;
+
+    println!(
+        "{} chunks in {} {}",
+        style(map.chunks).bold(),
+        style(map.topics.len()).bold(),
+        if map.topics.len() == 1 {
+            "topic"
+        } else {
+            "topics"
+        }
+    );
+    for (i, topic) in map.topics.iter().enumerate() {
+        let last_topic = i + 1 == map.topics.len();
+        let (branch, indent) = if last_topic {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let share = 100.0 * topic.chunks as f64 / map.chunks.max(1) as f64;
+        println!(
+            "{}{} {}",
+            branch,
+            style(&topic.label).cyan().bold(),
+            style(format!("({} chunks, {:.0}%)", topic.chunks, share)).dim()
+        );
+        let hidden = topic.files.len().saturating_sub(MAP_FILES_SHOWN);
+        let shown = &topic.files[..topic.files.len().min(MAP_FILES_SHOWN)];
+        for (j, file) in shown.iter().enumerate() {
+            let last_file = j + 1 == shown.len() && hidden == 0;
+            println!(
+                "{}{}{} {}",
+                indent,
+                if last_file {
+                    "└── "
+                } else {
+                    "├── "
+                },
+                file.path,
+                style(format!("({})", file.chunks)).dim()
+            );
+        }
+        if hidden > 0 {
+            println!(
+                "{}└── {}",
+                indent,
+                style(format!("... {} more files", hidden)).dim()
+            );
+        }
+    }
+}
+
+#[cfg(feature = "arrow")]
+fn export_index(path: &Path, output: &Path) -> Result<usize> {
+    ck_engine::export_index(path, output)
+}
+
+#[cfg(not(feature = "arrow"))]
+fn export_index(_path: &Path, _output: &Path) -> Result<usize> {
+    anyhow::bail!("--export-index requires ck to be built with the `arrow` feature")
+}
+
 async fn dump_file_chunks(file_path: &PathBuf) -> Result<()> {
     use std::path::Path;
 
@@ -737,7 +1890,12 @@ async fn dump_file_chunks(file_path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) -> Result<()> {
+async fn inspect_file_metadata(
+    file_path: &PathBuf,
+    neighbors: usize,
+    json: bool,
+    status: &StatusReporter,
+) -> Result<()> {
     use ck_embed::TokenEstimator;
     use console::style;
     use std::fs;
@@ -750,9 +1908,15 @@ async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) ->
         return Ok(());
     }
 
+    let inspection = ck_engine::inspect_file(path, neighbors);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&inspection?)?);
+        return Ok(());
+    }
+
     let metadata = fs::metadata(path)?;
-    let detected_lang = ck_core::Language::from_path(path);
-    let content = fs::read_to_string(path)?;
+    let content = ck_core::encoding::read_text(path)?.text;
+    let detected_lang = ck_core::Language::detect(path, &content);
     let total_tokens = TokenEstimator::estimate_tokens(&content);
 
     // Basic file info
@@ -768,9 +1932,25 @@ async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) ->
         println!("Language: {}", style(lang.to_string()).green());
     }
 
-    // Use model-aware chunking
+    match inspection {
+        Ok(inspection) => {
+            print_indexed_chunks(&inspection);
+            return Ok(());
+        }
+        Err(e) => println!("\n{}", style(e).yellow()),
+    }
+
+    // Not indexed: preview model-aware chunking
     let default_model = "nomic-embed-text-v1.5";
-    let chunks = ck_chunk::chunk_text_with_model(&content, detected_lang, Some(default_model))?;
+    let project = ck_models::ProjectConfig::for_path(path);
+    let chunks = ck_chunk::chunk_file(
+        path,
+        &content,
+        detected_lang,
+        Some(default_model),
+        project.chunk_strategy,
+        &ck_models::UserModels::for_user().chunkers,
+    )?;
 
     if chunks.is_empty() {
         println!("No chunks generated");
@@ -849,26 +2029,90 @@ async fn inspect_file_metadata(file_path: &PathBuf, status: &StatusReporter) ->
         println!("  ... and {} more chunks", chunks.len() - display_limit);
     }
 
-    // Index status
-    let parent_dir = path.parent().unwrap_or(Path::new("."));
-    if let Ok(stats) = ck_index::get_index_stats(parent_dir) {
-        if stats.total_files > 0 {
+    Ok(())
+}
+
+/// Render `--inspect` output for a file found in the index.
+fn print_indexed_chunks(inspection: &ck_engine::FileInspection) {
+    use console::style;
+
+    let format_time = |secs: u64| {
+        chrono::DateTime::from_timestamp(secs as i64, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_else(|| secs.to_string())
+    };
+
+    println!(
+        "Index: {} ({})",
+        style(inspection.index_root.display()).cyan(),
+        inspection.model
+    );
+    if !inspection.up_to_date {
+        println!(
+            "{}",
+            style("File changed since it was indexed; run 'ck --index' to refresh").yellow()
+        );
+    }
+
+    let tokens: Vec<usize> = inspection.chunks.iter().filter_map(|c| c.tokens).collect();
+    let embedded = inspection.chunks.iter().filter(|c| c.embedded).count();
+    print!(
+        "\nIndexed chunks: {} ({} embedded",
+        style(inspection.chunks.len()).green().bold(),
+        embedded
+    );
+    if let (Some(min), Some(max)) = (tokens.iter().min(), tokens.iter().max()) {
+        print!(
+            ", tokens: min={}, max={}, avg={}",
+            style(min).cyan(),
+            style(max).cyan(),
+            style(tokens.iter().sum::<usize>() / tokens.len()).cyan()
+        );
+    }
+    println!(")");
+
+    for (i, chunk) in inspection.chunks.iter().enumerate() {
+        let label = chunk
+            .breadcrumb
+            .as_deref()
+            .or(chunk.symbol.as_deref())
+            .unwrap_or("");
+        let embedding = match (chunk.embedded, chunk.embedded_at) {
+            (true, Some(at)) => format!("embedded {}", format_time(at)),
+            (true, None) => "embedded".to_string(),
+            (false, _) => "no embedding".to_string(),
+        };
+        println!(
+            "  {} {} L{}-{} | {} tokens | {} | {}",
+            style(format!("{:2}.", i + 1)).dim(),
+            style(chunk.chunk_type.as_deref().unwrap_or("text")).blue(),
+            chunk.span.line_start,
+            chunk.span.line_end,
+            style(chunk.tokens.map_or("?".to_string(), |t| t.to_string())).yellow(),
+            style(embedding).dim(),
+            label
+        );
+        if let Some(canonical) = &chunk.alias_of {
+            println!("       duplicate of {}", canonical.display());
+        }
+        for neighbor in &chunk.neighbors {
             println!(
-                "\nIndexed: {} files, {} chunks in directory",
-                style(stats.total_files).green(),
-                style(stats.total_chunks).green()
+                "       {} {}:{}-{} {}",
+                style(format!("{:.3}", neighbor.similarity)).green(),
+                neighbor.file.display(),
+                neighbor.span.line_start,
+                neighbor.span.line_end,
+                neighbor.symbol.as_deref().unwrap_or("")
             );
-        } else {
-            println!("\nNot indexed. Run 'ck --index .' to enable semantic search");
         }
     }
-
-    Ok(())
 }
 
 #[tokio::main]
 async fn main() {
-    if let Err(e) = run_main().await {
+    let result = run_main().await;
+    telemetry::shutdown();
+    if let Err(e) = result {
         eprintln!("DETAILED ERROR: {:#}", e);
         eprintln!("DEBUG: Error occurred in main");
 
@@ -891,9 +2135,32 @@ async fn run_main() -> Result<()> {
         return Ok(());
     }
 
-    // Handle MCP server mode first
+    // Handle MCP server mode first; it logs at info by default, the CLI at warn
+    telemetry::init(
+        &telemetry::TelemetryOptions {
+            log_level: cli.log_level.as_deref(),
+            chrome_trace: cli.trace_chrome.as_deref(),
+            otlp_endpoint: cli.trace_otlp.as_deref(),
+        },
+        if cli.serve {
+            tracing::Level::INFO
+        } else {
+            tracing::Level::WARN
+        },
+    )?;
+    resources::apply(cli.max_memory, cli.nice);
+    if let Some(keyfile) = &cli.keyfile {
+        ck_index::set_index_key(ck_index::IndexKey::Keyfile(keyfile.clone()));
+    }
+    highlight::apply(cli.color);
+    ck_engine::set_read_only(cli.read_only);
+    ck_engine::register_summarizer();
+    ck_index::set_cost_approver(cost_approver(&cli));
+    if !cli.validate_config {
+        config::check_at_startup(&config_path(&cli), &StatusReporter::new(cli.quiet))?;
+    }
     if cli.serve {
-        return run_mcp_server().await;
+        return run_mcp_server(&cli).await;
     }
 
     // Handle TUI mode
@@ -911,33 +2178,196 @@ async fn run_main() -> Result<()> {
     run_cli_mode(cli).await
 }
 
-async fn run_mcp_server() -> Result<()> {
-    // Configure service-safe logging for MCP mode (no stdout pollution)
-    tracing_subscriber::fmt()
-        .with_writer(std::io::stderr)
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into()),
-        )
-        .init();
+/// The path whose project config applies: the first path given, else the
+/// pattern when it names one (as with `ck --index DIR`), else the current
+/// directory.
+fn config_path(cli: &Cli) -> PathBuf {
+    cli.files
+        .first()
+        .cloned()
+        .or_else(|| {
+            cli.pattern
+                .as_ref()
+                .map(PathBuf::from)
+                .filter(|path| path.exists())
+        })
+        .unwrap_or_else(|| PathBuf::from("."))
+}
 
-    let cwd = std::env::current_dir()?;
-    let server = mcp_server::CkMcpServer::new(cwd)?;
-    server.run().await
+/// The one path a command works on: the first path given, else the
+/// pattern (as with `ck --stats DIR`), else the current directory.
+fn target_path(cli: &Cli) -> PathBuf {
+    cli.files
+        .first()
+        .cloned()
+        .or_else(|| cli.pattern.as_ref().map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Record the `multilingual` toggle in the project config governing `path`, and
+/// point out when an existing index was built with another default model.
+fn set_multilingual(path: &Path, enabled: bool, status: &StatusReporter) -> Result<()> {
+    let config_path = ck_models::ProjectConfig::locate(path);
+    let mut config = ck_models::ProjectConfig::load(&config_path)?;
+    config.multilingual = enabled;
+    config.save(&config_path)?;
+    status.success(&format!(
+        "Multilingual mode {} in {}",
+        if enabled { "on" } else { "off" },
+        config_path.display()
+    ));
+
+    let registry = ck_models::ModelRegistry::for_project(path);
+    let (default_alias, default_config) = registry
+        .resolve(None)
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    let root = config_path.parent().unwrap_or(path);
+    if let Ok(data) = std::fs::read(root.join(".ck").join("manifest.json"))
+        && let Ok(manifest) = serde_json::from_slice::<ck_index::IndexManifest>(&data)
+        && let Some(existing) = manifest.embedding_model
+        && existing != default_config.name
+    {
+        status.info(&format!(
+            "The index was built with {}; run `ck --switch-model {} {}` to re-embed it",
+            existing,
+            default_alias,
+            root.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Record the metric for new indexes in the project config governing `path`, and
+/// point out when an existing index compares embeddings another way.
+fn set_metric(
+    path: &Path,
+    metric: ck_models::SimilarityMetric,
+    status: &StatusReporter,
+) -> Result<()> {
+    let config_path = ck_models::ProjectConfig::locate(path);
+    let mut config = ck_models::ProjectConfig::load(&config_path)?;
+    config.metric = metric;
+    config.save(&config_path)?;
+    status.success(&format!(
+        "Metric {} for new indexes in {}",
+        metric,
+        config_path.display()
+    ));
+
+    let root = config_path.parent().unwrap_or(path);
+    if let Ok(data) = std::fs::read(root.join(".ck").join("manifest.json"))
+        && let Ok(manifest) = serde_json::from_slice::<ck_index::IndexManifest>(&data)
+        && manifest.embedding_model.is_some()
+        && manifest.metric != metric
+    {
+        status.info(&format!(
+            "The index uses the {} metric; run `ck --clean {}` and re-index to switch",
+            manifest.metric,
+            root.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Record any HNSW parameters given on the command line in the project config
+/// governing `path`.
+fn set_hnsw_params(path: &Path, cli: &Cli, status: &StatusReporter) -> Result<()> {
+    let config_path = ck_models::ProjectConfig::locate(path);
+    let mut config = ck_models::ProjectConfig::load(&config_path)?;
+    if let Some(m) = cli.hnsw_m {
+        config.hnsw.m = m;
+    }
+    if let Some(ef_construction) = cli.ef_construction {
+        config.hnsw.ef_construction = ef_construction;
+    }
+    if let Some(ef_search) = cli.ef_search {
+        config.hnsw.ef_search = ef_search;
+    }
+    config.save(&config_path)?;
+    status.success(&format!(
+        "HNSW parameters M={}, ef_construction={}, ef_search={} in {}",
+        config.hnsw.m,
+        config.hnsw.ef_construction,
+        config.hnsw.ef_search,
+        config_path.display()
+    ));
+    if cli.hnsw_m.is_some() || cli.ef_construction.is_some() {
+        status.info("The HNSW graph is rebuilt with these parameters at the next `ck --index`");
+    }
+    Ok(())
 }
 
-async fn run_cli_mode(cli: Cli) -> Result<()> {
-    // Regular CLI mode logging
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::WARN.into()),
+async fn run_mcp_server(cli: &Cli) -> Result<()> {
+    // Logging goes to stderr (see `telemetry::init`), so stdout stays protocol-only
+    let cwd = std::env::current_dir()?;
+    ck_engine::set_query_batch_delay(
+        cli.query_batch_delay
+            .map(std::time::Duration::from_millis)
+            .unwrap_or(ck_engine::DEFAULT_QUERY_BATCH_DELAY),
+    );
+    let mut server = mcp_server::CkMcpServer::new(cwd)?;
+    if let Some(path) = &cli.audit_log {
+        let log = mcp::audit::AuditLog::open(
+            path,
+            cli.audit_log_max_size
+                .unwrap_or(mcp::audit::DEFAULT_MAX_SIZE),
+            cli.audit_log_keep.unwrap_or(mcp::audit::DEFAULT_KEEP),
         )
-        .init();
+        .map_err(|e| anyhow::anyhow!("Cannot open audit log {}: {}", path.display(), e))?;
+        server = server.with_audit_log(log);
+    }
+    if let Some(path) = &cli.namespaces {
+        let namespaces = mcp::namespaces::Namespaces::load(path).map_err(|e| {
+            anyhow::anyhow!("Cannot load namespaces from {}: {}", path.display(), e)
+        })?;
+        tracing::info!(
+            "Serving namespaces: {}",
+            namespaces.names().collect::<Vec<_>>().join(", ")
+        );
+        server = server.with_namespaces(namespaces);
+    }
+    server.run().await
+}
 
+async fn run_cli_mode(mut cli: Cli) -> Result<()> {
     let status = StatusReporter::new(cli.quiet);
 
+    if let Some(name) = &cli.worktree {
+        cli.files = worktree::rebase_paths(&cli.files, name)?;
+    }
+
     // Handle command flags first (these take precedence over search)
+    if cli.validate_config {
+        if !config::validate(&config_path(&cli), &status)? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(enabled) = cli.multilingual {
+        let path = target_path(&cli);
+        set_multilingual(&path, enabled, &status)?;
+        if !cli.index {
+            return Ok(());
+        }
+    }
+
+    if let Some(metric) = cli.metric {
+        let path = target_path(&cli);
+        set_metric(&path, metric, &status)?;
+        if !cli.index {
+            return Ok(());
+        }
+    }
+
+    if cli.hnsw_m.is_some() || cli.ef_construction.is_some() || cli.ef_search.is_some() {
+        let path = target_path(&cli);
+        set_hnsw_params(&path, &cli, &status)?;
+        if !cli.index && !cli.tune_ann {
+            return Ok(());
+        }
+    }
+
     if let Some(model_name) = cli.switch_model.as_deref() {
         let path = cli
             .files
@@ -945,7 +2375,7 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             .cloned()
             .unwrap_or_else(|| PathBuf::from("."));
 
-        let registry = ck_models::ModelRegistry::default();
+        let registry = ck_models::ModelRegistry::with_user_models();
         let (model_alias, model_config) = registry
             .resolve(Some(model_name))
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
@@ -996,6 +2426,25 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             true,
         )
         .await?;
+
+        // A project pinned to another model would refuse the rebuilt index
+        if let Some(config_path) = ck_models::ProjectConfig::find(&path) {
+            let mut config = ck_models::ProjectConfig::load(&config_path)?;
+            if let Some(pinned) = config.model.as_deref()
+                && registry
+                    .resolve(Some(pinned))
+                    .ok()
+                    .is_none_or(|(_, pinned)| pinned.name != model_config.name)
+            {
+                config.model = Some(model_alias.clone());
+                config.save(&config_path)?;
+                status.info(&format!(
+                    "Set model to '{}' in {}",
+                    model_alias,
+                    config_path.display()
+                ));
+            }
+        }
         return Ok(());
     }
 
@@ -1006,11 +2455,41 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             .cloned()
             .unwrap_or_else(|| PathBuf::from("."));
 
-        let registry = ck_models::ModelRegistry::default();
+        if cli.model.is_none() {
+            ck_index::check_model_drift(&path)?;
+        }
+        let registry = ck_models::ModelRegistry::for_project(&path);
         let (model_alias, model_config) = registry
             .resolve(cli.model.as_deref())
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
 
+        if cli.dry_run {
+            status.section_header("Indexing Dry Run");
+            let file_options = ck_core::FileCollectionOptions {
+                respect_gitignore: !cli.no_ignore,
+                use_ckignore: !cli.no_ckignore,
+                exclude_patterns: build_exclude_patterns(&cli),
+                follow_symlinks: cli.follow_symlinks,
+                files_from: read_file_list(&cli)?,
+            };
+            let spinner = status.create_spinner("Chunking files and timing the model...");
+            let plan = ck_index::plan_index(&path, &file_options, &model_config)?;
+            status.finish_progress(spinner, "Plan ready");
+            print!("{}", plan);
+            return Ok(());
+        }
+
+        // Dropped shards are missing from the manifest, so the update below
+        // re-indexes them while every other shard stays up to date
+        for shard in &cli.rebuild_shard {
+            let shard = shard.trim_end_matches('/');
+            let dropped = ck_index::drop_shard(&path, shard)?;
+            status.info(&format!(
+                "Rebuilding shard {} ({} files dropped)",
+                shard, dropped
+            ));
+        }
+
         run_index_workflow(
             &status,
             &path,
@@ -1021,6 +2500,61 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             false,
         )
         .await?;
+
+        if let Some(query_model) = cli.query_model.as_deref() {
+            let spinner = status.create_spinner("Checking query model alignment...");
+            let pairing = ck_engine::pair_query_model(&path, query_model)?;
+            status.finish_progress(spinner, "Query model paired");
+            status.success(&format!(
+                "Queries will be embedded with {} (alignment {:.3} with {})",
+                pairing.query_model.name, pairing.alignment, pairing.index_model.name
+            ));
+        }
+        return Ok(());
+    }
+
+    if cli.index_history {
+        let path = target_path(&cli);
+        let registry = ck_models::ModelRegistry::for_project(&path);
+        let (model_alias, _) = registry
+            .resolve(cli.model.as_deref())
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+        status.section_header("Indexing History");
+        let spinner = status.create_spinner("Reading commits and documents...");
+        let history = ck_index::write_history(&path)?;
+        status.finish_progress(
+            spinner,
+            &format!(
+                "{} commits, {} documents",
+                history.commits, history.documents
+            ),
+        );
+
+        let spinner = status.create_spinner("Embedding history...");
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: false,
+            use_ckignore: false,
+            exclude_patterns: Vec::new(),
+            follow_symlinks: false,
+            files_from: Some(history.files),
+        };
+        let stats = ck_index::smart_update_index_with_detailed_progress(
+            &ck_index::history_root(&path),
+            false,
+            None,
+            None,
+            true,
+            &file_options,
+            Some(model_alias.as_str()),
+        )
+        .await?;
+        status.finish_progress(spinner, "History indexed");
+        status.success(&format!(
+            "Indexed {} new history files, {} up to date; search them with --history",
+            stats.files_added + stats.files_modified,
+            stats.files_up_to_date
+        ));
         return Ok(());
     }
 
@@ -1045,6 +2579,8 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                 respect_gitignore: !cli.no_ignore,
                 use_ckignore: !cli.no_ckignore,
                 exclude_patterns: exclude_patterns.clone(),
+                follow_symlinks: cli.follow_symlinks,
+                files_from: None,
             };
             let cleanup_stats = ck_index::cleanup_index(&clean_path, &file_options)?;
             status.finish_progress(cleanup_spinner, "Cleanup complete");
@@ -1075,6 +2611,113 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         return Ok(());
     }
 
+    if cli.compact {
+        let compact_path = cli
+            .files
+            .first()
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from("."));
+        status.section_header("Compacting Index");
+        status.info(&format!("Compacting index for {}", compact_path.display()));
+
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: !cli.no_ignore,
+            use_ckignore: !cli.no_ckignore,
+            exclude_patterns: build_exclude_patterns(&cli),
+            follow_symlinks: cli.follow_symlinks,
+            files_from: None,
+        };
+        let compact_spinner = status.create_spinner("Compacting...");
+        let progress_callback: Option<ck_index::ProgressCallback> =
+            compact_spinner.as_ref().map(|spinner| {
+                let spinner = spinner.clone();
+                Box::new(move |message: &str| spinner.set_message(message.to_string()))
+                    as ck_index::ProgressCallback
+            });
+        let stats = ck_index::compact_index(&compact_path, &file_options, progress_callback)?;
+        status.finish_progress(compact_spinner, "Compaction complete");
+
+        let reclaimed = stats.bytes_before.saturating_sub(stats.bytes_after);
+        status.info(&format!(
+            "Removed {} deleted entries, {} sidecars and {} stale temporary files; re-linked {} sidecars",
+            stats.entries_removed,
+            stats.sidecars_removed,
+            stats.temp_files_removed,
+            stats.sidecars_relinked
+        ));
+        status.success(&format!(
+            "Index size {:.1} MB -> {:.1} MB ({:.1} MB reclaimed)",
+            stats.bytes_before as f64 / (1024.0 * 1024.0),
+            stats.bytes_after as f64 / (1024.0 * 1024.0),
+            reclaimed as f64 / (1024.0 * 1024.0)
+        ));
+        return Ok(());
+    }
+
+    if cli.tune_ann {
+        let tune_path = target_path(&cli);
+        status.section_header("Tuning HNSW Graph");
+        let tune_spinner = status.create_spinner("Building graph and sampling ground truth...");
+        let report = ck_engine::tune_ann(&tune_path, cli.recall);
+        status.finish_progress(tune_spinner, "Sweep complete");
+        let report = report?;
+
+        status.info(&format!(
+            "Sampled {} of {} chunks; recall@10 against exact search:",
+            report.queries, report.chunks
+        ));
+        println!("{:>10}  {:>8}  {:>10}", "ef_search", "recall", "µs/query");
+        for point in &report.sweep {
+            println!(
+                "{:>10}  {:>8.3}  {:>10.0}",
+                point.ef_search, point.recall, point.micros_per_query
+            );
+        }
+        if report.reached_target {
+            status.success(&format!(
+                "ef_search={} reaches recall {:.2}; saved to {}",
+                report.recommended,
+                cli.recall,
+                report.config_path.display()
+            ));
+        } else {
+            status.warn(&format!(
+                "No ef_search reached recall {:.2}; saved the largest tried ({}) to {}. Consider a larger --hnsw-m",
+                cli.recall,
+                report.recommended,
+                report.config_path.display()
+            ));
+        }
+        return Ok(());
+    }
+
+    if cli.warm {
+        let warm_path = target_path(&cli);
+        let warm_spinner = status.create_spinner("Warming index...");
+        let report = ck_engine::warm_index(&warm_path).await;
+        status.finish_progress(warm_spinner, "Warm-up complete");
+        let report = report?;
+
+        status.info(&format!(
+            "Read {} sidecars ({:.1} MB) in {:.0?}",
+            report.sidecars,
+            report.bytes as f64 / (1024.0 * 1024.0),
+            report.read_time
+        ));
+        match &report.model {
+            Some(model) => status.info(&format!("Primed {} in {:.0?}", model, report.model_time)),
+            None => status.info("Index has no embeddings; no model to prime"),
+        }
+        if let Some(nodes) = report.graph_nodes {
+            status.info(&format!(
+                "Loaded HNSW graph ({} nodes) in {:.0?}",
+                nodes, report.graph_time
+            ));
+        }
+        status.success(&format!("{} is warm", report.index_root.display()));
+        return Ok(());
+    }
+
     if cli.add {
         // Handle --add flag
         // When using --add, the file path might be in pattern or files
@@ -1107,6 +2750,25 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         return Ok(());
     }
 
+    if cli.stats {
+        let stats_path = target_path(&cli);
+        let stats_path = stats_path.canonicalize().unwrap_or(stats_path);
+        let Some(index_root) = stats_path.ancestors().find(|dir| dir.join(".ck").is_dir()) else {
+            anyhow::bail!(
+                "No index found for {}. Run 'ck --index' first.",
+                stats_path.display()
+            );
+        };
+        let usage = ck_engine::load_usage(index_root);
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&usage)?);
+        } else {
+            status.section_header(&format!("Usage of {}", index_root.display()));
+            print!("{}", usage);
+        }
+        return Ok(());
+    }
+
     if cli.status || cli.status_verbose || cli.status_json {
         // Handle --status, --status-verbose, and --status-json flags
         let status_path = cli
@@ -1139,7 +2801,20 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                 "index_size_bytes": stats.index_size_bytes,
                 "index_created": stats.index_created,
                 "index_updated": stats.index_updated,
+                "snapshots": ck_index::list_snapshots(&status_path)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|snapshot| serde_json::json!({
+                        "generation": snapshot.generation,
+                        "updated": snapshot.updated,
+                        "files": snapshot.files,
+                    }))
+                    .collect::<Vec<_>>(),
             });
+            let schedule = mcp::schedule::status(&status_path);
+            if !schedule.is_empty() {
+                json_output["schedule"] = serde_json::to_value(&schedule)?;
+            }
 
             // Add model information if available
             let manifest_path = status_path.join(".ck").join("manifest.json");
@@ -1147,7 +2822,7 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                 && let Ok(manifest) = serde_json::from_slice::<ck_index::IndexManifest>(&data)
                 && let Some(model_name) = manifest.embedding_model
             {
-                let registry = ck_models::ModelRegistry::default();
+                let registry = ck_models::ModelRegistry::with_user_models();
                 let alias = registry
                     .models
                     .iter()
@@ -1187,7 +2862,7 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                 && let Ok(manifest) = serde_json::from_slice::<ck_index::IndexManifest>(&data)
                 && let Some(model_name) = manifest.embedding_model
             {
-                let registry = ck_models::ModelRegistry::default();
+                let registry = ck_models::ModelRegistry::with_user_models();
                 let alias = registry
                     .models
                     .iter()
@@ -1213,7 +2888,9 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                         model_name, alias, dims
                     ));
                 }
+                status.info(&format!("  Metric: {}", manifest.metric));
             }
+            print_schedule(&status, &mcp::schedule::status(&status_path));
 
             if verbose {
                 let size_mb = stats.total_size_bytes as f64 / (1024.0 * 1024.0);
@@ -1253,6 +2930,22 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                         compression_ratio * 100.0
                     ));
                 }
+
+                // Generations --as-of can search
+                let snapshots = ck_index::list_snapshots(&status_path).unwrap_or_default();
+                if !snapshots.is_empty() {
+                    status.info(&format!("  Snapshots ({}):", snapshots.len()));
+                    for snapshot in snapshots.iter().rev() {
+                        status.info(&format!(
+                            "    generation {}: {} files, {}",
+                            snapshot.generation,
+                            snapshot.files,
+                            chrono::DateTime::from_timestamp(snapshot.updated as i64, 0)
+                                .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+                                .unwrap_or_else(|| snapshot.updated.to_string())
+                        ));
+                    }
+                }
             }
         }
         return Ok(());
@@ -1270,10 +2963,12 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             std::process::exit(1);
         };
 
-        status.section_header("File Inspection");
+        if !cli.json {
+            status.section_header("File Inspection");
+        }
 
         // Inspect the file metadata
-        inspect_file_metadata(&file_path, &status).await?;
+        inspect_file_metadata(&file_path, cli.neighbors.unwrap_or(0), cli.json, &status).await?;
         return Ok(());
     }
 
@@ -1292,12 +2987,389 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         return Ok(());
     }
 
+    if let Some(output) = &cli.export_bundle {
+        let path = target_path(&cli);
+
+        status.section_header("Exporting Search Bundle");
+        let spinner = status.create_spinner("Collecting embeddings...");
+        let bundle = ck_engine::export_bundle(&path)?;
+        let bytes = bundle.to_bytes()?;
+        std::fs::write(output, &bytes)?;
+        status.finish_progress(spinner, "Bundle written");
+        status.success(&format!(
+            "Exported {} chunks ({}, {} dims, {} bytes) to {}",
+            bundle.len(),
+            bundle.model(),
+            bundle.dimensions(),
+            bytes.len(),
+            output.display()
+        ));
+        return Ok(());
+    }
+
+    if let Some(output) = &cli.export_vectors {
+        let path = target_path(&cli);
+        if output.extension().and_then(|ext| ext.to_str()) != Some("npy") {
+            anyhow::bail!(
+                "--export-vectors writes a .npy file; got {}",
+                output.display()
+            );
+        }
+        let rows = output.with_extension("jsonl");
+
+        status.section_header("Exporting Vectors");
+        let spinner = status.create_spinner("Collecting embeddings...");
+        let vectors = ck_engine::export_vectors(&path)?;
+        ck_engine::write_npy(
+            &vectors,
+            std::io::BufWriter::new(std::fs::File::create(output)?),
+        )?;
+        ck_engine::write_rows(
+            &vectors,
+            std::io::BufWriter::new(std::fs::File::create(&rows)?),
+        )?;
+        status.finish_progress(spinner, "Vectors written");
+        status.success(&format!(
+            "Exported {} vectors ({} dims) to {}, with their chunk ids in {}",
+            vectors.len(),
+            vectors[0].embedding.len(),
+            output.display(),
+            rows.display()
+        ));
+        return Ok(());
+    }
+
+    if let Some(output) = &cli.export_index {
+        let path = target_path(&cli);
+
+        status.section_header("Exporting Index");
+        let spinner = status.create_spinner("Collecting chunks...");
+        let rows = export_index(&path, output)?;
+        status.finish_progress(spinner, "Index written");
+        status.success(&format!("Exported {} chunks to {}", rows, output.display()));
+        return Ok(());
+    }
+
+    if cli.coverage {
+        let path = target_path(&cli);
+        let path = path.canonicalize().unwrap_or(path);
+        let Some(index_root) = path.ancestors().find(|dir| dir.join(".ck").is_dir()) else {
+            anyhow::bail!(
+                "No index found for {}. Run 'ck --index' first.",
+                path.display()
+            );
+        };
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: !cli.no_ignore,
+            use_ckignore: !cli.no_ckignore,
+            exclude_patterns: build_exclude_patterns(&cli),
+            follow_symlinks: cli.follow_symlinks,
+            files_from: None,
+        };
+
+        let spinner = status.create_spinner("Checking files against the index...");
+        let report = ck_index::index_coverage(index_root, &file_options);
+        status.finish_progress(spinner, "Coverage checked");
+        let report = report?;
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print_coverage(index_root, &report);
+        }
+        return Ok(());
+    }
+
+    if cli.verify {
+        let path = target_path(&cli);
+        let path = path.canonicalize().unwrap_or(path);
+        let Some(index_root) = path.ancestors().find(|dir| dir.join(".ck").is_dir()) else {
+            anyhow::bail!(
+                "No index found for {}. Run 'ck --index' first.",
+                path.display()
+            );
+        };
+
+        let spinner = status.create_spinner(if cli.reembed {
+            "Checking and re-embedding chunks..."
+        } else {
+            "Checking embeddings..."
+        });
+        let report = ck_index::verify_index(index_root, cli.reembed);
+        status.finish_progress(spinner, "Embeddings checked");
+        let report = report?;
+        if report.files_reembedded > 0 {
+            ck_engine::build_ann_index(index_root, false)?;
+            ck_engine::build_file_vectors(index_root, false)?;
+        }
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print_verify(&report);
+        }
+        return Ok(());
+    }
+
+    if cli.map {
+        let path = target_path(&cli);
+
+        let spinner = status.create_spinner(if cli.llm_labels {
+            "Clustering chunks and naming topics..."
+        } else {
+            "Clustering chunks..."
+        });
+        let map = ck_engine::topic_map(&path, cli.topics, cli.llm_labels);
+        status.finish_progress(spinner, "Map ready");
+        let map = map?;
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&map)?);
+        } else {
+            print_topic_map(&map);
+        }
+        return Ok(());
+    }
+
+    if cli.dupes {
+        let path = target_path(&cli);
+        let min_score = cli.min_score.unwrap_or(ck_engine::DEFAULT_MIN_SCORE);
+        if !(0.0..=1.0).contains(&min_score) {
+            anyhow::bail!("--min-score must be between 0 and 1; got {}", min_score);
+        }
+
+        let spinner = status.create_spinner("Comparing chunks...");
+        let report = ck_engine::find_duplicates(&path, min_score);
+        status.finish_progress(spinner, "Comparison complete");
+        let report = report?;
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print_duplicates(&report);
+        }
+        return Ok(());
+    }
+
+    if let Some(id) = &cli.embedding {
+        let path = target_path(&cli);
+        let Some(vector) = ck_engine::get_embedding(&path, id)? else {
+            eprintln!("No embedded chunk at {}", id);
+            std::process::exit(1);
+        };
+        #[derive(serde::Serialize)]
+        struct Output {
+            id: String,
+            path: String,
+            line_start: usize,
+            line_end: usize,
+            language: Option<String>,
+            symbol: Option<String>,
+            embedding: Vec<f32>,
+        }
+        let output = Output {
+            path: ck_core::paths::to_slash(&vector.path),
+            line_start: vector.span.line_start,
+            line_end: vector.span.line_end,
+            language: vector.language.map(|language| language.to_string()),
+            id: vector.id,
+            symbol: vector.symbol,
+            embedding: vector.embedding,
+        };
+        println!("{}", serde_json::to_string(&output)?);
+        return Ok(());
+    }
+
+    if let Some(kind) = cli.bench {
+        ck_engine::pause_usage_recording();
+        let corpus = target_path(&cli);
+        let models = if !cli.bench_models.is_empty() {
+            cli.bench_models.clone()
+        } else if let Some(model) = &cli.model {
+            vec![model.clone()]
+        } else {
+            vec![ck_engine::resolve_model_for_path(&corpus, None)?.alias]
+        };
+        let request = bench::BenchRequest {
+            kind,
+            corpus: &corpus,
+            models,
+            queries: bench::load_queries(cli.bench_queries.as_deref())?,
+            file_options: ck_core::FileCollectionOptions {
+                respect_gitignore: !cli.no_ignore,
+                use_ckignore: !cli.no_ckignore,
+                exclude_patterns: build_exclude_patterns(&cli),
+                follow_symlinks: cli.follow_symlinks,
+                files_from: None,
+            },
+        };
+
+        status.section_header("Benchmark");
+        let rows = bench::run(&request, &status).await?;
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+        } else {
+            println!("{}", bench::format_table(kind, &rows));
+        }
+        return Ok(());
+    }
+
+    if let Some(eval_path) = &cli.eval {
+        ck_engine::pause_usage_recording();
+        let corpus = target_path(&cli);
+        let eval_file = eval::load(eval_path)?;
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: !cli.no_ignore,
+            use_ckignore: !cli.no_ckignore,
+            exclude_patterns: build_exclude_patterns(&cli),
+            follow_symlinks: cli.follow_symlinks,
+            files_from: None,
+        };
+
+        status.section_header("Retrieval Evaluation");
+        let reports = eval::run(&eval_file, &corpus, &file_options, &status).await?;
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&reports)?);
+        } else {
+            println!("{}", eval::format_table(&reports));
+        }
+        return Ok(());
+    }
+
+    if let Some(rules_path) = &cli.check {
+        let root = target_path(&cli);
+        let rules = check::load(rules_path)?;
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: !cli.no_ignore,
+            use_ckignore: !cli.no_ckignore,
+            exclude_patterns: build_exclude_patterns(&cli),
+            follow_symlinks: cli.follow_symlinks,
+            files_from: None,
+        };
+        let changed = match &cli.diff {
+            Some(range) => Some(git_diff::changed_files(&root, range)?),
+            None => None,
+        };
+
+        status.section_header("Policy Check");
+        let reports = check::run(&rules, &root, &file_options, changed.as_deref(), &status).await?;
+        if cli.json {
+            println!("{}", serde_json::to_string_pretty(&reports)?);
+        } else {
+            println!("{}", check::format_report(&reports));
+        }
+        if reports.iter().any(check::RuleReport::failed) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(alias) = cli.add_model.as_deref() {
+        status.section_header("Adding Embedding Model");
+        let mut config = match (cli.onnx.as_deref(), cli.endpoint.as_deref()) {
+            (Some(onnx), _) => models::onnx_config(
+                alias,
+                onnx,
+                cli.tokenizer.as_deref(),
+                cli.pooling,
+                cli.truncation,
+            ),
+            (None, Some(url)) => {
+                models::endpoint_config(cli.endpoint_model.as_deref().unwrap_or(alias), url)
+            }
+            (None, None) => models::gguf_config(cli.gguf.as_deref().unwrap_or_default()),
+        };
+        config.query_instruction = cli.query_instruction.clone();
+        config.document_instruction = cli.document_instruction.clone();
+        let registry_path = models::add_model(alias, config, cli.dims, &status)?;
+        status.success(&format!(
+            "Saved '{}' to {}; index with `ck --index --model {}`",
+            alias,
+            registry_path.display(),
+            alias
+        ));
+        return Ok(());
+    }
+
+    if cli.stdin {
+        let mut options = build_options(&cli, cli.reindex, None);
+        options.path = target_path(&cli);
+        let summary = batch::run(
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            tokio::io::stdout(),
+            &options,
+            batch::BatchDefaults {
+                top_k: cli.top_k,
+                threshold: cli.threshold,
+                offset: cli.offset,
+            },
+        )
+        .await?;
+        status.info(&format!(
+            "Answered {} queries ({} failed)",
+            summary.queries, summary.failed
+        ));
+        return Ok(());
+    }
+
+    // Expand `@name` into the saved preset, then record a new one if requested
+    presets::expand_preset(&mut cli)?;
+    if let Some(name) = cli.save_as.clone() {
+        let config_path = presets::save_preset(&cli, &name)?;
+        status.success(&format!(
+            "Saved preset '@{}' to {}",
+            name.trim_start_matches('@'),
+            config_path.display()
+        ));
+    }
+
     // Validate conflicting flags
     if cli.files_with_matches && cli.files_without_matches {
         eprintln!("Error: Cannot use -l and -L together");
         std::process::exit(1);
     }
 
+    // --history searches the history index in place of the project's files
+    if cli.history {
+        let path = cli
+            .files
+            .first()
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from("."));
+        let root = ck_index::history_root(&path);
+        if !root.join(".ck").join("manifest.json").exists() {
+            anyhow::bail!(
+                "No history index for {}; run ck --index-history first",
+                path.display()
+            );
+        }
+        cli.files = vec![root];
+    }
+
+    if cli.ask || cli.context_bundle {
+        let query = cli.pattern.clone().unwrap_or_default();
+        let mut options = build_options(&cli, cli.reindex, None);
+        options.path = cli
+            .files
+            .first()
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from("."));
+        // Questions are prose; a regex search only runs when asked for
+        if !(cli.regex || cli.lexical || cli.hybrid || cli.symbol) {
+            options.mode = SearchMode::Semantic;
+        }
+        let found = if cli.context_bundle {
+            options.top_k = Some(cli.top_k.unwrap_or(ask::DEFAULT_BUNDLE_TOP_K));
+            let budget = cli.budget.unwrap_or(ask::DEFAULT_BUNDLE_BUDGET);
+            ask::run_bundle(&query, options, budget, &status).await?
+        } else {
+            options.top_k = Some(cli.top_k.unwrap_or(ask::DEFAULT_ASK_TOP_K));
+            ask::run(&query, options, &status).await?
+        };
+        if !found {
+            eprintln!("No matches found");
+            telemetry::shutdown();
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Default behavior: search with pattern
     if let Some(ref pattern) = cli.pattern {
         let reindex = cli.reindex;
@@ -1351,11 +3423,32 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
             include_patterns
         };
 
+        // --diff keeps the search root (and its index) but only admits changed files
+        let include_patterns = match &cli.diff {
+            Some(range) => {
+                let changed = git_diff::changed_files(&repo_root_path, range)?;
+                let scoped = git_diff::scope_to_changes(&changed, &include_patterns);
+                if scoped.is_empty() {
+                    if changed.is_empty() {
+                        eprintln!("No files changed in {}", range);
+                    } else {
+                        eprintln!("No files changed in {} under the given paths", range);
+                    }
+                    std::process::exit(1);
+                }
+                scoped
+            }
+            None => include_patterns,
+        };
+
         // Handle multiple files like grep; allow -h/-H overrides
         let mut show_filenames = if include_patterns.is_empty() {
             expanded_targets.len() > 1 || expanded_targets.iter().any(|p| p.is_dir())
         } else {
-            include_patterns.len() > 1 || include_patterns.iter().any(|p| p.is_dir)
+            // Diff scopes name the files they matched in, however few
+            include_patterns.len() > 1
+                || include_patterns.iter().any(|p| p.is_dir)
+                || cli.diff.is_some()
         };
         if cli.no_filenames {
             show_filenames = false;
@@ -1367,8 +3460,28 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
         options.show_filenames = show_filenames;
         options.include_patterns = include_patterns.clone();
         options.path = search_root.clone();
+        // Retrieve through the end of the requested page, then skip to its start
+        options.top_k = options.top_k.map(|limit| limit + cli.offset);
+
+        // Paths in different indexes are each searched with their own model
+        let index_roots = if options.mode == ck_core::SearchMode::Regex || options.as_of.is_some() {
+            None
+        } else {
+            ck_engine::index_roots(&expanded_targets).filter(|roots| roots.len() > 1)
+        };
 
-        let summary = run_search(pattern.clone(), search_root, options, &status).await?;
+        let output_format = cli.format.map(|format| (format, cli.columns.as_slice()));
+        let summary = run_search(
+            pattern.clone(),
+            search_root,
+            index_roots,
+            options,
+            cli.offset,
+            output_format,
+            (cli.group_by, cli.expand),
+            &status,
+        )
+        .await?;
 
         if cli.files_without_matches {
             let matched_canon: Vec<PathBuf> = summary
@@ -1406,7 +3519,8 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
 
                 // Get the pattern as a string
                 let options = build_options(&cli, false, repo_root);
-                let highlighted_preview = highlight_matches(&closest.preview, pattern, &options);
+                let highlighted_preview =
+                    highlight_matches(&closest.preview, pattern, &closest.file, &options);
 
                 // Print in red with same format as regular results, with header
                 eprintln!();
@@ -1420,6 +3534,7 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
                 );
             }
 
+            telemetry::shutdown();
             std::process::exit(1);
         }
     } else {
@@ -1430,6 +3545,15 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
     Ok(())
 }
 
+/// Intelligent defaults for semantic search: top-k and threshold per mode.
+fn default_limits(mode: &SearchMode) -> (Option<usize>, Option<f32>) {
+    match mode {
+        SearchMode::Semantic => (Some(10), Some(0.6)),
+        SearchMode::Symbol => (Some(10), None),
+        _ => (None, None),
+    }
+}
+
 fn build_options(cli: &Cli, reindex: bool, _repo_root: Option<&Path>) -> SearchOptions {
     let mode = if cli.semantic {
         SearchMode::Semantic
@@ -1437,6 +3561,8 @@ fn build_options(cli: &Cli, reindex: bool, _repo_root: Option<&Path>) -> SearchO
         SearchMode::Lexical
     } else if cli.hybrid {
         SearchMode::Hybrid
+    } else if cli.symbol {
+        SearchMode::Symbol
     } else {
         SearchMode::Regex
     };
@@ -1448,15 +3574,7 @@ fn build_options(cli: &Cli, reindex: bool, _repo_root: Option<&Path>) -> SearchO
     // Use the unified pattern builder
     let exclude_patterns = build_exclude_patterns(cli);
 
-    // Set intelligent defaults for semantic search
-    let default_topk = match mode {
-        SearchMode::Semantic => Some(10),
-        _ => None,
-    };
-    let default_threshold = match mode {
-        SearchMode::Semantic => Some(0.6),
-        _ => None,
-    };
+    let (default_topk, default_threshold) = default_limits(&mode);
 
     SearchOptions {
         mode,
@@ -1467,7 +3585,8 @@ fn build_options(cli: &Cli, reindex: bool, _repo_root: Option<&Path>) -> SearchO
         case_insensitive: cli.ignore_case,
         whole_word: cli.word_regexp,
         fixed_string: cli.fixed_strings,
-        line_numbers: cli.line_numbers,
+        line_numbers: cli.line_numbers || cli.column,
+        column: cli.column,
         context_lines: context,
         before_context_lines: before_context,
         after_context_lines: after_context,
@@ -1477,6 +3596,7 @@ fn build_options(cli: &Cli, reindex: bool, _repo_root: Option<&Path>) -> SearchO
         no_snippet: cli.no_snippet,
         reindex,
         show_scores: cli.show_scores,
+        explain: cli.explain,
         show_filenames: false, // Will be set by caller
         files_with_matches: cli.files_with_matches,
         files_without_matches: cli.files_without_matches,
@@ -1488,16 +3608,46 @@ fn build_options(cli: &Cli, reindex: bool, _repo_root: Option<&Path>) -> SearchO
         // Enhanced embedding options (search-time only)
         rerank: cli.rerank,
         rerank_model: cli.rerank_model.clone(),
+        rerank_scores: cli.rerank_scores,
+        rerank_top: cli.rerank_top,
+        rerank_budget: cli.rerank_budget.map(std::time::Duration::from_millis),
+        ann_oversample: cli.oversample,
         embedding_model: cli.model.clone(),
+        languages: cli.lang.clone(),
+        feedback: Default::default(),
+        hyde: cli.hyde,
+        top_files: cli.top_files,
+        by_file: cli.by_file,
+        score_normalization: cli.normalize_scores,
+        prefer: cli.prefer,
+        follow_symlinks: cli.follow_symlinks,
+        as_of: cli.as_of.clone(),
+        blame: cli.blame,
+        owner: cli.owner.clone(),
+        meta: cli.meta.clone(),
+        cancel: None,
+        instruction: cli.instruct.clone(),
     }
 }
 
-fn highlight_matches(text: &str, pattern: &str, options: &SearchOptions) -> String {
-    // Don't highlight if this is JSON/JSONL output
-    if options.json_output || options.jsonl_output {
+fn highlight_matches(text: &str, pattern: &str, file: &Path, options: &SearchOptions) -> String {
+    // Don't highlight if this is JSON/JSONL output or colors are off
+    if options.json_output || options.jsonl_output || !console::colors_enabled() {
         return text.to_string();
     }
 
+    // Color known languages, emphasizing what matched the query
+    let emphasis = match options.mode {
+        SearchMode::Regex => highlight_regex(pattern, options)
+            .map(|re| re.find_iter(text).map(|m| m.range()).collect())
+            .unwrap_or_default(),
+        SearchMode::Semantic | SearchMode::Hybrid => strongest_tokens(text, pattern),
+        _ => Vec::new(),
+    };
+    if let Some(colored) = highlight::syntax_highlight(text, file, &emphasis) {
+        return colored;
+    }
+
     match options.mode {
         SearchMode::Regex => highlight_regex_matches(text, pattern, options),
         SearchMode::Semantic | SearchMode::Hybrid => {
@@ -1508,7 +3658,7 @@ fn highlight_matches(text: &str, pattern: &str, options: &SearchOptions) -> Stri
     }
 }
 
-fn highlight_regex_matches(text: &str, pattern: &str, options: &SearchOptions) -> String {
+fn highlight_regex(pattern: &str, options: &SearchOptions) -> Result<regex::Regex, regex::Error> {
     // Build regex from pattern with EXACT same logic as regex_search in ck-engine
     let regex_pattern = if options.fixed_string {
         regex::escape(pattern)
@@ -1519,11 +3669,13 @@ fn highlight_regex_matches(text: &str, pattern: &str, options: &SearchOptions) -
         pattern.to_string()
     };
 
-    let regex_result = RegexBuilder::new(&regex_pattern)
+    RegexBuilder::new(&regex_pattern)
         .case_insensitive(options.case_insensitive)
-        .build();
+        .build()
+}
 
-    match regex_result {
+fn highlight_regex_matches(text: &str, pattern: &str, options: &SearchOptions) -> String {
+    match highlight_regex(pattern, options) {
         Ok(re) => {
             // Replace matches with highlighted versions
             re.replace_all(text, |caps: &regex::Captures| {
@@ -1540,6 +3692,21 @@ fn highlight_regex_matches(text: &str, pattern: &str, options: &SearchOptions) -
     }
 }
 
+/// Byte ranges of the tokens most similar to the query, in the heatmap's top
+/// (bold) buckets.
+fn strongest_tokens(text: &str, pattern: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    for token in heatmap::split_into_tokens(text) {
+        let score = heatmap::calculate_token_similarity(&token, pattern);
+        if token.chars().any(char::is_alphanumeric) && HeatmapBucket::from_score(score).is_bold() {
+            ranges.push(offset..offset + token.len());
+        }
+        offset += token.len();
+    }
+    ranges
+}
+
 fn highlight_semantic_chunks(text: &str, pattern: &str, _options: &SearchOptions) -> String {
     let tokens = heatmap::split_into_tokens(text);
 
@@ -1580,16 +3747,98 @@ struct SearchSummary {
     matched_paths: Vec<PathBuf>,
 }
 
+/// One-line score breakdown for `--explain`, e.g.
+/// `semantic: dense 0.712 · rerank 0.880 · path:**/vendor/** ×0.70 → 0.616`
+fn format_explanation(result: &ck_core::SearchResult) -> String {
+    let Some(explain) = &result.explain else {
+        return format!("regex match → {:.3}", result.score);
+    };
+    let mut parts = Vec::new();
+    if let Some(score) = explain.dense_score {
+        parts.push(format!("dense {:.3}", score));
+    }
+    if let Some(weight) = explain.facet_weight {
+        parts.push(format!("prefer ×{:.2}", weight));
+    }
+    if let Some(score) = explain.lexical_score {
+        parts.push(format!("lexical {:.3}", score));
+    }
+    if let Some(score) = explain.name_score {
+        parts.push(format!("name {:.3}", score));
+    }
+    if let Some(score) = explain.rerank_score {
+        parts.push(format!("rerank {:.3}", score));
+    }
+    if let Some(rank) = explain.vec_rank {
+        parts.push(format!("semantic rank {}", rank));
+    }
+    if let Some(rank) = explain.lex_rank {
+        parts.push(format!("regex rank {}", rank));
+    }
+    if let Some(rank) = explain.index_rank {
+        parts.push(format!("index rank {}", rank));
+    }
+    if explain.source == "rrf" || !explain.boosts.is_empty() {
+        parts.push(format!("base {:.3}", explain.base_score));
+    }
+    for boost in &explain.boosts {
+        parts.push(format!("{} ×{:.2}", boost.reason, boost.factor));
+    }
+    let source = if explain.source.is_empty() {
+        "boosted"
+    } else {
+        explain.source.as_str()
+    };
+    format!("{}: {} → {:.3}", source, parts.join(" · "), result.score)
+}
+
+/// One-line `--blame` summary: the last change, then the span's other authors.
+fn format_blame(blame: &ck_core::BlameInfo) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let days = now.saturating_sub(blame.time) / 86_400;
+    let age = match days {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        2..=60 => format!("{} days ago", days),
+        61..=729 => format!("{} months ago", days / 30),
+        _ => format!("{} years ago", days / 365),
+    };
+    let mut line = format!(
+        "last changed by {} {} ({})",
+        blame.author,
+        age,
+        &blame.commit[..blame.commit.len().min(8)]
+    );
+    let others: Vec<&str> = blame
+        .authors
+        .iter()
+        .map(|author| author.name.as_str())
+        .filter(|name| *name != blame.author)
+        .collect();
+    if !others.is_empty() {
+        line.push_str(&format!(" · also {}", others.join(", ")));
+    }
+    line
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn run_search(
     pattern: String,
     path: PathBuf,
+    index_roots: Option<Vec<PathBuf>>,
     mut options: SearchOptions,
+    offset: usize,
+    format: Option<(formats::OutputFormat, &[formats::Column])>,
+    (group_by, expand): (Option<grouping::GroupBy>, bool),
     status: &StatusReporter,
 ) -> Result<SearchSummary> {
     options.query = pattern;
     options.path = path;
 
-    if options.reindex {
+    // Several indexes are each refreshed by the search itself
+    if options.reindex && index_roots.is_none() {
         let reindex_spinner = status.create_spinner("Updating index...");
         let file_options = ck_core::FileCollectionOptions::from(&options);
         ck_index::update_index(&options.path, true, &file_options).await?;
@@ -1615,7 +3864,18 @@ async fn run_search(
         let resolved_model =
             ck_engine::resolve_model_for_path(&options.path, options.embedding_model.as_deref())?;
 
-        if resolved_model.alias == resolved_model.canonical_name() {
+        if let Some(roots) = &index_roots {
+            for root in roots {
+                let model =
+                    ck_engine::resolve_model_for_path(root, options.embedding_model.as_deref())?;
+                eprintln!(
+                    "🤖 Model: {} ({} dims) for {}",
+                    model.canonical_name(),
+                    model.dimensions(),
+                    root.display()
+                );
+            }
+        } else if resolved_model.alias == resolved_model.canonical_name() {
             eprintln!(
                 "🤖 Model: {} ({} dims)",
                 resolved_model.canonical_name(),
@@ -1732,29 +3992,68 @@ async fn run_search(
         (None, None)
     };
 
-    let search_results = ck_engine::search_enhanced_with_indexing_progress(
-        &options,
-        search_progress_callback,
-        indexing_progress_callback,
-        detailed_indexing_progress_callback,
-    )
-    .await?;
-    let results = &search_results.matches;
-    let matched_paths: Vec<PathBuf> = results.iter().map(|result| result.file.clone()).collect();
+    let search_results = match &index_roots {
+        Some(roots) => {
+            ck_engine::search_indexes_with_indexing_progress(
+                &options,
+                roots,
+                search_progress_callback,
+                indexing_progress_callback,
+                detailed_indexing_progress_callback,
+            )
+            .await?
+        }
+        None => {
+            ck_engine::search_enhanced_with_indexing_progress(
+                &options,
+                search_progress_callback,
+                indexing_progress_callback,
+                detailed_indexing_progress_callback,
+            )
+            .await?
+        }
+    };
+    let results = &search_results.matches[offset.min(search_results.matches.len())..];
+    let matched_paths: Vec<PathBuf> = results
+        .iter()
+        .flat_map(|result| {
+            std::iter::once(result.file.clone())
+                .chain(result.duplicates.iter().map(|d| d.file.clone()))
+        })
+        .collect();
 
     status.finish_progress(search_spinner, &format!("Found {} results", results.len()));
 
+    let groups = grouping::group(results, group_by);
+    let more_in_file = |more: &[&ck_core::SearchResult]| -> Vec<ck_core::GroupedMatch> {
+        more.iter()
+            .map(|hit| ck_core::GroupedMatch {
+                span: hit.span.clone(),
+                score: hit.score,
+            })
+            .collect()
+    };
+
     let mut has_matches = false;
-    if options.jsonl_output {
-        for result in results {
+    if let Some((format, columns)) = format {
+        has_matches = !results.is_empty();
+        let best: Vec<ck_core::SearchResult> = groups.iter().map(|g| g.best.clone()).collect();
+        println!("{}", formats::render(format, &best, &options, columns));
+    } else if options.jsonl_output {
+        for grouping::Grouped { best: result, more } in &groups {
             has_matches = true;
-            let jsonl_result =
+            let mut jsonl_result =
                 ck_core::JsonlSearchResult::from_search_result(result, !options.no_snippet);
+            if options.explain {
+                jsonl_result.explain = result.explain.clone();
+            }
+            jsonl_result.more_in_file = more_in_file(more);
             println!("{}", serde_json::to_string(&jsonl_result)?);
         }
     } else if options.json_output {
-        for result in results {
+        for grouping::Grouped { best: result, more } in &groups {
             has_matches = true;
+            let explain = result.explain.as_ref();
             let json_result = ck_core::JsonSearchResult {
                 file: result.file.display().to_string(),
                 span: result.span.clone(),
@@ -1762,12 +4061,21 @@ async fn run_search(
                 symbol: result.symbol.clone(),
                 score: result.score,
                 signals: ck_core::SearchSignals {
-                    lex_rank: None,
-                    vec_rank: None,
+                    lex_rank: explain.and_then(|e| e.lex_rank),
+                    vec_rank: explain.and_then(|e| e.vec_rank),
                     rrf_score: result.score,
+                    dense_score: explain.and_then(|e| e.dense_score),
+                    lexical_score: explain.and_then(|e| e.lexical_score),
+                    rerank_score: explain.and_then(|e| e.rerank_score),
+                    index_score: explain.and_then(|e| e.index_score),
                 },
                 preview: result.preview.clone(),
                 model: "none".to_string(),
+                duplicates: result.duplicates.clone(),
+                explain: explain.filter(|_| options.explain).cloned(),
+                blame: result.blame.clone(),
+                metadata: result.metadata.clone(),
+                more_in_file: more_in_file(more),
             };
             println!("{}", serde_json::to_string(&json_result)?);
         }
@@ -1776,9 +4084,11 @@ async fn run_search(
         let mut printed_files = std::collections::HashSet::new();
         for result in results {
             has_matches = true;
-            let file_path = &result.file;
-            if printed_files.insert(file_path.clone()) {
-                println!("{}", file_path.display());
+            let copies = result.duplicates.iter().map(|d| &d.file);
+            for file_path in std::iter::once(&result.file).chain(copies) {
+                if printed_files.insert(file_path.clone()) {
+                    println!("{}", file_path.display());
+                }
             }
         }
     } else if options.files_without_matches {
@@ -1786,7 +4096,7 @@ async fn run_search(
         has_matches = !results.is_empty();
     } else {
         // Normal output
-        for result in results {
+        for grouping::Grouped { best: result, more } in &groups {
             has_matches = true;
             let score_text = if options.show_scores {
                 format!("[{:.3}] ", result.score)
@@ -1794,7 +4104,14 @@ async fn run_search(
                 String::new()
             };
 
-            let highlighted_preview = highlight_matches(&result.preview, &options.query, &options);
+            let highlighted_preview =
+                highlight_matches(&result.preview, &options.query, &result.file, &options);
+            // --column appends the column, when the index recorded one
+            let line_number = if options.column && result.span.col_start > 0 {
+                format!("{}:{}", result.span.line_start, result.span.col_start)
+            } else {
+                result.span.line_start.to_string()
+            };
 
             // Format output based on options
             if options.line_numbers && options.show_filenames {
@@ -1803,7 +4120,7 @@ async fn run_search(
                     "{}{}:{}:{}",
                     score_text,
                     style(result.file.display()).cyan().bold(),
-                    style(result.span.line_start).yellow(),
+                    style(line_number).yellow(),
                     highlighted_preview
                 );
             } else if options.line_numbers {
@@ -1811,7 +4128,7 @@ async fn run_search(
                 println!(
                     "{}{}:{}",
                     score_text,
-                    style(result.span.line_start).yellow(),
+                    style(line_number).yellow(),
                     highlighted_preview
                 );
             } else if options.show_filenames {
@@ -1826,6 +4143,58 @@ async fn run_search(
                 // No filename or line number
                 println!("{}{}", score_text, highlighted_preview);
             }
+
+            if options.explain {
+                println!("  {}", style(format_explanation(result)).dim());
+            }
+
+            if let Some(blame) = &result.blame {
+                println!("  {}", style(format_blame(blame)).dim());
+            }
+
+            if options.show_filenames {
+                for duplicate in &result.duplicates {
+                    println!(
+                        "  {} {}:{}",
+                        style("also in").dim(),
+                        style(duplicate.file.display()).cyan(),
+                        style(duplicate.span.line_start).yellow()
+                    );
+                }
+            }
+
+            if expand {
+                for hit in more {
+                    let score_text = if options.show_scores {
+                        format!("[{:.3}] ", hit.score)
+                    } else {
+                        String::new()
+                    };
+                    let first_line = hit.preview.lines().next().unwrap_or("").trim();
+                    println!(
+                        "  {}{}: {}",
+                        score_text,
+                        style(hit.span.line_start).yellow(),
+                        highlight_matches(first_line, &options.query, &hit.file, &options)
+                    );
+                }
+            } else if !more.is_empty() {
+                let lines: Vec<String> = more
+                    .iter()
+                    .map(|hit| hit.span.line_start.to_string())
+                    .collect();
+                let noun = if more.len() == 1 { "match" } else { "matches" };
+                println!(
+                    "  {}",
+                    style(format!(
+                        "+{} more {} in this file (lines {})",
+                        more.len(),
+                        noun,
+                        lines.join(", ")
+                    ))
+                    .dim()
+                );
+            }
         }
     }
 
@@ -0,0 +1,200 @@
+//! `ck --serve --audit-log FILE`: one JSON line per tool call, recording who
+//! called which tool with what arguments, when, and which files came back.
+//! The log is only ever appended to. Once it reaches its size limit it is
+//! rotated to `FILE.1` (shifting older logs up to the number kept) and a new
+//! one is started, so a shared server's history is bounded but never edited.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Size at which the log is rotated unless `--audit-log-max-size` says otherwise.
+pub const DEFAULT_MAX_SIZE: u64 = 10 << 20;
+
+/// Rotated logs kept unless `--audit-log-keep` says otherwise.
+pub const DEFAULT_KEEP: usize = 5;
+
+/// One tool call.
+#[derive(Debug, Serialize)]
+pub struct AuditRecord {
+    /// RFC 3339, UTC
+    pub time: String,
+    /// The MCP client, as `name version` from its initialize request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// The account the server runs as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// With `--namespaces`, the namespace the call was routed to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub tool: String,
+    pub arguments: Map<String, Value>,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Files in the results, in result order, each once
+    pub files: Vec<String>,
+    pub duration_ms: u64,
+}
+
+impl AuditRecord {
+    /// The files a tool result lists under `results.matches[].file.path`.
+    pub fn files_in(result: Option<&Value>) -> Vec<String> {
+        let matches = result
+            .and_then(|result| result.pointer("/results/matches"))
+            .and_then(Value::as_array);
+        let mut files: Vec<String> = Vec::new();
+        for path in matches
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m.pointer("/file/path").and_then(Value::as_str))
+        {
+            if !files.iter().any(|file| file == path) {
+                files.push(path.to_string());
+            }
+        }
+        files
+    }
+}
+
+/// The account name from the environment, as `whoami` would print it.
+pub fn current_user() -> Option<String> {
+    ["USER", "USERNAME", "LOGNAME"]
+        .into_iter()
+        .find_map(|name| std::env::var(name).ok().filter(|user| !user.is_empty()))
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Open `path` for appending, creating it and its directory if needed.
+    pub fn open(path: &Path, max_size: u64, keep: usize) -> io::Result<Self> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size,
+            keep,
+            file: Mutex::new(append(path)?),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `record`, rotating first if the log is full. A call that cannot
+    /// be recorded is an error, so the server never answers unaudited.
+    pub fn record(&self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let size = file.metadata()?.len();
+        if size > 0 && size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+            *file = append(&self.path)?;
+        }
+        file.write_all(&line)?;
+        file.sync_data()
+    }
+
+    /// `FILE.{n-1}` becomes `FILE.{n}`, down to `FILE` becoming `FILE.1`; the
+    /// oldest beyond `keep` is dropped.
+    fn rotate(&self) -> io::Result<()> {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        if self.keep == 0 {
+            return fs::remove_file(&self.path);
+        }
+        match fs::remove_file(rotated(self.keep)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        for n in (1..self.keep).rev() {
+            match fs::rename(rotated(n), rotated(n + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        fs::rename(&self.path, rotated(1))
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn record(tool: &str) -> AuditRecord {
+        let result = json!({"results": {"matches": [
+            {"file": {"path": "src/auth.rs"}},
+            {"file": {"path": "src/session.rs"}},
+            {"file": {"path": "src/auth.rs"}},
+        ]}});
+        AuditRecord {
+            time: "2026-03-14T09:30:00+00:00".to_string(),
+            client: Some("claude-code 1.0".to_string()),
+            user: Some("alice".to_string()),
+            namespace: None,
+            tool: tool.to_string(),
+            arguments: json!({"query": "login"}).as_object().unwrap().clone(),
+            ok: true,
+            error: None,
+            files: AuditRecord::files_in(Some(&result)),
+            duration_ms: 12,
+        }
+    }
+
+    #[test]
+    fn test_records_append_as_json_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("logs/audit.jsonl");
+        let log = AuditLog::open(&path, DEFAULT_MAX_SIZE, DEFAULT_KEEP).unwrap();
+        log.record(&record("semantic_search")).unwrap();
+        log.record(&record("regex_search")).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["tool"], "semantic_search");
+        assert_eq!(lines[0]["files"], json!(["src/auth.rs", "src/session.rs"]));
+        assert_eq!(lines[1]["arguments"]["query"], "login");
+    }
+
+    #[test]
+    fn test_rotation_keeps_the_newest_logs() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let size = serde_json::to_vec(&record("regex_search")).unwrap().len() as u64 + 1;
+        let log = AuditLog::open(&path, size, 2).unwrap();
+        for _ in 0..4 {
+            log.record(&record("regex_search")).unwrap();
+        }
+
+        let lines = |path: &Path| fs::read_to_string(path).unwrap().lines().count();
+        assert_eq!(lines(&path), 1);
+        assert_eq!(lines(&dir.path().join("audit.jsonl.1")), 1);
+        assert_eq!(lines(&dir.path().join("audit.jsonl.2")), 1);
+        assert!(!dir.path().join("audit.jsonl.3").exists());
+    }
+}
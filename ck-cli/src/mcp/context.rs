@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, RwLock};
 use tracing::info;
 
@@ -16,12 +18,44 @@ pub struct McpContext {
     pub cwd: PathBuf,
     pub stats_cache: StatsCache,
     pub session_manager: SessionManager,
+    /// Recent semantic/lexical results, invalidated when the index generation changes
+    pub result_cache: Arc<ck_engine::ResultCache>,
     #[allow(dead_code)]
     pub index_locks: Arc<RwLock<HashMap<PathBuf, Arc<Mutex<()>>>>>,
     #[allow(dead_code)]
     pub operation_tokens: Arc<RwLock<HashMap<String, tokio_util::sync::CancellationToken>>>,
     #[allow(dead_code)]
     pub default_search_options: SearchOptions,
+    /// When the last tool call ended, and how many are running, so scheduled
+    /// maintenance can wait for a quiet moment
+    pub activity: Arc<Activity>,
+}
+
+#[derive(Debug)]
+pub struct Activity {
+    last: std::sync::Mutex<Instant>,
+    running: AtomicUsize,
+}
+
+/// A running tool call; see [`McpContext::begin_call`].
+pub struct ActiveCall(Arc<Activity>);
+
+impl Drop for ActiveCall {
+    fn drop(&mut self) {
+        if let Ok(mut last) = self.0.last.lock() {
+            *last = Instant::now();
+        }
+        self.0.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            last: std::sync::Mutex::new(Instant::now()),
+            running: AtomicUsize::new(0),
+        }
+    }
 }
 
 impl McpContext {
@@ -38,6 +72,7 @@ impl McpContext {
             whole_word: false,
             fixed_string: false,
             line_numbers: false,
+            column: false,
             context_lines: 0,
             before_context_lines: 0,
             after_context_lines: 0,
@@ -47,6 +82,7 @@ impl McpContext {
             no_snippet: false,
             reindex: false,
             show_scores: true,
+            explain: false,
             show_filenames: true,
             files_with_matches: false,
             files_without_matches: false,
@@ -57,22 +93,60 @@ impl McpContext {
             full_section: false,
             rerank: false,
             rerank_model: None,
+            rerank_scores: None,
+            rerank_top: None,
+            rerank_budget: None,
+            ann_oversample: None,
             embedding_model: None,
+            languages: Vec::new(),
+            feedback: Default::default(),
+            hyde: false,
+            top_files: None,
+            by_file: false,
+            score_normalization: ck_core::ScoreNormalization::Rank,
+            follow_symlinks: false,
+            as_of: None,
+            blame: false,
+            owner: None,
+            meta: Vec::new(),
+            prefer: None,
+            cancel: None,
+            instruction: None,
         };
 
         Ok(Self {
             cwd,
             stats_cache: StatsCache::default(), // 30-second TTL for MCP responsiveness
             session_manager: SessionManager::default(), // 5-minute TTL for search sessions
+            result_cache: Arc::new(ck_engine::ResultCache::default()),
             #[allow(dead_code)]
             index_locks: Arc::new(RwLock::new(HashMap::new())),
             #[allow(dead_code)]
             operation_tokens: Arc::new(RwLock::new(HashMap::new())),
             #[allow(dead_code)]
             default_search_options,
+            activity: Arc::new(Activity::default()),
         })
     }
 
+    /// Mark a tool call as running until the returned guard is dropped.
+    pub fn begin_call(&self) -> ActiveCall {
+        self.activity.running.fetch_add(1, Ordering::SeqCst);
+        ActiveCall(self.activity.clone())
+    }
+
+    /// How long no tool call has been running; zero while one is.
+    pub fn idle_for(&self) -> Duration {
+        if self.activity.running.load(Ordering::SeqCst) > 0 {
+            return Duration::ZERO;
+        }
+        self.activity
+            .last
+            .lock()
+            .map(|last| last.elapsed())
+            .unwrap_or_default()
+    }
+
     /// Get or create an index lock for the specified directory
     #[allow(dead_code)]
     pub async fn get_index_lock(&self, path: &PathBuf) -> Arc<Mutex<()>> {
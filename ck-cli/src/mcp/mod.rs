@@ -1,6 +1,9 @@
+pub mod audit;
 pub mod cache;
 pub mod context;
 pub mod errors;
+pub mod namespaces;
+pub mod schedule;
 pub mod session;
 pub mod tools;
 
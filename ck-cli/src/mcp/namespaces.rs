@@ -0,0 +1,393 @@
+//! `ck --serve --namespaces FILE`: one server hosting several indexes, each a
+//! named directory with its own API keys and quotas. Every tool call names its
+//! `namespace`; its `path` is resolved inside that namespace's root and may not
+//! leave it. The file maps names to settings:
+//!
+//! ```json
+//! {
+//!   "payments": {
+//!     "path": "/srv/repos/payments",
+//!     "api_key_env": "CK_PAYMENTS_KEYS",
+//!     "quota": { "requests_per_minute": 120, "max_results": 50 }
+//!   }
+//! }
+//! ```
+//!
+//! Keys are read from the environment variable named by `api_key_env` (several
+//! may be given, separated by commas), never from the file. A namespace
+//! without `api_key_env` is open to any caller.
+
+use rmcp::ErrorData;
+use rmcp::model::Tool;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Tools that work without a namespace: they read nothing from any index.
+const UNSCOPED_TOOLS: [&str; 1] = ["health_check"];
+
+/// Limits on one namespace's use of the server.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quota {
+    /// Tool calls accepted in any 60-second window
+    pub requests_per_minute: Option<u32>,
+    /// Cap on `top_k` and `page_size`, cursor pages included; searches that
+    /// leave either unset get it
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NamespaceConfig {
+    path: PathBuf,
+    #[serde(default)]
+    api_key_env: Option<String>,
+    #[serde(default)]
+    quota: Quota,
+}
+
+struct Namespace {
+    root: PathBuf,
+    /// SHA-256 of each accepted key; empty when the namespace is open
+    key_digests: Vec<[u8; 32]>,
+    quota: Quota,
+    recent: Mutex<VecDeque<Instant>>,
+}
+
+pub struct Namespaces {
+    entries: BTreeMap<String, Namespace>,
+}
+
+impl Namespaces {
+    /// Read `path`, resolving each root and reading each namespace's keys.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        let configs: BTreeMap<String, NamespaceConfig> = serde_json::from_str(&data)?;
+        if configs.is_empty() {
+            anyhow::bail!("{} defines no namespaces", path.display());
+        }
+        let mut entries = BTreeMap::new();
+        for (name, config) in configs {
+            let root = config.path.canonicalize().map_err(|e| {
+                anyhow::anyhow!(
+                    "Namespace '{}': cannot open {}: {}",
+                    name,
+                    config.path.display(),
+                    e
+                )
+            })?;
+            let key_digests = match &config.api_key_env {
+                Some(var) => {
+                    let keys = std::env::var(var)
+                        .map_err(|_| anyhow::anyhow!("Namespace '{}': {} is not set", name, var))?;
+                    let digests: Vec<[u8; 32]> = keys
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|key| !key.is_empty())
+                        .map(digest)
+                        .collect();
+                    if digests.is_empty() {
+                        anyhow::bail!("Namespace '{}': {} holds no keys", name, var);
+                    }
+                    digests
+                }
+                None => Vec::new(),
+            };
+            entries.insert(
+                name,
+                Namespace {
+                    root,
+                    key_digests,
+                    quota: config.quota,
+                    recent: Mutex::new(VecDeque::new()),
+                },
+            );
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// The root of the namespace called `name`.
+    pub fn root(&self, name: &str) -> Option<&Path> {
+        self.entries
+            .get(name)
+            .map(|namespace| namespace.root.as_path())
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = &Path> {
+        self.entries
+            .values()
+            .map(|namespace| namespace.root.as_path())
+    }
+
+    /// Advertise `namespace` and `api_key` in `tool`'s input schema.
+    pub fn add_to_schema(&self, tool: &mut Tool) {
+        let mut schema = (*tool.input_schema).clone();
+        if let Some(properties) = schema
+            .entry("properties")
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+        {
+            properties.insert(
+                "namespace".to_string(),
+                json!({
+                    "type": "string",
+                    "enum": self.names().collect::<Vec<_>>(),
+                    "description": "Index to use; paths are relative to its root"
+                }),
+            );
+            properties.insert(
+                "api_key".to_string(),
+                json!({"type": "string", "description": "Key for the namespace"}),
+            );
+        }
+        if !UNSCOPED_TOOLS.contains(&tool.name.as_ref())
+            && let Some(required) = schema
+                .entry("required")
+                .or_insert_with(|| json!([]))
+                .as_array_mut()
+        {
+            required.push(json!("namespace"));
+        }
+        tool.input_schema = std::sync::Arc::new(schema);
+    }
+
+    /// Check a call to `tool` against its namespace and rewrite its arguments
+    /// for it: `namespace` and `api_key` are removed, `path` is made absolute
+    /// within the namespace root, and result counts are capped. Returns the
+    /// namespace, or `None` for tools that need none.
+    pub fn route(
+        &self,
+        tool: &str,
+        arguments: &mut Map<String, Value>,
+    ) -> Result<Option<String>, ErrorData> {
+        let name = arguments.remove("namespace");
+        let key = arguments.remove("api_key");
+        if UNSCOPED_TOOLS.contains(&tool) && name.is_none() {
+            return Ok(None);
+        }
+        let Some(name) = name.as_ref().and_then(Value::as_str) else {
+            return Err(ErrorData::invalid_params(
+                format!(
+                    "namespace is required; one of: {}",
+                    self.names().collect::<Vec<_>>().join(", ")
+                ),
+                None,
+            ));
+        };
+        let namespace = self.entries.get(name).ok_or_else(|| {
+            ErrorData::invalid_params(format!("Unknown namespace '{}'", name), None)
+        })?;
+
+        namespace.authorize(key.as_ref().and_then(Value::as_str))?;
+        namespace.admit(name)?;
+
+        let requested = arguments.get("path").and_then(Value::as_str).unwrap_or(".");
+        let path = namespace.resolve(requested)?;
+        arguments.insert("path".to_string(), json!(path.to_string_lossy()));
+        if let Some(patterns) = arguments.get("include_patterns").and_then(Value::as_array) {
+            for pattern in patterns.iter().filter_map(Value::as_str) {
+                if pattern
+                    .split(';')
+                    .any(|segment| escapes(Path::new(segment.trim())))
+                {
+                    return Err(ErrorData::invalid_params(
+                        format!("include pattern '{}' leaves the namespace", pattern),
+                        None,
+                    ));
+                }
+            }
+        }
+        if let Some(max) = namespace.quota.max_results {
+            for field in ["top_k", "page_size"] {
+                if let Some(value) = arguments.get_mut(field)
+                    && value.as_u64().is_none_or(|n| n > max as u64)
+                {
+                    *value = json!(max);
+                }
+            }
+            if tool.ends_with("_search") {
+                arguments.entry("top_k").or_insert(json!(max));
+                arguments.entry("page_size").or_insert(json!(max));
+            }
+        }
+        Ok(Some(name.to_string()))
+    }
+}
+
+impl Namespace {
+    fn authorize(&self, key: Option<&str>) -> Result<(), ErrorData> {
+        if self.key_digests.is_empty() {
+            return Ok(());
+        }
+        let given = key.map(digest);
+        if given.is_some_and(|given| self.key_digests.iter().any(|key| same(key, &given))) {
+            Ok(())
+        } else {
+            Err(ErrorData::invalid_request(
+                "Missing or invalid api_key for this namespace",
+                None,
+            ))
+        }
+    }
+
+    /// Count a call against `requests_per_minute`.
+    fn admit(&self, name: &str) -> Result<(), ErrorData> {
+        let Some(limit) = self.quota.requests_per_minute else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        while recent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= Duration::from_secs(60))
+        {
+            recent.pop_front();
+        }
+        if recent.len() >= limit as usize {
+            return Err(ErrorData::invalid_request(
+                format!(
+                    "Namespace '{}' is over its quota of {} requests per minute",
+                    name, limit
+                ),
+                None,
+            ));
+        }
+        recent.push_back(now);
+        Ok(())
+    }
+
+    /// `requested`, relative to the root or absolute, as a path inside it.
+    fn resolve(&self, requested: &str) -> Result<PathBuf, ErrorData> {
+        let outside = || {
+            ErrorData::invalid_params(
+                format!("path '{}' is outside the namespace", requested),
+                None,
+            )
+        };
+        let joined = self.root.join(requested);
+        let resolved = joined
+            .canonicalize()
+            .map_err(|e| ErrorData::invalid_params(format!("path '{}': {}", requested, e), None))?;
+        if resolved.starts_with(&self.root) {
+            Ok(resolved)
+        } else {
+            Err(outside())
+        }
+    }
+}
+
+/// Whether a relative pattern could reach outside the directory it is
+/// resolved against.
+fn escapes(pattern: &Path) -> bool {
+    pattern.components().any(|component| {
+        matches!(
+            component,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    })
+}
+
+fn digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+/// Compare digests without stopping at the first difference.
+fn same(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn namespaces(dir: &TempDir, config: Value) -> Namespaces {
+        let path = dir.path().join("namespaces.json");
+        std::fs::write(&path, config.to_string()).unwrap();
+        Namespaces::load(&path).unwrap()
+    }
+
+    fn arguments(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_route_scopes_paths_and_keys() {
+        let dir = TempDir::new().unwrap();
+        let repo = dir.path().join("payments");
+        std::fs::create_dir_all(repo.join("src")).unwrap();
+        // SAFETY: no other test reads this variable
+        unsafe { std::env::set_var("CK_TEST_PAYMENTS_KEYS", "alpha, beta") };
+        let namespaces = namespaces(
+            &dir,
+            json!({"payments": {
+                "path": repo,
+                "api_key_env": "CK_TEST_PAYMENTS_KEYS",
+                "quota": {"max_results": 20}
+            }}),
+        );
+
+        let mut args = arguments(json!({
+            "namespace": "payments", "api_key": "beta", "query": "refund", "path": "src", "page_size": 100
+        }));
+        let routed = namespaces.route("semantic_search", &mut args).unwrap();
+        assert_eq!(routed.as_deref(), Some("payments"));
+        assert!(!args.contains_key("api_key") && !args.contains_key("namespace"));
+        assert_eq!(
+            PathBuf::from(args["path"].as_str().unwrap()),
+            repo.join("src").canonicalize().unwrap()
+        );
+        assert_eq!(args["page_size"], 20);
+        assert_eq!(args["top_k"], 20);
+
+        let mut next_page = arguments(json!({
+            "namespace": "payments", "api_key": "alpha", "query": "refund", "cursor": "abc"
+        }));
+        namespaces.route("semantic_search", &mut next_page).unwrap();
+        assert_eq!(next_page["page_size"], 20);
+
+        for bad in [
+            json!({"namespace": "payments", "api_key": "gamma", "path": "."}),
+            json!({"namespace": "payments", "path": "."}),
+            json!({"namespace": "payments", "api_key": "alpha", "path": ".."}),
+            json!({"namespace": "payments", "api_key": "alpha", "include_patterns": ["../*.rs"]}),
+            json!({"namespace": "billing", "api_key": "alpha"}),
+            json!({"query": "refund"}),
+        ] {
+            assert!(
+                namespaces
+                    .route("regex_search", &mut arguments(bad))
+                    .is_err()
+            );
+        }
+        assert_eq!(
+            namespaces.route("health_check", &mut Map::new()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_requests_per_minute_quota() {
+        let dir = TempDir::new().unwrap();
+        let namespaces = namespaces(
+            &dir,
+            json!({"docs": {"path": dir.path(), "quota": {"requests_per_minute": 2}}}),
+        );
+
+        let call =
+            || namespaces.route("index_status", &mut arguments(json!({"namespace": "docs"})));
+        assert!(call().is_ok());
+        assert!(call().is_ok());
+        let err = call().unwrap_err();
+        assert!(err.message.contains("quota"));
+    }
+}
@@ -0,0 +1,474 @@
+//! Maintenance a long-running `ck --serve` does by itself, on the `schedule`
+//! of each index's `.ckconfig.json`. A task falls due at the times its cron
+//! expression names but only runs once no tool call has come in for
+//! [`IDLE_AFTER`], so a busy server postpones it rather than slowing down.
+//! A run missed while the server was down is not made up; the last run of
+//! each task is kept in `.ck/schedule.json` for `ck --status`.
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone};
+use ck_models::{MaintenanceTask, ScheduledTask};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::context::McpContext;
+
+/// Last runs of the scheduled tasks, under `.ck`
+pub const STATE_FILE: &str = "schedule.json";
+
+/// How long the server must have gone without a tool call before a due task
+/// runs.
+pub const IDLE_AFTER: Duration = Duration::from_secs(120);
+
+/// How often due tasks are looked for
+const TICK: Duration = Duration::from_secs(30);
+
+/// Days searched for the next matching time before an expression is taken
+/// to match none (`0 0 31 2 *`).
+const HORIZON_DAYS: i64 = 366 * 5;
+
+/// A parsed five-field cron expression. Each field is a bit set of the
+/// values it matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    /// Whether the day-of-month and day-of-week fields were restricted: when
+    /// both are, a day matching either one matches, as in cron.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "expected 5 fields (minute hour day-of-month month day-of-week), found {}",
+                fields.len()
+            ));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7, "day-of-week")?;
+        // Both 0 and 7 are Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")? as u32,
+            days: parse_field(day, 1, 31, "day-of-month")? as u32,
+            months: parse_field(month, 1, 12, "month")? as u16,
+            weekdays: (weekdays & 0x7f) as u8,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+}
+
+/// The values of `min..=max` one cron field matches: `*`, a number, a range
+/// `a-b`, any of them stepped (`*/15`, `8-18/2`), or a comma-separated list.
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid {} field '{}'", name, field);
+    let number = |text: &str| -> Result<u32, String> {
+        let value: u32 = text.parse().map_err(|_| invalid())?;
+        if (min..=max).contains(&value) {
+            Ok(value)
+        } else {
+            Err(format!("{} {} is outside {}-{}", name, value, min, max))
+        }
+    };
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (number(start)?, number(end)?)
+        } else {
+            let start = number(range)?;
+            // `5/10` runs from 5 to the end of the range
+            (start, if part.contains('/') { max } else { start })
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl Cron {
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first matching minute after `after`. Local times skipped by a
+    /// clock change never match.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local();
+        let mut date = start.date();
+        for _ in 0..HORIZON_DAYS {
+            if self.matches_day(date) {
+                for hour in (0..24).filter(|h| self.hours & (1 << h) != 0) {
+                    for minute in (0..60).filter(|m| self.minutes & (1 << m) != 0) {
+                        let Some(time) = date.and_hms_opt(hour, minute, 0) else {
+                            continue;
+                        };
+                        if time <= start {
+                            continue;
+                        }
+                        if let Some(local) = Local.from_local_datetime(&time).earliest()
+                            && local > after
+                        {
+                            return Some(local);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+}
+
+/// The outcome of a task's last run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskRun {
+    /// Unix seconds when it started
+    pub started: i64,
+    pub ok: bool,
+    /// What it did, or why it failed
+    pub message: String,
+    pub duration_ms: u64,
+}
+
+/// `.ck/schedule.json`: each task's last run, keyed by [`state_key`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ScheduleState {
+    #[serde(default)]
+    runs: BTreeMap<String, TaskRun>,
+}
+
+/// Tasks are told apart by their cron expression too, so the same task may
+/// be scheduled twice (reindex hourly at night, daily by day).
+fn state_key(scheduled: &ScheduledTask) -> String {
+    format!("{} {}", scheduled.task, scheduled.cron.trim())
+}
+
+fn load_state(root: &Path) -> ScheduleState {
+    std::fs::read(root.join(".ck").join(STATE_FILE))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn record_run(root: &Path, scheduled: &ScheduledTask, run: TaskRun) -> std::io::Result<()> {
+    let mut state = load_state(root);
+    state.runs.insert(state_key(scheduled), run);
+    let path = root.join(".ck").join(STATE_FILE);
+    std::fs::write(path, serde_json::to_vec_pretty(&state)?)
+}
+
+/// A scheduled task as `ck --status` shows it.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatus {
+    pub task: MaintenanceTask,
+    pub cron: String,
+    /// Why the expression cannot be used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<TaskRun>,
+    /// Unix seconds when it is next due; in the past when it is waiting for
+    /// the server to go idle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_due: Option<i64>,
+}
+
+/// The schedule of the index rooted at `root`, with each task's last run and
+/// when it is next due.
+pub fn status(root: &Path) -> Vec<TaskStatus> {
+    let config = ck_models::ProjectConfig::for_path(root);
+    let state = load_state(root);
+    let now = Local::now();
+    config
+        .schedule
+        .into_iter()
+        .map(|scheduled| {
+            let last_run = state.runs.get(&state_key(&scheduled)).cloned();
+            let (error, next_due) = match scheduled.cron.parse::<Cron>() {
+                Ok(cron) => {
+                    let base = last_run
+                        .as_ref()
+                        .and_then(|run| Local.timestamp_opt(run.started, 0).single())
+                        .unwrap_or(now);
+                    (None, cron.next_after(base).map(|due| due.timestamp()))
+                }
+                Err(e) => (Some(e), None),
+            };
+            TaskStatus {
+                task: scheduled.task,
+                cron: scheduled.cron,
+                error,
+                last_run,
+                next_due,
+            }
+        })
+        .collect()
+}
+
+/// Run the scheduled tasks of the indexes at `roots` until the server stops.
+/// A task never run before is first due at its first matching time after
+/// the server started.
+pub async fn run(context: McpContext, roots: Vec<PathBuf>) {
+    let started = Local::now();
+    let mut ticks = tokio::time::interval(TICK);
+    loop {
+        ticks.tick().await;
+        for root in &roots {
+            if !root.join(".ck").is_dir() {
+                continue;
+            }
+            let config = ck_models::ProjectConfig::for_path(root);
+            for scheduled in &config.schedule {
+                let cron = match scheduled.cron.parse::<Cron>() {
+                    Ok(cron) => cron,
+                    Err(e) => {
+                        tracing::debug!("Skipping {} '{}': {}", scheduled.task, scheduled.cron, e);
+                        continue;
+                    }
+                };
+                let state = load_state(root);
+                let base = state
+                    .runs
+                    .get(&state_key(scheduled))
+                    .and_then(|run| Local.timestamp_opt(run.started, 0).single())
+                    .map_or(started, |last| last.max(started));
+                let due = cron.next_after(base).is_some_and(|due| due <= Local::now());
+                if !due || context.idle_for() < IDLE_AFTER {
+                    continue;
+                }
+
+                let lock = context.get_index_lock(root).await;
+                let _guard = lock.lock().await;
+                info!(
+                    "Running scheduled {} for {}",
+                    scheduled.task,
+                    root.display()
+                );
+                let begun = Instant::now();
+                let run_started = Local::now().timestamp();
+                let outcome = run_task(&context, root, scheduled.task).await;
+                let run = TaskRun {
+                    started: run_started,
+                    ok: outcome.is_ok(),
+                    message: outcome.unwrap_or_else(|e| e.to_string()),
+                    duration_ms: begun.elapsed().as_millis() as u64,
+                };
+                if !run.ok {
+                    warn!(
+                        "Scheduled {} for {} failed: {}",
+                        scheduled.task,
+                        root.display(),
+                        run.message
+                    );
+                }
+                if let Err(e) = record_run(root, scheduled, run) {
+                    warn!("Cannot record scheduled run in {}: {}", root.display(), e);
+                }
+            }
+        }
+    }
+}
+
+async fn run_task(
+    context: &McpContext,
+    root: &Path,
+    task: MaintenanceTask,
+) -> anyhow::Result<String> {
+    let options = ck_core::FileCollectionOptions {
+        respect_gitignore: true,
+        use_ckignore: true,
+        exclude_patterns: ck_core::get_default_exclude_patterns(),
+        follow_symlinks: false,
+        files_from: None,
+    };
+    let message = match task {
+        MaintenanceTask::Reindex => {
+            let stats = ck_index::smart_update_index(root, true, &options).await?;
+            let graph_root = root.to_path_buf();
+            tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+                ck_engine::build_ann_index(&graph_root, false)?;
+                ck_engine::build_file_vectors(&graph_root, false)?;
+                Ok(())
+            })
+            .await??;
+            context.stats_cache.invalidate(&root.to_path_buf()).await;
+            format!(
+                "{} files indexed, {} up to date, {} removed",
+                stats.files_indexed, stats.files_up_to_date, stats.orphaned_files_removed
+            )
+        }
+        MaintenanceTask::Compact => {
+            let compact_root = root.to_path_buf();
+            let stats = tokio::task::spawn_blocking(move || {
+                ck_index::compact_index(&compact_root, &options, None)
+            })
+            .await??;
+            context.stats_cache.invalidate(&root.to_path_buf()).await;
+            format!(
+                "{} entries and {} sidecars removed, {:.1} MB reclaimed",
+                stats.entries_removed,
+                stats.sidecars_removed,
+                stats.bytes_before.saturating_sub(stats.bytes_after) as f64 / (1024.0 * 1024.0)
+            )
+        }
+        MaintenanceTask::PruneCache => {
+            context.result_cache.clear();
+            let sessions = context.session_manager.cleanup_expired_sessions().await;
+            context.stats_cache.invalidate(&root.to_path_buf()).await;
+            format!(
+                "result cache cleared, {} expired sessions dropped",
+                sessions
+            )
+        }
+    };
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn test_cron_parsing() {
+        assert!("* * * *".parse::<Cron>().is_err());
+        assert!("60 * * * *".parse::<Cron>().is_err());
+        assert!("*/0 * * * *".parse::<Cron>().is_err());
+        assert!("5-2 * * * *".parse::<Cron>().is_err());
+        assert_eq!(
+            "@daily".parse::<Cron>().unwrap(),
+            "0 0 * * *".parse::<Cron>().unwrap()
+        );
+        assert_eq!(
+            "0 0 * * 7".parse::<Cron>().unwrap(),
+            "0 0 * * 0".parse::<Cron>().unwrap()
+        );
+        let cron: Cron = "*/15 1-5/2,22 * * *".parse().unwrap();
+        assert_eq!(cron.minutes, 1 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(cron.hours, 1 << 1 | 1 << 3 | 1 << 5 | 1 << 22);
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        // Nightly at 02:30
+        let cron: Cron = "30 2 * * *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(local(2026, 3, 10, 1, 0)),
+            Some(local(2026, 3, 10, 2, 30))
+        );
+        assert_eq!(
+            cron.next_after(local(2026, 3, 10, 2, 30)),
+            Some(local(2026, 3, 11, 2, 30))
+        );
+
+        // Weekdays at 23:00; 2026-03-14 is a Saturday
+        let cron: Cron = "0 23 * * 1-5".parse().unwrap();
+        assert_eq!(
+            cron.next_after(local(2026, 3, 13, 23, 30)),
+            Some(local(2026, 3, 16, 23, 0))
+        );
+
+        // Day of month or day of week when both are given: the 1st or any Sunday
+        let cron: Cron = "0 0 1 * 0".parse().unwrap();
+        assert_eq!(
+            cron.next_after(local(2026, 3, 2, 0, 0)),
+            Some(local(2026, 3, 8, 0, 0))
+        );
+
+        assert_eq!(
+            "0 0 31 2 *"
+                .parse::<Cron>()
+                .unwrap()
+                .next_after(local(2026, 1, 1, 0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_status_reports_last_and_next_run() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join(".ck")).unwrap();
+        std::fs::write(
+            root.join(ck_models::PROJECT_CONFIG_FILE),
+            r#"{"schedule": [
+                {"cron": "0 3 * * *", "task": "reindex"},
+                {"cron": "every night", "task": "compact"}
+            ]}"#,
+        )
+        .unwrap();
+        let scheduled = ScheduledTask {
+            cron: "0 3 * * *".to_string(),
+            task: MaintenanceTask::Reindex,
+        };
+        let started = local(2026, 3, 10, 3, 0);
+        record_run(
+            root,
+            &scheduled,
+            TaskRun {
+                started: started.timestamp(),
+                ok: true,
+                message: "done".to_string(),
+                duration_ms: 5,
+            },
+        )
+        .unwrap();
+
+        let tasks = status(root);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].last_run.as_ref().unwrap().message, "done");
+        assert_eq!(
+            tasks[0].next_due,
+            Some(local(2026, 3, 11, 3, 0).timestamp())
+        );
+        assert!(tasks[1].error.is_some());
+        assert!(tasks[1].next_due.is_none());
+    }
+}
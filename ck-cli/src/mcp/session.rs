@@ -2,6 +2,7 @@ use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
@@ -33,7 +34,6 @@ const DEFAULT_SNIPPET_LENGTH: usize = 500;
 pub struct SearchSession {
     #[allow(dead_code)]
     pub id: Uuid,
-    #[allow(dead_code)]
     pub search_options: SearchOptions,
     pub results: Vec<SearchResult>,
     #[allow(dead_code)]
@@ -275,9 +275,12 @@ impl SessionManager {
             return Err("Cursor has expired".to_string());
         }
 
-        // Use the original page size from the cursor to maintain consistency
+        // Use the original page size from the cursor to maintain consistency,
+        // but never more than this request allows
         let mut adjusted_config = config;
-        adjusted_config.page_size = parsed_cursor.original_page_size;
+        adjusted_config.page_size = parsed_cursor
+            .original_page_size
+            .min(adjusted_config.page_size);
 
         self.get_page(
             parsed_cursor.session_id,
@@ -287,6 +290,17 @@ impl SessionManager {
         .await
     }
 
+    /// Path the search behind `cursor` ran on
+    pub async fn cursor_path(&self, cursor: &str) -> Result<PathBuf, String> {
+        let parsed_cursor = self.parse_cursor(cursor)?;
+        self.sessions
+            .read()
+            .await
+            .get(&parsed_cursor.session_id)
+            .map(|session| session.search_options.path.clone())
+            .ok_or_else(|| "Session not found or expired".to_string())
+    }
+
     /// Create a base64-encoded cursor
     fn create_cursor(
         &self,
@@ -435,7 +449,6 @@ pub struct SessionStats {
 mod tests {
     use super::*;
     use ck_core::{Language, SearchMode};
-    use std::path::PathBuf;
 
     fn create_test_search_options() -> SearchOptions {
         SearchOptions {
@@ -448,6 +461,7 @@ mod tests {
             whole_word: false,
             fixed_string: false,
             line_numbers: false,
+            column: false,
             context_lines: 0,
             before_context_lines: 0,
             after_context_lines: 0,
@@ -457,6 +471,7 @@ mod tests {
             no_snippet: false,
             reindex: false,
             show_scores: true,
+            explain: false,
             show_filenames: true,
             files_with_matches: false,
             files_without_matches: false,
@@ -467,26 +482,38 @@ mod tests {
             full_section: false,
             rerank: false,
             rerank_model: None,
+            rerank_scores: None,
+            rerank_top: None,
+            rerank_budget: None,
+            ann_oversample: None,
             embedding_model: None,
+            languages: Vec::new(),
+            feedback: Default::default(),
+            hyde: false,
+            top_files: None,
+            by_file: false,
+            score_normalization: ck_core::ScoreNormalization::Rank,
+            follow_symlinks: false,
+            as_of: None,
+            blame: false,
+            owner: None,
+            meta: Vec::new(),
+            prefer: None,
+            cancel: None,
+            instruction: None,
         }
     }
 
     fn create_test_results(count: usize) -> Vec<SearchResult> {
         (0..count)
             .map(|i| SearchResult {
-                file: PathBuf::from(format!("/test/file_{}.rs", i)),
-                preview: format!("Test result {} content", i),
-                span: ck_core::Span {
-                    byte_start: i * 100,
-                    byte_end: (i + 1) * 100,
-                    line_start: i + 1,
-                    line_end: i + 1,
-                },
-                score: 0.8 - (i as f32 * 0.01),
                 lang: Some(Language::Rust),
-                symbol: None,
-                chunk_hash: None,
-                index_epoch: None,
+                ..SearchResult::new(
+                    format!("/test/file_{}.rs", i),
+                    ck_core::Span::new_unchecked(i * 100, (i + 1) * 100, i + 1, i + 1),
+                    0.8 - (i as f32 * 0.01),
+                    format!("Test result {} content", i),
+                )
             })
             .collect()
     }
@@ -542,6 +569,33 @@ mod tests {
         assert!(page2.next_cursor.is_none());
     }
 
+    #[tokio::test]
+    async fn test_cursor_page_size_never_exceeds_request() {
+        let manager = SessionManager::default();
+        let results = create_test_results(75);
+        let page1 = manager
+            .get_first_page(
+                create_test_search_options(),
+                results,
+                PaginationConfig::default(),
+            )
+            .await
+            .unwrap();
+        let cursor = page1.next_cursor.unwrap();
+        assert_eq!(
+            manager.cursor_path(&cursor).await.unwrap(),
+            PathBuf::from("/test/path")
+        );
+
+        let capped = PaginationConfig {
+            page_size: 10,
+            ..Default::default()
+        };
+        let page2 = manager.get_page_by_cursor(&cursor, capped).await.unwrap();
+        assert_eq!(page2.count, 10);
+        assert!(page2.has_more);
+    }
+
     #[tokio::test]
     async fn test_cursor_validation() {
         let manager = SessionManager::default();
@@ -20,7 +20,9 @@ use std::time::Instant;
 use tracing::info;
 use walkdir::WalkDir;
 
+use crate::mcp::audit::{self, AuditLog, AuditRecord};
 use crate::mcp::context::McpContext;
+use crate::mcp::namespaces::Namespaces;
 use crate::mcp::session::{PaginationConfig, SearchPage};
 use crate::path_utils::{build_include_patterns, expand_glob_patterns_with_base};
 use ck_core::{
@@ -141,6 +143,7 @@ pub struct SemanticSearchRequest {
     pub use_default_excludes: Option<bool>,
     pub rerank: Option<bool>,
     pub rerank_model: Option<String>,
+    pub instruct: Option<String>,
     pub case_insensitive: Option<bool>,
     pub whole_word: Option<bool>,
     pub fixed_string: Option<bool>,
@@ -185,6 +188,7 @@ pub struct HybridSearchRequest {
     pub use_default_excludes: Option<bool>,
     pub rerank: Option<bool>,
     pub rerank_model: Option<String>,
+    pub instruct: Option<String>,
     pub case_insensitive: Option<bool>,
     pub whole_word: Option<bool>,
     pub fixed_string: Option<bool>,
@@ -257,6 +261,7 @@ impl PaginationParams for SemanticSearchRequest {
             "threshold": self.threshold.unwrap_or(0.6),
             "rerank": self.rerank.unwrap_or(false),
             "rerank_model": self.rerank_model,
+            "instruct": self.instruct,
             "case_insensitive": self.case_insensitive.unwrap_or(false),
             "whole_word": self.whole_word.unwrap_or(false),
             "fixed_string": self.fixed_string.unwrap_or(false),
@@ -333,6 +338,7 @@ impl PaginationParams for HybridSearchRequest {
             "threshold": self.threshold.unwrap_or(0.02),
             "rerank": self.rerank.unwrap_or(false),
             "rerank_model": self.rerank_model,
+            "instruct": self.instruct,
             "case_insensitive": self.case_insensitive.unwrap_or(false),
             "whole_word": self.whole_word.unwrap_or(false),
             "fixed_string": self.fixed_string.unwrap_or(false),
@@ -392,6 +398,8 @@ impl PaginationParams for LexicalSearchRequest {
 pub struct CkMcpServer {
     context: McpContext,
     tool_router: ToolRouter<Self>,
+    audit_log: Option<Arc<AuditLog>>,
+    namespaces: Option<Arc<Namespaces>>,
 }
 
 impl ServerHandler for CkMcpServer {
@@ -451,17 +459,76 @@ All search tools support:
 
     async fn call_tool(
         &self,
-        request: CallToolRequestParam,
+        mut request: CallToolRequestParam,
         context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, ErrorData> {
+        let started = Instant::now();
+        // Scope the call to its namespace first, so keys never reach the log
+        let mut routed = Ok(None);
+        if let Some(namespaces) = &self.namespaces {
+            let mut arguments = request.arguments.take().unwrap_or_default();
+            routed = namespaces.route(&request.name, &mut arguments);
+            if let Ok(Some(name)) = &routed
+                && let Some(cursor) = arguments.get("cursor").and_then(Value::as_str)
+                && !self.cursor_in(cursor, namespaces.root(name)).await
+            {
+                routed = Err(ErrorData::invalid_params(
+                    "cursor belongs to a search in another namespace",
+                    None,
+                ));
+            }
+            request.arguments = Some(arguments);
+        }
+        let namespace = routed.as_ref().ok().cloned().flatten();
+        let audited = self.audit_log.as_ref().map(|_| {
+            let client = context
+                .peer
+                .peer_info()
+                .map(|info| format!("{} {}", info.client_info.name, info.client_info.version));
+            (client, request.name.to_string(), request.arguments.clone())
+        });
+
         let tool_context = ToolCallContext::new(self, request, context);
-        if let Some(route) = self.tool_router.map.get(&tool_context.name) {
+        let active = self.context.begin_call();
+        let result = if let Err(e) = routed {
+            Err(e)
+        } else if let Some(route) = self.tool_router.map.get(&tool_context.name) {
             (route.call)(tool_context).await
         } else {
             Err(ErrorData::method_not_found::<
                 rmcp::model::CallToolRequestMethod,
             >())
+        };
+        drop(active);
+
+        if let (Some(log), Some((client, tool, arguments))) = (&self.audit_log, audited) {
+            let record = AuditRecord {
+                time: chrono::Utc::now().to_rfc3339(),
+                client,
+                user: audit::current_user(),
+                namespace,
+                tool,
+                arguments: arguments.unwrap_or_default(),
+                ok: result.is_ok(),
+                error: result.as_ref().err().map(|e| e.message.to_string()),
+                files: AuditRecord::files_in(
+                    result
+                        .as_ref()
+                        .ok()
+                        .and_then(|r| r.structured_content.as_ref()),
+                ),
+                duration_ms: started.elapsed().as_millis() as u64,
+            };
+            // Results are withheld rather than returned unaudited
+            if let Err(e) = log.record(&record) {
+                tracing::error!("Cannot write audit log {}: {}", log.path().display(), e);
+                return Err(ErrorData::internal_error(
+                    format!("Audit log unavailable: {}", e),
+                    None,
+                ));
+            }
         }
+        result
     }
 
     async fn list_tools(
@@ -473,7 +540,13 @@ All search tools support:
             .tool_router
             .map
             .values()
-            .map(|route| route.attr.clone())
+            .map(|route| {
+                let mut tool = route.attr.clone();
+                if let Some(namespaces) = &self.namespaces {
+                    namespaces.add_to_schema(&mut tool);
+                }
+                tool
+            })
             .collect();
         Ok(ListToolsResult {
             tools,
@@ -489,9 +562,33 @@ impl CkMcpServer {
         Ok(Self {
             context,
             tool_router,
+            audit_log: None,
+            namespaces: None,
         })
     }
 
+    /// Serve `namespaces` instead of the working directory: every call must
+    /// name one, and its paths stay inside it.
+    pub fn with_namespaces(mut self, namespaces: Namespaces) -> Self {
+        self.namespaces = Some(Arc::new(namespaces));
+        self
+    }
+
+    /// Whether the search `cursor` pages through ran inside `root`. Unknown
+    /// cursors pass, to fail with their own error when the page is read.
+    async fn cursor_in(&self, cursor: &str, root: Option<&Path>) -> bool {
+        match self.context.session_manager.cursor_path(cursor).await {
+            Ok(path) => root.is_some_and(|root| path.starts_with(root)),
+            Err(_) => true,
+        }
+    }
+
+    /// Record every tool call in `log`.
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit_log = Some(Arc::new(log));
+        self
+    }
+
     /// Extract pagination configuration from request parameters
     fn extract_pagination_config(
         page_size: Option<usize>,
@@ -528,7 +625,9 @@ impl CkMcpServer {
                         "byte_start": result.span.byte_start,
                         "byte_end": result.span.byte_end,
                         "line_start": result.span.line_start,
-                        "line_end": result.span.line_end
+                        "line_end": result.span.line_end,
+                        "col_start": result.span.col_start,
+                        "col_end": result.span.col_end
                     },
                     "content": result.preview
                 },
@@ -582,8 +681,9 @@ impl CkMcpServer {
     where
         T: PaginationParams,
     {
+        // Without a page_size the cursor's own applies, up to the maximum
         let config = Self::extract_pagination_config(
-            request.get_page_size(),
+            Some(request.get_page_size().unwrap_or(usize::MAX)),
             request.get_include_snippet(),
             request.get_snippet_length(),
             request.get_context_lines(),
@@ -966,6 +1066,22 @@ impl CkMcpServer {
     pub async fn run(&self) -> Result<()> {
         info!("Starting ck MCP server");
 
+        let roots: Vec<PathBuf> = match &self.namespaces {
+            Some(namespaces) => namespaces.roots().map(Path::to_path_buf).collect(),
+            None => vec![self.context.cwd.clone()],
+        };
+        let scheduled: Vec<PathBuf> = roots
+            .into_iter()
+            .filter(|root| !ck_models::ProjectConfig::for_path(root).schedule.is_empty())
+            .collect();
+        if !scheduled.is_empty() {
+            if ck_engine::is_read_only() {
+                info!("Read-only: scheduled maintenance is not run");
+            } else {
+                tokio::spawn(crate::mcp::schedule::run(self.context.clone(), scheduled));
+            }
+        }
+
         let stdio_transport = transport::stdio();
         let running_service = self.clone().serve(stdio_transport).await?;
         running_service.waiting().await?;
@@ -1071,6 +1187,7 @@ impl CkMcpServer {
             whole_word: request.whole_word.unwrap_or(false),
             fixed_string: request.fixed_string.unwrap_or(false),
             line_numbers: false,
+            column: false,
             context_lines,
             before_context_lines,
             after_context_lines,
@@ -1080,6 +1197,7 @@ impl CkMcpServer {
             no_snippet: !include_snippet,
             reindex: false,
             show_scores: true,
+            explain: false,
             show_filenames: true,
             files_with_matches: false,
             files_without_matches: false,
@@ -1090,83 +1208,109 @@ impl CkMcpServer {
             full_section: false,
             rerank: request.rerank.unwrap_or(false),
             rerank_model: request.rerank_model.clone(),
+            rerank_scores: None,
+            rerank_top: None,
+            rerank_budget: None,
+            ann_oversample: None,
             embedding_model: None,
+            languages: Vec::new(),
+            feedback: Default::default(),
+            hyde: false,
+            top_files: None,
+            by_file: false,
+            score_normalization: ck_core::ScoreNormalization::Rank,
+            follow_symlinks: false,
+            as_of: None,
+            blame: false,
+            owner: None,
+            meta: Vec::new(),
+            prefer: None,
+            cancel: None,
+            instruction: request.instruct.clone(),
         };
 
-        // Note: Embedders are created fresh for each request by ck-engine
-        // Caching would require exposing search APIs that accept pre-created embedders
-
         // Perform the search with progress reporting
         let mut indexing_progress_callback = indexing_progress_callback;
         let mut effective_mode: Option<String> = None;
         let started = Instant::now();
-        let search_results = match ck_engine::search_enhanced_with_indexing_progress(
-            &options,
-            None,
-            indexing_progress_callback.take(),
-            None,
-        )
-        .await
-        {
-            Ok(results) => results,
-            Err(e) => {
-                let message = e.to_string();
-                if message.contains("No embeddings found") {
-                    tracing::warn!(
-                        "semantic search missing embeddings, attempting reindex: {}",
-                        message
-                    );
-                    let mut reindex_options = options.clone();
-                    reindex_options.reindex = true;
-                    match ck_engine::search_enhanced_with_indexing_progress(
-                        &reindex_options,
-                        None,
-                        None,
-                        None,
-                    )
-                    .await
-                    {
-                        Ok(results) => results,
-                        Err(retry_err) => {
-                            tracing::warn!("semantic search failed after reindex: {}", retry_err);
-                            // Fallback to lexical search when embeddings are unavailable
-                            let mut fallback_options = options.clone();
-                            fallback_options.mode = SearchMode::Lexical;
-                            fallback_options.reindex = true;
-                            match ck_engine::search_enhanced_with_indexing_progress(
-                                &fallback_options,
-                                None,
-                                None,
-                                None,
-                            )
-                            .await
-                            {
-                                Ok(mut lexical_results) => {
-                                    if let Some(limit) = top_k {
+        let cached = self.context.result_cache.get(&options);
+        ck_engine::record_cache_lookup(&options, cached.is_some());
+        let search_results = match cached {
+            Some(cached) => cached,
+            None => match ck_engine::search_enhanced_with_indexing_progress(
+                &options,
+                None,
+                indexing_progress_callback.take(),
+                None,
+            )
+            .await
+            {
+                Ok(results) => results,
+                Err(e) => {
+                    let message = e.to_string();
+                    if message.contains("No embeddings found") {
+                        tracing::warn!(
+                            "semantic search missing embeddings, attempting reindex: {}",
+                            message
+                        );
+                        let mut reindex_options = options.clone();
+                        reindex_options.reindex = true;
+                        match ck_engine::search_enhanced_with_indexing_progress(
+                            &reindex_options,
+                            None,
+                            None,
+                            None,
+                        )
+                        .await
+                        {
+                            Ok(results) => results,
+                            Err(retry_err) => {
+                                tracing::warn!(
+                                    "semantic search failed after reindex: {}",
+                                    retry_err
+                                );
+                                // Fallback to lexical search when embeddings are unavailable
+                                let mut fallback_options = options.clone();
+                                fallback_options.mode = SearchMode::Lexical;
+                                fallback_options.reindex = true;
+                                match ck_engine::search_enhanced_with_indexing_progress(
+                                    &fallback_options,
+                                    None,
+                                    None,
+                                    None,
+                                )
+                                .await
+                                {
+                                    Ok(mut lexical_results) => {
+                                        if let Some(limit) = top_k {
+                                            lexical_results
+                                                .matches
+                                                .truncate(limit.min(lexical_results.matches.len()));
+                                        }
+                                        effective_mode =
+                                            Some("semantic (lexical fallback)".to_string());
                                         lexical_results
-                                            .matches
-                                            .truncate(limit.min(lexical_results.matches.len()));
                                     }
-                                    effective_mode =
-                                        Some("semantic (lexical fallback)".to_string());
-                                    lexical_results
-                                }
-                                Err(final_err) => {
-                                    return Err(ErrorData::internal_error(
-                                        final_err.to_string(),
-                                        None,
-                                    ));
+                                    Err(final_err) => {
+                                        return Err(ErrorData::internal_error(
+                                            final_err.to_string(),
+                                            None,
+                                        ));
+                                    }
                                 }
                             }
                         }
+                    } else {
+                        tracing::warn!("semantic search failed: {}", message);
+                        return Err(ErrorData::internal_error(message, None));
                     }
-                } else {
-                    tracing::warn!("semantic search failed: {}", message);
-                    return Err(ErrorData::internal_error(message, None));
                 }
-            }
+            },
         };
         let elapsed_ms = started.elapsed().as_millis() as u64;
+        if effective_mode.is_none() {
+            self.context.result_cache.insert(&options, &search_results);
+        }
 
         // Create session and get first page
         let page = self
@@ -1278,6 +1422,7 @@ impl CkMcpServer {
             whole_word: request.whole_word.unwrap_or(false),
             fixed_string: request.fixed_string.unwrap_or(false),
             line_numbers: false,
+            column: false,
             context_lines,
             before_context_lines,
             after_context_lines,
@@ -1287,6 +1432,7 @@ impl CkMcpServer {
             no_snippet: !include_snippet,
             reindex: false,
             show_scores: true,
+            explain: false,
             show_filenames: true,
             files_with_matches: false,
             files_without_matches: false,
@@ -1297,18 +1443,43 @@ impl CkMcpServer {
             full_section: false,
             rerank: false,
             rerank_model: None,
+            rerank_scores: None,
+            rerank_top: None,
+            rerank_budget: None,
+            ann_oversample: None,
             embedding_model: None,
+            languages: Vec::new(),
+            feedback: Default::default(),
+            hyde: false,
+            top_files: None,
+            by_file: false,
+            score_normalization: ck_core::ScoreNormalization::Rank,
+            follow_symlinks: false,
+            as_of: None,
+            blame: false,
+            owner: None,
+            meta: Vec::new(),
+            prefer: None,
+            cancel: None,
+            instruction: None,
         };
 
         let started = Instant::now();
-        let search_results =
-            match ck_engine::search_enhanced_with_indexing_progress(&options, None, None, None)
-                .await
-            {
-                Ok(results) => results,
-                Err(e) => return Err(ErrorData::internal_error(e.to_string(), None)),
-            };
+        let cached = self.context.result_cache.get(&options);
+        ck_engine::record_cache_lookup(&options, cached.is_some());
+        let search_results = match cached {
+            Some(cached) => cached,
+            None => {
+                match ck_engine::search_enhanced_with_indexing_progress(&options, None, None, None)
+                    .await
+                {
+                    Ok(results) => results,
+                    Err(e) => return Err(ErrorData::internal_error(e.to_string(), None)),
+                }
+            }
+        };
         let elapsed_ms = started.elapsed().as_millis() as u64;
+        self.context.result_cache.insert(&options, &search_results);
 
         let page = self
             .context
@@ -1413,6 +1584,7 @@ impl CkMcpServer {
             whole_word: request.whole_word.unwrap_or(false),
             fixed_string: request.fixed_string.unwrap_or(false),
             line_numbers: true,
+            column: false,
             context_lines,
             before_context_lines: context_lines,
             after_context_lines: context_lines,
@@ -1422,6 +1594,7 @@ impl CkMcpServer {
             no_snippet: !include_snippet,
             reindex: false,
             show_scores: false, // No scores for regex search
+            explain: false,
             show_filenames: true,
             files_with_matches: false,
             files_without_matches: false,
@@ -1432,7 +1605,25 @@ impl CkMcpServer {
             full_section: false,
             rerank: false,
             rerank_model: None,
+            rerank_scores: None,
+            rerank_top: None,
+            rerank_budget: None,
+            ann_oversample: None,
             embedding_model: None,
+            languages: Vec::new(),
+            feedback: Default::default(),
+            hyde: false,
+            top_files: None,
+            by_file: false,
+            score_normalization: ck_core::ScoreNormalization::Rank,
+            follow_symlinks: false,
+            as_of: None,
+            blame: false,
+            owner: None,
+            meta: Vec::new(),
+            prefer: None,
+            cancel: None,
+            instruction: None,
         };
 
         // Perform the search (no indexing needed for regex)
@@ -1548,6 +1739,7 @@ impl CkMcpServer {
             whole_word: request.whole_word.unwrap_or(false),
             fixed_string: request.fixed_string.unwrap_or(false),
             line_numbers: false,
+            column: false,
             context_lines,
             before_context_lines,
             after_context_lines,
@@ -1557,6 +1749,7 @@ impl CkMcpServer {
             no_snippet: !include_snippet,
             reindex: false,
             show_scores: true,
+            explain: false,
             show_filenames: true,
             files_with_matches: false,
             files_without_matches: false,
@@ -1567,7 +1760,25 @@ impl CkMcpServer {
             full_section: false,
             rerank: request.rerank.unwrap_or(false),
             rerank_model: request.rerank_model.clone(),
+            rerank_scores: None,
+            rerank_top: None,
+            rerank_budget: None,
+            ann_oversample: None,
             embedding_model: None,
+            languages: Vec::new(),
+            feedback: Default::default(),
+            hyde: false,
+            top_files: None,
+            by_file: false,
+            score_normalization: ck_core::ScoreNormalization::Rank,
+            follow_symlinks: false,
+            as_of: None,
+            blame: false,
+            owner: None,
+            meta: Vec::new(),
+            prefer: None,
+            cancel: None,
+            instruction: request.instruct.clone(),
         };
 
         // Perform the search (suppress progress callbacks for MCP)
@@ -1682,7 +1893,7 @@ impl CkMcpServer {
                     && let Ok(manifest) = serde_json::from_slice::<ck_index::IndexManifest>(&data)
                     && let Some(model_name) = manifest.embedding_model
                 {
-                    let registry = ck_models::ModelRegistry::default();
+                    let registry = ck_models::ModelRegistry::with_user_models();
                     let alias = registry
                         .models
                         .iter()
@@ -1784,6 +1995,13 @@ impl CkMcpServer {
         let force = request.force.unwrap_or(false);
         let path_buf = PathBuf::from(path);
 
+        if ck_engine::is_read_only() {
+            return Err(ErrorData::invalid_request(
+                "The server was started with --read-only and does not update indexes",
+                None,
+            ));
+        }
+
         // Validate path exists
         if !path_buf.exists() {
             return Err(ErrorData::invalid_params(
@@ -1836,6 +2054,7 @@ impl CkMcpServer {
             whole_word: false,
             fixed_string: false,
             line_numbers: false,
+            column: false,
             context_lines: 0,
             before_context_lines: 0,
             after_context_lines: 0,
@@ -1845,6 +2064,7 @@ impl CkMcpServer {
             no_snippet: false,
             reindex: force, // Use the force parameter directly
             show_scores: false,
+            explain: false,
             show_filenames: false,
             files_with_matches: false,
             files_without_matches: false,
@@ -1855,7 +2075,25 @@ impl CkMcpServer {
             full_section: false,
             rerank: false,
             rerank_model: None,
+            rerank_scores: None,
+            rerank_top: None,
+            rerank_budget: None,
+            ann_oversample: None,
             embedding_model: None,
+            languages: Vec::new(),
+            feedback: Default::default(),
+            hyde: false,
+            top_files: None,
+            by_file: false,
+            score_normalization: ck_core::ScoreNormalization::Rank,
+            follow_symlinks: false,
+            as_of: None,
+            blame: false,
+            owner: None,
+            meta: Vec::new(),
+            prefer: None,
+            cancel: None,
+            instruction: None,
         };
 
         // Perform reindexing
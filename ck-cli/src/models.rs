@@ -0,0 +1,200 @@
+//! Bring-your-own embedding models (`ck --add-model ALIAS
+//! --onnx|--gguf|--endpoint ...`): probe an ONNX encoder, a GGUF model or an
+//! OpenAI-compatible endpoint and record it in the user model registry so
+//! `--model ALIAS` works like a built-in.
+
+use anyhow::{Result, bail};
+use ck_models::{ModelConfig, ModelRegistry, OnnxSource, Pooling, Truncation, UserModels};
+use std::path::{Path, PathBuf};
+
+use crate::progress::StatusReporter;
+
+/// Token limit recorded for added models; edit the registry entry to change it.
+const DEFAULT_MAX_TOKENS: usize = 512;
+
+/// Parse `--dims`: `auto` (detect from the model) or a positive width.
+pub fn parse_dims(value: &str) -> std::result::Result<usize, String> {
+    if value.eq_ignore_ascii_case("auto") {
+        return Ok(0);
+    }
+    match value.parse::<usize>() {
+        Ok(dims) if dims > 0 => Ok(dims),
+        _ => Err(format!(
+            "Invalid dimensions '{value}'; use auto or a positive number"
+        )),
+    }
+}
+
+/// Local files are recorded as absolute paths so the model resolves from any
+/// directory; anything else is kept as a Hugging Face reference.
+fn absolute_source(spec: &str) -> String {
+    let path = Path::new(spec);
+    if path.is_file() {
+        path.canonicalize()
+            .map(|path| ck_core::paths::strip_verbatim(&path))
+            .unwrap_or_else(|_| path.to_path_buf())
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        spec.to_string()
+    }
+}
+
+/// Registry entry for an ONNX encoder run by ONNX Runtime (`onnx` provider).
+pub fn onnx_config(
+    alias: &str,
+    model: &str,
+    tokenizer: Option<&str>,
+    pooling: Option<Pooling>,
+    truncation: Truncation,
+) -> ModelConfig {
+    ModelConfig {
+        name: alias.to_string(),
+        provider: "onnx".to_string(),
+        dimensions: 0,
+        max_tokens: DEFAULT_MAX_TOKENS,
+        truncation,
+        description: match pooling {
+            Some(pooling) => format!("User ONNX model ({} pooling)", pooling),
+            None => "User ONNX model".to_string(),
+        },
+        onnx: Some(OnnxSource {
+            model: absolute_source(model),
+            tokenizer: tokenizer.map(absolute_source),
+            pooling,
+        }),
+        normalize: true,
+        strict_dimensions: true,
+        query_instruction: None,
+        document_instruction: None,
+        remote: Default::default(),
+    }
+}
+
+/// Registry entry for a GGUF model served by a `llama-server` ck starts
+/// (`llama-server` provider); the source itself is the model name, as it is
+/// for Hugging Face models.
+pub fn gguf_config(model: &str) -> ModelConfig {
+    ModelConfig {
+        name: absolute_source(model),
+        provider: "llama-server".to_string(),
+        dimensions: 0,
+        max_tokens: DEFAULT_MAX_TOKENS,
+        truncation: Truncation::Head,
+        description: "User GGUF model (llama-server)".to_string(),
+        onnx: None,
+        normalize: true,
+        strict_dimensions: true,
+        query_instruction: None,
+        document_instruction: None,
+        remote: Default::default(),
+    }
+}
+
+/// Registry entry for the model `name` of the OpenAI-compatible endpoint at
+/// `url` (`openai` provider).
+pub fn endpoint_config(name: &str, url: &str) -> ModelConfig {
+    ModelConfig {
+        name: name.to_string(),
+        provider: "openai".to_string(),
+        dimensions: 0,
+        max_tokens: DEFAULT_MAX_TOKENS,
+        truncation: Truncation::Head,
+        description: format!("User endpoint model ({})", url),
+        onnx: None,
+        normalize: true,
+        strict_dimensions: true,
+        query_instruction: None,
+        document_instruction: None,
+        remote: ck_models::RemoteConfig {
+            url: Some(url.to_string()),
+            ..Default::default()
+        },
+    }
+}
+
+/// Load the model, embed a probe sentence, and save the entry (with its measured
+/// width) to the user registry. `dimensions` of 0 accepts whatever width the
+/// model produces. Returns the registry path.
+pub fn add_model(
+    alias: &str,
+    config: ModelConfig,
+    dimensions: usize,
+    status: &StatusReporter,
+) -> Result<PathBuf> {
+    if ModelRegistry::default().models.contains_key(alias) {
+        bail!("'{}' is a built-in model alias; choose another name", alias);
+    }
+    let Some(path) = ck_models::user_models_path() else {
+        bail!("Cannot locate a config directory for the user model registry; set XDG_CONFIG_HOME");
+    };
+    let config = ModelConfig {
+        dimensions,
+        ..config
+    };
+
+    let spinner = status.create_spinner(&format!("Probing {}...", alias));
+    let probe = probe(&config);
+    status.finish_progress(spinner, "Probe complete");
+    let detected = probe?;
+
+    let mut user = UserModels::load(&path)?;
+    let replaced = user
+        .models
+        .insert(
+            alias.to_string(),
+            ModelConfig {
+                dimensions: detected,
+                ..config
+            },
+        )
+        .is_some();
+    user.save(&path)?;
+
+    if replaced {
+        status.info(&format!("Replaced the existing '{}' entry", alias));
+    }
+    status.info(&format!(
+        "{} produces {}-dimensional embeddings",
+        alias, detected
+    ));
+    Ok(path)
+}
+
+/// Embed one sentence and check the vector is usable, returning its width.
+fn probe(config: &ModelConfig) -> Result<usize> {
+    let mut embedder = ck_embed::create_embedder_for_config(config, None)?;
+    let vectors = embedder.embed(&["fn main() { println!(\"hello\"); }".to_string()])?;
+    let Some(vector) = vectors.first() else {
+        bail!(
+            "Model '{}' returned no embedding for the probe",
+            config.name
+        );
+    };
+    if vector.is_empty() || vector.iter().any(|value| !value.is_finite()) {
+        bail!(
+            "Model '{}' returned an empty or non-finite embedding for the probe",
+            config.name
+        );
+    }
+    if vector.iter().all(|value| *value == 0.0) {
+        bail!(
+            "Model '{}' returned an all-zero embedding; check --pooling and that the graph's first output is the embedding",
+            config.name
+        );
+    }
+    Ok(vector.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dims() {
+        assert_eq!(parse_dims("auto"), Ok(0));
+        assert_eq!(parse_dims("768"), Ok(768));
+        assert!(parse_dims("0").is_err());
+        assert!(parse_dims("wide").is_err());
+    }
+}
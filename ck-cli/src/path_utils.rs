@@ -150,13 +150,7 @@ fn push_if_new(acc: &mut Vec<PathBuf>, candidate: PathBuf) {
 }
 
 fn canonicalize_lossy(path: &Path) -> PathBuf {
-    if let Ok(canonical) = path.canonicalize() {
-        return canonical;
-    }
-
-    std::env::current_dir()
-        .map(|cwd| cwd.join(path))
-        .unwrap_or_else(|_| path.to_path_buf())
+    ck_core::paths::canonicalize(path)
 }
 
 fn should_exclude_path(path: &Path, globset: &GlobSet, base_dir: Option<&Path>) -> bool {
@@ -0,0 +1,195 @@
+//! Named query presets (`ck --save-as NAME ...` / `ck @NAME`) stored in the
+//! project's `.ckconfig.json` so a team can share canned investigations.
+
+use anyhow::{Result, anyhow, bail};
+use ck_core::SearchMode;
+use ck_models::{ProjectConfig, QueryPreset};
+use std::path::{Path, PathBuf};
+
+use crate::Cli;
+
+/// Returns the preset name when `pattern` is an `@name` reference.
+pub fn preset_reference(pattern: &str) -> Option<&str> {
+    pattern.strip_prefix('@').filter(|name| !name.is_empty())
+}
+
+fn config_start(cli: &Cli) -> PathBuf {
+    cli.files
+        .first()
+        .filter(|p| p.exists())
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+fn cli_mode(cli: &Cli) -> Option<String> {
+    if cli.semantic {
+        Some("sem".to_string())
+    } else if cli.lexical {
+        Some("lex".to_string())
+    } else if cli.hybrid {
+        Some("hybrid".to_string())
+    } else if cli.symbol {
+        Some("sym".to_string())
+    } else if cli.regex {
+        Some("regex".to_string())
+    } else {
+        None
+    }
+}
+
+/// Replace an `@name` pattern with the stored preset. Flags given explicitly on
+/// the command line take precedence over the preset's values.
+pub fn expand_preset(cli: &mut Cli) -> Result<Option<String>> {
+    let Some(name) = cli.pattern.as_deref().and_then(preset_reference) else {
+        return Ok(None);
+    };
+    let name = name.to_string();
+
+    let config_path = ProjectConfig::find(&config_start(cli)).ok_or_else(|| {
+        anyhow!(
+            "Preset '@{}' not found: no {} in this directory or its parents. Save one with --save-as {}",
+            name,
+            ck_models::PROJECT_CONFIG_FILE,
+            name
+        )
+    })?;
+    let config = ProjectConfig::load(&config_path)?;
+    let preset = config.presets.get(&name).ok_or_else(|| {
+        let available = config.presets.keys().cloned().collect::<Vec<_>>();
+        if available.is_empty() {
+            anyhow!(
+                "Unknown preset '@{}'; {} defines no presets",
+                name,
+                config_path.display()
+            )
+        } else {
+            anyhow!(
+                "Unknown preset '@{}'. Available presets: {}",
+                name,
+                available.join(", ")
+            )
+        }
+    })?;
+
+    cli.pattern = Some(preset.query.clone());
+
+    if cli_mode(cli).is_none()
+        && let Some(mode) = preset.mode.as_deref()
+    {
+        match mode.parse::<SearchMode>().map_err(anyhow::Error::msg)? {
+            SearchMode::Semantic => cli.semantic = true,
+            SearchMode::Lexical => cli.lexical = true,
+            SearchMode::Hybrid => cli.hybrid = true,
+            SearchMode::Symbol => cli.symbol = true,
+            SearchMode::Regex => cli.regex = true,
+        }
+    }
+
+    if cli.files.is_empty() {
+        let base = config_path.parent().unwrap_or(Path::new("."));
+        cli.files = preset
+            .paths
+            .iter()
+            .map(|p| display_relative(&base.join(p)))
+            .collect();
+    }
+
+    cli.top_k = cli.top_k.or(preset.top_k);
+    cli.threshold = cli.threshold.or(preset.threshold);
+    cli.rerank |= preset.rerank;
+    if cli.rerank_model.is_none() {
+        cli.rerank_model = preset.rerank_model.clone();
+    }
+    cli.rerank_scores = cli.rerank_scores.or(preset.rerank_scores);
+    cli.rerank_top = cli.rerank_top.or(preset.rerank_top);
+    cli.rerank_budget = cli.rerank_budget.or(preset.rerank_budget_ms);
+    cli.ignore_case |= preset.case_insensitive;
+    cli.word_regexp |= preset.whole_word;
+    cli.fixed_strings |= preset.fixed_string;
+    for pattern in &preset.exclude {
+        if !cli.exclude.contains(pattern) {
+            cli.exclude.push(pattern.clone());
+        }
+    }
+    if cli.lang.is_empty() {
+        cli.lang = preset
+            .languages
+            .iter()
+            .map(|lang| lang.parse().map_err(|e: String| anyhow!(e)))
+            .collect::<Result<_>>()?;
+    }
+
+    Ok(Some(name))
+}
+
+/// Store the current search (after any preset expansion) under `name`.
+pub fn save_preset(cli: &Cli, name: &str) -> Result<PathBuf> {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        bail!(
+            "Invalid preset name '{}': must be non-empty without whitespace",
+            name
+        );
+    }
+    let query = cli
+        .pattern
+        .clone()
+        .ok_or_else(|| anyhow!("--save-as requires a search pattern"))?;
+
+    let config_path = ProjectConfig::locate(&config_start(cli));
+    let mut config = ProjectConfig::load(&config_path)?;
+    let base = config_path.parent().unwrap_or(Path::new("."));
+
+    let preset = QueryPreset {
+        query,
+        mode: cli_mode(cli),
+        paths: cli
+            .files
+            .iter()
+            .map(|p| ck_core::paths::to_slash(&relative_to(base, p)))
+            .collect(),
+        exclude: cli.exclude.clone(),
+        languages: cli.lang.iter().map(ToString::to_string).collect(),
+        top_k: cli.top_k,
+        threshold: cli.threshold,
+        rerank: cli.rerank,
+        rerank_model: cli.rerank_model.clone(),
+        rerank_scores: cli.rerank_scores,
+        rerank_top: cli.rerank_top,
+        rerank_budget_ms: cli.rerank_budget,
+        case_insensitive: cli.ignore_case,
+        whole_word: cli.word_regexp,
+        fixed_string: cli.fixed_strings,
+    };
+
+    config.presets.insert(name.to_string(), preset);
+    config.save(&config_path)?;
+    Ok(config_path)
+}
+
+fn relative_to(base: &Path, path: &Path) -> PathBuf {
+    ck_core::paths::relative_to(path, base).unwrap_or_else(|| path.to_path_buf())
+}
+
+fn display_relative(path: &Path) -> PathBuf {
+    let Ok(cwd) = std::env::current_dir() else {
+        return path.to_path_buf();
+    };
+    match path.strip_prefix(&cwd) {
+        Ok(rel) if rel.as_os_str().is_empty() => PathBuf::from("."),
+        Ok(rel) => rel.to_path_buf(),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_reference() {
+        assert_eq!(preset_reference("@auth-flows"), Some("auth-flows"));
+        assert_eq!(preset_reference("@"), None);
+        assert_eq!(preset_reference("auth"), None);
+    }
+}
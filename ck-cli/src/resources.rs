@@ -0,0 +1,99 @@
+//! `--max-memory` and `--nice`: keep indexing from taking over a laptop. The
+//! limits are handed to ck-embed before any model loads; `--nice` also lowers
+//! the process's CPU priority and shrinks the rayon pool used for chunking.
+
+/// Niceness `--nice` sets, as `nice -n 10` would from a default shell.
+#[cfg(unix)]
+const NICE_LEVEL: libc::c_int = 10;
+
+/// Parse `--max-memory`: bytes, or a number with a K, M, G or T suffix (powers
+/// of 1024; a trailing `B` or `iB` is accepted).
+pub fn parse_size(value: &str) -> std::result::Result<u64, String> {
+    let invalid = || format!("Invalid size '{value}'; use bytes or a K, M, G or T suffix");
+    let upper = value.trim().to_ascii_uppercase();
+    let digits = upper
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+        .trim_end();
+    let (number, shift) = match digits.char_indices().last() {
+        Some((at, 'K')) => (&digits[..at], 10),
+        Some((at, 'M')) => (&digits[..at], 20),
+        Some((at, 'G')) => (&digits[..at], 30),
+        Some((at, 'T')) => (&digits[..at], 40),
+        _ => (digits, 0),
+    };
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    let bytes = number * (1u64 << shift) as f64;
+    if !bytes.is_finite() || bytes < 1.0 || bytes >= u64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes as u64)
+}
+
+/// Apply the resource flags for the rest of the process.
+pub fn apply(max_memory: Option<u64>, nice: bool) {
+    let threads = nice.then(|| (num_cpus::get() / 2).max(1));
+    if nice {
+        lower_priority();
+        if let Some(threads) = threads
+            && let Err(e) = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build_global()
+        {
+            tracing::debug!("rayon pool already configured: {e}");
+        }
+    }
+    if !ck_embed::set_resource_limits(ck_embed::ResourceLimits {
+        threads,
+        max_memory,
+    }) {
+        tracing::warn!("Embedding resource limits were already set; --max-memory/--nice ignored");
+    }
+}
+
+/// Renice every thread of the process. On Linux the priority is per thread, so
+/// threads the async runtime already started are reniced one by one; threads
+/// started later (ONNX Runtime, rayon, llama-server) inherit it.
+#[cfg(unix)]
+fn lower_priority() {
+    #[cfg(target_os = "linux")]
+    if let Ok(tasks) = std::fs::read_dir("/proc/self/task") {
+        for tid in tasks
+            .flatten()
+            .filter_map(|task| task.file_name().to_str()?.parse::<libc::id_t>().ok())
+        {
+            // SAFETY: setpriority only reads its integer arguments
+            unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, NICE_LEVEL) };
+        }
+        return;
+    }
+    // SAFETY: as above; `0` is the calling process
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, NICE_LEVEL) } != 0 {
+        tracing::warn!(
+            "Could not lower CPU priority: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn lower_priority() {
+    tracing::debug!("--nice does not change CPU priority on this platform");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("1.5gib"), Ok(3 << 29));
+        assert_eq!(parse_size("64 KB"), Ok(64 << 10));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("-1G").is_err());
+    }
+}
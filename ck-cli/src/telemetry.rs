@@ -0,0 +1,155 @@
+//! Logging and span export. `--log-level` sets the stderr filter; `--trace-chrome`
+//! and `--trace-otlp` record the spans the library crates open around chunking,
+//! tokenization, ONNX runs, vector search and reranking, independently of what
+//! is printed.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::Level;
+use tracing_subscriber::filter::{EnvFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry};
+
+/// Crates whose spans are exported to trace files and collectors.
+const TRACED_CRATES: &[&str] = &[
+    "ck",
+    "ck_search",
+    "ck_engine",
+    "ck_index",
+    "ck_chunk",
+    "ck_embed",
+    "ck_ann",
+];
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Held until `shutdown` so exporters are flushed even on `process::exit` paths.
+static GUARD: Mutex<Option<TelemetryGuard>> = Mutex::new(None);
+
+#[derive(Debug, Default)]
+pub struct TelemetryOptions<'a> {
+    /// Filter directives from `--log-level` (a level such as `debug`, or
+    /// `RUST_LOG`-style `target=level` pairs); overrides `RUST_LOG`.
+    pub log_level: Option<&'a str>,
+    /// Write spans as Chrome trace-event JSON (chrome://tracing, Perfetto)
+    pub chrome_trace: Option<&'a Path>,
+    /// Send spans to an OTLP/HTTP collector
+    pub otlp_endpoint: Option<&'a str>,
+}
+
+/// Flushes exported spans when dropped.
+#[derive(Default)]
+struct TelemetryGuard {
+    _chrome: Option<tracing_chrome::FlushGuard>,
+    #[cfg(feature = "otlp")]
+    otlp: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.otlp.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush OTLP spans: {}", e);
+        }
+    }
+}
+
+fn log_filter(log_level: Option<&str>, default_level: Level) -> Result<EnvFilter> {
+    match log_level {
+        Some(directives) => EnvFilter::try_new(directives)
+            .with_context(|| format!("Invalid --log-level '{}'", directives)),
+        None => Ok(EnvFilter::from_default_env().add_directive(default_level.into())),
+    }
+}
+
+fn traced_targets() -> Targets {
+    TRACED_CRATES.iter().fold(Targets::new(), |targets, krate| {
+        targets.with_target(*krate, Level::DEBUG)
+    })
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_layer(endpoint: &str) -> Result<(BoxedLayer, opentelemetry_sdk::trace::SdkTracerProvider)> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()
+        .with_context(|| format!("Failed to create OTLP exporter for {}", endpoint))?;
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name("ck")
+                .build(),
+        )
+        .build();
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer("ck"))
+        .with_filter(traced_targets())
+        .boxed();
+    Ok((layer, provider))
+}
+
+#[cfg(not(feature = "otlp"))]
+fn otlp_layer(_endpoint: &str) -> Result<(BoxedLayer, ())> {
+    anyhow::bail!("--trace-otlp requires ck to be built with the `otlp` feature")
+}
+
+/// Install the global subscriber: human-readable logs on stderr at
+/// `default_level` (unless overridden), plus any requested span exporters.
+pub fn init(options: &TelemetryOptions<'_>, default_level: Level) -> Result<()> {
+    let mut guard = TelemetryGuard::default();
+    let mut layers: Vec<BoxedLayer> = vec![
+        tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_filter(log_filter(options.log_level, default_level)?)
+            .boxed(),
+    ];
+
+    if let Some(path) = options.chrome_trace {
+        let (layer, flush) = tracing_chrome::ChromeLayerBuilder::new()
+            .file(path)
+            .include_args(true)
+            .build();
+        layers.push(layer.with_filter(traced_targets()).boxed());
+        guard._chrome = Some(flush);
+    }
+
+    if let Some(endpoint) = options.otlp_endpoint {
+        let (layer, _provider) = otlp_layer(endpoint)?;
+        layers.push(layer);
+        #[cfg(feature = "otlp")]
+        {
+            guard.otlp = Some(_provider);
+        }
+    }
+
+    tracing_subscriber::registry().with(layers).try_init()?;
+    *GUARD.lock().unwrap_or_else(|e| e.into_inner()) = Some(guard);
+    Ok(())
+}
+
+/// Flush and close span exporters. Call before the process exits.
+pub fn shutdown() {
+    drop(GUARD.lock().unwrap_or_else(|e| e.into_inner()).take());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_level_accepts_levels_and_directives() {
+        assert!(log_filter(Some("debug"), Level::WARN).is_ok());
+        assert!(log_filter(Some("warn,ck_embed=trace"), Level::WARN).is_ok());
+        let err = log_filter(Some("ck_embed=loud"), Level::WARN).unwrap_err();
+        assert!(err.to_string().contains("Invalid --log-level"));
+    }
+}
@@ -0,0 +1,130 @@
+//! `--worktree`: run a command against another git worktree of the
+//! repository, such as a release branch checked out beside the main one.
+//! Each worktree is indexed on its own, under its own `.ck`.
+
+use anyhow::{Context, Result, bail};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git; --worktree needs git on PATH")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Worktree {
+    path: PathBuf,
+    /// Short branch name; `None` when detached or bare
+    branch: Option<String>,
+}
+
+/// Worktrees from `git worktree list --porcelain`: blank-line separated
+/// records of `worktree PATH`, `HEAD SHA` and `branch refs/heads/NAME`.
+fn parse_worktrees(porcelain: &str) -> Vec<Worktree> {
+    let mut worktrees: Vec<Worktree> = Vec::new();
+    for line in porcelain.lines() {
+        if let Some(path) = line.strip_prefix("worktree ") {
+            worktrees.push(Worktree {
+                path: PathBuf::from(path),
+                branch: None,
+            });
+        } else if let Some(branch) = line.strip_prefix("branch ")
+            && let Some(worktree) = worktrees.last_mut()
+        {
+            let branch = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+            worktree.branch = Some(branch.to_string());
+        }
+    }
+    worktrees
+}
+
+/// The worktree `name` names: by branch, then by directory name, then by
+/// path.
+fn find<'a>(worktrees: &'a [Worktree], name: &str) -> Option<&'a Worktree> {
+    let name = name.strip_prefix("refs/heads/").unwrap_or(name);
+    worktrees
+        .iter()
+        .find(|worktree| worktree.branch.as_deref() == Some(name))
+        .or_else(|| {
+            worktrees
+                .iter()
+                .find(|worktree| worktree.path.file_name().is_some_and(|file| file == name))
+        })
+        .or_else(|| {
+            let path = ck_core::paths::canonicalize(Path::new(name));
+            worktrees.iter().find(|worktree| worktree.path == path)
+        })
+}
+
+/// `paths` (the current directory when there are none) moved from the
+/// worktree they are in to the same places in the worktree `name` names.
+pub fn rebase_paths(paths: &[PathBuf], name: &str) -> Result<Vec<PathBuf>> {
+    let cwd = std::env::current_dir()?;
+    let paths = if paths.is_empty() {
+        vec![cwd.clone()]
+    } else {
+        paths.iter().map(|path| cwd.join(path)).collect()
+    };
+    let dir = paths[0]
+        .ancestors()
+        .find(|dir| dir.is_dir())
+        .unwrap_or(&cwd);
+    let toplevel = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?.trim());
+    let worktrees = parse_worktrees(&git(dir, &["worktree", "list", "--porcelain"])?);
+    let Some(worktree) = find(&worktrees, name) else {
+        let names: Vec<String> = worktrees
+            .iter()
+            .map(|worktree| match &worktree.branch {
+                Some(branch) => format!("{} ({})", branch, worktree.path.display()),
+                None => worktree.path.display().to_string(),
+            })
+            .collect();
+        bail!(
+            "No worktree named '{}'; worktrees: {}",
+            name,
+            names.join(", ")
+        );
+    };
+
+    paths
+        .iter()
+        .map(|path| {
+            let path = ck_core::paths::canonicalize(path);
+            match ck_core::paths::relative_to(&path, &toplevel) {
+                Some(relative) => Ok(worktree.path.join(relative)),
+                None => bail!("{} is outside the repository", path.display()),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_worktree_by_branch_or_directory() {
+        let porcelain = "worktree /src/app\nHEAD 1111\nbranch refs/heads/main\n\nworktree /src/app-release\nHEAD 2222\nbranch refs/heads/release/2.x\n\nworktree /src/app-bisect\nHEAD 3333\ndetached\n";
+        let worktrees = parse_worktrees(porcelain);
+        assert_eq!(worktrees.len(), 3);
+        assert_eq!(worktrees[2].branch, None);
+
+        let path = |name| find(&worktrees, name).map(|worktree| worktree.path.clone());
+        assert_eq!(path("release/2.x"), Some(PathBuf::from("/src/app-release")));
+        assert_eq!(path("refs/heads/main"), Some(PathBuf::from("/src/app")));
+        assert_eq!(path("app-bisect"), Some(PathBuf::from("/src/app-bisect")));
+        assert_eq!(path("feature"), None);
+    }
+}
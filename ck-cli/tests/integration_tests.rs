@@ -212,6 +212,180 @@ fn test_switch_model_force_rebuild() {
     );
 }
 
+#[test]
+#[serial]
+fn test_configured_model_drift_is_refused() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("main.rs"), "fn main() {}").unwrap();
+    fs::create_dir(temp_dir.path().join(".ck")).unwrap();
+    fs::write(
+        temp_dir.path().join(".ck").join("manifest.json"),
+        r#"{"version":"0.1.0","created":0,"updated":0,"files":{},"embedding_model":"BAAI/bge-small-en-v1.5","embedding_dimensions":384}"#,
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join(".ckconfig.json"),
+        r#"{"model":"nomic-v1.5"}"#,
+    )
+    .unwrap();
+
+    for args in [&["--index", "."][..], &["--sem", "entry point", "."][..]] {
+        let output = Command::new(ck_binary())
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck");
+        assert!(!output.status.success(), "{:?} should fail", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("Model drift"), "{}", stderr);
+        assert!(
+            stderr.contains("ck --switch-model nomic-v1.5"),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn test_encrypted_index_needs_its_key() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("main.rs"), "fn main() {}").unwrap();
+    fs::create_dir(temp_dir.path().join(".ck")).unwrap();
+    let manifest_path = temp_dir.path().join(".ck").join("manifest.json");
+    fs::write(
+        &manifest_path,
+        r#"{"version":"0.1.0","created":0,"updated":0,"files":{"main.rs":{"path":"main.rs","hash":"","last_modified":0,"size":12}}}"#,
+    )
+    .unwrap();
+
+    // An existing plain index is not encrypted piecemeal
+    let output = Command::new(ck_binary())
+        .args(["--index", "."])
+        .env("CK_INDEX_PASSPHRASE", "correct horse")
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("is not encrypted"), "{}", stderr);
+    assert!(stderr.contains("ck --clean"), "{}", stderr);
+    assert!(!temp_dir.path().join(".ck").join("encryption.json").exists());
+
+    // An encrypted index cannot be opened without a key
+    fs::write(
+        temp_dir.path().join(".ck").join("encryption.json"),
+        r#"{"version":1,"cipher":"aes-256-gcm","kdf":"argon2id","salt":"AAAAAAAAAAAAAAAAAAAAAA==","check":""}"#,
+    )
+    .unwrap();
+    let output = Command::new(ck_binary())
+        .args(["--sem", "entry point", "."])
+        .env_remove("CK_INDEX_PASSPHRASE")
+        .env_remove("CK_INDEX_KEYFILE")
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("is encrypted"), "{}", stderr);
+}
+
+#[test]
+fn test_usage_stats_count_searches() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("src")).unwrap();
+    fs::write(
+        temp_dir.path().join("src/net.rs"),
+        "fn retry_request() {}\n",
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        Command::new(ck_binary())
+            .args(args)
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck")
+    };
+    // Without an index there is nothing to report
+    assert!(!run(&["--stats", "."]).status.success());
+
+    fs::create_dir(temp_dir.path().join(".ck")).unwrap();
+    run(&["retry_request", "src"]);
+    run(&["retry_request", "."]);
+    run(&["retry_request", "src"]);
+    let output = run(&["--stats", "--json", "."]);
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["searches"]["regex"], 3);
+    assert_eq!(stats["paths"]["src"], 2);
+    assert_eq!(stats["paths"]["."], 1);
+}
+
+#[test]
+fn test_ask_without_llm_prints_the_context() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("upload.rs"),
+        "fn retry_upload() {\n    // three attempts\n}\n\nfn retry_upload_later() {}\n",
+    )
+    .unwrap();
+
+    let output = Command::new(ck_binary())
+        .args(["--ask", "--regex", "retry_upload", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("# Context for: retry_upload\n"));
+    // Both hits are a few lines apart, so they come back as one excerpt
+    assert!(stdout.contains("## upload.rs:1-5\n```rust\nfn retry_upload() {\n"));
+    assert_eq!(stdout.matches("## ").count(), 1);
+}
+
+#[test]
+fn test_context_bundle_fits_the_budget() {
+    let temp_dir = TempDir::new().unwrap();
+    for name in ["upload", "download", "sync"] {
+        fs::write(
+            temp_dir.path().join(format!("{name}.rs")),
+            format!("fn {name}_with_retry() {{\n    // retry the {name} with backoff\n}}\n"),
+        )
+        .unwrap();
+    }
+    let bundle = |budget: &str| {
+        Command::new(ck_binary())
+            .args([
+                "--context-bundle",
+                "--regex",
+                "retry",
+                "--budget",
+                budget,
+                ".",
+            ])
+            .current_dir(temp_dir.path())
+            .output()
+            .expect("Failed to run ck")
+    };
+
+    let output = bundle("8000tokens");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("# Context for: retry\n"));
+    assert_eq!(stdout.matches("\n## ").count(), 3);
+    assert!(stdout.contains("## sync.rs:1-2\n```rust\nfn sync_with_retry() {\n"));
+
+    // A tight budget keeps fewer files
+    let output = bundle("40");
+    assert!(output.status.success());
+    let count = String::from_utf8_lossy(&output.stdout)
+        .matches("\n## ")
+        .count();
+    assert!((1..3).contains(&count), "{count} excerpts");
+
+    assert!(!bundle("lots").status.success());
+}
+
 #[test]
 #[serial]
 fn test_semantic_search() {
@@ -403,6 +577,52 @@ fn test_line_numbers() {
     assert!(stdout.contains("2:matched line"));
 }
 
+#[test]
+fn test_column_numbers() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("columns.txt"),
+        "line 1\nthe matched line\n",
+    )
+    .unwrap();
+
+    let output = Command::new(ck_binary())
+        .args(["--column", "matched", temp_dir.path().to_str().unwrap()])
+        .output()
+        .expect("Failed to run ck with columns");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    // --column implies line numbers; "matched" starts at byte column 5
+    assert!(stdout.contains("2:5:the matched line"));
+}
+
+#[test]
+fn test_color_choice() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("lib.rs"), "fn retry_connect() {}\n").unwrap();
+
+    let run = |color: &str| {
+        let output = Command::new(ck_binary())
+            .args([color, "retry", temp_dir.path().to_str().unwrap()])
+            .env_remove("NO_COLOR")
+            .output()
+            .expect("Failed to run ck with --color");
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // Forced colors syntax-highlight the snippet and emphasize the match
+    let colored = run("--color=always");
+    assert!(colored.contains("\x1b[1;4;38;2;"));
+    assert!(colored.contains("retry"));
+
+    let plain = run("--color=never");
+    assert!(!plain.contains('\x1b'));
+    assert!(plain.contains("fn retry_connect() {}"));
+}
+
 #[test]
 #[serial]
 fn test_clean_command() {
@@ -1074,3 +1294,222 @@ fn test_switch_model_to_mixedbread() {
         "Manifest should now have Mixedbread model"
     );
 }
+
+/// Run ck with `--read-only` and `args` in a fresh project, expecting clap to
+/// refuse the combination before anything is written.
+fn assert_refused_with_read_only(args: &[&str]) {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+
+    let output = Command::new(ck_binary())
+        .current_dir(temp_dir.path())
+        .arg("--read-only")
+        .args(args)
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("cannot be used with"), "{stderr}");
+    assert!(!temp_dir.path().join(".ck").exists());
+    assert!(!temp_dir.path().join(".ckconfig.json").exists());
+}
+
+#[test]
+fn test_read_only_refuses_reembed() {
+    assert_refused_with_read_only(&["--verify", "--reembed"]);
+}
+
+#[test]
+fn test_read_only_refuses_index_history() {
+    assert_refused_with_read_only(&["--index-history"]);
+}
+
+#[test]
+fn test_read_only_refuses_save_as() {
+    assert_refused_with_read_only(&["main", ".", "--save-as", "mains"]);
+}
+
+#[test]
+fn test_save_and_run_query_preset() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join(".git")).unwrap();
+    fs::create_dir(temp_dir.path().join("src")).unwrap();
+    fs::write(
+        temp_dir.path().join("src").join("auth.rs"),
+        "fn refresh_token() {}\nfn login() {}\n",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("notes.txt"), "refresh_token notes\n").unwrap();
+
+    let output = Command::new(ck_binary())
+        .current_dir(temp_dir.path())
+        .args(["-i", "REFRESH_TOKEN", "src", "--save-as", "auth-flows"])
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+
+    let config = fs::read_to_string(temp_dir.path().join(".ckconfig.json")).unwrap();
+    let config: serde_json::Value = serde_json::from_str(&config).unwrap();
+    let preset = &config["presets"]["auth-flows"];
+    assert_eq!(preset["query"], "REFRESH_TOKEN");
+    assert_eq!(preset["paths"][0], "src");
+    assert_eq!(preset["case_insensitive"], true);
+
+    // Running the preset restores query, paths and flags
+    let output = Command::new(ck_binary())
+        .current_dir(temp_dir.path())
+        .args(["@auth-flows"])
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("fn refresh_token"));
+    assert!(!stdout.contains("notes"));
+
+    let output = Command::new(ck_binary())
+        .current_dir(temp_dir.path())
+        .args(["@missing"])
+        .output()
+        .expect("Failed to run ck");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Unknown preset '@missing'"));
+}
+
+#[test]
+fn test_symbol_lookup() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("config.rs"),
+        "pub fn parse_config(path: &str) -> String {\n    path.to_string()\n}\n\npub fn unrelated() {}\n",
+    )
+    .unwrap();
+
+    let output = Command::new(ck_binary())
+        .current_dir(temp_dir.path())
+        .args(["--sym", "parseConfig", "--jsonl", "."])
+        .output()
+        .expect("Failed to run ck");
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let first: serde_json::Value =
+        serde_json::from_str(stdout.lines().next().expect("at least one result")).unwrap();
+    assert_eq!(first["symbol"], "parse_config");
+    assert_eq!(first["span"]["line_start"], 1);
+}
+
+#[test]
+fn test_lang_filter() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("lib.rs"), "// TODO: rust\n").unwrap();
+    fs::write(temp_dir.path().join("app.py"), "# TODO: python\n").unwrap();
+    fs::write(
+        temp_dir.path().join("deploy"),
+        "#!/usr/bin/env python3\n# TODO: script\n",
+    )
+    .unwrap();
+
+    let output = Command::new(ck_binary())
+        .current_dir(temp_dir.path())
+        .args(["TODO", "--lang", "python", "--jsonl", "."])
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let results: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let mut paths: Vec<&str> = results
+        .iter()
+        .map(|r| r["path"].as_str().unwrap())
+        .collect();
+    paths.sort();
+    assert_eq!(paths.len(), 2, "stdout: {}", stdout);
+    assert!(paths[0].ends_with("app.py"));
+    assert!(paths[1].ends_with("deploy"));
+    assert!(results.iter().all(|r| r["language"] == "python"));
+}
+
+#[test]
+#[serial]
+fn test_explain_reports_score_breakdown() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("doc1.txt"),
+        "machine learning models learn from data",
+    )
+    .unwrap();
+
+    let output = Command::new(ck_binary())
+        .args(["--index", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck index");
+    assert!(output.status.success());
+
+    let output = Command::new(ck_binary())
+        .args(["--lex", "machine", "--jsonl", "--explain", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck lexical search");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let first: serde_json::Value =
+        serde_json::from_str(stdout.lines().next().expect("a result")).unwrap();
+    assert_eq!(first["explain"]["source"], "bm25");
+    assert!(first["explain"]["lexical_score"].is_number());
+
+    // Without the flag the breakdown stays out of machine-readable output
+    let output = Command::new(ck_binary())
+        .args(["--lex", "machine", "--jsonl", "."])
+        .current_dir(temp_dir.path())
+        .output()
+        .expect("Failed to run ck lexical search");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("\"explain\""));
+}
+
+#[test]
+fn test_format_sarif_and_rdjson() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(
+        temp_dir.path().join("config.rs"),
+        "fn connect() {\n    let password = \"hunter2\";\n}\n",
+    )
+    .unwrap();
+
+    let output = Command::new(ck_binary())
+        .current_dir(temp_dir.path())
+        .args(["password", "--format", "sarif", "."])
+        .output()
+        .expect("Failed to run ck");
+    assert!(output.status.success());
+    let sarif: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "ck/regex");
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "config.rs");
+    assert_eq!(location["region"]["startLine"], 2);
+
+    let output = Command::new(ck_binary())
+        .current_dir(temp_dir.path())
+        .args(["nothing-here", "--format", "reviewdog", "."])
+        .output()
+        .expect("Failed to run ck");
+    assert_eq!(output.status.code(), Some(1));
+    let rdjson: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(rdjson["source"]["name"], "ck");
+    assert!(rdjson["diagnostics"].as_array().unwrap().is_empty());
+}
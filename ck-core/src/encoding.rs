@@ -0,0 +1,201 @@
+//! Text files that are not clean UTF-8. UTF-16 (with a byte order mark, or
+//! detected from its zero bytes) and Latin-1 are transcoded; UTF-8 with a few
+//! invalid bytes keeps the rest of its text, each bad sequence replaced by
+//! U+FFFD. Indexing and result display both read files through
+//! [`read_text`], so line numbers agree between them.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// How a file's bytes were decoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1: every byte is the code point of the same value
+    Latin1,
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf16Le => "UTF-16LE",
+            TextEncoding::Utf16Be => "UTF-16BE",
+            TextEncoding::Latin1 => "Latin-1",
+        })
+    }
+}
+
+/// A file's text and how it was decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: TextEncoding,
+    /// Some bytes were invalid in `encoding` and became U+FFFD
+    pub lossy: bool,
+}
+
+impl DecodedText {
+    /// Whether the file was anything other than valid UTF-8.
+    pub fn needs_warning(&self) -> bool {
+        self.encoding != TextEncoding::Utf8 || self.lossy
+    }
+}
+
+/// Read `path` as text, whatever its encoding.
+pub fn read_text(path: &Path) -> std::io::Result<DecodedText> {
+    Ok(decode_text(&std::fs::read(path)?))
+}
+
+/// Decode `bytes`: a byte order mark wins, then UTF-16 by its zero bytes,
+/// then valid UTF-8, then Latin-1 for text with high bytes but no valid UTF-8
+/// multi-byte sequence, and otherwise UTF-8 with replacement characters.
+pub fn decode_text(bytes: &[u8]) -> DecodedText {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return utf8_lossy(rest);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        return utf16(rest, TextEncoding::Utf16Le);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        return utf16(rest, TextEncoding::Utf16Be);
+    }
+    // ASCII in UTF-16 is valid UTF-8 too, NULs and all, so it is tried first
+    if let Some(encoding) = detect_utf16(bytes) {
+        return utf16(bytes, encoding);
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return DecodedText {
+            text: text.to_string(),
+            encoding: TextEncoding::Utf8,
+            lossy: false,
+        };
+    }
+    if !bytes.contains(&0) && !has_utf8_sequence(bytes) {
+        return DecodedText {
+            text: bytes.iter().map(|&byte| byte as char).collect(),
+            encoding: TextEncoding::Latin1,
+            lossy: false,
+        };
+    }
+    utf8_lossy(bytes)
+}
+
+/// Whether `bytes` look like text: no NUL bytes, or UTF-16 with or without a
+/// byte order mark. Only the start of a file needs to be passed.
+pub fn looks_like_text(bytes: &[u8]) -> bool {
+    !bytes.contains(&0)
+        || bytes.starts_with(b"\xFF\xFE")
+        || bytes.starts_with(b"\xFE\xFF")
+        || detect_utf16(bytes).is_some()
+}
+
+fn utf8_lossy(bytes: &[u8]) -> DecodedText {
+    let text = String::from_utf8_lossy(bytes);
+    DecodedText {
+        lossy: matches!(text, std::borrow::Cow::Owned(_)),
+        text: text.into_owned(),
+        encoding: TextEncoding::Utf8,
+    }
+}
+
+fn utf16(bytes: &[u8], encoding: TextEncoding) -> DecodedText {
+    let units = bytes.chunks_exact(2).map(|pair| match encoding {
+        TextEncoding::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+        _ => u16::from_le_bytes([pair[0], pair[1]]),
+    });
+    let mut lossy = !bytes.len().is_multiple_of(2);
+    let text = char::decode_utf16(units)
+        .map(|unit| {
+            unit.unwrap_or_else(|_| {
+                lossy = true;
+                char::REPLACEMENT_CHARACTER
+            })
+        })
+        .collect();
+    DecodedText {
+        text,
+        encoding,
+        lossy,
+    }
+}
+
+/// UTF-16 without a byte order mark: mostly-ASCII text leaves the high byte
+/// of nearly every code unit zero, on the odd side for little-endian.
+fn detect_utf16(bytes: &[u8]) -> Option<TextEncoding> {
+    let pairs = bytes.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let zero_at = |offset: usize| {
+        bytes
+            .chunks_exact(2)
+            .filter(|pair| pair[offset] == 0 && pair[1 - offset] != 0)
+            .count()
+    };
+    let (odd, even) = (zero_at(1), zero_at(0));
+    if odd * 10 >= pairs * 7 && even == 0 {
+        Some(TextEncoding::Utf16Le)
+    } else if even * 10 >= pairs * 7 && odd == 0 {
+        Some(TextEncoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Whether `bytes` hold at least one valid UTF-8 multi-byte sequence, which
+/// Latin-1 text almost never does by accident.
+fn has_utf8_sequence(bytes: &[u8]) -> bool {
+    bytes.utf8_chunks().any(|chunk| !chunk.valid().is_ascii())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf16_with_and_without_bom() {
+        let le: Vec<u8> = "fn main() {}\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let be: Vec<u8> = "café\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+
+        let with_bom = decode_text(&[b"\xFF\xFE".as_slice(), &le].concat());
+        assert_eq!(with_bom.text, "fn main() {}\n");
+        assert_eq!(with_bom.encoding, TextEncoding::Utf16Le);
+        assert!(!with_bom.lossy);
+
+        assert_eq!(decode_text(&le).encoding, TextEncoding::Utf16Le);
+        let decoded = decode_text(&[b"\xFE\xFF".as_slice(), &be].concat());
+        assert_eq!(decoded.text, "café\n");
+        assert_eq!(decoded.encoding, TextEncoding::Utf16Be);
+        assert!(looks_like_text(&le));
+        assert!(!looks_like_text(b"\x7FELF\x02\x01\x01\x00\x00\x00\x00\x00"));
+    }
+
+    #[test]
+    fn test_latin1_and_lossy_utf8() {
+        let latin1 = decode_text(b"// Gr\xFC\xDFe aus K\xF6ln\n");
+        assert_eq!(latin1.text, "// Grüße aus Köln\n");
+        assert_eq!(latin1.encoding, TextEncoding::Latin1);
+        assert!(latin1.needs_warning());
+
+        // Valid UTF-8 elsewhere in the file marks one stray byte as damage
+        let damaged = decode_text(&["// naïve\nlet x = 1;".as_bytes(), b"\xFF\n"].concat());
+        assert_eq!(damaged.encoding, TextEncoding::Utf8);
+        assert!(damaged.lossy);
+        assert_eq!(damaged.text, "// naïve\nlet x = 1;\u{FFFD}\n");
+
+        let clean = decode_text("fn main() {}\n".as_bytes());
+        assert!(!clean.needs_warning());
+        let bom = decode_text(b"\xEF\xBB\xBFfn main() {}\n");
+        assert_eq!(bom.text, "fn main() {}\n");
+        assert!(!bom.needs_warning());
+    }
+}
@@ -1,7 +1,15 @@
+pub mod encoding;
 pub mod heatmap;
+pub mod paths;
+pub mod redact;
+pub mod shell;
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -30,6 +38,10 @@ pub enum CkError {
     #[error("Span validation error: {0}")]
     SpanValidation(String),
 
+    /// The operation's [`CancellationToken`] was cancelled
+    #[error("Operation cancelled")]
+    Cancelled,
+
     #[error("Other error: {0}")]
     Other(String),
 }
@@ -90,6 +102,79 @@ impl Language {
             .and_then(|ext| ext.to_str())
             .and_then(Self::from_extension)
     }
+
+    /// Language named by a `#!` interpreter line, e.g. `#!/usr/bin/env python3`.
+    pub fn from_shebang(content: &str) -> Option<Self> {
+        let line = content.lines().next()?.strip_prefix("#!")?;
+        let mut words = line.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-'))?;
+        }
+        let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        match interpreter {
+            "python" | "pypy" => Some(Language::Python),
+            "node" | "nodejs" | "bun" => Some(Language::JavaScript),
+            "deno" | "ts-node" | "tsx" => Some(Language::TypeScript),
+            "ruby" => Some(Language::Ruby),
+            "php" => Some(Language::Php),
+            "elixir" => Some(Language::Elixir),
+            "runhaskell" | "runghc" | "stack" => Some(Language::Haskell),
+            "swift" => Some(Language::Swift),
+            "dart" => Some(Language::Dart),
+            "kotlin" | "kscript" => Some(Language::Kotlin),
+            _ => None,
+        }
+    }
+
+    /// Detect from the extension, falling back to the shebang for scripts without one.
+    pub fn detect(path: &Path, content: &str) -> Option<Self> {
+        Self::from_path(path).or_else(|| Self::from_shebang(content))
+    }
+
+    /// Like [`Language::detect`], reading only the first line of the file and only
+    /// when the extension is not recognized.
+    pub fn detect_file(path: &Path) -> Option<Self> {
+        use std::io::{BufRead, Read};
+
+        Self::from_path(path).or_else(|| {
+            let file = std::fs::File::open(path).ok()?;
+            let mut first_line = String::new();
+            std::io::BufReader::new(file.take(256))
+                .read_line(&mut first_line)
+                .ok()?;
+            Self::from_shebang(&first_line)
+        })
+    }
+}
+
+impl std::str::FromStr for Language {
+    type Err = String;
+
+    /// Parse a `--lang` value: a language name (`rust`, `python`), a common alias
+    /// (`golang`, `c++`, `c#`) or a file extension (`rs`, `py`).
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        let lower = name.trim().to_lowercase();
+        let by_name = match lower.as_str() {
+            "rust" => Some(Language::Rust),
+            "python" => Some(Language::Python),
+            "javascript" => Some(Language::JavaScript),
+            "typescript" => Some(Language::TypeScript),
+            "haskell" => Some(Language::Haskell),
+            "go" | "golang" => Some(Language::Go),
+            "java" => Some(Language::Java),
+            "csharp" | "c#" => Some(Language::CSharp),
+            "ruby" => Some(Language::Ruby),
+            "swift" => Some(Language::Swift),
+            "kotlin" => Some(Language::Kotlin),
+            "dart" => Some(Language::Dart),
+            "elixir" => Some(Language::Elixir),
+            _ => None,
+        };
+        by_name
+            .or_else(|| Self::from_extension(&lower))
+            .ok_or_else(|| format!("unknown language '{}'", name))
+    }
 }
 
 impl std::fmt::Display for Language {
@@ -118,12 +203,19 @@ impl std::fmt::Display for Language {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Span {
     pub byte_start: usize,
     pub byte_end: usize,
     pub line_start: usize,
     pub line_end: usize,
+    /// 1-indexed byte column of `byte_start` on `line_start`; 0 when unknown
+    #[serde(default)]
+    pub col_start: usize,
+    /// Exclusive 1-indexed byte column of `byte_end` on `line_end`, not counting
+    /// the line break; 0 when unknown
+    #[serde(default)]
+    pub col_end: usize,
 }
 
 impl Span {
@@ -139,6 +231,8 @@ impl Span {
             byte_end,
             line_start,
             line_end,
+            col_start: 0,
+            col_end: 0,
         };
         span.validate()?;
         Ok(span)
@@ -160,9 +254,34 @@ impl Span {
             byte_end,
             line_start,
             line_end,
+            col_start: 0,
+            col_end: 0,
         }
     }
 
+    /// Set the column range from the byte range within `source`, the text the
+    /// span was taken from.
+    pub fn fill_columns(&mut self, source: &str) {
+        let bytes = source.as_bytes();
+        let line_begin = |at: usize| {
+            bytes[..at]
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |i| i + 1)
+        };
+
+        let start = self.byte_start.min(bytes.len());
+        let mut end = self.byte_end.clamp(start, bytes.len());
+        if end > start && bytes[end - 1] == b'\n' {
+            end -= 1;
+            if end > start && bytes[end - 1] == b'\r' {
+                end -= 1;
+            }
+        }
+        self.col_start = start - line_begin(start) + 1;
+        self.col_end = end - line_begin(end) + 1;
+    }
+
     /// Validate span invariants
     pub fn validate(&self) -> Result<()> {
         // Check for zero line numbers first (lines should be 1-indexed)
@@ -235,6 +354,210 @@ pub struct SearchResult {
     pub chunk_hash: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub index_epoch: Option<u64>,
+    /// Other locations of near-identical copies of this chunk
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<DuplicateLocation>,
+    /// How `score` was arrived at, when anything adjusted it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explain: Option<ScoreExplanation>,
+    /// Who last changed the result's lines, with `--blame`, `--owner` or owner boosts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blame: Option<BlameInfo>,
+    /// Key/value metadata attached to the chunk at index time, with `--meta`
+    /// or JSON output
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl SearchResult {
+    /// A result with only its location, score and preview set; everything
+    /// else starts empty
+    pub fn new(
+        file: impl Into<PathBuf>,
+        span: Span,
+        score: f32,
+        preview: impl Into<String>,
+    ) -> Self {
+        Self {
+            file: file.into(),
+            span,
+            score,
+            preview: preview.into(),
+            lang: None,
+            symbol: None,
+            chunk_hash: None,
+            index_epoch: None,
+            duplicates: Vec::new(),
+            explain: None,
+            blame: None,
+            metadata: BTreeMap::new(),
+        }
+    }
+}
+
+/// Git history of a result's lines: the newest commit touching them, and
+/// everyone whose lines survive in the span.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlameInfo {
+    /// Author of the newest commit touching the lines
+    pub author: String,
+    pub email: String,
+    pub commit: String,
+    /// Author time of that commit (Unix seconds)
+    pub time: u64,
+    /// Authors of the span's current lines, most lines first
+    pub authors: Vec<BlameAuthor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlameAuthor {
+    pub name: String,
+    pub email: String,
+    pub lines: usize,
+}
+
+impl BlameInfo {
+    /// Whether `owner` (any part of a name or email, ignoring case) wrote any
+    /// of the lines.
+    pub fn owned_by(&self, owner: &str) -> bool {
+        let owner = owner.to_lowercase();
+        self.authors.iter().any(|author| {
+            author.name.to_lowercase().contains(&owner)
+                || author.email.to_lowercase().contains(&owner)
+        })
+    }
+}
+
+/// Breakdown of a result's final score, shown by `--explain`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoreExplanation {
+    /// Retrieval path that produced the hit: `semantic`, `bm25`, `symbol`, or
+    /// how hybrid search fused its lists (`rrf`, `min-max` or `z-score`)
+    pub source: String,
+    /// Score as retrieved (after fusion and reranking), before boosts
+    pub base_score: f32,
+    /// Cosine similarity between query and chunk embeddings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dense_score: Option<f32>,
+    /// BM25 score normalized to the best hit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lexical_score: Option<f32>,
+    /// Fuzzy match between the query and a definition name (symbol mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_score: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_score: Option<f32>,
+    /// 1-based rank in the regex list fused by RRF (hybrid mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lex_rank: Option<usize>,
+    /// 1-based rank in the semantic list fused by RRF (hybrid mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vec_rank: Option<usize>,
+    /// 1-based rank within its own index, when indexes built with different
+    /// models are searched together and fused by RRF
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_rank: Option<usize>,
+    /// Score within its own index before `--normalize-scores` rescaled it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_score: Option<f32>,
+    /// Factor `--prefer` applied to the dense score for the chunk's share of
+    /// the preferred facet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub facet_weight: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub boosts: Vec<ScoreBoost>,
+}
+
+impl ScoreExplanation {
+    pub fn new(source: &str, base_score: f32) -> Self {
+        Self {
+            source: source.to_string(),
+            base_score,
+            ..Default::default()
+        }
+    }
+}
+
+/// One multiplicative adjustment applied to a result's score.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreBoost {
+    /// What triggered it, e.g. `path:**/vendor/**` or `recency`
+    pub reason: String,
+    pub factor: f32,
+}
+
+/// Where a deduplicated copy of a result's chunk lives (vendored or generated code).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateLocation {
+    pub file: PathBuf,
+    pub span: Span,
+}
+
+/// Another hit in the same file as a result, collapsed into it by `--group-by file`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupedMatch {
+    pub span: Span,
+    pub score: f32,
+}
+
+/// Hits the user marked relevant (pinned) or irrelevant (banned) during an
+/// interactive session. Semantic retrieval moves the query embedding toward pinned
+/// chunks and away from banned ones, and banned hits are left out of results.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RelevanceFeedback {
+    pub pinned: Vec<(PathBuf, Span)>,
+    pub banned: Vec<(PathBuf, Span)>,
+}
+
+impl RelevanceFeedback {
+    pub fn is_empty(&self) -> bool {
+        self.pinned.is_empty() && self.banned.is_empty()
+    }
+
+    pub fn is_pinned(&self, result: &SearchResult) -> bool {
+        Self::contains(&self.pinned, result)
+    }
+
+    pub fn is_banned(&self, result: &SearchResult) -> bool {
+        Self::contains(&self.banned, result)
+    }
+
+    /// Pin `result`, or unpin it if already pinned. Returns whether it is now pinned.
+    pub fn toggle_pin(&mut self, result: &SearchResult) -> bool {
+        Self::remove(&mut self.banned, result);
+        Self::toggle(&mut self.pinned, result)
+    }
+
+    /// Ban `result`, or lift the ban if already banned. Returns whether it is now banned.
+    pub fn toggle_ban(&mut self, result: &SearchResult) -> bool {
+        Self::remove(&mut self.pinned, result);
+        Self::toggle(&mut self.banned, result)
+    }
+
+    pub fn clear(&mut self) {
+        self.pinned.clear();
+        self.banned.clear();
+    }
+
+    fn contains(list: &[(PathBuf, Span)], result: &SearchResult) -> bool {
+        list.iter()
+            .any(|(file, span)| *file == result.file && *span == result.span)
+    }
+
+    fn remove(list: &mut Vec<(PathBuf, Span)>, result: &SearchResult) -> bool {
+        let before = list.len();
+        list.retain(|(file, span)| *file != result.file || *span != result.span);
+        list.len() != before
+    }
+
+    fn toggle(list: &mut Vec<(PathBuf, Span)>, result: &SearchResult) -> bool {
+        if Self::remove(list, result) {
+            return false;
+        }
+        list.push((result.file.clone(), result.span.clone()));
+        true
+    }
 }
 
 /// Enhanced search results that include near-miss information for threshold queries
@@ -255,6 +578,17 @@ pub struct JsonSearchResult {
     pub signals: SearchSignals,
     pub preview: String,
     pub model: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<DuplicateLocation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explain: Option<ScoreExplanation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blame: Option<BlameInfo>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// The file's other hits, best first, with `--group-by file`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub more_in_file: Vec<GroupedMatch>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -262,6 +596,8 @@ pub struct JsonlSearchResult {
     pub path: String,
     pub span: Span,
     pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub snippet: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -270,13 +606,35 @@ pub struct JsonlSearchResult {
     pub chunk_hash: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub index_epoch: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<DuplicateLocation>,
+    /// Score breakdown, only with `--explain`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explain: Option<ScoreExplanation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blame: Option<BlameInfo>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// The file's other hits, best first, with `--group-by file`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub more_in_file: Vec<GroupedMatch>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchSignals {
     pub lex_rank: Option<usize>,
     pub vec_rank: Option<usize>,
+    /// The combined score, whichever `--normalize-scores` scheme produced it
     pub rrf_score: f32,
+    /// Raw components the combined score was built from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dense_score: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lexical_score: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rerank_score: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_score: Option<f32>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -285,6 +643,274 @@ pub enum SearchMode {
     Lexical,
     Semantic,
     Hybrid,
+    /// Definition lookup by name over the index's symbol table
+    Symbol,
+}
+
+impl std::str::FromStr for SearchMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "regex" => Ok(SearchMode::Regex),
+            "lex" | "lexical" => Ok(SearchMode::Lexical),
+            "sem" | "semantic" => Ok(SearchMode::Semantic),
+            "hybrid" => Ok(SearchMode::Hybrid),
+            "sym" | "symbol" => Ok(SearchMode::Symbol),
+            _ => Err(format!(
+                "unknown search mode '{}' (expected regex, lex, sem, hybrid or sym)",
+                name
+            )),
+        }
+    }
+}
+
+/// How `ck --index` splits files into chunks (`chunk_strategy` in
+/// `.ckconfig.json`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStrategy {
+    /// Tree-sitter definitions where a grammar exists, else line windows
+    #[default]
+    Ast,
+    /// Line windows for every file, grammar or not
+    Lines,
+    /// Fixed character windows, cut wherever the count falls
+    Chars,
+}
+
+impl std::str::FromStr for ChunkStrategy {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "ast" => Ok(ChunkStrategy::Ast),
+            "lines" => Ok(ChunkStrategy::Lines),
+            "chars" => Ok(ChunkStrategy::Chars),
+            _ => Err(format!(
+                "unknown chunk strategy '{}' (expected ast, lines or chars)",
+                name
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ChunkStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ChunkStrategy::Ast => "ast",
+            ChunkStrategy::Lines => "lines",
+            ChunkStrategy::Chars => "chars",
+        })
+    }
+}
+
+/// How a cross-encoder reranker turns its raw logits into result scores.
+/// Logit scales differ between models, so only a calibrated transform keeps a
+/// `--threshold` meaningful when the reranker changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScoreTransform {
+    /// Logistic function of the logit: an absolute 0-1 relevance
+    #[default]
+    Sigmoid,
+    /// Rescaled to 0-1 within each reranked batch; best is 1, worst is 0
+    MinMax,
+    /// The model's logits, unbounded
+    Raw,
+}
+
+impl std::fmt::Display for ScoreTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ScoreTransform::Sigmoid => "sigmoid",
+            ScoreTransform::MinMax => "min-max",
+            ScoreTransform::Raw => "raw",
+        })
+    }
+}
+
+impl ScoreTransform {
+    /// Transform one batch of logits in place.
+    pub fn apply(self, scores: &mut [f32]) {
+        match self {
+            ScoreTransform::Sigmoid => {
+                for score in scores.iter_mut() {
+                    *score = 1.0 / (1.0 + (-*score).exp());
+                }
+            }
+            ScoreTransform::MinMax => {
+                let min = scores.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                let range = max - min;
+                for score in scores.iter_mut() {
+                    *score = if range > 0.0 {
+                        (*score - min) / range
+                    } else {
+                        1.0
+                    };
+                }
+            }
+            ScoreTransform::Raw => {}
+        }
+    }
+}
+
+impl std::str::FromStr for ScoreTransform {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "sigmoid" => Ok(ScoreTransform::Sigmoid),
+            "min-max" | "minmax" => Ok(ScoreTransform::MinMax),
+            "raw" | "logits" => Ok(ScoreTransform::Raw),
+            _ => Err(format!(
+                "unknown score transform '{}' (expected sigmoid, min-max or raw)",
+                name
+            )),
+        }
+    }
+}
+
+/// How scores from sources on different scales are combined: hybrid search's
+/// regex and semantic lists, and indexes searched together
+/// (`--normalize-scores`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScoreNormalization {
+    /// Reciprocal rank fusion, `Σ 1 / (60 + rank)`, where sources can't be
+    /// compared; raw scores where they can
+    #[default]
+    Rank,
+    /// Each source rescaled to 0-1, best 1 and worst 0
+    MinMax,
+    /// Each source as standard deviations from its mean score
+    ZScore,
+}
+
+impl ScoreNormalization {
+    /// Normalize one source's scores in place; `Rank` leaves them as they are.
+    pub fn apply(self, scores: &mut [f32]) {
+        match self {
+            ScoreNormalization::Rank => {}
+            ScoreNormalization::MinMax => ScoreTransform::MinMax.apply(scores),
+            ScoreNormalization::ZScore => {
+                let count = scores.len() as f32;
+                let mean = scores.iter().sum::<f32>() / count;
+                let variance = scores.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / count;
+                let deviation = variance.sqrt();
+                for score in scores.iter_mut() {
+                    *score = if deviation > 0.0 {
+                        (*score - mean) / deviation
+                    } else {
+                        0.0
+                    };
+                }
+            }
+        }
+    }
+
+    /// What a source that did not return a hit counts for when a hit's
+    /// normalized scores are averaged: 0 under min-max, and one deviation
+    /// below the source's worst hit under z-scores.
+    pub fn missing(self, normalized: &[f32]) -> f32 {
+        match self {
+            ScoreNormalization::Rank | ScoreNormalization::MinMax => 0.0,
+            ScoreNormalization::ZScore => normalized.iter().copied().fold(0.0, f32::min) - 1.0,
+        }
+    }
+}
+
+impl std::str::FromStr for ScoreNormalization {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "rank" | "rrf" => Ok(ScoreNormalization::Rank),
+            "min-max" | "minmax" => Ok(ScoreNormalization::MinMax),
+            "z-score" | "zscore" => Ok(ScoreNormalization::ZScore),
+            _ => Err(format!(
+                "unknown score normalization '{}' (expected rank, min-max or z-score)",
+                name
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ScoreNormalization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ScoreNormalization::Rank => "rank",
+            ScoreNormalization::MinMax => "min-max",
+            ScoreNormalization::ZScore => "z-score",
+        })
+    }
+}
+
+/// A kind of text within a chunk, for `--prefer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Facet {
+    Code,
+    /// Comments and docstrings, and prose files such as Markdown
+    Comments,
+    /// String literals
+    Strings,
+}
+
+impl std::str::FromStr for Facet {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "code" => Ok(Facet::Code),
+            "comments" | "comment" | "docs" => Ok(Facet::Comments),
+            "strings" | "string" => Ok(Facet::Strings),
+            _ => Err(format!(
+                "unknown facet '{}' (expected code, comments or strings)",
+                name
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Facet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Facet::Code => "code",
+            Facet::Comments => "comments",
+            Facet::Strings => "strings",
+        })
+    }
+}
+
+/// Shares of a chunk's non-whitespace text that are code, comments and
+/// string literals; they sum to 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Facets {
+    pub code: f32,
+    pub comments: f32,
+    pub strings: f32,
+}
+
+impl Facets {
+    /// How far `--prefer` moves a chunk's similarity either way: a chunk
+    /// entirely of the preferred facet scores 15% higher, one without any 15%
+    /// lower.
+    pub const PREFER_WEIGHT: f32 = 0.15;
+
+    pub fn share(&self, facet: Facet) -> f32 {
+        match facet {
+            Facet::Code => self.code,
+            Facet::Comments => self.comments,
+            Facet::Strings => self.strings,
+        }
+    }
+
+    /// Factor a chunk's similarity is multiplied by under `--prefer facet`.
+    pub fn weight(&self, facet: Facet) -> f32 {
+        1.0 + Self::PREFER_WEIGHT * (2.0 * self.share(facet) - 1.0)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -304,6 +930,12 @@ pub struct FileCollectionOptions {
     pub use_ckignore: bool,
     /// Patterns to exclude files/directories
     pub exclude_patterns: Vec<String>,
+    /// Descend into symlinked directories and index symlinked files. Off by
+    /// default: links are recorded but not traversed
+    pub follow_symlinks: bool,
+    /// Index exactly these files instead of walking the tree (`--files-from`).
+    /// Ignore files and exclude patterns do not apply to them
+    pub files_from: Option<Vec<PathBuf>>,
 }
 
 impl From<&SearchOptions> for FileCollectionOptions {
@@ -312,6 +944,8 @@ impl From<&SearchOptions> for FileCollectionOptions {
             respect_gitignore: opts.respect_gitignore,
             use_ckignore: true, // Always use .ckignore for hierarchical ignore support
             exclude_patterns: opts.exclude_patterns.clone(),
+            follow_symlinks: opts.follow_symlinks,
+            files_from: None,
         }
     }
 }
@@ -327,6 +961,8 @@ pub struct SearchOptions {
     pub whole_word: bool,
     pub fixed_string: bool,
     pub line_numbers: bool,
+    /// Print the 1-indexed column after the line number in plain output
+    pub column: bool,
     pub context_lines: usize,
     pub before_context_lines: usize,
     pub after_context_lines: usize,
@@ -336,6 +972,8 @@ pub struct SearchOptions {
     pub no_snippet: bool,
     pub reindex: bool,
     pub show_scores: bool,
+    /// Attach a per-result score breakdown to the output
+    pub explain: bool,
     pub show_filenames: bool,
     pub files_with_matches: bool,
     pub files_without_matches: bool,
@@ -347,7 +985,78 @@ pub struct SearchOptions {
     // Enhanced embedding options (search-time only)
     pub rerank: bool,
     pub rerank_model: Option<String>,
+    /// Overrides the reranker's configured score transform
+    pub rerank_scores: Option<ScoreTransform>,
+    /// Rerank only this many leading dense candidates; the rest keep dense order
+    pub rerank_top: Option<usize>,
+    /// Wall-clock limit on reranking, including model load; candidates not yet
+    /// reranked when it runs out keep dense order
+    pub rerank_budget: Option<Duration>,
+    /// Candidates taken from the ANN graph per requested result, re-scored
+    /// exactly before reranking; `None` uses the project's `hnsw.oversample`
+    pub ann_oversample: Option<usize>,
     pub embedding_model: Option<String>,
+    /// Restrict results to these languages (`--lang`); empty means all
+    pub languages: Vec<Language>,
+    /// Pinned/banned hits from an interactive session
+    pub feedback: RelevanceFeedback,
+    /// Blend the query vector with an LLM-drafted hypothetical snippet (`--hyde`)
+    pub hyde: bool,
+    /// Task description embedded before the query in place of the model's
+    /// default query instruction (`--instruct`)
+    pub instruction: Option<String>,
+    /// Two-stage search: rank files by their mean chunk vector, then score
+    /// chunks only in the best this many (`--top-files`)
+    pub top_files: Option<usize>,
+    /// One result per file, ranked by its mean chunk vector (`--by-file`)
+    pub by_file: bool,
+    /// How hybrid and multi-index searches combine their sources' scores
+    pub score_normalization: ScoreNormalization,
+    /// Traverse symlinks when collecting files (`--follow-symlinks`)
+    pub follow_symlinks: bool,
+    /// Search the kept snapshot of an earlier index generation, named by
+    /// generation or date (`--as-of`)
+    pub as_of: Option<String>,
+    /// Annotate results with git blame (`--blame`)
+    pub blame: bool,
+    /// Keep only results whose lines this author (name or email) wrote (`--owner`)
+    pub owner: Option<String>,
+    /// Keep only results whose index-time metadata has every one of these
+    /// key/value pairs (`--meta KEY=VALUE`)
+    pub meta: Vec<(String, String)>,
+    /// Favor chunks made mostly of this kind of text in semantic and hybrid
+    /// search (`--prefer`)
+    pub prefer: Option<Facet>,
+    /// Stops the search, and any index update it starts, when cancelled
+    pub cancel: Option<CancellationToken>,
+}
+
+/// Cooperative cancellation for index updates and searches, for hosts that
+/// embed ck. Clones share one flag; long operations check it between files
+/// and chunks, keep the files they finished, and stop.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// `Err(CkError::Cancelled)` once cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(CkError::Cancelled);
+        }
+        Ok(())
+    }
 }
 
 impl JsonlSearchResult {
@@ -356,6 +1065,7 @@ impl JsonlSearchResult {
             path: result.file.to_string_lossy().to_string(),
             span: result.span.clone(),
             language: result.lang.as_ref().map(|l| l.to_string()),
+            symbol: result.symbol.clone(),
             snippet: if include_snippet {
                 Some(result.preview.clone())
             } else {
@@ -368,6 +1078,11 @@ impl JsonlSearchResult {
             },
             chunk_hash: result.chunk_hash.clone(),
             index_epoch: result.index_epoch,
+            duplicates: result.duplicates.clone(),
+            explain: None,
+            blame: result.blame.clone(),
+            metadata: result.metadata.clone(),
+            more_in_file: Vec::new(),
         }
     }
 }
@@ -384,6 +1099,7 @@ impl Default for SearchOptions {
             whole_word: false,
             fixed_string: false,
             line_numbers: false,
+            column: false,
             context_lines: 0,
             before_context_lines: 0,
             after_context_lines: 0,
@@ -393,6 +1109,7 @@ impl Default for SearchOptions {
             no_snippet: false,
             reindex: false,
             show_scores: false,
+            explain: false,
             show_filenames: false,
             files_with_matches: false,
             files_without_matches: false,
@@ -404,7 +1121,25 @@ impl Default for SearchOptions {
             // Enhanced embedding options (search-time only)
             rerank: false,
             rerank_model: None,
+            rerank_scores: None,
+            rerank_top: None,
+            rerank_budget: None,
+            ann_oversample: None,
             embedding_model: None,
+            languages: Vec::new(),
+            feedback: Default::default(),
+            hyde: false,
+            top_files: None,
+            by_file: false,
+            score_normalization: ScoreNormalization::Rank,
+            follow_symlinks: false,
+            as_of: None,
+            blame: false,
+            owner: None,
+            meta: Vec::new(),
+            prefer: None,
+            cancel: None,
+            instruction: None,
         }
     }
 }
@@ -589,9 +1324,10 @@ pub fn build_exclude_patterns(additional_excludes: &[String], use_defaults: bool
 }
 
 pub fn get_sidecar_path(repo_root: &Path, file_path: &Path) -> PathBuf {
-    let relative = file_path.strip_prefix(repo_root).unwrap_or(file_path);
+    let relative =
+        paths::relative_to(file_path, repo_root).unwrap_or_else(|| file_path.to_path_buf());
     let mut sidecar = repo_root.join(".ck");
-    sidecar.push(relative);
+    sidecar.push(&relative);
     let ext = relative
         .extension()
         .map(|e| format!("{}.ck", e.to_string_lossy()))
@@ -874,6 +1610,8 @@ mod tests {
             byte_end: 10,
             line_start: 1,
             line_end: 2,
+            col_start: 0,
+            col_end: 0,
         };
 
         assert_eq!(span.byte_start, 0);
@@ -883,6 +1621,24 @@ mod tests {
         assert!(span.is_valid());
     }
 
+    #[test]
+    fn test_span_fill_columns() {
+        let source = "fn main() {\r\n    run();\r\n}\n";
+        let mut span = Span::new_unchecked(3, 27, 1, 3);
+        span.fill_columns(source);
+        assert_eq!((span.col_start, span.col_end), (4, 2));
+
+        // A span ending in a line break ends on that line, not the next
+        let mut line = Span::new_unchecked(13, 25, 2, 2);
+        line.fill_columns(source);
+        assert_eq!((line.col_start, line.col_end), (1, 11));
+
+        // Spans older than column tracking deserialize with unknown columns
+        let json = r#"{"byte_start":0,"byte_end":1,"line_start":1,"line_end":1}"#;
+        let legacy: Span = serde_json::from_str(json).unwrap();
+        assert_eq!((legacy.col_start, legacy.col_end), (0, 0));
+    }
+
     #[test]
     fn test_search_options_default() {
         let options = SearchOptions::default();
@@ -895,6 +1651,7 @@ mod tests {
         assert!(!options.whole_word);
         assert!(!options.fixed_string);
         assert!(!options.line_numbers);
+        assert!(!options.column);
         assert_eq!(options.context_lines, 0);
         assert!(options.recursive);
         assert!(!options.json_output);
@@ -903,6 +1660,14 @@ mod tests {
         assert!(!options.show_filenames);
     }
 
+    #[test]
+    fn test_search_mode_from_str_aliases() {
+        assert_eq!("semantic".parse::<SearchMode>(), Ok(SearchMode::Semantic));
+        assert_eq!("lex".parse::<SearchMode>(), Ok(SearchMode::Lexical));
+        assert_eq!(" Sym ".parse::<SearchMode>(), Ok(SearchMode::Symbol));
+        assert!("fuzzy".parse::<SearchMode>().is_err());
+    }
+
     #[test]
     fn test_file_metadata_serialization() {
         let metadata = FileMetadata {
@@ -924,19 +1689,16 @@ mod tests {
     #[test]
     fn test_search_result_serialization() {
         let result = SearchResult {
-            file: PathBuf::from("test.txt"),
-            span: Span {
-                byte_start: 0,
-                byte_end: 10,
-                line_start: 1,
-                line_end: 1,
-            },
-            score: 0.95,
-            preview: "hello world".to_string(),
             lang: Some(Language::Rust),
             symbol: Some("main".to_string()),
             chunk_hash: Some("abc123".to_string()),
             index_epoch: Some(1699123456),
+            ..SearchResult::new(
+                "test.txt",
+                Span::new_unchecked(0, 10, 1, 1),
+                0.95,
+                "hello world",
+            )
         };
 
         let json = serde_json::to_string(&result).unwrap();
@@ -951,22 +1713,55 @@ mod tests {
         assert_eq!(result.index_epoch, deserialized.index_epoch);
     }
 
+    #[test]
+    fn test_relevance_feedback_toggles() {
+        let result = |line: usize| {
+            SearchResult::new(
+                "src/auth.rs",
+                Span::new_unchecked(0, 10, line, line),
+                0.5,
+                "",
+            )
+        };
+        let mut feedback = RelevanceFeedback::default();
+
+        assert!(feedback.toggle_pin(&result(1)));
+        assert!(feedback.is_pinned(&result(1)));
+        assert!(!feedback.is_pinned(&result(2)));
+
+        // Banning a pinned hit moves it to the banned list
+        assert!(feedback.toggle_ban(&result(1)));
+        assert!(!feedback.is_pinned(&result(1)));
+        assert!(feedback.is_banned(&result(1)));
+
+        assert!(!feedback.toggle_ban(&result(1)));
+        assert!(feedback.is_empty());
+    }
+
     #[test]
     fn test_jsonl_search_result_conversion() {
         let result = SearchResult {
-            file: PathBuf::from("src/auth.rs"),
-            span: Span {
-                byte_start: 1203,
-                byte_end: 1456,
-                line_start: 42,
-                line_end: 58,
-            },
-            score: 0.89,
-            preview: "function authenticate(user) {...}".to_string(),
             lang: Some(Language::Rust),
             symbol: Some("authenticate".to_string()),
             chunk_hash: Some("abc123def456".to_string()),
             index_epoch: Some(1699123456),
+            duplicates: vec![DuplicateLocation {
+                file: PathBuf::from("vendor/auth.rs"),
+                span: Span {
+                    byte_start: 0,
+                    byte_end: 253,
+                    line_start: 1,
+                    line_end: 17,
+                    col_start: 0,
+                    col_end: 0,
+                },
+            }],
+            ..SearchResult::new(
+                "src/auth.rs",
+                Span::new_unchecked(1203, 1456, 42, 58),
+                0.89,
+                "function authenticate(user) {...}",
+            )
         };
 
         // Test with snippet
@@ -984,6 +1779,7 @@ mod tests {
             Some("abc123def456".to_string())
         );
         assert_eq!(jsonl_with_snippet.index_epoch, Some(1699123456));
+        assert_eq!(jsonl_with_snippet.duplicates, result.duplicates);
 
         // Test without snippet
         let jsonl_no_snippet = JsonlSearchResult::from_search_result(&result, false);
@@ -1067,6 +1863,10 @@ mod tests {
             lex_rank: Some(1),
             vec_rank: Some(2),
             rrf_score: 0.85,
+            dense_score: Some(0.72),
+            lexical_score: None,
+            rerank_score: None,
+            index_score: None,
         };
 
         let result = JsonSearchResult {
@@ -1076,6 +1876,8 @@ mod tests {
                 byte_end: 5,
                 line_start: 1,
                 line_end: 1,
+                col_start: 0,
+                col_end: 0,
             },
             lang: None, // txt is not a supported language
             symbol: None,
@@ -1083,6 +1885,11 @@ mod tests {
             signals,
             preview: "hello".to_string(),
             model: "bge-small".to_string(),
+            duplicates: Vec::new(),
+            explain: None,
+            blame: None,
+            metadata: Default::default(),
+            more_in_file: Vec::new(),
         };
 
         let json = serde_json::to_string(&result).unwrap();
@@ -1118,6 +1925,38 @@ mod tests {
         assert_eq!(Language::from_extension("unknown"), None);
     }
 
+    #[test]
+    fn test_language_from_shebang_and_name() {
+        assert_eq!(
+            Language::from_shebang("#!/usr/bin/env python3\nprint(1)"),
+            Some(Language::Python)
+        );
+        assert_eq!(
+            Language::from_shebang("#!/usr/bin/env -S deno run\n"),
+            Some(Language::TypeScript)
+        );
+        assert_eq!(
+            Language::from_shebang("#!/usr/local/bin/ruby2.7"),
+            Some(Language::Ruby)
+        );
+        assert_eq!(Language::from_shebang("#!/bin/sh"), None);
+        assert_eq!(Language::from_shebang("print(1)"), None);
+        assert_eq!(
+            Language::detect(Path::new("bin/tool"), "#!/usr/bin/env node"),
+            Some(Language::JavaScript)
+        );
+        assert_eq!(
+            Language::detect(Path::new("lib.rs"), "#!/usr/bin/env python"),
+            Some(Language::Rust)
+        );
+
+        assert_eq!("Rust".parse::<Language>(), Ok(Language::Rust));
+        assert_eq!("py".parse::<Language>(), Ok(Language::Python));
+        assert_eq!("golang".parse::<Language>(), Ok(Language::Go));
+        assert_eq!("c++".parse::<Language>(), Ok(Language::Cpp));
+        assert!("cobol".parse::<Language>().is_err());
+    }
+
     #[test]
     fn test_language_from_extension_case_insensitive() {
         // Test uppercase extensions - only for actually supported languages
@@ -1512,4 +2351,54 @@ mod tests {
         assert!(!patterns.iter().any(|p| p.starts_with(' ')));
         assert!(!patterns.iter().any(|p| p.ends_with(' ')));
     }
+
+    #[test]
+    fn test_score_transforms() {
+        let mut scores = vec![-2.0, 0.0, 6.0];
+        ScoreTransform::Sigmoid.apply(&mut scores);
+        assert!((scores[1] - 0.5).abs() < 1e-6);
+        assert!(scores.iter().all(|s| (0.0..=1.0).contains(s)));
+
+        let mut scores = vec![-2.0, 0.0, 6.0];
+        ScoreTransform::MinMax.apply(&mut scores);
+        assert_eq!(scores, vec![0.0, 0.25, 1.0]);
+
+        let mut scores = vec![-2.0, 6.0];
+        ScoreTransform::Raw.apply(&mut scores);
+        assert_eq!(scores, vec![-2.0, 6.0]);
+
+        assert_eq!(
+            "min-max".parse::<ScoreTransform>(),
+            Ok(ScoreTransform::MinMax)
+        );
+        assert!("softmax".parse::<ScoreTransform>().is_err());
+    }
+
+    #[test]
+    fn test_score_normalizations() {
+        let mut scores = vec![1.0, 2.0, 3.0];
+        ScoreNormalization::ZScore.apply(&mut scores);
+        let expected = [-1.224_744_9, 0.0, 1.224_744_9];
+        assert!(
+            scores
+                .iter()
+                .zip(expected)
+                .all(|(s, e)| (s - e).abs() < 1e-5)
+        );
+        assert!((ScoreNormalization::ZScore.missing(&scores) + 2.224_744_9).abs() < 1e-5);
+
+        // Regex hits all score 1: present beats missing under either scheme
+        let mut flat = vec![1.0, 1.0];
+        ScoreNormalization::ZScore.apply(&mut flat);
+        assert!(ScoreNormalization::ZScore.missing(&flat) < flat[0]);
+        let mut flat = vec![1.0, 1.0];
+        ScoreNormalization::MinMax.apply(&mut flat);
+        assert!(ScoreNormalization::MinMax.missing(&flat) < flat[0]);
+
+        assert_eq!(
+            "zscore".parse::<ScoreNormalization>(),
+            Ok(ScoreNormalization::ZScore)
+        );
+        assert_eq!(ScoreNormalization::default().to_string(), "rank");
+    }
 }
@@ -0,0 +1,198 @@
+//! One spelling per file. Windows hands out the same file as `C:\repo\a.rs`,
+//! `c:\repo\a.rs` and `\\?\C:\repo\a.rs` (the verbatim form `canonicalize`
+//! returns), and users type `./src/../src/a.rs`; the index keeps each file
+//! under a single relative key so none of these index it twice.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Whether file names compare case-insensitively on this platform's default
+/// file systems (NTFS, APFS).
+pub const CASE_INSENSITIVE: bool = cfg!(any(windows, target_os = "macos"));
+
+/// Longest path the plain (non-verbatim) Windows APIs accept.
+const MAX_PATH: usize = 260;
+
+/// `path` without a Windows verbatim prefix: `\\?\C:\x` becomes `C:\x` and
+/// `\\?\UNC\server\share\x` becomes `\\server\share\x`. Paths too long for
+/// the plain form keep the prefix, and other platforms have no such prefix.
+pub fn strip_verbatim(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    path.to_str()
+        .and_then(strip_verbatim_str)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+fn strip_verbatim_str(path: &str) -> Option<String> {
+    let plain = match path.strip_prefix(r"\\?\UNC\") {
+        Some(rest) => format!(r"\\{}", rest),
+        None => {
+            let rest = path.strip_prefix(r"\\?\")?;
+            // Only drive paths have a plain form; `\\?\Volume{..}` does not
+            let drive = rest.as_bytes();
+            if !(drive.len() >= 3
+                && drive[0].is_ascii_alphabetic()
+                && drive[1] == b':'
+                && drive[2] == b'\\')
+            {
+                return None;
+            }
+            rest.to_string()
+        }
+    };
+    // Verbatim paths take `.` and `..` literally; plain ones would fold them
+    let literal_dots = plain.split('\\').any(|part| part == "." || part == "..");
+    (plain.len() < MAX_PATH && !literal_dots).then_some(plain)
+}
+
+/// `path` with `.` segments dropped and `..` folded into its parent, without
+/// touching the file system. An empty result is `.`.
+pub fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `/..` is `/`
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+    normalized
+}
+
+/// `path` canonicalized (symlinks resolved, and on Windows the on-disk case)
+/// without a verbatim prefix. A path that does not exist is made absolute and
+/// normalized lexically instead.
+pub fn canonicalize(path: &Path) -> PathBuf {
+    match path.canonicalize() {
+        Ok(canonical) => strip_verbatim(&canonical),
+        Err(_) => normalize_lexically(&strip_verbatim(&absolute(path))),
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+/// `file` relative to `root` however either is spelled, or `None` when `file`
+/// is not under `root`. The spellings are compared as given, then as
+/// absolute paths, then canonicalized.
+pub fn relative_to(file: &Path, root: &Path) -> Option<PathBuf> {
+    if let Ok(relative) = file.strip_prefix(root) {
+        return Some(normalize_lexically(relative));
+    }
+    let lexical = |path: &Path| normalize_lexically(&strip_verbatim(&absolute(path)));
+    if let Ok(relative) = lexical(file).strip_prefix(lexical(root)) {
+        return Some(normalize_lexically(relative));
+    }
+    canonicalize(file)
+        .strip_prefix(canonicalize(root))
+        .ok()
+        .map(normalize_lexically)
+}
+
+/// `path` with `/` separators, the form relative paths take in the index on
+/// every platform.
+pub fn to_slash(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        path.replace('\\', "/")
+    } else {
+        path.into_owned()
+    }
+}
+
+/// A key equal for every spelling of the same relative path on this
+/// platform: normalized, `/`-separated, and case-folded where names compare
+/// case-insensitively.
+pub fn identity_key(path: &Path) -> String {
+    let key = to_slash(&normalize_lexically(path));
+    if CASE_INSENSITIVE {
+        key.to_lowercase()
+    } else {
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbatim_prefixes_are_stripped() {
+        assert_eq!(
+            strip_verbatim_str(r"\\?\C:\repo\src\a.rs").as_deref(),
+            Some(r"C:\repo\src\a.rs")
+        );
+        assert_eq!(
+            strip_verbatim_str(r"\\?\UNC\server\share\repo").as_deref(),
+            Some(r"\\server\share\repo")
+        );
+        assert_eq!(strip_verbatim_str(r"C:\repo"), None);
+        assert_eq!(strip_verbatim_str(r"\\?\Volume{1234}\repo"), None);
+        assert_eq!(strip_verbatim_str(r"\\?\C:\repo\..\a.rs"), None);
+        let long = format!(r"\\?\C:\{}", "d\\".repeat(200));
+        assert_eq!(strip_verbatim_str(&long), None);
+    }
+
+    #[test]
+    fn test_lexical_normalization() {
+        assert_eq!(
+            normalize_lexically(Path::new("./src/../src/./a.rs")),
+            PathBuf::from("src/a.rs")
+        );
+        assert_eq!(
+            normalize_lexically(Path::new("../x/../../y")),
+            PathBuf::from("../../y")
+        );
+        assert_eq!(normalize_lexically(Path::new("/..")), PathBuf::from("/"));
+        assert_eq!(normalize_lexically(Path::new("./")), PathBuf::from("."));
+    }
+
+    #[test]
+    fn test_relative_to_accepts_any_spelling() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/a.rs"), "").unwrap();
+
+        let expected = Some(PathBuf::from("src").join("a.rs"));
+        assert_eq!(relative_to(&root.join("src/a.rs"), root), expected);
+        assert_eq!(relative_to(&root.join("src/../src/a.rs"), root), expected);
+        assert_eq!(
+            relative_to(&root.join("src/a.rs"), &root.join(".")),
+            expected
+        );
+        assert_eq!(
+            relative_to(&canonicalize(&root.join("src/a.rs")), root),
+            expected
+        );
+        assert_eq!(relative_to(Path::new("/elsewhere/a.rs"), root), None);
+    }
+
+    #[test]
+    fn test_identity_key() {
+        assert_eq!(identity_key(Path::new("./src/a.rs")), "src/a.rs");
+        assert_eq!(
+            identity_key(Path::new("Src/A.rs")) == identity_key(Path::new("src/a.rs")),
+            CASE_INSENSITIVE
+        );
+    }
+}
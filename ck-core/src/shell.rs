@@ -0,0 +1,17 @@
+//! User-configured commands (`query_rewriter`, `metadata_command`,
+//! `history.command`) are shell command lines, run by the platform's shell.
+
+use std::process::Command;
+
+/// `command` run by `sh -c`, or `cmd /C` on Windows.
+pub fn command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
@@ -1,7 +1,7 @@
 #[cfg(feature = "mixedbread")]
 use ck_embed::create_embedder;
 #[cfg(feature = "mixedbread")]
-use ck_embed::reranker::create_reranker;
+use ck_embed::reranker::{Document, create_reranker};
 #[cfg(feature = "mixedbread")]
 use ck_models::{ModelRegistry, RerankModelRegistry};
 
@@ -136,15 +136,20 @@ fn run_example() {
             // Test 6: Reranking
             println!("\n6. Testing Reranking");
             let query = "error handling in Rust";
-            let documents = vec![
-                "Rust error handling with Result and Option types".to_string(),
-                "Python web development frameworks".to_string(),
-                "Rust provides excellent error handling mechanisms".to_string(),
-                "JavaScript async programming patterns".to_string(),
+            let documents = [
+                "Rust error handling with Result and Option types",
+                "Python web development frameworks",
+                "Rust provides excellent error handling mechanisms",
+                "JavaScript async programming patterns",
             ];
             println!("   Query: '{}'", query);
             println!("   Reranking {} documents...", documents.len());
 
+            let documents: Vec<Document> = documents
+                .iter()
+                .enumerate()
+                .map(|(i, text)| Document::new(i, text))
+                .collect();
             match reranker.rerank(query, &documents) {
                 Ok(results) => {
                     println!("   ✅ Successfully reranked documents");
@@ -154,10 +159,10 @@ fn run_example() {
                             "      {}. Score: {:.4} | Doc: {}",
                             i + 1,
                             result.score,
-                            if result.document.len() > 60 {
-                                &result.document[..60]
+                            if documents[result.id].text.len() > 60 {
+                                &documents[result.id].text[..60]
                             } else {
-                                &result.document
+                                documents[result.id].text
                             }
                         );
                     }
@@ -1,5 +1,5 @@
 #[cfg(feature = "fastembed")]
-use ck_embed::create_reranker;
+use ck_embed::{Document, create_reranker};
 
 fn main() {
     #[cfg(not(feature = "fastembed"))]
@@ -22,12 +22,12 @@ fn run_example() {
     println!("Created reranker: {}", reranker.id());
 
     let query = "error handling in programming";
-    let documents = vec![
-        "try catch exception handling in Java".to_string(),
-        "user interface design patterns".to_string(),
-        "error handling with Result types in Rust".to_string(),
-        "database connection management".to_string(),
-        "exception handling best practices".to_string(),
+    let documents = [
+        "try catch exception handling in Java",
+        "user interface design patterns",
+        "error handling with Result types in Rust",
+        "database connection management",
+        "exception handling best practices",
     ];
 
     println!("\nQuery: '{}'", query);
@@ -36,6 +36,11 @@ fn run_example() {
         println!("  {}: {}", i + 1, doc);
     }
 
+    let documents: Vec<Document> = documents
+        .iter()
+        .enumerate()
+        .map(|(i, text)| Document::new(i, text))
+        .collect();
     let results = reranker
         .rerank(query, &documents)
         .expect("Failed to rerank documents");
@@ -50,7 +55,7 @@ fn run_example() {
             "  {}: [Score: {:.3}] {}",
             i + 1,
             result.score,
-            result.document
+            documents[result.id].text
         );
     }
 
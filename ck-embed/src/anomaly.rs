@@ -0,0 +1,91 @@
+//! Vectors that came out of a model without meaning anything: all zeros, with
+//! NaN or infinite values, of the wrong length, or no different from what the
+//! model makes of empty input. Each one still matches queries (or, zeroed,
+//! never does), so they are worth finding before they skew results.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Cosine similarity to the padding vector at which a chunk's embedding is
+/// taken for the model's output on no input at all.
+pub const PADDING_SIMILARITY: f32 = 0.9999;
+
+/// How an embedding is degenerate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorAnomaly {
+    /// Not as long as the model's embeddings
+    WrongDimensions,
+    /// Holds a NaN or an infinity
+    NonFinite,
+    /// Every value is zero, as when a short row was zero-filled
+    Zero,
+    /// The same as the embedding of an empty string
+    Padding,
+}
+
+impl fmt::Display for VectorAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::WrongDimensions => "wrong dimensions",
+            Self::NonFinite => "NaN or infinite values",
+            Self::Zero => "all zeros",
+            Self::Padding => "same as empty input",
+        })
+    }
+}
+
+/// What is wrong with `vector`, if anything, for a model of `dim`
+/// dimensions. `padding` is the model's embedding of an empty string, when
+/// known.
+pub fn vector_anomaly(
+    vector: &[f32],
+    dim: usize,
+    padding: Option<&[f32]>,
+) -> Option<VectorAnomaly> {
+    if vector.len() != dim {
+        return Some(VectorAnomaly::WrongDimensions);
+    }
+    if vector.iter().any(|value| !value.is_finite()) {
+        return Some(VectorAnomaly::NonFinite);
+    }
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return Some(VectorAnomaly::Zero);
+    }
+    let padding = padding.filter(|padding| padding.len() == dim)?;
+    let padding_norm = padding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if padding_norm == 0.0 || !padding_norm.is_finite() {
+        return None;
+    }
+    let dot: f32 = vector.iter().zip(padding).map(|(a, b)| a * b).sum();
+    (dot / (norm * padding_norm) >= PADDING_SIMILARITY).then_some(VectorAnomaly::Padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_anomaly() {
+        let padding = [0.5, 0.5, 0.0];
+        assert_eq!(vector_anomaly(&[0.1, 0.9, 0.2], 3, Some(&padding)), None);
+        assert_eq!(
+            vector_anomaly(&[0.1, 0.9], 3, None),
+            Some(VectorAnomaly::WrongDimensions)
+        );
+        assert_eq!(
+            vector_anomaly(&[0.1, f32::NAN, 0.2], 3, None),
+            Some(VectorAnomaly::NonFinite)
+        );
+        assert_eq!(
+            vector_anomaly(&[0.0, 0.0, 0.0], 3, Some(&padding)),
+            Some(VectorAnomaly::Zero)
+        );
+        assert_eq!(
+            vector_anomaly(&[2.0, 2.0, 0.0], 3, Some(&padding)),
+            Some(VectorAnomaly::Padding)
+        );
+        assert_eq!(vector_anomaly(&[2.0, 2.0, 0.0], 3, None), None);
+    }
+}
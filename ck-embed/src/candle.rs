@@ -0,0 +1,212 @@
+//! BERT-family encoders run on candle, for builds that cannot ship ONNX Runtime
+//! (static musl binaries, platforms without ort prebuilts). Weights come from
+//! `model.safetensors` in a Hugging Face repo or a local directory; the
+//! `candle-cuda` and `candle-metal` features run them on the GPU when one is found.
+
+use std::path::{Path, PathBuf};
+
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config};
+use ck_models::{ModelConfig, Pooling};
+use ndarray::Array2;
+use tokenizers::Tokenizer;
+
+use crate::{
+    EmbedError, Embedder, ModelDownloadCallback, Result,
+    encoder_input::EncoderInputBuilder,
+    pooling::{POOLING_PATH, read_pooling},
+};
+
+const CONFIG_PATH: &str = "config.json";
+const TOKENIZER_PATH: &str = "tokenizer.json";
+const WEIGHTS_PATH: &str = "model.safetensors";
+
+pub struct CandleEmbedder {
+    model: BertModel,
+    inputs: EncoderInputBuilder,
+    device: Device,
+    dim: usize,
+    model_name: String,
+    pooling: Pooling,
+    normalize: bool,
+    strict_dimensions: bool,
+}
+
+impl CandleEmbedder {
+    pub fn new(
+        config: &ModelConfig,
+        progress_callback: Option<ModelDownloadCallback>,
+    ) -> Result<Self> {
+        if let Some(cb) = progress_callback.as_ref() {
+            cb(&format!(
+                "Downloading safetensors model ({}) if needed...",
+                config.name
+            ));
+        }
+
+        let config_path = fetch(&config.name, CONFIG_PATH, "model config")?;
+        let tokenizer_path = fetch(&config.name, TOKENIZER_PATH, "tokenizer")?;
+        let weights_path = fetch(&config.name, WEIGHTS_PATH, "safetensors weights")?;
+        // Repos without sentence-transformers metadata get mean pooling
+        let pooling = fetch(&config.name, POOLING_PATH, "pooling config")
+            .ok()
+            .and_then(|path| read_pooling(&path))
+            .unwrap_or_default();
+
+        let bert_config: Config = serde_json::from_str(&std::fs::read_to_string(&config_path)?)
+            .map_err(|e| EmbedError::model_load(&config.name, e))?;
+        let device = select_device();
+
+        if let Some(cb) = progress_callback.as_ref() {
+            cb(&format!("Loading candle embedder on {device:?}..."));
+        }
+
+        // SAFETY: the weights file is in ck's model cache or a user-chosen
+        // directory and is not modified while mapped
+        let vb =
+            unsafe { VarBuilder::from_mmaped_safetensors(&[weights_path], DType::F32, &device) }
+                .map_err(|e| EmbedError::model_load(&config.name, e))?;
+        let model = BertModel::load(vb, &bert_config)
+            .map_err(|e| EmbedError::model_load(&config.name, e))?;
+
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| EmbedError::Tokenizer(e.to_string()))?;
+        let inputs = EncoderInputBuilder::new(
+            tokenizer,
+            config.max_tokens.min(bert_config.max_position_embeddings),
+            config.truncation,
+            true,
+        )?;
+
+        let dim =
+            crate::resolve_dimensions(&config.name, config.dimensions, bert_config.hidden_size)?;
+
+        Ok(Self {
+            model,
+            inputs,
+            device,
+            dim,
+            model_name: config.name.clone(),
+            pooling,
+            normalize: config.normalize,
+            strict_dimensions: config.strict_dimensions,
+        })
+    }
+
+    /// Copy one padded `[batch, tokens]` input onto the model's device.
+    fn batch_tensor(&self, rows: &Array2<i64>) -> Result<Tensor> {
+        let values = rows
+            .as_slice()
+            .expect("encoder inputs are in standard layout");
+        Ok(Tensor::from_slice(values, rows.dim(), &self.device)?)
+    }
+}
+
+impl Embedder for CandleEmbedder {
+    fn id(&self) -> &'static str {
+        "candle"
+    }
+
+    fn dim(&self) -> usize {
+        self.dim
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    fn free_device_memory(&self) -> Option<u64> {
+        free_device_memory(&self.device)
+    }
+
+    #[tracing::instrument(name = "embed", level = "debug", skip_all, fields(model = %self.model_name, texts = texts.len()))]
+    fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let inputs = self.inputs.texts(texts)?;
+        let input_ids = self.batch_tensor(&inputs.input_ids)?;
+        let mask = self.batch_tensor(&inputs.attention_mask)?;
+        let type_ids = match &inputs.token_type_ids {
+            Some(type_ids) => self.batch_tensor(type_ids)?,
+            None => input_ids.zeros_like()?,
+        };
+
+        let _span = tracing::debug_span!("candle_forward", batch = texts.len()).entered();
+        let hidden = self.model.forward(&input_ids, &type_ids, Some(&mask))?;
+        let pooled = match self.pooling {
+            Pooling::Cls => hidden.narrow(1, 0, 1)?.squeeze(1)?,
+            Pooling::Mean => {
+                let mask = mask.to_dtype(DType::F32)?.unsqueeze(2)?;
+                let summed = hidden.broadcast_mul(&mask)?.sum(1)?;
+                summed.broadcast_div(&mask.sum(1)?.clamp(1.0, f32::MAX)?)?
+            }
+        };
+
+        pooled
+            .to_vec2::<f32>()?
+            .into_iter()
+            .map(|row| {
+                crate::check_width(
+                    &self.model_name,
+                    row.len(),
+                    self.dim,
+                    self.strict_dimensions,
+                )?;
+                Ok(crate::fit_row(row, self.dim, self.normalize))
+            })
+            .collect()
+    }
+}
+
+/// `file` from a local model directory, or downloaded from the Hugging Face repo.
+fn fetch(model: &str, file: &str, asset: &'static str) -> Result<PathBuf> {
+    let dir = Path::new(model);
+    if dir.is_dir() {
+        let path = dir.join(file);
+        return if path.is_file() {
+            Ok(path)
+        } else {
+            Err(EmbedError::model_load(
+                model,
+                format!("{asset} not found at {}", path.display()),
+            ))
+        };
+    }
+    crate::download_hub_file(model, model, file, asset)
+}
+
+/// Bytes the GPU has left for this process, once the model is loaded.
+fn free_device_memory(device: &Device) -> Option<u64> {
+    #[cfg(feature = "candle-cuda")]
+    if let Device::Cuda(cuda) = device {
+        use candle_core::cuda::cudarc::driver::result::mem_get_info;
+        cuda.cuda_stream().context().bind_to_thread().ok()?;
+        return mem_get_info().ok().map(|(free, _)| free as u64);
+    }
+    #[cfg(feature = "candle-metal")]
+    if let Device::Metal(metal) = device {
+        let device = metal.metal_device();
+        return Some(
+            device
+                .recommended_max_working_set_size()
+                .saturating_sub(device.current_allocated_size()) as u64,
+        );
+    }
+    let _ = device;
+    None
+}
+
+fn select_device() -> Device {
+    #[cfg(feature = "candle-cuda")]
+    if let Ok(device) = Device::new_cuda(0) {
+        return device;
+    }
+    #[cfg(feature = "candle-metal")]
+    if let Ok(device) = Device::new_metal(0) {
+        return device;
+    }
+    Device::Cpu
+}
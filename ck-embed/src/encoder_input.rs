@@ -0,0 +1,157 @@
+//! Tokenized, padded input tensors for BERT-style encoders. The embedders and
+//! rerankers run on ONNX Runtime or candle all feed `input_ids`,
+//! `attention_mask` and, for graphs that declare it, `token_type_ids`; this
+//! builds them in one place so truncation keeps each model's own special tokens
+//! (`[CLS]`/`[SEP]`, `<s>`/`</s>`) and padding uses the tokenizer's pad id.
+
+use ck_models::Truncation;
+use ndarray::Array2;
+#[cfg(feature = "mixedbread")]
+use ort::session::{Session, SessionOutputs};
+#[cfg(feature = "mixedbread")]
+use ort::value::Value;
+use tokenizers::{EncodeInput, Encoding, Tokenizer};
+
+use crate::{EmbedError, Result, truncation::kept_positions};
+
+pub(crate) struct EncoderInputBuilder {
+    tokenizer: Tokenizer,
+    max_length: usize,
+    truncation: Truncation,
+    token_type_ids: bool,
+    pad_id: u32,
+}
+
+/// One batch of encoder inputs, padded to its longest row.
+pub(crate) struct EncoderInputs {
+    pub input_ids: Array2<i64>,
+    pub attention_mask: Array2<i64>,
+    pub token_type_ids: Option<Array2<i64>>,
+}
+
+impl EncoderInputBuilder {
+    /// Builder for a graph whose inputs include `token_type_ids` when
+    /// `token_type_ids` is set. Truncation and padding configured in the
+    /// tokenizer file are replaced by the builder's own.
+    pub fn new(
+        mut tokenizer: Tokenizer,
+        max_length: usize,
+        truncation: Truncation,
+        token_type_ids: bool,
+    ) -> Result<Self> {
+        let pad_id = tokenizer.get_padding().map_or(0, |padding| padding.pad_id);
+        tokenizer.with_padding(None);
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| EmbedError::Tokenizer(e.to_string()))?;
+        Ok(Self {
+            tokenizer,
+            max_length: max_length.max(1),
+            truncation,
+            token_type_ids,
+            pad_id,
+        })
+    }
+
+    /// Inputs for the graph session `session`, which decides whether
+    /// `token_type_ids` are fed.
+    #[cfg(feature = "mixedbread")]
+    pub fn for_session(
+        tokenizer: Tokenizer,
+        max_length: usize,
+        truncation: Truncation,
+        session: &Session,
+    ) -> Result<Self> {
+        let token_type_ids = session
+            .inputs()
+            .iter()
+            .any(|input| input.name() == "token_type_ids");
+        Self::new(tokenizer, max_length, truncation, token_type_ids)
+    }
+
+    /// Inputs for single texts.
+    #[tracing::instrument(name = "tokenize", level = "debug", skip_all, fields(texts = texts.len()))]
+    pub fn texts(&self, texts: &[String]) -> Result<EncoderInputs> {
+        self.build(
+            texts
+                .iter()
+                .map(|text| EncodeInput::Single(text.as_str().into())),
+        )
+    }
+
+    /// Inputs for `(query, document)` pairs, as cross-encoders take them.
+    #[cfg(feature = "mixedbread")]
+    #[tracing::instrument(name = "tokenize", level = "debug", skip_all, fields(texts = pairs.len()))]
+    pub fn pairs(&self, pairs: &[(&str, &str)]) -> Result<EncoderInputs> {
+        self.build(
+            pairs
+                .iter()
+                .map(|(query, document)| EncodeInput::Dual((*query).into(), (*document).into())),
+        )
+    }
+
+    fn build<'s>(&self, inputs: impl Iterator<Item = EncodeInput<'s>>) -> Result<EncoderInputs> {
+        let encodings = inputs
+            .map(|input| {
+                self.tokenizer
+                    .encode(input, true)
+                    .map_err(|e| EmbedError::Tokenizer(format!("encode failed: {e}")))
+            })
+            .collect::<Result<Vec<Encoding>>>()?;
+
+        let rows: Vec<Vec<usize>> = encodings
+            .iter()
+            .map(|encoding| {
+                kept_positions(
+                    encoding.get_special_tokens_mask(),
+                    self.max_length,
+                    self.truncation,
+                )
+            })
+            .collect();
+
+        let batch = encodings.len();
+        let seq_len = rows.iter().map(Vec::len).max().unwrap_or(1).max(1);
+        let mut input_ids = Array2::from_elem((batch, seq_len), self.pad_id as i64);
+        let mut attention_mask = Array2::zeros((batch, seq_len));
+        let mut token_type_ids = self.token_type_ids.then(|| Array2::zeros((batch, seq_len)));
+
+        for (row, (encoding, positions)) in encodings.iter().zip(&rows).enumerate() {
+            let ids = encoding.get_ids();
+            let mask = encoding.get_attention_mask();
+            let type_ids = encoding.get_type_ids();
+            for (col, &pos) in positions.iter().enumerate() {
+                input_ids[[row, col]] = ids[pos] as i64;
+                attention_mask[[row, col]] = mask[pos] as i64;
+                if let Some(token_types) = token_type_ids.as_mut()
+                    && let Some(&type_id) = type_ids.get(pos)
+                {
+                    token_types[[row, col]] = type_id as i64;
+                }
+            }
+        }
+
+        Ok(EncoderInputs {
+            input_ids,
+            attention_mask,
+            token_type_ids,
+        })
+    }
+}
+
+#[cfg(feature = "mixedbread")]
+impl EncoderInputs {
+    /// Run `session` on these inputs.
+    pub fn run<'s>(&self, session: &'s mut Session) -> Result<SessionOutputs<'s>> {
+        let input_ids = Value::from_array(self.input_ids.clone())?;
+        let attention_mask = Value::from_array(self.attention_mask.clone())?;
+        Ok(match &self.token_type_ids {
+            Some(token_types) => session.run(ort::inputs![
+                input_ids,
+                attention_mask,
+                Value::from_array(token_types.clone())?
+            ])?,
+            None => session.run(ort::inputs![input_ids, attention_mask])?,
+        })
+    }
+}
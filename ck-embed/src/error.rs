@@ -0,0 +1,98 @@
+use thiserror::Error;
+
+/// Failures from loading and running embedding and reranking models.
+#[derive(Debug, Error)]
+pub enum EmbedError {
+    #[error(transparent)]
+    Model(#[from] ck_models::ModelError),
+
+    #[error("Unsupported {kind} provider '{provider}'")]
+    UnsupportedProvider {
+        kind: &'static str,
+        provider: String,
+    },
+
+    #[error("Model '{model}' requires the `{feature}` feature. Rebuild ck with {feature} support.")]
+    FeatureDisabled {
+        model: String,
+        feature: &'static str,
+    },
+
+    #[error("Failed to download {asset} for {model}: {message}")]
+    DownloadFailed {
+        model: String,
+        asset: &'static str,
+        message: String,
+    },
+
+    #[error("Failed to load model '{model}': {message}")]
+    ModelLoad { model: String, message: String },
+
+    #[error("Tokenizer error: {0}")]
+    Tokenizer(String),
+
+    #[error(
+        "Model '{model}' produces {actual}-dimensional embeddings, but its registry entry declares {expected}. Fix `dimensions` in the model registry, or set it to 0 to detect it automatically."
+    )]
+    DimMismatch {
+        model: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("Inference error: {0}")]
+    Inference(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl EmbedError {
+    pub(crate) fn model_load(model: &str, err: impl std::fmt::Display) -> Self {
+        Self::ModelLoad {
+            model: model.to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    /// Whether the model ran out of memory, so that a smaller batch may
+    /// succeed. Runtimes report it only in their messages: CUDA's
+    /// `CUDA_ERROR_OUT_OF_MEMORY`, ONNX Runtime's "Failed to allocate memory".
+    pub fn is_out_of_memory(&self) -> bool {
+        let Self::Inference(message) = self else {
+            return false;
+        };
+        let message = message.to_ascii_lowercase();
+        ["out of memory", "out_of_memory", "failed to allocate"]
+            .iter()
+            .any(|symptom| message.contains(symptom))
+    }
+
+    #[cfg(any(feature = "fastembed", feature = "mixedbread"))]
+    pub(crate) fn inference(err: impl std::fmt::Display) -> Self {
+        Self::Inference(err.to_string())
+    }
+}
+
+#[cfg(feature = "mixedbread")]
+impl From<ort::Error> for EmbedError {
+    fn from(err: ort::Error) -> Self {
+        Self::Inference(err.to_string())
+    }
+}
+
+#[cfg(feature = "mixedbread")]
+impl From<ndarray::ShapeError> for EmbedError {
+    fn from(err: ndarray::ShapeError) -> Self {
+        Self::Inference(err.to_string())
+    }
+}
+
+#[cfg(feature = "candle")]
+impl From<candle_core::Error> for EmbedError {
+    fn from(err: candle_core::Error) -> Self {
+        Self::Inference(err.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, EmbedError>;
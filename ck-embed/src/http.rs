@@ -0,0 +1,386 @@
+//! Requests to embedders served over HTTP, made reliable for large index
+//! builds against hosted APIs: bodies compressed as the model's `remote`
+//! config asks, transient failures retried with exponential backoff,
+//! `Retry-After` and `x-ratelimit-*` headers honored, and in-flight requests
+//! capped per endpoint across every embedder in the process.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, Condvar, LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ck_models::{Compression, RemoteConfig};
+use serde::de::DeserializeOwned;
+
+use crate::{EmbedError, Result};
+
+/// Bodies smaller than this are sent as they are.
+const COMPRESS_MIN_BYTES: usize = 1024;
+
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+/// Longest wait a `Retry-After` or rate-limit reset header can ask for.
+const SERVER_WAIT_MAX: Duration = Duration::from_secs(600);
+
+/// Endpoints by base URL, shared so shard workers' embedders queue together.
+static LIMITERS: LazyLock<Mutex<HashMap<String, Arc<Limiter>>>> = LazyLock::new(Default::default);
+
+#[derive(Default)]
+struct LimiterState {
+    in_flight: usize,
+    /// Set by rate-limit headers; no request starts before it
+    not_before: Option<Instant>,
+}
+
+/// Concurrency cap and rate-limit pause for one endpoint.
+#[derive(Default)]
+struct Limiter {
+    state: Mutex<LimiterState>,
+    freed: Condvar,
+}
+
+struct Permit<'a>(&'a Limiter);
+
+impl Limiter {
+    fn acquire(&self, max_in_flight: usize) -> Permit<'_> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.in_flight >= max_in_flight.max(1) {
+            state = self.freed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.in_flight += 1;
+        let wait = state
+            .not_before
+            .map(|at| at.saturating_duration_since(Instant::now()));
+        drop(state);
+        if let Some(wait) = wait.filter(|wait| !wait.is_zero()) {
+            tracing::debug!("Waiting {:?} for the endpoint's rate limit", wait);
+            std::thread::sleep(wait);
+        }
+        Permit(self)
+    }
+
+    fn pause_for(&self, wait: Duration) {
+        let until = Instant::now() + wait;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.not_before = Some(state.not_before.map_or(until, |at| at.max(until)));
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.in_flight -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+/// JSON requests to one endpoint under a model's `remote` settings.
+pub(crate) struct HttpTransport {
+    agent: ureq::Agent,
+    base_url: String,
+    config: RemoteConfig,
+    api_key: Option<String>,
+    limiter: Arc<Limiter>,
+}
+
+impl HttpTransport {
+    pub(crate) fn new(agent: ureq::Agent, base_url: String, config: &RemoteConfig) -> Self {
+        let api_key = config
+            .api_key_env
+            .as_ref()
+            .and_then(|name| std::env::var(name).ok())
+            .filter(|key| !key.is_empty());
+        let limiter = LIMITERS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(base_url.clone())
+            .or_default()
+            .clone();
+        Self {
+            agent,
+            base_url,
+            config: config.clone(),
+            api_key,
+            limiter,
+        }
+    }
+
+    pub(crate) fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub(crate) fn batch_size(&self) -> usize {
+        self.config.batch_size.max(1)
+    }
+
+    pub(crate) fn max_concurrency(&self) -> usize {
+        self.config.max_concurrency.max(1)
+    }
+
+    /// POST `body` to `path` and decode the JSON reply, retrying transient
+    /// failures.
+    pub(crate) fn post_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Result<T> {
+        let url = format!("{}{}", self.base_url, path);
+        let body = serde_json::to_vec(body)
+            .map_err(|e| EmbedError::Inference(format!("Invalid request body: {e}")))?;
+        let (payload, encoding) = compress(body, self.config.compression)?;
+
+        let mut attempt = 0;
+        loop {
+            let permit = self.limiter.acquire(self.config.max_concurrency);
+            let mut request = self
+                .agent
+                .post(&url)
+                .set("Content-Type", "application/json");
+            if let Some(encoding) = encoding {
+                request = request.set("Content-Encoding", encoding);
+            }
+            if let Some(key) = &self.api_key {
+                request = request.set("Authorization", &format!("Bearer {key}"));
+            }
+            let result = request.send_bytes(&payload);
+            drop(permit);
+
+            let retry_in = match result {
+                Ok(response) => {
+                    if let Some(wait) = exhausted_budget(&response) {
+                        self.limiter.pause_for(wait);
+                    }
+                    return Ok(response.into_json()?);
+                }
+                Err(ureq::Error::Status(code, response))
+                    if is_retryable(code) && attempt < self.config.max_retries =>
+                {
+                    let wait = retry_after(&response).unwrap_or_else(|| backoff(attempt));
+                    if code == 429 {
+                        self.limiter.pause_for(wait);
+                    }
+                    tracing::debug!("{} returned {}; retrying in {:?}", url, code, wait);
+                    wait
+                }
+                Err(ureq::Error::Status(code, response)) => {
+                    let detail = response.into_string().unwrap_or_default();
+                    return Err(EmbedError::Inference(format!(
+                        "{url} returned {code}: {}",
+                        detail.trim()
+                    )));
+                }
+                Err(ureq::Error::Transport(err)) if attempt < self.config.max_retries => {
+                    let wait = backoff(attempt);
+                    tracing::debug!(
+                        "Request to {} failed ({}); retrying in {:?}",
+                        url,
+                        err,
+                        wait
+                    );
+                    wait
+                }
+                Err(err) => {
+                    return Err(EmbedError::Inference(format!(
+                        "{url} request failed: {err}"
+                    )));
+                }
+            };
+            std::thread::sleep(retry_in);
+            attempt += 1;
+        }
+    }
+}
+
+fn compress(body: Vec<u8>, compression: Compression) -> Result<(Vec<u8>, Option<&'static str>)> {
+    if body.len() < COMPRESS_MIN_BYTES {
+        return Ok((body, None));
+    }
+    match compression {
+        Compression::None => Ok((body, None)),
+        Compression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&body)?;
+            Ok((encoder.finish()?, Some("gzip")))
+        }
+        Compression::Zstd => Ok((zstd::encode_all(body.as_slice(), 3)?, Some("zstd"))),
+    }
+}
+
+fn is_retryable(code: u16) -> bool {
+    matches!(code, 408 | 429) || (500..600).contains(&code)
+}
+
+/// `BACKOFF_BASE` doubled per attempt, capped, with up to a quarter added as
+/// jitter so parallel workers do not retry in lockstep.
+fn backoff(attempt: u32) -> Duration {
+    let delay = BACKOFF_BASE
+        .saturating_mul(1 << attempt.min(16))
+        .min(BACKOFF_MAX);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    delay + delay / 4 * (nanos % 1000) / 1000
+}
+
+/// `Retry-After` in seconds; HTTP dates are left to the backoff.
+fn retry_after(response: &ureq::Response) -> Option<Duration> {
+    response
+        .header("retry-after")?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .and_then(server_wait)
+}
+
+/// A wait of `secs` asked for by the server, capped at [`SERVER_WAIT_MAX`];
+/// none when it is negative or not a number.
+fn server_wait(secs: f64) -> Option<Duration> {
+    if secs.is_nan() || secs < 0.0 {
+        return None;
+    }
+    Some(
+        Duration::try_from_secs_f64(secs)
+            .unwrap_or(SERVER_WAIT_MAX)
+            .min(SERVER_WAIT_MAX),
+    )
+}
+
+/// How long to hold off when a response says no requests or tokens are left
+/// in the current window (OpenAI-style `x-ratelimit-*` headers).
+fn exhausted_budget(response: &ureq::Response) -> Option<Duration> {
+    ["requests", "tokens"]
+        .iter()
+        .filter(|kind| {
+            response
+                .header(&format!("x-ratelimit-remaining-{kind}"))
+                .and_then(|remaining| remaining.trim().parse::<u64>().ok())
+                == Some(0)
+        })
+        .filter_map(|kind| parse_reset(response.header(&format!("x-ratelimit-reset-{kind}"))?))
+        .max()
+}
+
+/// A reset interval such as `20ms`, `1s`, `6m0s` or `1h2m3.5s`, or bare seconds.
+fn parse_reset(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return server_wait(secs);
+    }
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .filter(|&end| end > 0)?;
+        let amount: f64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        total += amount * scale;
+        rest = &rest[unit_len..];
+    }
+    server_wait(total)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_reset_intervals() {
+        assert_eq!(parse_reset("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_reset("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_reset("9m3.5s"), Some(Duration::from_secs_f64(543.5)));
+        assert_eq!(parse_reset("2"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_reset("soon"), None);
+
+        // Servers cannot hold requests back indefinitely, or crash the wait
+        assert_eq!(parse_reset("1h2m3.5s"), Some(SERVER_WAIT_MAX));
+        assert_eq!(parse_reset("1e300"), Some(SERVER_WAIT_MAX));
+        assert_eq!(parse_reset("inf"), Some(SERVER_WAIT_MAX));
+        assert_eq!(parse_reset("-5"), None);
+        assert_eq!(parse_reset("NaN"), None);
+        assert_eq!(server_wait(1e300), Some(SERVER_WAIT_MAX));
+        assert_eq!(server_wait(-1.0), None);
+        assert!(backoff(20) <= BACKOFF_MAX + BACKOFF_MAX / 4);
+    }
+
+    #[test]
+    fn test_compress_round_trips() {
+        let body = br#"{"input": ["fn retry() {}"]}"#.repeat(100);
+        let (gzip, encoding) = compress(body.clone(), Compression::Gzip).unwrap();
+        assert_eq!(encoding, Some("gzip"));
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(gzip.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+
+        let (zstd, encoding) = compress(body.clone(), Compression::Zstd).unwrap();
+        assert_eq!(encoding, Some("zstd"));
+        assert_eq!(zstd::decode_all(zstd.as_slice()).unwrap(), body);
+
+        let (small, encoding) = compress(b"{}".to_vec(), Compression::Zstd).unwrap();
+        assert_eq!((small.as_slice(), encoding), (&b"{}"[..], None));
+    }
+
+    /// Answer each connection with the next canned response.
+    pub(crate) fn serve(responses: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_post_retries_rate_limited_requests() {
+        let url = serve(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 503 Service Unavailable\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: 11\r\n\r\n{\"ok\":true}",
+        ]);
+        let transport = HttpTransport::new(ureq::agent(), url.clone(), &RemoteConfig::default());
+        let reply: serde_json::Value = transport
+            .post_json("/v1/embeddings", &serde_json::json!({ "input": ["x"] }))
+            .unwrap();
+        assert_eq!(reply, serde_json::json!({ "ok": true }));
+
+        let url = serve(vec![
+            "HTTP/1.1 400 Bad Request\r\nConnection: close\r\nContent-Length: 9\r\n\r\nbad input",
+        ]);
+        let transport = HttpTransport::new(ureq::agent(), url, &RemoteConfig::default());
+        let err = transport
+            .post_json::<serde_json::Value>("/v1/embeddings", &serde_json::json!({}))
+            .unwrap_err();
+        assert!(err.to_string().contains("400: bad input"));
+    }
+}
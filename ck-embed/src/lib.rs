@@ -1,34 +1,96 @@
-use anyhow::{Result, bail};
+#[cfg(feature = "fastembed")]
+use ck_models::Truncation;
 use ck_models::{ModelConfig, ModelRegistry};
 #[cfg(feature = "fastembed")]
+use std::borrow::Cow;
+#[cfg(feature = "fastembed")]
 use std::path::Path;
-#[cfg(any(feature = "fastembed", feature = "mixedbread"))]
+#[cfg(any(
+    feature = "fastembed",
+    feature = "mixedbread",
+    feature = "candle",
+    feature = "llama-server"
+))]
 use std::path::PathBuf;
 
+mod anomaly;
+mod error;
+mod limits;
+mod plugin;
+mod providers;
 pub mod reranker;
 pub mod tokenizer;
 
+pub use anomaly::{PADDING_SIMILARITY, VectorAnomaly, vector_anomaly};
+pub use error::{EmbedError, Result};
+pub use limits::{ResourceLimits, gpu_batch_size, resource_limits, set_resource_limits};
+pub use plugin::{PLUGIN_PREFIX, PluginEmbedder};
+pub use providers::{
+    EmbedderFactory, RerankerFactory, embedder_providers, register_embedder_provider,
+    register_reranker_provider, reranker_providers,
+};
 pub use reranker::{
-    RerankResult, Reranker, create_reranker, create_reranker_for_config,
+    Document, RerankResult, Reranker, create_reranker, create_reranker_for_config,
     create_reranker_with_progress,
 };
 pub use tokenizer::TokenEstimator;
 
+#[cfg(any(feature = "mixedbread", feature = "candle"))]
+mod encoder_input;
 #[cfg(feature = "mixedbread")]
 mod mixedbread;
+#[cfg(any(feature = "mixedbread", feature = "candle"))]
+mod pooling;
+#[cfg(any(feature = "fastembed", feature = "mixedbread", feature = "candle"))]
+mod truncation;
 #[cfg(feature = "mixedbread")]
 use mixedbread::MixedbreadEmbedder;
 
+#[cfg(feature = "candle")]
+mod candle;
+#[cfg(feature = "candle")]
+use candle::CandleEmbedder;
+
+#[cfg(feature = "openai")]
+mod http;
+#[cfg(feature = "llama-server")]
+mod llama_server;
+#[cfg(feature = "llama-server")]
+pub use llama_server::LLAMA_SERVER_ENV;
+#[cfg(feature = "llama-server")]
+use llama_server::LlamaServerEmbedder;
+#[cfg(feature = "openai")]
+mod openai;
+#[cfg(feature = "openai")]
+use openai::OpenAiEmbedder;
+
 pub trait Embedder: Send + Sync {
     fn id(&self) -> &'static str;
     fn dim(&self) -> usize;
     fn model_name(&self) -> &str;
     fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    /// Input tokens sent so far, as the provider billed them; `None` for
+    /// providers that run locally and cost nothing per token.
+    fn tokens_used(&self) -> Option<u64> {
+        None
+    }
+
+    /// Bytes free on the GPU the model runs on, for sizing batches; `None`
+    /// on the CPU.
+    fn free_device_memory(&self) -> Option<u64> {
+        None
+    }
 }
 
 pub type ModelDownloadCallback = Box<dyn Fn(&str) + Send + Sync>;
 
-#[cfg(any(feature = "fastembed", feature = "mixedbread"))]
+#[cfg(any(
+    feature = "fastembed",
+    feature = "mixedbread",
+    feature = "candle",
+    feature = "llama-server"
+))]
 pub(crate) fn model_cache_root() -> Result<PathBuf> {
     let base = if let Some(cache_home) = std::env::var_os("XDG_CACHE_HOME") {
         PathBuf::from(cache_home).join("ck")
@@ -43,6 +105,76 @@ pub(crate) fn model_cache_root() -> Result<PathBuf> {
     Ok(base.join("models"))
 }
 
+/// Fetch `file` from the Hugging Face repo `repo` into the model cache.
+#[cfg(any(feature = "mixedbread", feature = "candle", feature = "llama-server"))]
+pub(crate) fn download_hub_file(
+    model_id: &str,
+    repo: &str,
+    file: &str,
+    asset: &'static str,
+) -> Result<PathBuf> {
+    use hf_hub::{Repo, RepoType, api::sync::ApiBuilder};
+
+    let cache_dir = model_cache_root()?;
+    std::fs::create_dir_all(&cache_dir)?;
+
+    let download_failed = |e: hf_hub::api::sync::ApiError| EmbedError::DownloadFailed {
+        model: model_id.to_string(),
+        asset,
+        message: e.to_string(),
+    };
+
+    let api = ApiBuilder::new()
+        .with_cache_dir(cache_dir)
+        .build()
+        .map_err(download_failed)?;
+
+    api.repo(Repo::with_revision(
+        repo.to_string(),
+        RepoType::Model,
+        "main".to_string(),
+    ))
+    .get(file)
+    .map_err(download_failed)
+}
+
+/// Check that a model returned embeddings `width` values wide, as it was
+/// loaded with. Under `strict` (a registry entry's `strict_dimensions`) any
+/// other width is an error; otherwise the rows are padded or cut by
+/// `fit_row`, which is logged.
+pub(crate) fn check_width(model: &str, width: usize, dim: usize, strict: bool) -> Result<()> {
+    if width == dim {
+        return Ok(());
+    }
+    if strict {
+        return Err(EmbedError::DimMismatch {
+            model: model.to_string(),
+            expected: dim,
+            actual: width,
+        });
+    }
+    tracing::debug!(
+        "Model '{}' returned {} values where {} were expected; fitting them",
+        model,
+        width,
+        dim
+    );
+    Ok(())
+}
+
+/// Pad or cut `row` to `dim` values and, with `normalize`, scale it to unit
+/// length.
+pub(crate) fn fit_row(mut row: Vec<f32>, dim: usize, normalize: bool) -> Vec<f32> {
+    row.resize(dim, 0.0);
+    let norm = row.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if normalize && norm > 0.0 {
+        for value in &mut row {
+            *value /= norm;
+        }
+    }
+    row
+}
+
 pub fn create_embedder(model_name: Option<&str>) -> Result<Box<dyn Embedder>> {
     create_embedder_with_progress(model_name, None)
 }
@@ -51,53 +183,256 @@ pub fn create_embedder_with_progress(
     model_name: Option<&str>,
     progress_callback: Option<ModelDownloadCallback>,
 ) -> Result<Box<dyn Embedder>> {
-    let registry = ModelRegistry::default();
+    let registry = ModelRegistry::with_user_models();
     let (_, config) = registry.resolve(model_name)?;
     create_embedder_for_config(&config, progress_callback)
 }
 
-#[allow(clippy::needless_return)]
+/// Reconcile a registry entry's `dimensions` with the width the model actually
+/// produces: `0` adopts the detected width, anything else must match it exactly.
+pub fn resolve_dimensions(model_name: &str, configured: usize, detected: usize) -> Result<usize> {
+    if configured != 0 && configured != detected {
+        return Err(EmbedError::DimMismatch {
+            model: model_name.to_string(),
+            expected: configured,
+            actual: detected,
+        });
+    }
+    Ok(detected)
+}
+
 pub fn create_embedder_for_config(
     config: &ModelConfig,
     progress_callback: Option<ModelDownloadCallback>,
 ) -> Result<Box<dyn Embedder>> {
-    match config.provider.as_str() {
-        "fastembed" => {
-            #[cfg(feature = "fastembed")]
-            {
-                return Ok(Box::new(FastEmbedder::new_with_progress(
-                    config.name.as_str(),
-                    progress_callback,
-                )?));
-            }
+    let embedder = create_provider_embedder(config, progress_callback)?;
+    let gpu_batch = embedder
+        .free_device_memory()
+        .map(|free| gpu_batch_size(free, config.max_tokens));
+    if let Some(batch) = gpu_batch {
+        tracing::debug!("Embedding up to {} texts per batch on the GPU", batch);
+    }
+    let batch = [resource_limits().batch_size(config.max_tokens), gpu_batch]
+        .into_iter()
+        .flatten()
+        .min();
+    Ok(match batch {
+        Some(batch) => Box::new(BatchLimited {
+            inner: embedder,
+            batch,
+        }),
+        None => embedder,
+    })
+}
 
-            #[cfg(not(feature = "fastembed"))]
-            {
-                if let Some(callback) = progress_callback.as_ref() {
-                    callback("fastembed provider unavailable; using dummy embedder");
-                }
-                return Ok(Box::new(DummyEmbedder::new_with_model(
-                    config.name.as_str(),
-                )));
-            }
+fn create_provider_embedder(
+    config: &ModelConfig,
+    progress_callback: Option<ModelDownloadCallback>,
+) -> Result<Box<dyn Embedder>> {
+    if let Some(program) = config.provider.strip_prefix(plugin::PLUGIN_PREFIX) {
+        return Ok(Box::new(PluginEmbedder::new(program, config)?));
+    }
+    let factory = providers::embedder_factory(&config.provider).ok_or_else(|| {
+        EmbedError::UnsupportedProvider {
+            kind: "embedding",
+            provider: config.provider.clone(),
         }
-        "mixedbread" => {
-            #[cfg(feature = "mixedbread")]
-            {
-                return Ok(Box::new(MixedbreadEmbedder::new(
-                    config,
-                    progress_callback,
-                )?));
-            }
-            #[cfg(not(feature = "mixedbread"))]
-            {
-                bail!(
-                    "Model '{}' requires the `mixedbread` feature. Rebuild ck with Mixedbread support.",
-                    config.name
-                );
+    })?;
+    factory(config, progress_callback)
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn fastembed_embedder(
+    config: &ModelConfig,
+    progress_callback: Option<ModelDownloadCallback>,
+) -> Result<Box<dyn Embedder>> {
+    #[cfg(feature = "fastembed")]
+    {
+        if !config.normalize {
+            // fastembed scales its output itself, so dot ranks as cosine
+            tracing::warn!(
+                "fastembed model '{}' always produces unit-length embeddings",
+                config.name
+            );
+        }
+        let embedder = FastEmbedder::new_with_progress(config.name.as_str(), progress_callback)?
+            .with_truncation(config.truncation);
+        resolve_dimensions(&config.name, config.dimensions, embedder.dim())?;
+        return Ok(Box::new(embedder));
+    }
+
+    #[cfg(not(feature = "fastembed"))]
+    {
+        if let Some(callback) = progress_callback.as_ref() {
+            callback("fastembed provider unavailable; using dummy embedder");
+        }
+        let mut embedder = DummyEmbedder::new_with_model(config.name.as_str());
+        if config.dimensions != 0 {
+            embedder.dim = config.dimensions;
+        }
+        return Ok(Box::new(embedder));
+    }
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn mixedbread_embedder(
+    config: &ModelConfig,
+    progress_callback: Option<ModelDownloadCallback>,
+) -> Result<Box<dyn Embedder>> {
+    #[cfg(feature = "mixedbread")]
+    {
+        return Ok(Box::new(MixedbreadEmbedder::new(
+            config,
+            progress_callback,
+        )?));
+    }
+    #[cfg(not(feature = "mixedbread"))]
+    {
+        let _ = progress_callback;
+        return Err(EmbedError::FeatureDisabled {
+            model: config.name.clone(),
+            feature: "mixedbread",
+        });
+    }
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn candle_embedder(
+    config: &ModelConfig,
+    progress_callback: Option<ModelDownloadCallback>,
+) -> Result<Box<dyn Embedder>> {
+    #[cfg(feature = "candle")]
+    {
+        return Ok(Box::new(CandleEmbedder::new(config, progress_callback)?));
+    }
+    #[cfg(not(feature = "candle"))]
+    {
+        let _ = progress_callback;
+        return Err(EmbedError::FeatureDisabled {
+            model: config.name.clone(),
+            feature: "candle",
+        });
+    }
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn llama_server_embedder(
+    config: &ModelConfig,
+    progress_callback: Option<ModelDownloadCallback>,
+) -> Result<Box<dyn Embedder>> {
+    #[cfg(feature = "llama-server")]
+    {
+        return Ok(Box::new(LlamaServerEmbedder::new(
+            config,
+            progress_callback,
+        )?));
+    }
+    #[cfg(not(feature = "llama-server"))]
+    {
+        let _ = progress_callback;
+        return Err(EmbedError::FeatureDisabled {
+            model: config.name.clone(),
+            feature: "llama-server",
+        });
+    }
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn openai_embedder(
+    config: &ModelConfig,
+    _progress_callback: Option<ModelDownloadCallback>,
+) -> Result<Box<dyn Embedder>> {
+    #[cfg(feature = "openai")]
+    {
+        return Ok(Box::new(OpenAiEmbedder::new(config)?));
+    }
+    #[cfg(not(feature = "openai"))]
+    {
+        return Err(EmbedError::FeatureDisabled {
+            model: config.name.clone(),
+            feature: "openai",
+        });
+    }
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn onnx_embedder(
+    config: &ModelConfig,
+    progress_callback: Option<ModelDownloadCallback>,
+) -> Result<Box<dyn Embedder>> {
+    let source = config.onnx.as_ref().ok_or_else(|| EmbedError::ModelLoad {
+        model: config.name.clone(),
+        message: "the onnx provider needs an `onnx` source in its registry entry".to_string(),
+    })?;
+    #[cfg(feature = "mixedbread")]
+    {
+        return Ok(Box::new(MixedbreadEmbedder::from_source(
+            config,
+            source,
+            progress_callback,
+        )?));
+    }
+    #[cfg(not(feature = "mixedbread"))]
+    {
+        let _ = (source, progress_callback);
+        return Err(EmbedError::FeatureDisabled {
+            model: config.name.clone(),
+            feature: "mixedbread",
+        });
+    }
+}
+
+/// Hands texts to `inner` at most `batch` at a time, keeping peak memory within
+/// the `--max-memory` budget or the memory free on the GPU. A batch that runs
+/// out of memory is retried at half the size, which then sticks.
+struct BatchLimited {
+    inner: Box<dyn Embedder>,
+    batch: usize,
+}
+
+impl Embedder for BatchLimited {
+    fn id(&self) -> &'static str {
+        self.inner.id()
+    }
+
+    fn dim(&self) -> usize {
+        self.inner.dim()
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+
+    fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        let mut rest = texts;
+        while !rest.is_empty() {
+            let batch = &rest[..self.batch.min(rest.len())];
+            match self.inner.embed(batch) {
+                Ok(batch_embeddings) => {
+                    embeddings.extend(batch_embeddings);
+                    rest = &rest[batch.len()..];
+                }
+                Err(e) if e.is_out_of_memory() && batch.len() > 1 => {
+                    self.batch = batch.len() / 2;
+                    tracing::warn!(
+                        "Out of memory embedding {} texts at once; retrying {} at a time",
+                        batch.len(),
+                        self.batch
+                    );
+                }
+                Err(e) => return Err(e),
             }
         }
-        provider => bail!("Unsupported embedding provider '{}'", provider),
+        Ok(embeddings)
+    }
+
+    fn tokens_used(&self) -> Option<u64> {
+        self.inner.tokens_used()
+    }
+
+    fn free_device_memory(&self) -> Option<u64> {
+        self.inner.free_device_memory()
     }
 }
 
@@ -151,6 +486,7 @@ pub struct FastEmbedder {
     model: fastembed::TextEmbedding,
     dim: usize,
     model_name: String,
+    truncation: Truncation,
 }
 
 #[cfg(feature = "fastembed")]
@@ -179,6 +515,10 @@ impl FastEmbedder {
             "BAAI/bge-base-en-v1.5" => EmbeddingModel::BGEBaseENV15,
             "BAAI/bge-large-en-v1.5" => EmbeddingModel::BGELargeENV15,
 
+            // Multilingual models
+            "BAAI/bge-m3" => EmbeddingModel::BGEM3,
+            "intfloat/multilingual-e5-small" => EmbeddingModel::MultilingualE5Small,
+
             // Default to Nomic v1.5 for better performance
             _ => EmbeddingModel::NomicEmbedTextV15,
         };
@@ -216,6 +556,9 @@ impl FastEmbedder {
             // BGE large can handle more
             EmbeddingModel::BGELargeENV15 => 512, // Conservative for BGE
 
+            EmbeddingModel::BGEM3 => 8192,
+            EmbeddingModel::MultilingualE5Small => 512,
+
             _ => 512, // Safe default
         };
 
@@ -224,36 +567,73 @@ impl FastEmbedder {
             .with_cache_dir(model_cache_dir)
             .with_max_length(max_length);
 
-        let embedding = TextEmbedding::try_new(init_options)?;
+        let embedding = TextEmbedding::try_new(init_options)
+            .map_err(|e| EmbedError::model_load(model_name, e))?;
 
         if let Some(ref callback) = progress_callback {
             callback("Model loaded successfully");
         }
 
-        let dim = match model {
-            // Small models (384 dimensions)
-            EmbeddingModel::BGESmallENV15 => 384,
-            EmbeddingModel::AllMiniLML6V2 => 384,
-
-            // Large context models (768 dimensions)
-            EmbeddingModel::NomicEmbedTextV1 => 768,
-            EmbeddingModel::NomicEmbedTextV15 => 768,
-            EmbeddingModel::JinaEmbeddingsV2BaseCode => 768,
-            EmbeddingModel::BGEBaseENV15 => 768,
-
-            // Large models (1024 dimensions)
-            EmbeddingModel::BGELargeENV15 => 1024,
-
-            _ => 384, // Default to 384 for BGE default
-        };
+        let dim = TextEmbedding::get_model_info(&model)
+            .map_err(|e| EmbedError::model_load(model_name, e))?
+            .dim;
 
         Ok(Self {
             model: embedding,
             dim,
             model_name: model_name.to_string(),
+            truncation: Truncation::Head,
         })
     }
 
+    /// Embed the part of over-long inputs that `truncation` keeps instead of
+    /// their start.
+    pub fn with_truncation(mut self, truncation: Truncation) -> Self {
+        self.truncation = truncation;
+        self
+    }
+
+    /// Cut inputs over the token limit down to the part `self.truncation`
+    /// keeps; fastembed itself can only drop the end.
+    fn fit_texts<'t>(&mut self, texts: &'t [String]) -> Result<Vec<Cow<'t, str>>> {
+        let truncation = self.truncation;
+        let tokenizer = &mut self.model.tokenizer;
+        let limit = tokenizer.get_truncation().cloned();
+        let Some(max_length) = limit.as_ref().map(|params| params.max_length) else {
+            return Ok(texts
+                .iter()
+                .map(|text| Cow::Borrowed(text.as_str()))
+                .collect());
+        };
+
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| EmbedError::Tokenizer(e.to_string()))?;
+        let encodings = texts
+            .iter()
+            .map(|text| tokenizer.encode(text.as_str(), true))
+            .collect::<std::result::Result<Vec<_>, _>>();
+        tokenizer
+            .with_truncation(limit)
+            .map_err(|e| EmbedError::Tokenizer(e.to_string()))?;
+        let encodings =
+            encodings.map_err(|e| EmbedError::Tokenizer(format!("encode failed: {e}")))?;
+
+        Ok(texts
+            .iter()
+            .zip(&encodings)
+            .map(|(text, encoding)| {
+                truncation::cut_text(
+                    text,
+                    encoding.get_special_tokens_mask(),
+                    encoding.get_offsets(),
+                    max_length,
+                    truncation,
+                )
+            })
+            .collect())
+    }
+
     fn check_model_exists(cache_dir: &Path, model_name: &str) -> bool {
         // Simple heuristic - check if model directory exists
         let model_dir = cache_dir.join(model_name.replace("/", "_"));
@@ -275,9 +655,19 @@ impl Embedder for FastEmbedder {
         &self.model_name
     }
 
+    #[tracing::instrument(name = "embed", level = "debug", skip_all, fields(model = %self.model_name, texts = texts.len()))]
     fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
-        let text_refs: Vec<&str> = texts.iter().map(|s| s.as_str()).collect();
-        let embeddings = self.model.embed(text_refs, None)?;
+        let fitted = match self.truncation {
+            Truncation::Head => texts.iter().map(|s| Cow::Borrowed(s.as_str())).collect(),
+            _ => self.fit_texts(texts)?,
+        };
+        let text_refs: Vec<&str> = fitted.iter().map(|s| s.as_ref()).collect();
+        // fastembed tokenizes and runs the ONNX session in one call
+        let _span = tracing::debug_span!("onnx_run", batch = texts.len()).entered();
+        let embeddings = self
+            .model
+            .embed(text_refs, None)
+            .map_err(EmbedError::inference)?;
         Ok(embeddings)
     }
 }
@@ -286,6 +676,18 @@ impl Embedder for FastEmbedder {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_resolve_dimensions() {
+        assert_eq!(resolve_dimensions("custom", 0, 512).unwrap(), 512);
+        assert_eq!(resolve_dimensions("custom", 384, 384).unwrap(), 384);
+        let err = resolve_dimensions("custom", 384, 768).unwrap_err();
+        assert!(
+            err.to_string().contains(
+                "produces 768-dimensional embeddings, but its registry entry declares 384"
+            )
+        );
+    }
+
     #[test]
     fn test_dummy_embedder() {
         let mut embedder = DummyEmbedder::new();
@@ -391,6 +793,54 @@ mod tests {
         assert_eq!(embeddings.len(), 0);
     }
 
+    /// Fails like CUDA for batches over `capacity` texts.
+    struct Cramped {
+        capacity: usize,
+    }
+
+    impl Embedder for Cramped {
+        fn id(&self) -> &'static str {
+            "cramped"
+        }
+
+        fn dim(&self) -> usize {
+            1
+        }
+
+        fn model_name(&self) -> &str {
+            "cramped"
+        }
+
+        fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            if texts.len() > self.capacity {
+                return Err(EmbedError::Inference(
+                    "CUDA_ERROR_OUT_OF_MEMORY: out of memory".to_string(),
+                ));
+            }
+            Ok(texts.iter().map(|text| vec![text.len() as f32]).collect())
+        }
+    }
+
+    #[test]
+    fn test_batch_limited_halves_batches_out_of_memory() {
+        let mut embedder = BatchLimited {
+            inner: Box::new(Cramped { capacity: 3 }),
+            batch: 8,
+        };
+        let texts: Vec<String> = (1..=10).map(|n| "x".repeat(n)).collect();
+        let embeddings = embedder.embed(&texts).unwrap();
+        let lengths: Vec<f32> = embeddings.iter().map(|e| e[0]).collect();
+        assert_eq!(lengths, (1..=10).map(|n| n as f32).collect::<Vec<_>>());
+        assert_eq!(embedder.batch, 2);
+
+        let mut embedder = BatchLimited {
+            inner: Box::new(Cramped { capacity: 0 }),
+            batch: 4,
+        };
+        assert!(embedder.embed(&texts).unwrap_err().is_out_of_memory());
+        assert!(!EmbedError::Inference("shape mismatch".to_string()).is_out_of_memory());
+    }
+
     #[test]
     fn test_embedder_single_text() {
         let mut embedder = DummyEmbedder::new();
@@ -0,0 +1,112 @@
+//! Process-wide limits on the resources embedding may use, set from
+//! `--max-memory` and `--nice` before the first model loads. Every ONNX Runtime
+//! session (fastembed, Mixedbread and `onnx` models) runs in one shared
+//! environment configured from them; llama.cpp servers get the thread count.
+
+use std::sync::OnceLock;
+
+/// Part of the memory budget left for batch activations; the rest holds the
+/// model weights and the runtime itself.
+const BATCH_MEMORY_DIVISOR: u64 = 2;
+/// Rough peak activation memory per input token of a BERT-style encoder.
+const BYTES_PER_TOKEN: u64 = 48 * 1024;
+/// Largest batch a memory budget allows, matching fastembed's default.
+const MAX_BATCH: usize = 256;
+
+static LIMITS: OnceLock<ResourceLimits> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Inference threads; `None` uses every core
+    pub threads: Option<usize>,
+    /// Approximate memory budget in bytes. Turns off ONNX Runtime's memory
+    /// arena and caps how many texts go to the model per call
+    pub max_memory: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Texts embedded per model call for inputs of up to `max_tokens` tokens,
+    /// or `None` when memory is not capped.
+    pub fn batch_size(&self, max_tokens: usize) -> Option<usize> {
+        Some(batch_for_budget(
+            self.max_memory? / BATCH_MEMORY_DIVISOR,
+            max_tokens,
+        ))
+    }
+}
+
+/// Texts embedded per model call on a GPU with `free` bytes of memory left
+/// once the model is loaded. Half is kept spare for the allocator's overhead
+/// and other processes on the device.
+pub fn gpu_batch_size(free: u64, max_tokens: usize) -> usize {
+    batch_for_budget(free / BATCH_MEMORY_DIVISOR, max_tokens)
+}
+
+fn batch_for_budget(budget: u64, max_tokens: usize) -> usize {
+    let per_text = max_tokens.max(1) as u64 * BYTES_PER_TOKEN;
+    (budget / per_text).clamp(1, MAX_BATCH as u64) as usize
+}
+
+/// Apply `limits` for the rest of the process. Returns `false` without changing
+/// anything when limits were already set or an ONNX model has already loaded.
+pub fn set_resource_limits(limits: ResourceLimits) -> bool {
+    if LIMITS.set(limits).is_err() {
+        return false;
+    }
+    limits == ResourceLimits::default() || configure_onnx_runtime(&limits)
+}
+
+/// The limits set with [`set_resource_limits`], or none.
+pub fn resource_limits() -> ResourceLimits {
+    LIMITS.get().copied().unwrap_or_default()
+}
+
+#[cfg(any(feature = "fastembed", feature = "mixedbread"))]
+fn configure_onnx_runtime(limits: &ResourceLimits) -> bool {
+    use ort::environment::GlobalThreadPoolOptions;
+
+    let mut environment = ort::init().with_name("ck");
+    if let Some(threads) = limits.threads {
+        // Idle workers would otherwise spin and hold their cores
+        match GlobalThreadPoolOptions::default()
+            .with_intra_threads(threads)
+            .and_then(|options| options.with_inter_threads(1))
+            .and_then(|options| options.with_spin_control(false))
+        {
+            Ok(options) => environment = environment.with_global_thread_pool(options),
+            Err(e) => tracing::warn!("Could not limit ONNX Runtime threads: {e}"),
+        }
+    }
+    if limits.max_memory.is_some() {
+        environment = environment.with_execution_providers([ort::ep::CPU::default()
+            .with_arena_allocator(false)
+            .build()]);
+    }
+    environment.commit()
+}
+
+#[cfg(not(any(feature = "fastembed", feature = "mixedbread")))]
+fn configure_onnx_runtime(_limits: &ResourceLimits) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_size_follows_memory_budget() {
+        assert_eq!(ResourceLimits::default().batch_size(512), None);
+
+        let gib = ResourceLimits {
+            threads: None,
+            max_memory: Some(1 << 30),
+        };
+        assert_eq!(gib.batch_size(512), Some(21));
+        assert_eq!(gib.batch_size(8192), Some(1));
+        assert_eq!(gib.batch_size(1), Some(MAX_BATCH));
+
+        assert_eq!(gpu_batch_size(8 << 30, 512), 170);
+        assert_eq!(gpu_batch_size(0, 512), 1);
+    }
+}
@@ -0,0 +1,188 @@
+//! GGUF embedding models served by a `llama-server` that ck starts. ck does
+//! not link llama.cpp: it runs llama.cpp's `llama-server` binary (from `PATH`
+//! or [`LLAMA_SERVER_ENV`]) on the model on a free local port, and embeds
+//! through its OpenAI-compatible endpoint with an [`OpenAiEmbedder`], so
+//! quantized embedders run with llama.cpp's small memory footprint. The
+//! server is stopped when the embedder is dropped. A server that is already
+//! running is an `openai` provider entry instead.
+
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use ck_models::ModelConfig;
+
+use crate::openai::OpenAiEmbedder;
+use crate::{EmbedError, Embedder, ModelDownloadCallback, Result};
+
+/// Overrides the `llama-server` binary found on `PATH`.
+pub const LLAMA_SERVER_ENV: &str = "CK_LLAMA_SERVER";
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The server ck started, stopped when dropped, including when the
+/// embedder fails to load.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+pub struct LlamaServerEmbedder {
+    client: OpenAiEmbedder,
+    _server: Server,
+}
+
+impl LlamaServerEmbedder {
+    pub fn new(
+        config: &ModelConfig,
+        progress_callback: Option<ModelDownloadCallback>,
+    ) -> Result<Self> {
+        if let Some(cb) = progress_callback.as_ref() {
+            cb(&format!(
+                "Downloading GGUF model ({}) if needed...",
+                config.name
+            ));
+        }
+        let model_path = resolve_gguf(&config.name)?;
+        if let Some(cb) = progress_callback.as_ref() {
+            cb("Starting llama-server...");
+        }
+        let (base_url, child) = start_server(&config.name, &model_path, config.max_tokens)?;
+        let mut server = Server(child);
+        wait_until_ready(&config.name, &base_url, &mut server)?;
+        Ok(Self {
+            client: OpenAiEmbedder::at(config, &base_url)?,
+            _server: server,
+        })
+    }
+}
+
+/// Poll `/health` until the model has loaded.
+fn wait_until_ready(model_id: &str, base_url: &str, server: &mut Server) -> Result<()> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = server.0.try_wait()? {
+            return Err(EmbedError::model_load(
+                model_id,
+                format!(
+                    "llama-server exited during startup ({status}); check that the file is a GGUF embedding model"
+                ),
+            ));
+        }
+        match ureq::get(&format!("{base_url}/health"))
+            .timeout(Duration::from_secs(2))
+            .call()
+        {
+            Ok(_) => return Ok(()),
+            // 503 while loading, connection refused before it listens
+            Err(e) if started.elapsed() >= STARTUP_TIMEOUT => {
+                return Err(EmbedError::model_load(
+                    model_id,
+                    format!("llama-server at {base_url} not ready: {e}"),
+                ));
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(200)),
+        }
+    }
+}
+
+impl Embedder for LlamaServerEmbedder {
+    fn id(&self) -> &'static str {
+        "llama-server"
+    }
+
+    fn dim(&self) -> usize {
+        self.client.dim()
+    }
+
+    fn model_name(&self) -> &str {
+        self.client.model_name()
+    }
+
+    fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.client.embed(texts)
+    }
+
+    fn tokens_used(&self) -> Option<u64> {
+        self.client.tokens_used()
+    }
+}
+
+/// A local `.gguf` file, or `owner/repo/file.gguf` downloaded from Hugging Face.
+fn resolve_gguf(name: &str) -> Result<PathBuf> {
+    let local = Path::new(name);
+    if local.is_file() {
+        return Ok(local.to_path_buf());
+    }
+    let mut parts = name.splitn(3, '/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(owner), Some(repo), Some(file)) if !owner.is_empty() && !repo.is_empty() => {
+            crate::download_hub_file(name, &format!("{owner}/{repo}"), file, "GGUF model")
+        }
+        _ => Err(EmbedError::model_load(
+            name,
+            "expected a local .gguf file or owner/repo/file.gguf",
+        )),
+    }
+}
+
+/// Start `llama-server` in embedding mode on a free local port.
+fn start_server(model_id: &str, model_path: &Path, max_tokens: usize) -> Result<(String, Child)> {
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let binary = std::env::var_os(LLAMA_SERVER_ENV).unwrap_or_else(|| "llama-server".into());
+    // Embedding inputs must fit in one physical batch
+    let batch = max_tokens.to_string();
+
+    let mut command = Command::new(&binary);
+    command
+        .arg("--model")
+        .arg(model_path)
+        .args(["--embeddings", "--host", "127.0.0.1"])
+        .args(["--port", &port.to_string()])
+        .args([
+            "--ctx-size",
+            &batch,
+            "--batch-size",
+            &batch,
+            "--ubatch-size",
+            &batch,
+        ]);
+    if let Some(threads) = crate::resource_limits().threads {
+        command.args(["--threads", &threads.to_string()]);
+    }
+
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| {
+            EmbedError::model_load(
+                model_id,
+                format!(
+                    "could not start {} ({e}); install llama.cpp or set {LLAMA_SERVER_ENV}",
+                    binary.to_string_lossy()
+                ),
+            )
+        })?;
+
+    Ok((format!("http://127.0.0.1:{port}"), child))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_gguf_rejects_bare_names() {
+        let err = resolve_gguf("nomic-embed.gguf").unwrap_err();
+        assert!(err.to_string().contains("owner/repo/file.gguf"));
+        let err = resolve_gguf("http://127.0.0.1:8080").unwrap_err();
+        assert!(err.to_string().contains("owner/repo/file.gguf"));
+    }
+}
@@ -1,30 +1,36 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result, anyhow};
-use hf_hub::{Repo, RepoType, api::sync::ApiBuilder};
-use ndarray::{Array2, ArrayView, ArrayViewD, Axis, Ix1, Ix2, Ix3};
+use ndarray::{Array2, ArrayView, ArrayViewD, Ix1, Ix2, Ix3};
 use ort::session::{Session, builder::GraphOptimizationLevel};
-use ort::value::Value;
-use tokenizers::{EncodeInput, Tokenizer};
+use tokenizers::Tokenizer;
 
 use crate::{
-    Embedder, ModelDownloadCallback, model_cache_root,
-    reranker::{RerankModelDownloadCallback, RerankResult, Reranker},
+    EmbedError, Embedder, ModelDownloadCallback, Result,
+    encoder_input::EncoderInputBuilder,
+    pooling::{POOLING_PATH, pool_tokens, read_pooling},
+    reranker::{Document, RerankModelDownloadCallback, RerankResult, Reranker, check_batch_shape},
 };
-use ck_models::{ModelConfig, RerankModelConfig};
+use ck_core::ScoreTransform;
+use ck_models::{ModelConfig, OnnxSource, Pooling, RerankModelConfig, Truncation};
 
 const EMBED_TOKENIZER_PATH: &str = "tokenizer.json";
 const EMBED_MODEL_PATH: &str = "onnx/model_quantized.onnx";
 const RERANK_TOKENIZER_PATH: &str = "tokenizer.json";
 const RERANK_MODEL_PATH: &str = "onnx/model_quantized.onnx";
+const ONNX_MODEL_PATH: &str = "onnx/model.onnx";
+const ONNX_TOKENIZER_PATH: &str = "tokenizer.json";
+/// `(query, document)` pairs per ONNX run, until one runs out of memory
+const RERANK_BATCH_PAIRS: usize = 64;
 
 pub struct MixedbreadEmbedder {
     session: Session,
-    tokenizer: Tokenizer,
+    inputs: EncoderInputBuilder,
     dim: usize,
-    max_length: usize,
     model_name: String,
-    requires_token_type_ids: bool,
+    pooling: Pooling,
+    normalize: bool,
+    strict_dimensions: bool,
+    id: &'static str,
 }
 
 impl MixedbreadEmbedder {
@@ -46,94 +52,105 @@ impl MixedbreadEmbedder {
             cb("Loading Mixedbread embedder session...");
         }
 
-        let session = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(num_cpus::get().max(1))?
-            .commit_from_file(&model_path)?;
+        Self::load(
+            config,
+            &model_path,
+            &tokenizer_path,
+            Pooling::Cls,
+            "mixedbread",
+        )
+    }
 
-        let tokenizer =
-            Tokenizer::from_file(tokenizer_path).map_err(|e| anyhow!("Tokenizer error: {e}"))?;
+    /// Embedder for a user-registered model (provider `onnx`), loaded from local
+    /// files or downloaded from the Hugging Face repo its source names.
+    pub fn from_source(
+        config: &ModelConfig,
+        source: &OnnxSource,
+        progress_callback: Option<ModelDownloadCallback>,
+    ) -> Result<Self> {
+        if let Some(cb) = progress_callback.as_ref() {
+            cb(&format!(
+                "Loading ONNX embedding model ({})...",
+                config.name
+            ));
+        }
+        let (model_path, tokenizer_path) = resolve_source(&config.name, source)?;
+        // Unset pooling follows the export's sentence-transformers config, else mean
+        let pooling = source
+            .pooling
+            .or_else(|| source_pooling(&config.name, source, &model_path))
+            .unwrap_or_default();
+        Self::load(config, &model_path, &tokenizer_path, pooling, "onnx")
+    }
 
-        let requires_token_type_ids = session
-            .inputs()
-            .iter()
-            .any(|input| input.name() == "token_type_ids");
+    fn load(
+        config: &ModelConfig,
+        model_path: &Path,
+        tokenizer_path: &Path,
+        pooling: Pooling,
+        id: &'static str,
+    ) -> Result<Self> {
+        let session = load_session(&config.name, model_path)?;
 
-        Ok(Self {
-            session,
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| EmbedError::Tokenizer(e.to_string()))?;
+        let inputs = EncoderInputBuilder::for_session(
             tokenizer,
+            config.max_tokens,
+            config.truncation,
+            &session,
+        )?;
+
+        let mut embedder = Self {
+            session,
+            inputs,
             dim: config.dimensions,
-            max_length: config.max_tokens,
             model_name: config.name.clone(),
-            requires_token_type_ids,
-        })
-    }
-
-    #[allow(clippy::type_complexity)]
-    fn build_inputs(
-        &self,
-        texts: &[String],
-    ) -> Result<(Array2<i64>, Array2<i64>, Option<Array2<i64>>)> {
-        let mut encodings = Vec::with_capacity(texts.len());
-        for text in texts {
-            let encoding = self
-                .tokenizer
-                .encode(text.as_str(), true)
-                .map_err(|e| anyhow!("Tokenizer encode failed: {e}"))?;
-            encodings.push(encoding);
-        }
-
-        let seq_len = encodings
-            .iter()
-            .map(|encoding| encoding.len())
-            .max()
-            .unwrap_or(1)
-            .min(self.max_length)
-            .max(1);
-
-        let batch = encodings.len();
-        let mut input_ids = vec![0i64; batch * seq_len];
-        let mut attention_mask = vec![0i64; batch * seq_len];
-        let mut token_types = if self.requires_token_type_ids {
-            Some(vec![0i64; batch * seq_len])
-        } else {
-            None
+            pooling,
+            normalize: config.normalize,
+            strict_dimensions: config.strict_dimensions,
+            id,
         };
+        // A wrong width would otherwise be silently truncated or zero-padded by
+        // `normalize_row`, so check it before any vectors are produced
+        let detected = match declared_dimensions(&embedder.session) {
+            Some(dim) => dim,
+            None => embedder.probe_dimensions()?,
+        };
+        embedder.dim = crate::resolve_dimensions(&config.name, config.dimensions, detected)?;
+        Ok(embedder)
+    }
 
-        for (row, encoding) in encodings.iter().enumerate() {
-            let ids = encoding.get_ids();
-            let mask = encoding.get_attention_mask();
-            let type_ids = encoding.get_type_ids();
-            let len = ids.len().min(seq_len);
-
-            let row_offset = row * seq_len;
-            for idx in 0..len {
-                input_ids[row_offset + idx] = ids[idx] as i64;
-                attention_mask[row_offset + idx] = mask[idx] as i64;
-            }
-
-            if let Some(ref mut token_types_buf) = token_types
-                && !type_ids.is_empty()
-            {
-                for idx in 0..len {
-                    token_types_buf[row_offset + idx] = type_ids[idx] as i64;
-                }
-            }
-        }
-
-        let token_type_array =
-            token_types.map(|buf| Array2::from_shape_vec((batch, seq_len), buf).unwrap());
-
-        Ok((
-            Array2::from_shape_vec((batch, seq_len), input_ids)
-                .expect("validated dimensions for input ids"),
-            Array2::from_shape_vec((batch, seq_len), attention_mask)
-                .expect("validated dimensions for attention mask"),
-            token_type_array,
-        ))
+    /// Run one short input through the model and measure the embedding width, for
+    /// graphs whose output shape is dynamic.
+    fn probe_dimensions(&mut self) -> Result<usize> {
+        let outputs = self
+            .inputs
+            .texts(&["dimension probe".to_string()])?
+            .run(&mut self.session)?;
+        let tensor = outputs[0].try_extract_array::<f32>().map_err(|e| {
+            EmbedError::Inference(format!("Failed to extract embedding tensor: {e}"))
+        })?;
+        tensor
+            .shape()
+            .last()
+            .copied()
+            .filter(|dim| *dim > 0)
+            .ok_or_else(|| {
+                EmbedError::Inference(format!(
+                    "Model '{}' returned an empty embedding",
+                    self.model_name
+                ))
+            })
     }
 
-    fn normalize(rows: ArrayViewD<'_, f32>, dim: usize) -> Result<Vec<Vec<f32>>> {
+    fn normalize(
+        rows: ArrayViewD<'_, f32>,
+        attention_mask: &Array2<i64>,
+        pooling: Pooling,
+        dim: usize,
+        unit_length: bool,
+    ) -> Result<Vec<Vec<f32>>> {
         let ndim = rows.ndim();
         match ndim {
             2 => {
@@ -141,24 +158,29 @@ impl MixedbreadEmbedder {
                 Ok(view
                     .rows()
                     .into_iter()
-                    .map(|row| normalize_row(row, dim))
+                    .map(|row| normalize_row(row, dim, unit_length))
                     .collect())
             }
             3 => {
                 let view = rows.into_dimensionality::<Ix3>()?;
                 Ok(view
                     .outer_iter()
-                    .map(|matrix| normalize_row(matrix.index_axis(Axis(0), 0), dim))
+                    .zip(attention_mask.rows())
+                    .map(|(matrix, mask)| {
+                        normalize_row(pool_tokens(matrix, mask, pooling).view(), dim, unit_length)
+                    })
                     .collect())
             }
-            other => Err(anyhow!("Unexpected embedding tensor rank: {other}")),
+            other => Err(EmbedError::Inference(format!(
+                "Unexpected embedding tensor rank: {other}"
+            ))),
         }
     }
 }
 
 impl Embedder for MixedbreadEmbedder {
     fn id(&self) -> &'static str {
-        "mixedbread"
+        self.id
     }
 
     fn dim(&self) -> usize {
@@ -169,40 +191,38 @@ impl Embedder for MixedbreadEmbedder {
         &self.model_name
     }
 
+    #[tracing::instrument(name = "embed", level = "debug", skip_all, fields(model = %self.model_name, texts = texts.len()))]
     fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
         if texts.is_empty() {
             return Ok(Vec::new());
         }
 
-        let (input_ids, attention_mask, token_types) = self.build_inputs(texts)?;
-
-        let outputs = if self.requires_token_type_ids {
-            let token_types = token_types.expect("token type ids required but missing");
-            self.session.run(ort::inputs![
-                Value::from_array(input_ids)?,
-                Value::from_array(attention_mask)?,
-                Value::from_array(token_types)?
-            ])?
-        } else {
-            self.session.run(ort::inputs![
-                Value::from_array(input_ids)?,
-                Value::from_array(attention_mask)?
-            ])?
-        };
+        let inputs = self.inputs.texts(texts)?;
 
-        let embedding_tensor = outputs[0]
-            .try_extract_array::<f32>()
-            .context("Failed to extract embedding tensor")?;
+        let _span = tracing::debug_span!("onnx_run", batch = texts.len()).entered();
+        let outputs = inputs.run(&mut self.session)?;
+
+        let embedding_tensor = outputs[0].try_extract_array::<f32>().map_err(|e| {
+            EmbedError::Inference(format!("Failed to extract embedding tensor: {e}"))
+        })?;
+        let width = embedding_tensor.shape().last().copied().unwrap_or(0);
+        crate::check_width(&self.model_name, width, self.dim, self.strict_dimensions)?;
 
-        Self::normalize(embedding_tensor, self.dim)
+        Self::normalize(
+            embedding_tensor,
+            &inputs.attention_mask,
+            self.pooling,
+            self.dim,
+            self.normalize,
+        )
     }
 }
 
 pub struct MixedbreadReranker {
     session: Session,
-    tokenizer: Tokenizer,
-    max_length: usize,
-    requires_token_type_ids: bool,
+    inputs: EncoderInputBuilder,
+    score_transform: ScoreTransform,
+    batch_pairs: usize,
 }
 
 impl MixedbreadReranker {
@@ -224,90 +244,78 @@ impl MixedbreadReranker {
             cb("Loading Mixedbread reranker session...");
         }
 
-        let session = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(num_cpus::get().max(1))?
-            .commit_from_file(&model_path)?;
+        let session = load_session(&config.name, &model_path)?;
 
-        let tokenizer =
-            Tokenizer::from_file(tokenizer_path).map_err(|e| anyhow!("Tokenizer error: {e}"))?;
-
-        let requires_token_type_ids = session
-            .inputs()
-            .iter()
-            .any(|input| input.name() == "token_type_ids");
+        let tokenizer = Tokenizer::from_file(tokenizer_path)
+            .map_err(|e| EmbedError::Tokenizer(e.to_string()))?;
+        let inputs = EncoderInputBuilder::for_session(tokenizer, 512, Truncation::Head, &session)?;
 
         Ok(Self {
             session,
-            tokenizer,
-            max_length: 512,
-            requires_token_type_ids,
+            inputs,
+            score_transform: config.score_transform,
+            batch_pairs: RERANK_BATCH_PAIRS,
         })
     }
+}
 
-    #[allow(clippy::type_complexity)]
-    fn build_inputs(
-        &self,
-        query: &str,
-        documents: &[String],
-    ) -> Result<(Array2<i64>, Array2<i64>, Option<Array2<i64>>)> {
-        let mut encodings = Vec::with_capacity(documents.len());
-        for doc in documents {
-            let encoding = self
-                .tokenizer
-                .encode(EncodeInput::Dual(query.into(), doc.as_str().into()), true)
-                .map_err(|e| anyhow!("Tokenizer encode failed: {e}"))?;
-            encodings.push(encoding);
+impl MixedbreadReranker {
+    /// Logits of `pairs`, run `batch_pairs` at a time. A run that is out of
+    /// memory is retried at half the size, which then sticks.
+    fn batched_logits(&mut self, pairs: &[(&str, &str)]) -> Result<Vec<f32>> {
+        let mut logits = Vec::with_capacity(pairs.len());
+        let mut rest = pairs;
+        while !rest.is_empty() {
+            let batch = &rest[..self.batch_pairs.min(rest.len())];
+            match self.logits(batch) {
+                Ok(batch_logits) => {
+                    logits.extend(batch_logits);
+                    rest = &rest[batch.len()..];
+                }
+                Err(e) if e.is_out_of_memory() && batch.len() > 1 => {
+                    self.batch_pairs = batch.len() / 2;
+                    tracing::warn!(
+                        "Out of memory reranking {} pairs at once; retrying {} at a time",
+                        batch.len(),
+                        self.batch_pairs
+                    );
+                }
+                Err(e) => return Err(e),
+            }
         }
+        Ok(logits)
+    }
 
-        let seq_len = encodings
-            .iter()
-            .map(|encoding| encoding.len())
-            .max()
-            .unwrap_or(1)
-            .min(self.max_length)
-            .max(1);
-
-        let batch = encodings.len();
-        let mut input_ids = vec![0i64; batch * seq_len];
-        let mut attention_mask = vec![0i64; batch * seq_len];
-        let mut token_types = if self.requires_token_type_ids {
-            Some(vec![0i64; batch * seq_len])
-        } else {
-            None
-        };
-
-        for (row, encoding) in encodings.iter().enumerate() {
-            let ids = encoding.get_ids();
-            let mask = encoding.get_attention_mask();
-            let type_ids = encoding.get_type_ids();
-            let len = ids.len().min(seq_len);
-            let offset = row * seq_len;
+    /// Raw logits for `(query, document)` pairs, in one ONNX run.
+    fn logits(&mut self, pairs: &[(&str, &str)]) -> Result<Vec<f32>> {
+        let inputs = self.inputs.pairs(pairs)?;
 
-            for idx in 0..len {
-                input_ids[offset + idx] = ids[idx] as i64;
-                attention_mask[offset + idx] = mask[idx] as i64;
-            }
+        let _span = tracing::debug_span!("onnx_run", batch = pairs.len()).entered();
+        let outputs = inputs.run(&mut self.session)?;
 
-            if let Some(ref mut token_types_buf) = token_types
-                && !type_ids.is_empty()
-            {
-                for idx in 0..len {
-                    token_types_buf[offset + idx] = type_ids[idx] as i64;
-                }
-            }
-        }
+        let logits = outputs[0]
+            .try_extract_array::<f32>()
+            .map_err(|e| EmbedError::Inference(format!("Failed to extract reranker logits: {e}")))?
+            .into_dimensionality::<Ix2>()?;
 
-        let token_type_array =
-            token_types.map(|buf| Array2::from_shape_vec((batch, seq_len), buf).unwrap());
+        Ok(logits
+            .rows()
+            .into_iter()
+            .map(|row| row.iter().copied().next().unwrap_or(0.0))
+            .collect())
+    }
 
-        Ok((
-            Array2::from_shape_vec((batch, seq_len), input_ids)
-                .expect("validated dimensions for input ids"),
-            Array2::from_shape_vec((batch, seq_len), attention_mask)
-                .expect("validated dimensions for attention mask"),
-            token_type_array,
-        ))
+    /// Transformed scores for `documents`, as `RerankResult`s.
+    fn results(&self, documents: &[Document<'_>], mut scores: Vec<f32>) -> Vec<RerankResult> {
+        self.score_transform.apply(&mut scores);
+        documents
+            .iter()
+            .zip(scores)
+            .map(|(document, score)| RerankResult {
+                id: document.id,
+                score,
+            })
+            .collect()
     }
 }
 
@@ -316,51 +324,57 @@ impl Reranker for MixedbreadReranker {
         "mixedbread_reranker"
     }
 
-    fn rerank(&mut self, query: &str, documents: &[String]) -> Result<Vec<RerankResult>> {
+    #[tracing::instrument(name = "rerank", level = "debug", skip_all, fields(documents = documents.len()))]
+    fn rerank(&mut self, query: &str, documents: &[Document<'_>]) -> Result<Vec<RerankResult>> {
         if documents.is_empty() {
             return Ok(Vec::new());
         }
+        let pairs: Vec<(&str, &str)> = documents.iter().map(|doc| (query, doc.text)).collect();
+        let scores = self.batched_logits(&pairs)?;
+        Ok(self.results(documents, scores))
+    }
 
-        let (input_ids, attention_mask, token_types) = self.build_inputs(query, documents)?;
-
-        let outputs = if self.requires_token_type_ids {
-            let token_types = token_types.expect("token type ids required but missing");
-            self.session.run(ort::inputs![
-                Value::from_array(input_ids)?,
-                Value::from_array(attention_mask)?,
-                Value::from_array(token_types)?
-            ])?
-        } else {
-            self.session.run(ort::inputs![
-                Value::from_array(input_ids)?,
-                Value::from_array(attention_mask)?
-            ])?
-        };
-
-        let logits = outputs[0]
-            .try_extract_array::<f32>()
-            .context("Failed to extract reranker logits")?
-            .into_dimensionality::<Ix2>()?;
-
-        let mut results = Vec::with_capacity(documents.len());
-        for (i, row) in logits.rows().into_iter().enumerate() {
-            let logit = row
-                .get(0)
-                .copied()
-                .unwrap_or_else(|| row.iter().copied().next().unwrap_or(0.0));
-            let score = 1.0 / (1.0 + (-logit).exp());
-            results.push(RerankResult {
-                query: query.to_string(),
-                document: documents[i].clone(),
-                score,
-            });
-        }
-
-        Ok(results)
+    /// Packs the pairs of every query into shared runs of up to
+    /// `RERANK_BATCH_PAIRS`, so many queries with a few candidates each
+    /// cost a few full batches rather than one small run per query.
+    #[tracing::instrument(name = "rerank_batch", level = "debug", skip_all, fields(queries = queries.len()))]
+    fn rerank_batch(
+        &mut self,
+        queries: &[String],
+        docs_per_query: &[Vec<Document<'_>>],
+    ) -> Result<Vec<Vec<RerankResult>>> {
+        check_batch_shape(queries, docs_per_query)?;
+        let pairs: Vec<(&str, &str)> = queries
+            .iter()
+            .zip(docs_per_query)
+            .flat_map(|(query, documents)| {
+                documents.iter().map(move |doc| (query.as_str(), doc.text))
+            })
+            .collect();
+        let mut logits = self.batched_logits(&pairs)?.into_iter();
+        Ok(docs_per_query
+            .iter()
+            .map(|documents| {
+                let scores = logits.by_ref().take(documents.len()).collect();
+                self.results(documents, scores)
+            })
+            .collect())
     }
 }
 
-fn normalize_row(row: ArrayView<'_, f32, Ix1>, dim: usize) -> Vec<f32> {
+/// Embedding width declared by the graph's first output, when it is static.
+fn declared_dimensions(session: &Session) -> Option<usize> {
+    let shape = session.outputs().first()?.dtype().tensor_shape()?;
+    shape
+        .last()
+        .copied()
+        .filter(|dim| *dim > 0)
+        .map(|dim| dim as usize)
+}
+
+/// Pad or cut `row` to `dim` values and, with `unit_length`, scale it to unit
+/// length.
+fn normalize_row(row: ArrayView<'_, f32, Ix1>, dim: usize, unit_length: bool) -> Vec<f32> {
     let take = row.len().min(dim);
     let mut values = vec![0f32; dim];
     let mut norm = 0.0;
@@ -369,7 +383,7 @@ fn normalize_row(row: ArrayView<'_, f32, Ix1>, dim: usize) -> Vec<f32> {
         norm += value * value;
     }
 
-    if norm > 0.0 {
+    if unit_length && norm > 0.0 {
         let inv = norm.sqrt().recip();
         for value in values.iter_mut().take(take) {
             *value *= inv;
@@ -379,32 +393,111 @@ fn normalize_row(row: ArrayView<'_, f32, Ix1>, dim: usize) -> Vec<f32> {
     values
 }
 
+fn load_session(model_id: &str, model_path: &std::path::Path) -> Result<Session> {
+    Session::builder()
+        .and_then(|builder| builder.with_optimization_level(GraphOptimizationLevel::Level3))
+        .and_then(|builder| {
+            let limits = crate::resource_limits();
+            builder
+                .with_intra_threads(limits.threads.unwrap_or_else(num_cpus::get).max(1))?
+                .with_memory_pattern(limits.max_memory.is_none())
+        })
+        .and_then(|builder| builder.commit_from_file(model_path))
+        .map_err(|e| EmbedError::model_load(model_id, e))
+}
+
 fn download_assets(
     model_id: &str,
     model_path: &str,
     tokenizer_path: &str,
 ) -> Result<(PathBuf, PathBuf)> {
-    let cache_dir = model_cache_root()?;
-    std::fs::create_dir_all(&cache_dir)?;
-
-    let api = ApiBuilder::new()
-        .with_cache_dir(cache_dir)
-        .build()
-        .context("Failed to initialize Hugging Face Hub client")?;
-
-    let repo = Repo::with_revision(model_id.to_string(), RepoType::Model, "main".to_string());
-    let tokenizer = api
-        .repo(Repo::with_revision(
-            model_id.to_string(),
-            RepoType::Model,
-            "main".to_string(),
-        ))
-        .get(tokenizer_path)
-        .with_context(|| format!("Failed to download tokenizer for {model_id}"))?;
-    let model = api
-        .repo(repo)
-        .get(model_path)
-        .with_context(|| format!("Failed to download ONNX model for {model_id}"))?;
+    let tokenizer = crate::download_hub_file(model_id, model_id, tokenizer_path, "tokenizer")?;
+    let model = crate::download_hub_file(model_id, model_id, model_path, "ONNX model")?;
+    Ok((model, tokenizer))
+}
+
+/// Split a Hugging Face reference `owner/repo[/file]` into the repo and the file
+/// within it, defaulting to `default_file`.
+fn split_hub_reference<'a>(reference: &'a str, default_file: &'a str) -> (&'a str, &'a str) {
+    let mut parts = reference.splitn(3, '/');
+    let owner_len = parts.next().map_or(0, str::len);
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(file)) => (&reference[..owner_len + 1 + name.len()], file),
+        _ => (reference, default_file),
+    }
+}
+
+/// Pooling named by the `1_Pooling/config.json` of a user-registered model:
+/// beside a local export (or above its `onnx/` directory), or in its repo.
+fn source_pooling(model_id: &str, source: &OnnxSource, model: &Path) -> Option<Pooling> {
+    let config = if Path::new(&source.model).is_file() {
+        model
+            .ancestors()
+            .skip(1)
+            .take(2)
+            .map(|dir| dir.join(POOLING_PATH))
+            .find(|candidate| candidate.is_file())?
+    } else {
+        let (repo, _) = split_hub_reference(&source.model, ONNX_MODEL_PATH);
+        crate::download_hub_file(model_id, repo, POOLING_PATH, "pooling config").ok()?
+    };
+    read_pooling(&config)
+}
+
+/// Local paths, or downloaded copies, of a user-registered model's files.
+fn resolve_source(model_id: &str, source: &OnnxSource) -> Result<(PathBuf, PathBuf)> {
+    let local_model = Path::new(&source.model);
+    let model = if local_model.is_file() {
+        local_model.to_path_buf()
+    } else {
+        let (repo, file) = split_hub_reference(&source.model, ONNX_MODEL_PATH);
+        crate::download_hub_file(model_id, repo, file, "ONNX model")?
+    };
+
+    let tokenizer = match source.tokenizer.as_deref() {
+        Some(tokenizer) if Path::new(tokenizer).is_file() => PathBuf::from(tokenizer),
+        Some(tokenizer) => {
+            let (repo, file) = split_hub_reference(tokenizer, ONNX_TOKENIZER_PATH);
+            crate::download_hub_file(model_id, repo, file, "tokenizer")?
+        }
+        // Exports usually keep the tokenizer at the repo root, above `onnx/`
+        None if local_model.is_file() => model
+            .ancestors()
+            .skip(1)
+            .take(2)
+            .map(|dir| dir.join(ONNX_TOKENIZER_PATH))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| {
+                EmbedError::model_load(
+                    model_id,
+                    format!(
+                        "no {ONNX_TOKENIZER_PATH} next to {}; pass the tokenizer explicitly",
+                        model.display()
+                    ),
+                )
+            })?,
+        None => {
+            let (repo, _) = split_hub_reference(&source.model, ONNX_MODEL_PATH);
+            crate::download_hub_file(model_id, repo, ONNX_TOKENIZER_PATH, "tokenizer")?
+        }
+    };
 
     Ok((model, tokenizer))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_hub_reference() {
+        assert_eq!(
+            split_hub_reference("acme/encoder", ONNX_MODEL_PATH),
+            ("acme/encoder", "onnx/model.onnx")
+        );
+        assert_eq!(
+            split_hub_reference("acme/encoder/export/model_int8.onnx", ONNX_MODEL_PATH),
+            ("acme/encoder", "export/model_int8.onnx")
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Embedders behind an OpenAI-compatible `/v1/embeddings` endpoint: a hosted
+//! API, or a `llama-server` (or similar) that is already running. The
+//! registry entry's `remote.url` is the endpoint and its `name` the model
+//! requested from it. Requests go through [`HttpTransport`] under the
+//! model's `remote` settings.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use ck_models::ModelConfig;
+use serde::Deserialize;
+
+use crate::http::HttpTransport;
+use crate::{EmbedError, Embedder, Result, TokenEstimator};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// One request's embeddings, in input order.
+type BatchResult = Result<Vec<Vec<f32>>>;
+
+pub struct OpenAiEmbedder {
+    transport: HttpTransport,
+    dim: usize,
+    model_name: String,
+    normalize: bool,
+    strict_dimensions: bool,
+    tokens_used: AtomicU64,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct Usage {
+    prompt_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl OpenAiEmbedder {
+    pub fn new(config: &ModelConfig) -> Result<Self> {
+        let url = config
+            .remote
+            .url
+            .as_deref()
+            .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+            .ok_or_else(|| {
+                EmbedError::model_load(
+                    &config.name,
+                    "the openai provider needs an http(s) `remote.url` in its registry entry",
+                )
+            })?;
+        Self::at(config, url)
+    }
+
+    /// Embed with `config`'s model at the endpoint `base_url`, after a probe
+    /// embedding that detects its width.
+    pub(crate) fn at(config: &ModelConfig, base_url: &str) -> Result<Self> {
+        let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+        let mut embedder = Self {
+            transport: HttpTransport::new(
+                agent,
+                base_url.trim_end_matches('/').to_string(),
+                &config.remote,
+            ),
+            dim: config.dimensions,
+            model_name: config.name.clone(),
+            normalize: config.normalize,
+            strict_dimensions: config.strict_dimensions,
+            tokens_used: AtomicU64::new(0),
+        };
+
+        let detected = embedder
+            .request(&["dimension probe".to_string()])?
+            .first()
+            .map(Vec::len)
+            .filter(|dim| *dim > 0)
+            .ok_or_else(|| {
+                EmbedError::Inference(format!(
+                    "Model '{}' returned an empty embedding",
+                    config.name
+                ))
+            })?;
+        embedder.dim = crate::resolve_dimensions(&config.name, config.dimensions, detected)?;
+        Ok(embedder)
+    }
+
+    /// Embed `texts` in batches, up to the endpoint's concurrency limit at once.
+    fn request(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let batches: Vec<&[String]> = texts.chunks(self.transport.batch_size()).collect();
+        if batches.len() <= 1 {
+            return self.request_batch(texts);
+        }
+
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<BatchResult>>> =
+            Mutex::new((0..batches.len()).map(|_| None).collect());
+        let workers = self.transport.max_concurrency().min(batches.len());
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(batch) = batches.get(i) else {
+                            break;
+                        };
+                        let result = self.request_batch(batch);
+                        let failed = result.is_err();
+                        results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(result);
+                        if failed {
+                            // Leave the remaining batches; the embed call fails anyway
+                            next.store(batches.len(), Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for result in results.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            match result {
+                Some(result) => embeddings.extend(result?),
+                None => {
+                    return Err(EmbedError::Inference(
+                        "embedding request was not sent".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(embeddings)
+    }
+
+    fn request_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let response: EmbeddingResponse = self.transport.post_json(
+            "/v1/embeddings",
+            &serde_json::json!({ "input": texts, "model": self.model_name }),
+        )?;
+        // Servers that do not report usage are billed by estimate
+        let tokens = response.usage.map_or_else(
+            || {
+                texts
+                    .iter()
+                    .map(|text| TokenEstimator::estimate_tokens(text) as u64)
+                    .sum()
+            },
+            |usage| usage.prompt_tokens,
+        );
+        self.tokens_used.fetch_add(tokens, Ordering::Relaxed);
+
+        let mut data = response.data;
+        if data.len() != texts.len() {
+            return Err(EmbedError::Inference(format!(
+                "{} returned {} embeddings for {} inputs",
+                self.transport.base_url(),
+                data.len(),
+                texts.len()
+            )));
+        }
+        data.sort_by_key(|item| item.index);
+        Ok(data.into_iter().map(|item| item.embedding).collect())
+    }
+}
+
+impl Embedder for OpenAiEmbedder {
+    fn id(&self) -> &'static str {
+        "openai"
+    }
+
+    fn dim(&self) -> usize {
+        self.dim
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    #[tracing::instrument(name = "embed", level = "debug", skip_all, fields(model = %self.model_name, texts = texts.len()))]
+    fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.request(texts)?
+            .into_iter()
+            .map(|row| {
+                crate::check_width(
+                    &self.model_name,
+                    row.len(),
+                    self.dim,
+                    self.strict_dimensions,
+                )?;
+                Ok(crate::fit_row(row, self.dim, self.normalize))
+            })
+            .collect()
+    }
+
+    fn tokens_used(&self) -> Option<u64> {
+        Some(self.tokens_used.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::tests::serve;
+
+    fn config(url: Option<String>) -> ModelConfig {
+        let mut config = ck_models::ModelRegistry::default()
+            .get_default_model()
+            .unwrap()
+            .clone();
+        config.name = "acme-embed".to_string();
+        config.provider = "openai".to_string();
+        config.dimensions = 0;
+        config.remote.url = url;
+        config.remote.batch_size = 1;
+        config.remote.max_concurrency = 1;
+        config
+    }
+
+    #[test]
+    fn test_openai_embeds_in_batches_from_the_configured_url() {
+        let url = serve(vec![
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: 40\r\n\r\n{\"data\":[{\"index\":0,\"embedding\":[3,4]}]}",
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: 40\r\n\r\n{\"data\":[{\"index\":0,\"embedding\":[0,2]}]}",
+            "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: 40\r\n\r\n{\"data\":[{\"index\":0,\"embedding\":[5,0]}]}",
+        ]);
+        let mut embedder = OpenAiEmbedder::new(&config(Some(url))).unwrap();
+        assert_eq!(embedder.dim(), 2);
+        let embeddings = embedder
+            .embed(&["retry logic".to_string(), "parse config".to_string()])
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![0.0, 1.0], vec![1.0, 0.0]]);
+        assert!(embedder.tokens_used().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_openai_needs_a_url() {
+        let err = OpenAiEmbedder::new(&config(None)).err().unwrap();
+        assert!(err.to_string().contains("remote.url"), "{err}");
+        let err = OpenAiEmbedder::new(&config(Some("127.0.0.1:8080".to_string())))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("remote.url"), "{err}");
+    }
+}
@@ -0,0 +1,318 @@
+//! Embedders run out of process. A registry entry whose provider is
+//! `plugin:/path/to/bin` has ck start that program and talk to it over stdio,
+//! one JSON object per line, so an embedder can be closed source or written in
+//! any language. Each request carries the entry's `name` and the texts to
+//! embed:
+//!
+//! ```text
+//! > {"model":"acme-embed","texts":["fn main() {}","retry logic"]}
+//! < {"embeddings":[[0.12,-0.03,...],[0.08,0.41,...]]}
+//! ```
+//!
+//! A plugin that cannot embed answers `{"error":"message"}` instead. Anything
+//! it writes to stderr goes to ck's stderr. It is started once per embedder
+//! and stopped when stdin closes. One that does not answer a request within
+//! two minutes is killed, and the request fails.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use ck_models::ModelConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{EmbedError, Embedder, Result};
+
+/// Provider prefix naming a plugin executable.
+pub const PLUGIN_PREFIX: &str = "plugin:";
+
+/// How long a plugin may take to answer one request.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    model: &'a str,
+    texts: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    #[serde(default)]
+    embeddings: Vec<Vec<f32>>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// An embedder plugin. Its pipes are served by threads of their own, as for
+/// chunker plugins, so a plugin that stops reading or answering costs a
+/// timeout, not a hang.
+pub struct PluginEmbedder {
+    child: Child,
+    /// Closed to ask the plugin to exit, or once it has been killed
+    requests: Option<Sender<String>>,
+    /// In a mutex only so the embedder is `Sync`; requests hold `&mut self`
+    replies: Mutex<Receiver<std::io::Result<String>>>,
+    timeout: Duration,
+    program: String,
+    dim: usize,
+    model_name: String,
+    normalize: bool,
+    strict_dimensions: bool,
+}
+
+impl PluginEmbedder {
+    pub fn new(program: &str, config: &ModelConfig) -> Result<Self> {
+        Self::with_timeout(program, config, REPLY_TIMEOUT)
+    }
+
+    fn with_timeout(program: &str, config: &ModelConfig, timeout: Duration) -> Result<Self> {
+        let load_error = |err: std::io::Error| {
+            EmbedError::model_load(
+                &config.name,
+                format!("cannot start plugin '{}': {}", program, err),
+            )
+        };
+        let mut child = Command::new(program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(load_error)?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+        let (requests, pending) = mpsc::channel::<String>();
+        std::thread::spawn(move || {
+            for line in pending {
+                if stdin
+                    .write_all(line.as_bytes())
+                    .and_then(|_| stdin.flush())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        let (answered, replies) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in stdout.lines() {
+                if answered.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut embedder = Self {
+            child,
+            requests: Some(requests),
+            replies: Mutex::new(replies),
+            timeout,
+            program: program.to_string(),
+            dim: config.dimensions,
+            model_name: config.name.clone(),
+            normalize: config.normalize,
+            strict_dimensions: config.strict_dimensions,
+        };
+        let detected = embedder
+            .request(&["dimension probe".to_string()])?
+            .first()
+            .map(Vec::len)
+            .filter(|dim| *dim > 0)
+            .ok_or_else(|| {
+                EmbedError::Inference(format!("Plugin '{}' returned an empty embedding", program))
+            })?;
+        embedder.dim = crate::resolve_dimensions(&config.name, config.dimensions, detected)?;
+        Ok(embedder)
+    }
+
+    fn request(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let failed = |message: String| {
+            EmbedError::Inference(format!("Plugin '{}' {}", self.program, message))
+        };
+        let mut line = serde_json::to_string(&EmbedRequest {
+            model: &self.model_name,
+            texts,
+        })
+        .map_err(|e| failed(format!("request could not be encoded: {}", e)))?;
+        line.push('\n');
+        let requests = self
+            .requests
+            .as_ref()
+            .ok_or_else(|| failed("is stopped".to_string()))?;
+        if requests.send(line).is_err() {
+            return Err(failed("stopped reading requests".to_string()));
+        }
+
+        let replies = self.replies.get_mut().unwrap_or_else(|e| e.into_inner());
+        let reply = match replies.recv_timeout(self.timeout) {
+            Ok(reply) => reply?,
+            Err(RecvTimeoutError::Timeout) => {
+                self.requests = None;
+                let _ = self.child.kill();
+                return Err(EmbedError::Inference(format!(
+                    "Plugin '{}' did not answer within {:?}",
+                    self.program, self.timeout
+                )));
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(failed("exited without answering".to_string()));
+            }
+        };
+        let response: EmbedResponse = serde_json::from_str(&reply)
+            .map_err(|e| failed(format!("sent an invalid response: {}", e)))?;
+        if let Some(error) = response.error {
+            return Err(failed(format!("failed: {}", error)));
+        }
+        if response.embeddings.len() != texts.len() {
+            return Err(failed(format!(
+                "returned {} embeddings for {} texts",
+                response.embeddings.len(),
+                texts.len()
+            )));
+        }
+        Ok(response.embeddings)
+    }
+}
+
+impl Drop for PluginEmbedder {
+    fn drop(&mut self) {
+        // Closing stdin asks the plugin to exit; one that ignores it is killed
+        drop(self.requests.take());
+        if let Ok(None) = self.child.try_wait() {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            if let Ok(None) = self.child.try_wait() {
+                let _ = self.child.kill();
+            }
+        }
+        let _ = self.child.wait();
+    }
+}
+
+impl Embedder for PluginEmbedder {
+    fn id(&self) -> &'static str {
+        "plugin"
+    }
+
+    fn dim(&self) -> usize {
+        self.dim
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    #[tracing::instrument(name = "embed", level = "debug", skip_all, fields(model = %self.model_name, texts = texts.len()))]
+    fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.request(texts)?
+            .into_iter()
+            .map(|row| {
+                crate::check_width(
+                    &self.model_name,
+                    row.len(),
+                    self.dim,
+                    self.strict_dimensions,
+                )?;
+                Ok(crate::fit_row(row, self.dim, self.normalize))
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    /// A plugin answering every request with `reply`.
+    fn plugin(dir: &TempDir, reply: &str) -> String {
+        let path = dir.path().join("plugin.sh");
+        std::fs::write(
+            &path,
+            format!("#!/bin/sh\nwhile read -r line; do echo '{}'; done\n", reply),
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn config() -> ModelConfig {
+        let mut config = ck_models::ModelRegistry::default()
+            .get_default_model()
+            .unwrap()
+            .clone();
+        config.name = "acme-embed".to_string();
+        config.dimensions = 0;
+        config.normalize = true;
+        config
+    }
+
+    #[test]
+    fn test_plugin_embeds_over_stdio() {
+        let dir = TempDir::new().unwrap();
+        let program = plugin(&dir, r#"{"embeddings":[[3.0,4.0]]}"#);
+
+        let mut embedder = PluginEmbedder::new(&program, &config()).unwrap();
+        assert_eq!(embedder.dim(), 2);
+        let embeddings = embedder.embed(&["retry logic".to_string()]).unwrap();
+        assert_eq!(embeddings, vec![vec![0.6, 0.8]]);
+        assert!(embedder.embed(&["a".to_string(), "b".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_plugin_width_mismatch_fails_unless_lenient() {
+        let dir = TempDir::new().unwrap();
+        let program = plugin(&dir, r#"{"embeddings":[[3.0,4.0]]}"#);
+
+        let mut embedder = PluginEmbedder::new(&program, &config()).unwrap();
+        embedder.dim = 3;
+        let err = embedder.embed(&["retry logic".to_string()]).unwrap_err();
+        assert!(matches!(
+            err,
+            EmbedError::DimMismatch {
+                expected: 3,
+                actual: 2,
+                ..
+            }
+        ));
+
+        embedder.strict_dimensions = false;
+        let embeddings = embedder.embed(&["retry logic".to_string()]).unwrap();
+        assert_eq!(embeddings, vec![vec![0.6, 0.8, 0.0]]);
+    }
+
+    #[test]
+    fn test_plugin_errors_reach_the_caller() {
+        let dir = TempDir::new().unwrap();
+        let program = plugin(&dir, r#"{"error":"model not loaded"}"#);
+
+        let err = PluginEmbedder::new(&program, &config()).err().unwrap();
+        assert!(err.to_string().contains("model not loaded"));
+        assert!(PluginEmbedder::new("/nonexistent/plugin", &config()).is_err());
+    }
+
+    #[test]
+    fn test_stuck_plugin_times_out() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("stuck.sh");
+        std::fs::write(&path, "#!/bin/sh\nwhile read -r line; do sleep 60; done\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let started = std::time::Instant::now();
+        let err = PluginEmbedder::with_timeout(
+            &path.to_string_lossy(),
+            &config(),
+            Duration::from_millis(200),
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(err, EmbedError::Inference(_)));
+        assert!(err.to_string().contains("did not answer"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+}
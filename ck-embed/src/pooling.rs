@@ -0,0 +1,118 @@
+//! Pooling of per-token hidden states into one embedding, for encoders whose
+//! graph outputs only the last hidden state (`[batch, tokens, dims]`). Which
+//! pooling a model was trained with comes from its registry entry or, failing
+//! that, the sentence-transformers `1_Pooling/config.json` beside it.
+
+use ck_models::Pooling;
+use ndarray::{Array1, ArrayView1, ArrayView2};
+use std::path::Path;
+
+/// sentence-transformers pooling settings, present in most embedding repos
+pub(crate) const POOLING_PATH: &str = "1_Pooling/config.json";
+
+/// Pooling named by a sentence-transformers `1_Pooling/config.json`.
+pub(crate) fn read_pooling(path: &Path) -> Option<Pooling> {
+    let settings: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    let enabled = |key: &str| settings.get(key).and_then(|v| v.as_bool()) == Some(true);
+    if enabled("pooling_mode_cls_token") {
+        Some(Pooling::Cls)
+    } else if enabled("pooling_mode_mean_tokens") {
+        Some(Pooling::Mean)
+    } else {
+        None
+    }
+}
+
+/// One row's `[tokens, dims]` hidden states pooled over the tokens its
+/// attention `mask` keeps: their mean, or the first of them (`[CLS]`, which
+/// need not be at position 0 when the tokenizer pads on the left).
+#[cfg_attr(not(feature = "mixedbread"), allow(dead_code))]
+pub(crate) fn pool_tokens(
+    states: ArrayView2<'_, f32>,
+    mask: ArrayView1<'_, i64>,
+    pooling: Pooling,
+) -> Array1<f32> {
+    let mut kept = states
+        .outer_iter()
+        .zip(mask)
+        .filter(|(_, keep)| **keep != 0)
+        .map(|(token, _)| token);
+    match pooling {
+        Pooling::Cls => kept
+            .next()
+            .map(|token| token.to_owned())
+            .unwrap_or_else(|| Array1::zeros(states.ncols())),
+        Pooling::Mean => {
+            let mut sum = Array1::<f32>::zeros(states.ncols());
+            let mut count = 0.0f32;
+            for token in kept {
+                sum += &token;
+                count += 1.0;
+            }
+            if count > 0.0 {
+                sum /= count;
+            }
+            sum
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn test_read_pooling() {
+        let temp_dir = std::env::temp_dir().join(format!("ck-pooling-{}", std::process::id()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("config.json");
+
+        std::fs::write(
+            &path,
+            r#"{"word_embedding_dimension": 384, "pooling_mode_cls_token": true, "pooling_mode_mean_tokens": false}"#,
+        )
+        .unwrap();
+        assert_eq!(read_pooling(&path), Some(Pooling::Cls));
+
+        std::fs::write(&path, r#"{"pooling_mode_mean_tokens": true}"#).unwrap();
+        assert_eq!(read_pooling(&path), Some(Pooling::Mean));
+
+        std::fs::write(&path, r#"{"pooling_mode_max_tokens": true}"#).unwrap();
+        assert_eq!(read_pooling(&path), None);
+
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_pool_tokens_skips_padding() {
+        let states = array![[9.0, 9.0], [1.0, 2.0], [3.0, 4.0], [9.0, 9.0]];
+
+        let right_padded = array![1, 1, 1, 0];
+        assert_eq!(
+            pool_tokens(states.view(), right_padded.view(), Pooling::Mean),
+            array![13.0 / 3.0, 5.0]
+        );
+        assert_eq!(
+            pool_tokens(states.view(), right_padded.view(), Pooling::Cls),
+            array![9.0, 9.0]
+        );
+
+        let left_padded = array![0, 1, 1, 0];
+        assert_eq!(
+            pool_tokens(states.view(), left_padded.view(), Pooling::Mean),
+            array![2.0, 3.0]
+        );
+        assert_eq!(
+            pool_tokens(states.view(), left_padded.view(), Pooling::Cls),
+            array![1.0, 2.0]
+        );
+
+        let empty = array![0, 0, 0, 0];
+        assert_eq!(
+            pool_tokens(states.view(), empty.view(), Pooling::Mean),
+            array![0.0, 0.0]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Embedding and reranking providers by name. The `provider` of a registry
+//! entry picks the factory that builds its model. Built-in providers are
+//! registered from the start; a downstream crate adds its own, or replaces a
+//! built-in, with [`register_embedder_provider`] and
+//! [`register_reranker_provider`] before it creates models.
+
+use ck_models::{ModelConfig, RerankModelConfig};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use crate::reranker::{RerankModelDownloadCallback, Reranker};
+use crate::{Embedder, ModelDownloadCallback, Result};
+
+/// Builds an embedder for a registry entry naming this provider.
+pub type EmbedderFactory = Arc<
+    dyn Fn(&ModelConfig, Option<ModelDownloadCallback>) -> Result<Box<dyn Embedder>> + Send + Sync,
+>;
+
+/// Builds a reranker for a registry entry naming this provider.
+pub type RerankerFactory = Arc<
+    dyn Fn(&RerankModelConfig, Option<RerankModelDownloadCallback>) -> Result<Box<dyn Reranker>>
+        + Send
+        + Sync,
+>;
+
+static EMBEDDERS: LazyLock<RwLock<HashMap<String, EmbedderFactory>>> = LazyLock::new(|| {
+    let builtins: [(&str, EmbedderFactory); 6] = [
+        ("fastembed", Arc::new(crate::fastembed_embedder)),
+        ("mixedbread", Arc::new(crate::mixedbread_embedder)),
+        ("candle", Arc::new(crate::candle_embedder)),
+        ("llama-server", Arc::new(crate::llama_server_embedder)),
+        ("openai", Arc::new(crate::openai_embedder)),
+        ("onnx", Arc::new(crate::onnx_embedder)),
+    ];
+    RwLock::new(
+        builtins
+            .into_iter()
+            .map(|(name, factory)| (name.to_string(), factory))
+            .collect(),
+    )
+});
+
+static RERANKERS: LazyLock<RwLock<HashMap<String, RerankerFactory>>> = LazyLock::new(|| {
+    let builtins: [(&str, RerankerFactory); 2] = [
+        ("fastembed", Arc::new(crate::reranker::fastembed_reranker)),
+        ("mixedbread", Arc::new(crate::reranker::mixedbread_reranker)),
+    ];
+    RwLock::new(
+        builtins
+            .into_iter()
+            .map(|(name, factory)| (name.to_string(), factory))
+            .collect(),
+    )
+});
+
+/// Make `name` an embedding provider, replacing any provider of that name.
+pub fn register_embedder_provider<F>(name: &str, factory: F)
+where
+    F: Fn(&ModelConfig, Option<ModelDownloadCallback>) -> Result<Box<dyn Embedder>>
+        + Send
+        + Sync
+        + 'static,
+{
+    EMBEDDERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_string(), Arc::new(factory));
+}
+
+/// Make `name` a reranking provider, replacing any provider of that name.
+pub fn register_reranker_provider<F>(name: &str, factory: F)
+where
+    F: Fn(&RerankModelConfig, Option<RerankModelDownloadCallback>) -> Result<Box<dyn Reranker>>
+        + Send
+        + Sync
+        + 'static,
+{
+    RERANKERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_string(), Arc::new(factory));
+}
+
+/// Names of the registered embedding providers, sorted.
+pub fn embedder_providers() -> Vec<String> {
+    sorted_names(&EMBEDDERS.read().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Names of the registered reranking providers, sorted.
+pub fn reranker_providers() -> Vec<String> {
+    sorted_names(&RERANKERS.read().unwrap_or_else(|e| e.into_inner()))
+}
+
+// Factories are cloned out so that loading a model never holds the lock
+
+pub(crate) fn embedder_factory(name: &str) -> Option<EmbedderFactory> {
+    EMBEDDERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .cloned()
+}
+
+pub(crate) fn reranker_factory(name: &str) -> Option<RerankerFactory> {
+    RERANKERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .cloned()
+}
+
+fn sorted_names<T>(providers: &HashMap<String, T>) -> Vec<String> {
+    let mut names: Vec<String> = providers.keys().cloned().collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reranker::DummyReranker;
+    use crate::{DummyEmbedder, create_embedder_for_config};
+
+    #[test]
+    fn test_builtin_providers_are_registered() {
+        let embedders = embedder_providers();
+        for name in [
+            "candle",
+            "fastembed",
+            "llama-server",
+            "mixedbread",
+            "onnx",
+            "openai",
+        ] {
+            assert!(embedders.iter().any(|provider| provider == name));
+        }
+        let rerankers = reranker_providers();
+        assert!(rerankers.iter().any(|provider| provider == "fastembed"));
+    }
+
+    #[test]
+    fn test_registered_provider_builds_models() {
+        register_embedder_provider("test-embedder", |config, _| {
+            Ok(Box::new(DummyEmbedder::new_with_model(&config.name)))
+        });
+        register_reranker_provider("test-reranker", |_, _| Ok(Box::new(DummyReranker::new())));
+
+        let mut config = ck_models::ModelRegistry::default()
+            .get_default_model()
+            .unwrap()
+            .clone();
+        config.provider = "test-embedder".to_string();
+        config.name = "acme/embed".to_string();
+        let embedder = create_embedder_for_config(&config, None).unwrap();
+        assert_eq!(embedder.model_name(), "acme/embed");
+
+        config.provider = "unregistered".to_string();
+        assert!(create_embedder_for_config(&config, None).is_err());
+
+        assert!(reranker_factory("test-reranker").is_some());
+    }
+}
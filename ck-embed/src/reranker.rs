@@ -1,22 +1,70 @@
-use anyhow::{Result, bail};
+use crate::{EmbedError, Result};
 use ck_models::{RerankModelConfig, RerankModelRegistry};
 
 #[cfg(feature = "mixedbread")]
 use crate::mixedbread::MixedbreadReranker;
 
+#[cfg(feature = "fastembed")]
+use ck_core::ScoreTransform;
 #[cfg(feature = "fastembed")]
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+/// A document to rerank. `id` is the caller's handle for it (an index into
+/// its own results, say), handed back with the score so text is never copied.
+#[derive(Debug, Clone, Copy)]
+pub struct Document<'a> {
+    pub id: usize,
+    pub text: &'a str,
+}
+
+impl<'a> Document<'a> {
+    pub fn new(id: usize, text: &'a str) -> Self {
+        Self { id, text }
+    }
+}
+
+/// The score of the document with this `id`.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct RerankResult {
-    pub query: String,
-    pub document: String,
+    pub id: usize,
     pub score: f32,
 }
 
 pub trait Reranker: Send + Sync {
     fn id(&self) -> &'static str;
-    fn rerank(&mut self, query: &str, documents: &[String]) -> Result<Vec<RerankResult>>;
+    /// One result per document, in no particular order.
+    fn rerank(&mut self, query: &str, documents: &[Document<'_>]) -> Result<Vec<RerankResult>>;
+
+    /// Rerank the documents of several queries at once: `docs_per_query[i]`
+    /// belongs to `queries[i]`, and so does the `i`th list of results. Models
+    /// that can pack pairs from different queries into shared batches override
+    /// this; the default reranks one query at a time.
+    fn rerank_batch(
+        &mut self,
+        queries: &[String],
+        docs_per_query: &[Vec<Document<'_>>],
+    ) -> Result<Vec<Vec<RerankResult>>> {
+        check_batch_shape(queries, docs_per_query)?;
+        queries
+            .iter()
+            .zip(docs_per_query)
+            .map(|(query, documents)| self.rerank(query, documents))
+            .collect()
+    }
+}
+
+pub(crate) fn check_batch_shape(
+    queries: &[String],
+    docs_per_query: &[Vec<Document<'_>>],
+) -> Result<()> {
+    if queries.len() != docs_per_query.len() {
+        return Err(EmbedError::Inference(format!(
+            "rerank_batch got {} queries but {} document lists",
+            queries.len(),
+            docs_per_query.len()
+        )));
+    }
+    Ok(())
 }
 
 pub type RerankModelDownloadCallback = Box<dyn Fn(&str) + Send + Sync>;
@@ -34,46 +82,61 @@ pub fn create_reranker_with_progress(
     create_reranker_for_config(&config, progress_callback)
 }
 
-#[allow(clippy::needless_return)]
 pub fn create_reranker_for_config(
     config: &RerankModelConfig,
     progress_callback: Option<RerankModelDownloadCallback>,
 ) -> Result<Box<dyn Reranker>> {
-    match config.provider.as_str() {
-        "fastembed" => {
-            #[cfg(feature = "fastembed")]
-            {
-                return Ok(Box::new(FastReranker::new_with_progress(
-                    config.name.as_str(),
-                    progress_callback,
-                )?));
-            }
-
-            #[cfg(not(feature = "fastembed"))]
-            {
-                if let Some(callback) = progress_callback.as_ref() {
-                    callback("fastembed reranker unavailable; using dummy reranker");
-                }
-                return Ok(Box::new(DummyReranker::new()));
-            }
+    let factory = crate::providers::reranker_factory(&config.provider).ok_or_else(|| {
+        EmbedError::UnsupportedProvider {
+            kind: "reranker",
+            provider: config.provider.clone(),
         }
-        "mixedbread" => {
-            #[cfg(feature = "mixedbread")]
-            {
-                return Ok(Box::new(MixedbreadReranker::new(
-                    config,
-                    progress_callback,
-                )?));
-            }
-            #[cfg(not(feature = "mixedbread"))]
-            {
-                bail!(
-                    "Reranking model '{}' requires the `mixedbread` feature. Rebuild ck with Mixedbread support.",
-                    config.name
-                );
-            }
+    })?;
+    factory(config, progress_callback)
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn fastembed_reranker(
+    config: &RerankModelConfig,
+    progress_callback: Option<RerankModelDownloadCallback>,
+) -> Result<Box<dyn Reranker>> {
+    #[cfg(feature = "fastembed")]
+    {
+        return Ok(Box::new(
+            FastReranker::new_with_progress(config.name.as_str(), progress_callback)?
+                .with_score_transform(config.score_transform),
+        ));
+    }
+
+    #[cfg(not(feature = "fastembed"))]
+    {
+        let _ = config;
+        if let Some(callback) = progress_callback.as_ref() {
+            callback("fastembed reranker unavailable; using dummy reranker");
         }
-        provider => bail!("Unsupported reranker provider '{}'", provider),
+        return Ok(Box::new(DummyReranker::new()));
+    }
+}
+
+#[allow(clippy::needless_return)]
+pub(crate) fn mixedbread_reranker(
+    config: &RerankModelConfig,
+    progress_callback: Option<RerankModelDownloadCallback>,
+) -> Result<Box<dyn Reranker>> {
+    #[cfg(feature = "mixedbread")]
+    {
+        return Ok(Box::new(MixedbreadReranker::new(
+            config,
+            progress_callback,
+        )?));
+    }
+    #[cfg(not(feature = "mixedbread"))]
+    {
+        let _ = progress_callback;
+        return Err(EmbedError::FeatureDisabled {
+            model: config.name.clone(),
+            feature: "mixedbread",
+        });
     }
 }
 
@@ -96,15 +159,14 @@ impl Reranker for DummyReranker {
         "dummy_reranker"
     }
 
-    fn rerank(&mut self, query: &str, documents: &[String]) -> Result<Vec<RerankResult>> {
+    fn rerank(&mut self, _query: &str, documents: &[Document<'_>]) -> Result<Vec<RerankResult>> {
         // Dummy reranker just returns documents in original order with random scores
         Ok(documents
             .iter()
             .enumerate()
             .map(|(i, doc)| {
                 RerankResult {
-                    query: query.to_string(),
-                    document: doc.clone(),
+                    id: doc.id,
                     score: 0.5 + (i as f32 * 0.1) % 0.5, // Fake scores between 0.5-1.0
                 }
             })
@@ -115,8 +177,8 @@ impl Reranker for DummyReranker {
 #[cfg(feature = "fastembed")]
 pub struct FastReranker {
     model: fastembed::TextRerank,
-    #[allow(dead_code)] // Keep for future use (debugging, logging)
     model_name: String,
+    score_transform: ScoreTransform,
 }
 
 #[cfg(feature = "fastembed")]
@@ -163,7 +225,8 @@ impl FastReranker {
             .with_show_download_progress(progress_callback.is_some())
             .with_cache_dir(model_cache_dir);
 
-        let reranker = TextRerank::try_new(init_options)?;
+        let reranker =
+            TextRerank::try_new(init_options).map_err(|e| EmbedError::model_load(model_name, e))?;
 
         if let Some(ref callback) = progress_callback {
             callback("Reranker model loaded successfully");
@@ -172,9 +235,15 @@ impl FastReranker {
         Ok(Self {
             model: reranker,
             model_name: model_name.to_string(),
+            score_transform: ScoreTransform::default(),
         })
     }
 
+    pub fn with_score_transform(mut self, score_transform: ScoreTransform) -> Self {
+        self.score_transform = score_transform;
+        self
+    }
+
     fn get_model_cache_dir() -> Result<PathBuf> {
         // Use platform-appropriate cache directory (same as embedder)
         let cache_dir = if let Some(cache_home) = std::env::var_os("XDG_CACHE_HOME") {
@@ -204,21 +273,27 @@ impl Reranker for FastReranker {
         "fastembed_reranker"
     }
 
-    fn rerank(&mut self, query: &str, documents: &[String]) -> Result<Vec<RerankResult>> {
-        // Convert documents to string references
-        let docs: Vec<&str> = documents.iter().map(|s| s.as_str()).collect();
+    #[tracing::instrument(name = "rerank", level = "debug", skip_all, fields(model = %self.model_name, documents = documents.len()))]
+    fn rerank(&mut self, query: &str, documents: &[Document<'_>]) -> Result<Vec<RerankResult>> {
+        let docs: Vec<&str> = documents.iter().map(|doc| doc.text).collect();
 
         // Get reranking scores - fastembed rerank takes (query, documents)
-        let results = self.model.rerank(query, docs, true, None)?;
-
-        // Convert to our format
+        let _span = tracing::debug_span!("onnx_run", batch = documents.len()).entered();
+        let results = self
+            .model
+            .rerank(query, docs, true, None)
+            .map_err(EmbedError::inference)?;
+
+        // fastembed returns raw logits sorted best first; `index` maps each back
+        // to its document
+        let mut scores: Vec<f32> = results.iter().map(|result| result.score).collect();
+        self.score_transform.apply(&mut scores);
         let rerank_results = results
-            .into_iter()
-            .enumerate()
-            .map(|(i, result)| RerankResult {
-                query: query.to_string(),
-                document: documents[i].clone(),
-                score: result.score,
+            .iter()
+            .zip(scores)
+            .map(|(result, score)| RerankResult {
+                id: documents[result.index].id,
+                score,
             })
             .collect();
 
@@ -236,17 +311,21 @@ mod tests {
         assert_eq!(reranker.id(), "dummy_reranker");
 
         let query = "find error handling";
-        let documents = vec![
-            "try catch block".to_string(),
-            "function definition".to_string(),
-            "error handling code".to_string(),
+        let texts = [
+            "try catch block",
+            "function definition",
+            "error handling code",
         ];
-
+        let documents: Vec<Document> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| Document::new(i * 10, text))
+            .collect();
         let results = reranker.rerank(query, &documents).unwrap();
         assert_eq!(results.len(), 3);
 
-        for result in &results {
-            assert_eq!(result.query, query);
+        for (result, document) in results.iter().zip(&documents) {
+            assert_eq!(result.id, document.id);
             assert!(result.score >= 0.5 && result.score <= 1.0);
         }
     }
@@ -275,9 +354,9 @@ mod tests {
                 assert_eq!(reranker.id(), "fastembed_reranker");
 
                 let query = "error handling";
-                let documents = vec![
-                    "try catch exception handling".to_string(),
-                    "user interface design".to_string(),
+                let documents = [
+                    Document::new(0, "try catch exception handling"),
+                    Document::new(1, "user interface design"),
                 ];
 
                 let result = reranker.rerank(query, &documents);
@@ -286,8 +365,9 @@ mod tests {
                 let results = result.unwrap();
                 assert_eq!(results.len(), 2);
 
-                // First result should be more relevant to query
-                assert!(results[0].score > results[1].score);
+                // The first document should be more relevant to the query
+                let score = |id| results.iter().find(|r| r.id == id).unwrap().score;
+                assert!(score(0) > score(1));
             }
             Err(_) => {
                 // In test environments, FastEmbed might not be available
@@ -300,7 +380,7 @@ mod tests {
     fn test_reranker_empty_input() {
         let mut reranker = DummyReranker::new();
         let query = "test query";
-        let documents: Vec<String> = vec![];
+        let documents: Vec<Document> = vec![];
         let results = reranker.rerank(query, &documents).unwrap();
         assert_eq!(results.len(), 0);
     }
@@ -309,11 +389,33 @@ mod tests {
     fn test_reranker_single_document() {
         let mut reranker = DummyReranker::new();
         let query = "test query";
-        let documents = vec!["single document".to_string()];
+        let documents = [Document::new(7, "single document")];
         let results = reranker.rerank(query, &documents).unwrap();
 
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].query, query);
-        assert_eq!(results[0].document, "single document");
+        assert_eq!(results[0].id, 7);
+    }
+
+    #[test]
+    fn test_rerank_batch_keeps_queries_apart() {
+        let mut reranker = DummyReranker::new();
+        let queries = vec!["first".to_string(), "second".to_string()];
+        let docs_per_query = vec![
+            vec![Document::new(3, "a"), Document::new(4, "b")],
+            vec![Document::new(9, "c")],
+        ];
+
+        let results = reranker.rerank_batch(&queries, &docs_per_query).unwrap();
+        let ids: Vec<Vec<usize>> = results
+            .iter()
+            .map(|results| results.iter().map(|r| r.id).collect())
+            .collect();
+        assert_eq!(ids, vec![vec![3, 4], vec![9]]);
+
+        assert!(
+            reranker
+                .rerank_batch(&queries, &docs_per_query[..1])
+                .is_err()
+        );
     }
 }
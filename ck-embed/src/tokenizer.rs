@@ -15,8 +15,10 @@ impl TokenEstimator {
             return 0;
         }
 
-        // More sophisticated estimation based on content type
-        let char_count = text.chars().count();
+        // More sophisticated estimation based on content type. CJK characters
+        // are close to one token each, so they are counted separately.
+        let cjk_count = text.chars().filter(|c| Self::is_cjk(*c)).count();
+        let char_count = text.chars().count() - cjk_count;
 
         // Detect if text is primarily code vs natural language
         let code_indicators = Self::count_code_indicators(text);
@@ -35,7 +37,14 @@ impl TokenEstimator {
             4.8
         };
 
-        (char_count as f32 / chars_per_token).ceil() as usize
+        cjk_count + (char_count as f32 / chars_per_token).ceil() as usize
+    }
+
+    /// Han, kana and Hangul characters
+    fn is_cjk(c: char) -> bool {
+        matches!(c as u32,
+            0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF
+            | 0xF900..=0xFAFF | 0x20000..=0x2FA1F)
     }
 
     /// Check if text exceeds token limit for a given model
@@ -53,6 +62,8 @@ impl TokenEstimator {
             "jina-embeddings-v2-base-code" => 8192,
             "BAAI/bge-base-en-v1.5" => 512,
             "BAAI/bge-large-en-v1.5" => 512,
+            "BAAI/bge-m3" => 8192,
+            "intfloat/multilingual-e5-small" => 512,
             _ => 8192, // Default to Nomic limit
         }
     }
@@ -115,6 +126,13 @@ mod tests {
         assert!((2..=4).contains(&tokens), "Got {} tokens", tokens);
     }
 
+    #[test]
+    fn test_estimate_tokens_cjk() {
+        // Each ideograph is roughly a token, not a fifth of one
+        let tokens = TokenEstimator::estimate_tokens("重试逻辑");
+        assert_eq!(tokens, 4);
+    }
+
     #[test]
     fn test_estimate_tokens_code() {
         let code = r#"
@@ -0,0 +1,131 @@
+//! Fitting over-long inputs to a model's token limit. Providers that build
+//! their own input tensors keep the positions [`kept_positions`] picks; those
+//! that only take text (fastembed) embed [`cut_text`] instead.
+
+use std::borrow::Cow;
+
+use ck_models::Truncation;
+
+/// Positions of the tokens to feed from an encoding with `special` as its
+/// special-tokens mask. Content between the leading and trailing special tokens
+/// is cut to fit `max_length` according to `truncation`.
+pub(crate) fn kept_positions(
+    special: &[u32],
+    max_length: usize,
+    truncation: Truncation,
+) -> Vec<usize> {
+    let len = special.len();
+    if len <= max_length {
+        return (0..len).collect();
+    }
+
+    let lead = special.iter().take_while(|&&s| s != 0).count();
+    let trail = special[lead..]
+        .iter()
+        .rev()
+        .take_while(|&&s| s != 0)
+        .count();
+    let Some(budget) = max_length.checked_sub(lead + trail) else {
+        return (0..max_length).collect();
+    };
+
+    let content = lead..len - trail;
+    let kept: Vec<usize> = match truncation {
+        Truncation::Head => content.take(budget).collect(),
+        Truncation::Tail => content.skip(len - lead - trail - budget).collect(),
+        Truncation::Middle => {
+            let head = budget.div_ceil(2);
+            let tail = budget - head;
+            content
+                .clone()
+                .take(head)
+                .chain(content.skip(len - lead - trail - tail))
+                .collect()
+        }
+    };
+    (0..lead).chain(kept).chain(len - trail..len).collect()
+}
+
+/// `text` with the tokens `kept_positions` drops cut out, for providers that
+/// tokenize internally and only accept text. `special` and `offsets` come from
+/// encoding `text` without truncation; kept runs are joined with a newline.
+pub(crate) fn cut_text<'t>(
+    text: &'t str,
+    special: &[u32],
+    offsets: &[(usize, usize)],
+    max_length: usize,
+    truncation: Truncation,
+) -> Cow<'t, str> {
+    let kept = kept_positions(special, max_length, truncation);
+    if kept.len() == special.len() {
+        return Cow::Borrowed(text);
+    }
+    let content: Vec<usize> = kept.into_iter().filter(|&pos| special[pos] == 0).collect();
+    let (Some(&first), Some(&last)) = (content.first(), content.last()) else {
+        return Cow::Borrowed(text);
+    };
+
+    let mut cut = String::with_capacity(text.len());
+    let mut start = offsets[first].0;
+    for pair in content.windows(2) {
+        if pair[1] != pair[0] + 1 {
+            cut.push_str(&text[start..offsets[pair[0]].1]);
+            cut.push('\n');
+            start = offsets[pair[1]].0;
+        }
+    }
+    cut.push_str(&text[start..offsets[last].1]);
+    Cow::Owned(cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kept_positions_preserve_special_tokens() {
+        // [CLS] a b c d e f [SEP]
+        let special = [1, 0, 0, 0, 0, 0, 0, 1];
+        assert_eq!(kept_positions(&special, 10, Truncation::Head).len(), 8);
+        assert_eq!(
+            kept_positions(&special, 5, Truncation::Head),
+            [0, 1, 2, 3, 7]
+        );
+        assert_eq!(
+            kept_positions(&special, 5, Truncation::Tail),
+            [0, 4, 5, 6, 7]
+        );
+        assert_eq!(
+            kept_positions(&special, 6, Truncation::Middle),
+            [0, 1, 2, 5, 6, 7]
+        );
+        // A pair keeps its separator when the second text is cut
+        let pair = [1, 0, 0, 1, 0, 0, 0, 1];
+        assert_eq!(
+            kept_positions(&pair, 6, Truncation::Head),
+            [0, 1, 2, 3, 4, 7]
+        );
+        // Limits below the special tokens fall back to a plain cut
+        assert_eq!(kept_positions(&special, 1, Truncation::Head), [0]);
+    }
+
+    #[test]
+    fn test_cut_text_keeps_both_ends() {
+        // [CLS] alpha beta gamma delta [SEP]
+        let text = "alpha beta gamma delta";
+        let special = [1, 0, 0, 0, 0, 1];
+        let offsets = [(0, 0), (0, 5), (6, 10), (11, 16), (17, 22), (0, 0)];
+        assert_eq!(
+            cut_text(text, &special, &offsets, 4, Truncation::Middle),
+            "alpha\ndelta"
+        );
+        assert_eq!(
+            cut_text(text, &special, &offsets, 4, Truncation::Tail),
+            "gamma delta"
+        );
+        assert!(matches!(
+            cut_text(text, &special, &offsets, 6, Truncation::Middle),
+            Cow::Borrowed(_)
+        ));
+    }
+}
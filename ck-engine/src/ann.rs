@@ -0,0 +1,339 @@
+//! HNSW graph over an index's chunk embeddings, for indexes too large to scan
+//! on every query. `ck --index` builds it once the index holds
+//! [`MIN_GRAPH_CHUNKS`] embedded chunks and `index_backend` is `hnsw`. Searches
+//! only use it while it matches the index's generation, model and graph
+//! parameters; otherwise they scan every shard exactly.
+
+use anyhow::Result;
+use ck_ann::{HnswIndex, HnswParams};
+use ck_core::CkError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::semantic_v3::reconstruct_original_path;
+
+/// Graph file kept next to `manifest.json`.
+pub const ANN_INDEX_FILE: &str = "ann_index.bin";
+
+/// Below this many embedded chunks an exact scan is fast enough.
+pub const MIN_GRAPH_CHUNKS: usize = 20_000;
+
+/// Neighbors compared per sampled query when tuning.
+const TUNE_K: usize = 10;
+
+/// Sampled chunks used as tuning queries.
+const TUNE_QUERIES: usize = 200;
+
+/// `ef_search` values tried by [`tune_ann`], smallest first.
+const EF_SWEEP: [usize; 12] = [10, 16, 24, 32, 48, 64, 96, 128, 192, 256, 384, 512];
+
+#[derive(Serialize, Deserialize)]
+struct GraphFile {
+    generation: u64,
+    model: String,
+    /// Source of each graph node: file relative to the index root, chunk index
+    nodes: Vec<(PathBuf, u32)>,
+    graph: HnswIndex,
+}
+
+/// A graph that is current for its index, loaded for one search.
+pub(crate) struct AnnGraph {
+    nodes: Vec<(PathBuf, u32)>,
+    graph: HnswIndex,
+    oversample: usize,
+}
+
+impl AnnGraph {
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Candidate chunks for the best `limit` results, most similar first.
+    /// `oversample` overrides the project's candidates per result.
+    pub(crate) fn candidates(
+        &self,
+        query: &[f32],
+        limit: usize,
+        oversample: Option<usize>,
+    ) -> Result<Vec<(&Path, usize)>> {
+        let oversample = oversample.unwrap_or(self.oversample).max(1);
+        let fetch = limit.saturating_mul(oversample);
+        let params = self.graph.params();
+        Ok(self
+            .graph
+            .search_with_ef(query, fetch, params.ef_search)?
+            .into_iter()
+            .map(|(id, _)| {
+                let (path, chunk) = &self.nodes[id as usize];
+                (path.as_path(), *chunk as usize)
+            })
+            .collect())
+    }
+}
+
+fn graph_params(config: &ck_models::HnswConfig) -> HnswParams {
+    HnswParams {
+        m: config.m,
+        ef_construction: config.ef_construction,
+        ef_search: config.ef_search,
+    }
+}
+
+fn index_model(index_root: &Path) -> Option<String> {
+    let data = std::fs::read(index_root.join(".ck").join("manifest.json")).ok()?;
+    serde_json::from_slice::<ck_index::IndexManifest>(&data)
+        .ok()?
+        .embedding_model
+}
+
+fn read_graph(index_root: &Path) -> Option<GraphFile> {
+    let index_dir = index_root.join(".ck");
+    let data = std::fs::read(index_dir.join(ANN_INDEX_FILE)).ok()?;
+    bincode::deserialize(&ck_index::unseal(&index_dir, data).ok()?).ok()
+}
+
+/// Whether `file` was built from the index as it is now, with `config`'s shape.
+fn is_current(file: &GraphFile, index_root: &Path, config: &ck_models::HnswConfig) -> bool {
+    let params = file.graph.params();
+    Some(file.generation) == ck_index::read_index_generation(index_root)
+        && Some(&file.model) == index_model(index_root).as_ref()
+        && params.m == config.m.max(2)
+        && params.ef_construction == config.ef_construction.max(1)
+}
+
+/// The index's graph, if the HNSW backend is on and the graph is current.
+pub(crate) fn load_current(index_root: &Path) -> Option<AnnGraph> {
+    let project = ck_models::ProjectConfig::for_path(index_root);
+    if project.index_backend != "hnsw" {
+        return None;
+    }
+    let file = read_graph(index_root)?;
+    if !is_current(&file, index_root, &project.hnsw) {
+        tracing::debug!("HNSW graph is stale; scanning shards exactly");
+        return None;
+    }
+    let mut graph = file.graph;
+    graph.set_ef_search(project.hnsw.ef_search);
+    Some(AnnGraph {
+        nodes: file.nodes,
+        graph,
+        oversample: project.hnsw.oversample,
+    })
+}
+
+/// Build or refresh the HNSW graph for the index at `index_root`. Without
+/// `force`, nothing is built unless the `hnsw` backend is configured and the
+/// index is large enough, and a stale graph is removed. Returns the number of
+/// chunks in the graph, if there is one.
+pub fn build_ann_index(index_root: &Path, force: bool) -> Result<Option<usize>> {
+    let project = ck_models::ProjectConfig::for_path(index_root);
+    let index_dir = index_root.join(".ck");
+    let graph_path = index_dir.join(ANN_INDEX_FILE);
+
+    if let Some(file) = read_graph(index_root)
+        && is_current(&file, index_root, &project.hnsw)
+    {
+        return Ok(Some(file.graph.len()));
+    }
+    let remove_stale = || match std::fs::remove_file(&graph_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(None),
+    };
+    if !force && project.index_backend != "hnsw" {
+        return Ok(remove_stale()?);
+    }
+    // The graph ranks by angle, which only matches the exact scores of a
+    // cosine index
+    let metric = super::index_metric(index_root);
+    if metric != ck_models::SimilarityMetric::Cosine {
+        if !force {
+            return Ok(remove_stale()?);
+        }
+        return Err(CkError::Index(format!(
+            "The HNSW graph needs a cosine index, but this one uses the {} metric",
+            metric
+        ))
+        .into());
+    }
+    let (Some(model), Some(generation)) = (
+        index_model(index_root),
+        ck_index::read_index_generation(index_root),
+    ) else {
+        if !force {
+            return Ok(remove_stale()?);
+        }
+        return Err(CkError::Index(
+            "No embeddings found. Run 'ck --index' first with embeddings.".to_string(),
+        )
+        .into());
+    };
+
+    let mut nodes = Vec::new();
+    let mut vectors = Vec::new();
+    for shard in ck_index::list_shards(&index_dir)? {
+        for sidecar in shard.sidecars() {
+            let (Ok(index_entry), Some(file)) = (
+                ck_index::load_index_entry(&sidecar),
+                reconstruct_original_path(&sidecar, &index_dir, index_root),
+            ) else {
+                continue;
+            };
+            let relative = file.strip_prefix(index_root).unwrap_or(&file).to_path_buf();
+            for (chunk, entry) in index_entry.chunks.into_iter().enumerate() {
+                // Duplicates are found through their canonical chunk
+                if let Some(embedding) = entry.embedding
+                    && entry.alias_of.is_none()
+                {
+                    nodes.push((relative.clone(), chunk as u32));
+                    vectors.push(embedding);
+                }
+            }
+        }
+    }
+    if !force && vectors.len() < MIN_GRAPH_CHUNKS {
+        return Ok(remove_stale()?);
+    }
+
+    let graph = {
+        let _span = tracing::info_span!("ann_build", vectors = vectors.len()).entered();
+        HnswIndex::build_with(&vectors, graph_params(&project.hnsw))?
+    };
+    let file = GraphFile {
+        generation,
+        model,
+        nodes,
+        graph,
+    };
+    let tmp = graph_path.with_extension("bin.tmp");
+    std::fs::write(
+        &tmp,
+        ck_index::seal(&index_dir, bincode::serialize(&file)?)?,
+    )?;
+    std::fs::rename(&tmp, &graph_path)?;
+    Ok(Some(file.graph.len()))
+}
+
+/// Recall and latency of one `ef_search` value.
+#[derive(Debug, Clone, Serialize)]
+pub struct TunePoint {
+    pub ef_search: usize,
+    /// Mean fraction of the exact top 10 the graph returned
+    pub recall: f32,
+    pub micros_per_query: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TuneReport {
+    pub chunks: usize,
+    pub queries: usize,
+    pub sweep: Vec<TunePoint>,
+    /// Smallest `ef_search` reaching the target, or the largest tried
+    pub recommended: usize,
+    pub reached_target: bool,
+    pub config_path: PathBuf,
+}
+
+/// Sweep `ef_search` over sampled chunks of the index at `index_root`, using
+/// their exact nearest neighbors as ground truth, and record the smallest value
+/// reaching `recall_target` in the project config.
+pub fn tune_ann(index_root: &Path, recall_target: f32) -> Result<TuneReport> {
+    build_ann_index(index_root, true)?;
+    let graph = read_graph(index_root)
+        .ok_or_else(|| CkError::Index("The HNSW graph could not be read".to_string()))?
+        .graph;
+
+    let stride = (graph.len() / TUNE_QUERIES).max(1);
+    let samples: Vec<usize> = (0..graph.len())
+        .step_by(stride)
+        .take(TUNE_QUERIES)
+        .collect();
+    // Each sample's own node is excluded from its neighbors
+    let truths: Vec<HashSet<u32>> = samples
+        .iter()
+        .map(|&n| {
+            graph
+                .exact_search(graph.vector(n), TUNE_K + 1)
+                .into_iter()
+                .map(|(id, _)| id)
+                .filter(|&id| id != graph.id(n))
+                .take(TUNE_K)
+                .collect()
+        })
+        .collect();
+
+    let mut sweep = Vec::new();
+    for ef_search in EF_SWEEP {
+        let started = Instant::now();
+        let mut found = 0;
+        let mut expected = 0;
+        for (&n, truth) in samples.iter().zip(&truths) {
+            let hits = graph.search_with_ef(graph.vector(n), TUNE_K + 1, ef_search)?;
+            found += hits
+                .iter()
+                .filter(|(id, _)| *id != graph.id(n))
+                .take(TUNE_K)
+                .filter(|(id, _)| truth.contains(id))
+                .count();
+            expected += truth.len();
+        }
+        sweep.push(TunePoint {
+            ef_search,
+            recall: found as f32 / expected.max(1) as f32,
+            micros_per_query: started.elapsed().as_secs_f64() * 1e6 / samples.len().max(1) as f64,
+        });
+    }
+
+    let best = sweep
+        .iter()
+        .find(|point| point.recall >= recall_target)
+        .map(|point| point.ef_search);
+    let recommended = best.unwrap_or(EF_SWEEP[EF_SWEEP.len() - 1]);
+
+    let config_path = ck_models::ProjectConfig::locate(index_root);
+    let mut config = ck_models::ProjectConfig::load(&config_path)?;
+    config.hnsw.ef_search = recommended;
+    config.save(&config_path)?;
+
+    Ok(TuneReport {
+        chunks: graph.len(),
+        queries: samples.len(),
+        sweep,
+        recommended,
+        reached_target: best.is_some(),
+        config_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_graph_needs_an_embedded_index() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(build_ann_index(temp_dir.path(), false).unwrap().is_none());
+        assert!(tune_ann(temp_dir.path(), 0.95).is_err());
+        assert!(load_current(temp_dir.path()).is_none());
+        assert!(!temp_dir.path().join(".ckconfig.json").exists());
+    }
+
+    #[test]
+    fn test_candidates_oversample_the_limit() {
+        let vectors: Vec<Vec<f32>> = (0..50)
+            .map(|i| vec![1.0, i as f32 / 50.0, (i % 7) as f32])
+            .collect();
+        let graph = AnnGraph {
+            nodes: (0..50).map(|i| (PathBuf::from("a.rs"), i)).collect(),
+            graph: HnswIndex::build_with(&vectors, HnswParams::default()).unwrap(),
+            oversample: 4,
+        };
+        let query = [1.0, 0.5, 3.0];
+        assert_eq!(graph.candidates(&query, 5, None).unwrap().len(), 20);
+        assert_eq!(graph.candidates(&query, 5, Some(2)).unwrap().len(), 10);
+        // Zero would fetch nothing, so at least the limit is always fetched
+        assert_eq!(graph.candidates(&query, 5, Some(0)).unwrap().len(), 5);
+    }
+}
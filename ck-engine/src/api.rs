@@ -0,0 +1,326 @@
+//! Library facade for embedding ck in other Rust tools without spawning the CLI.
+//!
+//! ```no_run
+//! use ck_engine::{CkIndex, Query};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let index = CkIndex::open("path/to/repo")?;
+//! for hit in index.search(Query::semantic("retry with backoff").top_k(5)).await? {
+//!     println!("{}:{} {:.3}", hit.path.display(), hit.span.line_start, hit.score);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Searches create or refresh the `.ck` index as needed, exactly like the CLI.
+//! A [`CancellationToken`] stops an update or search from another task; a
+//! cancelled update keeps the files it finished, so the next one resumes.
+
+use anyhow::Result;
+use ck_core::{
+    CkError, DuplicateLocation, FileCollectionOptions, Language, SearchMode, SearchOptions,
+    SearchResult, Span,
+};
+use std::path::{Path, PathBuf};
+
+use super::ChunkVector;
+
+pub use ck_core::CancellationToken;
+
+/// Handle to a directory tree searchable with ck.
+#[derive(Debug, Clone)]
+pub struct CkIndex {
+    root: PathBuf,
+}
+
+impl CkIndex {
+    /// Open the tree rooted at `root`. The index itself is created lazily by the
+    /// first search or [`CkIndex::update`].
+    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref();
+        let root = root
+            .canonicalize()
+            .map(|root| ck_core::paths::strip_verbatim(&root))
+            .map_err(|e| CkError::Index(format!("Cannot open {}: {}", root.display(), e)))?;
+        if !root.is_dir() {
+            return Err(CkError::Index(format!("{} is not a directory", root.display())).into());
+        }
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Bring the index up to date with the tree, computing embeddings when
+    /// `embeddings` is set.
+    pub async fn update(&self, embeddings: bool) -> Result<()> {
+        self.update_cancellable(embeddings, &CancellationToken::new())
+            .await
+    }
+
+    /// [`CkIndex::update`] that stops with [`CkError::Cancelled`] once `cancel`
+    /// is cancelled, keeping the files indexed so far.
+    pub async fn update_cancellable(
+        &self,
+        embeddings: bool,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        let file_options = FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: ck_core::get_default_exclude_patterns(),
+            follow_symlinks: false,
+            files_from: None,
+        };
+        super::ensure_index_updated_with_progress(
+            &self.root,
+            false,
+            embeddings,
+            None,
+            None,
+            &file_options,
+            None,
+            Some(cancel),
+        )
+        .await
+    }
+
+    pub fn stats(&self) -> Result<ck_index::IndexStats> {
+        Ok(ck_index::get_index_stats(&self.root)?)
+    }
+
+    /// The embedding of the chunk `id` names (`src/net.rs:12-40`, as
+    /// [`Hit::chunk_id`] gives it, or `src/net.rs:12` for the chunk spanning
+    /// line 12), or `None` when no embedded chunk matches.
+    pub fn get_embedding(&self, id: &str) -> Result<Option<ChunkVector>> {
+        super::get_embedding(&self.root, id)
+    }
+
+    /// Every embedded chunk with its vector, ordered by path and line, for
+    /// clustering or visualization outside ck.
+    pub fn vectors(&self) -> Result<Vec<ChunkVector>> {
+        super::export_vectors(&self.root)
+    }
+
+    pub async fn search(&self, query: Query) -> Result<Vec<Hit>> {
+        let path = match &query.scope {
+            Some(scope) => self.root.join(scope),
+            None => self.root.clone(),
+        };
+        let top_k = query.top_k.or(match query.mode {
+            SearchMode::Semantic | SearchMode::Symbol => Some(10),
+            _ => None,
+        });
+        let options = SearchOptions {
+            mode: query.mode,
+            query: query.text,
+            path,
+            top_k,
+            threshold: query.threshold,
+            line_numbers: true,
+            rerank: query.rerank,
+            languages: query.languages,
+            instruction: query.instruction,
+            cancel: query.cancel,
+            ..Default::default()
+        };
+        let results = super::search_enhanced(&options).await?;
+        Ok(results.matches.into_iter().map(Hit::from).collect())
+    }
+}
+
+/// What to search for; build with one of the mode constructors and refine with
+/// the chained setters.
+#[derive(Debug, Clone)]
+pub struct Query {
+    text: String,
+    mode: SearchMode,
+    top_k: Option<usize>,
+    threshold: Option<f32>,
+    languages: Vec<Language>,
+    scope: Option<PathBuf>,
+    rerank: bool,
+    instruction: Option<String>,
+    cancel: Option<CancellationToken>,
+}
+
+impl Query {
+    fn new(mode: SearchMode, text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            mode,
+            top_k: None,
+            threshold: None,
+            languages: Vec::new(),
+            scope: None,
+            rerank: false,
+            instruction: None,
+            cancel: None,
+        }
+    }
+
+    /// Search by meaning using the index's embeddings
+    pub fn semantic(text: impl Into<String>) -> Self {
+        Self::new(SearchMode::Semantic, text)
+    }
+
+    /// BM25 full-text search
+    pub fn lexical(text: impl Into<String>) -> Self {
+        Self::new(SearchMode::Lexical, text)
+    }
+
+    /// Regex and semantic results fused with reciprocal rank fusion
+    pub fn hybrid(text: impl Into<String>) -> Self {
+        Self::new(SearchMode::Hybrid, text)
+    }
+
+    /// Regular expression over file contents; needs no index
+    pub fn regex(pattern: impl Into<String>) -> Self {
+        Self::new(SearchMode::Regex, pattern)
+    }
+
+    /// Definition lookup by name
+    pub fn symbol(name: impl Into<String>) -> Self {
+        Self::new(SearchMode::Symbol, name)
+    }
+
+    /// Maximum number of hits (semantic and symbol queries default to 10)
+    pub fn top_k(mut self, top_k: usize) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    /// Minimum score for a hit
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Only return hits in `language`; may be called more than once
+    pub fn language(mut self, language: Language) -> Self {
+        self.languages.push(language);
+        self
+    }
+
+    /// Restrict the search to a file or directory relative to the index root
+    pub fn scope(mut self, path: impl Into<PathBuf>) -> Self {
+        self.scope = Some(path.into());
+        self
+    }
+
+    /// Rerank semantic hits with the default cross-encoder
+    pub fn rerank(mut self, rerank: bool) -> Self {
+        self.rerank = rerank;
+        self
+    }
+
+    /// Embed the query after `instruction` instead of the model's default
+    /// query instruction, for instruction-tuned models; `""` embeds it bare
+    pub fn instruct(mut self, instruction: impl Into<String>) -> Self {
+        self.instruction = Some(instruction.into());
+        self
+    }
+
+    /// Stop the search, and any index update it needs, with
+    /// [`CkError::Cancelled`] once `cancel` is cancelled
+    pub fn cancel_on(mut self, cancel: &CancellationToken) -> Self {
+        self.cancel = Some(cancel.clone());
+        self
+    }
+}
+
+/// One search hit.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Hit {
+    pub path: PathBuf,
+    pub span: Span,
+    pub score: f32,
+    /// First lines of the matching chunk (the matching line for regex queries)
+    pub snippet: String,
+    pub language: Option<Language>,
+    /// Qualified definition name, for symbol queries
+    pub symbol: Option<String>,
+    /// Near-identical copies of the hit elsewhere in the tree
+    pub duplicates: Vec<DuplicateLocation>,
+}
+
+impl Hit {
+    /// Id of the hit's chunk, for [`CkIndex::get_embedding`]
+    pub fn chunk_id(&self) -> String {
+        super::chunk_id(&self.path, &self.span)
+    }
+}
+
+impl From<SearchResult> for Hit {
+    fn from(result: SearchResult) -> Self {
+        Self {
+            path: result.file,
+            span: result.span,
+            score: result.score,
+            snippet: result.preview,
+            language: result.lang,
+            symbol: result.symbol,
+            duplicates: result.duplicates,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_regex_query_through_facade() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        std::fs::write(temp_dir.path().join("src/lib.rs"), "// TODO: rust\n").unwrap();
+        std::fs::write(temp_dir.path().join("notes.py"), "# TODO: python\n").unwrap();
+
+        let index = CkIndex::open(temp_dir.path()).unwrap();
+        let hits = index
+            .search(Query::regex("TODO").language(Language::Rust))
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].path.ends_with("src/lib.rs"));
+        assert_eq!(hits[0].span.line_start, 1);
+
+        let scoped = index
+            .search(Query::regex("TODO").scope("notes.py"))
+            .await
+            .unwrap();
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(scoped[0].language, Some(Language::Python));
+
+        assert!(CkIndex::open(temp_dir.path().join("missing")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_query_and_update_stop() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("lib.rs"), "fn retry() {}\n").unwrap();
+        let index = CkIndex::open(temp_dir.path()).unwrap();
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let err = index
+            .search(Query::lexical("retry").cancel_on(&cancel))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CkError>(),
+            Some(CkError::Cancelled)
+        ));
+        let err = index.update_cancellable(false, &cancel).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CkError>(),
+            Some(CkError::Cancelled)
+        ));
+
+        let hits = index.search(Query::lexical("retry")).await.unwrap();
+        assert_eq!(hits.len(), 1);
+    }
+}
@@ -0,0 +1,353 @@
+//! `ck --ask`: answer a question from the code a search retrieves, and
+//! `ck --context-bundle`: the same excerpts cut to a token budget.
+//!
+//! The top hits become an [`AnswerContext`]: hits in the same file are merged
+//! into one excerpt when they overlap or nearly touch, and excerpts whose text
+//! repeats an earlier one are dropped. With an LLM configured under `llm` in
+//! the user's `config.json` (and the `remote` feature), the model answers from
+//! those excerpts with `path:line` citations; without one the context itself,
+//! as Markdown, is the output, ready to paste into a chat.
+
+use anyhow::Result;
+use ck_core::{Language, SearchResult};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Hits in one file at most this many lines apart are merged into one excerpt.
+const MERGE_GAP: usize = 3;
+
+const SYSTEM_PROMPT: &str = "You answer questions about a codebase using only the source \
+excerpts provided. Cite the excerpt behind every claim as [path:start-end], using the ranges \
+given in the excerpt headings. If the excerpts do not answer the question, say so instead of \
+guessing.";
+
+/// One excerpt of the answer context: consecutive lines of a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextExcerpt {
+    pub file: PathBuf,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub language: Option<Language>,
+    pub text: String,
+}
+
+impl ContextExcerpt {
+    /// `path:start-end`, the form answers cite excerpts by.
+    pub fn citation(&self) -> String {
+        let path = self.file.to_string_lossy();
+        let path = path.strip_prefix("./").unwrap_or(&path);
+        if self.line_start == self.line_end {
+            format!("{}:{}", path, self.line_start)
+        } else {
+            format!("{}:{}-{}", path, self.line_start, self.line_end)
+        }
+    }
+}
+
+/// The question and the excerpts retrieved for it, best hit first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnswerContext {
+    pub question: String,
+    pub excerpts: Vec<ContextExcerpt>,
+}
+
+impl AnswerContext {
+    /// Approximate tokens of the Markdown rendering.
+    pub fn estimated_tokens(&self) -> usize {
+        ck_embed::TokenEstimator::estimate_tokens(&self.to_string())
+    }
+}
+
+impl fmt::Display for AnswerContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Context for: {}", self.question)?;
+        for excerpt in &self.excerpts {
+            let fence = excerpt
+                .language
+                .filter(|l| *l != Language::Pdf)
+                .map(|l| l.to_string())
+                .unwrap_or_default();
+            writeln!(f)?;
+            writeln!(f, "## {}", excerpt.citation())?;
+            writeln!(f, "```{}", fence)?;
+            writeln!(f, "{}", excerpt.text.trim_end())?;
+            writeln!(f, "```")?;
+        }
+        Ok(())
+    }
+}
+
+/// Spans of one file's hits, merged where they overlap or nearly touch.
+fn merge_spans(mut spans: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    spans.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + MERGE_GAP + 1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// File contents read while stitching, so trying several selections of hits
+/// reads each file once; `None` for files that could not be read.
+#[derive(Default)]
+struct FileContents(HashMap<PathBuf, Option<String>>);
+
+impl FileContents {
+    fn get(&mut self, file: &Path) -> Option<&str> {
+        self.0
+            .entry(file.to_path_buf())
+            .or_insert_with(|| {
+                let repo_root = super::find_nearest_index_root(file)
+                    .unwrap_or_else(|| file.parent().unwrap_or(file).to_path_buf());
+                let mut content = super::read_file_content(file, &repo_root).ok()?;
+                // Context goes to the chat model, so it is redacted like previews;
+                // a file whose rules do not compile is left out
+                let redaction = ck_models::ProjectConfig::for_path(&repo_root).redaction;
+                if let Some(redactor) = redaction.redactor().ok()? {
+                    redactor.redact_in_place(&mut content);
+                }
+                Some(content)
+            })
+            .as_deref()
+    }
+}
+
+fn stitch(question: &str, results: &[&SearchResult], contents: &mut FileContents) -> AnswerContext {
+    let mut files: Vec<&Path> = Vec::new();
+    for result in results {
+        if !files.contains(&result.file.as_path()) {
+            files.push(&result.file);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut excerpts = Vec::new();
+    for file in files {
+        let hits: Vec<&SearchResult> = results.iter().copied().filter(|r| r.file == file).collect();
+        let spans = merge_spans(
+            hits.iter()
+                .map(|r| (r.span.line_start, r.span.line_end.max(r.span.line_start)))
+                .collect(),
+        );
+        let lines: Vec<&str> = contents
+            .get(file)
+            .map(|c| c.lines().collect())
+            .unwrap_or_default();
+
+        for (start, end) in spans {
+            let text = if lines.is_empty() {
+                // Unreadable now (deleted, or a PDF never indexed): use the previews
+                hits.iter()
+                    .filter(|r| r.span.line_start >= start && r.span.line_start <= end)
+                    .map(|r| r.preview.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            } else {
+                let from = start.max(1) - 1;
+                lines
+                    .get(from.min(lines.len())..end.min(lines.len()))
+                    .unwrap_or_default()
+                    .join("\n")
+            };
+            if text.trim().is_empty() || !seen.insert(text.trim().to_string()) {
+                continue;
+            }
+            excerpts.push(ContextExcerpt {
+                file: file.to_path_buf(),
+                line_start: start,
+                line_end: end,
+                language: hits.iter().find_map(|r| r.lang),
+                text,
+            });
+        }
+    }
+
+    AnswerContext {
+        question: question.to_string(),
+        excerpts,
+    }
+}
+
+/// Stitch `results` (ranked) into excerpts. Files keep the order of their best
+/// hit; excerpts within a file follow the file.
+pub fn build_answer_context(question: &str, results: &[SearchResult]) -> AnswerContext {
+    let results: Vec<&SearchResult> = results.iter().collect();
+    stitch(question, &results, &mut FileContents::default())
+}
+
+/// [`build_answer_context`] limited to what fits in `budget` tokens, as
+/// estimated for the Markdown bundle. Every file's best hit is tried before any
+/// file's second, so the bundle covers more files before it goes deeper into
+/// one; hits too large for what is left are skipped in favour of smaller ones,
+/// and hits whose chunk repeats one already taken are dropped.
+pub fn build_budgeted_context(
+    question: &str,
+    results: &[SearchResult],
+    budget: usize,
+) -> AnswerContext {
+    let mut order: Vec<usize> = Vec::with_capacity(results.len());
+    let mut later = Vec::new();
+    for (rank, result) in results.iter().enumerate() {
+        if results[..rank].iter().any(|r| r.file == result.file) {
+            later.push(rank);
+        } else {
+            order.push(rank);
+        }
+    }
+    order.extend(later);
+
+    let mut contents = FileContents::default();
+    let mut chosen: Vec<usize> = Vec::new();
+    let mut hashes = HashSet::new();
+    let mut context = stitch(question, &[], &mut contents);
+    for rank in order {
+        let result = &results[rank];
+        if result
+            .chunk_hash
+            .as_ref()
+            .is_some_and(|hash| hashes.contains(hash))
+        {
+            continue;
+        }
+        let at = chosen.partition_point(|&r| r < rank);
+        chosen.insert(at, rank);
+        let selection: Vec<&SearchResult> = chosen.iter().map(|&r| &results[r]).collect();
+        let candidate = stitch(question, &selection, &mut contents);
+        if candidate.estimated_tokens() <= budget {
+            hashes.extend(result.chunk_hash.clone());
+            context = candidate;
+        } else {
+            chosen.remove(at);
+        }
+    }
+    context
+}
+
+/// Whether `--ask` at `path` can synthesize an answer: an LLM is configured
+/// and this build can reach it.
+pub fn answer_llm_available(path: &Path) -> bool {
+    cfg!(feature = "remote")
+        && ck_models::ProjectConfig::for_path(path)
+            .trusted()
+            .llm
+            .is_configured()
+}
+
+/// Have the LLM configured for `path` answer `context.question` from the
+/// excerpts, passing the answer to `on_token` as it streams in.
+pub fn synthesize_answer(
+    path: &Path,
+    context: &AnswerContext,
+    on_token: &mut dyn FnMut(&str),
+) -> Result<String> {
+    let config = super::llm::config_for(path, "--ask")?;
+    let prompt = format!("{}\nQuestion: {}", context, context.question);
+    super::llm::complete(&config, SYSTEM_PROMPT, &prompt, Some(on_token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::Span;
+    use tempfile::TempDir;
+
+    fn hit(file: &Path, line_start: usize, line_end: usize) -> SearchResult {
+        SearchResult {
+            lang: Some(Language::Rust),
+            ..SearchResult::new(
+                file,
+                Span::new_unchecked(0, 0, line_start, line_end),
+                0.8,
+                "",
+            )
+        }
+    }
+
+    #[test]
+    fn test_merge_spans_joins_nearby_hits() {
+        assert_eq!(
+            merge_spans(vec![(20, 30), (1, 5), (8, 12), (25, 40), (60, 61)]),
+            vec![(1, 12), (20, 40), (60, 61)]
+        );
+    }
+
+    #[test]
+    fn test_context_is_stitched_and_deduplicated() {
+        let temp_dir = TempDir::new().unwrap();
+        let body: String = (1..=30).map(|i| format!("line {i}\n")).collect();
+        let retry = temp_dir.path().join("retry.rs");
+        let copy = temp_dir.path().join("copy.rs");
+        std::fs::write(&retry, &body).unwrap();
+        std::fs::write(&copy, &body).unwrap();
+
+        let results = vec![
+            hit(&retry, 10, 12),
+            hit(&copy, 10, 12),
+            hit(&retry, 2, 3),
+            hit(&retry, 13, 14),
+        ];
+        let context = build_answer_context("how are retries scheduled", &results);
+        let spans: Vec<_> = context
+            .excerpts
+            .iter()
+            .map(|e| (e.file.as_path(), e.line_start, e.line_end))
+            .collect();
+        // The copy repeats retry.rs:10-14 only in part, so it stays
+        assert_eq!(
+            spans,
+            vec![
+                (retry.as_path(), 2, 3),
+                (retry.as_path(), 10, 14),
+                (copy.as_path(), 10, 12)
+            ]
+        );
+        assert_eq!(
+            context.excerpts[1].text,
+            "line 10\nline 11\nline 12\nline 13\nline 14"
+        );
+
+        // An identical excerpt elsewhere is dropped
+        let context = build_answer_context("q", &[hit(&retry, 10, 12), hit(&copy, 10, 12)]);
+        assert_eq!(context.excerpts.len(), 1);
+
+        let markdown = context.to_string();
+        assert!(markdown.starts_with("# Context for: q\n"));
+        assert!(markdown.contains("retry.rs:10-12\n```rust\nline 10\n"));
+    }
+
+    #[test]
+    fn test_budget_spreads_over_files_first() {
+        let temp_dir = TempDir::new().unwrap();
+        let retry = temp_dir.path().join("retry.rs");
+        let backoff = temp_dir.path().join("backoff.rs");
+        let body = |name: &str| -> String { (1..=30).map(|i| format!("{name} {i}\n")).collect() };
+        std::fs::write(&retry, body("retry")).unwrap();
+        std::fs::write(&backoff, body("backoff")).unwrap();
+
+        let mut copy = hit(&backoff, 20, 21);
+        copy.chunk_hash = Some("same".to_string());
+        let mut original = hit(&retry, 1, 2);
+        original.chunk_hash = Some("same".to_string());
+        let results = vec![original, hit(&retry, 20, 22), hit(&backoff, 1, 2), copy];
+
+        let expected = build_answer_context("q", &[results[0].clone(), results[2].clone()]);
+        let context = build_budgeted_context("q", &results, expected.estimated_tokens());
+        assert_eq!(context, expected);
+
+        // A bigger budget takes the rest, except the repeated chunk
+        let context = build_budgeted_context("q", &results, 10_000);
+        assert_eq!(context.excerpts.len(), 3);
+        assert!(
+            context
+                .excerpts
+                .iter()
+                .all(|e| e.line_start != 20 || e.file == retry)
+        );
+
+        assert!(build_budgeted_context("q", &results, 5).excerpts.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! Git blame for results: who wrote a hit's lines and when the newest of them
+//! changed, for `--blame`, `--owner` and owner boosts.
+//!
+//! Each file with results is blamed once through libgit2, against its
+//! working-tree content so that line numbers match the hits, and every
+//! result in it reads its lines from that blame. Lines not committed yet are
+//! credited to "Not Committed Yet", as `git blame` does. Results outside a git
+//! repository, in untracked files, or in PDFs (whose lines come from
+//! extracted text) stay unannotated.
+
+use ck_core::{BlameAuthor, BlameInfo, SearchResult};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Annotate every result that has no blame yet, blaming files in parallel.
+pub fn annotate_blame(results: &mut [SearchResult]) {
+    let mut by_file: HashMap<PathBuf, Vec<&mut SearchResult>> = HashMap::new();
+    for result in results.iter_mut().filter(|result| result.blame.is_none()) {
+        by_file.entry(result.file.clone()).or_default().push(result);
+    }
+    by_file.into_par_iter().for_each(|(file, results)| {
+        let Some(blame) = FileBlame::load(&file) else {
+            return;
+        };
+        for result in results {
+            result.blame = blame.span(result.span.line_start, result.span.line_end);
+        }
+    });
+}
+
+/// Lines of a file last changed by one commit.
+struct Hunk {
+    /// First line, 1-based
+    start: usize,
+    lines: usize,
+    commit: String,
+    author: String,
+    email: String,
+    time: u64,
+}
+
+/// The blame of one file, as hunks in line order.
+struct FileBlame {
+    hunks: Vec<Hunk>,
+}
+
+impl FileBlame {
+    fn load(file: &Path) -> Option<Self> {
+        if ck_core::pdf::is_pdf_file(file) {
+            return None;
+        }
+        let dir = file.parent().filter(|dir| !dir.as_os_str().is_empty());
+        let repo = git2::Repository::discover(dir.unwrap_or(Path::new("."))).ok()?;
+        let workdir = ck_core::paths::canonicalize(repo.workdir()?);
+        let file = ck_core::paths::canonicalize(file);
+        let relative = file.strip_prefix(&workdir).ok()?;
+        let content = std::fs::read(&file).ok()?;
+        let blamed = repo
+            .blame_file(relative, None)
+            .and_then(|committed| {
+                let current = committed.blame_buffer(&content)?;
+                Ok(current.iter().map(|hunk| Hunk::from_git(&hunk)).collect())
+            })
+            .map_err(|e| tracing::debug!("git blame failed for {:?}: {}", file, e))
+            .ok()?;
+        Some(Self { hunks: blamed })
+    }
+
+    /// The newest commit among lines `line_start..=line_end` and their
+    /// authors by line count.
+    fn span(&self, line_start: usize, line_end: usize) -> Option<BlameInfo> {
+        if line_start == 0 {
+            return None;
+        }
+        let line_end = line_end.max(line_start);
+        let mut newest: Option<&Hunk> = None;
+        let mut authors: Vec<BlameAuthor> = Vec::new();
+        for hunk in &self.hunks {
+            let lines = (hunk.start + hunk.lines)
+                .min(line_end + 1)
+                .saturating_sub(hunk.start.max(line_start));
+            if lines == 0 {
+                continue;
+            }
+            if newest.is_none_or(|newest| hunk.time > newest.time) {
+                newest = Some(hunk);
+            }
+            match authors
+                .iter_mut()
+                .find(|author| author.name == hunk.author && author.email == hunk.email)
+            {
+                Some(author) => author.lines += lines,
+                None => authors.push(BlameAuthor {
+                    name: hunk.author.clone(),
+                    email: hunk.email.clone(),
+                    lines,
+                }),
+            }
+        }
+        authors.sort_by_key(|author| std::cmp::Reverse(author.lines));
+        let newest = newest?;
+        Some(BlameInfo {
+            author: newest.author.clone(),
+            email: newest.email.clone(),
+            commit: newest.commit.clone(),
+            time: newest.time,
+            authors,
+        })
+    }
+}
+
+impl Hunk {
+    fn from_git(hunk: &git2::BlameHunk<'_>) -> Self {
+        let commit = hunk.final_commit_id();
+        let (author, email, time) = if commit.is_zero() {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            (
+                "Not Committed Yet".to_string(),
+                "not.committed.yet".to_string(),
+                now,
+            )
+        } else {
+            let signature = hunk.final_signature();
+            (
+                String::from_utf8_lossy(signature.name_bytes()).into_owned(),
+                String::from_utf8_lossy(signature.email_bytes()).into_owned(),
+                signature.when().seconds().max(0) as u64,
+            )
+        };
+        Self {
+            start: hunk.final_start_line(),
+            lines: hunk.lines_in_hunk(),
+            commit: commit.to_string(),
+            author,
+            email,
+            time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit(repo: &git2::Repository, author: &str, time: i64, content: &str) -> git2::Oid {
+        std::fs::write(repo.workdir().unwrap().join("session.rs"), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("session.rs")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let email = format!("{}@example.com", author.to_lowercase());
+        let signature = git2::Signature::new(author, &email, &git2::Time::new(time, 0)).unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            author,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_blame_finds_newest_commit_and_line_owners() {
+        let dir = TempDir::new().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        commit(
+            &repo,
+            "Ada",
+            1000,
+            "fn expire() {\n    check();\n    old();\n}\n",
+        );
+        let newest = commit(
+            &repo,
+            "Grace",
+            2000,
+            "fn expire() {\n    check();\n    new();\n}\n",
+        );
+        std::fs::write(
+            dir.path().join("session.rs"),
+            "fn expire() {\n    check();\n    new();\n    log();\n}\n",
+        )
+        .unwrap();
+
+        let blame = FileBlame::load(&dir.path().join("session.rs")).unwrap();
+        let span = blame.span(1, 3).unwrap();
+        assert_eq!(span.author, "Grace");
+        assert_eq!(span.commit, newest.to_string());
+        assert_eq!(span.time, 2000);
+        let owners: Vec<(&str, usize)> = span
+            .authors
+            .iter()
+            .map(|author| (author.name.as_str(), author.lines))
+            .collect();
+        assert_eq!(owners, vec![("Ada", 2), ("Grace", 1)]);
+        assert!(span.owned_by("ADA"));
+        assert!(span.owned_by("grace@example"));
+        assert!(!span.owned_by("linus"));
+
+        // A working-tree edit is blamed where it is, not on a shifted line
+        assert_eq!(blame.span(4, 4).unwrap().author, "Not Committed Yet");
+        assert_eq!(blame.span(5, 5).unwrap().author, "Ada");
+        assert!(blame.span(0, 0).is_none());
+    }
+
+    #[test]
+    fn test_untracked_file_has_no_blame() {
+        let dir = TempDir::new().unwrap();
+        git2::Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "draft\n").unwrap();
+        assert!(FileBlame::load(&dir.path().join("notes.txt")).is_none());
+    }
+}
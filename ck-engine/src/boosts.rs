@@ -0,0 +1,225 @@
+//! Post-retrieval ranking boosts from the project's `.ckconfig.json`: glob-based
+//! path weights (down-weight tests and vendored code), a recency bonus, and
+//! weights for results whose git blame names a given owner.
+
+use ck_core::{ScoreBoost, ScoreExplanation, SearchResult};
+use ck_models::RankingBoosts;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
+use std::time::SystemTime;
+
+const SECONDS_PER_DAY: f32 = 86_400.0;
+
+/// Multiply each result's score by the configured boosts, record what applied in
+/// `explain`, and re-sort by the adjusted score. Paths are matched relative to
+/// `project_root`.
+pub fn apply_boosts(
+    results: &mut [SearchResult],
+    boosts: &RankingBoosts,
+    project_root: &Path,
+    now: SystemTime,
+) {
+    if !is_active(boosts) {
+        return;
+    }
+    let rules = compile_rules(boosts);
+    let recency_enabled = recency_enabled(boosts);
+    let root = super::canonicalize_for_matching(project_root);
+
+    for result in results.iter_mut() {
+        let mut applied = Vec::new();
+
+        if let Some(set) = &rules {
+            let file = super::canonicalize_for_matching(&result.file);
+            let relative = file.strip_prefix(&root).unwrap_or(&file);
+            for index in set.matches(relative) {
+                let rule = &boosts.paths[index];
+                applied.push(ScoreBoost {
+                    reason: format!("path:{}", rule.glob),
+                    factor: rule.weight,
+                });
+            }
+        }
+
+        if recency_enabled && let Some(age_days) = file_age_days(&result.file, now) {
+            let decay = 0.5f32.powf(age_days / boosts.recency_half_life_days);
+            applied.push(ScoreBoost {
+                reason: "recency".to_string(),
+                factor: 1.0 + boosts.recency_weight * decay,
+            });
+        }
+
+        if let Some(blame) = &result.blame {
+            for rule in &boosts.owners {
+                if blame.owned_by(&rule.owner) {
+                    applied.push(ScoreBoost {
+                        reason: format!("owner:{}", rule.owner),
+                        factor: rule.weight,
+                    });
+                }
+            }
+        }
+
+        if applied.is_empty() {
+            continue;
+        }
+        let explanation = result
+            .explain
+            .get_or_insert_with(|| ScoreExplanation::new("", result.score));
+        for boost in applied {
+            result.score *= boost.factor;
+            explanation.boosts.push(boost);
+        }
+    }
+
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+/// Glob set whose match indices line up with `boosts.paths`; `None` when there
+/// are no usable rules.
+/// Whether `boosts` can change any score.
+pub(crate) fn is_active(boosts: &RankingBoosts) -> bool {
+    !boosts.paths.is_empty() || recency_enabled(boosts) || !boosts.owners.is_empty()
+}
+
+fn recency_enabled(boosts: &RankingBoosts) -> bool {
+    boosts.recency_weight > 0.0 && boosts.recency_half_life_days > 0.0
+}
+
+fn compile_rules(boosts: &RankingBoosts) -> Option<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    let mut any = false;
+    for rule in &boosts.paths {
+        match Glob::new(&rule.glob) {
+            Ok(glob) => {
+                builder.add(glob);
+                any = true;
+            }
+            Err(e) => {
+                tracing::warn!("Ignoring invalid boost glob '{}': {}", rule.glob, e);
+                // Keep indices aligned with `boosts.paths`
+                builder.add(Glob::new("").expect("empty glob is valid"));
+            }
+        }
+    }
+    if !any {
+        return None;
+    }
+    builder.build().ok()
+}
+
+fn file_age_days(path: &Path, now: SystemTime) -> Option<f32> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let age = now.duration_since(modified).unwrap_or_default();
+    Some(age.as_secs_f32() / SECONDS_PER_DAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_models::{OwnerBoost, PathBoost};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn result(file: PathBuf, score: f32) -> SearchResult {
+        SearchResult::new(file, ck_core::Span::new_unchecked(0, 1, 1, 1), score, "")
+    }
+
+    #[test]
+    fn test_path_boosts_reorder_and_explain() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("vendor/lib")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("vendor/lib/auth.rs"), "").unwrap();
+        std::fs::write(root.join("src/auth.rs"), "").unwrap();
+
+        let mut results = vec![
+            result(root.join("vendor/lib/auth.rs"), 0.9),
+            result(root.join("src/auth.rs"), 0.8),
+        ];
+        apply_boosts(
+            &mut results,
+            &RankingBoosts::default(),
+            root,
+            SystemTime::now(),
+        );
+
+        assert!(results[0].file.ends_with("src/auth.rs"));
+        assert!(results[0].explain.is_none());
+        let vendored = results[1].explain.as_ref().unwrap();
+        assert_eq!(vendored.base_score, 0.9);
+        assert_eq!(vendored.boosts[0].reason, "path:**/vendor/**");
+        assert!((results[1].score - 0.63).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_recency_boost_decays_with_age() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("fresh.rs");
+        std::fs::write(&file, "").unwrap();
+        let boosts = RankingBoosts {
+            recency_weight: 0.5,
+            recency_half_life_days: 10.0,
+            paths: vec![PathBoost {
+                glob: "[".to_string(),
+                weight: 0.1,
+            }],
+            owners: Vec::new(),
+        };
+
+        let mut fresh = vec![result(file.clone(), 1.0)];
+        apply_boosts(&mut fresh, &boosts, temp_dir.path(), SystemTime::now());
+        assert!((fresh[0].score - 1.5).abs() < 0.01);
+
+        let later = SystemTime::now() + Duration::from_secs(10 * 86_400);
+        let mut aged = vec![result(file, 1.0)];
+        apply_boosts(&mut aged, &boosts, temp_dir.path(), later);
+        assert!((aged[0].score - 1.25).abs() < 0.01);
+        assert_eq!(aged[0].explain.as_ref().unwrap().boosts.len(), 1);
+    }
+
+    #[test]
+    fn test_owner_boost_uses_blame_authors() {
+        let root = Path::new("/project");
+        let owned = |name: &str| ck_core::BlameInfo {
+            author: name.to_string(),
+            email: format!("{}@example.com", name.to_lowercase()),
+            commit: String::new(),
+            time: 0,
+            authors: vec![ck_core::BlameAuthor {
+                name: name.to_string(),
+                email: format!("{}@example.com", name.to_lowercase()),
+                lines: 3,
+            }],
+        };
+        let mut ada = result(root.join("src/session.rs"), 0.5);
+        ada.blame = Some(owned("Ada"));
+        let mut grace = result(root.join("src/expiry.rs"), 0.6);
+        grace.blame = Some(owned("Grace"));
+        let boosts = RankingBoosts {
+            paths: Vec::new(),
+            owners: vec![OwnerBoost {
+                owner: "ada".to_string(),
+                weight: 1.5,
+            }],
+            ..RankingBoosts::default()
+        };
+
+        let mut results = vec![grace, ada];
+        apply_boosts(&mut results, &boosts, root, SystemTime::now());
+
+        assert!(results[0].file.ends_with("src/session.rs"));
+        assert!((results[0].score - 0.75).abs() < 1e-6);
+        assert_eq!(
+            results[0].explain.as_ref().unwrap().boosts[0].reason,
+            "owner:ada"
+        );
+        assert!(results[1].explain.is_none());
+    }
+}
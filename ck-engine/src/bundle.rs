@@ -0,0 +1,111 @@
+//! Export an index's embeddings as a [`SearchBundle`] that query-only hosts (the
+//! `ck-wasm` browser build) can load without the tree, the sidecars or a model.
+
+use anyhow::Result;
+use ck_ann::{BundleChunk, SearchBundle};
+use ck_core::CkError;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use super::semantic_v3::reconstruct_original_path;
+
+/// Lines of chunk text kept as the hit preview, matching semantic search output.
+const SNIPPET_LINES: usize = 3;
+
+/// Collect every embedded chunk of the index covering `path`. Paths in the bundle
+/// are relative to the index root; duplicate chunks are stored once.
+pub fn export_bundle(path: &Path) -> Result<SearchBundle> {
+    let index_root = super::find_nearest_index_root(path).ok_or_else(|| {
+        CkError::Index(format!(
+            "No index found for {}. Run 'ck --index' first.",
+            path.display()
+        ))
+    })?;
+    let index_dir = index_root.join(".ck");
+    let model = super::resolve_model_from_root(&index_root, None)?;
+    // Bundles are published, so their snippets are redacted like previews
+    let redactor = ck_models::ProjectConfig::for_path(&index_root)
+        .redaction
+        .redactor()?;
+
+    let mut chunks = Vec::new();
+    for entry in WalkDir::new(&index_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| !ck_index::is_snapshot_store(entry))
+    {
+        let entry = entry?;
+        let sidecar = entry.path();
+        if !entry.file_type().is_file()
+            || sidecar.extension().and_then(|s| s.to_str()) != Some("ck")
+        {
+            continue;
+        }
+        let (Ok(index_entry), Some(file)) = (
+            ck_index::load_index_entry(sidecar),
+            reconstruct_original_path(sidecar, &index_dir, &index_root),
+        ) else {
+            continue;
+        };
+        let Ok(mut content) = super::read_file_content(&file, &index_root) else {
+            continue;
+        };
+        if let Some(redactor) = &redactor {
+            redactor.redact_in_place(&mut content);
+        }
+        let lines: Vec<&str> = content.lines().collect();
+        let relative = file.strip_prefix(&index_root).unwrap_or(&file);
+        let relative = relative.to_string_lossy().replace('\\', "/");
+
+        for chunk in index_entry.chunks {
+            let Some(embedding) = chunk.embedding else {
+                continue;
+            };
+            if chunk.alias_of.is_some() {
+                continue;
+            }
+            // The file is read once for all of its chunks
+            let start = chunk.span.line_start.max(1) - 1;
+            let end = chunk
+                .span
+                .line_end
+                .min(start + SNIPPET_LINES)
+                .min(lines.len());
+            let snippet = lines.get(start..end.max(start)).unwrap_or_default();
+            let bundle_chunk = BundleChunk {
+                path: relative.clone(),
+                line_start: chunk.span.line_start,
+                line_end: chunk.span.line_end,
+                language: chunk
+                    .language
+                    .or_else(|| ck_core::Language::from_path(&file))
+                    .map(|l| l.to_string()),
+                symbol: chunk.symbol,
+                snippet: snippet.join("\n"),
+            };
+            chunks.push((bundle_chunk, embedding));
+        }
+    }
+
+    if chunks.is_empty() {
+        return Err(CkError::Index(
+            "No embeddings found. Run 'ck --index' first with embeddings.".to_string(),
+        )
+        .into());
+    }
+
+    SearchBundle::new(model.canonical_name(), chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_export_requires_an_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let err = export_bundle(temp_dir.path()).err().unwrap();
+        assert!(err.to_string().contains("No index found"));
+    }
+}
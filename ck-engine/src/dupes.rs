@@ -0,0 +1,262 @@
+//! `--dupes`: the embedding index as a clone detector. Every embedded chunk
+//! is compared with its nearest neighbors in other files, found through an
+//! HNSW graph over the chunks, and chunks whose cosine similarity reaches the
+//! threshold are grouped, transitively, into sets of copies. Similarity is always cosine, whatever metric the index searches
+//! with, so a threshold means the same on every index.
+//!
+//! Chunks of fewer than [`MIN_LINES`] lines are left out: short blocks such
+//! as imports or one-line accessors look alike without being copies.
+
+use anyhow::Result;
+use ck_ann::{HnswIndex, HnswParams};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::vectors::{ChunkVector, export_vectors};
+
+/// Default similarity for `--min-score`.
+pub const DEFAULT_MIN_SCORE: f32 = 0.95;
+
+/// Shortest chunk, in lines, compared for duplicates.
+pub const MIN_LINES: usize = 3;
+
+/// Neighbors looked up per chunk. A chunk with more copies than this still
+/// joins all of them, through the copies' own neighbors.
+const NEIGHBORS: usize = 32;
+
+/// A chunk in a group of duplicates.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateChunk {
+    pub id: String,
+    /// Path relative to the index root
+    pub path: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// Similarity to the closest other chunk of the group
+    pub score: f32,
+}
+
+/// Chunks linked by similarities at or above the threshold.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    /// Ordered by path and line
+    pub chunks: Vec<DuplicateChunk>,
+    /// Pairs of the group at or above the threshold
+    pub pairs: usize,
+    pub max_score: f32,
+    pub min_score: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateReport {
+    /// Chunks compared
+    pub chunks: usize,
+    pub min_score: f32,
+    /// Closest groups first
+    pub groups: Vec<DuplicateGroup>,
+}
+
+/// Groups of chunks in different files of the index covering `path` whose
+/// embeddings are at least `min_score` alike.
+pub fn find_duplicates(path: &Path, min_score: f32) -> Result<DuplicateReport> {
+    let vectors: Vec<ChunkVector> = export_vectors(path)?
+        .into_iter()
+        .filter(|vector| vector.span.line_count() >= MIN_LINES)
+        .collect();
+    let groups = group_duplicates(&vectors, min_score)?;
+    Ok(DuplicateReport {
+        chunks: vectors.len(),
+        min_score,
+        groups,
+    })
+}
+
+/// Representative of `node`'s set in a union-find forest.
+fn root(parent: &mut [usize], mut node: usize) -> usize {
+    while parent[node] != node {
+        parent[node] = parent[parent[node]];
+        node = parent[node];
+    }
+    node
+}
+
+fn group_duplicates(vectors: &[ChunkVector], min_score: f32) -> Result<Vec<DuplicateGroup>> {
+    let embeddings: Vec<Vec<f32>> = vectors
+        .iter()
+        .map(|vector| vector.embedding.clone())
+        .collect();
+    let params = HnswParams::default();
+    let graph = HnswIndex::build_with(&embeddings, params)?;
+    drop(embeddings);
+
+    // Each pair is found from either end; keep it once, under (lower, higher)
+    let mut pairs: Vec<(usize, usize, f32)> = (0..vectors.len())
+        .into_par_iter()
+        .map(|i| {
+            let near = graph.search_with_ef(
+                graph.vector(i),
+                NEIGHBORS + 1,
+                params.ef_search.max(2 * NEIGHBORS),
+            )?;
+            Ok(near
+                .into_iter()
+                .map(|(j, score)| (j as usize, score))
+                .filter(|&(j, score)| {
+                    j != i && score >= min_score && vectors[i].path != vectors[j].path
+                })
+                .map(|(j, score)| (i.min(j), i.max(j), score))
+                .collect::<Vec<_>>())
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    pairs.sort_by_key(|&(i, j, _)| (i, j));
+    pairs.dedup_by_key(|&mut (i, j, _)| (i, j));
+
+    let mut parent: Vec<usize> = (0..vectors.len()).collect();
+    let mut best = vec![f32::MIN; vectors.len()];
+    for &(i, j, score) in &pairs {
+        let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+        parent[a.max(b)] = a.min(b);
+        best[i] = best[i].max(score);
+        best[j] = best[j].max(score);
+    }
+
+    let mut groups: BTreeMap<usize, DuplicateGroup> = BTreeMap::new();
+    for &(i, _, score) in &pairs {
+        let group = groups
+            .entry(root(&mut parent, i))
+            .or_insert_with(|| DuplicateGroup {
+                chunks: Vec::new(),
+                pairs: 0,
+                max_score: score,
+                min_score: score,
+            });
+        group.pairs += 1;
+        group.max_score = group.max_score.max(score);
+        group.min_score = group.min_score.min(score);
+    }
+    for (i, vector) in vectors.iter().enumerate() {
+        if best[i] == f32::MIN {
+            continue;
+        }
+        if let Some(group) = groups.get_mut(&root(&mut parent, i)) {
+            group.chunks.push(DuplicateChunk {
+                id: vector.id.clone(),
+                path: ck_core::paths::to_slash(&vector.path),
+                line_start: vector.span.line_start,
+                line_end: vector.span.line_end,
+                symbol: vector.symbol.clone(),
+                score: best[i],
+            });
+        }
+    }
+
+    let mut groups: Vec<DuplicateGroup> = groups.into_values().collect();
+    for group in &mut groups {
+        group
+            .chunks
+            .sort_by(|a, b| (a.path.as_str(), a.line_start).cmp(&(b.path.as_str(), b.line_start)));
+    }
+    groups.sort_by(|a, b| {
+        b.max_score
+            .total_cmp(&a.max_score)
+            .then_with(|| b.chunks.len().cmp(&a.chunks.len()))
+            .then_with(|| a.chunks[0].id.cmp(&b.chunks[0].id))
+    });
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::Span;
+    use std::path::PathBuf;
+
+    fn vector(path: &str, line_start: usize, embedding: Vec<f32>) -> ChunkVector {
+        let span = Span {
+            byte_start: 0,
+            byte_end: 10,
+            line_start,
+            line_end: line_start + 9,
+            col_start: 0,
+            col_end: 0,
+        };
+        ChunkVector {
+            id: super::super::vectors::chunk_id(Path::new(path), &span),
+            path: PathBuf::from(path),
+            span,
+            language: None,
+            symbol: None,
+            alias_of: None,
+            embedding,
+        }
+    }
+
+    #[test]
+    fn test_groups_similar_chunks_across_files() {
+        let vectors = [
+            vector("src/a.rs", 1, vec![1.0, 0.0, 0.0]),
+            vector("src/b.rs", 1, vec![0.99, 0.05, 0.0]),
+            vector("src/c.rs", 20, vec![0.98, 0.0, 0.08]),
+            // Same file as the first, so only linked through the others
+            vector("src/a.rs", 40, vec![2.0, 0.0, 0.0]),
+            vector("src/d.rs", 1, vec![0.0, 1.0, 0.0]),
+        ];
+        let groups = group_duplicates(&vectors, DEFAULT_MIN_SCORE).unwrap();
+        assert_eq!(groups.len(), 1);
+        let ids: Vec<&str> = groups[0].chunks.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "src/a.rs:1-10",
+                "src/a.rs:40-49",
+                "src/b.rs:1-10",
+                "src/c.rs:20-29"
+            ]
+        );
+        assert_eq!(groups[0].pairs, 5);
+        assert!(groups[0].min_score >= DEFAULT_MIN_SCORE);
+        assert!(groups[0].max_score > 0.99 && groups[0].max_score <= 1.0 + 1e-6);
+
+        assert!(group_duplicates(&vectors[3..], 0.5).unwrap().is_empty());
+        assert!(group_duplicates(&[], 0.5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_groups_more_copies_than_neighbors() {
+        let copies = 3 * NEIGHBORS;
+        let vectors: Vec<ChunkVector> = (0..copies)
+            .map(|n| {
+                vector(
+                    &format!("src/copy{n}.rs"),
+                    1,
+                    vec![1.0, 0.001 * n as f32, 0.0],
+                )
+            })
+            .chain((0..copies).map(|n| {
+                let angle = n as f32 * 0.1;
+                vector(
+                    &format!("src/other{n}.rs"),
+                    1,
+                    vec![0.0, angle.cos(), angle.sin()],
+                )
+            }))
+            .collect();
+        let groups = group_duplicates(&vectors, 0.99).unwrap();
+        let biggest = groups.iter().map(|group| group.chunks.len()).max();
+        assert_eq!(biggest, Some(copies));
+        assert!(groups.iter().all(|group| {
+            group.chunks.iter().all(|chunk| chunk.path.contains("copy"))
+                || group
+                    .chunks
+                    .iter()
+                    .all(|chunk| chunk.path.contains("other"))
+        }));
+    }
+}
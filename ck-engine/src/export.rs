@@ -0,0 +1,251 @@
+//! `--export-index`: the index as one table for DuckDB, Polars or Lance, a row
+//! per chunk with its path, lines, kind, symbol, breadcrumb, language,
+//! metadata (a JSON object), text and vector. Written as Parquet (`.parquet`)
+//! or an Arrow IPC file (`.arrow`, `.feather`); the schema's `ck.model` key
+//! names the embedding model. Chunk text is redacted as previews are, and is
+//! null where the file changed since it was indexed. Needs the `arrow`
+//! feature.
+
+use anyhow::Result;
+use arrow_array::builder::{
+    ArrayBuilder, FixedSizeListBuilder, Float32Builder, StringBuilder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{Field, Schema};
+use ck_core::CkError;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::semantic_v3::reconstruct_original_path;
+use super::vectors::{chunk_id, index_root_for, sidecars};
+
+/// Schema metadata key naming the embedding model.
+pub const MODEL_METADATA_KEY: &str = "ck.model";
+
+/// Every chunk of the index covering `path` as one record batch, ordered by
+/// path and line.
+pub fn index_record_batch(path: &Path) -> Result<RecordBatch> {
+    let index_root = index_root_for(path)?;
+    let index_dir = index_root.join(".ck");
+    let redactor = ck_models::ProjectConfig::for_path(&index_root)
+        .redaction
+        .redactor()?;
+
+    let mut ids = StringBuilder::new();
+    let mut paths = StringBuilder::new();
+    let mut line_starts = UInt64Builder::new();
+    let mut line_ends = UInt64Builder::new();
+    let mut byte_starts = UInt64Builder::new();
+    let mut byte_ends = UInt64Builder::new();
+    let mut kinds = StringBuilder::new();
+    let mut symbols = StringBuilder::new();
+    let mut breadcrumbs = StringBuilder::new();
+    let mut languages = StringBuilder::new();
+    let mut aliases = StringBuilder::new();
+    let mut metadata = StringBuilder::new();
+    let mut texts = StringBuilder::new();
+    let mut embeddings = Vec::new();
+
+    for sidecar in sidecars(&index_root)? {
+        let entry = ck_index::load_index_entry(&sidecar)?;
+        let Some(file) = reconstruct_original_path(&sidecar, &index_dir, &index_root) else {
+            continue;
+        };
+        let relative = ck_core::paths::relative_to(&file, &index_root).unwrap_or(file.clone());
+        let language = ck_core::Language::from_path(&file);
+        // Text is read once per file, and only trusted while it is unchanged
+        let content = super::read_file_content(&file, &index_root)
+            .ok()
+            .filter(|_| {
+                ck_core::compute_file_hash(&file).ok() == Some(entry.metadata.hash.clone())
+            });
+
+        for chunk in entry.chunks {
+            ids.append_value(chunk_id(&relative, &chunk.span));
+            paths.append_value(ck_core::paths::to_slash(&relative));
+            line_starts.append_value(chunk.span.line_start as u64);
+            line_ends.append_value(chunk.span.line_end as u64);
+            byte_starts.append_value(chunk.span.byte_start as u64);
+            byte_ends.append_value(chunk.span.byte_end as u64);
+            kinds.append_option(chunk.chunk_type);
+            symbols.append_option(chunk.symbol);
+            breadcrumbs.append_option(chunk.breadcrumb);
+            languages.append_option(chunk.language.or(language).map(|l| l.to_string()));
+            aliases.append_option(chunk.alias_of.as_deref().map(ck_core::paths::to_slash));
+            if chunk.metadata.is_empty() {
+                metadata.append_null();
+            } else {
+                metadata.append_value(serde_json::to_string(&chunk.metadata)?);
+            }
+            let text = content
+                .as_deref()
+                .and_then(|content| content.get(chunk.span.byte_start..chunk.span.byte_end))
+                .map(|text| {
+                    let mut text = text.to_string();
+                    if let Some(redactor) = &redactor {
+                        redactor.redact_in_place(&mut text);
+                    }
+                    text
+                });
+            texts.append_option(text);
+            embeddings.push((chunk.embedding, relative.clone(), chunk.span.line_start));
+        }
+    }
+    if ids.is_empty() {
+        return Err(CkError::Index(format!(
+            "No chunks found in the index at {}. Run 'ck --index' first.",
+            index_root.display()
+        ))
+        .into());
+    }
+
+    let dimensions = embeddings
+        .iter()
+        .find_map(|(embedding, _, _)| embedding.as_ref().map(Vec::len))
+        .unwrap_or(0);
+    let mut vectors = FixedSizeListBuilder::new(Float32Builder::new(), dimensions as i32);
+    for (embedding, file, line) in embeddings {
+        match embedding {
+            Some(embedding) if embedding.len() == dimensions => {
+                vectors.values().append_slice(&embedding);
+                vectors.append(true);
+            }
+            Some(embedding) => {
+                return Err(CkError::Index(format!(
+                    "{}:{} has {} dimensions where other chunks have {}; re-index with one model",
+                    file.display(),
+                    line,
+                    embedding.len(),
+                    dimensions
+                ))
+                .into());
+            }
+            None => {
+                vectors.values().append_nulls(dimensions);
+                vectors.append(false);
+            }
+        }
+    }
+
+    let columns: Vec<(&str, ArrayRef, bool)> = vec![
+        ("id", Arc::new(ids.finish()), false),
+        ("path", Arc::new(paths.finish()), false),
+        ("line_start", Arc::new(line_starts.finish()), false),
+        ("line_end", Arc::new(line_ends.finish()), false),
+        ("byte_start", Arc::new(byte_starts.finish()), false),
+        ("byte_end", Arc::new(byte_ends.finish()), false),
+        ("kind", Arc::new(kinds.finish()), true),
+        ("symbol", Arc::new(symbols.finish()), true),
+        ("breadcrumb", Arc::new(breadcrumbs.finish()), true),
+        ("language", Arc::new(languages.finish()), true),
+        ("alias_of", Arc::new(aliases.finish()), true),
+        ("metadata", Arc::new(metadata.finish()), true),
+        ("text", Arc::new(texts.finish()), true),
+        ("vector", Arc::new(vectors.finish()), true),
+    ];
+    let fields: Vec<Field> = columns
+        .iter()
+        .map(|(name, array, nullable)| Field::new(*name, array.data_type().clone(), *nullable))
+        .collect();
+    let model = std::fs::read(index_dir.join("manifest.json"))
+        .ok()
+        .and_then(|data| serde_json::from_slice::<ck_index::IndexManifest>(&data).ok())
+        .and_then(|manifest| manifest.embedding_model);
+    let schema = Schema::new(fields).with_metadata(
+        model
+            .map(|model| HashMap::from([(MODEL_METADATA_KEY.to_string(), model)]))
+            .unwrap_or_default(),
+    );
+    let arrays = columns.into_iter().map(|(_, array, _)| array).collect();
+    Ok(RecordBatch::try_new(Arc::new(schema), arrays)?)
+}
+
+/// Write the index covering `path` to `output`, as Parquet or Arrow IPC by
+/// its extension, and return the number of chunks written.
+pub fn export_index(path: &Path, output: &Path) -> Result<usize> {
+    let extension = output
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    if !matches!(extension.as_deref(), Some("parquet" | "arrow" | "feather")) {
+        return Err(CkError::Index(format!(
+            "Cannot tell the format of {}; use a .parquet, .arrow or .feather file",
+            output.display()
+        ))
+        .into());
+    }
+    let batch = index_record_batch(path)?;
+    let file = File::create(output)?;
+    if extension.as_deref() == Some("parquet") {
+        let properties = parquet::file::properties::WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::ZSTD(Default::default()))
+            .build();
+        let mut writer =
+            parquet::arrow::ArrowWriter::try_new(file, batch.schema(), Some(properties))?;
+        writer.write(&batch)?;
+        writer.close()?;
+    } else {
+        let mut writer = arrow_ipc::writer::FileWriter::try_new(file, &batch.schema())?;
+        writer.write(&batch)?;
+        writer.finish()?;
+    }
+    Ok(batch.num_rows())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_export_index_round_trips_through_parquet() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(
+            root.join("lib.rs"),
+            "fn alpha() -> u32 {\n    1\n}\n\nfn beta() -> u32 {\n    2\n}\n",
+        )
+        .unwrap();
+        let options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        ck_index::smart_update_index(root, false, &options)
+            .await
+            .unwrap();
+
+        let output = root.join("chunks.parquet");
+        let rows = export_index(root, &output).unwrap();
+        assert!(rows > 0);
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&output).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.map(|batch| batch.unwrap()).next().unwrap();
+        assert_eq!(batch.num_rows(), rows);
+        let column = |name| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .clone()
+        };
+        assert!(column("path").iter().all(|path| path == Some("lib.rs")));
+        assert!(column("id").value(0).starts_with("lib.rs:1-"));
+        assert!(column("text").value(0).contains("fn alpha"));
+        // Indexed without embeddings, so every vector is null
+        assert_eq!(batch.column_by_name("vector").unwrap().null_count(), rows);
+
+        let err = export_index(root, &root.join("chunks.csv")).err().unwrap();
+        assert!(err.to_string().contains(".parquet"));
+    }
+}
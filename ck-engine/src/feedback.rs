@@ -0,0 +1,150 @@
+//! Relevance feedback for interactive sessions: Rocchio-style query adjustment
+//! toward pinned chunks and away from banned ones, plus removal of banned hits.
+
+use ck_core::{RelevanceFeedback, SearchResult, Span};
+use std::path::{Path, PathBuf};
+
+/// Weight of the original query vector.
+const ALPHA: f32 = 1.0;
+/// Weight of the centroid of pinned chunks.
+const BETA: f32 = 0.75;
+/// Weight of the centroid of banned chunks.
+const GAMMA: f32 = 0.25;
+
+/// Feedback locations with files canonicalized once for matching.
+pub(crate) struct FeedbackTargets {
+    pinned: Vec<(PathBuf, Span)>,
+    banned: Vec<(PathBuf, Span)>,
+}
+
+impl FeedbackTargets {
+    pub(crate) fn new(feedback: &RelevanceFeedback) -> Self {
+        let canonical = |list: &[(PathBuf, Span)]| {
+            list.iter()
+                .map(|(file, span)| (super::canonicalize_for_matching(file), span.clone()))
+                .collect()
+        };
+        Self {
+            pinned: canonical(&feedback.pinned),
+            banned: canonical(&feedback.banned),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pinned.is_empty() && self.banned.is_empty()
+    }
+
+    /// Each file (canonical) with pinned or banned chunks, once.
+    pub(crate) fn files(&self) -> impl Iterator<Item = &Path> {
+        let mut files: Vec<&Path> = self
+            .pinned
+            .iter()
+            .chain(&self.banned)
+            .map(|(file, _)| file.as_path())
+            .collect();
+        files.sort_unstable();
+        files.dedup();
+        files.into_iter()
+    }
+
+    /// Whether the chunk at `file` (canonical) and `span` was pinned.
+    pub(crate) fn pins(&self, file: &Path, span: &Span) -> bool {
+        covers(&self.pinned, file, span)
+    }
+
+    /// Whether the chunk at `file` (canonical) and `span` was banned.
+    pub(crate) fn bans(&self, file: &Path, span: &Span) -> bool {
+        covers(&self.banned, file, span)
+    }
+}
+
+/// A feedback location refers to a chunk when it falls inside the chunk's lines, so
+/// hits pinned from regex or lexical results still select the surrounding chunk.
+fn covers(targets: &[(PathBuf, Span)], file: &Path, span: &Span) -> bool {
+    targets.iter().any(|(target_file, target_span)| {
+        target_file == file
+            && target_span.line_start <= span.line_end
+            && target_span.line_end >= span.line_start
+    })
+}
+
+/// `ALPHA * query + BETA * mean(pinned) - GAMMA * mean(banned)`
+pub(crate) fn rocchio(query: &[f32], pinned: &[&[f32]], banned: &[&[f32]]) -> Vec<f32> {
+    let mut adjusted: Vec<f32> = query.iter().map(|x| ALPHA * x).collect();
+    for (vectors, weight) in [(pinned, BETA), (banned, -GAMMA)] {
+        let vectors: Vec<&[f32]> = vectors
+            .iter()
+            .copied()
+            .filter(|v| v.len() == query.len())
+            .collect();
+        if vectors.is_empty() {
+            continue;
+        }
+        let scale = weight / vectors.len() as f32;
+        for vector in vectors {
+            for (a, x) in adjusted.iter_mut().zip(vector) {
+                *a += scale * x;
+            }
+        }
+    }
+    adjusted
+}
+
+/// Remove results overlapping a banned location.
+pub(crate) fn drop_banned(results: &mut Vec<SearchResult>, feedback: &RelevanceFeedback) {
+    if feedback.banned.is_empty() {
+        return;
+    }
+    let targets = FeedbackTargets::new(feedback);
+    results.retain(|result| {
+        !targets.bans(
+            &super::canonicalize_for_matching(&result.file),
+            &result.span,
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(line_start: usize, line_end: usize) -> Span {
+        Span {
+            byte_start: 0,
+            byte_end: 0,
+            line_start,
+            line_end,
+            col_start: 0,
+            col_end: 0,
+        }
+    }
+
+    #[test]
+    fn test_rocchio_moves_toward_pinned_and_away_from_banned() {
+        let query = [1.0, 0.0, 0.0];
+        let pinned: [&[f32]; 2] = [&[0.0, 1.0, 0.0], &[0.0, 1.0, 0.0]];
+        let banned: [&[f32]; 1] = [&[0.0, 0.0, 1.0]];
+
+        let adjusted = rocchio(&query, &pinned, &banned);
+        assert_eq!(adjusted, vec![ALPHA, BETA, -GAMMA]);
+
+        // Mismatched dimensions are ignored rather than panicking
+        let wrong: [&[f32]; 1] = [&[1.0]];
+        assert_eq!(rocchio(&query, &wrong, &[]), query.to_vec());
+    }
+
+    #[test]
+    fn test_feedback_targets_match_by_line_overlap() {
+        let feedback = RelevanceFeedback {
+            pinned: vec![(PathBuf::from("/nonexistent/a.rs"), span(12, 12))],
+            banned: Vec::new(),
+        };
+        let targets = FeedbackTargets::new(&feedback);
+        let file = Path::new("/nonexistent/a.rs");
+
+        assert!(targets.pins(file, &span(10, 20)));
+        assert!(!targets.pins(file, &span(13, 20)));
+        assert!(!targets.pins(Path::new("/nonexistent/b.rs"), &span(10, 20)));
+        assert!(!targets.bans(file, &span(10, 20)));
+    }
+}
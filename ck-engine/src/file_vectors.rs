@@ -0,0 +1,155 @@
+//! File-level vectors: the mean of each file's chunk embeddings.
+//!
+//! They back two-stage semantic search (`--top-files N` ranks files first and
+//! scores chunks only inside the best N) and file-granularity results
+//! (`--by-file`). With `"file_vectors": true` in `.ckconfig.json`, `ck --index`
+//! keeps them in [`FILE_VECTORS_FILE`]; searches use that file while it matches
+//! the index's generation and model, and otherwise average the sidecars' chunk
+//! vectors on the fly.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::semantic_v3::reconstruct_original_path;
+
+/// File vectors kept next to `manifest.json`.
+pub const FILE_VECTORS_FILE: &str = "file_vectors.bin";
+
+#[derive(Serialize, Deserialize)]
+struct FileVectorsFile {
+    generation: u64,
+    model: String,
+    /// Files relative to the index root, with their mean chunk vector
+    files: Vec<(PathBuf, Vec<f32>)>,
+}
+
+/// The mean of `vectors`, or `None` when there are none.
+fn mean(vectors: &[&[f32]]) -> Option<Vec<f32>> {
+    let mut sum = vec![0.0f32; vectors.first()?.len()];
+    for vector in vectors {
+        for (total, x) in sum.iter_mut().zip(vector.iter()) {
+            *total += x;
+        }
+    }
+    let count = vectors.len() as f32;
+    Some(sum.into_iter().map(|x| x / count).collect())
+}
+
+/// Average every sidecar's canonical chunk vectors. Chunks that only alias a
+/// copy elsewhere carry no vector and do not count.
+fn compute(index_root: &Path) -> Result<Vec<(PathBuf, Vec<f32>)>> {
+    let index_dir = index_root.join(".ck");
+    let mut files = Vec::new();
+    for shard in ck_index::list_shards(&index_dir)? {
+        for sidecar in shard.sidecars() {
+            let (Ok(entry), Some(file)) = (
+                ck_index::load_index_entry(&sidecar),
+                reconstruct_original_path(&sidecar, &index_dir, index_root),
+            ) else {
+                continue;
+            };
+            let vectors: Vec<&[f32]> = entry
+                .chunks
+                .iter()
+                .filter_map(|chunk| chunk.embedding.as_deref())
+                .collect();
+            if let Some(vector) = mean(&vectors) {
+                let relative = file.strip_prefix(index_root).unwrap_or(&file);
+                files.push((relative.to_path_buf(), vector));
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn index_model(index_root: &Path) -> Option<String> {
+    let data = std::fs::read(index_root.join(".ck").join("manifest.json")).ok()?;
+    serde_json::from_slice::<ck_index::IndexManifest>(&data)
+        .ok()?
+        .embedding_model
+}
+
+fn read_current(index_root: &Path) -> Option<FileVectorsFile> {
+    let index_dir = index_root.join(".ck");
+    let data = std::fs::read(index_dir.join(FILE_VECTORS_FILE)).ok()?;
+    let file: FileVectorsFile =
+        bincode::deserialize(&ck_index::unseal(&index_dir, data).ok()?).ok()?;
+    let current = Some(file.generation) == ck_index::read_index_generation(index_root)
+        && Some(&file.model) == index_model(index_root).as_ref();
+    if !current {
+        tracing::debug!("File vectors are stale; averaging chunk vectors instead");
+    }
+    current.then_some(file)
+}
+
+/// Every file's vector, relative to `index_root`: the stored ones when they
+/// are current, else computed from the sidecars.
+pub(crate) fn load_file_vectors(index_root: &Path) -> Result<Vec<(PathBuf, Vec<f32>)>> {
+    match read_current(index_root) {
+        Some(file) => Ok(file.files),
+        None => compute(index_root),
+    }
+}
+
+/// Store file vectors for the index at `index_root`. Without `force`, nothing
+/// is stored unless the project enables `file_vectors`, and a stale file is
+/// removed. Returns the number of files covered, if they are stored.
+pub fn build_file_vectors(index_root: &Path, force: bool) -> Result<Option<usize>> {
+    let index_dir = index_root.join(".ck");
+    let path = index_dir.join(FILE_VECTORS_FILE);
+    if let Some(file) = read_current(index_root) {
+        return Ok(Some(file.files.len()));
+    }
+    let remove_stale = || match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(None),
+    };
+    let enabled = force || ck_models::ProjectConfig::for_path(index_root).file_vectors;
+    let (true, Some(model), Some(generation)) = (
+        enabled,
+        index_model(index_root),
+        ck_index::read_index_generation(index_root),
+    ) else {
+        return Ok(remove_stale()?);
+    };
+
+    let file = FileVectorsFile {
+        generation,
+        model,
+        files: compute(index_root)?,
+    };
+    let tmp = path.with_extension("bin.tmp");
+    std::fs::write(
+        &tmp,
+        ck_index::seal(&index_dir, bincode::serialize(&file)?)?,
+    )?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(Some(file.files.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_mean_of_chunk_vectors() {
+        assert_eq!(mean(&[]), None);
+        assert_eq!(
+            mean(&[&[1.0, 0.0], &[0.0, 1.0], &[2.0, 2.0]]),
+            Some(vec![1.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn test_stale_file_vectors_are_removed_when_disabled() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_dir = temp_dir.path().join(".ck");
+        std::fs::create_dir_all(&index_dir).unwrap();
+        std::fs::write(index_dir.join(FILE_VECTORS_FILE), b"stale").unwrap();
+
+        assert_eq!(build_file_vectors(temp_dir.path(), false).unwrap(), None);
+        assert!(!index_dir.join(FILE_VECTORS_FILE).exists());
+    }
+}
@@ -0,0 +1,118 @@
+//! HyDE (hypothetical document embeddings) for `--hyde`.
+//!
+//! Vague natural-language questions sit far from code in embedding space. With
+//! `--hyde`, the chat model configured under `llm` in the user's `config.json`
+//! drafts a short snippet that could answer the query; that snippet is
+//! embedded too, and the query vector becomes a weighted average of both.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+const SYSTEM_PROMPT: &str = "You write code. Given a question about a codebase, reply with one short, \
+plausible code snippet (under 30 lines) that would answer it, as it might appear in the source. \
+Reply with the code only, no explanation.";
+
+/// Endpoint, model and query.
+type DraftKey = (String, String, String);
+
+static DRAFTS: LazyLock<Mutex<HashMap<DraftKey, String>>> = LazyLock::new(Default::default);
+
+/// Drafts kept before the cache is cleared.
+const DRAFT_CAPACITY: usize = 256;
+
+/// `reply` without the Markdown fence models like to wrap code in.
+fn strip_code_fence(reply: &str) -> Option<String> {
+    let content = reply.trim();
+    let content = match content.strip_prefix("```") {
+        Some(fenced) => {
+            let body = fenced.split_once('\n').map_or("", |(_, body)| body);
+            body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+        }
+        None => content,
+    };
+    (!content.is_empty()).then(|| content.to_string())
+}
+
+/// `weight` parts `hypothetical` to the rest `query`, scaled back to the query
+/// vector's length so dot-product indexes score it like a plain query.
+pub(crate) fn blend(query: &[f32], hypothetical: &[f32], weight: f32) -> Vec<f32> {
+    let weight = weight.clamp(0.0, 1.0);
+    let mut blended: Vec<f32> = query
+        .iter()
+        .zip(hypothetical)
+        .map(|(q, h)| (1.0 - weight) * q + weight * h)
+        .collect();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let (target, current) = (norm(query), norm(&blended));
+    if current > 0.0 {
+        for x in &mut blended {
+            *x *= target / current;
+        }
+    }
+    blended
+}
+
+/// A hypothetical snippet answering `query`, from the chat model the project
+/// at `index_root` configures. Drafts are kept for the life of the process.
+pub(crate) fn draft_snippet(index_root: &Path, query: &str) -> Result<String> {
+    let config = crate::llm::config_for(index_root, "--hyde")?;
+    let endpoint = config.endpoint.clone().unwrap_or_default();
+    let model = config.model.clone().unwrap_or_default();
+
+    let key = (endpoint.clone(), model.clone(), query.to_string());
+    if let Some(draft) = DRAFTS.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return Ok(draft.clone());
+    }
+    let reply = crate::llm::complete(&config, SYSTEM_PROMPT, query, None)?;
+    let draft = strip_code_fence(&reply).unwrap_or(reply);
+    let mut drafts = DRAFTS.lock().unwrap_or_else(|e| e.into_inner());
+    if drafts.len() >= DRAFT_CAPACITY {
+        drafts.clear();
+    }
+    drafts.insert(key, draft.clone());
+    Ok(draft)
+}
+
+/// Share of the hypothetical snippet in the query vector for `index_root`.
+pub(crate) fn weight(index_root: &Path) -> f32 {
+    ck_models::ProjectConfig::for_path(index_root).hyde.weight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_code_fences_are_stripped() {
+        assert_eq!(
+            strip_code_fence("```rust\nfn retry() {}\n```\n").as_deref(),
+            Some("fn retry() {}")
+        );
+        assert_eq!(
+            strip_code_fence("  fn retry() {}  ").as_deref(),
+            Some("fn retry() {}")
+        );
+        assert_eq!(strip_code_fence("```\n```"), None);
+    }
+
+    #[test]
+    fn test_blend_keeps_the_query_length() {
+        let blended = blend(&[2.0, 0.0], &[0.0, 2.0], 0.5);
+        assert!((blended[0] - blended[1]).abs() < 1e-6);
+        let norm = blended.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 2.0).abs() < 1e-5);
+        assert_eq!(blend(&[1.0, 0.0], &[0.0, 1.0], 0.0), vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_hyde_needs_a_configured_endpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let err = draft_snippet(temp_dir.path(), "how are retries scheduled")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("\"llm\""));
+    }
+}
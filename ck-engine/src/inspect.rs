@@ -0,0 +1,204 @@
+//! Per-file view of the index: how a file was chunked and embedded, and which
+//! indexed chunks sit closest to each of its chunks. Used to debug why a file
+//! that should match a query is not retrieved.
+
+use anyhow::Result;
+use ck_core::{CkError, Span};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::semantic_v3::reconstruct_original_path;
+
+/// A file's chunks as stored in the index.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileInspection {
+    /// Path relative to the index root
+    pub file: PathBuf,
+    pub index_root: PathBuf,
+    /// Embedding model the index was built with
+    pub model: String,
+    /// Modification time of the file when it was indexed (Unix seconds)
+    pub indexed_mtime: u64,
+    /// False when the file changed after it was indexed
+    pub up_to_date: bool,
+    pub chunks: Vec<InspectedChunk>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InspectedChunk {
+    pub span: Span,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breadcrumb: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<usize>,
+    pub embedded: bool,
+    /// When the embedding was computed (Unix seconds); absent for chunks
+    /// embedded before ck recorded it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedded_at: Option<u64>,
+    /// File holding the canonical copy when this chunk is a duplicate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias_of: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub neighbors: Vec<Neighbor>,
+}
+
+/// An indexed chunk from another file, ranked by embedding similarity.
+#[derive(Debug, Clone, Serialize)]
+pub struct Neighbor {
+    /// Path relative to the index root
+    pub file: PathBuf,
+    pub span: Span,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub similarity: f32,
+}
+
+/// Load the index entry for `path` and, when `neighbors` is non-zero, attach the
+/// `neighbors` most similar chunks from other files to each embedded chunk.
+pub fn inspect_file(path: &Path, neighbors: usize) -> Result<FileInspection> {
+    let file = ck_core::paths::strip_verbatim(&path.canonicalize()?);
+    let index_root = super::find_nearest_index_root(&file).ok_or_else(|| {
+        CkError::Index(format!(
+            "No index found for {}. Run 'ck --index' first.",
+            path.display()
+        ))
+    })?;
+    let relative = file
+        .strip_prefix(&index_root)
+        .unwrap_or(&file)
+        .to_path_buf();
+
+    let sidecar = ck_core::get_sidecar_path(&index_root, &file);
+    if !sidecar.exists() {
+        return Err(CkError::Index(format!(
+            "{} is not in the index at {}. It may be excluded, binary, or added since the last 'ck --index'.",
+            relative.display(),
+            index_root.display()
+        ))
+        .into());
+    }
+    let entry = ck_index::load_index_entry(&sidecar)?;
+    let model = super::resolve_model_from_root(&index_root, None)?;
+    let up_to_date = ck_core::compute_file_hash(&file)
+        .map(|hash| hash == entry.metadata.hash)
+        .unwrap_or(false);
+
+    let mut chunks: Vec<InspectedChunk> = entry
+        .chunks
+        .iter()
+        .map(|chunk| InspectedChunk {
+            span: chunk.span.clone(),
+            chunk_type: chunk.chunk_type.clone(),
+            symbol: chunk.symbol.clone(),
+            breadcrumb: chunk.breadcrumb.clone(),
+            tokens: chunk.estimated_tokens,
+            embedded: chunk.embedding.is_some(),
+            embedded_at: chunk.embedded_at,
+            alias_of: chunk.alias_of.clone(),
+            neighbors: Vec::new(),
+        })
+        .collect();
+
+    if neighbors > 0 {
+        let others = collect_other_chunks(&index_root, &sidecar)?;
+        let metric = super::index_metric(&index_root);
+        for (inspected, chunk) in chunks.iter_mut().zip(&entry.chunks) {
+            if let Some(embedding) = &chunk.embedding {
+                inspected.neighbors = nearest(embedding, &others, neighbors, metric);
+            }
+        }
+    }
+
+    Ok(FileInspection {
+        file: relative,
+        index_root,
+        model: model.canonical_name().to_string(),
+        indexed_mtime: entry.metadata.last_modified,
+        up_to_date,
+        chunks,
+    })
+}
+
+/// Embedded chunks from every sidecar except `skip`, with paths relative to the root.
+fn collect_other_chunks(
+    index_root: &Path,
+    skip: &Path,
+) -> Result<Vec<(PathBuf, ck_index::ChunkEntry)>> {
+    let index_dir = index_root.join(".ck");
+    let mut chunks = Vec::new();
+    for entry in WalkDir::new(&index_dir)
+        .into_iter()
+        .filter_entry(|entry| !ck_index::is_snapshot_store(entry))
+    {
+        let entry = entry?;
+        let sidecar = entry.path();
+        if !entry.file_type().is_file()
+            || sidecar == skip
+            || sidecar.extension().and_then(|s| s.to_str()) != Some("ck")
+        {
+            continue;
+        }
+        let (Ok(index_entry), Some(file)) = (
+            ck_index::load_index_entry(sidecar),
+            reconstruct_original_path(sidecar, &index_dir, index_root),
+        ) else {
+            continue;
+        };
+        let relative = file.strip_prefix(index_root).unwrap_or(&file).to_path_buf();
+        chunks.extend(
+            index_entry
+                .chunks
+                .into_iter()
+                .filter(|chunk| chunk.embedding.is_some())
+                .map(|chunk| (relative.clone(), chunk)),
+        );
+    }
+    Ok(chunks)
+}
+
+fn nearest(
+    embedding: &[f32],
+    candidates: &[(PathBuf, ck_index::ChunkEntry)],
+    limit: usize,
+    metric: ck_models::SimilarityMetric,
+) -> Vec<Neighbor> {
+    let mut scored: Vec<(f32, &PathBuf, &ck_index::ChunkEntry)> = candidates
+        .iter()
+        .filter_map(|(file, chunk)| {
+            let other = chunk.embedding.as_ref()?;
+            Some((metric.similarity(embedding, other), file, chunk))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(similarity, file, chunk)| Neighbor {
+            file: file.clone(),
+            span: chunk.span.clone(),
+            symbol: chunk.symbol.clone(),
+            similarity,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_inspect_requires_an_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("lib.rs");
+        std::fs::write(&file, "fn main() {}\n").unwrap();
+        let err = inspect_file(&file, 0).unwrap_err();
+        assert!(err.to_string().contains("No index found"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Chat completions from the LLM configured under `llm` in the user's
+//! `config.json`, or in `.ckconfig.json` when the project is trusted
+//! (`CK_TRUST_PROJECT=1`), for `--hyde` and `--ask`. A project's own config
+//! is not enough, since it would choose where the query and the API key go.
+//!
+//! Any OpenAI-compatible endpoint works (hosted APIs, Ollama, llama.cpp's
+//! server, vLLM). Requests need the `remote` feature; without it every call
+//! fails with an error naming the feature.
+
+use anyhow::Result;
+use ck_core::CkError;
+use ck_models::LlmConfig;
+use std::path::Path;
+
+/// The LLM configured for `path`, or an error saying how to configure one.
+pub(crate) fn config_for(path: &Path, purpose: &str) -> Result<LlmConfig> {
+    let project = ck_models::ProjectConfig::for_path(path);
+    let config = project.trusted().llm;
+    check_configured(path, purpose, config, &project)
+}
+
+fn check_configured(
+    path: &Path,
+    purpose: &str,
+    config: LlmConfig,
+    project: &ck_models::ProjectConfig,
+) -> Result<LlmConfig> {
+    if config.is_configured() {
+        return Ok(config);
+    }
+    let user_config = ck_models::user_config_path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| ck_models::USER_CONFIG_FILE.to_string());
+    let project_config = ck_models::ProjectConfig::find(path)
+        .unwrap_or_else(|| path.join(ck_models::PROJECT_CONFIG_FILE));
+    let message = if project.llm.is_configured() {
+        format!(
+            "{} needs an LLM: the \"llm\" in {} is only used with {}=1; set it in {} instead",
+            purpose,
+            project_config.display(),
+            ck_models::TRUST_PROJECT_ENV,
+            user_config
+        )
+    } else {
+        format!(
+            "{} needs an LLM: set \"llm\": {{\"endpoint\": ..., \"model\": ...}} in {}",
+            purpose, user_config
+        )
+    };
+    Err(CkError::Search(message).into())
+}
+
+/// The reply text of an OpenAI-style chat completion.
+#[cfg_attr(not(feature = "remote"), allow(dead_code))]
+fn message_content(response: &serde_json::Value) -> Option<&str> {
+    response.pointer("/choices/0/message/content")?.as_str()
+}
+
+/// The text carried by one line of a streamed completion (`data: {...}`
+/// server-sent events); `None` for keep-alives, `[DONE]` and other lines.
+#[cfg_attr(not(feature = "remote"), allow(dead_code))]
+fn stream_delta(line: &str) -> Option<String> {
+    let data = line.strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return None;
+    }
+    let event: serde_json::Value = serde_json::from_str(data).ok()?;
+    event
+        .pointer("/choices/0/delta/content")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Ask `config`'s model to reply to `user` under the `system` prompt. With
+/// `on_token`, the reply is streamed and each piece is passed on as it
+/// arrives; either way the whole reply is returned.
+#[cfg(feature = "remote")]
+pub(crate) fn complete(
+    config: &LlmConfig,
+    system: &str,
+    user: &str,
+    on_token: Option<&mut dyn FnMut(&str)>,
+) -> Result<String> {
+    use std::io::BufRead;
+
+    const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+
+    let endpoint = config.endpoint.as_deref().unwrap_or_default();
+    let url = format!("{}/chat/completions", endpoint.trim_end_matches('/'));
+    let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+    let mut request = agent.post(&url);
+    if let Ok(key) = std::env::var(&config.api_key_env) {
+        request = request.set("Authorization", &format!("Bearer {}", key));
+    }
+    let response = request
+        .send_json(serde_json::json!({
+            "model": config.model,
+            "temperature": 0.2,
+            "stream": on_token.is_some(),
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": user },
+            ],
+        }))
+        .map_err(|e| CkError::Search(format!("LLM request to {} failed: {}", url, e)))?;
+
+    let reply = match on_token {
+        None => {
+            let body: serde_json::Value = response.into_json()?;
+            message_content(&body).unwrap_or_default().to_string()
+        }
+        Some(on_token) => {
+            let mut reply = String::new();
+            for line in std::io::BufReader::new(response.into_reader()).lines() {
+                if let Some(delta) = stream_delta(&line?) {
+                    on_token(&delta);
+                    reply.push_str(&delta);
+                }
+            }
+            reply
+        }
+    };
+    if reply.trim().is_empty() {
+        return Err(CkError::Search(format!("LLM endpoint {} returned no completion", url)).into());
+    }
+    Ok(reply)
+}
+
+#[cfg(not(feature = "remote"))]
+pub(crate) fn complete(
+    _config: &LlmConfig,
+    _system: &str,
+    _user: &str,
+    _on_token: Option<&mut dyn FnMut(&str)>,
+) -> Result<String> {
+    anyhow::bail!("talking to an LLM requires ck to be built with the `remote` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_completion_parsing() {
+        let body =
+            serde_json::json!({ "choices": [{ "message": { "content": "fn retry() {}" } }] });
+        assert_eq!(message_content(&body), Some("fn retry() {}"));
+        assert_eq!(message_content(&serde_json::json!({ "choices": [] })), None);
+
+        assert_eq!(
+            stream_delta(r#"data: {"choices":[{"delta":{"content":"Retries"}}]}"#).as_deref(),
+            Some("Retries")
+        );
+        assert_eq!(stream_delta(r#"data: {"choices":[{"delta":{}}]}"#), None);
+        assert_eq!(stream_delta("data: [DONE]"), None);
+        assert_eq!(stream_delta(": keep-alive"), None);
+    }
+
+    #[test]
+    fn test_llm_must_be_configured() {
+        let temp_dir = TempDir::new().unwrap();
+        let unset = ck_models::ProjectConfig::default();
+        let err = check_configured(temp_dir.path(), "--ask", LlmConfig::default(), &unset)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("--ask needs an LLM"));
+
+        // A project's endpoint is used only once the user trusts the project
+        let project = ck_models::ProjectConfig {
+            llm: LlmConfig {
+                endpoint: Some("http://localhost:11434/v1".to_string()),
+                model: Some("qwen2.5-coder".to_string()),
+                api_key_env: "AWS_SECRET_ACCESS_KEY".to_string(),
+            },
+            ..Default::default()
+        };
+        let untrusted = project.trusted_with(Default::default(), false).llm;
+        let err = check_configured(temp_dir.path(), "--ask", untrusted, &project)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(ck_models::TRUST_PROJECT_ENV));
+
+        let trusted = project.trusted_with(Default::default(), true).llm;
+        assert!(check_configured(temp_dir.path(), "--ask", trusted, &project).is_ok());
+    }
+}
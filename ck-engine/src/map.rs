@@ -0,0 +1,404 @@
+//! `--map`: an overview of what a codebase is about. Chunk embeddings are
+//! grouped with k-means, and each group is labelled by the identifier words
+//! that set it apart from the others (or, with `--llm-labels`, named by the
+//! LLM configured under `llm`), with the files it draws on.
+//!
+//! Near-duplicate chunks are left out so copies do not pull a cluster their
+//! way. Seeding is deterministic, so the same index gives the same map.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use super::symbols::split_identifier_words;
+use super::vectors::{ChunkVector, export_vectors, index_root_for};
+
+/// Most k-means rounds before giving up on convergence.
+const MAX_ITERATIONS: usize = 50;
+/// Upper bound on the number of topics when none is asked for.
+const MAX_DEFAULT_TOPICS: usize = 24;
+/// Distinctive terms kept per topic.
+const TERMS_PER_TOPIC: usize = 5;
+/// Chunks nearest each topic's centre reported as its examples.
+const EXAMPLES_PER_TOPIC: usize = 3;
+
+/// Words that say nothing about what code does: keywords and filler common
+/// to most languages.
+const STOP_WORDS: &str = "\
+    and any are args arg async await bool break but case catch char class const continue def \
+    default else end enum err export extends false final fmt for from func function get has \
+    impl import int into let map match mod mut new nil none not null obj private protected \
+    pub public ref res result return self set some static str string struct super switch that \
+    the this throw trait true try type undefined unwrap use usize val value var vec void \
+    where while with you";
+
+/// A group of similar chunks.
+#[derive(Debug, Clone, Serialize)]
+pub struct Topic {
+    /// The LLM's name for the topic, or its terms joined
+    pub label: String,
+    /// Words most particular to the topic's chunks, most distinctive first
+    pub terms: Vec<String>,
+    pub chunks: usize,
+    /// Ids of the chunks nearest the topic's centre
+    pub examples: Vec<String>,
+    /// Files with chunks in the topic, most chunks first
+    pub files: Vec<TopicFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicFile {
+    /// Path relative to the index root
+    pub path: String,
+    pub chunks: usize,
+}
+
+/// The topics of an index, largest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicMap {
+    pub chunks: usize,
+    pub topics: Vec<Topic>,
+}
+
+/// Cluster the index covering `path` into `topics` groups (by default about
+/// the square root of half the chunk count), labelling them by their terms,
+/// or with the configured LLM when `llm_labels` is set.
+pub fn topic_map(path: &Path, topics: Option<usize>, llm_labels: bool) -> Result<TopicMap> {
+    let index_root = index_root_for(path)?;
+    let llm = if llm_labels {
+        Some(crate::llm::config_for(&index_root, "--llm-labels")?)
+    } else {
+        None
+    };
+    let vectors: Vec<ChunkVector> = export_vectors(path)?
+        .into_iter()
+        .filter(|vector| vector.alias_of.is_none())
+        .collect();
+    let texts = chunk_texts(&index_root, &vectors)?;
+
+    let k = topics
+        .unwrap_or_else(|| {
+            ((vectors.len() as f64 / 2.0).sqrt().round() as usize).clamp(2, MAX_DEFAULT_TOPICS)
+        })
+        .clamp(1, vectors.len());
+    let points: Vec<Vec<f32>> = vectors
+        .iter()
+        .map(|vector| normalized(&vector.embedding))
+        .collect();
+    let assignments = kmeans(&points, k);
+
+    let mut members: Vec<Vec<usize>> = vec![Vec::new(); k];
+    for (chunk, &cluster) in assignments.iter().enumerate() {
+        members[cluster].push(chunk);
+    }
+    members.retain(|chunks| !chunks.is_empty());
+    let words: Vec<HashSet<String>> = texts.iter().map(|text| terms_of(text)).collect();
+    let terms = distinctive_terms(&members, &words);
+
+    let mut map = Vec::with_capacity(members.len());
+    for (chunks, terms) in members.iter().zip(terms) {
+        let centroid = mean(chunks.iter().map(|&chunk| points[chunk].as_slice()));
+        let mut nearest = chunks.clone();
+        nearest.sort_by(|&a, &b| dot(&points[b], &centroid).total_cmp(&dot(&points[a], &centroid)));
+        nearest.truncate(EXAMPLES_PER_TOPIC);
+
+        let mut files: BTreeMap<String, usize> = BTreeMap::new();
+        for &chunk in chunks {
+            *files
+                .entry(ck_core::paths::to_slash(&vectors[chunk].path))
+                .or_default() += 1;
+        }
+        let mut files: Vec<TopicFile> = files
+            .into_iter()
+            .map(|(path, chunks)| TopicFile { path, chunks })
+            .collect();
+        files.sort_by(|a, b| b.chunks.cmp(&a.chunks).then_with(|| a.path.cmp(&b.path)));
+
+        let label = match &llm {
+            Some(config) => {
+                let excerpts: Vec<&str> =
+                    nearest.iter().map(|&chunk| texts[chunk].as_str()).collect();
+                name_topic(config, &terms, &files, &excerpts)?
+            }
+            None => terms.join(", "),
+        };
+        map.push(Topic {
+            label,
+            terms,
+            chunks: chunks.len(),
+            examples: nearest
+                .iter()
+                .map(|&chunk| vectors[chunk].id.clone())
+                .collect(),
+            files,
+        });
+    }
+    map.sort_by(|a, b| b.chunks.cmp(&a.chunks).then_with(|| a.label.cmp(&b.label)));
+
+    Ok(TopicMap {
+        chunks: vectors.len(),
+        topics: map,
+    })
+}
+
+/// Each chunk's text, redacted; its symbol and path when the file is gone.
+fn chunk_texts(index_root: &Path, vectors: &[ChunkVector]) -> Result<Vec<String>> {
+    let redactor = ck_models::ProjectConfig::for_path(index_root)
+        .redaction
+        .redactor()?;
+    let mut contents: HashMap<&Path, Option<String>> = HashMap::new();
+    Ok(vectors
+        .iter()
+        .map(|vector| {
+            // Files are read once for all of their chunks
+            let content = contents.entry(&vector.path).or_insert_with(|| {
+                super::read_file_content(&index_root.join(&vector.path), index_root).ok()
+            });
+            let mut text = content
+                .as_deref()
+                .and_then(|content| content.get(vector.span.byte_start..vector.span.byte_end))
+                .map(str::to_string)
+                .unwrap_or_else(|| {
+                    format!(
+                        "{} {}",
+                        vector.symbol.as_deref().unwrap_or_default(),
+                        vector.path.display()
+                    )
+                });
+            if let Some(redactor) = &redactor {
+                redactor.redact_in_place(&mut text);
+            }
+            text
+        })
+        .collect())
+}
+
+/// The words a chunk's text is about: identifier parts of three letters or
+/// more, without keywords, numbers or hashes.
+fn terms_of(text: &str) -> HashSet<String> {
+    split_identifier_words(text)
+        .into_iter()
+        .filter(|word| {
+            word.len() >= 3
+                && word.chars().any(|c| c.is_alphabetic())
+                && !(word.chars().any(|c| c.is_ascii_digit())
+                    && word.chars().all(|c| c.is_ascii_hexdigit()))
+                && !STOP_WORDS.split_whitespace().any(|stop| stop == word)
+        })
+        .collect()
+}
+
+/// The terms most particular to each cluster: how many of its chunks use a
+/// word, weighed against how many chunks use it overall (class-based TF-IDF).
+fn distinctive_terms(clusters: &[Vec<usize>], words: &[HashSet<String>]) -> Vec<Vec<String>> {
+    let mut total: HashMap<&str, usize> = HashMap::new();
+    for chunk_words in words {
+        for word in chunk_words {
+            *total.entry(word.as_str()).or_default() += 1;
+        }
+    }
+    let chunks = words.len() as f64;
+
+    clusters
+        .iter()
+        .map(|members| {
+            let mut counts: HashMap<&str, usize> = HashMap::new();
+            for &chunk in members {
+                for word in &words[chunk] {
+                    *counts.entry(word.as_str()).or_default() += 1;
+                }
+            }
+            let size = members.len() as f64;
+            let mut scored: Vec<(&str, f64)> = counts
+                .into_iter()
+                // A word in a single chunk describes that chunk, not the topic
+                .filter(|&(_, count)| count > 1 || members.len() == 1)
+                .map(|(word, count)| {
+                    let idf = (chunks / total[word] as f64).ln() + 1.0;
+                    (word, count as f64 / size * idf)
+                })
+                .collect();
+            scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+            scored
+                .into_iter()
+                .take(TERMS_PER_TOPIC)
+                .map(|(word, _)| word.to_string())
+                .collect()
+        })
+        .collect()
+}
+
+const SYSTEM_PROMPT: &str = "You name groups of related source code for a map of a codebase. \
+Given a group's distinctive words, its main files and a few excerpts, reply with a name of two \
+to five words saying what the code does. Reply with the name only.";
+
+/// A short name for a topic from the configured LLM.
+fn name_topic(
+    config: &ck_models::LlmConfig,
+    terms: &[String],
+    files: &[TopicFile],
+    excerpts: &[&str],
+) -> Result<String> {
+    let mut prompt = format!("Distinctive words: {}\nFiles:", terms.join(", "));
+    for file in files.iter().take(10) {
+        prompt.push_str(&format!("\n- {}", file.path));
+    }
+    for excerpt in excerpts {
+        let head: Vec<&str> = excerpt.lines().take(20).collect();
+        prompt.push_str(&format!("\n\n```\n{}\n```", head.join("\n")));
+    }
+    let reply = crate::llm::complete(config, SYSTEM_PROMPT, &prompt, None)?;
+    let name = reply
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default();
+    Ok(name
+        .trim()
+        .trim_matches(|c| c == '"' || c == '*' || c == '.')
+        .to_string())
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = dot(vector, vector).sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn mean<'a>(points: impl Iterator<Item = &'a [f32]>) -> Vec<f32> {
+    let mut sum: Vec<f32> = Vec::new();
+    let mut count = 0;
+    for point in points {
+        if sum.is_empty() {
+            sum = vec![0.0; point.len()];
+        }
+        for (s, x) in sum.iter_mut().zip(point) {
+            *s += x;
+        }
+        count += 1;
+    }
+    normalized(
+        &sum.iter()
+            .map(|s| s / count.max(1) as f32)
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Spherical k-means over unit vectors with k-means++ seeding from a fixed
+/// seed: each point's cluster.
+fn kmeans(points: &[Vec<f32>], k: usize) -> Vec<usize> {
+    // SplitMix64, so the same index always gives the same map
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut next = move || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as f64 / u64::MAX as f64
+    };
+
+    let distance = |a: &[f32], b: &[f32]| (1.0 - dot(a, b)).max(0.0) as f64;
+    let mut centroids =
+        vec![points[(next() * points.len() as f64) as usize % points.len()].clone()];
+    let mut nearest: Vec<f64> = points.iter().map(|p| distance(p, &centroids[0])).collect();
+    while centroids.len() < k {
+        let total: f64 = nearest.iter().map(|d| d * d).sum();
+        let chosen = if total == 0.0 {
+            centroids.len() % points.len()
+        } else {
+            let mut target = next() * total;
+            nearest
+                .iter()
+                .position(|d| {
+                    target -= d * d;
+                    target <= 0.0
+                })
+                .unwrap_or(points.len() - 1)
+        };
+        centroids.push(points[chosen].clone());
+        for (d, point) in nearest.iter_mut().zip(points) {
+            *d = d.min(distance(point, &centroids[centroids.len() - 1]));
+        }
+    }
+
+    let mut assignments = vec![usize::MAX; points.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (assignment, point) in assignments.iter_mut().zip(points) {
+            let best = (0..centroids.len())
+                .max_by(|&a, &b| {
+                    dot(point, &centroids[a])
+                        .total_cmp(&dot(point, &centroids[b]))
+                        .then(b.cmp(&a))
+                })
+                .unwrap_or(0);
+            if *assignment != best {
+                *assignment = best;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+        for (cluster, centroid) in centroids.iter_mut().enumerate() {
+            let members = points
+                .iter()
+                .zip(&assignments)
+                .filter(|&(_, &a)| a == cluster)
+                .map(|(point, _)| point.as_slice());
+            let updated = mean(members);
+            // An emptied cluster keeps its old centre
+            if !updated.is_empty() {
+                *centroid = updated;
+            }
+        }
+    }
+    assignments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kmeans_separates_topics_and_finds_their_terms() {
+        let points: Vec<Vec<f32>> = [
+            [1.0, 0.1, 0.0],
+            [0.9, 0.0, 0.1],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.1],
+            [0.1, 0.9, 0.0],
+            [0.0, 1.0, 0.0],
+        ]
+        .iter()
+        .map(|p| normalized(p))
+        .collect();
+        let assignments = kmeans(&points, 2);
+        assert_eq!(assignments[0], assignments[1]);
+        assert_eq!(assignments[1], assignments[2]);
+        assert_eq!(assignments[3], assignments[4]);
+        assert_ne!(assignments[0], assignments[3]);
+        assert_eq!(kmeans(&points, 2), assignments, "seeding is deterministic");
+
+        let texts = [
+            "fn retry_request(backoff: Duration) -> Result<Response>",
+            "let backoff = retry_policy.next_backoff();",
+            "async fn send_request(url: &str)",
+            "fn parse_config(path: &Path) -> Config",
+            "let config = ConfigLoader::parse(toml)",
+            "struct ConfigLoader { path: PathBuf }",
+        ];
+        let words: Vec<HashSet<String>> = texts.iter().map(|text| terms_of(text)).collect();
+        assert!(!words[0].contains("result") && words[0].contains("backoff"));
+        let clusters = vec![vec![0, 1, 2], vec![3, 4, 5]];
+        let terms = distinctive_terms(&clusters, &words);
+        assert_eq!(terms[0][..2], ["backoff", "request"]);
+        assert_eq!(terms[1][0], "config");
+    }
+}
@@ -0,0 +1,61 @@
+//! Index-time metadata on results, for `--meta KEY=VALUE` filters and JSON
+//! output. Each result takes the metadata of the indexed chunk covering its
+//! first line, read from the file's sidecar; results in files that are not
+//! indexed carry none.
+//!
+//! With `link_generated`, hits in generated code whose chunks name their
+//! schema fold into the schema's own hit, which lists them as copies.
+
+use ck_core::{DuplicateLocation, SearchResult};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Annotate every result with its chunk's metadata, reading each file's
+/// sidecar once.
+pub(crate) fn annotate_metadata(results: &mut [SearchResult]) {
+    let mut entries: HashMap<PathBuf, Option<ck_index::IndexEntry>> = HashMap::new();
+    for result in results {
+        let entry = entries
+            .entry(result.file.clone())
+            .or_insert_with(|| {
+                let file = ck_core::paths::canonicalize(&result.file);
+                let index_root = super::find_nearest_index_root(&file)?;
+                ck_index::load_index_entry(&ck_core::get_sidecar_path(&index_root, &file)).ok()
+            })
+            .as_ref();
+        if let Some(entry) = entry {
+            result.metadata = ck_index::metadata_at(entry, result.span.line_start);
+        }
+    }
+}
+
+/// Fold each hit in generated code into the best hit in the schema it was
+/// generated from, when that is among `results`.
+pub(crate) fn fold_generated(results: &mut Vec<SearchResult>) {
+    let mut folded = vec![false; results.len()];
+    for generated in 0..results.len() {
+        let Some(source) = results[generated].metadata.get(ck_index::GENERATED_FROM) else {
+            continue;
+        };
+        let Some(schema) = results.iter().position(|result| {
+            result.file.ends_with(source) && !result.metadata.contains_key(ck_index::GENERATED_FROM)
+        }) else {
+            continue;
+        };
+        let location = DuplicateLocation {
+            file: results[generated].file.clone(),
+            span: results[generated].span.clone(),
+        };
+        results[schema].duplicates.push(location);
+        folded[generated] = true;
+    }
+    let mut folded = folded.into_iter();
+    results.retain(|_| !folded.next().unwrap_or(false));
+}
+
+/// Whether `result` carries every one of the `--meta` pairs.
+pub(crate) fn matches_all(result: &SearchResult, filters: &[(String, String)]) -> bool {
+    filters
+        .iter()
+        .all(|(key, value)| result.metadata.get(key) == Some(value))
+}
@@ -0,0 +1,285 @@
+//! Query embedders and rerankers stay loaded for the life of the process, so
+//! repeated searches (`ck --stdin` batches, the MCP server, the TUI) pay model
+//! load time once per model rather than once per query. Recent query vectors
+//! are kept too, so a query searched across several indexes is embedded once
+//! per distinct model.
+//!
+//! A long-running server can also coalesce queries: with a query batch delay
+//! set, a query waits up to that long for concurrent queries on the same model
+//! and they are embedded together in one call.
+
+use anyhow::Result;
+use ck_embed::{Embedder, Reranker};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::sync::{Notify, oneshot};
+
+pub(crate) type SharedEmbedder = Arc<Mutex<Box<dyn Embedder>>>;
+pub(crate) type SharedReranker = Arc<Mutex<Box<dyn Reranker>>>;
+
+static EMBEDDERS: LazyLock<Mutex<HashMap<String, SharedEmbedder>>> =
+    LazyLock::new(Default::default);
+static RERANKERS: LazyLock<Mutex<HashMap<String, SharedReranker>>> =
+    LazyLock::new(Default::default);
+/// Embedder key and query text.
+type QueryKey = (String, String);
+
+static QUERY_VECTORS: LazyLock<Mutex<HashMap<QueryKey, Vec<f32>>>> =
+    LazyLock::new(Default::default);
+
+/// Query vectors kept before the cache is cleared.
+const QUERY_VECTOR_CAPACITY: usize = 256;
+
+/// Query batch delay `ck --serve` uses unless told otherwise.
+pub const DEFAULT_QUERY_BATCH_DELAY: Duration = Duration::from_millis(2);
+
+/// Queries embedded in one call at most; a full batch goes without waiting.
+const MAX_QUERY_BATCH: usize = 32;
+
+/// Microseconds a query waits for others; 0 embeds each query alone.
+static QUERY_BATCH_DELAY: AtomicU64 = AtomicU64::new(0);
+
+static OPEN_BATCHES: LazyLock<Mutex<HashMap<String, Arc<QueryBatch>>>> =
+    LazyLock::new(Default::default);
+
+type QueryReply = std::result::Result<Option<Vec<f32>>, String>;
+
+/// Queries waiting to share one embedding call.
+#[derive(Default)]
+struct QueryBatch {
+    /// Each query with where its vector goes
+    queries: Mutex<Vec<(String, oneshot::Sender<QueryReply>)>>,
+    /// Signalled when the batch is full
+    full: Notify,
+}
+
+/// Let queries wait up to `delay` to be embedded together with concurrent
+/// queries on the same model; zero turns batching off.
+pub fn set_query_batch_delay(delay: Duration) {
+    QUERY_BATCH_DELAY.store(delay.as_micros() as u64, Ordering::Relaxed);
+}
+
+pub(crate) fn embedder_key(config: &ck_models::ModelConfig) -> String {
+    format!(
+        "{}:{}:{}:{}:{}",
+        config.provider, config.name, config.dimensions, config.normalize, config.truncation
+    )
+}
+
+/// Loaded embedder for `config`, created on first use.
+pub(crate) fn embedder(config: &ck_models::ModelConfig) -> Result<SharedEmbedder> {
+    let key = embedder_key(config);
+    let mut cache = EMBEDDERS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(embedder) = cache.get(&key) {
+        return Ok(embedder.clone());
+    }
+    let embedder = Arc::new(Mutex::new(ck_embed::create_embedder_for_config(
+        config, None,
+    )?));
+    cache.insert(key, embedder.clone());
+    Ok(embedder)
+}
+
+/// `query` embedded with `config`, reusing the vector of an earlier call.
+pub(crate) async fn query_vector(
+    config: &ck_models::ModelConfig,
+    query: &str,
+) -> Result<Option<Vec<f32>>> {
+    let key = (embedder_key(config), query.to_string());
+    if let Some(vector) = QUERY_VECTORS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&key)
+    {
+        return Ok(Some(vector.clone()));
+    }
+    let embedder = embedder(config)?;
+    let delay = Duration::from_micros(QUERY_BATCH_DELAY.load(Ordering::Relaxed));
+    let Some(vector) = embed_query(&key.0, &embedder, query, delay).await? else {
+        return Ok(None);
+    };
+    let mut cache = QUERY_VECTORS.lock().unwrap_or_else(|e| e.into_inner());
+    if cache.len() >= QUERY_VECTOR_CAPACITY {
+        cache.clear();
+    }
+    cache.insert(key, vector.clone());
+    Ok(Some(vector))
+}
+
+/// Run `texts` through `embedder` on the blocking pool, so that model
+/// inference never holds up a runtime worker.
+pub(crate) async fn embed_blocking(
+    embedder: &SharedEmbedder,
+    texts: Vec<String>,
+) -> Result<Vec<Vec<f32>>> {
+    let embedder = embedder.clone();
+    tokio::task::spawn_blocking(move || {
+        embedder
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .embed(&texts)
+    })
+    .await?
+    .map_err(Into::into)
+}
+
+/// Embed `query`, alone when `delay` is zero, and otherwise in one call with
+/// the queries for the same embedder that arrive within `delay` of the first.
+async fn embed_query(
+    key: &str,
+    embedder: &SharedEmbedder,
+    query: &str,
+    delay: Duration,
+) -> Result<Option<Vec<f32>>> {
+    if delay.is_zero() {
+        return Ok(embed_blocking(embedder, vec![query.to_string()])
+            .await?
+            .into_iter()
+            .next());
+    }
+
+    // Join the open batch, or open one and send it when the delay is up
+    let (reply, leader) = {
+        let mut open = OPEN_BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+        let (batch, leader) = match open.get(key) {
+            Some(batch) => (batch.clone(), None),
+            None => {
+                let batch = Arc::new(QueryBatch::default());
+                open.insert(key.to_string(), batch.clone());
+                (batch.clone(), Some(batch))
+            }
+        };
+        let (sender, reply) = oneshot::channel();
+        let mut queries = batch.queries.lock().unwrap_or_else(|e| e.into_inner());
+        queries.push((query.to_string(), sender));
+        if queries.len() >= MAX_QUERY_BATCH {
+            open.remove(key);
+            batch.full.notify_one();
+        }
+        (reply, leader)
+    };
+
+    if let Some(batch) = leader {
+        let _ = tokio::time::timeout(delay, batch.full.notified()).await;
+        {
+            let mut open = OPEN_BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+            if open.get(key).is_some_and(|open| Arc::ptr_eq(open, &batch)) {
+                open.remove(key);
+            }
+        }
+        // Closed: no query can join now
+        let (queries, senders): (Vec<String>, Vec<_>) =
+            std::mem::take(&mut *batch.queries.lock().unwrap_or_else(|e| e.into_inner()))
+                .into_iter()
+                .unzip();
+        tracing::debug!(queries = queries.len(), "Embedding query batch");
+        match embed_blocking(embedder, queries).await {
+            Ok(vectors) => {
+                let mut vectors = vectors.into_iter();
+                for sender in senders {
+                    let _ = sender.send(Ok(vectors.next()));
+                }
+            }
+            Err(e) => {
+                for sender in senders {
+                    let _ = sender.send(Err(e.to_string()));
+                }
+            }
+        }
+    }
+
+    reply
+        .await
+        .map_err(|_| anyhow::anyhow!("Query batch was dropped before it was embedded"))?
+        .map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// Loaded reranker for `config`, created on first use.
+pub(crate) fn reranker(config: &ck_models::RerankModelConfig) -> Result<SharedReranker> {
+    let key = reranker_key(config);
+    let mut cache = RERANKERS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(reranker) = cache.get(&key) {
+        return Ok(reranker.clone());
+    }
+    let reranker = Arc::new(Mutex::new(ck_embed::create_reranker_for_config(
+        config, None,
+    )?));
+    cache.insert(key, reranker.clone());
+    Ok(reranker)
+}
+
+fn reranker_key(config: &ck_models::RerankModelConfig) -> String {
+    format!(
+        "{}:{}:{}",
+        config.provider, config.name, config.score_transform
+    )
+}
+
+/// Serve `config` with `reranker` instead of loading the model.
+#[cfg(test)]
+pub(crate) fn set_reranker(config: &ck_models::RerankModelConfig, reranker: Box<dyn Reranker>) {
+    RERANKERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(reranker_key(config), Arc::new(Mutex::new(reranker)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct CountingEmbedder {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Embedder for CountingEmbedder {
+        fn id(&self) -> &'static str {
+            "counting"
+        }
+
+        fn dim(&self) -> usize {
+            1
+        }
+
+        fn model_name(&self) -> &str {
+            "counting"
+        }
+
+        fn embed(&mut self, texts: &[String]) -> ck_embed::Result<Vec<Vec<f32>>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(texts.iter().map(|text| vec![text.len() as f32]).collect())
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_concurrent_queries_share_one_embedding_call() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let embedder: SharedEmbedder = Arc::new(Mutex::new(Box::new(CountingEmbedder {
+            calls: calls.clone(),
+        })));
+        let delay = Duration::from_millis(200);
+
+        // One runtime thread: a query that blocked it would starve the others
+        let (a, b, c, d) = tokio::join!(
+            embed_query("test-batch", &embedder, "a", delay),
+            embed_query("test-batch", &embedder, "bb", delay),
+            embed_query("test-batch", &embedder, "ccc", delay),
+            embed_query("test-batch", &embedder, "dddd", delay),
+        );
+        let vectors: Vec<Vec<f32>> = [a, b, c, d]
+            .into_iter()
+            .map(|vector| vector.unwrap().unwrap())
+            .collect();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(vectors, vec![vec![1.0], vec![2.0], vec![3.0], vec![4.0]]);
+
+        embed_query("test-batch", &embedder, "e", Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
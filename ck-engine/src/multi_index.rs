@@ -0,0 +1,386 @@
+//! One query over several indexes, each built with its own embedding model.
+//!
+//! A workspace of repositories with their own `.ckconfig.json` can hold one
+//! index per repository, each on a different model. Every index is brought up
+//! to date and searched with the model it was built with: the query is
+//! embedded once per distinct model, then the indexes are scored concurrently.
+//! Similarities from different models are not on one scale, so their rankings
+//! are merged with reciprocal rank fusion; indexes that share a model are
+//! merged by score.
+
+use anyhow::Result;
+use ck_core::{
+    IncludePattern, ScoreExplanation, ScoreNormalization, SearchMode, SearchOptions, SearchResults,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::query_model::load_pairing;
+use super::{
+    DetailedIndexingProgressCallback, IndexingProgressCallback, SearchProgressCallback,
+    ensure_index_updated_with_progress, find_nearest_index_root, index_metric, model_cache,
+    resolve_model_from_root, search_updated_index, usage,
+};
+
+/// Damping constant of reciprocal rank fusion, as in hybrid search.
+const RRF_K: f32 = 60.0;
+
+/// A progress callback handed to every index's search.
+type SharedCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// The index each of `paths` belongs to: the nearest directory holding a `.ck`
+/// index, or else the nearest `.ckconfig.json`. Distinct roots come back once,
+/// in order. `None` when a path has neither, leaving the choice of index to a
+/// single-root search.
+pub fn index_roots(paths: &[PathBuf]) -> Option<Vec<PathBuf>> {
+    let mut roots = Vec::new();
+    for path in paths {
+        let root = find_nearest_index_root(path).or_else(|| {
+            ck_models::ProjectConfig::find(path)
+                .and_then(|config| config.parent().map(Path::to_path_buf))
+        })?;
+        let root = ck_core::paths::canonicalize(&root);
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    Some(roots)
+}
+
+/// `options` narrowed to the index at `root`: its include patterns are those
+/// under `root`, with `root` itself meaning the whole index. `None` when
+/// patterns were given but none fall under `root`.
+fn options_for_root(options: &SearchOptions, root: &Path) -> Option<SearchOptions> {
+    let scoped: Vec<IncludePattern> = options
+        .include_patterns
+        .iter()
+        .filter(|pattern| pattern.path.starts_with(root))
+        .cloned()
+        .collect();
+    if !options.include_patterns.is_empty() && scoped.is_empty() {
+        return None;
+    }
+    let mut root_options = options.clone();
+    root_options.path = root.to_path_buf();
+    root_options.include_patterns = scoped
+        .into_iter()
+        .filter(|pattern| !(pattern.is_dir && pattern.path == root))
+        .collect();
+    Some(root_options)
+}
+
+/// [`search_indexes_with_indexing_progress`] without progress reporting.
+pub async fn search_indexes(options: &SearchOptions, roots: &[PathBuf]) -> Result<SearchResults> {
+    search_indexes_with_indexing_progress(options, roots, None, None, None).await
+}
+
+/// Search every index in `roots` (see [`index_roots`]) for `options.query` and
+/// merge the results into one ranking of at most `options.top_k` hits.
+pub async fn search_indexes_with_indexing_progress(
+    options: &SearchOptions,
+    roots: &[PathBuf],
+    progress_callback: Option<SearchProgressCallback>,
+    indexing_progress_callback: Option<IndexingProgressCallback>,
+    detailed_indexing_progress_callback: Option<DetailedIndexingProgressCallback>,
+) -> Result<SearchResults> {
+    let progress: Option<SharedCallback> = progress_callback.map(Arc::from);
+    let indexing: Option<SharedCallback> = indexing_progress_callback.map(Arc::from);
+    let detailed: Option<Arc<dyn Fn(ck_index::EmbeddingProgress) + Send + Sync>> =
+        detailed_indexing_progress_callback.map(Arc::from);
+    let need_embeddings = matches!(
+        options.mode,
+        SearchMode::Semantic | SearchMode::Hybrid | SearchMode::Symbol
+    );
+
+    // Indexes are updated one at a time, so only one model embeds at once
+    let mut routes = Vec::new();
+    let mut models = Vec::new();
+    for root in roots {
+        let Some(root_options) = options_for_root(options, root) else {
+            continue;
+        };
+        if options.mode != SearchMode::Regex {
+            ensure_index_updated_with_progress(
+                root,
+                options.reindex,
+                need_embeddings,
+                indexing.clone().map(|callback| {
+                    Box::new(move |file: &str| callback(file)) as IndexingProgressCallback
+                }),
+                detailed.clone().map(|callback| {
+                    Box::new(move |progress| callback(progress)) as DetailedIndexingProgressCallback
+                }),
+                &ck_core::FileCollectionOptions::from(&root_options),
+                options.embedding_model.as_deref(),
+                options.cancel.as_ref(),
+            )
+            .await?;
+        }
+        if need_embeddings {
+            let model = resolve_model_from_root(root, options.embedding_model.as_deref())?;
+            // Later searches of indexes on the same model reuse this vector;
+            // paired query models validate their fingerprints per index instead
+            if load_pairing(root, &model, options.embedding_model.as_deref())?.is_none() {
+                let query_text = model.config.instructed_query(
+                    &super::query_rewrite::rewrite_query(root, &options.query),
+                    options.instruction.as_deref(),
+                );
+                model_cache::query_vector(&model.config, &query_text).await?;
+            }
+            let key = format!("{}:{}", model.canonical_name(), index_metric(root));
+            if !models.contains(&key) {
+                models.push(key);
+            }
+        }
+        routes.push(root_options);
+    }
+    if let Some(callback) = &progress {
+        callback(&format!(
+            "Searching {} indexes with {} models...",
+            routes.len(),
+            models.len().max(1)
+        ));
+    }
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for (position, route) in routes.into_iter().enumerate() {
+        let progress = progress.clone().map(|callback| {
+            Box::new(move |message: &str| callback(message)) as SearchProgressCallback
+        });
+        tasks.spawn(async move {
+            let started = std::time::Instant::now();
+            let results = search_updated_index(&route, progress).await;
+            if results.is_ok() {
+                usage::record_search(&route, started.elapsed());
+            }
+            (position, results)
+        });
+    }
+    let mut lists = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (position, results) = joined?;
+        lists.push((position, results?));
+    }
+    lists.sort_by_key(|(position, _)| *position);
+    let mut lists: Vec<SearchResults> = lists.into_iter().map(|(_, results)| results).collect();
+
+    // Lexical scores are normalized per index, and model similarities only
+    // compare within one model
+    let comparable = match options.mode {
+        SearchMode::Regex | SearchMode::Hybrid => true,
+        SearchMode::Semantic | SearchMode::Symbol => models.len() <= 1,
+        SearchMode::Lexical => false,
+    };
+    Ok(match options.score_normalization {
+        ScoreNormalization::Rank if comparable => merge_by_score(lists, options.top_k),
+        ScoreNormalization::Rank => fuse_by_rank(lists, options.top_k),
+        normalization => {
+            for list in &mut lists {
+                normalize_list(&mut list.matches, normalization);
+            }
+            merge_by_score(lists, options.top_k)
+        }
+    })
+}
+
+/// Rescale one index's scores, keeping each hit's own score in its explanation.
+fn normalize_list(matches: &mut [ck_core::SearchResult], normalization: ScoreNormalization) {
+    let mut scores: Vec<f32> = matches.iter().map(|result| result.score).collect();
+    normalization.apply(&mut scores);
+    for (result, score) in matches.iter_mut().zip(scores) {
+        let raw = std::mem::replace(&mut result.score, score);
+        let explain = result
+            .explain
+            .get_or_insert_with(|| ScoreExplanation::new("regex", raw));
+        explain.index_score = Some(raw);
+    }
+}
+
+fn by_score_desc(a: &ck_core::SearchResult, b: &ck_core::SearchResult) -> std::cmp::Ordering {
+    b.score
+        .partial_cmp(&a.score)
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+fn merge_by_score(lists: Vec<SearchResults>, top_k: Option<usize>) -> SearchResults {
+    let mut matches = Vec::new();
+    let mut closest_below_threshold: Option<ck_core::SearchResult> = None;
+    for list in lists {
+        matches.extend(list.matches);
+        if let Some(near) = list.closest_below_threshold
+            && closest_below_threshold
+                .as_ref()
+                .is_none_or(|closest| near.score > closest.score)
+        {
+            closest_below_threshold = Some(near);
+        }
+    }
+    matches.sort_by(by_score_desc);
+    if let Some(top_k) = top_k {
+        matches.truncate(top_k);
+    }
+    SearchResults {
+        matches,
+        closest_below_threshold,
+    }
+}
+
+/// Reciprocal rank fusion over per-index rankings: each hit scores
+/// `Σ 1 / (60 + rank)` over the lists it appears in, and records its rank in
+/// its own index.
+fn fuse_by_rank(lists: Vec<SearchResults>, top_k: Option<usize>) -> SearchResults {
+    let mut fused: HashMap<(PathBuf, usize), ck_core::SearchResult> = HashMap::new();
+    let mut closest_below_threshold = None;
+    for list in lists {
+        closest_below_threshold = closest_below_threshold.or(list.closest_below_threshold);
+        for (rank, mut result) in list.matches.into_iter().enumerate() {
+            let score = 1.0 / (RRF_K + (rank + 1) as f32);
+            let key = (result.file.clone(), result.span.line_start);
+            if let Some(existing) = fused.get_mut(&key) {
+                existing.score += score;
+                continue;
+            }
+            let mut explain = result
+                .explain
+                .take()
+                .unwrap_or_else(|| ScoreExplanation::new("regex", result.score));
+            explain.index_rank = Some(rank + 1);
+            result.explain = Some(explain);
+            result.score = score;
+            fused.insert(key, result);
+        }
+    }
+    let mut matches: Vec<_> = fused.into_values().collect();
+    matches.sort_by(|a, b| by_score_desc(a, b).then_with(|| a.file.cmp(&b.file)));
+    if let Some(top_k) = top_k {
+        matches.truncate(top_k);
+    }
+    SearchResults {
+        matches,
+        closest_below_threshold,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::{SearchResult, Span};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn hit(file: &str, score: f32) -> SearchResult {
+        SearchResult::new(file, Span::new_unchecked(0, 10, 1, 1), score, "")
+    }
+
+    fn list(hits: Vec<SearchResult>) -> SearchResults {
+        SearchResults {
+            matches: hits,
+            closest_below_threshold: None,
+        }
+    }
+
+    #[test]
+    fn test_index_roots_follow_indexes_and_configs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("api/.ck")).unwrap();
+        fs::create_dir_all(root.join("api/src")).unwrap();
+        fs::create_dir_all(root.join("web/src")).unwrap();
+        fs::write(root.join("web/.ckconfig.json"), r#"{"model":"nomic-v1.5"}"#).unwrap();
+
+        let roots =
+            index_roots(&[root.join("api/src"), root.join("web/src"), root.join("api")]).unwrap();
+        assert_eq!(roots, vec![root.join("api"), root.join("web")]);
+
+        // A path outside any index or project leaves routing to the caller
+        fs::create_dir(root.join("scratch")).unwrap();
+        if ck_models::ProjectConfig::find(&root.join("scratch")).is_none() {
+            assert!(index_roots(&[root.join("api"), root.join("scratch")]).is_none());
+        }
+    }
+
+    #[test]
+    fn test_options_for_root_keep_patterns_under_the_root() {
+        let options = SearchOptions {
+            include_patterns: vec![
+                IncludePattern {
+                    path: PathBuf::from("/ws/api"),
+                    is_dir: true,
+                },
+                IncludePattern {
+                    path: PathBuf::from("/ws/web/src/app.ts"),
+                    is_dir: false,
+                },
+            ],
+            ..SearchOptions::default()
+        };
+        let api = options_for_root(&options, Path::new("/ws/api")).unwrap();
+        assert_eq!(api.path, PathBuf::from("/ws/api"));
+        assert!(api.include_patterns.is_empty());
+        let web = options_for_root(&options, Path::new("/ws/web")).unwrap();
+        assert_eq!(web.include_patterns.len(), 1);
+        assert!(options_for_root(&options, Path::new("/ws/docs")).is_none());
+    }
+
+    #[test]
+    fn test_rankings_from_different_models_are_fused_by_rank() {
+        // The second model scores everything higher; rank fusion interleaves
+        let fused = fuse_by_rank(
+            vec![
+                list(vec![hit("api/a.rs", 0.62), hit("api/b.rs", 0.55)]),
+                list(vec![hit("web/x.ts", 0.91), hit("web/y.ts", 0.88)]),
+            ],
+            Some(3),
+        );
+        let files: Vec<_> = fused.matches.iter().map(|r| r.file.clone()).collect();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("api/a.rs"),
+                PathBuf::from("web/x.ts"),
+                PathBuf::from("api/b.rs")
+            ]
+        );
+        assert_eq!(
+            fused.matches[2].explain.as_ref().unwrap().index_rank,
+            Some(2)
+        );
+
+        let merged = merge_by_score(
+            vec![
+                list(vec![hit("api/a.rs", 0.62)]),
+                list(vec![hit("web/x.ts", 0.91), hit("web/y.ts", 0.88)]),
+            ],
+            None,
+        );
+        assert_eq!(merged.matches[0].file, PathBuf::from("web/x.ts"));
+        assert_eq!(merged.matches[2].file, PathBuf::from("api/a.rs"));
+    }
+
+    #[test]
+    fn test_normalized_lists_merge_on_one_scale() {
+        // Each index's best hit scores 1 whatever its model's raw scale
+        let mut api = vec![hit("api/a.rs", 0.62), hit("api/b.rs", 0.42)];
+        let mut web = vec![
+            hit("web/x.ts", 0.91),
+            hit("web/y.ts", 0.90),
+            hit("web/z.ts", 0.81),
+        ];
+        normalize_list(&mut api, ScoreNormalization::MinMax);
+        normalize_list(&mut web, ScoreNormalization::MinMax);
+        let merged = merge_by_score(vec![list(api), list(web)], None);
+
+        let scores: Vec<f32> = merged.matches.iter().map(|r| r.score).collect();
+        assert_eq!(scores[0], 1.0);
+        assert_eq!(scores[1], 1.0);
+        assert!((scores[2] - 0.9).abs() < 1e-5);
+        let b = merged
+            .matches
+            .iter()
+            .find(|r| r.file == Path::new("api/b.rs"))
+            .unwrap();
+        assert_eq!(b.score, 0.0);
+        assert_eq!(b.explain.as_ref().unwrap().index_score, Some(0.42));
+    }
+}
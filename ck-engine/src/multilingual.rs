@@ -0,0 +1,251 @@
+//! Cross-lingual search for projects with `multilingual` enabled.
+//!
+//! Semantic search relies on the multilingual embedder: `multilingual-e5`
+//! embeds queries and chunks into one space whatever their language, so a
+//! Chinese question finds English comments and the other way round. Lexical
+//! analysis needs help of its own. Tantivy's default tokenizer splits on
+//! whitespace and punctuation, which leaves Chinese or Japanese text as one
+//! token per sentence; this one emits each CJK character as its own token so
+//! BM25 can match words inside unsegmented text, and folds full-width Latin
+//! letters and digits (common in CJK input) to ASCII.
+
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex};
+use tantivy::Index;
+use tantivy::schema::{IndexRecordOption, Schema, TextFieldIndexing, TextOptions};
+use tantivy::tokenizer::{
+    LowerCaser, RemoveLongFilter, TextAnalyzer, Token, TokenStream, Tokenizer,
+};
+
+/// Name the analyzer is registered under in each tantivy index.
+pub(crate) const TOKENIZER_NAME: &str = "ck_multilingual";
+
+/// Options for the `content` field: tantivy's default analyzer, or the
+/// CJK-aware one for multilingual projects.
+pub(crate) fn content_options(multilingual: bool) -> TextOptions {
+    let tokenizer = if multilingual {
+        TOKENIZER_NAME
+    } else {
+        "default"
+    };
+    TextOptions::default().set_stored().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(tokenizer)
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+    )
+}
+
+/// Make the multilingual analyzer available to `index`; required before writing
+/// to or querying an index whose schema names it.
+pub(crate) fn register(index: &Index) {
+    index.tokenizers().register(
+        TOKENIZER_NAME,
+        TextAnalyzer::builder(CjkTokenizer::default())
+            .filter(RemoveLongFilter::limit(40))
+            .filter(LowerCaser)
+            .build(),
+    );
+}
+
+/// Whether the `content` field of an existing index was built for `multilingual`.
+pub(crate) fn schema_matches(schema: &Schema, multilingual: bool) -> bool {
+    let Ok(field) = schema.get_field("content") else {
+        return false;
+    };
+    let tokenizer = match schema.get_field_entry(field).field_type() {
+        tantivy::schema::FieldType::Str(options) => options
+            .get_indexing_options()
+            .map(|indexing| indexing.tokenizer().to_string()),
+        _ => None,
+    };
+    (tokenizer.as_deref() == Some(TOKENIZER_NAME)) == multilingual
+}
+
+/// Han, kana and Hangul: scripts written without spaces between words.
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF      // Hiragana, Katakana
+        | 0x3400..=0x4DBF    // CJK Extension A
+        | 0x4E00..=0x9FFF    // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF    // Hangul syllables
+        | 0xF900..=0xFAFF    // CJK Compatibility Ideographs
+        | 0x20000..=0x2FA1F) // CJK Extensions B onwards
+}
+
+/// Full-width forms of ASCII (`ｒｅｔｒｙ`, `３`) as ASCII, and the
+/// ideographic space as a space.
+fn fold_width(c: char) -> char {
+    match c as u32 {
+        0xFF01..=0xFF5E => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        0x3000 => ' ',
+        _ => c,
+    }
+}
+
+/// Alphanumeric runs as words, with every CJK character a token of its own.
+#[derive(Clone, Default)]
+pub(crate) struct CjkTokenizer {
+    token: Token,
+}
+
+pub(crate) struct CjkTokenStream<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    token: &'a mut Token,
+}
+
+impl Tokenizer for CjkTokenizer {
+    type TokenStream<'a> = CjkTokenStream<'a>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> CjkTokenStream<'a> {
+        self.token.reset();
+        CjkTokenStream {
+            chars: text.char_indices().peekable(),
+            token: &mut self.token,
+        }
+    }
+}
+
+impl TokenStream for CjkTokenStream<'_> {
+    fn advance(&mut self) -> bool {
+        self.token.text.clear();
+        self.token.position = self.token.position.wrapping_add(1);
+        while let Some((start, raw)) = self.chars.next() {
+            let c = fold_width(raw);
+            if !c.is_alphanumeric() {
+                continue;
+            }
+            self.token.text.push(c);
+            let mut end = start + raw.len_utf8();
+            if !is_cjk(c) {
+                while let Some(&(offset, next)) = self.chars.peek() {
+                    let folded = fold_width(next);
+                    if !folded.is_alphanumeric() || is_cjk(folded) {
+                        break;
+                    }
+                    self.token.text.push(folded);
+                    end = offset + next.len_utf8();
+                    self.chars.next();
+                }
+            }
+            self.token.offset_from = start;
+            self.token.offset_to = end;
+            return true;
+        }
+        false
+    }
+
+    fn token(&self) -> &Token {
+        self.token
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.token
+    }
+}
+
+/// Whether `text` is written mostly in a script other than Latin: CJK,
+/// Cyrillic, Arabic and so on.
+pub(crate) fn is_mostly_non_latin(text: &str) -> bool {
+    let (mut latin, mut other) = (0usize, 0usize);
+    for c in text.chars().map(fold_width).filter(|c| c.is_alphabetic()) {
+        if c.is_ascii_alphabetic() || ('\u{00C0}'..='\u{024F}').contains(&c) {
+            latin += 1;
+        } else {
+            other += 1;
+        }
+    }
+    other > latin
+}
+
+/// Whether the embedding model `name` was trained on many languages, so
+/// queries and chunks in different languages can match.
+pub(crate) fn is_multilingual_model(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    ["multilingual", "bge-m3", "jina-embeddings-v3", "labse"]
+        .iter()
+        .any(|marker| name.contains(marker))
+}
+
+/// Models already warned about, so long-running sessions warn once each.
+static WARNED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// Warn when a query in a non-Latin script is about to be embedded by an
+/// English-only model, which matches it to code almost at random.
+pub(crate) fn check_query_language(model_name: &str, query: &str) {
+    if is_multilingual_model(model_name) || !is_mostly_non_latin(query) {
+        return;
+    }
+    if WARNED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(model_name.to_string())
+    {
+        tracing::warn!(
+            "The query is not in English, but {} is an English-only model. For cross-lingual search, \
+             run `ck --multilingual --switch-model multilingual-e5 .`",
+            model_name
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cjk_characters_are_separate_tokens() {
+        let mut tokenizer = CjkTokenizer::default();
+        let mut stream = tokenizer.token_stream("重试逻辑 retry_count=3 では");
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            tokens.push(stream.token().text.clone());
+        }
+        assert_eq!(
+            tokens,
+            ["重", "试", "逻", "辑", "retry", "count", "3", "で", "は"]
+        );
+
+        // Full-width input folds to the ASCII the code is written in, with
+        // offsets into the original text
+        let text = "调用ｒｅｔｒｙ（３）";
+        let mut stream = tokenizer.token_stream(text);
+        let mut tokens = Vec::new();
+        while stream.advance() {
+            let token = stream.token();
+            tokens.push((
+                token.text.clone(),
+                &text[token.offset_from..token.offset_to],
+            ));
+        }
+        assert_eq!(
+            tokens,
+            [
+                ("调".to_string(), "调"),
+                ("用".to_string(), "用"),
+                ("retry".to_string(), "ｒｅｔｒｙ"),
+                ("3".to_string(), "３"),
+            ]
+        );
+
+        let mut builder = Schema::builder();
+        builder.add_text_field("content", content_options(true));
+        let schema = builder.build();
+        assert!(schema_matches(&schema, true));
+        assert!(!schema_matches(&schema, false));
+    }
+
+    #[test]
+    fn test_query_language_detection() {
+        assert!(is_mostly_non_latin("如何重试失败的请求"));
+        assert!(is_mostly_non_latin("リトライ処理 retry"));
+        assert!(is_mostly_non_latin("повторить запрос"));
+        assert!(!is_mostly_non_latin("retry the request"));
+        assert!(!is_mostly_non_latin("réessayer la requête"));
+        assert!(!is_mostly_non_latin("ｒｅｔｒｙ"));
+        assert!(!is_mostly_non_latin("42 == 42"));
+
+        assert!(is_multilingual_model("intfloat/multilingual-e5-small"));
+        assert!(is_multilingual_model("BAAI/bge-m3"));
+        assert!(!is_multilingual_model("BAAI/bge-small-en-v1.5"));
+    }
+}
@@ -0,0 +1,346 @@
+//! Two-model search: the index is embedded with a large model, queries with a
+//! small model distilled into the same vector space, so interactive searches avoid
+//! loading the large model.
+//!
+//! Pairing embeds a fixed probe set with both models and records the vectors as
+//! fingerprints in `.ck/query_model.json`. The small model embeds the probes
+//! again the first time it is used in a process, and every query is checked
+//! against those vectors: the model must still behave as when it was paired
+//! and still be aligned with the index, without touching the large model.
+
+use anyhow::Result;
+use ck_core::CkError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+use super::ResolvedModel;
+use super::model_cache;
+use super::semantic_v3::cosine_similarity;
+
+pub const QUERY_MODEL_FILE: &str = "query_model.json";
+
+/// Mean cosine between query-model and index-model probe embeddings below which
+/// the two models are not treated as sharing a vector space.
+pub const MIN_ALIGNMENT: f32 = 0.85;
+/// Mean cosine between the query model's current and recorded probe embeddings
+/// below which it is treated as a different model.
+const MIN_FINGERPRINT_MATCH: f32 = 0.99;
+
+/// Probe embeddings of each query model used so far, by embedder key.
+static PROBE_VECTORS: LazyLock<Mutex<HashMap<String, Vec<Vec<f32>>>>> =
+    LazyLock::new(Default::default);
+
+const PROBES: &[&str] = &[
+    "retry an HTTP request with exponential backoff",
+    "parse the configuration file and apply defaults",
+    "fn main() { println!(\"hello world\"); }",
+    "class UserRepository:\n    def find_by_email(self, email):",
+    "SELECT id, name FROM users WHERE active = 1",
+];
+
+/// A model's identity plus its embeddings of the probe set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelFingerprint {
+    pub name: String,
+    pub dimensions: usize,
+    pub probes: Vec<Vec<f32>>,
+}
+
+/// Query model recorded for an index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryModelPairing {
+    pub index_model: ModelFingerprint,
+    pub query_model: ModelFingerprint,
+    /// Mean probe cosine between the two models when they were paired
+    pub alignment: f32,
+}
+
+impl QueryModelPairing {
+    /// Check freshly computed query-model probe embeddings against the recorded
+    /// fingerprints; returns the current alignment with the index model.
+    fn validate(&self, probes: &[Vec<f32>]) -> Result<f32> {
+        let stability = mean_cosine(probes, &self.query_model.probes);
+        if stability < MIN_FINGERPRINT_MATCH {
+            return Err(CkError::Embedding(format!(
+                "Query model '{}' no longer matches the fingerprint recorded when it was paired with this index (similarity {:.3}). Re-pair it with `ck --index --query-model {}`.",
+                self.query_model.name, stability, self.query_model.name
+            ))
+            .into());
+        }
+        let alignment = mean_cosine(probes, &self.index_model.probes);
+        if alignment < MIN_ALIGNMENT {
+            return Err(CkError::Embedding(format!(
+                "Query model '{}' is not aligned with index model '{}' (alignment {:.3}, need {:.2}).",
+                self.query_model.name, self.index_model.name, alignment, MIN_ALIGNMENT
+            ))
+            .into());
+        }
+        Ok(alignment)
+    }
+}
+
+/// Mean pairwise cosine; 0.0 when the sets differ in size.
+fn mean_cosine(a: &[Vec<f32>], b: &[Vec<f32>]) -> f32 {
+    if a.is_empty() || a.len() != b.len() {
+        return 0.0;
+    }
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| cosine_similarity(x, y))
+        .sum::<f32>()
+        / a.len() as f32
+}
+
+fn probe_texts() -> Vec<String> {
+    PROBES.iter().map(|probe| probe.to_string()).collect()
+}
+
+/// The query model `name` configured for an index compared with `metric`, as
+/// the index model is.
+fn query_model_config(
+    name: &str,
+    metric: ck_models::SimilarityMetric,
+) -> Result<ck_models::ModelConfig> {
+    let (_, config) = ck_models::ModelRegistry::with_user_models()
+        .resolve(Some(name))
+        .map_err(|e| CkError::Embedding(e.to_string()))?;
+    Ok(config.for_metric(metric))
+}
+
+fn fingerprint(config: &ck_models::ModelConfig) -> Result<ModelFingerprint> {
+    let mut embedder = ck_embed::create_embedder_for_config(config, None)?;
+    Ok(ModelFingerprint {
+        name: config.name.clone(),
+        dimensions: config.dimensions,
+        probes: embedder.embed(&probe_texts())?,
+    })
+}
+
+/// Pair `query_model` with the index covering `path` after checking that both
+/// models produce the same dimensions and that their probe embeddings align.
+pub fn pair_query_model(path: &Path, query_model: &str) -> Result<QueryModelPairing> {
+    let index_root = super::find_nearest_index_root(path).ok_or_else(|| {
+        CkError::Index(format!(
+            "No index found for {}. Run 'ck --index' first.",
+            path.display()
+        ))
+    })?;
+    let index_model = super::resolve_model_from_root(&index_root, None)?;
+    let query_config = query_model_config(query_model, super::index_metric(&index_root))?;
+
+    if query_config.dimensions != index_model.dimensions() {
+        return Err(CkError::Embedding(format!(
+            "Query model '{}' produces {} dimensions but index model '{}' produces {}; a query model must be distilled into the index model's vector space.",
+            query_config.name,
+            query_config.dimensions,
+            index_model.canonical_name(),
+            index_model.dimensions()
+        ))
+        .into());
+    }
+
+    let index_fingerprint = fingerprint(&index_model.config)?;
+    let query_fingerprint = fingerprint(&query_config)?;
+    let alignment = mean_cosine(&query_fingerprint.probes, &index_fingerprint.probes);
+    if alignment < MIN_ALIGNMENT {
+        return Err(CkError::Embedding(format!(
+            "Query model '{}' is not aligned with index model '{}' (alignment {:.3}, need {:.2}); a query model must be distilled into the index model's vector space.",
+            query_config.name,
+            index_model.canonical_name(),
+            alignment,
+            MIN_ALIGNMENT
+        ))
+        .into());
+    }
+
+    let pairing = QueryModelPairing {
+        index_model: index_fingerprint,
+        query_model: query_fingerprint,
+        alignment,
+    };
+    std::fs::write(
+        index_root.join(".ck").join(QUERY_MODEL_FILE),
+        serde_json::to_vec_pretty(&pairing)?,
+    )?;
+    Ok(pairing)
+}
+
+/// Pairing that applies to searches of the index at `index_root`, unless the
+/// caller asked for a specific model.
+pub(crate) fn load_pairing(
+    index_root: &Path,
+    index_model: &ResolvedModel,
+    requested_model: Option<&str>,
+) -> Result<Option<QueryModelPairing>> {
+    let path = index_root.join(".ck").join(QUERY_MODEL_FILE);
+    if requested_model.is_some() || !path.exists() {
+        return Ok(None);
+    }
+    let pairing: QueryModelPairing = serde_json::from_slice(&std::fs::read(&path)?)?;
+    if pairing.index_model.name != index_model.canonical_name() {
+        return Err(CkError::Embedding(format!(
+            "Query model '{}' was paired with index model '{}', but the index now uses '{}'. Re-pair with `ck --index --query-model {}`.",
+            pairing.query_model.name,
+            pairing.index_model.name,
+            index_model.canonical_name(),
+            pairing.query_model.name
+        ))
+        .into());
+    }
+    Ok(Some(pairing))
+}
+
+/// The probe embeddings of the query model `config`, computed on its first use.
+async fn probe_vectors(config: &ck_models::ModelConfig) -> Result<Vec<Vec<f32>>> {
+    let key = model_cache::embedder_key(config);
+    if let Some(probes) = PROBE_VECTORS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&key)
+    {
+        return Ok(probes.clone());
+    }
+    let embedder = model_cache::embedder(config)?;
+    let probes = model_cache::embed_blocking(&embedder, probe_texts()).await?;
+    PROBE_VECTORS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, probes.clone());
+    Ok(probes)
+}
+
+/// Embed `query` with the paired query model when there is one (validating it
+/// against the recorded fingerprints), else with the index model. `metric` is
+/// the index's similarity metric.
+pub(crate) async fn embed_query(
+    index_model: &ResolvedModel,
+    pairing: Option<&QueryModelPairing>,
+    metric: ck_models::SimilarityMetric,
+    query: &str,
+) -> Result<Option<Vec<f32>>> {
+    let Some(pairing) = pairing else {
+        return model_cache::query_vector(&index_model.config, query).await;
+    };
+
+    let config = query_model_config(&pairing.query_model.name, metric)?;
+    pairing.validate(&probe_vectors(&config).await?)?;
+    model_cache::query_vector(&config, query).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairing(index_probes: Vec<Vec<f32>>, query_probes: Vec<Vec<f32>>) -> QueryModelPairing {
+        let fingerprint = |name: &str, probes| ModelFingerprint {
+            name: name.to_string(),
+            dimensions: 2,
+            probes,
+        };
+        QueryModelPairing {
+            index_model: fingerprint("large", index_probes),
+            query_model: fingerprint("small", query_probes),
+            alignment: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_validate_checks_fingerprint_then_alignment() {
+        let aligned = pairing(
+            vec![vec![1.0, 0.0], vec![0.0, 1.0]],
+            vec![vec![1.0, 0.1], vec![0.1, 1.0]],
+        );
+        let alignment = aligned.validate(&[vec![1.0, 0.1], vec![0.1, 1.0]]).unwrap();
+        assert!(alignment > MIN_ALIGNMENT);
+
+        // The query model now behaves differently from when it was paired
+        let err = aligned
+            .validate(&[vec![0.0, 1.0], vec![1.0, 0.0]])
+            .unwrap_err();
+        assert!(err.to_string().contains("no longer matches"));
+
+        // Recorded consistently, but pointing away from the index space
+        let misaligned = pairing(
+            vec![vec![1.0, 0.0], vec![0.0, 1.0]],
+            vec![vec![0.0, 1.0], vec![1.0, 0.0]],
+        );
+        let err = misaligned
+            .validate(&[vec![0.0, 1.0], vec![1.0, 0.0]])
+            .unwrap_err();
+        assert!(err.to_string().contains("not aligned"));
+    }
+
+    #[test]
+    fn test_load_pairing_is_skipped_without_file_or_with_explicit_model() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+        let (alias, config) = ck_models::ModelRegistry::default().resolve(None).unwrap();
+        let model = ResolvedModel { alias, config };
+
+        assert!(
+            load_pairing(temp_dir.path(), &model, None)
+                .unwrap()
+                .is_none()
+        );
+
+        let recorded = pairing(vec![vec![1.0, 0.0]], vec![vec![1.0, 0.0]]);
+        std::fs::write(
+            temp_dir.path().join(".ck").join(QUERY_MODEL_FILE),
+            serde_json::to_vec(&recorded).unwrap(),
+        )
+        .unwrap();
+        assert!(
+            load_pairing(temp_dir.path(), &model, Some("bge-small"))
+                .unwrap()
+                .is_none()
+        );
+        let err = load_pairing(temp_dir.path(), &model, None).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("was paired with index model 'large'")
+        );
+    }
+
+    #[test]
+    fn test_query_model_config_follows_the_index_metric() {
+        use ck_models::SimilarityMetric;
+
+        let config = query_model_config("bge-small", SimilarityMetric::Dot).unwrap();
+        assert!(!config.normalize);
+        let config = query_model_config("bge-small", SimilarityMetric::Cosine).unwrap();
+        assert!(config.normalize);
+    }
+
+    #[test]
+    fn test_instruction_replaces_the_model_default() {
+        let (_, mut config) = ck_models::ModelRegistry::default()
+            .resolve(Some("bge-small"))
+            .unwrap();
+        assert_eq!(config.instructed_query("retry", None), "retry");
+
+        config.query_instruction = Some("search_query:".to_string());
+        assert_eq!(
+            config.instructed_query("retry", None),
+            "search_query: retry"
+        );
+        assert_eq!(
+            config.instructed_query("retry", Some("Find code for this question")),
+            "Find code for this question: retry"
+        );
+        assert_eq!(config.instructed_query("retry", Some("")), "retry");
+        assert_eq!(config.instructed_document("fn retry() {}"), "fn retry() {}");
+
+        // E5 embeds queries and passages with their own prefixes, which keeps
+        // questions in one language close to code commented in another
+        let (_, e5) = ck_models::ModelRegistry::default()
+            .resolve(Some(ck_models::MULTILINGUAL_MODEL))
+            .unwrap();
+        assert_eq!(e5.instructed_query("如何重试", None), "query: 如何重试");
+        assert_eq!(
+            e5.instructed_document("// Retry with backoff"),
+            "passage: // Retry with backoff"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Query rewriting through an external command.
+//!
+//! `"query_rewriter": "<command>"` in the user's `config.json` has the raw
+//! query piped through a program before it is embedded, e.g. a script that
+//! asks an LLM for a hypothetical answer (HyDE) or adds synonyms. A project's
+//! `.ckconfig.json` may set one too, but it only runs with
+//! `CK_TRUST_PROJECT=1`: otherwise searching a freshly cloned repository
+//! would run whatever command the repository chose.
+//! The command runs through the shell with the query on stdin (and in
+//! `CK_QUERY`); its trimmed stdout is what gets embedded. Only the embedding
+//! text changes: keyword matching, reranking and the reported query keep the
+//! original. A command that fails, times out or prints nothing leaves the
+//! query as it was, so a broken hook degrades search instead of stopping it.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the command may run before the raw query is used instead.
+const REWRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Rewrites kept before the cache is cleared.
+const REWRITE_CAPACITY: usize = 256;
+
+/// Command and raw query.
+type RewriteKey = (String, String);
+
+static REWRITES: LazyLock<Mutex<HashMap<RewriteKey, String>>> = LazyLock::new(Default::default);
+
+/// Run `command` with `query` on stdin and return its trimmed stdout.
+fn run_rewriter(command: &str, query: &str, timeout: Duration) -> Result<String, String> {
+    let mut child = ck_core::shell::command(command)
+        .env("CK_QUERY", query)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not start: {e}"))?;
+
+    // Feed stdin and drain the pipes on their own threads so a command that
+    // ignores its input or prints a lot cannot deadlock against us
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = query.to_string();
+    let writer = std::thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as _));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", timeout.as_secs()));
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+    let _ = writer.join();
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(format!("{status}: {}", stderr.trim()));
+    }
+    let rewritten = String::from_utf8_lossy(&stdout).trim().to_string();
+    if rewritten.is_empty() {
+        return Err("printed nothing".to_string());
+    }
+    Ok(rewritten)
+}
+
+/// `query` as `command` rewrites it, or `query` itself when the command fails.
+/// Results are kept for the life of the process, so a query embedded for
+/// several indexes or searched again runs the command once.
+fn rewrite_with(command: &str, query: &str, timeout: Duration) -> String {
+    let key = (command.to_string(), query.to_string());
+    if let Some(rewritten) = REWRITES.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return rewritten.clone();
+    }
+
+    let rewritten = match run_rewriter(command, query, timeout) {
+        Ok(rewritten) => rewritten,
+        Err(e) => {
+            tracing::warn!(
+                "query_rewriter '{}' {}; using the query as typed",
+                command,
+                e
+            );
+            query.to_string()
+        }
+    };
+    let mut rewrites = REWRITES.lock().unwrap_or_else(|e| e.into_inner());
+    if rewrites.len() >= REWRITE_CAPACITY {
+        rewrites.clear();
+    }
+    rewrites.insert(key, rewritten.clone());
+    rewritten
+}
+
+/// The text to embed for `query` when searching the index at `index_root`:
+/// the output of the user's `query_rewriter`, or of the project's when it is
+/// trusted, or `query` when neither is set.
+pub(crate) fn rewrite_query(index_root: &Path, query: &str) -> String {
+    let project = ck_models::ProjectConfig::for_path(index_root);
+    if project.query_rewriter.is_some() && !ck_models::project_trusted() {
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        WARNED.call_once(|| {
+            tracing::warn!(
+                "Ignoring the query_rewriter in {}: project commands run only with {}=1",
+                ck_models::PROJECT_CONFIG_FILE,
+                ck_models::TRUST_PROJECT_ENV
+            )
+        });
+    }
+    match project.trusted().query_rewriter {
+        Some(command) if !command.trim().is_empty() && !query.trim().is_empty() => {
+            rewrite_with(&command, query, REWRITE_TIMEOUT)
+        }
+        _ => query.to_string(),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_query_is_piped_through_the_command() {
+        assert_eq!(
+            rewrite_with("tr a-z A-Z", "retry with backoff", REWRITE_TIMEOUT),
+            "RETRY WITH BACKOFF"
+        );
+        assert_eq!(
+            rewrite_with(
+                "printf '%s\\nexponential backoff\\n' \"$CK_QUERY\"",
+                "retry",
+                REWRITE_TIMEOUT
+            ),
+            "retry\nexponential backoff"
+        );
+
+        // Failures fall back to the query as typed
+        assert_eq!(rewrite_with("exit 3", "retry", REWRITE_TIMEOUT), "retry");
+        assert_eq!(rewrite_with("true", "retry", REWRITE_TIMEOUT), "retry");
+        assert_eq!(
+            rewrite_with("sleep 5", "retry", Duration::from_millis(100)),
+            "retry"
+        );
+    }
+
+    #[test]
+    fn test_project_rewriter_needs_trust() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let marker = root.join("ran");
+        let project = ck_models::ProjectConfig {
+            query_rewriter: Some(format!("touch {}; echo pwned", marker.display())),
+            ..Default::default()
+        };
+        project
+            .save(&root.join(ck_models::PROJECT_CONFIG_FILE))
+            .unwrap();
+        assert_eq!(rewrite_query(root, "parse config"), "parse config");
+        assert!(!marker.exists());
+
+        let user = ck_models::UserConfig {
+            query_rewriter: Some("sed 's/config/configuration/'".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            project.trusted_with(user.clone(), false).query_rewriter,
+            user.query_rewriter
+        );
+        assert_eq!(
+            project
+                .trusted_with(Default::default(), true)
+                .query_rewriter,
+            project.query_rewriter
+        );
+        assert_eq!(
+            project
+                .trusted_with(Default::default(), false)
+                .query_rewriter,
+            None
+        );
+    }
+}
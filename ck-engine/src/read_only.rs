@@ -0,0 +1,200 @@
+//! `--read-only`: search indexes as they are, for indexes on network shares or
+//! owned by someone else. Nothing is written: indexes are not built or
+//! updated, usage statistics are not recorded, and a missing or stale lexical
+//! index is built in memory. Tantivy's lock files are not created either.
+//!
+//! Without it, an index directory the user cannot write to is reported up
+//! front, pointing at `--read-only`, instead of failing partway through an
+//! update.
+
+use anyhow::Result;
+use ck_core::CkError;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tantivy::directory::error::{
+    DeleteError, LockError, OpenDirectoryError, OpenReadError, OpenWriteError,
+};
+use tantivy::directory::{
+    Directory, DirectoryLock, FileHandle, Lock, MmapDirectory, WatchCallback, WatchHandle, WritePtr,
+};
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Search without writing anything, for the rest of the process.
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// The index `path` would be searched in must exist and be readable, since
+/// read-only mode will not build it.
+pub(crate) fn check_readable(path: &Path, index_root: Option<&Path>) -> Result<()> {
+    let Some(index_root) = index_root else {
+        return Err(CkError::Index(format!(
+            "No index found for {}; --read-only only searches existing indexes",
+            path.display()
+        ))
+        .into());
+    };
+    let manifest = index_root.join(".ck").join("manifest.json");
+    std::fs::File::open(&manifest)
+        .map_err(|e| CkError::Index(format!("Cannot read index {}: {}", manifest.display(), e)))?;
+    Ok(())
+}
+
+/// An existing index directory must be writable before it is updated.
+pub(crate) fn check_writable(index_root: &Path) -> Result<()> {
+    let index_dir = index_root.join(".ck");
+    if !index_dir.is_dir() {
+        return Ok(());
+    }
+    let probe = index_dir.join(format!(".write-probe.{}", std::process::id()));
+    let created = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe);
+    match created {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Ok(())
+        }
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+            ) =>
+        {
+            Err(CkError::Index(format!(
+                "Index {} is not writable ({}); search it as it is with --read-only",
+                index_dir.display(),
+                e
+            ))
+            .into())
+        }
+        // Anything else is left for the update itself to report
+        Err(_) => Ok(()),
+    }
+}
+
+/// A Tantivy directory that refuses writes and takes its locks without lock
+/// files, so searching never touches the index.
+#[derive(Debug, Clone)]
+pub(crate) struct ReadOnlyDirectory {
+    inner: MmapDirectory,
+    root: PathBuf,
+}
+
+impl ReadOnlyDirectory {
+    pub(crate) fn open(path: &Path) -> std::result::Result<Self, OpenDirectoryError> {
+        Ok(Self {
+            inner: MmapDirectory::open(path)?,
+            root: path.to_path_buf(),
+        })
+    }
+
+    fn refused(&self, path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is opened read-only", self.root.join(path).display()),
+        )
+    }
+}
+
+impl Directory for ReadOnlyDirectory {
+    fn get_file_handle(
+        &self,
+        path: &Path,
+    ) -> std::result::Result<Arc<dyn FileHandle>, OpenReadError> {
+        self.inner.get_file_handle(path)
+    }
+
+    fn delete(&self, path: &Path) -> std::result::Result<(), DeleteError> {
+        Err(DeleteError::IoError {
+            io_error: Arc::new(self.refused(path)),
+            filepath: path.to_path_buf(),
+        })
+    }
+
+    fn exists(&self, path: &Path) -> std::result::Result<bool, OpenReadError> {
+        self.inner.exists(path)
+    }
+
+    fn open_write(&self, path: &Path) -> std::result::Result<WritePtr, OpenWriteError> {
+        Err(OpenWriteError::wrap_io_error(
+            self.refused(path),
+            path.to_path_buf(),
+        ))
+    }
+
+    fn atomic_read(&self, path: &Path) -> std::result::Result<Vec<u8>, OpenReadError> {
+        self.inner.atomic_read(path)
+    }
+
+    fn atomic_write(&self, path: &Path, _data: &[u8]) -> io::Result<()> {
+        Err(self.refused(path))
+    }
+
+    fn sync_directory(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn acquire_lock(&self, _lock: &Lock) -> std::result::Result<DirectoryLock, LockError> {
+        // Readers only lock against a concurrent writer's commit; a reader
+        // that can't write a lock file goes without
+        Ok(DirectoryLock::from(Box::new(())))
+    }
+
+    fn watch(&self, watch_callback: WatchCallback) -> tantivy::Result<WatchHandle> {
+        self.inner.watch(watch_callback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_only_directory_refuses_writes() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("meta.json"), b"{}").unwrap();
+        let directory = ReadOnlyDirectory::open(temp_dir.path()).unwrap();
+
+        assert_eq!(
+            directory.atomic_read(Path::new("meta.json")).unwrap(),
+            b"{}"
+        );
+        assert!(
+            directory
+                .atomic_write(Path::new("meta.json"), b"[]")
+                .is_err()
+        );
+        assert!(directory.open_write(Path::new("new.idx")).is_err());
+        assert!(directory.delete(Path::new("meta.json")).is_err());
+        directory
+            .acquire_lock(&tantivy::directory::META_LOCK)
+            .unwrap();
+        let mut entries: Vec<_> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec!["meta.json"]);
+    }
+
+    #[test]
+    fn test_check_readable_requires_an_index() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(check_readable(temp_dir.path(), None).is_err());
+        std::fs::create_dir_all(temp_dir.path().join(".ck")).unwrap();
+        assert!(check_readable(temp_dir.path(), Some(temp_dir.path())).is_err());
+        std::fs::write(temp_dir.path().join(".ck/manifest.json"), b"{}").unwrap();
+        assert!(check_readable(temp_dir.path(), Some(temp_dir.path())).is_ok());
+        assert!(check_writable(temp_dir.path()).is_ok());
+    }
+}
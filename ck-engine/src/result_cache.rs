@@ -0,0 +1,322 @@
+//! In-process cache of search results for long-lived hosts (MCP server, TUI).
+//!
+//! Entries are keyed by `(index root, index generation, query hash, params hash)`.
+//! The generation comes from the index manifest and advances on every index write,
+//! so a changed index simply stops matching old keys; stale entries for that root
+//! are dropped on the next insert.
+
+use ck_core::{SearchMode, SearchOptions, SearchResults};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Default number of result lists kept per cache.
+pub const DEFAULT_RESULT_CACHE_CAPACITY: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    root: PathBuf,
+    generation: u64,
+    query_hash: u64,
+    params_hash: u64,
+}
+
+struct CacheEntry {
+    results: SearchResults,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResultCacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+pub struct ResultCache {
+    state: Mutex<CacheState>,
+    capacity: usize,
+}
+
+impl Default for ResultCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_RESULT_CACHE_CAPACITY)
+    }
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(CacheState::default()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Look up cached results for `options` against the current index generation.
+    pub fn get(&self, options: &SearchOptions) -> Option<SearchResults> {
+        let key = cache_key(options)?;
+        let mut state = self.state.lock().ok()?;
+        state.clock += 1;
+        let now = state.clock;
+        match state.entries.get_mut(&key) {
+            Some(entry) => {
+                entry.last_used = now;
+                let results = entry.results.clone();
+                state.hits += 1;
+                Some(results)
+            }
+            None => {
+                state.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Store results for `options`. Call after the search so the key reflects any
+    /// index update the search itself performed.
+    pub fn insert(&self, options: &SearchOptions, results: &SearchResults) {
+        let Some(key) = cache_key(options) else {
+            return;
+        };
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        // Entries from older generations of this index can never hit again
+        state
+            .entries
+            .retain(|k, _| k.root != key.root || k.generation == key.generation);
+
+        if state.entries.len() >= self.capacity
+            && !state.entries.contains_key(&key)
+            && let Some(oldest) = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(k, _)| k.clone())
+        {
+            state.entries.remove(&oldest);
+        }
+
+        state.clock += 1;
+        let last_used = state.clock;
+        state.entries.insert(
+            key,
+            CacheEntry {
+                results: results.clone(),
+                last_used,
+            },
+        );
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.entries.clear();
+        }
+    }
+
+    pub fn stats(&self) -> ResultCacheStats {
+        self.state
+            .lock()
+            .map(|state| ResultCacheStats {
+                entries: state.entries.len(),
+                hits: state.hits,
+                misses: state.misses,
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Only index-backed modes are cacheable: regex (and the regex half of hybrid)
+/// reads live files, which the manifest generation does not track.
+fn cache_key(options: &SearchOptions) -> Option<CacheKey> {
+    if options.reindex
+        || !matches!(
+            options.mode,
+            SearchMode::Semantic | SearchMode::Lexical | SearchMode::Symbol
+        )
+    {
+        return None;
+    }
+    let root = crate::find_nearest_index_root(&options.path)?;
+    let generation = ck_index::read_index_generation(&root)?;
+
+    Some(CacheKey {
+        root,
+        generation,
+        query_hash: hash_value(&options.query),
+        params_hash: params_hash(options),
+    })
+}
+
+fn params_hash(options: &SearchOptions) -> u64 {
+    let include: Vec<_> = options
+        .include_patterns
+        .iter()
+        .map(|p| (&p.path, p.is_dir))
+        .collect();
+    let feedback: Vec<_> = [&options.feedback.pinned, &options.feedback.banned]
+        .iter()
+        .map(|list| {
+            list.iter()
+                .map(|(file, span)| (file, span.line_start, span.line_end))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    hash_value(&(
+        format!("{:?}", options.mode),
+        &options.path,
+        options.top_k,
+        options.threshold.map(f32::to_bits),
+        (
+            options.case_insensitive,
+            options.whole_word,
+            options.fixed_string,
+            options.full_section,
+        ),
+        (
+            options.context_lines,
+            options.before_context_lines,
+            options.after_context_lines,
+        ),
+        (&options.exclude_patterns, include, &options.languages),
+        (
+            options.respect_gitignore,
+            options.use_ckignore,
+            options.follow_symlinks,
+            feedback,
+        ),
+        (
+            options.rerank,
+            &options.rerank_model,
+            &options.embedding_model,
+            options.hyde,
+        ),
+        (options.top_files, options.by_file, &options.as_of),
+        (options.score_normalization, options.prefer),
+        (
+            options.blame,
+            &options.owner,
+            &options.instruction,
+            &options.meta,
+            options.json_output || options.jsonl_output,
+        ),
+    ))
+}
+
+fn hash_value<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::SearchResult;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write_manifest(root: &std::path::Path, generation: u64) {
+        fs::create_dir_all(root.join(".ck")).unwrap();
+        fs::write(
+            root.join(".ck").join("manifest.json"),
+            format!(
+                r#"{{"version":"0.1.0","created":0,"updated":0,"files":{{}},"embedding_model":null,"embedding_dimensions":null,"generation":{}}}"#,
+                generation
+            ),
+        )
+        .unwrap();
+    }
+
+    fn results(file: &str) -> SearchResults {
+        SearchResults {
+            matches: vec![SearchResult::new(
+                file,
+                ck_core::Span::new_unchecked(0, 1, 1, 1),
+                0.9,
+                "",
+            )],
+            closest_below_threshold: None,
+        }
+    }
+
+    #[test]
+    fn test_result_cache_hits_until_generation_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        write_manifest(temp_dir.path(), 1);
+
+        let cache = ResultCache::new(4);
+        let options = SearchOptions {
+            mode: SearchMode::Semantic,
+            query: "auth".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        assert!(cache.get(&options).is_none());
+        cache.insert(&options, &results("a.rs"));
+        let hit = cache.get(&options).expect("cached");
+        assert_eq!(hit.matches[0].file, PathBuf::from("a.rs"));
+
+        // Different params miss
+        let other = SearchOptions {
+            top_k: Some(3),
+            ..options.clone()
+        };
+        assert!(cache.get(&other).is_none());
+
+        // Index rewritten: old entry no longer matches
+        write_manifest(temp_dir.path(), 2);
+        assert!(cache.get(&options).is_none());
+        cache.insert(&options, &results("b.rs"));
+        assert_eq!(cache.stats().entries, 1);
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 3);
+    }
+
+    #[test]
+    fn test_result_cache_skips_regex_and_evicts_lru() {
+        let temp_dir = TempDir::new().unwrap();
+        write_manifest(temp_dir.path(), 1);
+
+        let cache = ResultCache::new(2);
+        let base = SearchOptions {
+            mode: SearchMode::Lexical,
+            path: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let regex = SearchOptions {
+            mode: SearchMode::Regex,
+            ..base.clone()
+        };
+        cache.insert(&regex, &results("r.rs"));
+        assert!(cache.get(&regex).is_none());
+
+        let q = |query: &str| SearchOptions {
+            query: query.to_string(),
+            ..base.clone()
+        };
+        cache.insert(&q("one"), &results("1.rs"));
+        cache.insert(&q("two"), &results("2.rs"));
+        assert!(cache.get(&q("one")).is_some());
+        cache.insert(&q("three"), &results("3.rs"));
+
+        assert!(cache.get(&q("one")).is_some());
+        assert!(cache.get(&q("two")).is_none());
+        assert!(cache.get(&q("three")).is_some());
+    }
+}
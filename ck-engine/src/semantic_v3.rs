@@ -1,13 +1,27 @@
 use anyhow::Result;
-use ck_core::{CkError, SearchOptions, SearchResult};
-use std::path::Path;
-use walkdir::WalkDir;
+use ck_core::{
+    CkError, DuplicateLocation, ScoreExplanation, ScoreTransform, SearchOptions, SearchResult,
+};
+use ck_embed::Document;
+use ck_models::SimilarityMetric;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+use super::ann::{AnnGraph, load_current};
+use super::feedback::{FeedbackTargets, rocchio};
+use super::file_vectors::load_file_vectors;
+use super::model_cache;
+use super::query_model::{embed_query, load_pairing};
 use super::{
     SearchProgressCallback, extract_content_from_span, find_nearest_index_root,
     resolve_model_from_root,
 };
 
+/// Candidates scored per reranker call under a budget, which is checked between calls.
+const RERANK_BATCH_SIZE: usize = 16;
+
 /// New semantic search implementation using span-based storage
 pub async fn semantic_search_v3(options: &SearchOptions) -> Result<ck_core::SearchResults> {
     semantic_search_v3_with_progress(options, None).await
@@ -34,49 +48,27 @@ pub async fn semantic_search_v3_with_progress(
         .into());
     }
 
-    if let Some(ref callback) = progress_callback {
-        callback("Loading embeddings from sidecar files...");
-    }
-
-    // Collect all sidecar files and their embeddings
-    let mut file_chunks: Vec<(std::path::PathBuf, ck_index::ChunkEntry)> = Vec::new();
-
-    for entry in WalkDir::new(&index_dir) {
-        let entry = entry?;
-        if entry.file_type().is_file() {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("ck") {
-                // Load the sidecar file
-                if let Ok(index_entry) = ck_index::load_index_entry(path) {
-                    let original_file = reconstruct_original_path(path, &index_dir, &index_root);
-                    if let Some(original_file) = original_file {
-                        if !super::path_matches_include(&original_file, &options.include_patterns) {
-                            continue;
-                        }
-                        for chunk in index_entry.chunks {
-                            if chunk.embedding.is_some() {
-                                file_chunks.push((original_file.clone(), chunk));
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    // An index built without embeddings has nothing to score; say so before
+    // loading a model
+    let manifest = std::fs::read(index_dir.join("manifest.json"))
+        .ok()
+        .and_then(|data| serde_json::from_slice::<ck_index::IndexManifest>(&data).ok());
+    if manifest
+        .as_ref()
+        .is_some_and(|manifest| manifest.embedding_model.is_none())
+    {
+        return Err(no_embeddings_error());
     }
+    let metric = manifest.map(|manifest| manifest.metric).unwrap_or_default();
 
-    if file_chunks.is_empty() {
-        return Err(CkError::Index(
-            "No embeddings found. Run 'ck --index' first with embeddings.".to_string(),
-        )
-        .into());
-    }
+    // Read one generation throughout, even if an update in this process
+    // replaces sidecars meanwhile
+    let generation = ck_index::pin_generation(&index_root);
 
-    if let Some(ref callback) = progress_callback {
-        callback(&format!(
-            "Found {} chunks with embeddings",
-            file_chunks.len()
-        ));
-    }
+    // Pinned and banned chunks steer the query before any shard is scored
+    let feedback = FeedbackTargets::new(&options.feedback);
+    let (pinned_embeddings, banned_embeddings) =
+        feedback_embeddings(&feedback, &index_root, &generation);
 
     // Create embedder and embed the query
     if let Some(ref callback) = progress_callback {
@@ -101,76 +93,230 @@ pub async fn semantic_search_v3_with_progress(
         }
     }
 
-    let mut embedder = ck_embed::create_embedder_for_config(&resolved_model.config, None)?;
-    let query_embeddings = embedder.embed(std::slice::from_ref(&options.query))?;
+    let pairing = load_pairing(
+        &index_root,
+        &resolved_model,
+        options.embedding_model.as_deref(),
+    )?;
+    if let (Some(callback), Some(pairing)) = (&progress_callback, &pairing) {
+        callback(&format!(
+            "Embedding query with paired model {} (alignment {:.3})",
+            pairing.query_model.name, pairing.alignment
+        ));
+    }
 
-    if query_embeddings.is_empty() {
+    super::multilingual::check_query_language(resolved_model.canonical_name(), &options.query);
+    let query_text = super::query_rewrite::rewrite_query(&index_root, &options.query);
+    if query_text != options.query
+        && let Some(ref callback) = progress_callback
+    {
+        callback(&format!("Query rewritten for embedding: {}", query_text));
+    }
+    let query_text = resolved_model
+        .config
+        .instructed_query(&query_text, options.instruction.as_deref());
+    let Some(mut query_vector) =
+        embed_query(&resolved_model, pairing.as_ref(), metric, &query_text).await?
+    else {
         return Ok(ck_core::SearchResults {
             matches: Vec::new(),
             closest_below_threshold: None,
         });
+    };
+
+    if options.hyde {
+        if let Some(ref callback) = progress_callback {
+            callback("Drafting a hypothetical snippet for the query...");
+        }
+        let draft = super::hyde::draft_snippet(&index_root, &options.query)?;
+        tracing::debug!("HyDE draft for {:?}:\n{}", options.query, draft);
+        if let Some(hypothetical) =
+            embed_query(&resolved_model, pairing.as_ref(), metric, &draft).await?
+        {
+            query_vector = super::hyde::blend(
+                &query_vector,
+                &hypothetical,
+                super::hyde::weight(&index_root),
+            );
+        }
     }
 
-    let query_embedding = &query_embeddings[0];
+    let query_embedding = if pinned_embeddings.is_empty() && banned_embeddings.is_empty() {
+        query_vector
+    } else {
+        if let Some(ref callback) = progress_callback {
+            callback(&format!(
+                "Adjusting query with {} pinned and {} banned chunks...",
+                pinned_embeddings.len(),
+                banned_embeddings.len()
+            ));
+        }
+        let pinned: Vec<&[f32]> = pinned_embeddings.iter().map(Vec::as_slice).collect();
+        let banned: Vec<&[f32]> = banned_embeddings.iter().map(Vec::as_slice).collect();
+        rocchio(&query_vector, &pinned, &banned)
+    };
 
     if let Some(ref callback) = progress_callback {
-        callback("Computing similarity scores...");
+        callback("Scoring shards...");
     }
 
-    // Compute similarities
-    let mut similarities: Vec<(f32, &std::path::PathBuf, &ck_index::ChunkEntry)> = Vec::new();
+    // Fan out: each shard keeps its own best `limit` chunks, which always
+    // include its share of the overall best `limit`
+    let limit = options.top_k.unwrap_or(usize::MAX);
+    let scoring = Scoring {
+        options,
+        feedback: &feedback,
+        query: &query_embedding,
+        metric,
+        generation: &generation,
+    };
+    // Restrict results to a specific file or directory when one was given
+    let target = options
+        .path
+        .canonicalize()
+        .unwrap_or_else(|_| options.path.clone());
+    let passes_path_filter = |file: &std::path::PathBuf| {
+        if options.path.is_file() {
+            file.canonicalize().unwrap_or_else(|_| file.clone()) == target
+        } else if options.path != Path::new(".") {
+            file.canonicalize()
+                .unwrap_or_else(|_| file.clone())
+                .starts_with(&target)
+        } else {
+            true
+        }
+    };
 
-    for (file_path, chunk) in &file_chunks {
-        if let Some(ref embedding) = chunk.embedding {
-            let similarity = cosine_similarity(query_embedding, embedding);
-            similarities.push((similarity, file_path, chunk));
+    let shards = ck_index::list_shards(&index_dir)?;
+    // Two-stage and file-granularity searches rank files first
+    let file_stage = options.by_file || options.top_files.is_some();
+    let graph = if !file_stage
+        && options.top_k.is_some()
+        && options.include_patterns.is_empty()
+        && options.languages.is_empty()
+        && searches_whole_index(&options.path, &index_root)
+    {
+        load_current(&index_root)
+    } else {
+        None
+    };
+    let graph_hits = match &graph {
+        Some(graph) => {
+            let _span =
+                tracing::debug_span!("ann_search", graph = true, topk = ?options.top_k).entered();
+            score_graph_candidates(graph, &index_root, &scoring, limit)?
         }
+        None => Vec::new(),
+    };
+    // A graph search that finds nothing falls back to scanning every shard
+    let used_graph = !graph_hits.is_empty();
+    let (scanned, similarities) = if used_graph {
+        (graph_hits.len(), graph_hits)
+    } else if file_stage {
+        let _span = tracing::debug_span!("file_stage", top_files = ?options.top_files).entered();
+        let stored = load_file_vectors(&index_root)?;
+        let total = stored.len();
+        let files = rank_files(
+            stored,
+            &query_embedding,
+            metric,
+            options,
+            limit,
+            |relative| {
+                let file = index_root.join(relative);
+                super::path_matches_include(&file, &options.include_patterns)
+                    && passes_path_filter(&file)
+            },
+        );
+        if let Some(ref callback) = progress_callback {
+            callback(&format!(
+                "Ranked {} files by their mean vector; scoring chunks in the best {}",
+                total,
+                files.len()
+            ));
+        }
+        let hits = score_files(&files, &index_root, &scoring);
+        let hits = if options.by_file {
+            best_chunk_per_file(&files, &index_root, hits)
+        } else {
+            let mut hits = hits;
+            keep_best(&mut hits, limit);
+            hits
+        };
+        (total, hits)
+    } else {
+        let _span =
+            tracing::debug_span!("ann_search", shards = shards.len(), topk = ?options.top_k)
+                .entered();
+        let shard_hits: Vec<ShardHits> = shards
+            .par_iter()
+            .map(|shard| scan_shard(shard, &index_dir, &index_root, &scoring, limit))
+            .collect();
+        let scanned: usize = shard_hits.iter().map(|shard| shard.scanned).sum();
+        let mut similarities: Vec<Hit> = shard_hits
+            .into_iter()
+            .flat_map(|shard| shard.hits)
+            .collect();
+        keep_best(&mut similarities, limit);
+        (scanned, similarities)
+    };
+
+    if let Some(cancel) = &options.cancel {
+        cancel.check()?;
+    }
+    if scanned == 0 {
+        return Err(no_embeddings_error());
     }
 
-    // Sort by similarity (highest first)
-    similarities.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    if let Some(ref callback) = progress_callback
+        && used_graph
+    {
+        callback(&format!(
+            "Scored {} candidate chunks from the HNSW graph",
+            scanned
+        ));
+    } else if let Some(ref callback) = progress_callback
+        && !file_stage
+    {
+        callback(&format!(
+            "Scored {} chunks with embeddings across {} shards",
+            scanned,
+            shards.len()
+        ));
+    }
 
-    // Apply threshold and top_k filtering
+    // Apply threshold filtering
     let mut results = Vec::new();
     let mut closest_below_threshold: Option<SearchResult> = None;
-    let limit = options.top_k.unwrap_or(similarities.len());
 
-    for (similarity, file_path, chunk) in similarities.into_iter().take(limit) {
+    for (similarity, file_path, chunk) in similarities {
         let is_below_threshold = options
             .threshold
             .is_some_and(|threshold| similarity < threshold);
 
-        // Check if we're filtering by a specific file or directory (apply to both above/below threshold)
-        let passes_path_filter = if options.path.is_file() {
-            let target_file = options
-                .path
-                .canonicalize()
-                .unwrap_or_else(|_| options.path.clone());
-            let result_file = file_path
-                .canonicalize()
-                .unwrap_or_else(|_| file_path.clone());
-            result_file == target_file
-        } else if options.path != Path::new(".") {
-            // Filter by directory path - only include files within the specified directory
-            let target_dir = options
-                .path
-                .canonicalize()
-                .unwrap_or_else(|_| options.path.clone());
-            let result_file = file_path
-                .canonicalize()
-                .unwrap_or_else(|_| file_path.clone());
-            result_file.starts_with(&target_dir)
-        } else {
-            true
-        };
-
-        if !passes_path_filter {
+        // Report the chunk at its first location that passes the include and path
+        // filters (applied to both above/below threshold); other copies ride along
+        let mut locations = std::iter::once((file_path.clone(), chunk.span.clone()))
+            .chain(
+                chunk
+                    .aliases
+                    .iter()
+                    .map(|alias| (index_root.join(&alias.path), alias.span.clone())),
+            )
+            .filter(|(file, _)| {
+                super::path_matches_include(file, &options.include_patterns)
+                    && passes_path_filter(file)
+            });
+        let Some((file, span)) = locations.next() else {
             continue;
-        }
+        };
+        let duplicates = locations
+            .map(|(file, span)| DuplicateLocation { file, span })
+            .collect();
 
         // Extract content from the file using the span, skip if file doesn't exist
         let content = if options.full_section {
-            match extract_content_from_span(file_path, &chunk.span).await {
+            match extract_content_from_span(&file, &span).await {
                 Ok(content) => content,
                 Err(_) => {
                     // Skip files that no longer exist (stale index entries)
@@ -178,7 +324,7 @@ pub async fn semantic_search_v3_with_progress(
                 }
             }
         } else {
-            match extract_content_from_span(file_path, &chunk.span).await {
+            match extract_content_from_span(&file, &span).await {
                 Ok(full_content) => {
                     // Take first 3 lines for preview
                     full_content.lines().take(3).collect::<Vec<_>>().join("\n")
@@ -191,14 +337,27 @@ pub async fn semantic_search_v3_with_progress(
         };
 
         let search_result = SearchResult {
-            file: file_path.clone(),
-            span: chunk.span.clone(),
+            lang: chunk
+                .language
+                .or_else(|| ck_core::Language::from_path(&file)),
+            file,
+            span,
             score: similarity,
             preview: content,
-            lang: ck_core::Language::from_path(file_path),
             symbol: None,
             chunk_hash: None,
             index_epoch: None,
+            duplicates,
+            explain: Some(ScoreExplanation {
+                dense_score: Some(similarity / facet_weight(&chunk, options.prefer)),
+                facet_weight: options.prefer.map(|_| facet_weight(&chunk, options.prefer)),
+                ..ScoreExplanation::new(
+                    if options.by_file { "file" } else { "semantic" },
+                    similarity,
+                )
+            }),
+            blame: None,
+            metadata: Default::default(),
         };
 
         if is_below_threshold {
@@ -217,49 +376,46 @@ pub async fn semantic_search_v3_with_progress(
         if let Some(ref callback) = progress_callback {
             callback("Reranking results for improved relevance...");
         }
+        let deadline = options.rerank_budget.map(|budget| Instant::now() + budget);
 
         let rerank_registry = ck_models::RerankModelRegistry::default();
-        let (rerank_alias, rerank_config) = rerank_registry
+        let (rerank_alias, mut rerank_config) = rerank_registry
             .resolve(options.rerank_model.as_deref())
             .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        // Scores are transformed once over every reranked candidate, so batches
+        // cut short by the budget still share one scale
+        let transform = options
+            .rerank_scores
+            .unwrap_or(rerank_config.score_transform);
+        rerank_config.score_transform = ScoreTransform::Raw;
 
-        match ck_embed::create_reranker_for_config(&rerank_config, None) {
-            Ok(mut reranker) => {
+        match model_cache::reranker(&rerank_config) {
+            Ok(reranker) => {
                 if let Some(ref callback) = progress_callback {
                     callback(&format!("Reranking results with model {}", rerank_alias));
                 }
 
-                let documents: Vec<String> = results.iter().map(|r| r.preview.clone()).collect();
-
-                match reranker.rerank(&options.query, &documents) {
-                    Ok(rerank_results) => {
-                        // Create a map from document text to indices for handling duplicates
-                        let mut doc_to_indices: std::collections::HashMap<String, Vec<usize>> =
-                            std::collections::HashMap::new();
-                        for (i, result) in results.iter().enumerate() {
-                            doc_to_indices
-                                .entry(result.preview.clone())
-                                .or_default()
-                                .push(i);
+                let candidates = options
+                    .rerank_top
+                    .map_or(results.len(), |top| top.min(results.len()));
+                let mut reranker = reranker.lock().unwrap_or_else(|e| e.into_inner());
+                match rerank_leading(
+                    reranker.as_mut(),
+                    &options.query,
+                    &mut results,
+                    candidates,
+                    transform,
+                    deadline,
+                ) {
+                    Ok(reranked) => {
+                        if reranked < candidates {
+                            tracing::info!(
+                                "Rerank budget reached after {} of {} candidates; the rest keep their dense order",
+                                reranked,
+                                candidates
+                            );
                         }
 
-                        // Update results with reranked scores
-                        // The reranker returns results in reranked order, so we match by document text
-                        for rerank_result in rerank_results.iter() {
-                            if let Some(indices) = doc_to_indices.get_mut(&rerank_result.document)
-                                && let Some(idx) = indices.pop()
-                            {
-                                results[idx].score = rerank_result.score;
-                            }
-                        }
-
-                        // Re-sort by reranked scores
-                        results.sort_by(|a, b| {
-                            b.score
-                                .partial_cmp(&a.score)
-                                .unwrap_or(std::cmp::Ordering::Equal)
-                        });
-
                         // Apply top_k limit again after reranking
                         if let Some(limit) = options.top_k {
                             results.truncate(limit);
@@ -282,7 +438,422 @@ pub async fn semantic_search_v3_with_progress(
     })
 }
 
-fn reconstruct_original_path(
+/// Rerank the first `candidates` results (in dense order) a batch at a time,
+/// starting no new batch once `deadline` has passed. Reranked results move to
+/// the front, sorted by their transformed scores; the rest keep their dense
+/// scores and order. Returns how many results were reranked.
+fn rerank_leading(
+    reranker: &mut dyn ck_embed::Reranker,
+    query: &str,
+    results: &mut [SearchResult],
+    candidates: usize,
+    transform: ScoreTransform,
+    deadline: Option<Instant>,
+) -> Result<usize> {
+    // Without a budget there is nothing to check between calls
+    let batch_size = match deadline {
+        Some(_) => RERANK_BATCH_SIZE,
+        None => candidates.max(1),
+    };
+    let mut scores: Vec<f32> = Vec::with_capacity(candidates);
+    for batch in results[..candidates].chunks(batch_size) {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break;
+        }
+        let reranked = reranker.rerank(query, &rerank_documents(batch))?;
+        scores.extend(batch_scores(batch, reranked));
+    }
+    Ok(apply_rerank_scores(results, scores, transform))
+}
+
+/// Rerank several searches' dense results together, grouped by reranker: each
+/// group makes one `rerank_batch` call, which models that support it pack into
+/// shared batches. Each search then gets what `semantic_search_v3` would have
+/// given it with `rerank` set and no `rerank_budget`. A group whose reranker
+/// fails keeps its dense scores.
+pub(crate) fn rerank_together(searches: &mut [(&SearchOptions, &mut Vec<SearchResult>)]) {
+    let registry = ck_models::RerankModelRegistry::default();
+    // (model with raw scores, its configured transform, member searches)
+    let mut groups: Vec<(ck_models::RerankModelConfig, ScoreTransform, Vec<usize>)> = Vec::new();
+    for (i, (options, results)) in searches.iter().enumerate() {
+        if results.is_empty() {
+            continue;
+        }
+        let mut config = match registry.resolve(options.rerank_model.as_deref()) {
+            Ok((_, config)) => config,
+            Err(e) => {
+                tracing::warn!("Failed to resolve reranker, using original scores: {}", e);
+                continue;
+            }
+        };
+        let transform = config.score_transform;
+        config.score_transform = ScoreTransform::Raw;
+        match groups
+            .iter_mut()
+            .find(|(model, _, _)| model.provider == config.provider && model.name == config.name)
+        {
+            Some((_, _, members)) => members.push(i),
+            None => groups.push((config, transform, vec![i])),
+        }
+    }
+
+    for (config, configured, members) in groups {
+        let reranker = match model_cache::reranker(&config) {
+            Ok(reranker) => reranker,
+            Err(e) => {
+                tracing::warn!("Failed to create reranker, using original scores: {}", e);
+                continue;
+            }
+        };
+        let candidates = |options: &SearchOptions, results: &[SearchResult]| {
+            options
+                .rerank_top
+                .map_or(results.len(), |top| top.min(results.len()))
+        };
+        let queries: Vec<String> = members
+            .iter()
+            .map(|&i| searches[i].0.query.clone())
+            .collect();
+        let outcome = {
+            let docs_per_query: Vec<Vec<Document>> = members
+                .iter()
+                .map(|&i| {
+                    let (options, results) = &searches[i];
+                    rerank_documents(&results[..candidates(options, results)])
+                })
+                .collect();
+            let mut reranker = reranker.lock().unwrap_or_else(|e| e.into_inner());
+            reranker.rerank_batch(&queries, &docs_per_query)
+        };
+        let reranked = match outcome {
+            Ok(reranked) => reranked,
+            Err(e) => {
+                tracing::warn!("Reranking failed, using original scores: {}", e);
+                continue;
+            }
+        };
+
+        for (&i, reranked) in members.iter().zip(reranked) {
+            let (options, results) = &mut searches[i];
+            let leading = candidates(options, results);
+            let scores = batch_scores(&results[..leading], reranked);
+            apply_rerank_scores(results, scores, options.rerank_scores.unwrap_or(configured));
+            if let Some(limit) = options.top_k {
+                results.truncate(limit);
+            }
+        }
+    }
+}
+
+/// `batch` as reranker documents, identified by their position in it.
+fn rerank_documents(batch: &[SearchResult]) -> Vec<Document<'_>> {
+    batch
+        .iter()
+        .enumerate()
+        .map(|(i, result)| Document::new(i, &result.preview))
+        .collect()
+}
+
+/// Scores for `batch` in order: the reranker's where it gave one, the dense
+/// score otherwise.
+fn batch_scores(batch: &[SearchResult], reranked: Vec<ck_embed::RerankResult>) -> Vec<f32> {
+    let mut scores: Vec<f32> = batch.iter().map(|r| r.score).collect();
+    for rerank_result in reranked {
+        if let Some(score) = scores.get_mut(rerank_result.id) {
+            *score = rerank_result.score;
+        }
+    }
+    scores
+}
+
+/// Give the leading results their raw rerank `scores` under `transform`, and
+/// sort them to the front. Returns how many were reranked.
+fn apply_rerank_scores(
+    results: &mut [SearchResult],
+    mut scores: Vec<f32>,
+    transform: ScoreTransform,
+) -> usize {
+    let reranked = scores.len();
+    transform.apply(&mut scores);
+    for (result, score) in results.iter_mut().zip(scores) {
+        result.score = score;
+        if let Some(explanation) = result.explain.as_mut() {
+            explanation.rerank_score = Some(score);
+            explanation.base_score = score;
+        }
+    }
+    results[..reranked].sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    reranked
+}
+
+/// A scored chunk and the file it came from.
+type Hit = (f32, PathBuf, ck_index::ChunkEntry);
+
+struct ShardHits {
+    /// Chunks that passed the filters and were scored
+    scanned: usize,
+    hits: Vec<Hit>,
+}
+
+/// Score one shard's chunks against `query`, keeping the best `limit`.
+fn scan_shard(
+    shard: &ck_index::Shard,
+    index_dir: &Path,
+    index_root: &Path,
+    scoring: &Scoring,
+    limit: usize,
+) -> ShardHits {
+    let mut scanned = 0;
+    let mut hits = Vec::new();
+    for sidecar in scoring.generation.sidecars(shard) {
+        if scoring
+            .options
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled())
+        {
+            break;
+        }
+        let Ok(index_entry) = scoring.generation.load_entry(&sidecar) else {
+            continue;
+        };
+        let Some(original_file) = reconstruct_original_path(&sidecar, index_dir, index_root) else {
+            continue;
+        };
+        let filter = ChunkFilter::new(&original_file, scoring);
+        for chunk in index_entry.chunks {
+            let Some(similarity) = filter.score(&chunk, scoring) else {
+                continue;
+            };
+            scanned += 1;
+            hits.push((similarity, original_file.clone(), chunk));
+            if hits.len() >= limit.saturating_mul(2).max(64) {
+                keep_best(&mut hits, limit);
+            }
+        }
+    }
+    keep_best(&mut hits, limit);
+    ShardHits { scanned, hits }
+}
+
+/// Score the graph's candidate chunks exactly, under the same filters as a
+/// shard scan. Only used for unfiltered searches of the whole index, since the
+/// graph's candidates come from every file.
+fn score_graph_candidates(
+    graph: &AnnGraph,
+    index_root: &Path,
+    scoring: &Scoring,
+    limit: usize,
+) -> Result<Vec<Hit>> {
+    let mut by_file: HashMap<&Path, Vec<usize>> = HashMap::new();
+    for (file, chunk) in graph.candidates(scoring.query, limit, scoring.options.ann_oversample)? {
+        by_file.entry(file).or_default().push(chunk);
+    }
+    let mut hits = Vec::new();
+    for (relative, chunks) in by_file {
+        let Ok(index_entry) = scoring
+            .generation
+            .load_entry(&ck_core::get_sidecar_path(index_root, relative))
+        else {
+            continue;
+        };
+        let original_file = index_root.join(relative);
+        let filter = ChunkFilter::new(&original_file, scoring);
+        for chunk in chunks {
+            // A graph built from the current generation always matches its
+            // sidecars, but a stale sidecar is skipped rather than trusted
+            let Some(chunk) = index_entry.chunks.get(chunk).cloned() else {
+                continue;
+            };
+            if let Some(similarity) = filter.score(&chunk, scoring) {
+                hits.push((similarity, original_file.clone(), chunk));
+            }
+        }
+    }
+    keep_best(&mut hits, limit);
+    Ok(hits)
+}
+
+/// Score every chunk of `files` (ranked, relative to `index_root`) under the
+/// usual filters.
+/// Files that pass `keep`, by the similarity of their mean vector to `query`,
+/// best first: the best `top_files` of them, or `limit` for `--by-file`.
+fn rank_files(
+    stored: Vec<(PathBuf, Vec<f32>)>,
+    query: &[f32],
+    metric: SimilarityMetric,
+    options: &SearchOptions,
+    limit: usize,
+    keep: impl Fn(&Path) -> bool,
+) -> Vec<(f32, PathBuf)> {
+    let mut files: Vec<(f32, PathBuf)> = stored
+        .into_iter()
+        .filter(|(relative, _)| keep(relative))
+        .map(|(relative, vector)| (metric.similarity(query, &vector), relative))
+        .collect();
+    files.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    files.truncate(match options.top_files {
+        Some(top_files) if !options.by_file => top_files,
+        _ => limit,
+    });
+    files
+}
+
+fn score_files(files: &[(f32, PathBuf)], index_root: &Path, scoring: &Scoring) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    for (_, relative) in files {
+        let Ok(index_entry) = scoring
+            .generation
+            .load_entry(&ck_core::get_sidecar_path(index_root, relative))
+        else {
+            continue;
+        };
+        let original_file = index_root.join(relative);
+        let filter = ChunkFilter::new(&original_file, scoring);
+        for chunk in index_entry.chunks {
+            if let Some(similarity) = filter.score(&chunk, scoring) {
+                hits.push((similarity, original_file.clone(), chunk));
+            }
+        }
+    }
+    hits
+}
+
+/// One hit per file in `files`' order, scored by the file's similarity and
+/// placed at its best chunk. Files with no chunk left in `hits` are dropped.
+fn best_chunk_per_file(files: &[(f32, PathBuf)], index_root: &Path, hits: Vec<Hit>) -> Vec<Hit> {
+    let mut best: HashMap<PathBuf, Hit> = HashMap::new();
+    for hit in hits {
+        match best.get(&hit.1) {
+            Some(current) if current.0 >= hit.0 => {}
+            _ => {
+                best.insert(hit.1.clone(), hit);
+            }
+        }
+    }
+    files
+        .iter()
+        .filter_map(|(similarity, relative)| {
+            let (_, file, chunk) = best.remove(&index_root.join(relative))?;
+            Some((*similarity, file, chunk))
+        })
+        .collect()
+}
+
+/// The query every chunk is compared with, and what decides whether it is.
+struct Scoring<'a> {
+    options: &'a SearchOptions,
+    feedback: &'a FeedbackTargets,
+    query: &'a [f32],
+    /// The index's metric
+    metric: SimilarityMetric,
+    /// The generation sidecars are read at
+    generation: &'a ck_index::GenerationPin,
+}
+
+/// Per-file state for deciding which of a file's chunks are scored.
+struct ChunkFilter {
+    included: bool,
+    /// Canonical path of the file, only needed to match feedback
+    canonical_file: Option<PathBuf>,
+}
+
+impl ChunkFilter {
+    fn new(original_file: &Path, scoring: &Scoring) -> Self {
+        Self {
+            included: super::path_matches_include(original_file, &scoring.options.include_patterns),
+            canonical_file: (!scoring.feedback.is_empty())
+                .then(|| super::canonicalize_for_matching(original_file)),
+        }
+    }
+
+    /// Similarity of `chunk` to the query, or `None` when it is filtered out.
+    fn score(&self, chunk: &ck_index::ChunkEntry, scoring: &Scoring) -> Option<f32> {
+        let feedback = scoring.feedback;
+        if let Some(file) = &self.canonical_file
+            && !feedback.pins(file, &chunk.span)
+            && feedback.bans(file, &chunk.span)
+        {
+            return None;
+        }
+        // Duplicates are reported through their canonical chunk, which may
+        // still match when its own file is filtered out
+        match &chunk.embedding {
+            Some(embedding)
+                if chunk.alias_of.is_none()
+                    && (self.included || !chunk.aliases.is_empty())
+                    && super::language_matches(chunk.language, &scoring.options.languages) =>
+            {
+                let similarity = scoring.metric.similarity(scoring.query, embedding);
+                Some(similarity * facet_weight(chunk, scoring.options.prefer))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Factor `--prefer` gives `chunk`: 1 without a preference, or for chunks
+/// indexed before facets were recorded.
+fn facet_weight(chunk: &ck_index::ChunkEntry, prefer: Option<ck_core::Facet>) -> f32 {
+    match (prefer, &chunk.facets) {
+        (Some(facet), Some(facets)) => facets.weight(facet),
+        _ => 1.0,
+    }
+}
+
+/// Sort by similarity (highest first) and keep the first `limit`.
+fn keep_best(hits: &mut Vec<Hit>, limit: usize) {
+    hits.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    hits.truncate(limit);
+}
+
+/// Embeddings of the pinned and banned chunks, read from their files' sidecars.
+fn feedback_embeddings(
+    feedback: &FeedbackTargets,
+    index_root: &Path,
+    generation: &ck_index::GenerationPin,
+) -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+    let mut pinned = Vec::new();
+    let mut banned = Vec::new();
+    let canonical_root = super::canonicalize_for_matching(index_root);
+    for file in feedback.files() {
+        let Ok(relative) = file.strip_prefix(&canonical_root) else {
+            continue;
+        };
+        let sidecar = ck_core::get_sidecar_path(index_root, relative);
+        let Ok(index_entry) = generation.load_entry(&sidecar) else {
+            continue;
+        };
+        for chunk in index_entry.chunks {
+            let Some(embedding) = chunk.embedding else {
+                continue;
+            };
+            if feedback.pins(file, &chunk.span) {
+                pinned.push(embedding);
+            } else if feedback.bans(file, &chunk.span) {
+                banned.push(embedding);
+            }
+        }
+    }
+    (pinned, banned)
+}
+
+/// Whether a search of `path` covers every file under `index_root`.
+fn searches_whole_index(path: &Path, index_root: &Path) -> bool {
+    path == Path::new(".")
+        || super::canonicalize_for_matching(path) == super::canonicalize_for_matching(index_root)
+}
+
+fn no_embeddings_error() -> anyhow::Error {
+    CkError::Index("No embeddings found. Run 'ck --index' first with embeddings.".to_string())
+        .into()
+}
+
+pub(crate) fn reconstruct_original_path(
     sidecar_path: &Path,
     index_dir: &Path,
     repo_root: &Path,
@@ -304,18 +875,218 @@ fn reconstruct_original_path(
     Some(repo_root.join(original_path))
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    if a.len() != b.len() {
-        return 0.0;
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    SimilarityMetric::Cosine.similarity(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::Span;
+    use ck_embed::{RerankResult, Reranker};
+
+    /// Scores documents by length and counts the calls it receives.
+    #[derive(Default)]
+    struct LengthReranker {
+        calls: usize,
     }
 
-    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    impl Reranker for LengthReranker {
+        fn id(&self) -> &'static str {
+            "length"
+        }
 
-    if norm_a == 0.0 || norm_b == 0.0 {
-        0.0
-    } else {
-        dot_product / (norm_a * norm_b)
+        fn rerank(
+            &mut self,
+            _query: &str,
+            documents: &[Document<'_>],
+        ) -> ck_embed::Result<Vec<RerankResult>> {
+            self.calls += 1;
+            Ok(documents
+                .iter()
+                .map(|document| RerankResult {
+                    id: document.id,
+                    score: document.text.len() as f32,
+                })
+                .collect())
+        }
+    }
+
+    fn dense_results(previews: &[&str]) -> Vec<SearchResult> {
+        previews
+            .iter()
+            .enumerate()
+            .map(|(i, preview)| {
+                let score = 0.9 - i as f32 * 0.1;
+                SearchResult {
+                    file: format!("src/{}.rs", i).into(),
+                    span: Span {
+                        byte_start: 0,
+                        byte_end: preview.len(),
+                        line_start: 1,
+                        line_end: 1,
+                        col_start: 0,
+                        col_end: 0,
+                    },
+                    score,
+                    preview: preview.to_string(),
+                    lang: None,
+                    symbol: None,
+                    chunk_hash: None,
+                    index_epoch: None,
+                    duplicates: Vec::new(),
+                    explain: Some(ScoreExplanation::new("semantic", score)),
+                    blame: None,
+                    metadata: Default::default(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rerank_leading_keeps_dense_order_past_cutoff() {
+        let mut results = dense_results(&["a", "bbb", "cc", "dddd"]);
+        let mut reranker = LengthReranker::default();
+        let reranked = rerank_leading(
+            &mut reranker,
+            "q",
+            &mut results,
+            3,
+            ScoreTransform::Raw,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(reranked, 3);
+        assert_eq!(reranker.calls, 1);
+        let previews: Vec<_> = results.iter().map(|r| r.preview.as_str()).collect();
+        assert_eq!(previews, ["bbb", "cc", "a", "dddd"]);
+        assert_eq!(results[0].explain.as_ref().unwrap().rerank_score, Some(3.0));
+        assert!((results[3].score - 0.6).abs() < 1e-6);
+        assert_eq!(results[3].explain.as_ref().unwrap().rerank_score, None);
+    }
+
+    #[test]
+    fn test_rerank_leading_stops_at_deadline() {
+        let mut results = dense_results(&["a", "bbb"]);
+        let mut reranker = LengthReranker::default();
+        let reranked = rerank_leading(
+            &mut reranker,
+            "q",
+            &mut results,
+            2,
+            ScoreTransform::Sigmoid,
+            Some(Instant::now()),
+        )
+        .unwrap();
+
+        assert_eq!(reranked, 0);
+        assert_eq!(reranker.calls, 0);
+        assert_eq!(results[0].preview, "a");
+        assert!((results[0].score - 0.9).abs() < 1e-6);
+    }
+
+    /// [`LengthReranker`] scoring that counts `rerank_batch` calls.
+    struct BatchCountingReranker(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Reranker for BatchCountingReranker {
+        fn id(&self) -> &'static str {
+            "batch-counting"
+        }
+
+        fn rerank(
+            &mut self,
+            query: &str,
+            documents: &[Document<'_>],
+        ) -> ck_embed::Result<Vec<RerankResult>> {
+            LengthReranker::default().rerank(query, documents)
+        }
+
+        fn rerank_batch(
+            &mut self,
+            queries: &[String],
+            docs_per_query: &[Vec<Document<'_>>],
+        ) -> ck_embed::Result<Vec<Vec<RerankResult>>> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            queries
+                .iter()
+                .zip(docs_per_query)
+                .map(|(query, documents)| self.rerank(query, documents))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_rerank_together_shares_one_batch_per_model() {
+        let (_, mut config) = ck_models::RerankModelRegistry::default()
+            .resolve(Some("bge"))
+            .unwrap();
+        config.score_transform = ScoreTransform::Raw;
+        let batches = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        model_cache::set_reranker(&config, Box::new(BatchCountingReranker(batches.clone())));
+
+        let search = |query: &str, top_k| SearchOptions {
+            query: query.to_string(),
+            rerank: true,
+            rerank_model: Some("bge".to_string()),
+            rerank_scores: Some(ScoreTransform::Raw),
+            top_k,
+            ..Default::default()
+        };
+        let (first, second) = (search("q1", None), search("q2", Some(1)));
+        let mut first_results = dense_results(&["a", "bbb", "cc"]);
+        let mut second_results = dense_results(&["dd", "e"]);
+        rerank_together(&mut [(&first, &mut first_results), (&second, &mut second_results)]);
+
+        assert_eq!(batches.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let previews: Vec<_> = first_results.iter().map(|r| r.preview.as_str()).collect();
+        assert_eq!(previews, ["bbb", "cc", "a"]);
+        assert_eq!(second_results.len(), 1);
+        assert_eq!(second_results[0].preview, "dd");
+    }
+
+    #[test]
+    fn test_top_files_keeps_the_closest_file_vectors() {
+        let stored = vec![
+            (PathBuf::from("billing.rs"), vec![0.0, 1.0]),
+            (PathBuf::from("auth/login.rs"), vec![1.0, 0.1]),
+            (PathBuf::from("auth/session.rs"), vec![1.0, 0.5]),
+            (PathBuf::from("vendor/auth.rs"), vec![1.0, 0.0]),
+        ];
+        let query = [1.0, 0.0];
+        let ranked_names = |options: &SearchOptions| {
+            rank_files(
+                stored.clone(),
+                &query,
+                SimilarityMetric::Cosine,
+                options,
+                3,
+                |relative| !relative.starts_with("vendor"),
+            )
+            .into_iter()
+            .map(|(_, relative)| relative)
+            .collect::<Vec<_>>()
+        };
+
+        let top_files = SearchOptions {
+            top_files: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            ranked_names(&top_files),
+            [
+                PathBuf::from("auth/login.rs"),
+                PathBuf::from("auth/session.rs")
+            ]
+        );
+
+        // --by-file ranks as many files as results were asked for
+        let by_file = SearchOptions {
+            top_files: Some(2),
+            by_file: true,
+            ..Default::default()
+        };
+        assert_eq!(ranked_names(&by_file).len(), 3);
+        assert_eq!(ranked_names(&by_file)[2], PathBuf::from("billing.rs"));
     }
 }
@@ -0,0 +1,78 @@
+//! `--as-of`: search a kept snapshot of an earlier index generation.
+//!
+//! A snapshot holds its own `.ck` and the files as they were indexed (see
+//! `ck_index::list_snapshots`), so the search itself runs unchanged against
+//! the snapshot's root with updates turned off. Paths are rebased into the
+//! snapshot on the way in and back onto the live tree on the way out, so
+//! results cite files where they sit in the repository.
+
+use anyhow::Result;
+use ck_core::{CkError, SearchOptions, SearchResult, SearchResults};
+use std::path::Path;
+
+use super::{
+    SearchProgressCallback, candidate_options, find_nearest_index_root, finish_results,
+    search_in_mode,
+};
+
+/// `path` moved from under `from` to the same place under `to`; unchanged
+/// when it is not under `from`.
+fn rebase(path: &Path, from: &Path, to: &Path) -> std::path::PathBuf {
+    match ck_core::paths::relative_to(path, from) {
+        Some(relative) => to.join(relative),
+        None => path.to_path_buf(),
+    }
+}
+
+pub(crate) async fn search_snapshot(
+    options: &SearchOptions,
+    as_of: &str,
+    progress_callback: Option<SearchProgressCallback>,
+) -> Result<SearchResults> {
+    let index_root = find_nearest_index_root(&options.path).ok_or_else(|| {
+        CkError::Index(format!(
+            "No index found for {}; --as-of searches an index's kept snapshots",
+            options.path.display()
+        ))
+    })?;
+    let snapshot = ck_index::resolve_snapshot(&index_root, as_of)?;
+    if let Some(ref callback) = progress_callback {
+        callback(&format!(
+            "Searching the snapshot of generation {} ({} files)",
+            snapshot.generation, snapshot.files
+        ));
+    }
+
+    let mut snapshot_options = options.clone();
+    snapshot_options.as_of = None;
+    snapshot_options.reindex = false;
+    snapshot_options.path = rebase(&options.path, &index_root, &snapshot.root);
+    for pattern in &mut snapshot_options.include_patterns {
+        pattern.path = rebase(&pattern.path, &index_root, &snapshot.root);
+    }
+    if !snapshot_options.path.exists() {
+        return Err(CkError::Search(format!(
+            "{} was not indexed at generation {}",
+            options.path.display(),
+            snapshot.generation
+        ))
+        .into());
+    }
+
+    let candidates = candidate_options(&snapshot_options);
+    let mut results = search_in_mode(
+        candidates.as_ref().unwrap_or(&snapshot_options),
+        progress_callback,
+    )
+    .await?;
+    let to_live = |result: &mut SearchResult| {
+        result.file = rebase(&result.file, &snapshot.root, &index_root);
+        for duplicate in &mut result.duplicates {
+            duplicate.file = rebase(&duplicate.file, &snapshot.root, &index_root);
+        }
+    };
+    results.matches.iter_mut().for_each(to_live);
+    results.closest_below_threshold.iter_mut().for_each(to_live);
+    finish_results(options, &mut results)?;
+    Ok(results)
+}
@@ -0,0 +1,25 @@
+//! LLM summaries for `"summarize"` in `.ckconfig.json`: chunks of the files it
+//! lists are embedded from a summary written by the chat model configured
+//! under `llm`, for generated code and data tables that embed poorly as they
+//! are. Indexing lives in ck-index, so the client is handed to it with
+//! [`register_summarizer`].
+
+use std::path::Path;
+
+const SYSTEM_PROMPT: &str = "You describe files for a code search index. Given an excerpt of \
+generated code or a data table, reply with a plain-English summary (under 80 words) of what it \
+contains: the entities, fields, operations and notable values someone might search for. Reply \
+with the summary only.";
+
+/// A summary of `text`, from the chat model the project at `repo_root`
+/// configures.
+fn summarize_chunk(repo_root: &Path, text: &str) -> Result<String, String> {
+    let config = crate::llm::config_for(repo_root, "\"summarize\"").map_err(|e| e.to_string())?;
+    crate::llm::complete(&config, SYSTEM_PROMPT, text, None).map_err(|e| e.to_string())
+}
+
+/// Let indexing summarize the files `"summarize"` lists through the LLM
+/// configured under `llm`. Call once at startup.
+pub fn register_summarizer() {
+    ck_index::set_summarizer(summarize_chunk);
+}
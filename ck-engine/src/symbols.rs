@@ -0,0 +1,380 @@
+use anyhow::Result;
+use ck_core::{CkError, DuplicateLocation, ScoreExplanation, SearchOptions, SearchResult, Span};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::semantic_v3::{cosine_similarity, reconstruct_original_path};
+use super::{
+    SearchProgressCallback, extract_content_from_span, find_nearest_index_root,
+    resolve_model_from_root,
+};
+
+/// Weight of the name match in the blended symbol score; the remainder comes from
+/// the embedding similarity between the query and the definition body.
+const NAME_WEIGHT: f32 = 0.7;
+
+/// A named definition (function, class, method, module) recorded in the index.
+#[derive(Debug, Clone)]
+pub struct SymbolDefinition {
+    pub name: String,
+    /// Chunk type as stored in the sidecar ("function", "class", ...)
+    pub kind: Option<String>,
+    /// Enclosing scopes, e.g. `Parser::impl Display`
+    pub breadcrumb: Option<String>,
+    pub file: PathBuf,
+    pub span: Span,
+    pub language: Option<ck_core::Language>,
+    /// Near-identical copies of the definition elsewhere in the tree
+    pub duplicates: Vec<DuplicateLocation>,
+    embedding: Option<Vec<f32>>,
+}
+
+impl SymbolDefinition {
+    /// Name qualified with its enclosing scopes (`Outer::inner`)
+    pub fn qualified_name(&self) -> String {
+        match &self.breadcrumb {
+            Some(breadcrumb) => format!("{}::{}", breadcrumb, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Symbol table built from the chunk metadata stored in an index's sidecars.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    definitions: Vec<SymbolDefinition>,
+}
+
+impl SymbolTable {
+    pub fn load(index_root: &Path) -> Result<Self> {
+        let index_dir = index_root.join(".ck");
+        let mut definitions = Vec::new();
+
+        for entry in WalkDir::new(&index_dir)
+            .into_iter()
+            .filter_entry(|entry| !ck_index::is_snapshot_store(entry))
+        {
+            let entry = entry?;
+            let path = entry.path();
+            if !entry.file_type().is_file()
+                || path.extension().and_then(|s| s.to_str()) != Some("ck")
+            {
+                continue;
+            }
+            let Ok(index_entry) = ck_index::load_index_entry(path) else {
+                continue;
+            };
+            let Some(file) = reconstruct_original_path(path, &index_dir, index_root) else {
+                continue;
+            };
+            for chunk in index_entry.chunks {
+                if chunk.alias_of.is_some() {
+                    continue;
+                }
+                if let Some(name) = chunk.symbol {
+                    definitions.push(SymbolDefinition {
+                        name,
+                        kind: chunk.chunk_type,
+                        breadcrumb: chunk.breadcrumb,
+                        file: file.clone(),
+                        span: chunk.span,
+                        language: chunk.language,
+                        duplicates: chunk
+                            .aliases
+                            .into_iter()
+                            .map(|alias| DuplicateLocation {
+                                file: index_root.join(alias.path),
+                                span: alias.span,
+                            })
+                            .collect(),
+                        embedding: chunk.embedding,
+                    });
+                }
+            }
+        }
+
+        Ok(Self { definitions })
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    pub fn definitions(&self) -> &[SymbolDefinition] {
+        &self.definitions
+    }
+
+    /// Definitions whose name matches `name` exactly (case-sensitive).
+    pub fn lookup(&self, name: &str) -> Vec<&SymbolDefinition> {
+        self.definitions.iter().filter(|d| d.name == name).collect()
+    }
+}
+
+/// Fuzzy similarity between a query and a symbol name in `[0, 1]`.
+///
+/// Exact and case/separator-insensitive matches score highest, followed by prefix
+/// and substring matches, overlap of identifier words (`parseConfig` ~ `parse_config`),
+/// and finally in-order subsequences (`pcfg` ~ `parse_config`).
+pub fn name_similarity(query: &str, name: &str) -> f32 {
+    let query = query.trim();
+    if query.is_empty() || name.is_empty() {
+        return 0.0;
+    }
+    if query == name {
+        return 1.0;
+    }
+
+    let squash = |s: &str| {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    let q = squash(query);
+    let n = squash(name);
+    if q.is_empty() || n.is_empty() {
+        return 0.0;
+    }
+    if q == n {
+        return 0.95;
+    }
+    if n.starts_with(&q) {
+        return 0.85;
+    }
+    if n.contains(&q) {
+        return 0.7;
+    }
+
+    let query_words = split_identifier_words(query);
+    let name_words = split_identifier_words(name);
+    let shared = query_words
+        .iter()
+        .filter(|w| name_words.contains(w))
+        .count();
+    if shared > 0 {
+        return 0.6 * shared as f32 / query_words.len().max(name_words.len()) as f32;
+    }
+
+    let mut name_chars = n.chars();
+    if q.chars().all(|c| name_chars.any(|nc| nc == c)) {
+        return 0.3 * q.len() as f32 / n.len() as f32;
+    }
+
+    0.0
+}
+
+/// Split `parseHTTPConfig`, `parse_http_config` or `parse http config` into
+/// lowercase words.
+pub(crate) fn split_identifier_words(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let chars: Vec<char> = s.chars().collect();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        let boundary = c.is_uppercase()
+            && !current.is_empty()
+            && (chars[i - 1].is_lowercase()
+                || chars.get(i + 1).is_some_and(|next| next.is_lowercase()));
+        if boundary {
+            words.push(std::mem::take(&mut current));
+        }
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Look up definitions by name, blending fuzzy name matching with the embedding
+/// similarity of each definition's body to the query.
+pub async fn symbol_search_with_progress(
+    options: &SearchOptions,
+    progress_callback: Option<SearchProgressCallback>,
+) -> Result<ck_core::SearchResults> {
+    let index_root = find_nearest_index_root(&options.path)
+        .ok_or_else(|| CkError::Index("No index found. Run 'ck --index' first.".to_string()))?;
+
+    if let Some(ref callback) = progress_callback {
+        callback("Loading symbol table...");
+    }
+    let table = SymbolTable::load(&index_root)?;
+    if table.is_empty() {
+        return Err(CkError::Index(
+            "No symbols found in the index. Rebuild it with 'ck --clean . && ck --index .' to record definition names."
+                .to_string(),
+        )
+        .into());
+    }
+
+    let query_embedding = if table.definitions.iter().any(|d| d.embedding.is_some()) {
+        if let Some(ref callback) = progress_callback {
+            callback("Embedding query...");
+        }
+        let resolved_model =
+            resolve_model_from_root(&index_root, options.embedding_model.as_deref())?;
+        let pairing = super::query_model::load_pairing(
+            &index_root,
+            &resolved_model,
+            options.embedding_model.as_deref(),
+        )?;
+        let query_text = resolved_model.config.instructed_query(
+            &super::query_rewrite::rewrite_query(&index_root, &options.query),
+            options.instruction.as_deref(),
+        );
+        super::query_model::embed_query(
+            &resolved_model,
+            pairing.as_ref(),
+            super::index_metric(&index_root),
+            &query_text,
+        )
+        .await?
+    } else {
+        None
+    };
+
+    let target = options
+        .path
+        .canonicalize()
+        .unwrap_or_else(|_| options.path.clone());
+    let in_scope = |file: &Path| {
+        if !super::path_matches_include(file, &options.include_patterns) {
+            return false;
+        }
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        if target.is_file() {
+            file == target
+        } else {
+            file.starts_with(&target)
+        }
+    };
+    let mut scored: Vec<(ScoreExplanation, &SymbolDefinition)> = table
+        .definitions
+        .iter()
+        .filter(|def| super::language_matches(def.language, &options.languages))
+        .filter(|def| in_scope(&def.file) || def.duplicates.iter().any(|d| in_scope(&d.file)))
+        .filter_map(|def| {
+            let name_score = name_similarity(&options.query, &def.name);
+            let dense_score = match (&query_embedding, &def.embedding) {
+                (Some(query), Some(body)) => Some(cosine_similarity(query, body).clamp(0.0, 1.0)),
+                _ => None,
+            };
+            let score = match dense_score {
+                Some(semantic) => NAME_WEIGHT * name_score + (1.0 - NAME_WEIGHT) * semantic,
+                None => name_score,
+            };
+            let explanation = ScoreExplanation {
+                name_score: Some(name_score),
+                dense_score,
+                ..ScoreExplanation::new("symbol", score)
+            };
+            (score > 0.0).then_some((explanation, def))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| {
+        b.0.base_score
+            .partial_cmp(&a.0.base_score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut matches = Vec::new();
+    let mut closest_below_threshold = None;
+    let limit = options.top_k.unwrap_or(scored.len());
+
+    for (explanation, def) in scored.into_iter() {
+        let score = explanation.base_score;
+        if matches.len() >= limit {
+            break;
+        }
+        // Report the first in-scope copy; the rest are listed as duplicates
+        let mut locations = std::iter::once(DuplicateLocation {
+            file: def.file.clone(),
+            span: def.span.clone(),
+        })
+        .chain(def.duplicates.iter().cloned())
+        .filter(|location| in_scope(&location.file));
+        let Some(DuplicateLocation { file, span }) = locations.next() else {
+            continue;
+        };
+        let duplicates = locations.collect();
+        let Ok(content) = extract_content_from_span(&file, &span).await else {
+            continue;
+        };
+        let preview = if options.full_section {
+            content
+        } else {
+            content.lines().take(3).collect::<Vec<_>>().join("\n")
+        };
+        let result = SearchResult {
+            lang: def.language.or_else(|| ck_core::Language::from_path(&file)),
+            file,
+            span,
+            score,
+            preview,
+            symbol: Some(def.qualified_name()),
+            chunk_hash: None,
+            index_epoch: None,
+            duplicates,
+            explain: Some(explanation),
+            blame: None,
+            metadata: Default::default(),
+        };
+
+        if options.threshold.is_some_and(|t| score < t) {
+            if closest_below_threshold.is_none() {
+                closest_below_threshold = Some(result);
+            }
+            break;
+        }
+        matches.push(result);
+    }
+
+    Ok(ck_core::SearchResults {
+        matches,
+        closest_below_threshold,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_similarity_ordering() {
+        let exact = name_similarity("parse_config", "parse_config");
+        let case = name_similarity("ParseConfig", "parse_config");
+        let prefix = name_similarity("parse", "parse_config");
+        let words = name_similarity("config parser", "parse_config_file");
+        let subseq = name_similarity("pcfg", "parse_config");
+
+        assert_eq!(exact, 1.0);
+        assert!(case < exact && case > prefix);
+        assert!(prefix > words);
+        assert!(words > 0.0);
+        assert!(subseq > 0.0 && subseq < words);
+        assert_eq!(name_similarity("zzz", "parse_config"), 0.0);
+    }
+
+    #[test]
+    fn test_split_identifier_words() {
+        assert_eq!(
+            split_identifier_words("parseHTTPConfig"),
+            vec!["parse", "http", "config"]
+        );
+        assert_eq!(
+            split_identifier_words("parse_http_config"),
+            vec!["parse", "http", "config"]
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Local usage statistics for `ck --stats`.
+//!
+//! Searches, result-cache hits and index updates are counted in
+//! `.ck/usage.json` next to the index they used, so a team can weigh what the
+//! index costs to build against how much it is searched. Nothing leaves the
+//! machine, queries themselves are not recorded, and `"usage_stats": false` in
+//! `.ckconfig.json` turns recording off. Writes are best effort: a failure is
+//! logged and never fails the search, and concurrent processes may lose the
+//! odd count.
+
+use ck_core::{SearchMode, SearchOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+/// Statistics file kept next to `manifest.json`.
+pub const USAGE_FILE: &str = "usage.json";
+
+/// Searched paths kept; the least searched are dropped beyond this.
+const MAX_PATHS: usize = 100;
+
+/// Paths shown by the report.
+const TOP_PATHS: usize = 10;
+
+static RECORDING: AtomicBool = AtomicBool::new(true);
+
+/// Counters for one index since it started recording.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageStats {
+    /// Unix seconds of the first and the latest recorded event
+    pub since: u64,
+    pub last_used: u64,
+    /// Searches run against the index, by mode
+    pub searches: BTreeMap<String, u64>,
+    /// Time those searches took, excluding index updates
+    pub search_ms: u64,
+    /// Repeated searches the MCP server answered from its result cache
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Index builds and updates that changed the index, and their time
+    pub index_runs: u64,
+    pub index_ms: u64,
+    /// Searches by scope, relative to the index root (`.` for all of it)
+    pub paths: BTreeMap<String, u64>,
+}
+
+impl UsageStats {
+    /// Searches run plus searches answered from the cache.
+    pub fn queries(&self) -> u64 {
+        self.searches.values().sum::<u64>() + self.cache_hits
+    }
+
+    pub fn average_search_ms(&self) -> Option<f64> {
+        let run: u64 = self.searches.values().sum();
+        (run > 0).then(|| self.search_ms as f64 / run as f64)
+    }
+
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    /// The most searched paths, most searched first.
+    pub fn top_paths(&self, limit: usize) -> Vec<(&str, u64)> {
+        let mut paths: Vec<(&str, u64)> = self
+            .paths
+            .iter()
+            .map(|(path, count)| (path.as_str(), *count))
+            .collect();
+        paths.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        paths.truncate(limit);
+        paths
+    }
+
+    fn count_path(&mut self, path: String) {
+        *self.paths.entry(path).or_default() += 1;
+        if self.paths.len() > MAX_PATHS
+            && let Some(least) = self
+                .top_paths(usize::MAX)
+                .last()
+                .map(|(path, _)| path.to_string())
+        {
+            self.paths.remove(&least);
+        }
+    }
+}
+
+fn seconds(duration: Duration) -> String {
+    let s = duration.as_secs();
+    match s {
+        s if s < 60 => format!("{:.1}s", duration.as_secs_f64()),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s => format!("{}h {}m", s / 3600, s % 3600 / 60),
+    }
+}
+
+impl fmt::Display for UsageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.queries() == 0 && self.index_runs == 0 {
+            return writeln!(f, "No usage recorded yet");
+        }
+        let days = self.last_used.saturating_sub(self.since) / 86_400 + 1;
+        writeln!(
+            f,
+            "Queries:        {} over {} day{}",
+            self.queries(),
+            days,
+            if days == 1 { "" } else { "s" }
+        )?;
+        if !self.searches.is_empty() {
+            let modes: Vec<String> = self
+                .searches
+                .iter()
+                .map(|(mode, count)| format!("{} {}", mode, count))
+                .collect();
+            writeln!(f, "  by mode:      {}", modes.join(", "))?;
+        }
+        if let Some(average) = self.average_search_ms() {
+            writeln!(f, "Avg latency:    {:.0} ms", average)?;
+        }
+        if let Some(rate) = self.cache_hit_rate() {
+            writeln!(
+                f,
+                "Cache hits:     {:.0}% ({} of {})",
+                rate * 100.0,
+                self.cache_hits,
+                self.cache_hits + self.cache_misses
+            )?;
+        }
+        writeln!(
+            f,
+            "Index updates:  {} taking {}",
+            self.index_runs,
+            seconds(Duration::from_millis(self.index_ms))
+        )?;
+        let paths = self.top_paths(TOP_PATHS);
+        if !paths.is_empty() {
+            writeln!(f, "Most searched:")?;
+            let width = paths.iter().map(|(_, count)| count.to_string().len()).max();
+            for (path, count) in paths {
+                writeln!(
+                    f,
+                    "  {:>width$}  {}",
+                    count,
+                    path,
+                    width = width.unwrap_or(1)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stop recording for the rest of the process, for runs such as benchmarks
+/// whose searches are not real use.
+pub fn pause_usage_recording() {
+    RECORDING.store(false, Ordering::Relaxed);
+}
+
+/// The statistics recorded for the index at `index_root`.
+pub fn load_usage(index_root: &Path) -> UsageStats {
+    std::fs::read(index_root.join(".ck").join(USAGE_FILE))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn update(index_root: &Path, apply: impl FnOnce(&mut UsageStats)) {
+    let index_dir = index_root.join(".ck");
+    if !RECORDING.load(Ordering::Relaxed)
+        || crate::read_only::is_read_only()
+        || !index_dir.is_dir()
+        || !ck_models::ProjectConfig::for_path(index_root).usage_stats
+    {
+        return;
+    }
+    let mut stats = load_usage(index_root);
+    apply(&mut stats);
+    let now = now();
+    if stats.since == 0 {
+        stats.since = now;
+    }
+    stats.last_used = now;
+
+    let path = index_dir.join(USAGE_FILE);
+    let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+    let written = serde_json::to_vec_pretty(&stats)
+        .map_err(std::io::Error::other)
+        .and_then(|data| std::fs::write(&tmp, data))
+        .and_then(|_| std::fs::rename(&tmp, &path));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&tmp);
+        tracing::debug!("Could not record usage in {}: {}", path.display(), e);
+    }
+}
+
+fn mode_name(mode: &SearchMode) -> &'static str {
+    match mode {
+        SearchMode::Regex => "regex",
+        SearchMode::Lexical => "lex",
+        SearchMode::Semantic => "sem",
+        SearchMode::Hybrid => "hybrid",
+        SearchMode::Symbol => "sym",
+    }
+}
+
+/// The index `options` searches, and its scope relative to that index.
+fn scope(options: &SearchOptions) -> Option<(std::path::PathBuf, String)> {
+    let path = options
+        .path
+        .canonicalize()
+        .unwrap_or_else(|_| options.path.clone());
+    let root = super::find_nearest_index_root(&path)?;
+    let relative = path.strip_prefix(&root).unwrap_or(&path);
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    Some((
+        root,
+        if relative.is_empty() {
+            ".".to_string()
+        } else {
+            relative
+        },
+    ))
+}
+
+/// Count a search that ran against the index, and how long it took.
+pub fn record_search(options: &SearchOptions, elapsed: Duration) {
+    if options.query.is_empty() {
+        return;
+    }
+    let Some((root, path)) = scope(options) else {
+        return;
+    };
+    update(&root, |stats| {
+        *stats
+            .searches
+            .entry(mode_name(&options.mode).to_string())
+            .or_default() += 1;
+        stats.search_ms += elapsed.as_millis() as u64;
+        stats.count_path(path);
+    });
+}
+
+/// Count a result-cache lookup. A hit is a search answered without running
+/// one; a miss is followed by the search itself.
+pub fn record_cache_lookup(options: &SearchOptions, hit: bool) {
+    let Some((root, path)) = scope(options) else {
+        return;
+    };
+    update(&root, |stats| {
+        if hit {
+            stats.cache_hits += 1;
+            stats.count_path(path);
+        } else {
+            stats.cache_misses += 1;
+        }
+    });
+}
+
+/// Count an index build or update that changed the index at `index_root`.
+pub fn record_index_run(index_root: &Path, elapsed: Duration) {
+    update(index_root, |stats| {
+        stats.index_runs += 1;
+        stats.index_ms += elapsed.as_millis() as u64;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_usage_is_recorded_per_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("src/api")).unwrap();
+        let options = |path: &Path, mode| SearchOptions {
+            mode,
+            query: "retry".to_string(),
+            path: path.to_path_buf(),
+            ..SearchOptions::default()
+        };
+
+        // Nothing is written without an index
+        record_search(&options(&root, SearchMode::Lexical), Duration::ZERO);
+        assert!(!root.join(".ck").exists());
+
+        std::fs::create_dir(root.join(".ck")).unwrap();
+        record_search(
+            &options(&root, SearchMode::Semantic),
+            Duration::from_millis(30),
+        );
+        record_search(
+            &options(&root.join("src/api"), SearchMode::Semantic),
+            Duration::from_millis(10),
+        );
+        record_cache_lookup(&options(&root.join("src/api"), SearchMode::Semantic), true);
+        record_cache_lookup(&options(&root, SearchMode::Lexical), false);
+        record_index_run(&root, Duration::from_secs(90));
+
+        let stats = load_usage(&root);
+        assert_eq!(stats.queries(), 3);
+        assert_eq!(stats.searches.get("sem"), Some(&2));
+        assert_eq!(stats.average_search_ms(), Some(20.0));
+        assert_eq!(stats.cache_hit_rate(), Some(0.5));
+        assert_eq!(stats.top_paths(1), vec![("src/api", 2)]);
+        assert!(stats.since > 0);
+        let report = stats.to_string();
+        assert!(report.contains("Queries:        3 over 1 day"));
+        assert!(report.contains("Index updates:  1 taking 1m 30s"));
+
+        // Projects can turn recording off
+        std::fs::write(
+            root.join(ck_models::PROJECT_CONFIG_FILE),
+            r#"{"usage_stats":false}"#,
+        )
+        .unwrap();
+        record_index_run(&root, Duration::from_secs(1));
+        assert_eq!(load_usage(&root).index_runs, 1);
+    }
+
+    #[test]
+    fn test_least_searched_paths_are_dropped() {
+        let mut stats = UsageStats::default();
+        stats.count_path("hot".to_string());
+        stats.count_path("hot".to_string());
+        for i in 0..MAX_PATHS + 5 {
+            stats.count_path(format!("cold{i}"));
+        }
+        assert_eq!(stats.paths.len(), MAX_PATHS);
+        assert_eq!(stats.top_paths(1), vec![("hot", 2)]);
+    }
+}
@@ -0,0 +1,291 @@
+//! Raw chunk embeddings, for clustering, visualization and other analysis
+//! outside ck: one chunk's vector by id ([`get_embedding`], `ck --embedding`)
+//! or every vector in the index ([`export_vectors`], `ck --export-vectors`).
+//!
+//! A chunk's id is its file's path relative to the index root and its lines,
+//! `src/net.rs:12-40`, as search results print them. Near-duplicate chunks
+//! keep their own vectors and are exported like any other, with `alias_of`
+//! naming the copy search results show instead.
+
+use anyhow::Result;
+use ck_core::{CkError, Language, Span};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::semantic_v3::reconstruct_original_path;
+
+/// An embedded chunk and its vector.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct ChunkVector {
+    /// `path:line_start-line_end`
+    pub id: String,
+    /// Path relative to the index root
+    pub path: PathBuf,
+    pub span: Span,
+    pub language: Option<Language>,
+    pub symbol: Option<String>,
+    /// The canonical copy of a near-duplicate chunk, relative to the index root
+    pub alias_of: Option<PathBuf>,
+    pub embedding: Vec<f32>,
+}
+
+/// Id of the chunk spanning `span` of the file at `path`.
+pub fn chunk_id(path: &Path, span: &Span) -> String {
+    format!(
+        "{}:{}-{}",
+        ck_core::paths::to_slash(path),
+        span.line_start,
+        span.line_end
+    )
+}
+
+/// Split `id` into its path and lines; `path:LINE` names the first chunk
+/// spanning that line.
+fn parse_id(id: &str) -> Option<(&str, usize, Option<usize>)> {
+    let (path, lines) = id.rsplit_once(':')?;
+    match lines.split_once('-') {
+        Some((start, end)) => Some((path, start.parse().ok()?, Some(end.parse().ok()?))),
+        None => Some((path, lines.parse().ok()?, None)),
+    }
+}
+
+pub(crate) fn index_root_for(path: &Path) -> Result<PathBuf> {
+    let root =
+        super::find_nearest_index_root(&ck_core::paths::canonicalize(path)).ok_or_else(|| {
+            CkError::Index(format!(
+                "No index found for {}. Run 'ck --index' first.",
+                path.display()
+            ))
+        })?;
+    Ok(root)
+}
+
+/// The embedded chunks of the file whose sidecar is `sidecar`, with the
+/// file's path relative to `index_root`.
+fn file_vectors(sidecar: &Path, index_root: &Path) -> Result<Vec<ChunkVector>> {
+    let index_dir = index_root.join(".ck");
+    let entry = ck_index::load_index_entry(sidecar)?;
+    let file = reconstruct_original_path(sidecar, &index_dir, index_root)
+        .unwrap_or_else(|| sidecar.to_path_buf());
+    let relative = ck_core::paths::relative_to(&file, index_root).unwrap_or(file);
+    let language = ck_core::Language::from_path(&relative);
+    Ok(entry
+        .chunks
+        .into_iter()
+        .filter_map(|chunk| {
+            Some(ChunkVector {
+                id: chunk_id(&relative, &chunk.span),
+                path: relative.clone(),
+                span: chunk.span,
+                language: chunk.language.or(language),
+                symbol: chunk.symbol,
+                alias_of: chunk.alias_of,
+                embedding: chunk.embedding?,
+            })
+        })
+        .collect())
+}
+
+/// The vector of the chunk `id` names in the index covering `path`, or
+/// `None` when the file has no such embedded chunk. The id's path may also
+/// be absolute, or relative to the current directory.
+pub fn get_embedding(path: &Path, id: &str) -> Result<Option<ChunkVector>> {
+    let (file, line_start, line_end) = parse_id(id).ok_or_else(|| {
+        CkError::Search(format!(
+            "Invalid chunk id '{}'; expected PATH:START-END or PATH:LINE",
+            id
+        ))
+    })?;
+    let index_root = index_root_for(path)?;
+    let file = Path::new(file);
+    let file = if file.is_absolute() || !index_root.join(file).exists() {
+        ck_core::paths::canonicalize(file)
+    } else {
+        index_root.join(file)
+    };
+    let sidecar = ck_core::get_sidecar_path(&index_root, &file);
+    if !sidecar.exists() {
+        return Ok(None);
+    }
+    let chunks = file_vectors(&sidecar, &index_root)?;
+    Ok(match line_end {
+        Some(line_end) => chunks
+            .into_iter()
+            .find(|chunk| chunk.span.line_start == line_start && chunk.span.line_end == line_end),
+        None => chunks
+            .into_iter()
+            .find(|chunk| chunk.span.line_start <= line_start && line_start <= chunk.span.line_end),
+    })
+}
+
+/// The sidecars of the index at `index_root`, in path order.
+pub(crate) fn sidecars(index_root: &Path) -> Result<Vec<PathBuf>> {
+    let mut sidecars = Vec::new();
+    for entry in WalkDir::new(index_root.join(".ck"))
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| !ck_index::is_snapshot_store(entry))
+    {
+        let entry = entry?;
+        if entry.file_type().is_file()
+            && entry.path().extension().and_then(|s| s.to_str()) == Some("ck")
+        {
+            sidecars.push(entry.into_path());
+        }
+    }
+    Ok(sidecars)
+}
+
+/// Every embedded chunk of the index covering `path`, ordered by path and
+/// line.
+pub fn export_vectors(path: &Path) -> Result<Vec<ChunkVector>> {
+    let index_root = index_root_for(path)?;
+    let mut vectors = Vec::new();
+    for sidecar in sidecars(&index_root)? {
+        vectors.extend(file_vectors(&sidecar, &index_root)?);
+    }
+    if vectors.is_empty() {
+        return Err(CkError::Index(
+            "No embeddings found. Run 'ck --index' first with embeddings.".to_string(),
+        )
+        .into());
+    }
+    Ok(vectors)
+}
+
+/// Write the vectors as a NumPy `.npy` array of little-endian `float32`,
+/// one row per chunk.
+pub fn write_npy(vectors: &[ChunkVector], mut out: impl Write) -> Result<()> {
+    let dimensions = vectors.first().map_or(0, |vector| vector.embedding.len());
+    if let Some(vector) = vectors
+        .iter()
+        .find(|vector| vector.embedding.len() != dimensions)
+    {
+        return Err(CkError::Index(format!(
+            "{} has {} dimensions where other chunks have {}; re-index with one model",
+            vector.id,
+            vector.embedding.len(),
+            dimensions
+        ))
+        .into());
+    }
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+        vectors.len(),
+        dimensions
+    );
+    // Magic, version and length take 10 bytes; the header ends in a newline
+    // and pads the data to a 64-byte boundary
+    let padding = 63 - (10 + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+    out.write_all(b"\x93NUMPY\x01\x00")?;
+    out.write_all(&(header.len() as u16).to_le_bytes())?;
+    out.write_all(header.as_bytes())?;
+    for vector in vectors {
+        for value in &vector.embedding {
+            out.write_all(&value.to_le_bytes())?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Write one JSON line per vector, in order, describing it without its
+/// embedding: the row labels of an array written by [`write_npy`].
+pub fn write_rows(vectors: &[ChunkVector], mut out: impl Write) -> Result<()> {
+    #[derive(Serialize)]
+    struct Row<'a> {
+        id: &'a str,
+        path: String,
+        line_start: usize,
+        line_end: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        symbol: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        alias_of: Option<String>,
+    }
+    for vector in vectors {
+        let row = Row {
+            id: &vector.id,
+            path: ck_core::paths::to_slash(&vector.path),
+            line_start: vector.span.line_start,
+            line_end: vector.span.line_end,
+            language: vector.language.map(|language| language.to_string()),
+            symbol: vector.symbol.as_deref(),
+            alias_of: vector.alias_of.as_deref().map(ck_core::paths::to_slash),
+        };
+        serde_json::to_writer(&mut out, &row)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(line_start: usize, embedding: Vec<f32>) -> ChunkVector {
+        let span = Span {
+            byte_start: 0,
+            byte_end: 10,
+            line_start,
+            line_end: line_start + 4,
+            col_start: 0,
+            col_end: 0,
+        };
+        ChunkVector {
+            id: chunk_id(Path::new("src/net.rs"), &span),
+            path: PathBuf::from("src/net.rs"),
+            span,
+            language: Some(Language::Rust),
+            symbol: None,
+            alias_of: None,
+            embedding,
+        }
+    }
+
+    #[test]
+    fn test_npy_layout_and_chunk_ids() {
+        let vectors = [
+            vector(1, vec![1.0, 2.0, 3.0]),
+            vector(6, vec![4.0, 5.0, 6.0]),
+        ];
+        assert_eq!(vectors[1].id, "src/net.rs:6-10");
+        assert_eq!(
+            parse_id("src/net.rs:6-10"),
+            Some(("src/net.rs", 6, Some(10)))
+        );
+        assert_eq!(
+            parse_id("C:/src/net.rs:7"),
+            Some(("C:/src/net.rs", 7, None))
+        );
+        assert_eq!(parse_id("src/net.rs"), None);
+
+        let mut npy = Vec::new();
+        write_npy(&vectors, &mut npy).unwrap();
+        assert_eq!(&npy[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&npy[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (2, 3)"));
+        assert!(header.ends_with('\n'));
+        let data = &npy[10 + header_len..];
+        assert_eq!(data.len(), 6 * 4);
+        assert_eq!(f32::from_le_bytes(data[12..16].try_into().unwrap()), 4.0);
+
+        let mut rows = Vec::new();
+        write_rows(&vectors, &mut rows).unwrap();
+        let first = String::from_utf8(rows).unwrap();
+        assert!(first.starts_with("{\"id\":\"src/net.rs:1-5\",\"path\":\"src/net.rs\""));
+
+        let mixed = [vector(1, vec![1.0, 2.0]), vector(6, vec![1.0])];
+        assert!(write_npy(&mixed, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! `--warm`: pay an index's cold-start costs before the first real query.
+//!
+//! Reading every sidecar pulls the index into the page cache, loading the
+//! HNSW graph and descending it once touches its entry layers, and embedding a
+//! throwaway query loads the model and runs its first (slowest) inference.
+//! The embedder stays in [`model_cache`](super::model_cache), so a warm-up in a
+//! long-lived process (the MCP server, the TUI, a library user) also keeps the
+//! session ready; from a shell it leaves the files hot for the next `ck`.
+
+use anyhow::Result;
+use ck_core::CkError;
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::ann::load_current;
+use super::query_model::{embed_query, load_pairing};
+use super::{find_nearest_index_root, resolve_model_from_root};
+
+/// Text embedded to prime the model; its vector is never searched for.
+const WARM_QUERY: &str = "warm up";
+
+/// What a warm-up loaded, and how long each part took.
+#[derive(Debug, Clone)]
+pub struct WarmReport {
+    pub index_root: PathBuf,
+    pub sidecars: usize,
+    pub bytes: u64,
+    pub read_time: Duration,
+    /// Nodes of the HNSW graph, when the index has a current one
+    pub graph_nodes: Option<usize>,
+    pub graph_time: Duration,
+    /// The embedding model primed, when the index has embeddings
+    pub model: Option<String>,
+    pub model_time: Duration,
+}
+
+/// Warm the index nearest to `path`: read its sidecars, load its graph and
+/// prime its embedding model.
+pub async fn warm_index(path: &Path) -> Result<WarmReport> {
+    let index_root = find_nearest_index_root(path).ok_or_else(|| {
+        CkError::Index(format!(
+            "No index found for {}. Run 'ck --index' first.",
+            path.display()
+        ))
+    })?;
+    let index_dir = index_root.join(".ck");
+
+    let started = Instant::now();
+    let sidecars: Vec<PathBuf> = ck_index::list_shards(&index_dir)?
+        .iter()
+        .flat_map(|shard| shard.sidecars())
+        .collect();
+    let bytes = sidecars
+        .par_iter()
+        .map(|sidecar| std::fs::read(sidecar).map_or(0, |data| data.len() as u64))
+        .sum();
+    let read_time = started.elapsed();
+
+    let started = Instant::now();
+    let manifest = std::fs::read(index_dir.join("manifest.json"))
+        .ok()
+        .and_then(|data| serde_json::from_slice::<ck_index::IndexManifest>(&data).ok());
+    let (model, query) = match manifest.and_then(|manifest| manifest.embedding_model) {
+        Some(_) => {
+            let resolved = resolve_model_from_root(&index_root, None)?;
+            let pairing = load_pairing(&index_root, &resolved, None)?;
+            let query = embed_query(
+                &resolved,
+                pairing.as_ref(),
+                super::index_metric(&index_root),
+                WARM_QUERY,
+            )
+            .await?;
+            (Some(resolved.canonical_name().to_string()), query)
+        }
+        None => (None, None),
+    };
+    let model_time = started.elapsed();
+
+    let started = Instant::now();
+    let graph = load_current(&index_root);
+    if let (Some(graph), Some(query)) = (&graph, &query) {
+        // One greedy descent reads the entry point and upper layers
+        if let Err(err) = graph.candidates(query, 1, Some(1)) {
+            tracing::debug!("Warm-up graph search failed: {}", err);
+        }
+    }
+    let graph_time = started.elapsed();
+
+    Ok(WarmReport {
+        index_root,
+        sidecars: sidecars.len(),
+        bytes,
+        read_time,
+        graph_nodes: graph.map(|graph| graph.len()),
+        graph_time,
+        model,
+        model_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_warm_needs_an_index() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(warm_index(temp_dir.path()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_warm_reads_an_index_without_embeddings() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_dir = temp_dir.path().join(".ck");
+        std::fs::create_dir_all(&index_dir).unwrap();
+        std::fs::write(index_dir.join("manifest.json"), "{}").unwrap();
+        std::fs::write(index_dir.join("lib.rs.ck"), b"sidecar").unwrap();
+
+        let report = warm_index(temp_dir.path()).await.unwrap();
+        assert_eq!(report.sidecars, 1);
+        assert_eq!(report.bytes, 7);
+        assert!(report.model.is_none());
+        assert!(report.graph_nodes.is_none());
+    }
+}
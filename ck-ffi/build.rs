@@ -0,0 +1,11 @@
+fn main() {
+    #[cfg(feature = "header")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("set by cargo");
+        cbindgen::generate(&crate_dir)
+            .expect("failed to generate C header")
+            .write_to_file(std::path::Path::new(&crate_dir).join("include/ck.h"));
+    }
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
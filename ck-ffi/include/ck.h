@@ -0,0 +1,146 @@
+#ifndef CK_H
+#define CK_H
+
+/* Generated by cbindgen from ck-ffi/src/lib.rs; regenerate with `cargo build -p ck-ffi --features header`. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Meaning-based search over embeddings (the default).
+ */
+#define CK_MODE_SEMANTIC 0
+
+/**
+ * BM25 full-text search.
+ */
+#define CK_MODE_LEXICAL 1
+
+/**
+ * Regex and semantic results fused with reciprocal rank fusion.
+ */
+#define CK_MODE_HYBRID 2
+
+/**
+ * Regular expression over file contents.
+ */
+#define CK_MODE_REGEX 3
+
+/**
+ * Definition lookup by name.
+ */
+#define CK_MODE_SYMBOL 4
+
+/**
+ * Opaque list of hits returned by `ck_search`.
+ */
+typedef struct CkHits CkHits;
+
+/**
+ * Opaque handle to an opened tree.
+ */
+typedef struct CkIndex CkIndex;
+
+/**
+ * One search hit. Strings are owned by the `CkHits` list the hit came from.
+ */
+typedef struct CkHit {
+  const char *path;
+  uint32_t line_start;
+  uint32_t line_end;
+  float score;
+  const char *snippet;
+  /**
+   * NULL when the language is unknown
+   */
+  const char *language;
+  /**
+   * NULL unless the hit is a symbol definition
+   */
+  const char *symbol;
+} CkHit;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Open the tree rooted at `root`. Returns NULL on failure.
+ *
+ * # Safety
+ * `root` must be a valid NUL-terminated UTF-8 string.
+ */
+struct CkIndex *ck_index_open(const char *root);
+
+/**
+ * Bring the index up to date, computing embeddings when `embeddings` is true.
+ * Returns 0 on success and -1 on failure.
+ *
+ * # Safety
+ * `index` must come from `ck_index_open` and not have been freed.
+ */
+int32_t ck_index_update(const struct CkIndex *index, bool embeddings);
+
+/**
+ * Run a query in `mode` (one of the `CK_MODE_*` constants). `top_k` of 0 uses
+ * the mode's default; a negative `threshold` means no threshold. Returns NULL on
+ * failure; an empty list is not a failure.
+ *
+ * # Safety
+ * `index` must come from `ck_index_open` and not have been freed; `query` must
+ * be a valid NUL-terminated UTF-8 string.
+ */
+struct CkHits *ck_search(const struct CkIndex *index,
+                         const char *query,
+                         uint32_t mode,
+                         uint32_t top_k,
+                         float threshold);
+
+/**
+ * Number of hits in `hits` (0 for NULL).
+ *
+ * # Safety
+ * `hits` must be NULL or come from `ck_search` and not have been freed.
+ */
+size_t ck_hits_len(const struct CkHits *hits);
+
+/**
+ * Hit at `i`, or NULL when out of range. The pointer is valid until
+ * `ck_hits_free`.
+ *
+ * # Safety
+ * `hits` must be NULL or come from `ck_search` and not have been freed.
+ */
+const struct CkHit *ck_hits_get(const struct CkHits *hits, size_t i);
+
+/**
+ * Free a hit list. NULL is ignored.
+ *
+ * # Safety
+ * `hits` must be NULL or come from `ck_search`, and must not be used afterwards.
+ */
+void ck_hits_free(struct CkHits *hits);
+
+/**
+ * Close an index handle. NULL is ignored.
+ *
+ * # Safety
+ * `index` must be NULL or come from `ck_index_open`, and must not be used
+ * afterwards.
+ */
+void ck_index_free(struct CkIndex *index);
+
+/**
+ * Message for the most recent failure on this thread, or NULL. Valid until the
+ * next failing call on the same thread.
+ */
+const char *ck_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CK_H */
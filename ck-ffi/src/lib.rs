@@ -0,0 +1,288 @@
+//! Stable C ABI over `ck_engine::CkIndex` for editors and other native hosts
+//! (Neovim through LuaJIT FFI, Emacs modules, C/C++ plugins).
+//!
+//! Lifecycle: `ck_index_open` → `ck_search` → `ck_hits_len` / `ck_hits_get` →
+//! `ck_hits_free` → `ck_index_free`. Functions that fail return NULL or a negative
+//! status and leave a message for `ck_last_error`. The header is `include/ck.h`.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr;
+
+/// Meaning-based search over embeddings (the default).
+pub const CK_MODE_SEMANTIC: u32 = 0;
+/// BM25 full-text search.
+pub const CK_MODE_LEXICAL: u32 = 1;
+/// Regex and semantic results fused with reciprocal rank fusion.
+pub const CK_MODE_HYBRID: u32 = 2;
+/// Regular expression over file contents.
+pub const CK_MODE_REGEX: u32 = 3;
+/// Definition lookup by name.
+pub const CK_MODE_SYMBOL: u32 = 4;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(message));
+}
+
+/// Run `f`, turning errors and panics into `fallback` plus a `ck_last_error` message.
+fn guard<T>(fallback: T, f: impl FnOnce() -> Result<T, String>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(message)) => {
+            set_last_error(message);
+            fallback
+        }
+        Err(_) => {
+            set_last_error("internal panic in ck");
+            fallback
+        }
+    }
+}
+
+/// # Safety
+/// `s` must be NULL or a valid NUL-terminated string.
+unsafe fn read_str<'a>(s: *const c_char, what: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("{} is NULL", what));
+    }
+    // SAFETY: non-null and NUL-terminated per the caller's contract
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map_err(|_| format!("{} is not valid UTF-8", what))
+}
+
+/// Opaque handle to an opened tree.
+pub struct CkIndex {
+    inner: ck_engine::CkIndex,
+    runtime: tokio::runtime::Runtime,
+}
+
+/// One search hit. Strings are owned by the `CkHits` list the hit came from.
+#[repr(C)]
+pub struct CkHit {
+    pub path: *const c_char,
+    pub line_start: u32,
+    pub line_end: u32,
+    pub score: f32,
+    pub snippet: *const c_char,
+    /// NULL when the language is unknown
+    pub language: *const c_char,
+    /// NULL unless the hit is a symbol definition
+    pub symbol: *const c_char,
+}
+
+/// Opaque list of hits returned by `ck_search`.
+pub struct CkHits {
+    hits: Vec<CkHit>,
+    // Backing storage for the pointers in `hits`; CString buffers do not move
+    _strings: Vec<CString>,
+}
+
+impl CkHits {
+    fn new(hits: Vec<ck_engine::Hit>) -> Self {
+        let mut strings = Vec::new();
+        let mut intern = |s: String| {
+            let s = CString::new(s.replace('\0', " ")).unwrap_or_default();
+            let ptr = s.as_ptr();
+            strings.push(s);
+            ptr
+        };
+        let hits = hits
+            .into_iter()
+            .map(|hit| CkHit {
+                path: intern(hit.path.display().to_string()),
+                line_start: hit.span.line_start as u32,
+                line_end: hit.span.line_end as u32,
+                score: hit.score,
+                snippet: intern(hit.snippet),
+                language: hit.language.map_or(ptr::null(), |l| intern(l.to_string())),
+                symbol: hit.symbol.map_or(ptr::null(), &mut intern),
+            })
+            .collect();
+        Self {
+            hits,
+            _strings: strings,
+        }
+    }
+}
+
+/// Open the tree rooted at `root`. Returns NULL on failure.
+///
+/// # Safety
+/// `root` must be a valid NUL-terminated UTF-8 string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ck_index_open(root: *const c_char) -> *mut CkIndex {
+    guard(ptr::null_mut(), || {
+        // SAFETY: forwarded caller contract
+        let root = unsafe { read_str(root, "root") }?;
+        let inner = ck_engine::CkIndex::open(root).map_err(|e| e.to_string())?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(CkIndex { inner, runtime })))
+    })
+}
+
+/// Bring the index up to date, computing embeddings when `embeddings` is true.
+/// Returns 0 on success and -1 on failure.
+///
+/// # Safety
+/// `index` must come from `ck_index_open` and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ck_index_update(index: *const CkIndex, embeddings: bool) -> i32 {
+    guard(-1, || {
+        // SAFETY: valid handle per the caller's contract
+        let index = unsafe { index.as_ref() }.ok_or("index is NULL")?;
+        index
+            .runtime
+            .block_on(index.inner.update(embeddings))
+            .map_err(|e| e.to_string())?;
+        Ok(0)
+    })
+}
+
+/// Run a query in `mode` (one of the `CK_MODE_*` constants). `top_k` of 0 uses
+/// the mode's default; a negative `threshold` means no threshold. Returns NULL on
+/// failure; an empty list is not a failure.
+///
+/// # Safety
+/// `index` must come from `ck_index_open` and not have been freed; `query` must
+/// be a valid NUL-terminated UTF-8 string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ck_search(
+    index: *const CkIndex,
+    query: *const c_char,
+    mode: u32,
+    top_k: u32,
+    threshold: f32,
+) -> *mut CkHits {
+    guard(ptr::null_mut(), || {
+        // SAFETY: valid handle and string per the caller's contract
+        let index = unsafe { index.as_ref() }.ok_or("index is NULL")?;
+        let text = unsafe { read_str(query, "query") }?;
+        let mut query = match mode {
+            CK_MODE_SEMANTIC => ck_engine::Query::semantic(text),
+            CK_MODE_LEXICAL => ck_engine::Query::lexical(text),
+            CK_MODE_HYBRID => ck_engine::Query::hybrid(text),
+            CK_MODE_REGEX => ck_engine::Query::regex(text),
+            CK_MODE_SYMBOL => ck_engine::Query::symbol(text),
+            other => return Err(format!("unknown search mode {}", other)),
+        };
+        if top_k > 0 {
+            query = query.top_k(top_k as usize);
+        }
+        if threshold >= 0.0 {
+            query = query.threshold(threshold);
+        }
+        let hits = index
+            .runtime
+            .block_on(index.inner.search(query))
+            .map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(CkHits::new(hits))))
+    })
+}
+
+/// Number of hits in `hits` (0 for NULL).
+///
+/// # Safety
+/// `hits` must be NULL or come from `ck_search` and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ck_hits_len(hits: *const CkHits) -> usize {
+    // SAFETY: valid list per the caller's contract
+    unsafe { hits.as_ref() }.map_or(0, |hits| hits.hits.len())
+}
+
+/// Hit at `i`, or NULL when out of range. The pointer is valid until
+/// `ck_hits_free`.
+///
+/// # Safety
+/// `hits` must be NULL or come from `ck_search` and not have been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ck_hits_get(hits: *const CkHits, i: usize) -> *const CkHit {
+    // SAFETY: valid list per the caller's contract
+    unsafe { hits.as_ref() }
+        .and_then(|hits| hits.hits.get(i))
+        .map_or(ptr::null(), |hit| hit as *const CkHit)
+}
+
+/// Free a hit list. NULL is ignored.
+///
+/// # Safety
+/// `hits` must be NULL or come from `ck_search`, and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ck_hits_free(hits: *mut CkHits) {
+    if !hits.is_null() {
+        // SAFETY: allocated by `ck_search` via Box::into_raw
+        drop(unsafe { Box::from_raw(hits) });
+    }
+}
+
+/// Close an index handle. NULL is ignored.
+///
+/// # Safety
+/// `index` must be NULL or come from `ck_index_open`, and must not be used
+/// afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ck_index_free(index: *mut CkIndex) {
+    if !index.is_null() {
+        // SAFETY: allocated by `ck_index_open` via Box::into_raw
+        drop(unsafe { Box::from_raw(index) });
+    }
+}
+
+/// Message for the most recent failure on this thread, or NULL. Valid until the
+/// next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn ck_last_error() -> *const c_char {
+    LAST_ERROR.with(|slot| slot.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_open_search_iterate_free() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("lib.rs"), "fn a() {}\n// TODO: ffi\n").unwrap();
+        let root = CString::new(temp_dir.path().to_str().unwrap()).unwrap();
+        let query = CString::new("TODO").unwrap();
+
+        unsafe {
+            let index = ck_index_open(root.as_ptr());
+            assert!(!index.is_null());
+
+            let hits = ck_search(index, query.as_ptr(), CK_MODE_REGEX, 0, -1.0);
+            assert_eq!(ck_hits_len(hits), 1);
+            let hit = &*ck_hits_get(hits, 0);
+            assert_eq!(hit.line_start, 2);
+            assert!(
+                CStr::from_ptr(hit.path)
+                    .to_str()
+                    .unwrap()
+                    .ends_with("lib.rs")
+            );
+            assert_eq!(CStr::from_ptr(hit.language).to_str().unwrap(), "rust");
+            assert!(hit.symbol.is_null());
+            assert!(ck_hits_get(hits, 1).is_null());
+            ck_hits_free(hits);
+
+            assert!(ck_search(index, query.as_ptr(), 99, 0, -1.0).is_null());
+            let error = CStr::from_ptr(ck_last_error()).to_str().unwrap();
+            assert!(error.contains("unknown search mode"));
+
+            ck_index_free(index);
+        }
+
+        let missing = CString::new("/nonexistent/ck-ffi-root").unwrap();
+        assert!(unsafe { ck_index_open(missing.as_ptr()) }.is_null());
+    }
+}
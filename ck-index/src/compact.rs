@@ -0,0 +1,103 @@
+//! `ck --compact`: garbage-collect what incremental updates leave behind.
+//!
+//! Updates only rewrite the sidecars of files they re-index. Deleted files keep
+//! their manifest entries and sidecars, in effect as tombstones, until an
+//! explicit cleanup. A killed run can also leave a journal and half-written
+//! temporary files in `.ck`. There are no shared segments or ANN graph to
+//! merge: each sidecar holds one file's chunks and embeddings, and semantic
+//! search scans them directly. Compaction therefore reclaims space by dropping
+//! dead files and re-linking duplicates around them.
+
+use crate::{
+    CompactStats, ProgressCallback, Result, cleanup_validation, deduplicate_index, generation,
+    index_size_on_disk, load_or_create_manifest, normalize_manifest_paths, remove_empty_dirs,
+    save_manifest,
+};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Prefix `tempfile` gives the files `atomic_write` persists over sidecars.
+const TEMP_PREFIX: &str = ".tmp";
+
+/// Temporary files younger than this may belong to a run still in progress.
+const STALE_TEMP_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// Compact the index at `path`, reporting each phase to `progress_callback`.
+pub fn compact_index(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    progress_callback: Option<ProgressCallback>,
+) -> Result<CompactStats> {
+    let index_dir = path.join(".ck");
+    let manifest_path = index_dir.join("manifest.json");
+    if !manifest_path.exists() {
+        return Ok(CompactStats::default());
+    }
+    let _writing = generation::begin_write(path);
+    let report = |message: &str| {
+        if let Some(callback) = &progress_callback {
+            callback(message);
+        }
+    };
+    let mut stats = CompactStats {
+        bytes_before: index_size_on_disk(&index_dir),
+        ..Default::default()
+    };
+
+    report("Folding journal into manifest...");
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    normalize_manifest_paths(&mut manifest, path);
+
+    report("Dropping deleted files...");
+    let cleanup =
+        cleanup_validation::validate_and_cleanup_index(path, &index_dir, &mut manifest, options)?;
+    stats.entries_removed = cleanup.orphaned_entries_removed;
+    stats.sidecars_removed = cleanup.orphaned_sidecars_removed;
+    if stats.entries_removed > 0 {
+        manifest.touch();
+    }
+    save_manifest(&manifest_path, &manifest)?;
+
+    report("Removing stale temporary files...");
+    stats.temp_files_removed = remove_stale_temp_files(&index_dir)?;
+
+    if manifest.embedding_model.is_some() {
+        report("Re-linking duplicate chunks...");
+        stats.sidecars_relinked = deduplicate_index(path, &manifest)?;
+        if stats.sidecars_relinked > 0 {
+            manifest.touch();
+            save_manifest(&manifest_path, &manifest)?;
+        }
+    }
+
+    report("Removing empty directories...");
+    remove_empty_dirs(&index_dir)?;
+
+    stats.bytes_after = index_size_on_disk(&index_dir);
+    Ok(stats)
+}
+
+fn remove_stale_temp_files(index_dir: &Path) -> Result<usize> {
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in walkdir::WalkDir::new(index_dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file()
+            || !entry.file_name().to_string_lossy().starts_with(TEMP_PREFIX)
+        {
+            continue;
+        }
+        let stale = entry
+            .metadata()?
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= STALE_TEMP_AGE);
+        if stale {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
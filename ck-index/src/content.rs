@@ -0,0 +1,313 @@
+//! Text extracted from PDFs, kept under `.ck/content` for chunking, regex
+//! search and display.
+//!
+//! Sidecars store spans rather than chunk text, so source files are never
+//! copied into the index; extracted text is the exception, and on docs-heavy
+//! repos it dominates the index's size. Each file is stored as a zstd frame.
+//! Once the corpus holds enough text, the first indexing run to notice trains
+//! a dictionary on it ([`CONTENT_DICTIONARY_FILE`]) and recompresses every
+//! file with it, since documents from one project share much of their
+//! vocabulary. Readers decompress one file at a time, when a result from it is
+//! searched or shown. Caches written as plain text by earlier versions are
+//! still read. In encrypted indexes the compressed files and the dictionary
+//! are sealed like every other artifact.
+
+use crate::{Result, encryption};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Dictionary shared by the compressed content files, next to `manifest.json`.
+pub const CONTENT_DICTIONARY_FILE: &str = "content.dict";
+
+const COMPRESSED_EXTENSION: &str = "zst";
+
+const COMPRESSION_LEVEL: i32 = 9;
+
+/// Upper bound on the trained dictionary's size.
+const DICTIONARY_SIZE: usize = 64 * 1024;
+
+/// Training needs at least this much text; below it a dictionary costs more
+/// than it saves.
+const MIN_TRAINING_BYTES: usize = 1024 * 1024;
+
+/// Texts are cut into samples of this size for training.
+const SAMPLE_SIZE: usize = 4096;
+
+/// Where the text extracted from `file_path` is stored.
+pub fn content_path(repo_root: &Path, file_path: &Path) -> PathBuf {
+    let plain = ck_core::pdf::get_content_cache_path(repo_root, file_path);
+    let mut name = plain.into_os_string();
+    name.push(".");
+    name.push(COMPRESSED_EXTENSION);
+    PathBuf::from(name)
+}
+
+fn dictionary_path(repo_root: &Path) -> PathBuf {
+    repo_root.join(".ck").join(CONTENT_DICTIONARY_FILE)
+}
+
+/// The contents of a stored file, decrypted when the index is encrypted.
+fn read_stored(repo_root: &Path, path: &Path) -> io::Result<Vec<u8>> {
+    encryption::unseal(&repo_root.join(".ck"), fs::read(path)?).map_err(io::Error::other)
+}
+
+fn write_stored(repo_root: &Path, path: &Path, data: Vec<u8>) -> Result<()> {
+    fs::write(path, encryption::seal(&repo_root.join(".ck"), data)?)?;
+    Ok(())
+}
+
+fn read_dictionary(repo_root: &Path) -> io::Result<Option<Vec<u8>>> {
+    match read_stored(repo_root, &dictionary_path(repo_root)) {
+        Ok(dictionary) => Ok(Some(dictionary)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn compress(text: &[u8], dictionary: Option<&[u8]>) -> io::Result<Vec<u8>> {
+    let mut compressor =
+        zstd::bulk::Compressor::with_dictionary(COMPRESSION_LEVEL, dictionary.unwrap_or_default())?;
+    compressor.compress(text)
+}
+
+/// Store `text` as the content of `file_path`, compressed with the corpus
+/// dictionary when there is one.
+pub(crate) fn write_content(repo_root: &Path, file_path: &Path, text: &str) -> Result<()> {
+    let path = content_path(repo_root, file_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let dictionary = read_dictionary(repo_root)?;
+    write_stored(
+        repo_root,
+        &path,
+        compress(text.as_bytes(), dictionary.as_deref())?,
+    )?;
+    // A plain-text cache from an earlier version is superseded
+    remove_file_if_exists(&ck_core::pdf::get_content_cache_path(repo_root, file_path))?;
+    Ok(())
+}
+
+/// Reader over the stored content of `file_path`, decompressing as it goes.
+pub fn open_content(repo_root: &Path, file_path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let path = content_path(repo_root, file_path);
+    let data = match read_stored(repo_root, &path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let legacy = ck_core::pdf::get_content_cache_path(repo_root, file_path);
+            return Ok(Box::new(BufReader::new(fs::File::open(legacy)?)));
+        }
+        Err(e) => return Err(e),
+    };
+    let frame_dictionary = zstd::zstd_safe::get_dict_id_from_frame(&data);
+    let reader = Cursor::new(data);
+    let decoder = match frame_dictionary {
+        None => zstd::stream::read::Decoder::with_buffer(reader)?,
+        Some(id) => {
+            let dictionary = read_dictionary(repo_root)?.unwrap_or_default();
+            if zstd::zstd_safe::get_dict_id_from_dict(&dictionary) != Some(id) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} needs a content dictionary this index no longer has; run 'ck --clean' and index again to extract it",
+                        path.display()
+                    ),
+                ));
+            }
+            zstd::stream::read::Decoder::with_dictionary(reader, &dictionary)?
+        }
+    };
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+/// The stored content of `file_path`.
+pub fn read_content(repo_root: &Path, file_path: &Path) -> io::Result<String> {
+    let mut text = String::new();
+    open_content(repo_root, file_path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// When the content of `file_path` was last stored, if it has been.
+pub(crate) fn content_modified(repo_root: &Path, file_path: &Path) -> Option<SystemTime> {
+    [
+        content_path(repo_root, file_path),
+        ck_core::pdf::get_content_cache_path(repo_root, file_path),
+    ]
+    .iter()
+    .find_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+}
+
+fn remove_file_if_exists(path: &Path) -> io::Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Remove the stored content of `file_path`; returns whether there was any.
+pub(crate) fn remove_content(repo_root: &Path, file_path: &Path) -> io::Result<bool> {
+    let compressed = remove_file_if_exists(&content_path(repo_root, file_path))?;
+    let plain = remove_file_if_exists(&ck_core::pdf::get_content_cache_path(repo_root, file_path))?;
+    Ok(compressed || plain)
+}
+
+/// Train the content dictionary once the corpus holds enough extracted text,
+/// and recompress every content file with it. Returns whether a dictionary
+/// was trained; an index that already has one keeps it.
+pub(crate) fn train_dictionary(repo_root: &Path) -> Result<bool> {
+    let content_dir = repo_root.join(".ck").join("content");
+    if dictionary_path(repo_root).exists() || !content_dir.is_dir() {
+        return Ok(false);
+    }
+    let mut files = Vec::new();
+    for entry in WalkDir::new(&content_dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.into_path();
+        let text = if path
+            .extension()
+            .is_some_and(|ext| ext == COMPRESSED_EXTENSION)
+        {
+            zstd::stream::decode_all(Cursor::new(read_stored(repo_root, &path)?))?
+        } else {
+            fs::read(&path)?
+        };
+        files.push((path, text));
+    }
+    if files.iter().map(|(_, text)| text.len()).sum::<usize>() < MIN_TRAINING_BYTES {
+        return Ok(false);
+    }
+
+    let samples: Vec<&[u8]> = files
+        .iter()
+        .flat_map(|(_, text)| text.chunks(SAMPLE_SIZE))
+        .collect();
+    let dictionary = match zstd::dict::from_samples(&samples, DICTIONARY_SIZE) {
+        Ok(dictionary) => dictionary,
+        Err(e) => {
+            tracing::debug!("Content dictionary training failed: {}", e);
+            return Ok(false);
+        }
+    };
+
+    // Files are rewritten before the dictionary appears, so an interrupted run
+    // leaves frames that still decode, and trains again next time
+    for (path, text) in &files {
+        let compressed_path = if path
+            .extension()
+            .is_some_and(|ext| ext == COMPRESSED_EXTENSION)
+        {
+            path.clone()
+        } else {
+            let mut name = path.clone().into_os_string();
+            name.push(".");
+            name.push(COMPRESSED_EXTENSION);
+            PathBuf::from(name)
+        };
+        let tmp = compressed_path.with_extension("zst.tmp");
+        write_stored(repo_root, &tmp, compress(text, Some(&dictionary))?)?;
+        fs::rename(&tmp, &compressed_path)?;
+        if compressed_path != *path {
+            fs::remove_file(path)?;
+        }
+    }
+    let path = dictionary_path(repo_root);
+    let tmp = path.with_extension("dict.tmp");
+    write_stored(repo_root, &tmp, dictionary.clone())?;
+    fs::rename(&tmp, &path)?;
+    tracing::info!(
+        "Trained a {} byte content dictionary on {} extracted files",
+        dictionary.len(),
+        files.len()
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn page(n: usize) -> String {
+        format!(
+            "Section {n}. The operator shall verify the pressure relief valve \
+             before starting pump {n}, and record the reading in the maintenance log.\n"
+        )
+    }
+
+    #[test]
+    fn test_content_round_trips_and_reads_legacy_caches() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let manual = root.join("docs/manual.pdf");
+        let text = (0..50).map(page).collect::<String>();
+
+        write_content(root, &manual, &text).unwrap();
+        let stored = content_path(root, &manual);
+        assert!(stored.ends_with("docs/manual.pdf.txt.zst"));
+        assert!(fs::metadata(&stored).unwrap().len() < text.len() as u64 / 4);
+        assert_eq!(read_content(root, &manual).unwrap(), text);
+        let second_line = open_content(root, &manual)
+            .unwrap()
+            .lines()
+            .nth(1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(second_line, page(1).trim_end());
+
+        // Earlier versions wrote plain text
+        let guide = root.join("docs/guide.pdf");
+        let legacy = ck_core::pdf::get_content_cache_path(root, &guide);
+        fs::write(&legacy, "plain text").unwrap();
+        assert_eq!(read_content(root, &guide).unwrap(), "plain text");
+        assert!(content_modified(root, &guide).is_some());
+        assert!(remove_content(root, &guide).unwrap());
+        assert!(read_content(root, &guide).is_err());
+    }
+
+    #[test]
+    fn test_dictionary_is_trained_once_the_corpus_is_large_enough() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let first = root.join("a.pdf");
+        write_content(root, &first, &page(0)).unwrap();
+        assert!(!train_dictionary(root).unwrap());
+
+        let texts: Vec<(PathBuf, String)> = (0..200)
+            .map(|i| {
+                let text = (i..i + 60).map(page).collect::<String>();
+                (root.join(format!("manuals/m{i}.pdf")), text)
+            })
+            .collect();
+        for (file, text) in &texts {
+            write_content(root, file, text).unwrap();
+        }
+        // One file predates compression
+        let legacy_file = root.join("old.pdf");
+        let legacy = ck_core::pdf::get_content_cache_path(root, &legacy_file);
+        fs::write(&legacy, page(7)).unwrap();
+
+        assert!(train_dictionary(root).unwrap());
+        assert!(root.join(".ck").join(CONTENT_DICTIONARY_FILE).exists());
+        assert!(!train_dictionary(root).unwrap());
+        for (file, text) in &texts {
+            assert_eq!(&read_content(root, file).unwrap(), text);
+        }
+        assert!(!legacy.exists());
+        assert_eq!(read_content(root, &legacy_file).unwrap(), page(7));
+        assert_eq!(read_content(root, &first).unwrap(), page(0));
+
+        // New content uses the dictionary too
+        let later = root.join("later.pdf");
+        write_content(root, &later, &page(3)).unwrap();
+        let frame = read_stored(root, &content_path(root, &later)).unwrap();
+        assert!(zstd::zstd_safe::get_dict_id_from_frame(&frame).is_some());
+        assert_eq!(read_content(root, &later).unwrap(), page(3));
+    }
+}
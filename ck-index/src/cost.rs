@@ -0,0 +1,324 @@
+//! What an index build costs on a priced embedding endpoint: the estimate
+//! every build checks before it sends anything, and the running spend the
+//! manifest keeps afterwards. Endpoints are priced by the
+//! `remote.cost_per_million_tokens` of their registry entry; local models
+//! have no price and skip both. A build that would spend money goes ahead
+//! only when the approver set with [`set_cost_approver`] accepts the estimate,
+//! whether it was started by `ck --index`, `--add`, a search refreshing the
+//! index, or a reindex from the MCP server or its schedule.
+
+use ck_core::compute_file_hash;
+use ck_embed::Embedder;
+use ck_models::ModelConfig;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use super::{
+    IndexError, IndexManifest, Result, RunSettings, extract_pdf_text, find_repo_root, is_text_file,
+    limits, load_or_create_manifest, path_utils, walk_files,
+};
+
+/// Chunks and tokens the next build would embed, and their price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostEstimate {
+    pub model: String,
+    pub files: usize,
+    pub chunks: usize,
+    pub tokens: u64,
+    /// USD at the endpoint's price
+    pub cost: f64,
+}
+
+impl fmt::Display for CostEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Embedding {} files ({} chunks, ~{} tokens) with {} costs about ${:.2}",
+            self.files, self.chunks, self.tokens, self.model, self.cost
+        )
+    }
+}
+
+/// Tokens sent to a priced endpoint over the life of an index, and what they
+/// cost. Tokens are the provider's own count where it reports one.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddingSpend {
+    pub tokens: u64,
+    pub cost: f64,
+}
+
+/// Accepts or refuses what a build would spend, with the reason for a refusal.
+type CostApprover = Arc<dyn Fn(&CostEstimate) -> std::result::Result<(), String> + Send + Sync>;
+
+static APPROVER: RwLock<Option<CostApprover>> = RwLock::new(None);
+
+/// Ask `approver` before any build embeds with a priced endpoint, replacing
+/// any approver set before. Without one, such builds are refused.
+pub fn set_cost_approver<F>(approver: F)
+where
+    F: Fn(&CostEstimate) -> std::result::Result<(), String> + Send + Sync + 'static,
+{
+    *APPROVER.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(approver));
+}
+
+/// Estimate what indexing `path` with `config` would cost, or `None` when the
+/// model has no price. Only files new or changed since the last build count.
+/// Chunks of a changed file that did not change are reused rather than sent,
+/// so the estimate errs high.
+pub fn estimate_cost(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    config: &ModelConfig,
+) -> Result<Option<CostEstimate>> {
+    if config.remote.cost_per_million_tokens.is_none() {
+        return Ok(None);
+    }
+    let repo_root = find_repo_root(path)?;
+    let manifest = load_or_create_manifest(&path.join(".ck").join("manifest.json"))?;
+    let project = ck_models::ProjectConfig::for_path(&repo_root);
+    let (walked, _) = walk_files(path, options)?;
+    let (files, _) = limits::retain_indexable(walked, &project.limits);
+    Ok(Some(estimate_files(&manifest, config, &repo_root, &files)))
+}
+
+/// Refuse to embed `files` with a priced endpoint unless the approver accepts
+/// what they would cost. Files listed under `"summarize"` count their chunk
+/// text, so the estimate errs high for them too.
+pub(crate) fn guard(
+    manifest: &IndexManifest,
+    config: &ModelConfig,
+    repo_root: &Path,
+    files: &[PathBuf],
+) -> Result<()> {
+    if config.remote.cost_per_million_tokens.is_none() {
+        return Ok(());
+    }
+    approve_cost(&estimate_files(manifest, config, repo_root, files))
+}
+
+/// Ask the approver set with [`set_cost_approver`] whether a build may spend
+/// `estimate`; one that sends nothing needs no approval.
+pub fn approve_cost(estimate: &CostEstimate) -> Result<()> {
+    if estimate.tokens == 0 {
+        return Ok(());
+    }
+    let approver = APPROVER.read().unwrap_or_else(|e| e.into_inner()).clone();
+    match approver {
+        Some(approver) => approver(estimate).map_err(IndexError::Cost),
+        None => Err(IndexError::Cost(format!(
+            "{}; refusing to spend it without approval",
+            estimate
+        ))),
+    }
+}
+
+fn estimate_files(
+    manifest: &IndexManifest,
+    config: &ModelConfig,
+    repo_root: &Path,
+    files: &[PathBuf],
+) -> CostEstimate {
+    let settings = RunSettings::load(repo_root);
+    let counts: Vec<(usize, u64)> = files
+        .par_iter()
+        .filter(|file| !is_embedded(manifest, &config.name, repo_root, file))
+        .filter_map(|file| {
+            let chunks = chunk_file(file, &config.name, &settings)?;
+            Some((chunks.len(), total_tokens(&chunks)))
+        })
+        .collect();
+
+    let tokens = counts.iter().map(|(_, tokens)| tokens).sum();
+    CostEstimate {
+        model: config.name.clone(),
+        files: counts.len(),
+        chunks: counts.iter().map(|(chunks, _)| chunks).sum(),
+        tokens,
+        cost: config.remote.cost_of(tokens).unwrap_or_default(),
+    }
+}
+
+/// Whether `file` is already embedded, unchanged, by `model`. Switching
+/// models re-embeds everything.
+pub(crate) fn is_embedded(
+    manifest: &IndexManifest,
+    model: &str,
+    repo_root: &Path,
+    file: &Path,
+) -> bool {
+    if manifest.embedding_model.as_deref() != Some(model) {
+        return false;
+    }
+    let key = path_utils::to_manifest_path(&path_utils::to_standard_path(file, repo_root));
+    manifest
+        .files
+        .get(&key)
+        .is_some_and(|metadata| compute_file_hash(file).is_ok_and(|hash| hash == metadata.hash))
+}
+
+/// The chunks `file` splits into, or `None` for files the build would skip.
+/// Nothing is written, not even extracted PDF text.
+pub(crate) fn chunk_file(
+    file: &Path,
+    model_name: &str,
+    settings: &RunSettings,
+) -> Option<Vec<ck_chunk::Chunk>> {
+    if !is_text_file(file) {
+        return None;
+    }
+    let (content, lang) = if ck_core::pdf::is_pdf_file(file) {
+        (extract_pdf_text(file).ok()?, Some(ck_core::Language::Pdf))
+    } else {
+        let content = ck_core::encoding::read_text(file).ok()?.text;
+        let lang = ck_core::Language::detect(file, &content);
+        (content, lang)
+    };
+    ck_chunk::chunk_file(
+        file,
+        &content,
+        lang,
+        Some(model_name),
+        settings.project.chunk_strategy,
+        &settings.chunkers,
+    )
+    .ok()
+}
+
+pub(crate) fn total_tokens(chunks: &[ck_chunk::Chunk]) -> u64 {
+    chunks
+        .iter()
+        .map(|chunk| chunk.metadata.estimated_tokens as u64)
+        .sum()
+}
+
+/// Add what `embedders` sent to a priced endpoint to the index's spend.
+pub(crate) fn record_spend(
+    manifest: &mut IndexManifest,
+    config: &ModelConfig,
+    embedders: &[Box<dyn Embedder>],
+) {
+    let tokens: u64 = embedders
+        .iter()
+        .filter_map(|embedder| embedder.tokens_used())
+        .sum();
+    let Some(cost) = config.remote.cost_of(tokens).filter(|_| tokens > 0) else {
+        return;
+    };
+    let spend = manifest.embedding_spend.get_or_insert_default();
+    spend.tokens += tokens;
+    spend.cost += cost;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn priced(cost_per_million_tokens: Option<f64>) -> ModelConfig {
+        let mut config = ck_models::ModelRegistry::default()
+            .get_default_model()
+            .unwrap()
+            .clone();
+        config.remote.cost_per_million_tokens = cost_per_million_tokens;
+        config
+    }
+
+    #[test]
+    fn test_estimate_counts_unindexed_files_at_the_model_price() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("retry.rs"),
+            "fn retry_with_backoff(attempts: u32) -> bool {\n    attempts < 5\n}\n",
+        )
+        .unwrap();
+        let options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+
+        assert!(
+            estimate_cost(temp_dir.path(), &options, &priced(None))
+                .unwrap()
+                .is_none()
+        );
+
+        let estimate = estimate_cost(temp_dir.path(), &options, &priced(Some(2.0)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(estimate.files, 1);
+        assert!(estimate.chunks >= 1);
+        assert!(estimate.tokens > 0);
+        assert!((estimate.cost - estimate.tokens as f64 * 2.0 / 1_000_000.0).abs() < 1e-12);
+        assert!(!temp_dir.path().join(".ck").exists());
+    }
+
+    #[test]
+    fn test_guard_asks_the_approver() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("retry.rs");
+        std::fs::write(&file, "fn retry() -> bool {\n    true\n}\n").unwrap();
+        let files = [file];
+        let manifest = IndexManifest::default();
+        let guard = |price| guard(&manifest, &priced(price), temp_dir.path(), &files);
+
+        assert!(guard(None).is_ok());
+        assert!(matches!(guard(Some(2.0)), Err(IndexError::Cost(_))));
+
+        set_cost_approver(|estimate| {
+            if estimate.cost < 1.0 {
+                Ok(())
+            } else {
+                Err("over budget".to_string())
+            }
+        });
+        assert!(guard(Some(2.0)).is_ok());
+        assert_eq!(guard(Some(1e12)).unwrap_err().to_string(), "over budget");
+    }
+
+    /// A paid endpoint that has billed a fixed number of tokens.
+    struct Billed(u64);
+
+    impl Embedder for Billed {
+        fn id(&self) -> &'static str {
+            "billed"
+        }
+
+        fn dim(&self) -> usize {
+            4
+        }
+
+        fn model_name(&self) -> &str {
+            "billed"
+        }
+
+        fn embed(&mut self, texts: &[String]) -> ck_embed::Result<Vec<Vec<f32>>> {
+            Ok(vec![vec![0.0; 4]; texts.len()])
+        }
+
+        fn tokens_used(&self) -> Option<u64> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn test_record_spend_accumulates_priced_tokens() {
+        let mut manifest = IndexManifest::default();
+        let embedders: Vec<Box<dyn Embedder>> =
+            vec![Box::new(Billed(300_000)), Box::new(Billed(200_000))];
+
+        record_spend(&mut manifest, &priced(None), &embedders);
+        assert!(manifest.embedding_spend.is_none());
+
+        record_spend(&mut manifest, &priced(Some(2.0)), &embedders);
+        record_spend(&mut manifest, &priced(Some(2.0)), &embedders);
+        let spend = manifest.embedding_spend.unwrap();
+        assert_eq!(spend.tokens, 1_000_000);
+        assert!((spend.cost - 2.0).abs() < 1e-9);
+    }
+}
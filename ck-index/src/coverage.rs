@@ -0,0 +1,292 @@
+//! `ck --coverage`: the files of a project the index leaves out, and why, so
+//! that "no results" can be told apart from "not indexed". Every file below
+//! the root is walked, ignored or not, and each one the index does not hold
+//! as it is on disk gets a reason. A directory left out as a whole (say
+//! `node_modules/`, or a submodule) is reported once, with its file count.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::cost::chunk_file;
+use super::{
+    Result, RunSettings, is_text_file, load_or_create_manifest, normalize_manifest_paths,
+    path_utils, submodules, walker,
+};
+
+/// Why a file is not searchable semantically as it is on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverageReason {
+    /// Matched by `.gitignore`, `.ckignore`, an exclude pattern or the
+    /// default excludes, or hidden
+    Ignored,
+    /// Inside a git submodule, with `"submodules"` off
+    Submodule,
+    Binary,
+    /// Over `limits.max_file_size`
+    TooLarge,
+    /// Its extension is disabled under `limits.extensions`
+    DisabledExtension,
+    /// Chunks into more than `limits.max_chunks_per_file`
+    TooManyChunks,
+    /// Indexable, but new since the last build (or it failed to index)
+    NotIndexed,
+    /// Indexed, but changed since
+    Changed,
+}
+
+impl fmt::Display for CoverageReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ignored => "ignored",
+            Self::Submodule => "in a submodule",
+            Self::Binary => "binary",
+            Self::TooLarge => "over max_file_size",
+            Self::DisabledExtension => "extension disabled",
+            Self::TooManyChunks => "over max_chunks_per_file",
+            Self::NotIndexed => "not indexed yet",
+            Self::Changed => "changed since indexed",
+        })
+    }
+}
+
+/// A file, or a directory left out as a whole, missing from the index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UncoveredPath {
+    /// Relative to the root; directories end in `/`
+    pub path: String,
+    pub reason: CoverageReason,
+    /// Files it stands for: 1 for a file
+    pub files: usize,
+}
+
+/// How much of a project the index covers.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CoverageReport {
+    /// Files below the root, ignored ones included
+    pub files: usize,
+    /// Files the index holds as they are on disk
+    pub indexed: usize,
+    /// Ordered by reason, then path
+    pub uncovered: Vec<UncoveredPath>,
+}
+
+impl CoverageReport {
+    /// Files left out for each reason.
+    pub fn counts(&self) -> BTreeMap<CoverageReason, usize> {
+        let mut counts = BTreeMap::new();
+        for uncovered in &self.uncovered {
+            *counts.entry(uncovered.reason).or_default() += uncovered.files;
+        }
+        counts
+    }
+}
+
+/// Check every file below `path`, the root of an index, against the index
+/// and the rules a build with `options` applies.
+pub fn index_coverage(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+) -> Result<CoverageReport> {
+    let index_dir = path.join(".ck");
+    let mut manifest = load_or_create_manifest(&index_dir.join("manifest.json"))?;
+    normalize_manifest_paths(&mut manifest, path);
+    let settings = RunSettings::load(path);
+    let project = &settings.project;
+    let model = manifest
+        .embedding_model
+        .clone()
+        .unwrap_or_else(|| ck_models::ModelRegistry::default().default_model);
+
+    let options = ck_core::FileCollectionOptions {
+        files_from: None,
+        ..options.clone()
+    };
+    let mut walked: HashSet<PathBuf> = HashSet::new();
+    for entry in walker(path, &options, project.submodules)? {
+        let Ok(entry) = entry else { continue };
+        if entry.file_type().is_some_and(|ft| ft.is_file()) {
+            walked.insert(entry.into_path());
+        }
+    }
+
+    let mut report = CoverageReport::default();
+    let mut left_out: Vec<(PathBuf, CoverageReason)> = Vec::new();
+    let mut kept_dirs: HashSet<PathBuf> = HashSet::new();
+    for file in all_files(path, options.follow_symlinks) {
+        report.files += 1;
+        let relative = path_utils::to_standard_path(&file, path);
+        if !walked.contains(&file) {
+            let reason = if project.submodules || submodules::submodule_of(path, &file).is_none() {
+                CoverageReason::Ignored
+            } else {
+                CoverageReason::Submodule
+            };
+            left_out.push((relative, reason));
+            continue;
+        }
+        kept_dirs.extend(relative.ancestors().skip(1).map(Path::to_path_buf));
+
+        let key = path_utils::to_manifest_path(&relative);
+        let reason = if !is_text_file(&file) {
+            CoverageReason::Binary
+        } else if !project.limits.extension_enabled(&file) {
+            CoverageReason::DisabledExtension
+        } else if project
+            .limits
+            .max_file_size
+            .is_some_and(|max| fs::metadata(&file).is_ok_and(|metadata| metadata.len() > max))
+        {
+            CoverageReason::TooLarge
+        } else if let Some(metadata) = manifest.files.get(&key) {
+            if is_unchanged(&file, metadata) {
+                report.indexed += 1;
+                continue;
+            }
+            CoverageReason::Changed
+        } else if project.limits.max_chunks_per_file.is_some_and(|limit| {
+            chunk_file(&file, &model, &settings).is_some_and(|chunks| chunks.len() > limit)
+        }) {
+            CoverageReason::TooManyChunks
+        } else {
+            CoverageReason::NotIndexed
+        };
+        report.uncovered.push(UncoveredPath {
+            path: ck_core::paths::to_slash(&relative),
+            reason,
+            files: 1,
+        });
+    }
+
+    report.uncovered.extend(collapse(left_out, &kept_dirs));
+    report
+        .uncovered
+        .sort_by(|a, b| (a.reason, &a.path).cmp(&(b.reason, &b.path)));
+    Ok(report)
+}
+
+/// Every regular file below `root`, except in `.git` and `.ck` directories.
+fn all_files(root: &Path, follow_symlinks: bool) -> Vec<PathBuf> {
+    ignore::WalkBuilder::new(root)
+        .standard_filters(false)
+        .follow_links(follow_symlinks)
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !matches!(entry.file_name().to_str(), Some(".git" | ".ck"))
+        })
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Whether `file` is as the manifest recorded it: same size and time, or
+/// failing that the same content.
+fn is_unchanged(file: &Path, metadata: &ck_core::FileMetadata) -> bool {
+    let Ok(fs_metadata) = fs::metadata(file) else {
+        return false;
+    };
+    let modified = fs_metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|since| since.as_secs());
+    (modified == Some(metadata.last_modified) && fs_metadata.len() == metadata.size)
+        || ck_core::compute_file_hash(file).is_ok_and(|hash| hash == metadata.hash)
+}
+
+/// Left-out files, each under the topmost directory holding no kept file
+/// (reported once for all of its files), or on its own.
+fn collapse(
+    files: Vec<(PathBuf, CoverageReason)>,
+    kept_dirs: &HashSet<PathBuf>,
+) -> Vec<UncoveredPath> {
+    let mut dirs: BTreeMap<(PathBuf, CoverageReason), usize> = BTreeMap::new();
+    let mut uncovered = Vec::new();
+    for (file, reason) in files {
+        let parents: Vec<&Path> = file.ancestors().skip(1).collect();
+        let outermost = parents
+            .iter()
+            .rev()
+            .find(|dir| !dir.as_os_str().is_empty() && !kept_dirs.contains(**dir));
+        match outermost {
+            Some(dir) => *dirs.entry((dir.to_path_buf(), reason)).or_default() += 1,
+            None => uncovered.push(UncoveredPath {
+                path: ck_core::paths::to_slash(&file),
+                reason,
+                files: 1,
+            }),
+        }
+    }
+    uncovered.extend(
+        dirs.into_iter()
+            .map(|((dir, reason), files)| UncoveredPath {
+                path: format!("{}/", ck_core::paths::to_slash(&dir)),
+                reason,
+                files,
+            }),
+    );
+    uncovered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_coverage_explains_missing_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("node_modules/lib/dist")).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(
+            root.join("src/logo.png"),
+            [0x89, b'P', b'N', b'G', 0, 0, 0, 1],
+        )
+        .unwrap();
+        fs::write(
+            root.join("node_modules/lib/index.js"),
+            "module.exports = 1;\n",
+        )
+        .unwrap();
+        fs::write(root.join("node_modules/lib/dist/a.js"), "var a;\n").unwrap();
+        fs::write(root.join(".ckignore"), "node_modules/\n").unwrap();
+
+        let options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        crate::smart_update_index(root, false, &options)
+            .await
+            .unwrap();
+        fs::write(root.join("src/new.rs"), "fn added() {}\n").unwrap();
+
+        let report = index_coverage(root, &options).unwrap();
+        let uncovered: Vec<(&str, CoverageReason, usize)> = report
+            .uncovered
+            .iter()
+            .map(|u| (u.path.as_str(), u.reason, u.files))
+            .collect();
+        assert_eq!(
+            uncovered,
+            [
+                (".ckignore", CoverageReason::Ignored, 1),
+                ("node_modules/", CoverageReason::Ignored, 2),
+                ("src/logo.png", CoverageReason::Binary, 1),
+                ("src/new.rs", CoverageReason::NotIndexed, 1),
+            ]
+        );
+        assert_eq!(report.files, 6);
+        assert_eq!(report.indexed, 1);
+        assert_eq!(report.counts()[&CoverageReason::Ignored], 3);
+    }
+}
@@ -0,0 +1,479 @@
+//! Near-duplicate chunk detection across the index.
+//!
+//! Vendored copies and generated files repeat the same code many times over. A
+//! chunk with the content hash of a chunk already indexed (its text, without
+//! the path context line) reuses that chunk's embedding instead of being
+//! embedded again. After an index
+//! pass, chunks whose simhash fingerprints are within `MAX_HAMMING_DISTANCE` bits
+//! are grouped; the first location (by path) stays the canonical chunk and lists
+//! the others as aliases, while the aliases point back at it and are skipped at
+//! query time. Embeddings are kept on every chunk so any copy can become
+//! canonical again when the others change or disappear.
+//!
+//! Both read the hash index in `.ck/chunk_hashes.bin`: the content hashes,
+//! fingerprints and links of every sidecar, so that a pass only reads the
+//! sidecars written since the last one.
+
+use crate::{IndexManifest, Result, TimedEmbedding, encryption};
+use ck_core::Span;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+use super::{
+    ChunkEntry, IndexEntry, atomic_write, generation, load_index_entry, path_utils,
+    save_index_entry,
+};
+
+/// Fingerprints differing in at most this many bits are treated as duplicates.
+const MAX_HAMMING_DISTANCE: u32 = 3;
+
+/// Chunks smaller than this (estimated tokens) are too generic to dedup.
+const MIN_DEDUP_TOKENS: usize = 32;
+
+/// Tokens per shingle when fingerprinting.
+const SHINGLE_SIZE: usize = 3;
+
+/// The hash index, next to the manifest.
+pub(crate) const HASH_INDEX_FILE: &str = "chunk_hashes.bin";
+
+/// Another location of a near-identical chunk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkAlias {
+    /// Path relative to the index root
+    pub path: PathBuf,
+    pub span: Span,
+}
+
+/// 64-bit simhash over shingles of identifier/number tokens. Whitespace and
+/// punctuation are ignored, so reformatted copies still collide.
+pub fn simhash(text: &str) -> u64 {
+    let tokens: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|t| !t.is_empty())
+        .collect();
+    if tokens.is_empty() {
+        return 0;
+    }
+
+    let mut weights = [0i32; 64];
+    let mut add = |shingle: &[&str]| {
+        let digest = blake3::hash(shingle.join(" ").as_bytes());
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest.as_bytes()[..8]);
+        let hash = u64::from_le_bytes(bytes);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash >> bit & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    };
+    if tokens.len() < SHINGLE_SIZE {
+        add(&tokens);
+    } else {
+        tokens.windows(SHINGLE_SIZE).for_each(&mut add);
+    }
+
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0u64, |acc, (bit, _)| acc | 1 << bit)
+}
+
+/// Modification time (ns) and length of a file, to tell when it was rewritten.
+type Stamp = (u64, u64);
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()?;
+    Some((modified.as_nanos() as u64, metadata.len()))
+}
+
+/// The chunks of every indexed file, by path relative to the index root.
+#[derive(Default, Serialize, Deserialize)]
+struct HashIndex {
+    files: HashMap<PathBuf, IndexedFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedFile {
+    /// Stamp of the sidecar these chunks were read from
+    sidecar: Stamp,
+    chunks: Vec<IndexedChunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedChunk {
+    /// Set on chunks with an embedding
+    content_hash: Option<String>,
+    span: Span,
+    /// Set on chunks with an embedding that are large enough to dedup
+    fingerprint: Option<u64>,
+    alias_of: Option<PathBuf>,
+    aliases: Vec<ChunkAlias>,
+}
+
+impl HashIndex {
+    fn load(index_dir: &Path) -> Self {
+        fs::read(index_dir.join(HASH_INDEX_FILE))
+            .ok()
+            .and_then(|data| encryption::unseal(index_dir, data).ok())
+            .and_then(|data| bincode::deserialize(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, index_dir: &Path) -> Result<()> {
+        let data = encryption::seal(index_dir, bincode::serialize(self)?)?;
+        atomic_write(&index_dir.join(HASH_INDEX_FILE), &data)
+    }
+}
+
+impl IndexedFile {
+    fn read(sidecar: Stamp, entry: &IndexEntry) -> Self {
+        let chunks = entry
+            .chunks
+            .iter()
+            .map(|chunk| {
+                let embedded = chunk.embedding.is_some();
+                IndexedChunk {
+                    content_hash: chunk.content_hash.clone().filter(|_| embedded),
+                    span: chunk.span.clone(),
+                    fingerprint: chunk.fingerprint.filter(|_| {
+                        embedded && chunk.estimated_tokens.unwrap_or(0) >= MIN_DEDUP_TOKENS
+                    }),
+                    alias_of: chunk.alias_of.clone(),
+                    aliases: chunk.aliases.clone(),
+                }
+            })
+            .collect();
+        Self { sidecar, chunks }
+    }
+}
+
+/// Chunk hashes of the indexes this process has built, with where each chunk
+/// is, as of the hash index stamped `stamp` plus the files embedded since.
+#[derive(Default)]
+struct EmbeddedChunks {
+    stamp: Option<Stamp>,
+    locations: HashMap<String, (PathBuf, usize)>,
+}
+
+static EMBEDDED: LazyLock<Mutex<HashMap<PathBuf, EmbeddedChunks>>> =
+    LazyLock::new(Default::default);
+
+/// Run `f` on the chunk locations of the index at `repo_root`, reloaded when
+/// the hash index on disk changed.
+fn with_embedded<T>(repo_root: &Path, f: impl FnOnce(&mut EmbeddedChunks) -> T) -> T {
+    let index_dir = repo_root.join(".ck");
+    let current = stamp(&index_dir.join(HASH_INDEX_FILE));
+    let mut indexes = EMBEDDED.lock().unwrap_or_else(|e| e.into_inner());
+    let embedded = indexes.entry(repo_root.to_path_buf()).or_default();
+    if embedded.locations.is_empty() || embedded.stamp != current {
+        embedded.stamp = current;
+        embedded.locations.clear();
+        for (path, file) in HashIndex::load(&index_dir).files {
+            for (chunk, indexed) in file.chunks.into_iter().enumerate() {
+                if let Some(hash) = indexed.content_hash {
+                    embedded.locations.insert(hash, (path.clone(), chunk));
+                }
+            }
+        }
+    }
+    f(embedded)
+}
+
+/// Record the chunks of `path` (relative to `repo_root`) just embedded, so
+/// that files indexed after it can reuse their embeddings.
+pub(crate) fn remember(repo_root: &Path, path: &Path, chunks: &[ChunkEntry]) {
+    with_embedded(repo_root, |embedded| {
+        for (index, chunk) in chunks.iter().enumerate() {
+            if let (Some(hash), Some(_)) = (&chunk.content_hash, &chunk.embedding) {
+                embedded
+                    .locations
+                    .entry(hash.clone())
+                    .or_insert_with(|| (path.to_path_buf(), index));
+            }
+        }
+    });
+}
+
+/// Embeddings of chunks already in the index at `repo_root`, by content hash,
+/// for one file being indexed. Each sidecar is read once and a hit is checked
+/// against it, so a stale location only costs an embedding.
+pub(crate) struct SharedEmbeddings<'a> {
+    repo_root: &'a Path,
+    sidecars: HashMap<PathBuf, Option<Vec<ChunkEntry>>>,
+}
+
+impl<'a> SharedEmbeddings<'a> {
+    pub(crate) fn new(repo_root: &'a Path) -> Self {
+        Self {
+            repo_root,
+            sidecars: HashMap::new(),
+        }
+    }
+
+    /// The embedding of an indexed chunk with content hash `hash`, when it has
+    /// `dim` dimensions.
+    pub(crate) fn get(&mut self, hash: &str, dim: usize) -> Option<TimedEmbedding> {
+        let (path, index) = with_embedded(self.repo_root, |embedded| {
+            embedded.locations.get(hash).cloned()
+        })?;
+        let index_dir = self.repo_root.join(".ck");
+        let chunks = self.sidecars.entry(path).or_insert_with_key(|path| {
+            let sidecar = path_utils::get_sidecar_path_for_standard_path(&index_dir, path);
+            load_index_entry(&sidecar).ok().map(|entry| entry.chunks)
+        });
+        let chunk = chunks.as_ref()?.get(index)?;
+        match &chunk.embedding {
+            Some(embedding)
+                if chunk.content_hash.as_deref() == Some(hash) && embedding.len() == dim =>
+            {
+                Some((embedding.clone(), chunk.embedded_at))
+            }
+            _ => None,
+        }
+    }
+}
+
+struct Candidate {
+    path: PathBuf,
+    chunk: usize,
+    span: Span,
+    fingerprint: u64,
+}
+
+/// Recompute duplicate groups for the index at `repo_root` and rewrite the
+/// sidecars whose alias links changed. Only sidecars written since the last
+/// run are read. Returns the number of sidecars rewritten.
+pub fn deduplicate_index(repo_root: &Path, manifest: &IndexManifest) -> Result<usize> {
+    let index_dir = repo_root.join(".ck");
+    if !index_dir.is_dir() {
+        return Ok(0);
+    }
+    let _writing = generation::begin_write(repo_root);
+
+    // Bring the hash index up to date with the manifest's sidecars
+    let mut hashes = HashIndex::load(&index_dir);
+    let mut files = HashMap::with_capacity(manifest.files.len());
+    let mut refreshed = false;
+    for manifest_path in manifest.files.keys() {
+        let path = path_utils::from_manifest_path(manifest_path);
+        if path.is_absolute() {
+            continue;
+        }
+        let sidecar = path_utils::get_sidecar_path_for_standard_path(&index_dir, &path);
+        let Some(sidecar_stamp) = stamp(&sidecar) else {
+            continue;
+        };
+        let file = match hashes.files.remove(&path) {
+            Some(file) if file.sidecar == sidecar_stamp => file,
+            _ => {
+                let Ok(entry) = load_index_entry(&sidecar) else {
+                    continue;
+                };
+                refreshed = true;
+                IndexedFile::read(sidecar_stamp, &entry)
+            }
+        };
+        files.insert(path, file);
+    }
+    refreshed |= !hashes.files.is_empty();
+    hashes.files = files;
+
+    let mut candidates: Vec<Candidate> = hashes
+        .files
+        .iter()
+        .flat_map(|(path, file)| {
+            file.chunks
+                .iter()
+                .enumerate()
+                .filter_map(|(chunk, indexed)| {
+                    Some(Candidate {
+                        path: path.clone(),
+                        chunk,
+                        span: indexed.span.clone(),
+                        fingerprint: indexed.fingerprint?,
+                    })
+                })
+        })
+        .collect();
+    candidates.sort_by(|a, b| (&a.path, a.chunk).cmp(&(&b.path, b.chunk)));
+    let groups = group_near_duplicates(&candidates);
+
+    // Desired links per (file, chunk)
+    let mut alias_of: HashMap<(&Path, usize), &Path> = HashMap::new();
+    let mut aliases: HashMap<(&Path, usize), Vec<ChunkAlias>> = HashMap::new();
+    for group in &groups {
+        let canonical = &candidates[group[0]];
+        for &member in &group[1..] {
+            let alias = &candidates[member];
+            alias_of.insert((&alias.path, alias.chunk), &canonical.path);
+            aliases
+                .entry((&canonical.path, canonical.chunk))
+                .or_default()
+                .push(ChunkAlias {
+                    path: alias.path.clone(),
+                    span: alias.span.clone(),
+                });
+        }
+    }
+
+    // Rewrite the sidecars whose recorded links differ
+    let mut rewritten = 0;
+    for (path, file) in &mut hashes.files {
+        let want = |chunk: usize| {
+            let key = (path.as_path(), chunk);
+            (
+                alias_of.get(&key).map(|p| p.to_path_buf()),
+                aliases.get(&key).cloned().unwrap_or_default(),
+            )
+        };
+        let stale = file.chunks.iter().enumerate().any(|(chunk, indexed)| {
+            let (want_alias_of, want_aliases) = want(chunk);
+            indexed.alias_of != want_alias_of || indexed.aliases != want_aliases
+        });
+        if !stale {
+            continue;
+        }
+        let sidecar = path_utils::get_sidecar_path_for_standard_path(&index_dir, path);
+        let mut index_entry = load_index_entry(&sidecar)?;
+        for (chunk, entry) in index_entry.chunks.iter_mut().enumerate() {
+            (entry.alias_of, entry.aliases) = want(chunk);
+        }
+        save_index_entry(&sidecar, &index_entry)?;
+        if let Some(sidecar_stamp) = stamp(&sidecar) {
+            *file = IndexedFile::read(sidecar_stamp, &index_entry);
+        }
+        rewritten += 1;
+    }
+
+    if refreshed || rewritten > 0 {
+        hashes.save(&index_dir)?;
+    }
+    if !groups.is_empty() {
+        tracing::debug!(
+            "Found {} duplicate chunk groups; rewrote {} sidecars",
+            groups.len(),
+            rewritten
+        );
+    }
+    Ok(rewritten)
+}
+
+/// Group candidate indices whose fingerprints are within `MAX_HAMMING_DISTANCE`.
+/// Exact fingerprint matches are merged first; distinct fingerprints are then
+/// compared only when they agree on one of four 16-bit bands, which any pair
+/// within 3 differing bits must. Each returned group is sorted, so its first
+/// member (lowest path) is the canonical chunk.
+fn group_near_duplicates(candidates: &[Candidate]) -> Vec<Vec<usize>> {
+    let mut by_fingerprint: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, candidate) in candidates.iter().enumerate() {
+        by_fingerprint
+            .entry(candidate.fingerprint)
+            .or_default()
+            .push(i);
+    }
+    let mut fingerprints: Vec<u64> = by_fingerprint.keys().copied().collect();
+    fingerprints.sort_unstable();
+
+    let mut parent: Vec<usize> = (0..fingerprints.len()).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for band in 0..4 {
+        let mut buckets: HashMap<u16, Vec<usize>> = HashMap::new();
+        for (i, fingerprint) in fingerprints.iter().enumerate() {
+            buckets
+                .entry((fingerprint >> (band * 16)) as u16)
+                .or_default()
+                .push(i);
+        }
+        for bucket in buckets.values() {
+            for (n, &a) in bucket.iter().enumerate() {
+                for &b in &bucket[n + 1..] {
+                    if (fingerprints[a] ^ fingerprints[b]).count_ones() <= MAX_HAMMING_DISTANCE {
+                        let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+                        parent[ra.max(rb)] = ra.min(rb);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, fingerprint) in fingerprints.iter().enumerate() {
+        let root = find(&mut parent, i);
+        groups
+            .entry(root)
+            .or_default()
+            .extend(&by_fingerprint[fingerprint]);
+    }
+
+    let mut groups: Vec<Vec<usize>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort_unstable();
+            group
+        })
+        .collect();
+    groups.sort_unstable();
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simhash_ignores_formatting() {
+        let original = "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}";
+        let reformatted = "fn add(a: i32,\n       b: i32) -> i32 { a + b }";
+        let different = "struct Parser { input: String, position: usize }";
+
+        assert_eq!(simhash(original), simhash(reformatted));
+        assert!((simhash(original) ^ simhash(different)).count_ones() > MAX_HAMMING_DISTANCE);
+    }
+
+    #[test]
+    fn test_group_near_duplicates_picks_lowest_path() {
+        let span = Span {
+            byte_start: 0,
+            byte_end: 1,
+            line_start: 1,
+            line_end: 1,
+            col_start: 0,
+            col_end: 0,
+        };
+        let candidate = |path: &str, fingerprint: u64| Candidate {
+            path: PathBuf::from(path),
+            chunk: 0,
+            span: span.clone(),
+            fingerprint,
+        };
+        let candidates = vec![
+            candidate("src/a.rs", 0xFFFF_0000_FFFF_0000),
+            candidate("src/b.rs", 0x1234_5678_9ABC_DEF0),
+            candidate("vendor/a.rs", 0xFFFF_0000_FFFF_0000),
+            candidate("vendor/c.rs", 0xFFFF_0000_FFFF_0007),
+        ];
+
+        assert_eq!(group_near_duplicates(&candidates), vec![vec![0, 2, 3]]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Embedding drift: `.ckconfig.json` selects a different model from the one the
+//! index was built with.
+//!
+//! Vectors from different models live in unrelated spaces, so neither a query
+//! embedded with one nor new chunks embedded with it can be compared against
+//! the other's. Rather than mix them, updates through the configured model are
+//! refused, and the error carries what rebuilding with it would cost next to
+//! the index as it is.
+
+use crate::{
+    IndexError, IndexManifest, Result, index_size_on_disk, list_shards, load_index_entry,
+    load_or_create_manifest,
+};
+use std::fmt;
+use std::path::Path;
+
+/// Sidecars store embeddings as `f32`.
+const BYTES_PER_DIMENSION: u64 = 4;
+
+/// A pause longer than this between embedding timestamps ends an indexing run.
+const RUN_GAP_SECS: u64 = 60;
+
+/// One model's side of a [`RebuildEstimate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelFootprint {
+    pub model: String,
+    pub dimensions: usize,
+    /// Size of the whole `.ck` directory
+    pub disk_bytes: u64,
+    /// Time to embed every chunk, when the index recorded enough to tell
+    pub seconds: Option<u64>,
+}
+
+/// The index as built next to a rebuild with the configured model. Disk
+/// scales the stored vectors by the dimension ratio; time extrapolates the
+/// longest recorded indexing run, scaled the same way as a rough proxy for
+/// model size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RebuildEstimate {
+    pub chunks: usize,
+    pub current: ModelFootprint,
+    pub configured: ModelFootprint,
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+pub(crate) fn duration(seconds: Option<u64>) -> String {
+    match seconds {
+        None => "unknown".to_string(),
+        Some(s) if s < 60 => format!("~{}s", s.max(1)),
+        Some(s) if s < 3600 => format!("~{}m {}s", s / 60, s % 60),
+        Some(s) => format!("~{}h {}m", s / 3600, s % 3600 / 60),
+    }
+}
+
+impl fmt::Display for RebuildEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Rebuild estimate for {} chunks:", self.chunks)?;
+        let width = self.current.model.len().max(self.configured.model.len());
+        for (label, side) in [("indexed", &self.current), ("configured", &self.configured)] {
+            writeln!(
+                f,
+                "  {:<10}  {:<width$}  {:>5} dims  {:>10}  {}",
+                label,
+                side.model,
+                side.dimensions,
+                megabytes(side.disk_bytes),
+                duration(side.seconds)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Seconds per chunk in the longest run of embeddings the index recorded, or
+/// `None` when no run took measurable time.
+fn seconds_per_chunk(mut timestamps: Vec<u64>) -> Option<f64> {
+    timestamps.sort_unstable();
+    let mut best: Option<(usize, u64)> = None;
+    let mut start = 0;
+    for end in 1..=timestamps.len() {
+        if end == timestamps.len() || timestamps[end] - timestamps[end - 1] > RUN_GAP_SECS {
+            let count = end - start;
+            if best.is_none_or(|(best_count, _)| count > best_count) {
+                best = Some((count, timestamps[end - 1] - timestamps[start]));
+            }
+            start = end;
+        }
+    }
+    match best {
+        Some((count, elapsed)) if elapsed > 0 => Some(elapsed as f64 / count as f64),
+        _ => None,
+    }
+}
+
+/// What rebuilding the index at `repo_root` with `configured` would take,
+/// next to the index as `manifest` describes it.
+pub fn estimate_rebuild(
+    repo_root: &Path,
+    manifest: &IndexManifest,
+    configured: &ck_models::ModelConfig,
+) -> Result<RebuildEstimate> {
+    let index_dir = repo_root.join(".ck");
+    let mut chunks = 0usize;
+    let mut timestamps = Vec::new();
+    for shard in list_shards(&index_dir)? {
+        for sidecar in shard.sidecars() {
+            let Ok(entry) = load_index_entry(&sidecar) else {
+                continue;
+            };
+            // Duplicates reuse their canonical chunk's vector
+            for chunk in entry.chunks.iter().filter(|c| c.alias_of.is_none()) {
+                chunks += 1;
+                if chunk.embedding.is_some()
+                    && let Some(at) = chunk.embedded_at
+                {
+                    timestamps.push(at);
+                }
+            }
+        }
+    }
+
+    let current_dims = manifest
+        .embedding_dimensions
+        .unwrap_or(configured.dimensions);
+    let ratio = configured.dimensions as f64 / current_dims.max(1) as f64;
+    let disk_bytes = index_size_on_disk(&index_dir);
+    let vector_bytes = |dims: usize| chunks as u64 * dims as u64 * BYTES_PER_DIMENSION;
+    let seconds = seconds_per_chunk(timestamps).map(|per_chunk| per_chunk * chunks as f64);
+
+    Ok(RebuildEstimate {
+        chunks,
+        current: ModelFootprint {
+            model: manifest.embedding_model.clone().unwrap_or_default(),
+            dimensions: current_dims,
+            disk_bytes,
+            seconds: seconds.map(|s| s.round() as u64),
+        },
+        configured: ModelFootprint {
+            model: configured.name.clone(),
+            dimensions: configured.dimensions,
+            disk_bytes: (disk_bytes.saturating_sub(vector_bytes(current_dims)))
+                + vector_bytes(configured.dimensions),
+            seconds: seconds.map(|s| (s * ratio).round() as u64),
+        },
+    })
+}
+
+/// Fail with [`IndexError::ModelDrift`] when the project config governing
+/// `repo_root` selects a model other than the one `manifest` was built with.
+/// Indexes without embeddings, and projects that leave `model` unset, pass.
+pub(crate) fn check_manifest(repo_root: &Path, manifest: &IndexManifest) -> Result<()> {
+    let (Some(indexed), Some(selected)) = (
+        manifest.embedding_model.as_deref(),
+        ck_models::ProjectConfig::for_path(repo_root).model,
+    ) else {
+        return Ok(());
+    };
+    let registry = ck_models::ModelRegistry::with_user_models();
+    let (_, configured) = registry.resolve(Some(&selected))?;
+    if configured.name == indexed {
+        return Ok(());
+    }
+    Err(IndexError::ModelDrift {
+        indexed: indexed.to_string(),
+        configured: configured.name.clone(),
+        alias: selected,
+        root: repo_root.to_path_buf(),
+        estimate: Box::new(estimate_rebuild(repo_root, manifest, &configured)?),
+    })
+}
+
+/// [`check_manifest`] for the index at `path`, if there is one.
+pub fn check_model_drift(path: &Path) -> Result<()> {
+    let manifest_path = path.join(".ck").join("manifest.json");
+    if !manifest_path.exists() {
+        return Ok(());
+    }
+    check_manifest(path, &load_or_create_manifest(&manifest_path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_seconds_per_chunk_uses_the_longest_run() {
+        assert_eq!(seconds_per_chunk(Vec::new()), None);
+        // Everything embedded within the same second cannot be timed
+        assert_eq!(seconds_per_chunk(vec![100, 100, 100]), None);
+        // A later incremental update does not stretch the full build
+        let mut timestamps: Vec<u64> = (0..10).map(|i| 1_000 + i * 2).collect();
+        timestamps.extend([50_000, 50_001]);
+        assert_eq!(seconds_per_chunk(timestamps), Some(1.8));
+    }
+
+    #[test]
+    fn test_drift_needs_a_configured_model() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir(root.join(".ck")).unwrap();
+        let manifest = IndexManifest {
+            embedding_model: Some("BAAI/bge-small-en-v1.5".to_string()),
+            embedding_dimensions: Some(384),
+            ..Default::default()
+        };
+        check_manifest(root, &manifest).unwrap();
+
+        // The configured model may be named by alias or full name
+        let config_path = root.join(ck_models::PROJECT_CONFIG_FILE);
+        let mut config = ck_models::ProjectConfig {
+            model: Some("bge-small".to_string()),
+            ..Default::default()
+        };
+        config.save(&config_path).unwrap();
+        check_manifest(root, &manifest).unwrap();
+        assert_eq!(
+            ck_models::ModelRegistry::for_project(root).default_model,
+            "bge-small"
+        );
+
+        config.model = Some("nomic-v1.5".to_string());
+        config.save(&config_path).unwrap();
+        let err = check_manifest(root, &manifest).unwrap_err();
+        let IndexError::ModelDrift {
+            configured,
+            estimate,
+            ..
+        } = &err
+        else {
+            panic!("expected drift, got {err}");
+        };
+        assert_eq!(configured, "nomic-embed-text-v1.5");
+        assert_eq!(estimate.current.dimensions, 384);
+        assert_eq!(estimate.configured.dimensions, 768);
+        let message = err.to_string();
+        assert!(message.contains("ck --switch-model nomic-v1.5"));
+        assert!(message.contains("indexed     BAAI/bge-small-en-v1.5"));
+
+        // Unknown models are reported rather than ignored
+        config.model = Some("no-such-model".to_string());
+        config.save(&config_path).unwrap();
+        assert!(check_manifest(root, &manifest).is_err());
+    }
+}
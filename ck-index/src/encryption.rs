@@ -0,0 +1,386 @@
+//! Encryption at rest for the index artifacts derived from source.
+//!
+//! An index created while a key is available is encrypted: sidecars (chunk
+//! spans, metadata and vectors), extracted document text, its compression
+//! dictionary and the HNSW graph are sealed with AES-256-GCM, and decrypted
+//! in memory when read. The key comes from a passphrase (stretched with
+//! Argon2id) or a keyfile, given through [`set_index_key`] or the
+//! [`PASSPHRASE_ENV`] / [`KEYFILE_ENV`] variables. `.ck/encryption.json`
+//! records the salt and a sealed check value, so a wrong key is reported
+//! before anything is read. The manifest and journal stay readable: they hold
+//! paths, hashes and timestamps, which incremental updates need without a key.
+
+use crate::{IndexError, IndexManifest, Result};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, RwLock};
+
+/// Key parameters kept next to `manifest.json` in encrypted indexes.
+pub const ENCRYPTION_FILE: &str = "encryption.json";
+
+/// Passphrase for encrypted indexes, when no key was set explicitly.
+pub const PASSPHRASE_ENV: &str = "CK_INDEX_PASSPHRASE";
+
+/// Keyfile for encrypted indexes, when no key was set explicitly.
+pub const KEYFILE_ENV: &str = "CK_INDEX_KEYFILE";
+
+/// Prefix of every sealed artifact, followed by the nonce and ciphertext.
+const MAGIC: &[u8; 6] = b"CKENC\x01";
+
+const NONCE_LEN: usize = 12;
+
+const SALT_LEN: usize = 16;
+
+/// Sealed into `encryption.json` to tell a wrong key from a corrupt file.
+const CHECK_PLAINTEXT: &[u8] = b"ck index key check";
+
+/// Where an index's key comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IndexKey {
+    Passphrase(String),
+    /// Any file; its whole content is the key material
+    Keyfile(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Kdf {
+    Argon2id,
+    Keyfile,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptionFile {
+    version: u32,
+    cipher: String,
+    kdf: Kdf,
+    salt: String,
+    check: String,
+}
+
+static KEY_SOURCE: RwLock<Option<IndexKey>> = RwLock::new(None);
+
+type KeyCache = HashMap<(PathBuf, IndexKey), Key<Aes256Gcm>>;
+
+/// Derived keys by index directory and source; Argon2 is too slow to run per
+/// artifact.
+static KEYS: OnceLock<Mutex<KeyCache>> = OnceLock::new();
+
+/// Use `key` for every index this process creates or opens, instead of the
+/// environment.
+pub fn set_index_key(key: IndexKey) {
+    *KEY_SOURCE.write().unwrap_or_else(|e| e.into_inner()) = Some(key);
+}
+
+fn key_source() -> Option<IndexKey> {
+    if let Some(key) = KEY_SOURCE.read().unwrap_or_else(|e| e.into_inner()).clone() {
+        return Some(key);
+    }
+    if let Some(path) = std::env::var_os(KEYFILE_ENV).filter(|p| !p.is_empty()) {
+        return Some(IndexKey::Keyfile(PathBuf::from(path)));
+    }
+    std::env::var(PASSPHRASE_ENV)
+        .ok()
+        .filter(|p| !p.is_empty())
+        .map(IndexKey::Passphrase)
+}
+
+fn encryption_error(message: impl Into<String>) -> IndexError {
+    IndexError::Encryption(message.into())
+}
+
+/// Whether the index at `repo_root` is encrypted.
+pub fn is_encrypted(repo_root: &Path) -> bool {
+    repo_root.join(".ck").join(ENCRYPTION_FILE).exists()
+}
+
+fn derive_key(source: &IndexKey, kdf: Kdf, salt: &[u8]) -> Result<Key<Aes256Gcm>> {
+    let mut key = Key::<Aes256Gcm>::default();
+    match (source, kdf) {
+        (IndexKey::Passphrase(passphrase), Kdf::Argon2id) => {
+            argon2::Argon2::default()
+                .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+                .map_err(|e| encryption_error(format!("Key derivation failed: {}", e)))?;
+        }
+        (IndexKey::Keyfile(path), Kdf::Keyfile) => {
+            let material = fs::read(path).map_err(|e| {
+                encryption_error(format!("Cannot read keyfile {}: {}", path.display(), e))
+            })?;
+            let mut hasher = blake3::Hasher::new_derive_key("ck index keyfile v1");
+            hasher.update(salt);
+            hasher.update(&material);
+            key.copy_from_slice(hasher.finalize().as_bytes());
+        }
+        (IndexKey::Passphrase(_), Kdf::Keyfile) => {
+            return Err(encryption_error(format!(
+                "This index is keyed by a keyfile; set {} or pass --keyfile",
+                KEYFILE_ENV
+            )));
+        }
+        (IndexKey::Keyfile(_), Kdf::Argon2id) => {
+            return Err(encryption_error(format!(
+                "This index is keyed by a passphrase; set {}",
+                PASSPHRASE_ENV
+            )));
+        }
+    }
+    Ok(key)
+}
+
+fn seal_with(key: &Key<Aes256Gcm>, data: &[u8]) -> Result<Vec<u8>> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = Aes256Gcm::new(key)
+        .encrypt(&nonce, data)
+        .map_err(|_| encryption_error("Encryption failed"))?;
+    let mut sealed = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+fn unseal_with(key: &Key<Aes256Gcm>, sealed: &[u8]) -> Option<Vec<u8>> {
+    let body = sealed.strip_prefix(MAGIC.as_slice())?;
+    if body.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+    Aes256Gcm::new(key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .ok()
+}
+
+fn read_encryption_file(index_dir: &Path) -> Result<Option<EncryptionFile>> {
+    match fs::read(index_dir.join(ENCRYPTION_FILE)) {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The key for the encrypted index in `index_dir`, checked against its
+/// `encryption.json`; `None` when the index is not encrypted.
+fn index_key(index_dir: &Path) -> Result<Option<Key<Aes256Gcm>>> {
+    key_for(index_dir, key_source().as_ref())
+}
+
+fn key_for(index_dir: &Path, source: Option<&IndexKey>) -> Result<Option<Key<Aes256Gcm>>> {
+    let Some(file) = read_encryption_file(index_dir)? else {
+        return Ok(None);
+    };
+    let Some(source) = source else {
+        return Err(encryption_error(format!(
+            "The index in {} is encrypted; set {} or {} (or pass --keyfile) to open it",
+            index_dir.display(),
+            PASSPHRASE_ENV,
+            KEYFILE_ENV
+        )));
+    };
+    let cache_key = (index_dir.to_path_buf(), source.clone());
+    let keys = KEYS.get_or_init(Default::default);
+    if let Some(key) = keys
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&cache_key)
+    {
+        return Ok(Some(*key));
+    }
+    let decode = |value: &str| {
+        BASE64
+            .decode(value)
+            .map_err(|e| encryption_error(format!("Invalid {}: {}", ENCRYPTION_FILE, e)))
+    };
+    let key = derive_key(source, file.kdf, &decode(&file.salt)?)?;
+    if unseal_with(&key, &decode(&file.check)?).as_deref() != Some(CHECK_PLAINTEXT) {
+        return Err(encryption_error(format!(
+            "Wrong key for the encrypted index in {}",
+            index_dir.display()
+        )));
+    }
+    keys.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(cache_key, key);
+    Ok(Some(key))
+}
+
+/// Seal `data` for an artifact of the index in `index_dir`; returned as is
+/// when the index is not encrypted.
+pub fn seal(index_dir: &Path, data: Vec<u8>) -> Result<Vec<u8>> {
+    match index_key(index_dir)? {
+        Some(key) => seal_with(&key, &data),
+        None => Ok(data),
+    }
+}
+
+/// Open an artifact of the index in `index_dir`. Data that was never sealed,
+/// such as files written before the index was encrypted, is returned as is.
+pub fn unseal(index_dir: &Path, data: Vec<u8>) -> Result<Vec<u8>> {
+    if !data.starts_with(MAGIC) {
+        return Ok(data);
+    }
+    let Some(key) = index_key(index_dir)? else {
+        return Err(encryption_error(format!(
+            "Found encrypted data in {}, but the index has no {}",
+            index_dir.display(),
+            ENCRYPTION_FILE
+        )));
+    };
+    unseal_with(&key, &data)
+        .ok_or_else(|| encryption_error("Encrypted index file failed authentication"))
+}
+
+/// The `.ck` directory holding `artifact`.
+pub(crate) fn index_dir_of(artifact: &Path) -> &Path {
+    artifact
+        .ancestors()
+        .find(|dir| dir.file_name().is_some_and(|name| name == ".ck"))
+        .unwrap_or_else(|| artifact.parent().unwrap_or(artifact))
+}
+
+/// Before writing to the index at `repo_root`: encrypt a new index when a key
+/// is available, and check the key of an encrypted one. An existing
+/// unencrypted index is not converted piecemeal; it has to be rebuilt.
+pub(crate) fn prepare(repo_root: &Path, manifest: &IndexManifest) -> Result<()> {
+    prepare_with(repo_root, manifest, key_source().as_ref())
+}
+
+fn prepare_with(
+    repo_root: &Path,
+    manifest: &IndexManifest,
+    source: Option<&IndexKey>,
+) -> Result<()> {
+    let index_dir = repo_root.join(".ck");
+    if key_for(&index_dir, source)?.is_some() {
+        return Ok(());
+    }
+    let Some(source) = source else {
+        return Ok(());
+    };
+    if !manifest.files.is_empty() {
+        return Err(encryption_error(format!(
+            "The index in {} is not encrypted; remove it with 'ck --clean {}' and index again to encrypt it",
+            index_dir.display(),
+            repo_root.display()
+        )));
+    }
+
+    let kdf = match source {
+        IndexKey::Passphrase(_) => Kdf::Argon2id,
+        IndexKey::Keyfile(_) => Kdf::Keyfile,
+    };
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(source, kdf, &salt)?;
+    let file = EncryptionFile {
+        version: 1,
+        cipher: "aes-256-gcm".to_string(),
+        kdf,
+        salt: BASE64.encode(salt),
+        check: BASE64.encode(seal_with(&key, CHECK_PLAINTEXT)?),
+    };
+    fs::create_dir_all(&index_dir)?;
+    fs::write(
+        index_dir.join(ENCRYPTION_FILE),
+        serde_json::to_vec_pretty(&file)?,
+    )?;
+    tracing::info!("Encrypting the new index in {}", index_dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // The process-wide key is left alone so other tests' indexes stay plain
+    fn seal_for(index_dir: &Path, source: &IndexKey, data: &[u8]) -> Result<Vec<u8>> {
+        seal_with(&key_for(index_dir, Some(source))?.unwrap(), data)
+    }
+
+    fn unseal_for(index_dir: &Path, source: Option<&IndexKey>, data: &[u8]) -> Result<Vec<u8>> {
+        let key = key_for(index_dir, source)?.unwrap();
+        unseal_with(&key, data).ok_or_else(|| encryption_error("failed authentication"))
+    }
+
+    #[test]
+    fn test_artifacts_open_only_with_the_right_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let keyfile = temp_dir.path().join("index.key");
+        fs::write(&keyfile, "team secret").unwrap();
+        let source = IndexKey::Keyfile(keyfile.clone());
+        let root = temp_dir.path().join("repo");
+        let index_dir = root.join(".ck");
+        assert_eq!(index_dir_of(&index_dir.join("src/lib.rs.ck")), index_dir);
+
+        prepare_with(&root, &IndexManifest::default(), Some(&source)).unwrap();
+        assert!(is_encrypted(&root));
+        let sealed = seal_for(&index_dir, &source, b"fn main() {}").unwrap();
+        assert!(sealed.starts_with(MAGIC));
+        assert!(!sealed.windows(4).any(|w| w == b"main"));
+        assert_eq!(
+            unseal_for(&index_dir, Some(&source), &sealed).unwrap(),
+            b"fn main() {}"
+        );
+        // Tampering is detected rather than decoded
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(unseal_for(&index_dir, Some(&source), &tampered).is_err());
+
+        let err = key_for(&index_dir, None).unwrap_err();
+        assert!(err.to_string().contains("is encrypted"));
+        let err = key_for(&index_dir, Some(&IndexKey::Passphrase("x".to_string()))).unwrap_err();
+        assert!(err.to_string().contains("keyed by a keyfile"));
+        let other = temp_dir.path().join("other.key");
+        fs::write(&other, "someone else's secret").unwrap();
+        let err = key_for(&index_dir, Some(&IndexKey::Keyfile(other))).unwrap_err();
+        assert!(err.to_string().contains("Wrong key"));
+    }
+
+    #[test]
+    fn test_passphrase_keys_and_plain_indexes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let source = IndexKey::Passphrase("correct horse".to_string());
+
+        // A populated plain index is not encrypted in place
+        let mut manifest = IndexManifest::default();
+        manifest.files.insert(
+            PathBuf::from("src/lib.rs"),
+            ck_core::FileMetadata {
+                path: PathBuf::from("src/lib.rs"),
+                hash: String::new(),
+                last_modified: 0,
+                size: 0,
+            },
+        );
+        let err = prepare_with(root, &manifest, Some(&source)).unwrap_err();
+        assert!(err.to_string().contains("ck --clean"));
+        assert!(!is_encrypted(root));
+        // Without a key, nothing changes
+        prepare_with(root, &manifest, None).unwrap();
+        assert_eq!(
+            seal(&root.join(".ck"), b"plain".to_vec()).unwrap(),
+            b"plain"
+        );
+        assert_eq!(
+            unseal(&root.join(".ck"), b"plain".to_vec()).unwrap(),
+            b"plain"
+        );
+
+        prepare_with(root, &IndexManifest::default(), Some(&source)).unwrap();
+        let sealed = seal_for(&root.join(".ck"), &source, b"vectors").unwrap();
+        assert_eq!(
+            unseal_for(&root.join(".ck"), Some(&source), &sealed).unwrap(),
+            b"vectors"
+        );
+        let wrong = IndexKey::Passphrase("wrong horse".to_string());
+        assert!(key_for(&root.join(".ck"), Some(&wrong)).is_err());
+    }
+}
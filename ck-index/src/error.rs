@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+use super::INDEX_INTERRUPTED_MSG;
+
+/// Failures from building, updating and reading the `.ck` index.
+#[derive(Debug, Error)]
+pub enum IndexError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid index manifest: {0}")]
+    Manifest(#[from] serde_json::Error),
+
+    /// A sidecar could not be decoded (truncated, or written by another layout)
+    #[error("Corrupt index entry: {0}")]
+    IndexCorrupt(#[from] bincode::Error),
+
+    #[error(transparent)]
+    Core(#[from] ck_core::CkError),
+
+    #[error(transparent)]
+    Model(#[from] ck_models::ModelError),
+
+    #[error(transparent)]
+    Embed(#[from] ck_embed::EmbedError),
+
+    #[error(
+        "Model mismatch: Index was created with '{existing}', but you're trying to use '{requested}'. {hint}"
+    )]
+    ModelMismatch {
+        existing: String,
+        requested: String,
+        hint: String,
+    },
+
+    /// The project config selects a model the index was not built with
+    #[error(
+        "Model drift: the project config selects '{configured}', but the index was built with '{indexed}'. Their embeddings are not comparable, so ck will not mix them.\n{estimate}Rebuild with 'ck --switch-model {alias} {root}', or set \"model\" to '{indexed}' in .ckconfig.json to keep the current index.",
+        root = .root.display()
+    )]
+    ModelDrift {
+        indexed: String,
+        configured: String,
+        /// The model as the project config names it
+        alias: String,
+        root: PathBuf,
+        estimate: Box<super::drift::RebuildEstimate>,
+    },
+
+    #[error(
+        "Embedder returned {actual} embeddings for {expected} chunks in file {file:?}. Expected equal counts."
+    )]
+    EmbeddingCount {
+        file: PathBuf,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error(
+        "Embedder returned empty results for chunk {chunk} in file {file:?}. This may indicate an issue with the embedding model or chunk content."
+    )]
+    EmptyEmbedding { file: PathBuf, chunk: usize },
+
+    #[error("Failed to chunk {file:?}: {message}")]
+    Chunking { file: PathBuf, message: String },
+
+    #[error("Failed to extract text from PDF {file:?}: {message}")]
+    PdfExtract { file: PathBuf, message: String },
+
+    #[error("Binary file, skipping")]
+    BinaryFile,
+
+    #[error(
+        "{file:?} has {chunks} chunks, over the max_chunks_per_file limit of {limit}; skipping"
+    )]
+    TooManyChunks {
+        file: PathBuf,
+        chunks: usize,
+        limit: usize,
+    },
+
+    #[error("{}", INDEX_INTERRUPTED_MSG)]
+    Interrupted,
+
+    /// A build would embed with a priced endpoint and its cost was not approved
+    #[error("{0}")]
+    Cost(String),
+
+    #[error("Worker thread panicked")]
+    WorkerPanicked,
+
+    #[error("System clock error: {0}")]
+    Clock(#[from] std::time::SystemTimeError),
+
+    #[error("Invalid exclude pattern: {0}")]
+    Pattern(#[from] ignore::Error),
+
+    #[error("Directory walk failed: {0}")]
+    Walk(#[from] walkdir::Error),
+
+    /// A missing or wrong key for an encrypted index, or a damaged artifact
+    #[error("{0}")]
+    Encryption(String),
+
+    /// `--as-of` names no kept snapshot, or snapshots are not kept
+    #[error("{0}")]
+    Snapshot(String),
+
+    /// `git log` or the `history.command` provider failed
+    #[error("{0}")]
+    History(String),
+
+    #[error("Failed to replace index file: {0}")]
+    Persist(#[from] tempfile::PersistError),
+}
+
+pub type Result<T> = std::result::Result<T, IndexError>;
@@ -0,0 +1,170 @@
+//! `ck --index --files-from FILE`: index the files another tool selected
+//! (`git ls-files`, `fd`, ...) instead of walking the tree. Entries are
+//! newline-delimited, or NUL-delimited when the list contains a NUL byte
+//! (`git ls-files -z`, `fd -0`). Relative entries are resolved against the
+//! current directory, as those tools print them.
+//!
+//! A build from a list saves it in the index, and later updates (the
+//! automatic one before a search, `--reindex`, `--switch-model`) index the
+//! saved list instead of walking the tree. `ck --clean` forgets it.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{Result, is_text_file};
+
+/// The selection of the last `--files-from` build, NUL-separated paths
+/// relative to the indexed directory.
+pub(crate) const FILE_LIST: &str = "files_from";
+
+/// The paths in a file list, in order, without empty entries.
+pub fn parse_file_list(data: &[u8]) -> Vec<PathBuf> {
+    let delimiter = if data.contains(&0) { b'\0' } else { b'\n' };
+    data.split(|&byte| byte == delimiter)
+        .map(|entry| entry.strip_suffix(b"\r").unwrap_or(entry))
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect()
+}
+
+/// The file list saved by an earlier `--files-from` build of `path`, as
+/// paths under `path`; `None` when the index was built by walking the tree.
+pub fn saved_file_list(path: &Path) -> Option<Vec<PathBuf>> {
+    let data = fs::read(path.join(".ck").join(FILE_LIST)).ok()?;
+    Some(
+        parse_file_list(&data)
+            .into_iter()
+            .map(|file| path.join(file))
+            .collect(),
+    )
+}
+
+/// Save `files`, the selection a build of `path` indexes, when it came from
+/// a file list.
+pub(crate) fn record(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    files: &[PathBuf],
+) -> Result<()> {
+    if options.files_from.is_none() {
+        return Ok(());
+    }
+    let mut data = Vec::new();
+    for file in files {
+        let relative = file.strip_prefix(path).unwrap_or(file);
+        data.extend_from_slice(relative.as_os_str().as_encoded_bytes());
+        data.push(0);
+    }
+    let index_dir = path.join(".ck");
+    fs::create_dir_all(&index_dir)?;
+    fs::write(index_dir.join(FILE_LIST), data)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// The listed files that can be indexed under `path`, spelled as the walker
+/// would spell them (`path` joined with the path inside it). Entries that are
+/// missing, outside `path`, inside `.ck`, not regular files or binary are
+/// left out with a warning; repeats are kept once.
+pub(crate) fn listed_files(path: &Path, files: &[PathBuf]) -> Vec<PathBuf> {
+    let root = ck_core::paths::canonicalize(path);
+    let index_dir = path.join(".ck");
+    let mut seen = HashSet::new();
+    let mut kept = Vec::new();
+    for file in files {
+        let resolved = match file.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                tracing::warn!("Skipping listed file {:?}: {}", file, e);
+                continue;
+            }
+        };
+        let Ok(relative) = resolved.strip_prefix(&root) else {
+            tracing::warn!("Skipping listed file {:?}: outside {:?}", file, path);
+            continue;
+        };
+        let candidate = path.join(relative);
+        if !candidate.is_file() || candidate.starts_with(&index_dir) || !is_text_file(&candidate) {
+            tracing::debug!("Skipping listed file {:?}", file);
+            continue;
+        }
+        if seen.insert(resolved) {
+            kept.push(candidate);
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_file_list_delimiters() {
+        assert_eq!(
+            parse_file_list(b"src/a.rs\r\nsrc/b.rs\n\n"),
+            vec![PathBuf::from("src/a.rs"), PathBuf::from("src/b.rs")]
+        );
+        assert_eq!(
+            parse_file_list(b"with\nnewline.rs\0b.rs\0"),
+            vec![PathBuf::from("with\nnewline.rs"), PathBuf::from("b.rs")]
+        );
+        assert!(parse_file_list(b"").is_empty());
+    }
+
+    #[test]
+    fn test_listed_files_stay_inside_the_index_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("repo");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join(".ck")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn answer() {}\n").unwrap();
+        std::fs::write(root.join(".ck/manifest.json"), "{}").unwrap();
+        std::fs::write(temp_dir.path().join("outside.rs"), "fn outside() {}\n").unwrap();
+
+        let listed = vec![
+            root.join("src/lib.rs"),
+            root.join("src/../src/lib.rs"),
+            root.join("src/missing.rs"),
+            root.join("src"),
+            root.join(".ck/manifest.json"),
+            temp_dir.path().join("outside.rs"),
+        ];
+        assert_eq!(listed_files(&root, &listed), vec![root.join("src/lib.rs")]);
+    }
+
+    #[test]
+    fn test_recorded_list_is_saved_relative_to_the_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let files = vec![root.join("src/lib.rs"), root.join("README.md")];
+        let walked = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        record(root, &walked, &files).unwrap();
+        assert!(saved_file_list(root).is_none());
+
+        let listed = ck_core::FileCollectionOptions {
+            files_from: Some(files.clone()),
+            ..walked
+        };
+        record(root, &listed, &files).unwrap();
+        assert_eq!(saved_file_list(root), Some(files));
+    }
+}
@@ -0,0 +1,369 @@
+//! Generation pinning: searches that overlap an index update in the same
+//! process (an MCP server re-indexing between queries, a library embedding
+//! `ck-index`) read one consistent generation.
+//!
+//! Every finished file advances the manifest generation. A search pins the
+//! generation current when it starts with [`pin_generation`] and reads
+//! sidecars through the pin. While a pin or an update is active, an update
+//! keeps the version of each sidecar it replaces or removes, hard-linked into
+//! the snapshot store, together with the generation that version was last
+//! live in. A pinned read of a file changed since the pin gets that version,
+//! and a file first indexed since the pin is skipped. Kept versions are
+//! deleted as soon as no pin or update can still ask for them; ones left by
+//! a killed process are removed by `ck --compact` like any temporary file.
+//!
+//! Other processes reading the same index see each sidecar replaced
+//! atomically, but not a single generation across files.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+use crate::{IndexEntry, Result, SNAPSHOTS_DIR, Shard, encryption, load_index_entry, shard_of};
+
+/// Pins, updates and kept sidecar versions of the indexes open in this
+/// process, by canonical `.ck` directory.
+static INDEXES: LazyLock<Mutex<HashMap<PathBuf, Generations>>> = LazyLock::new(Default::default);
+
+/// Numbers the kept versions, so their names never collide.
+static NEXT_RETIRED: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Default)]
+struct Generations {
+    /// Latest generation this process has committed or read
+    live: u64,
+    /// Open pins, by pinned generation
+    pins: BTreeMap<u64, usize>,
+    /// Updates in progress
+    writers: usize,
+    retired: Vec<Retired>,
+}
+
+/// The version of a sidecar an update replaced or removed.
+struct Retired {
+    /// Sidecar path, relative to `.ck`
+    relative: PathBuf,
+    /// Last generation in which this version was live
+    generation: u64,
+    /// The kept version, or `None` when the file was not indexed before
+    kept: Option<PathBuf>,
+}
+
+impl Generations {
+    fn is_idle(&self) -> bool {
+        self.pins.is_empty() && self.writers == 0 && self.retired.is_empty()
+    }
+
+    /// Delete the kept versions no pin or update can still ask for.
+    fn reclaim(&mut self) {
+        let oldest_pin = self.pins.keys().next().copied();
+        let live = self.writers > 0;
+        let current = self.live;
+        self.retired.retain(|retired| {
+            let needed = oldest_pin.is_some_and(|pin| pin <= retired.generation)
+                || (live && retired.generation >= current);
+            if !needed && let Some(kept) = &retired.kept {
+                let _ = fs::remove_file(kept);
+            }
+            needed
+        });
+    }
+
+    /// The version of `relative` a pin at `generation` reads: `Some` when a
+    /// kept version or absence stands in for the live sidecar.
+    fn version_at(&self, relative: &Path, generation: u64) -> Option<Option<PathBuf>> {
+        self.retired
+            .iter()
+            .filter(|retired| retired.relative == relative && retired.generation >= generation)
+            .min_by_key(|retired| retired.generation)
+            .map(|retired| retired.kept.clone())
+    }
+}
+
+fn indexes() -> MutexGuard<'static, HashMap<PathBuf, Generations>> {
+    INDEXES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn canonical(index_dir: &Path) -> PathBuf {
+    index_dir
+        .canonicalize()
+        .unwrap_or_else(|_| index_dir.to_path_buf())
+}
+
+/// One generation of the index at a root, held for the length of a search.
+pub struct GenerationPin {
+    /// `.ck` as the caller spelled it, for relating sidecar paths to it
+    index_dir: PathBuf,
+    key: PathBuf,
+    generation: u64,
+}
+
+/// Pin the current generation of the index at `index_root`.
+pub fn pin_generation(index_root: &Path) -> GenerationPin {
+    let index_dir = index_root.join(".ck");
+    let key = canonical(&index_dir);
+    let mut indexes = indexes();
+    let state = indexes.entry(key.clone()).or_default();
+    // An update in this process keeps `live` current; otherwise the index may
+    // have moved on in another process
+    if state.writers == 0 {
+        let stored = crate::read_index_generation(index_root).unwrap_or(0);
+        state.live = state.live.max(stored);
+    }
+    let generation = state.live;
+    *state.pins.entry(generation).or_default() += 1;
+    GenerationPin {
+        index_dir,
+        key,
+        generation,
+    }
+}
+
+impl GenerationPin {
+    /// The pinned generation.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn version_of(&self, sidecar: &Path) -> Option<Option<PathBuf>> {
+        let relative = sidecar.strip_prefix(&self.index_dir).ok()?;
+        indexes()
+            .get(&self.key)?
+            .version_at(relative, self.generation)
+    }
+
+    /// Load `sidecar` as it was at the pinned generation. Fails with
+    /// `NotFound` for a file that was not indexed then.
+    pub fn load_entry(&self, sidecar: &Path) -> Result<IndexEntry> {
+        let not_found = || io::Error::from(io::ErrorKind::NotFound).into();
+        if let Some(version) = self.version_of(sidecar) {
+            return version.map_or_else(|| Err(not_found()), |kept| load_index_entry(&kept));
+        }
+        let loaded = load_index_entry(sidecar);
+        // The sidecar may have been replaced between the check and the read
+        match self.version_of(sidecar) {
+            Some(version) => {
+                version.map_or_else(|| Err(not_found()), |kept| load_index_entry(&kept))
+            }
+            None => loaded,
+        }
+    }
+
+    /// The sidecars of `shard` at the pinned generation: those on disk, and
+    /// those removed since. Load them with [`GenerationPin::load_entry`].
+    pub fn sidecars(&self, shard: &Shard) -> Vec<PathBuf> {
+        let mut sidecars: Vec<PathBuf> = shard.sidecars().collect();
+        let removed: Vec<PathBuf> = indexes()
+            .get(&self.key)
+            .map(|state| {
+                state
+                    .retired
+                    .iter()
+                    .filter(|retired| {
+                        retired.kept.is_some()
+                            && retired.generation >= self.generation
+                            && shard_of(&retired.relative) == shard.name
+                    })
+                    .map(|retired| self.index_dir.join(&retired.relative))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !removed.is_empty() {
+            let present: HashSet<PathBuf> = sidecars.iter().cloned().collect();
+            let mut removed: Vec<PathBuf> = removed
+                .into_iter()
+                .filter(|sidecar| !present.contains(sidecar))
+                .collect();
+            removed.sort();
+            removed.dedup();
+            sidecars.extend(removed);
+        }
+        sidecars
+    }
+}
+
+impl Drop for GenerationPin {
+    fn drop(&mut self) {
+        let mut indexes = indexes();
+        let Some(state) = indexes.get_mut(&self.key) else {
+            return;
+        };
+        if let Some(count) = state.pins.get_mut(&self.generation) {
+            *count -= 1;
+            if *count == 0 {
+                state.pins.remove(&self.generation);
+            }
+        }
+        state.reclaim();
+        if state.is_idle() {
+            indexes.remove(&self.key);
+        }
+    }
+}
+
+/// Held by an update of the index at a root while it writes sidecars.
+pub(crate) struct WriteGuard {
+    key: PathBuf,
+}
+
+/// Mark an update of the index at `repo_root` as in progress, so that pins
+/// taken during it keep the versions it replaces.
+pub(crate) fn begin_write(repo_root: &Path) -> WriteGuard {
+    let key = canonical(&repo_root.join(".ck"));
+    let mut indexes = indexes();
+    let state = indexes.entry(key.clone()).or_default();
+    if state.writers == 0 {
+        let stored = crate::read_index_generation(repo_root).unwrap_or(0);
+        state.live = state.live.max(stored);
+    }
+    state.writers += 1;
+    WriteGuard { key }
+}
+
+impl Drop for WriteGuard {
+    fn drop(&mut self) {
+        let mut indexes = indexes();
+        let Some(state) = indexes.get_mut(&self.key) else {
+            return;
+        };
+        state.writers -= 1;
+        state.reclaim();
+        if state.is_idle() {
+            indexes.remove(&self.key);
+        }
+    }
+}
+
+/// Before `sidecar` is replaced or removed: keep its current version for
+/// pins that may still read it.
+pub(crate) fn retire(sidecar: &Path) -> io::Result<()> {
+    let mut indexes = indexes();
+    if indexes.is_empty() {
+        return Ok(());
+    }
+    let index_dir = encryption::index_dir_of(sidecar);
+    let Some(state) = indexes.get_mut(&canonical(index_dir)) else {
+        return Ok(());
+    };
+    if state.pins.is_empty() && state.writers == 0 {
+        return Ok(());
+    }
+    let Ok(relative) = sidecar.strip_prefix(index_dir) else {
+        return Ok(());
+    };
+    let kept = if sidecar.exists() {
+        let store = index_dir.join(SNAPSHOTS_DIR);
+        fs::create_dir_all(&store)?;
+        // Named like a temporary file so `ck --compact` clears any a killed
+        // process left behind
+        let kept = store.join(format!(
+            ".tmp-retired-{}-{}.ck",
+            std::process::id(),
+            NEXT_RETIRED.fetch_add(1, Ordering::Relaxed)
+        ));
+        if fs::hard_link(sidecar, &kept).is_err() {
+            fs::copy(sidecar, &kept)?;
+        }
+        Some(kept)
+    } else {
+        None
+    };
+    state.retired.push(Retired {
+        relative: relative.to_path_buf(),
+        generation: state.live,
+        kept,
+    });
+    Ok(())
+}
+
+/// After the manifest at `manifest_path` reached `generation`, by a save or a
+/// journal record.
+pub(crate) fn committed(manifest_path: &Path, generation: u64) {
+    let mut indexes = indexes();
+    if indexes.is_empty() {
+        return;
+    }
+    let Some(index_dir) = manifest_path.parent() else {
+        return;
+    };
+    if let Some(state) = indexes.get_mut(&canonical(index_dir)) {
+        state.live = state.live.max(generation);
+        state.reclaim();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IndexManifest, list_shards, save_index_entry, save_manifest};
+    use ck_core::FileMetadata;
+    use tempfile::TempDir;
+
+    fn entry(path: &str, hash: &str) -> IndexEntry {
+        IndexEntry {
+            metadata: FileMetadata {
+                path: PathBuf::from(path),
+                hash: hash.to_string(),
+                last_modified: 0,
+                size: 0,
+            },
+            chunks: Vec::new(),
+        }
+    }
+
+    fn commit(manifest_path: &Path, manifest: &mut IndexManifest) {
+        manifest.touch();
+        save_manifest(manifest_path, manifest).unwrap();
+    }
+
+    #[test]
+    fn test_pinned_search_reads_one_generation() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let index_dir = root.join(".ck");
+        let manifest_path = index_dir.join("manifest.json");
+        let changed = index_dir.join("changed.rs.ck");
+        let deleted = index_dir.join("deleted.rs.ck");
+        let added = index_dir.join("added.rs.ck");
+
+        let mut manifest = IndexManifest::default();
+        save_index_entry(&changed, &entry("changed.rs", "old")).unwrap();
+        save_index_entry(&deleted, &entry("deleted.rs", "old")).unwrap();
+        commit(&manifest_path, &mut manifest);
+
+        let pin = pin_generation(root);
+        {
+            let _writing = begin_write(root);
+            save_index_entry(&changed, &entry("changed.rs", "new")).unwrap();
+            crate::remove_sidecar(&deleted).unwrap();
+            save_index_entry(&added, &entry("added.rs", "new")).unwrap();
+            commit(&manifest_path, &mut manifest);
+        }
+
+        let root_shard = &list_shards(&index_dir).unwrap()[0];
+        let mut seen: Vec<String> = pin
+            .sidecars(root_shard)
+            .iter()
+            .filter_map(|sidecar| pin.load_entry(sidecar).ok())
+            .map(|entry| format!("{}:{}", entry.metadata.path.display(), entry.metadata.hash))
+            .collect();
+        seen.sort();
+        assert_eq!(seen, ["changed.rs:old", "deleted.rs:old"]);
+
+        // A search started after the update sees it
+        let later = pin_generation(root);
+        assert_eq!(later.generation(), pin.generation() + 1);
+        assert_eq!(later.load_entry(&changed).unwrap().metadata.hash, "new");
+        assert!(later.load_entry(&deleted).is_err());
+        drop(later);
+
+        // Kept versions go with the last pin that could read them
+        let store = index_dir.join(SNAPSHOTS_DIR);
+        assert_eq!(fs::read_dir(&store).unwrap().count(), 2);
+        drop(pin);
+        assert_eq!(fs::read_dir(&store).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,395 @@
+//! Project history for `ck --history`: commit messages, and pull request or
+//! issue text printed by `"history": {"command": ...}` in `.ckconfig.json`.
+//!
+//! `ck --index-history` writes each commit and document as a Markdown file
+//! under `.ck/.history` and indexes that directory as a repository of its own,
+//! like a snapshot, so every search mode runs against it unchanged and a
+//! result cites the file of the commit or document it matched. A commit's
+//! file is written once, since commits do not change, and removed once the
+//! commit falls out of the newest `max_commits`. Documents are replaced by
+//! the command's output on every run.
+//!
+//! The command prints a JSON array, or JSON lines, of objects with `id` (or
+//! `number`), `title`, `body` and an optional `url`, which is what
+//! `gh pr list --state all --json number,title,body,url` prints. A command
+//! that runs longer than five minutes is stopped and the run fails. Like
+//! `metadata_command`, the command runs only with
+//! [`ck_models::TRUST_PROJECT_ENV`] set.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::{IndexError, Result};
+
+/// Directory under `.ck` holding the history files and their index. Hidden,
+/// like [`crate::SNAPSHOTS_DIR`], so it can never be mistaken for the shard
+/// of a top-level directory of the repository.
+pub const HISTORY_DIR: &str = ".history";
+
+/// How long the history command may run.
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(300);
+
+const COMMITS_DIR: &str = "commits";
+const DOCUMENTS_DIR: &str = "documents";
+
+/// The files of one `--index-history` run.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFiles {
+    /// Every current history file, to index
+    pub files: Vec<PathBuf>,
+    pub commits: usize,
+    pub documents: usize,
+}
+
+/// Searchable root of the history of the project at `repo_root`.
+pub fn history_root(repo_root: &Path) -> PathBuf {
+    repo_root.join(".ck").join(HISTORY_DIR)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Commit {
+    hash: String,
+    author: String,
+    date: String,
+    message: String,
+    files: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Document {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(default)]
+    number: Option<Value>,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+/// Bring the history files of the project at `repo_root` up to date under
+/// [`history_root`], ready to be indexed there.
+pub fn write_history(repo_root: &Path) -> Result<HistoryFiles> {
+    write_history_with(repo_root, ck_models::project_trusted())
+}
+
+/// [`write_history`], running the project's history command only when it is
+/// `trusted`.
+fn write_history_with(repo_root: &Path, trusted: bool) -> Result<HistoryFiles> {
+    let mut config = ck_models::ProjectConfig::for_path(repo_root).history;
+    if config.command.is_some() && !trusted {
+        tracing::warn!(
+            "Ignoring the history command in {}: project commands run only with {}=1",
+            ck_models::PROJECT_CONFIG_FILE,
+            ck_models::TRUST_PROJECT_ENV
+        );
+        config.command = None;
+    }
+    let root = history_root(repo_root);
+    // Files under the root resolve to its index, not the project's
+    fs::create_dir_all(root.join(".ck"))?;
+    let mut written = HistoryFiles::default();
+
+    match git_log(repo_root, config.max_commits) {
+        Ok(commits) => {
+            let dir = root.join(COMMITS_DIR);
+            fs::create_dir_all(&dir)?;
+            let mut current = HashSet::new();
+            for commit in &commits {
+                let path = dir.join(format!("{}.md", commit.hash));
+                if !path.exists() {
+                    fs::write(&path, commit.render())?;
+                }
+                current.insert(path.clone());
+                written.files.push(path);
+            }
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if !current.contains(&path) {
+                    fs::remove_file(&path)?;
+                }
+            }
+            written.commits = commits.len();
+        }
+        Err(e) if config.command.is_some() => {
+            tracing::warn!("Indexing no commits: {}", e);
+        }
+        Err(e) => return Err(e),
+    }
+
+    let dir = root.join(DOCUMENTS_DIR);
+    if let Some(command) = &config.command {
+        let documents = run_provider(command, repo_root)?;
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        for (id, document) in documents {
+            let path = dir.join(format!("{}.md", file_name(&id)));
+            fs::write(&path, document.render())?;
+            written.files.push(path);
+            written.documents += 1;
+        }
+    } else if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    Ok(written)
+}
+
+fn git_log(repo_root: &Path, max_commits: usize) -> Result<Vec<Commit>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_root)
+        .args(["log", "-n", &max_commits.to_string()])
+        .args([
+            "--format=%x1e%H%x1f%an <%ae>%x1f%aI%x1f%B%x1f",
+            "--name-only",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(IndexError::History(format!(
+            "git log failed in {}: {}",
+            repo_root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Commits from `git log` records: a record separator, then hash, author,
+/// date, message and the `--name-only` file list, separated by unit
+/// separators.
+fn parse_log(output: &str) -> Vec<Commit> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.split('\x1f');
+            let hash = fields.next()?.trim();
+            if hash.is_empty() {
+                return None;
+            }
+            Some(Commit {
+                hash: hash.to_string(),
+                author: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                message: fields.next()?.trim().to_string(),
+                files: fields
+                    .next()
+                    .unwrap_or_default()
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+impl Commit {
+    /// The commit much as `git log --name-only` shows it.
+    fn render(&self) -> String {
+        let mut text = format!(
+            "commit {}\nAuthor: {}\nDate: {}\n\n{}\n",
+            self.hash, self.author, self.date, self.message
+        );
+        if !self.files.is_empty() {
+            text.push_str("\nFiles:\n");
+            for file in &self.files {
+                text.push_str(&format!("  {}\n", file));
+            }
+        }
+        text
+    }
+}
+
+/// The documents `command` prints, by id. Documents without one are
+/// reported and left out.
+fn run_provider(command: &str, repo_root: &Path) -> Result<Vec<(String, Document)>> {
+    let mut shell = ck_core::shell::command(command);
+    shell.current_dir(repo_root);
+    let output = crate::metadata::run_with_timeout(shell, String::new(), PROVIDER_TIMEOUT)
+        .map_err(|e| IndexError::History(format!("history command '{}' {}", command, e)))?;
+    if !output.status.success() {
+        return Err(IndexError::History(format!(
+            "history command '{}' failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let documents: Vec<Document> = if stdout.trim_start().starts_with('[') {
+        serde_json::from_str(&stdout)
+    } else {
+        stdout
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect()
+    }
+    .map_err(|e| {
+        IndexError::History(format!(
+            "history command '{}' printed invalid JSON: {}",
+            command, e
+        ))
+    })?;
+    Ok(documents
+        .into_iter()
+        .filter_map(|document| {
+            let id = match document.id.as_ref().or(document.number.as_ref()) {
+                Some(Value::String(id)) => id.clone(),
+                Some(Value::Null) | None => {
+                    tracing::warn!(
+                        "Skipping history document without an id: {}",
+                        document.title
+                    );
+                    return None;
+                }
+                Some(id) => id.to_string(),
+            };
+            Some((id, document))
+        })
+        .collect())
+}
+
+impl Document {
+    fn render(&self) -> String {
+        let mut text = format!("# {}\n\n", self.title);
+        if let Some(url) = &self.url {
+            text.push_str(&format!("{}\n\n", url));
+        }
+        if let Some(body) = &self.body {
+            text.push_str(body.trim());
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// `id` with everything but letters, digits, `-` and `_` replaced, so ids
+/// like `owner/repo#12` name a file.
+fn file_name(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_records() {
+        let output = "\x1eabc123\x1fAda <ada@example.com>\x1f2024-05-01T10:00:00+00:00\x1fRemove retry from the uploader\n\nRetries hid auth failures.\n\x1f\n\nsrc/upload.rs\nsrc/lib.rs\n\x1edef456\x1fBob <bob@example.com>\x1f2024-04-30T09:00:00+00:00\x1fInitial commit\n\x1f\n";
+        let commits = parse_log(output);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].hash, "abc123");
+        assert_eq!(
+            commits[0].message,
+            "Remove retry from the uploader\n\nRetries hid auth failures."
+        );
+        assert_eq!(commits[0].files, ["src/upload.rs", "src/lib.rs"]);
+        assert!(commits[1].files.is_empty());
+        assert!(commits[0].render().contains("\nFiles:\n  src/upload.rs\n"));
+
+        assert_eq!(file_name("acme/api#12"), "acme-api-12");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_history_store_stays_apart_from_a_history_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join("history")).unwrap();
+        fs::write(root.join("history/notes.md"), "Retry was removed\n").unwrap();
+        ck_models::ProjectConfig {
+            history: ck_models::HistoryConfig {
+                command: Some(r#"echo '[{"id": 7, "title": "Drop retry"}]'"#.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .save(&root.join(ck_models::PROJECT_CONFIG_FILE))
+        .unwrap();
+        let options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        crate::index_directory(root, false, &options, None)
+            .await
+            .unwrap();
+
+        let written = write_history_with(root, true).unwrap();
+        assert_eq!(written.documents, 1);
+        crate::index_directory(&history_root(root), false, &options, None)
+            .await
+            .unwrap();
+
+        let shards = crate::list_shards(&root.join(".ck")).unwrap();
+        let names: Vec<&str> = shards.iter().map(|shard| shard.name.as_str()).collect();
+        assert_eq!(names, [crate::ROOT_SHARD, "history"]);
+        let sidecars: Vec<PathBuf> = shards[1].sidecars().collect();
+        assert_eq!(sidecars.len(), 1);
+        assert!(sidecars[0].ends_with("history/notes.md.ck"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_untrusted_history_command_never_runs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for args in [
+            &["init", "-q"][..],
+            &[
+                "-c",
+                "user.name=ck",
+                "-c",
+                "user.email=ck@example.com",
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "Initial commit",
+            ],
+        ] {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(root)
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        ck_models::ProjectConfig {
+            history: ck_models::HistoryConfig {
+                command: Some("touch spawned; echo '[]'".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .save(&root.join(ck_models::PROJECT_CONFIG_FILE))
+        .unwrap();
+
+        let written = write_history_with(root, false).unwrap();
+        assert_eq!((written.commits, written.documents), (1, 0));
+        assert!(!root.join("spawned").exists());
+
+        write_history_with(root, true).unwrap();
+        assert!(root.join("spawned").exists());
+    }
+}
@@ -0,0 +1,157 @@
+//! Checkpointed indexing progress.
+//!
+//! Rewriting the whole manifest after every file made long index runs quadratic,
+//! so finished files are instead appended to `manifest.journal` as one JSON line
+//! each, and the manifest is only rewritten at checkpoints. Loading a manifest
+//! replays the journal over it, so an interrupted run resumes after the last
+//! file it finished and searches see everything indexed so far. Every manifest
+//! save folds the journal in and removes it.
+
+use crate::{IndexManifest, Result, generation, save_manifest};
+use ck_core::FileMetadata;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Journal file kept next to `manifest.json`.
+pub const JOURNAL_FILE: &str = "manifest.journal";
+
+/// Finished files recorded before the manifest is checkpointed.
+const CHECKPOINT_FILES: usize = 64;
+
+/// Longest time between checkpoints while files keep finishing.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// One finished file, with the manifest generation after it was recorded.
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    generation: u64,
+    file: FileMetadata,
+}
+
+/// Appends completion records for one index run and checkpoints the manifest.
+pub(crate) struct Journal {
+    manifest_path: PathBuf,
+    file: Option<File>,
+    pending: usize,
+    last_checkpoint: Instant,
+}
+
+impl Journal {
+    pub fn new(manifest_path: &Path) -> Self {
+        Self {
+            manifest_path: manifest_path.to_path_buf(),
+            file: None,
+            pending: 0,
+            last_checkpoint: Instant::now(),
+        }
+    }
+
+    /// Add a finished file to `manifest` and the journal, checkpointing when
+    /// enough files or time have accumulated.
+    pub fn record(&mut self, manifest: &mut IndexManifest, metadata: FileMetadata) -> Result<()> {
+        manifest.touch();
+        let record = Record {
+            generation: manifest.generation,
+            file: metadata,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(journal_path(&self.manifest_path))?,
+            ),
+        };
+        file.write_all(&line)?;
+        generation::committed(&self.manifest_path, record.generation);
+        manifest.files.insert(record.file.path.clone(), record.file);
+
+        self.pending += 1;
+        if self.pending >= CHECKPOINT_FILES || self.last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL
+        {
+            self.checkpoint(manifest)?;
+        }
+        Ok(())
+    }
+
+    /// Write `manifest` and start a fresh journal.
+    pub fn checkpoint(&mut self, manifest: &IndexManifest) -> Result<()> {
+        self.file = None;
+        save_manifest(&self.manifest_path, manifest)?;
+        self.pending = 0;
+        self.last_checkpoint = Instant::now();
+        Ok(())
+    }
+
+    /// Checkpoint if anything was recorded since the last one.
+    pub fn finish(&mut self, manifest: &IndexManifest) -> Result<()> {
+        if self.pending > 0 {
+            self.checkpoint(manifest)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn journal_path(manifest_path: &Path) -> PathBuf {
+    manifest_path.with_file_name(JOURNAL_FILE)
+}
+
+/// Apply the records journaled after `manifest` was last saved. A torn final
+/// line, left by a run killed mid-write, is ignored. Returns how many files the
+/// journal contributed.
+pub(crate) fn replay(manifest_path: &Path, manifest: &mut IndexManifest) -> Result<usize> {
+    let Some(records) = read_records(manifest_path)? else {
+        return Ok(0);
+    };
+    let mut replayed = 0;
+    for record in records {
+        manifest.generation = manifest.generation.max(record.generation);
+        manifest.files.insert(record.file.path.clone(), record.file);
+        replayed += 1;
+    }
+    if replayed > 0 {
+        tracing::info!("Resuming from {} journaled index records", replayed);
+    }
+    Ok(replayed)
+}
+
+/// Generation of the last journaled record, for readers that only need to know
+/// whether the index changed since the manifest was saved.
+pub(crate) fn latest_generation(manifest_path: &Path) -> Option<u64> {
+    read_records(manifest_path)
+        .ok()
+        .flatten()?
+        .last()
+        .map(|record| record.generation)
+}
+
+fn read_records(manifest_path: &Path) -> Result<Option<Vec<Record>>> {
+    let file = match File::open(journal_path(manifest_path)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str::<Record>(&line?) {
+            Ok(record) => records.push(record),
+            Err(_) => break,
+        }
+    }
+    Ok(Some(records))
+}
+
+/// Drop the journal once the manifest holds everything it recorded.
+pub(crate) fn clear(manifest_path: &Path) -> Result<()> {
+    match fs::remove_file(journal_path(manifest_path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
@@ -1,27 +1,79 @@
-use anyhow::Result;
+use ck_core::encoding::{DecodedText, TextEncoding};
 use ck_core::{
-    FileMetadata, Language, Span, compute_chunk_hash, compute_file_hash, get_sidecar_path,
+    CancellationToken, FileMetadata, Language, Span, compute_chunk_hash, compute_file_hash,
+    get_sidecar_path,
 };
 use ignore::{WalkBuilder, overrides::OverrideBuilder};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::sync::Once;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, Once};
 use std::time::SystemTime;
 use tempfile::NamedTempFile;
 use walkdir::WalkDir;
 
+mod compact;
+mod content;
+mod cost;
+mod coverage;
+mod dedup;
+mod drift;
+mod encryption;
+mod error;
+mod file_list;
+mod generation;
+mod history;
+mod journal;
+mod limits;
+mod metadata;
+mod plan;
+mod shard;
+mod snapshot;
+mod submodules;
+mod summarize;
+mod verify;
+
+pub use compact::compact_index;
+pub use content::{CONTENT_DICTIONARY_FILE, content_path, open_content, read_content};
+pub use cost::{CostEstimate, EmbeddingSpend, approve_cost, estimate_cost, set_cost_approver};
+pub use coverage::{CoverageReason, CoverageReport, UncoveredPath, index_coverage};
+pub use dedup::{ChunkAlias, deduplicate_index, simhash};
+pub use drift::{ModelFootprint, RebuildEstimate, check_model_drift, estimate_rebuild};
+pub use encryption::{
+    ENCRYPTION_FILE, IndexKey, KEYFILE_ENV, PASSPHRASE_ENV, is_encrypted, seal, set_index_key,
+    unseal,
+};
+pub use error::{IndexError, Result};
+pub use file_list::{parse_file_list, saved_file_list};
+pub use generation::{GenerationPin, pin_generation};
+pub use history::{HISTORY_DIR, HistoryFiles, history_root, write_history};
+pub use journal::JOURNAL_FILE;
+use journal::Journal;
+pub use metadata::{GENERATED_FROM, METADATA_FILE, Metadata, metadata_at};
+pub use plan::{IndexPlan, PlannedFile, plan_index};
+pub use shard::{ROOT_SHARD, Shard, drop_shard, list_shards, shard_of};
+pub use snapshot::{SNAPSHOTS_DIR, Snapshot, is_snapshot_store, list_snapshots, resolve_snapshot};
+pub use submodules::{SUBMODULE, SUBMODULE_COMMIT};
+pub use summarize::{Summarizer, set_summarizer};
+pub use verify::{FlaggedChunk, VerifyReport, verify_index};
+
 fn legacy_model_config(name: &str, dimensions: Option<usize>) -> ck_models::ModelConfig {
     ck_models::ModelConfig {
         name: name.to_string(),
         provider: "fastembed".to_string(),
         dimensions: dimensions.unwrap_or(384),
         max_tokens: 8192,
+        truncation: ck_models::Truncation::Head,
         description: "Legacy ck embedding model (inferred from manifest)".to_string(),
+        onnx: None,
+        normalize: true,
+        strict_dimensions: true,
+        query_instruction: None,
+        document_instruction: None,
+        remote: Default::default(),
     }
 }
 
@@ -79,14 +131,19 @@ pub enum IndexingProgress {
 
 pub type EnhancedProgressCallback = Box<dyn Fn(IndexingProgress) + Send + Sync>;
 
-// Global interrupt flag
-static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// Token of the latest update started without one of its own; Ctrl-C and
+// `request_interrupt` cancel it
+static INTERRUPT: LazyLock<Mutex<CancellationToken>> = LazyLock::new(Default::default);
 static HANDLER_INIT: Once = Once::new();
 
 pub const INDEX_INTERRUPTED_MSG: &str = "Indexing interrupted by user";
 
 pub fn request_interrupt() {
-    INTERRUPTED.store(true, Ordering::SeqCst);
+    interrupt_token().cancel();
+}
+
+fn interrupt_token() -> CancellationToken {
+    INTERRUPT.lock().unwrap_or_else(|e| e.into_inner()).clone()
 }
 
 /// Build override patterns for excluding files during directory traversal
@@ -120,6 +177,12 @@ pub struct ChunkEntry {
     pub chunk_type: Option<String>, // "function", "class", "method", or None for generic
     #[serde(default)]
     pub breadcrumb: Option<String>,
+    /// Name of the definition the chunk covers; feeds the symbol table
+    #[serde(default)]
+    pub symbol: Option<String>,
+    /// Language detected for the chunk's file (extension, then shebang)
+    #[serde(default)]
+    pub language: Option<Language>,
     #[serde(default)]
     pub ancestry: Option<Vec<String>>,
     #[serde(default)]
@@ -133,6 +196,33 @@ pub struct ChunkEntry {
     /// Blake3 hash of the chunk text for incremental indexing
     #[serde(default)]
     pub chunk_hash: Option<String>,
+    /// Blake3 hash of the embedded text without its path context line, the same
+    /// in every copy of a chunk; copies reuse one embedding through it
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// Simhash of the chunk text for near-duplicate detection
+    #[serde(default)]
+    pub fingerprint: Option<u64>,
+    /// Set on duplicates: the file holding the canonical copy of this chunk
+    #[serde(default)]
+    pub alias_of: Option<PathBuf>,
+    /// Set on canonical chunks: other locations of near-identical copies
+    #[serde(default)]
+    pub aliases: Vec<ChunkAlias>,
+    /// When the embedding was computed (Unix seconds); kept when a later run
+    /// reuses it
+    #[serde(default)]
+    pub embedded_at: Option<u64>,
+    /// Key/value pairs from `.ckmeta.json` files and the `metadata_command`
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Shares of the chunk that are code, comments and strings, for `--prefer`
+    #[serde(default)]
+    pub facets: Option<ck_core::Facets>,
+    /// LLM summary embedded in place of the chunk's text, for files listed
+    /// under `"summarize"`
+    #[serde(default)]
+    pub summary: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -150,8 +240,43 @@ pub struct IndexManifest {
     /// - v2 = blake3 of chunk text + leading_trivia + trailing_trivia
     #[serde(default)]
     pub chunk_hash_version: Option<u32>,
+    /// Monotonic counter bumped on every manifest write; consumers key caches on it
+    #[serde(default)]
+    pub generation: u64,
+    /// Layout version of the bincode sidecars; see `SIDECAR_FORMAT_VERSION`
+    #[serde(default)]
+    pub sidecar_version: u32,
+    /// How embeddings are compared, fixed when the first embeddings are written;
+    /// indexes from before metrics were configurable are cosine
+    #[serde(default)]
+    pub metric: ck_models::SimilarityMetric,
+    /// Tokens sent to a priced embedding endpoint and their cost, over every build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_spend: Option<EmbeddingSpend>,
+    /// The model's document instruction when the embeddings were written; a
+    /// change re-embeds every file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_instruction: Option<String>,
+    /// The commit checked out in each indexed submodule, by path; a submodule
+    /// that moves has its files reindexed for the new `submodule_commit`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub submodule_commits: BTreeMap<String, String>,
 }
 
+/// Bump whenever `ChunkEntry`/`IndexEntry` change shape. Sidecars are bincode, which
+/// cannot skip or default fields, so indexes written with an older layout are rebuilt.
+/// - 0 = pre-versioning layout
+/// - 1 = adds `ChunkEntry::symbol`
+/// - 2 = adds `ChunkEntry::{fingerprint, alias_of, aliases}`
+/// - 3 = adds `ChunkEntry::language`
+/// - 4 = adds `ChunkEntry::embedded_at`
+/// - 5 = adds `Span::{col_start, col_end}`
+/// - 6 = adds `ChunkEntry::metadata`
+/// - 7 = adds `ChunkEntry::facets`
+/// - 8 = adds `ChunkEntry::summary`
+/// - 9 = adds `ChunkEntry::content_hash`
+pub const SIDECAR_FORMAT_VERSION: u32 = 9;
+
 impl Default for IndexManifest {
     fn default() -> Self {
         let now = SystemTime::now()
@@ -167,8 +292,109 @@ impl Default for IndexManifest {
             embedding_model: None, // Default to None for backward compatibility
             embedding_dimensions: None,
             chunk_hash_version: Some(2), // v2 = blake3 of chunk text + trivia
+            generation: 0,
+            sidecar_version: SIDECAR_FORMAT_VERSION,
+            metric: ck_models::SimilarityMetric::default(),
+            embedding_spend: None,
+            document_instruction: None,
+            submodule_commits: BTreeMap::new(),
+        }
+    }
+}
+
+impl IndexManifest {
+    /// Record a modification: refresh `updated` and advance `generation`.
+    pub fn touch(&mut self) {
+        self.updated = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.generation += 1;
+    }
+}
+
+/// Settle the metric of an index about to be embedded into: one without
+/// embeddings yet takes the project's, any other keeps its own. Returns
+/// `config` set up to embed for it.
+fn fix_metric(
+    manifest: &mut IndexManifest,
+    repo_root: &Path,
+    config: ck_models::ModelConfig,
+) -> ck_models::ModelConfig {
+    let configured = ck_models::ProjectConfig::for_path(repo_root).metric;
+    if manifest.embedding_model.is_none() {
+        manifest.metric = configured;
+    } else if manifest.metric != configured {
+        tracing::warn!(
+            "Index uses the {} metric but .ckconfig.json asks for {}; run 'ck --clean' and re-index to switch",
+            manifest.metric,
+            configured
+        );
+    }
+    config.for_metric(manifest.metric)
+}
+
+/// Forget every indexed file when the model's document instruction is not the
+/// one the index was embedded with, so the next pass re-embeds them all.
+fn reembed_on_new_instruction(manifest: &mut IndexManifest, config: &ck_models::ModelConfig) {
+    if manifest.document_instruction == config.document_instruction {
+        return;
+    }
+    if manifest.embedding_model.is_some() && !manifest.files.is_empty() {
+        tracing::info!(
+            "The document instruction of {} changed; re-embedding every file",
+            config.name
+        );
+        manifest.files.clear();
+    }
+    manifest.document_instruction = config.document_instruction.clone();
+}
+
+/// Drop the files of every submodule checked out at another commit than the
+/// last build saw, so they are reindexed with the new `submodule_commit`;
+/// their unchanged chunks keep their embeddings.
+fn refresh_moved_submodules(manifest: &mut IndexManifest, repo_root: &Path) {
+    let commits = if ck_models::ProjectConfig::for_path(repo_root).submodules {
+        submodules::checked_out(repo_root)
+    } else {
+        BTreeMap::new()
+    };
+    for (submodule, commit) in &commits {
+        if manifest.submodule_commits.get(submodule) == Some(commit) {
+            continue;
         }
+        let dir = Path::new(submodule);
+        let before = manifest.files.len();
+        manifest
+            .files
+            .retain(|path, _| !path_utils::from_manifest_path(path).starts_with(dir));
+        if manifest.files.len() < before {
+            tracing::info!(
+                "Submodule {} moved to {}; reindexing its files",
+                submodule,
+                commit
+            );
+        }
+    }
+    manifest.submodule_commits = commits;
+}
+
+/// Read the current generation of the index rooted at `path`, if one exists.
+pub fn read_index_generation(path: &Path) -> Option<u64> {
+    let manifest_path = path.join(".ck").join("manifest.json");
+    let data = fs::read(&manifest_path).ok()?;
+
+    #[derive(Deserialize)]
+    struct GenerationOnly {
+        #[serde(default)]
+        generation: u64,
     }
+
+    let saved = serde_json::from_slice::<GenerationOnly>(&data)
+        .ok()?
+        .generation;
+    // Files finished since the last checkpoint advance it too
+    Some(journal::latest_generation(&manifest_path).map_or(saved, |journaled| journaled.max(saved)))
 }
 
 /// Common filtering logic for directory traversal entries
@@ -179,21 +405,115 @@ fn should_include_file(entry: &ignore::DirEntry, index_dir: &Path) -> bool {
         && !path.starts_with(index_dir)
 }
 
-/// Apply common filtering to a WalkBuilder iterator
-fn filter_and_collect_files(walker: ignore::Walk, index_dir: &Path) -> Vec<PathBuf> {
-    walker
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| should_include_file(entry, index_dir))
-        .map(|entry| entry.path().to_path_buf())
-        .collect()
+/// Whether a walk error is a symlink pointing back at one of its ancestors.
+fn is_symlink_loop(err: &ignore::Error) -> bool {
+    match err {
+        ignore::Error::Loop { .. } => true,
+        ignore::Error::WithPath { err, .. }
+        | ignore::Error::WithDepth { err, .. }
+        | ignore::Error::WithLineNumber { err, .. } => is_symlink_loop(err),
+        _ => false,
+    }
+}
+
+/// Apply common filtering to a WalkBuilder iterator. Returns the files and the
+/// number of symlinks left untraversed. With `follow_symlinks`, a file reached
+/// through several links is kept once, under its shortest path.
+fn filter_and_collect_files(
+    walker: ignore::Walk,
+    index_dir: &Path,
+    follow_symlinks: bool,
+) -> (Vec<PathBuf>, usize) {
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut skipped_symlinks = 0;
+    let mut targets: HashMap<PathBuf, usize> = HashMap::new();
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if is_symlink_loop(&err) => {
+                tracing::warn!("Skipping symlink cycle: {}", err);
+                continue;
+            }
+            Err(_) => continue,
+        };
+        if !follow_symlinks && entry.depth() > 0 && entry.path_is_symlink() {
+            tracing::debug!("Not following symlink {:?}", entry.path());
+            skipped_symlinks += 1;
+            continue;
+        }
+        if !should_include_file(&entry, index_dir) {
+            continue;
+        }
+        let path = entry.into_path();
+        if !follow_symlinks {
+            files.push(path);
+            continue;
+        }
+        match targets.entry(ck_core::paths::canonicalize(&path)) {
+            std::collections::hash_map::Entry::Occupied(seen) => {
+                let kept = &mut files[*seen.get()];
+                if (path.components().count(), &path) < (kept.components().count(), &*kept) {
+                    *kept = path;
+                }
+            }
+            std::collections::hash_map::Entry::Vacant(slot) => {
+                slot.insert(files.len());
+                files.push(path);
+            }
+        }
+    }
+    (files, skipped_symlinks)
 }
 
+/// The files below `path` a search reads, submodules included.
 pub fn collect_files(
     path: &Path,
     options: &ck_core::FileCollectionOptions,
 ) -> Result<Vec<PathBuf>> {
+    Ok(collect_walked(path, options, true)?.0)
+}
+
+/// The files an index build walks, plus the number of symlinks not followed.
+/// Unlike [`collect_files`], submodules are left out unless `"submodules"` is
+/// set in the project config, and an index built from a file list keeps
+/// indexing that list.
+fn walk_files(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+) -> Result<(Vec<PathBuf>, usize)> {
+    if options.files_from.is_none()
+        && let Some(files) = saved_file_list(path)
+    {
+        return Ok((file_list::listed_files(path, &files), 0));
+    }
+    let submodules = ck_models::ProjectConfig::for_path(path).submodules;
+    collect_walked(path, options, submodules)
+}
+
+fn collect_walked(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    submodules: bool,
+) -> Result<(Vec<PathBuf>, usize)> {
+    if let Some(files) = &options.files_from {
+        return Ok((file_list::listed_files(path, files), 0));
+    }
     let index_dir = path.join(".ck");
+    Ok(filter_and_collect_files(
+        walker(path, options, submodules)?,
+        &index_dir,
+        options.follow_symlinks,
+    ))
+}
 
+/// A walk of `path` applying the ignore rules and exclude patterns a build
+/// uses, descending into submodules only with `submodules`; entries are not
+/// yet filtered to text files.
+fn walker(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    submodules: bool,
+) -> Result<ignore::Walk> {
     if options.respect_gitignore {
         let overrides = build_overrides(path, &options.exclude_patterns)?;
         let mut walker_builder = WalkBuilder::new(path);
@@ -201,17 +521,19 @@ pub fn collect_files(
             .git_ignore(true)
             .git_global(true)
             .git_exclude(true)
-            .hidden(true);
+            .hidden(true)
+            .follow_links(options.follow_symlinks);
 
         // Add .ckignore support (hierarchical, like .gitignore)
         if options.use_ckignore {
             walker_builder.add_custom_ignore_filename(".ckignore");
         }
+        if !submodules {
+            walker_builder.filter_entry(|entry| !submodules::is_submodule(entry));
+        }
 
         walker_builder.overrides(overrides);
-        let walker = walker_builder.build();
-
-        Ok(filter_and_collect_files(walker, &index_dir))
+        Ok(walker_builder.build())
     } else {
         // Use WalkBuilder without gitignore support, but still apply overrides
         use ck_core::get_default_exclude_patterns;
@@ -227,17 +549,19 @@ pub fn collect_files(
             .git_ignore(false)
             .git_global(false)
             .git_exclude(false)
-            .hidden(true);
+            .hidden(true)
+            .follow_links(options.follow_symlinks);
 
         // Add .ckignore support even without gitignore
         if options.use_ckignore {
             walker_builder.add_custom_ignore_filename(".ckignore");
         }
+        if !submodules {
+            walker_builder.filter_entry(|entry| !submodules::is_submodule(entry));
+        }
 
         walker_builder.overrides(combined_overrides);
-        let walker = walker_builder.build();
-
-        Ok(filter_and_collect_files(walker, &index_dir))
+        Ok(walker_builder.build())
     }
 }
 
@@ -245,7 +569,7 @@ fn collect_files_as_hashset(
     path: &Path,
     options: &ck_core::FileCollectionOptions,
 ) -> Result<HashSet<PathBuf>> {
-    Ok(collect_files(path, options)?.into_iter().collect())
+    Ok(walk_files(path, options)?.0.into_iter().collect())
 }
 
 pub async fn index_directory(
@@ -253,37 +577,55 @@ pub async fn index_directory(
     compute_embeddings: bool,
     options: &ck_core::FileCollectionOptions,
     model: Option<&str>,
-) -> Result<()> {
+) -> Result<UpdateStats> {
+    index_directory_until(path, compute_embeddings, options, model, &interrupt_token()).await
+}
+
+async fn index_directory_until(
+    path: &Path,
+    compute_embeddings: bool,
+    options: &ck_core::FileCollectionOptions,
+    model: Option<&str>,
+    cancel: &CancellationToken,
+) -> Result<UpdateStats> {
     tracing::info!(
         "index_directory called with compute_embeddings={}",
         compute_embeddings
     );
     let index_dir = path.join(".ck");
     fs::create_dir_all(&index_dir)?;
+    let _writing = generation::begin_write(path);
 
     let manifest_path = index_dir.join("manifest.json");
     let mut manifest = load_or_create_manifest(&manifest_path)?;
+    encryption::prepare(path, &manifest)?;
+    upgrade_stale_sidecars(&mut manifest);
     normalize_manifest_paths(&mut manifest, path);
+    refresh_moved_submodules(&mut manifest, path);
 
     // Handle model configuration for embeddings
     let resolved_model = if compute_embeddings {
-        let model_registry = ck_models::ModelRegistry::default();
-        let (alias, config) = model_registry
-            .resolve(model)
-            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        if model.is_none() {
+            drift::check_manifest(path, &manifest)?;
+        }
+        let model_registry = ck_models::ModelRegistry::for_project(path);
+        let (alias, config) = model_registry.resolve(model)?;
 
         if let Some(existing_model) = &manifest.embedding_model
             && existing_model != &config.name
         {
-            return Err(anyhow::anyhow!(
-                "Model mismatch: Index was created with '{}', but you're trying to use '{}'. \
-                Please run 'ck --clean {}' to remove the old index, then rerun with the new model.",
-                existing_model,
-                config.name,
-                path.display()
-            ));
+            return Err(IndexError::ModelMismatch {
+                existing: existing_model.clone(),
+                requested: config.name.clone(),
+                hint: format!(
+                    "Please run 'ck --clean {}' to remove the old index, then rerun with the new model.",
+                    path.display()
+                ),
+            });
         }
 
+        let config = fix_metric(&mut manifest, path, config);
+        reembed_on_new_instruction(&mut manifest, &config);
         manifest.embedding_model = Some(config.name.clone());
         manifest.embedding_dimensions = Some(config.dimensions);
 
@@ -292,44 +634,73 @@ pub async fn index_directory(
         None
     };
 
-    let files = collect_files(path, options)?;
+    let settings = RunSettings::load(path);
+    let (walked, symlinks_skipped) = walk_files(path, options)?;
+    file_list::record(path, options, &walked)?;
+    let (files, skipped) = limits::retain_indexable(walked, &settings.project.limits);
+    let mut stats = UpdateStats {
+        files_skipped_too_large: skipped.too_large,
+        files_skipped_by_extension: skipped.by_extension,
+        symlinks_skipped,
+        ..Default::default()
+    };
+    let mut journal = Journal::new(&manifest_path);
 
     if compute_embeddings {
-        // Sequential processing with small-batch embeddings for streaming performance
-        tracing::info!("Creating embedder for {} files", files.len());
+        // Small-batch embeddings streamed per file, one embedder per shard worker
         let (_, config) = resolved_model
             .as_ref()
             .expect("resolved model must be present when computing embeddings");
-        let mut embedder = ck_embed::create_embedder_for_config(config, None)?;
-
-        for file_path in files.iter() {
-            match index_single_file(file_path, path, Some(&mut embedder)) {
-                Ok(entry) => {
-                    // Write sidecar immediately
-                    let sidecar_path = get_sidecar_path(path, file_path);
-                    save_index_entry(&sidecar_path, &entry)?;
-
-                    // Update and save manifest immediately
-                    let manifest_key = entry.metadata.path.clone();
-                    manifest.files.insert(manifest_key, entry.metadata);
-                    manifest.updated = SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs();
-                    save_manifest(&manifest_path, &manifest)?;
-                }
-                Err(e) => {
-                    // Suppress warnings for binary files and UTF-8 errors in .git directories
-                    let error_msg = e.to_string();
-                    let is_binary_skip = error_msg.contains("Binary file, skipping");
-                    let is_utf8_error = error_msg.contains("stream did not contain valid UTF-8");
-                    let is_git_file = file_path.components().any(|c| c.as_os_str() == ".git");
-
-                    if !(is_binary_skip || is_utf8_error && is_git_file) {
-                        tracing::warn!("Failed to index {:?}: {}", file_path, e);
+        cost::guard(&manifest, config, path, &files)?;
+        let workers = shard::worker_count(settings.project.shard_workers, &files, path);
+        tracing::info!("Creating {} embedder(s) for {} files", workers, files.len());
+        let mut embedders = (0..workers)
+            .map(|_| ck_embed::create_embedder_for_config(config, None))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        manifest.embedding_dimensions = Some(embedders[0].dim());
+        journal.checkpoint(&manifest)?;
+
+        shard::embed_shards(
+            &files,
+            path,
+            &settings,
+            &mut embedders,
+            None,
+            None,
+            cancel,
+            |file_path, result| {
+                match result {
+                    Ok(indexed) => {
+                        stats.encoding_warnings.extend(indexed.encoding_warning);
+                        let entry = indexed.entry;
+                        // Write sidecar immediately, then journal the finished file
+                        let sidecar_path = get_sidecar_path(path, &file_path);
+                        save_index_entry(&sidecar_path, &entry)?;
+                        journal.record(&mut manifest, entry.metadata)?;
+                    }
+                    Err(e) => {
+                        // Suppress warnings for binary files and UTF-8 errors in .git directories
+                        let error_msg = e.to_string();
+                        let is_expected_skip =
+                            matches!(e, IndexError::BinaryFile | IndexError::TooManyChunks { .. });
+                        let is_utf8_error =
+                            error_msg.contains("stream did not contain valid UTF-8");
+                        let is_git_file = file_path.components().any(|c| c.as_os_str() == ".git");
+
+                        if !(is_expected_skip || is_utf8_error && is_git_file) {
+                            tracing::warn!("Failed to index {:?}: {}", file_path, e);
+                        }
+                        if matches!(e, IndexError::TooManyChunks { .. }) {
+                            stats.files_skipped_too_many_chunks += 1;
+                        }
                     }
                 }
-            }
+                Ok(())
+            },
+        )?;
+        cost::record_spend(&mut manifest, config, &embedders);
+        if cancel.is_cancelled() {
+            eprintln!("Indexing interrupted. Progress is saved; rerun to resume.");
         }
     } else {
         // Parallel processing with streaming using producer-consumer pattern
@@ -342,22 +713,37 @@ pub async fn index_directory(
 
         // Spawn worker thread for parallel processing
         let worker_handle = thread::spawn(move || {
+            let cancel = CancellationToken::new();
             files_clone.par_iter().for_each(|file_path| {
-                match index_single_file(file_path, &path_clone, None) {
-                    Ok(entry) => {
-                        if tx.send((file_path.clone(), entry)).is_err() {
+                let result = index_single_file_with_progress(
+                    file_path,
+                    &path_clone,
+                    &settings,
+                    None,
+                    None,
+                    0,
+                    1,
+                    &cancel,
+                );
+                match result {
+                    Ok(indexed) => {
+                        if tx.send((file_path.clone(), Some(indexed))).is_err() {
                             // Receiver dropped, stop processing
                         }
                     }
+                    // Counted by the main thread
+                    Err(IndexError::TooManyChunks { .. }) => {
+                        let _ = tx.send((file_path.clone(), None));
+                    }
                     Err(e) => {
                         // Suppress warnings for binary files and UTF-8 errors in .git directories
                         let error_msg = e.to_string();
-                        let is_binary_skip = error_msg.contains("Binary file, skipping");
+                        let is_expected_skip = matches!(e, IndexError::BinaryFile);
                         let is_utf8_error =
                             error_msg.contains("stream did not contain valid UTF-8");
                         let is_git_file = file_path.components().any(|c| c.as_os_str() == ".git");
 
-                        if !(is_binary_skip || is_utf8_error && is_git_file) {
+                        if !(is_expected_skip || is_utf8_error && is_git_file) {
                             tracing::warn!("Failed to index {:?}: {}", file_path, e);
                         }
                     }
@@ -366,50 +752,55 @@ pub async fn index_directory(
         });
 
         // Main thread: stream results as they arrive
-        while let Ok((file_path, entry)) = rx.recv() {
-            // Write sidecar immediately
+        while let Ok((file_path, indexed)) = rx.recv() {
+            let Some(indexed) = indexed else {
+                stats.files_skipped_too_many_chunks += 1;
+                continue;
+            };
+            stats.encoding_warnings.extend(indexed.encoding_warning);
+            let entry = indexed.entry;
+            // Write sidecar immediately, then journal the finished file
             let sidecar_path = get_sidecar_path(path, &file_path);
             save_index_entry(&sidecar_path, &entry)?;
-
-            // Update and save manifest immediately
-            let manifest_key = entry.metadata.path.clone();
-            manifest.files.insert(manifest_key, entry.metadata);
-            manifest.updated = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            save_manifest(&manifest_path, &manifest)?;
+            journal.record(&mut manifest, entry.metadata)?;
         }
 
         // Wait for worker to complete
         worker_handle
             .join()
-            .map_err(|_| anyhow::anyhow!("Worker thread panicked"))?;
+            .map_err(|_| IndexError::WorkerPanicked)?;
     }
 
-    // Manifest is already updated after each file in streaming mode
-    // Only save manifest if using parallel processing (non-embedding case)
+    // Finished files are journaled as they stream in; fold them into the manifest
     if !compute_embeddings {
-        manifest.updated = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        manifest.touch();
         save_manifest(&manifest_path, &manifest)?;
+    } else {
+        journal.finish(&manifest)?;
+        if cancel.is_cancelled() {
+            return Err(IndexError::Interrupted);
+        }
+        refresh_duplicates(path, &mut manifest, &manifest_path)?;
     }
+    content::train_dictionary(path)?;
+    keep_snapshot(path);
 
-    Ok(())
+    Ok(stats)
 }
 
 pub async fn index_file(file_path: &Path, compute_embeddings: bool) -> Result<()> {
     let repo_root = find_repo_root(file_path)?;
     let index_dir = repo_root.join(".ck");
     fs::create_dir_all(&index_dir)?;
+    let _writing = generation::begin_write(&repo_root);
 
     let manifest_path = index_dir.join("manifest.json");
     let mut manifest = load_or_create_manifest(&manifest_path)?;
+    encryption::prepare(&repo_root, &manifest)?;
 
     let entry = if compute_embeddings {
-        let model_registry = ck_models::ModelRegistry::default();
+        drift::check_manifest(&repo_root, &manifest)?;
+        let model_registry = ck_models::ModelRegistry::for_project(&repo_root);
         let (alias, config) = if let Some(existing) = manifest.embedding_model.as_deref() {
             match model_registry.resolve(Some(existing)) {
                 Ok(resolved) => resolved,
@@ -419,17 +810,21 @@ pub async fn index_file(file_path: &Path, compute_embeddings: bool) -> Result<()
                 ),
             }
         } else {
-            model_registry
-                .resolve(None)
-                .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            model_registry.resolve(None)?
         };
 
+        let config = fix_metric(&mut manifest, &repo_root, config);
         manifest.embedding_model = Some(config.name.clone());
         manifest.embedding_dimensions = Some(config.dimensions);
         tracing::debug!("Using embedding model '{}' ({})", config.name, alias);
+        cost::guard(&manifest, &config, &repo_root, &[file_path.to_path_buf()])?;
 
         let mut embedder = ck_embed::create_embedder_for_config(&config, None)?;
-        index_single_file(file_path, &repo_root, Some(&mut embedder))?
+
+        manifest.embedding_dimensions = Some(embedder.dim());
+        let entry = index_single_file(file_path, &repo_root, Some(&mut embedder))?;
+        cost::record_spend(&mut manifest, &config, std::slice::from_ref(&embedder));
+        entry
     } else {
         index_single_file(file_path, &repo_root, None)?
     };
@@ -438,10 +833,7 @@ pub async fn index_file(file_path: &Path, compute_embeddings: bool) -> Result<()
     save_index_entry(&sidecar_path, &entry)?;
     let manifest_key = entry.metadata.path.clone();
     manifest.files.insert(manifest_key, entry.metadata);
-    manifest.updated = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    manifest.touch();
 
     save_manifest(&manifest_path, &manifest)?;
 
@@ -461,17 +853,29 @@ pub async fn update_index(
             options,
             None, // model - use existing from manifest for update
         )
-        .await;
+        .await
+        .map(|_| ());
     }
+    let _writing = generation::begin_write(path);
 
     let manifest_path = index_dir.join("manifest.json");
     let mut manifest = load_or_create_manifest(&manifest_path)?;
-
-    let files = collect_files(path, options)?;
+    encryption::prepare(path, &manifest)?;
+    upgrade_stale_sidecars(&mut manifest);
+    refresh_moved_submodules(&mut manifest, path);
+
+    let settings = RunSettings::load(path);
+    let walked = walk_files(path, options)?.0;
+    file_list::record(path, options, &walked)?;
+    let (files, skipped) = limits::retain_indexable(walked, &settings.project.limits);
+    let mut dropped = false;
+    for file_path in &skipped.files {
+        dropped |= drop_skipped_file(&mut manifest, path, path, file_path)?;
+    }
 
     let updates: Vec<(PathBuf, IndexEntry)> = if compute_embeddings {
         // Sequential processing when computing embeddings (for memory efficiency)
-        let model_registry = ck_models::ModelRegistry::default();
+        let model_registry = ck_models::ModelRegistry::for_project(path);
         let (alias, config) = if let Some(existing) = manifest.embedding_model.as_deref() {
             match model_registry.resolve(Some(existing)) {
                 Ok(resolved) => resolved,
@@ -481,11 +885,11 @@ pub async fn update_index(
                 ),
             }
         } else {
-            model_registry
-                .resolve(None)
-                .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            model_registry.resolve(None)?
         };
 
+        let config = fix_metric(&mut manifest, path, config);
+        reembed_on_new_instruction(&mut manifest, &config);
         manifest.embedding_model = Some(config.name.clone());
         manifest.embedding_dimensions = Some(config.dimensions);
         tracing::debug!(
@@ -493,9 +897,12 @@ pub async fn update_index(
             config.name,
             alias
         );
+        cost::guard(&manifest, &config, path, &files)?;
 
         let mut embedder = ck_embed::create_embedder_for_config(&config, None)?;
-        files
+
+        manifest.embedding_dimensions = Some(embedder.dim());
+        let updates = files
             .iter()
             .filter_map(|file_path| {
                 let manifest_key =
@@ -509,18 +916,21 @@ pub async fn update_index(
                     None => true,
                 };
                 if needs_update {
-                    match index_single_file(file_path, path, Some(&mut embedder)) {
+                    match index_single_file_with(file_path, path, &settings, Some(&mut embedder)) {
                         Ok(entry) => Some((file_path.clone(), entry)),
                         Err(e) => {
                             // Suppress warnings for binary files and UTF-8 errors in .git directories
                             let error_msg = e.to_string();
-                            let is_binary_skip = error_msg.contains("Binary file, skipping");
+                            let is_expected_skip = matches!(
+                                e,
+                                IndexError::BinaryFile | IndexError::TooManyChunks { .. }
+                            );
                             let is_utf8_error =
                                 error_msg.contains("stream did not contain valid UTF-8");
                             let is_git_file =
                                 file_path.components().any(|c| c.as_os_str() == ".git");
 
-                            if !(is_binary_skip || is_utf8_error && is_git_file) {
+                            if !(is_expected_skip || is_utf8_error && is_git_file) {
                                 tracing::warn!("Failed to index {:?}: {}", file_path, e);
                             }
                             None
@@ -530,7 +940,9 @@ pub async fn update_index(
                     None
                 }
             })
-            .collect()
+            .collect();
+        cost::record_spend(&mut manifest, &config, std::slice::from_ref(&embedder));
+        updates
     } else {
         // Parallel processing when not computing embeddings
         files
@@ -548,18 +960,21 @@ pub async fn update_index(
                 };
 
                 if needs_update {
-                    match index_single_file(file_path, path, None) {
+                    match index_single_file_with(file_path, path, &settings, None) {
                         Ok(entry) => Some((file_path.clone(), entry)),
                         Err(e) => {
                             // Suppress warnings for binary files and UTF-8 errors in .git directories
                             let error_msg = e.to_string();
-                            let is_binary_skip = error_msg.contains("Binary file, skipping");
+                            let is_expected_skip = matches!(
+                                e,
+                                IndexError::BinaryFile | IndexError::TooManyChunks { .. }
+                            );
                             let is_utf8_error =
                                 error_msg.contains("stream did not contain valid UTF-8");
                             let is_git_file =
                                 file_path.components().any(|c| c.as_os_str() == ".git");
 
-                            if !(is_binary_skip || is_utf8_error && is_git_file) {
+                            if !(is_expected_skip || is_utf8_error && is_git_file) {
                                 tracing::warn!("Failed to index {:?}: {}", file_path, e);
                             }
                             None
@@ -572,6 +987,7 @@ pub async fn update_index(
             .collect()
     };
 
+    let has_updates = !updates.is_empty();
     for (file_path, entry) in updates {
         let sidecar_path = get_sidecar_path(path, &file_path);
         save_index_entry(&sidecar_path, &entry)?;
@@ -580,12 +996,12 @@ pub async fn update_index(
     }
 
     if !manifest.files.is_empty() {
-        manifest.updated = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        manifest.touch();
         save_manifest(&manifest_path, &manifest)?;
     }
+    if compute_embeddings && (has_updates || dropped) {
+        refresh_duplicates(path, &mut manifest, &manifest_path)?;
+    }
 
     Ok(())
 }
@@ -606,6 +1022,7 @@ pub fn cleanup_index(
     if !index_dir.exists() {
         return Ok(CleanupStats::default());
     }
+    let _writing = generation::begin_write(path);
 
     let manifest_path = index_dir.join("manifest.json");
     let mut manifest = load_or_create_manifest(&manifest_path)?;
@@ -622,11 +1039,9 @@ pub fn cleanup_index(
 
     // Update manifest if changes were made
     if stats.orphaned_entries_removed > 0 {
-        manifest.updated = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        manifest.touch();
         save_manifest(&manifest_path, &manifest)?;
+        refresh_duplicates(path, &mut manifest, &manifest_path)?;
     }
 
     Ok(stats)
@@ -670,23 +1085,27 @@ pub fn get_index_stats(path: &Path) -> Result<IndexStats> {
         }
     }
 
-    // Calculate index size on disk
-    if let Ok(entries) = WalkDir::new(&index_dir)
-        .into_iter()
-        .collect::<Result<Vec<_>, _>>()
-    {
-        for entry in entries {
-            if entry.file_type().is_file()
-                && let Ok(metadata) = entry.metadata()
-            {
-                stats.index_size_bytes += metadata.len();
-            }
-        }
-    }
+    stats.index_size_bytes = index_size_on_disk(&index_dir);
 
     Ok(stats)
 }
 
+/// Total size of the files under `index_dir`, or 0 if it cannot be walked.
+fn index_size_on_disk(index_dir: &Path) -> u64 {
+    let Ok(entries) = WalkDir::new(index_dir)
+        .into_iter()
+        .collect::<std::result::Result<Vec<_>, _>>()
+    else {
+        return 0;
+    };
+    entries
+        .iter()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
 pub async fn smart_update_index(
     path: &Path,
     compute_embeddings: bool,
@@ -723,7 +1142,8 @@ pub async fn smart_update_index_with_progress(
     .await
 }
 
-/// Enhanced indexing with detailed embedding progress
+/// Enhanced indexing with detailed embedding progress. Ctrl-C, or
+/// [`request_interrupt`], stops it.
 pub async fn smart_update_index_with_detailed_progress(
     path: &Path,
     force_rebuild: bool,
@@ -733,23 +1153,53 @@ pub async fn smart_update_index_with_detailed_progress(
     options: &ck_core::FileCollectionOptions,
     model: Option<&str>,
 ) -> Result<UpdateStats> {
-    let index_dir = path.join(".ck");
-    let mut stats = UpdateStats::default();
-
     // Set up interrupt handler (only once per process)
     HANDLER_INIT.call_once(|| {
         let _ = ctrlc::set_handler(move || {
-            INTERRUPTED.store(true, Ordering::SeqCst);
+            interrupt_token().cancel();
             eprintln!("\nIndexing interrupted by user. Cleaning up...");
         });
     });
 
-    // Reset interrupt flag for this indexing operation
-    INTERRUPTED.store(false, Ordering::SeqCst);
+    // A fresh token for this indexing operation
+    let cancel = CancellationToken::new();
+    *INTERRUPT.lock().unwrap_or_else(|e| e.into_inner()) = cancel.clone();
+
+    smart_update_index_cancellable(
+        path,
+        force_rebuild,
+        progress_callback,
+        detailed_progress_callback,
+        compute_embeddings,
+        options,
+        model,
+        &cancel,
+    )
+    .await
+}
+
+/// [`smart_update_index_with_detailed_progress`] stopped by `cancel` instead of
+/// Ctrl-C, for hosts that embed ck. A cancelled update keeps the files it
+/// finished (the next update resumes from them) and returns
+/// [`IndexError::Interrupted`].
+#[allow(clippy::too_many_arguments)]
+pub async fn smart_update_index_cancellable(
+    path: &Path,
+    force_rebuild: bool,
+    progress_callback: Option<ProgressCallback>,
+    detailed_progress_callback: Option<DetailedProgressCallback>,
+    compute_embeddings: bool,
+    options: &ck_core::FileCollectionOptions,
+    model: Option<&str>,
+    cancel: &CancellationToken,
+) -> Result<UpdateStats> {
+    let index_dir = path.join(".ck");
+    let mut stats = UpdateStats::default();
 
     if force_rebuild {
-        clean_index(path)?;
-        index_directory(path, compute_embeddings, options, model).await?;
+        snapshot::clear_keeping_snapshots(path)?;
+        let mut stats =
+            index_directory_until(path, compute_embeddings, options, model, cancel).await?;
         let index_stats = get_index_stats(path)?;
         stats.files_indexed = index_stats.total_files;
         return Ok(stats);
@@ -764,18 +1214,23 @@ pub async fn smart_update_index_with_detailed_progress(
 
     // Then perform incremental update
     fs::create_dir_all(&index_dir)?;
+    let _writing = generation::begin_write(path);
     let manifest_path = index_dir.join("manifest.json");
     let mut manifest = load_or_create_manifest(&manifest_path)?;
+    encryption::prepare(path, &manifest)?;
+    upgrade_stale_sidecars(&mut manifest);
     normalize_manifest_paths(&mut manifest, &repo_root);
+    refresh_moved_submodules(&mut manifest, &repo_root);
 
     // Handle model configuration for embeddings
     let resolved_model = if compute_embeddings {
-        let model_registry = ck_models::ModelRegistry::default();
+        let model_registry = ck_models::ModelRegistry::for_project(&repo_root);
 
+        if model.is_none() {
+            drift::check_manifest(&repo_root, &manifest)?;
+        }
         let resolved = if let Some(requested) = model {
-            model_registry
-                .resolve(Some(requested))
-                .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            model_registry.resolve(Some(requested))?
         } else if let Some(existing_model) = &manifest.embedding_model {
             match model_registry.resolve(Some(existing_model.as_str())) {
                 Ok(resolved) => resolved,
@@ -785,34 +1240,49 @@ pub async fn smart_update_index_with_detailed_progress(
                 ),
             }
         } else {
-            model_registry
-                .resolve(None)
-                .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            model_registry.resolve(None)?
         };
 
         if let Some(existing_model) = &manifest.embedding_model
             && existing_model != &resolved.1.name
         {
-            return Err(anyhow::anyhow!(
-                "Model mismatch: Index was created with '{}', but you're trying to use '{}'. \
-                    Please run 'ck --clean .' to remove the old index, then 'ck --index --model {}' to rebuild with the new model.",
-                existing_model,
-                resolved.1.name,
-                model.unwrap_or("default")
-            ));
+            return Err(IndexError::ModelMismatch {
+                existing: existing_model.clone(),
+                requested: resolved.1.name.clone(),
+                hint: format!(
+                    "Please run 'ck --clean .' to remove the old index, then 'ck --index --model {}' to rebuild with the new model.",
+                    model.unwrap_or("default")
+                ),
+            });
         }
 
-        manifest.embedding_model = Some(resolved.1.name.clone());
-        manifest.embedding_dimensions = Some(resolved.1.dimensions);
+        let (alias, config) = resolved;
+        let config = fix_metric(&mut manifest, &repo_root, config);
+        reembed_on_new_instruction(&mut manifest, &config);
+        manifest.embedding_model = Some(config.name.clone());
+        manifest.embedding_dimensions = Some(config.dimensions);
 
-        Some(resolved)
+        Some((alias, config))
     } else {
         None
     };
 
     // For incremental updates, only process files in the search scope
     // The cleanup phase already handled removing orphaned files from the entire repo
-    let current_files = collect_files(path, options)?;
+    let settings = RunSettings::load(&repo_root);
+    let (walked, symlinks_skipped) = walk_files(path, options)?;
+    file_list::record(path, options, &walked)?;
+    stats.symlinks_skipped = symlinks_skipped;
+    let (current_files, skipped) = limits::retain_indexable(walked, &settings.project.limits);
+    stats.files_skipped_too_large = skipped.too_large;
+    stats.files_skipped_by_extension = skipped.by_extension;
+
+    // Files indexed before a limit ruled them out leave the index
+    for file_path in &skipped.files {
+        if drop_skipped_file(&mut manifest, path, &repo_root, file_path)? {
+            stats.orphaned_files_removed += 1;
+        }
+    }
 
     // First pass: determine which files need updating and collect stats
     let mut files_to_update = Vec::new();
@@ -820,9 +1290,9 @@ pub async fn smart_update_index_with_detailed_progress(
 
     for file_path in current_files {
         // Check for interrupt
-        if INTERRUPTED.load(Ordering::SeqCst) {
+        if cancel.is_cancelled() {
             eprintln!("Indexing interrupted during file scanning.");
-            return Ok(stats);
+            return Err(IndexError::Interrupted);
         }
 
         let manifest_key =
@@ -886,77 +1356,78 @@ pub async fn smart_update_index_with_detailed_progress(
     }
 
     // Second pass: index the files that need updating
+    let mut journal = Journal::new(&manifest_path);
     if compute_embeddings {
-        // Sequential processing with streaming - write each file immediately
+        // Streaming - write each file immediately; shard workers embed in parallel
         let (_, config) = resolved_model
             .as_ref()
             .expect("resolved model must exist for embedding updates");
-        let mut embedder = ck_embed::create_embedder_for_config(config, None)?;
+        cost::guard(&manifest, config, &repo_root, &files_to_update)?;
+        let workers = shard::worker_count(settings.project.shard_workers, &files_to_update, path);
+        let mut embedders = (0..workers)
+            .map(|_| ck_embed::create_embedder_for_config(config, None))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        manifest.embedding_dimensions = Some(embedders[0].dim());
+        if !files_to_update.is_empty() {
+            journal.checkpoint(&manifest)?;
+        }
         let mut _processed_count = 0;
 
-        for file_path in files_to_update.iter() {
-            // Check for interrupt
-            if INTERRUPTED.load(Ordering::SeqCst) {
-                eprintln!(
-                    "Indexing interrupted. {} files processed.",
-                    _processed_count
-                );
-                break;
-            }
-
-            if let Some(ref callback) = progress_callback
-                && let Some(file_name) = file_path.file_name()
-            {
-                callback(&file_name.to_string_lossy());
-            }
-
-            // Call detailed progress version if callback is provided, otherwise use regular version
-            let result = if let Some(ref detailed_callback) = detailed_progress_callback {
-                index_single_file_with_progress(
-                    file_path,
-                    path,
-                    Some(&mut embedder),
-                    Some(detailed_callback),
-                    _processed_count,
-                    files_to_update.len(),
-                )
-            } else {
-                index_single_file_with_progress(file_path, path, Some(&mut embedder), None, 0, 1)
-            };
+        shard::embed_shards(
+            &files_to_update,
+            path,
+            &settings,
+            &mut embedders,
+            progress_callback.as_ref(),
+            detailed_progress_callback.as_ref(),
+            cancel,
+            |file_path, result| {
+                match result {
+                    Ok(indexed) => {
+                        // Aggregate chunk statistics
+                        stats.chunks_reused += indexed.chunks_reused;
+                        stats.chunks_embedded += indexed.chunks_embedded;
+                        stats.chunks_degenerate += indexed.chunks_degenerate;
+                        stats.encoding_warnings.extend(indexed.encoding_warning);
+                        let entry = indexed.entry;
+
+                        // Write sidecar immediately, then journal the finished file
+                        let sidecar_path = get_sidecar_path(path, &file_path);
+                        save_index_entry(&sidecar_path, &entry)?;
+                        journal.record(&mut manifest, entry.metadata)?;
+                        _processed_count += 1;
+                    }
+                    Err(e) => {
+                        // Suppress warnings for binary files and UTF-8 errors in .git directories
+                        let error_msg = e.to_string();
+                        let is_expected_skip =
+                            matches!(e, IndexError::BinaryFile | IndexError::TooManyChunks { .. });
+                        let is_utf8_error =
+                            error_msg.contains("stream did not contain valid UTF-8");
+                        let is_git_file = file_path.components().any(|c| c.as_os_str() == ".git");
 
-            match result {
-                Ok((entry, file_chunks_reused, file_chunks_embedded)) => {
-                    // Aggregate chunk statistics
-                    stats.chunks_reused += file_chunks_reused;
-                    stats.chunks_embedded += file_chunks_embedded;
-
-                    // Write sidecar immediately
-                    let sidecar_path = get_sidecar_path(path, file_path);
-                    save_index_entry(&sidecar_path, &entry)?;
-
-                    // Update and save manifest immediately
-                    let manifest_key = entry.metadata.path.clone();
-                    manifest.files.insert(manifest_key, entry.metadata);
-                    manifest.updated = SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs();
-                    save_manifest(&manifest_path, &manifest)?;
-                    _processed_count += 1;
-                }
-                Err(e) => {
-                    // Suppress warnings for binary files and UTF-8 errors in .git directories
-                    let error_msg = e.to_string();
-                    let is_binary_skip = error_msg.contains("Binary file, skipping");
-                    let is_utf8_error = error_msg.contains("stream did not contain valid UTF-8");
-                    let is_git_file = file_path.components().any(|c| c.as_os_str() == ".git");
-
-                    if !(is_binary_skip || is_utf8_error && is_git_file) {
-                        tracing::warn!("Failed to index {:?}: {}", file_path, e);
+                        if !(is_expected_skip || is_utf8_error && is_git_file) {
+                            tracing::warn!("Failed to index {:?}: {}", file_path, e);
+                        }
+                        if matches!(e, IndexError::TooManyChunks { .. }) {
+                            stats.files_skipped_too_many_chunks += 1;
+                            if drop_skipped_file(&mut manifest, path, &repo_root, &file_path)? {
+                                stats.orphaned_files_removed += 1;
+                            }
+                        } else {
+                            stats.files_errored += 1;
+                        }
                     }
-                    stats.files_errored += 1;
                 }
-            }
+                Ok(())
+            },
+        )?;
+        cost::record_spend(&mut manifest, config, &embedders);
+        if cancel.is_cancelled() {
+            eprintln!(
+                "Indexing interrupted. {} files processed.",
+                _processed_count
+            );
         }
 
         stats.files_indexed = _processed_count;
@@ -968,6 +1439,7 @@ pub async fn smart_update_index_with_detailed_progress(
         let (tx, rx) = mpsc::channel();
         let files_clone = files_to_update.clone();
         let path_clone = path.to_path_buf();
+        let worker_cancel = cancel.clone();
 
         // Spawn worker thread for parallel processing
         let worker_handle = thread::spawn(move || {
@@ -976,26 +1448,42 @@ pub async fn smart_update_index_with_detailed_progress(
             // Use par_iter with try_for_each to allow early exit on interrupt
             let result = files_clone.par_iter().try_for_each(|file_path| {
                 // Check for interrupt
-                if INTERRUPTED.load(Ordering::SeqCst) {
+                if worker_cancel.is_cancelled() {
                     return Err("interrupted");
                 }
 
-                match index_single_file(file_path, &path_clone, None) {
-                    Ok(entry) => {
-                        if tx.send((file_path.clone(), entry)).is_err() {
+                let result = index_single_file_with_progress(
+                    file_path,
+                    &path_clone,
+                    &settings,
+                    None,
+                    None,
+                    0,
+                    1,
+                    &worker_cancel,
+                );
+                match result {
+                    Ok(indexed) => {
+                        if tx.send((file_path.clone(), Some(indexed))).is_err() {
                             // Receiver dropped, stop processing
                             return Err("receiver_dropped");
                         }
                     }
+                    // Counted and dropped from the index by the main thread
+                    Err(IndexError::TooManyChunks { .. }) => {
+                        if tx.send((file_path.clone(), None)).is_err() {
+                            return Err("receiver_dropped");
+                        }
+                    }
                     Err(e) => {
                         // Suppress warnings for binary files and UTF-8 errors in .git directories
                         let error_msg = e.to_string();
-                        let is_binary_skip = error_msg.contains("Binary file, skipping");
+                        let is_expected_skip = matches!(e, IndexError::BinaryFile);
                         let is_utf8_error =
                             error_msg.contains("stream did not contain valid UTF-8");
                         let is_git_file = file_path.components().any(|c| c.as_os_str() == ".git");
 
-                        if !(is_binary_skip || is_utf8_error && is_git_file) {
+                        if !(is_expected_skip || is_utf8_error && is_git_file) {
                             tracing::warn!("Failed to index {:?}: {}", file_path, e);
                         }
                     }
@@ -1011,9 +1499,9 @@ pub async fn smart_update_index_with_detailed_progress(
 
         // Main thread: stream results as they arrive
         let mut _processed_count = 0;
-        while let Ok((file_path, entry)) = rx.recv() {
+        while let Ok((file_path, indexed)) = rx.recv() {
             // Check for interrupt
-            if INTERRUPTED.load(Ordering::SeqCst) {
+            if cancel.is_cancelled() {
                 eprintln!(
                     "Indexing interrupted. {} files processed.",
                     _processed_count
@@ -1022,24 +1510,26 @@ pub async fn smart_update_index_with_detailed_progress(
                 break;
             }
 
+            let Some(indexed) = indexed else {
+                stats.files_skipped_too_many_chunks += 1;
+                if drop_skipped_file(&mut manifest, path, &repo_root, &file_path)? {
+                    stats.orphaned_files_removed += 1;
+                }
+                continue;
+            };
+
             if let Some(ref callback) = progress_callback
                 && let Some(file_name) = file_path.file_name()
             {
                 callback(&file_name.to_string_lossy());
             }
+            stats.encoding_warnings.extend(indexed.encoding_warning);
+            let entry = indexed.entry;
 
-            // Write sidecar immediately
+            // Write sidecar immediately, then journal the finished file
             let sidecar_path = get_sidecar_path(path, &file_path);
             save_index_entry(&sidecar_path, &entry)?;
-
-            // Update and save manifest immediately
-            let manifest_key = entry.metadata.path.clone();
-            manifest.files.insert(manifest_key, entry.metadata);
-            manifest.updated = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
-            save_manifest(&manifest_path, &manifest)?;
+            journal.record(&mut manifest, entry.metadata)?;
             _processed_count += 1;
         }
 
@@ -1048,75 +1538,176 @@ pub async fn smart_update_index_with_detailed_progress(
         // Wait for worker to complete
         worker_handle
             .join()
-            .map_err(|_| anyhow::anyhow!("Worker thread panicked"))?;
+            .map_err(|_| IndexError::WorkerPanicked)?;
     }
 
-    // For sequential processing (embeddings), manifest is already saved after each file
-    // Only save manifest for parallel processing or if there were metadata-only changes
-    if !compute_embeddings
-        && (stats.files_indexed > 0 || stats.orphaned_files_removed > 0 || manifest_changed)
-    {
-        manifest.updated = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        save_manifest(&manifest_path, &manifest)?;
+    // Finished files are journaled as they stream in; fold them into the manifest,
+    // along with any metadata-only changes
+    if stats.files_indexed > 0 || stats.orphaned_files_removed > 0 || manifest_changed {
+        if !compute_embeddings {
+            manifest.touch();
+        }
+        journal.checkpoint(&manifest)?;
+    }
+    if cancel.is_cancelled() {
+        return Err(IndexError::Interrupted);
+    }
+    if compute_embeddings && (stats.files_indexed > 0 || stats.orphaned_files_removed > 0) {
+        refresh_duplicates(path, &mut manifest, &manifest_path)?;
+    }
+    if stats.files_indexed > 0 {
+        content::train_dictionary(path)?;
     }
+    keep_snapshot(path);
 
     Ok(stats)
 }
 
+/// Snapshot the generation an update just wrote, when the project keeps them.
+/// A failed snapshot costs `--as-of` one generation, not the update.
+fn keep_snapshot(repo_root: &Path) {
+    let keep = ck_models::ProjectConfig::for_path(repo_root).snapshots;
+    if let Err(e) = snapshot::take_snapshot(repo_root, keep) {
+        tracing::warn!("Failed to snapshot the index: {}", e);
+    }
+}
+
+/// Remove `file_path`, which the `limits` rules now skip, from the manifest
+/// along with its sidecar. Returns whether it had been indexed.
+fn drop_skipped_file(
+    manifest: &mut IndexManifest,
+    path: &Path,
+    repo_root: &Path,
+    file_path: &Path,
+) -> Result<bool> {
+    let manifest_key =
+        path_utils::to_manifest_path(&path_utils::to_standard_path(file_path, repo_root));
+    if manifest.files.remove(&manifest_key).is_none() {
+        return Ok(false);
+    }
+    match remove_sidecar(&get_sidecar_path(path, file_path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    tracing::info!(
+        "Removed {:?} from the index: over the configured limits",
+        file_path
+    );
+    Ok(true)
+}
+
 fn index_single_file(
     file_path: &Path,
     repo_root: &Path,
     embedder: Option<&mut Box<dyn ck_embed::Embedder>>,
 ) -> Result<IndexEntry> {
-    let (entry, _chunks_reused, _chunks_embedded) =
-        index_single_file_with_progress(file_path, repo_root, embedder, None, 0, 1)?;
-    Ok(entry)
+    index_single_file_with(
+        file_path,
+        repo_root,
+        &RunSettings::load(repo_root),
+        embedder,
+    )
 }
 
-fn index_single_file_with_progress(
+/// [`index_single_file`] with settings already loaded for the run.
+fn index_single_file_with(
     file_path: &Path,
     repo_root: &Path,
+    settings: &RunSettings,
     embedder: Option<&mut Box<dyn ck_embed::Embedder>>,
-    detailed_progress: Option<&DetailedProgressCallback>,
-    file_index: usize,
+) -> Result<IndexEntry> {
+    let indexed = index_single_file_with_progress(
+        file_path,
+        repo_root,
+        settings,
+        embedder,
+        None,
+        0,
+        1,
+        &CancellationToken::new(),
+    )?;
+    Ok(indexed.entry)
+}
+
+/// Configuration every file of one run is indexed with, read once when the
+/// run starts instead of again for each file.
+pub(crate) struct RunSettings {
+    project: ck_models::ProjectConfig,
+    chunkers: BTreeMap<String, String>,
+    registry: ck_models::ModelRegistry,
+    metadata_command: Option<String>,
+}
+
+impl RunSettings {
+    pub(crate) fn load(repo_root: &Path) -> Self {
+        let project = ck_models::ProjectConfig::for_path(repo_root);
+        let metadata_command = metadata::command_for(&project);
+        Self {
+            project,
+            chunkers: ck_models::UserModels::for_user().chunkers,
+            registry: ck_models::ModelRegistry::with_user_models(),
+            metadata_command,
+        }
+    }
+}
+
+/// A file indexed by `index_single_file_with_progress`.
+#[derive(Debug)]
+pub(crate) struct IndexedFile {
+    pub(crate) entry: IndexEntry,
+    pub(crate) chunks_reused: usize,
+    pub(crate) chunks_embedded: usize,
+    /// Chunks whose embedding is all zeros, not finite or the wrong length
+    pub(crate) chunks_degenerate: usize,
+    /// Set when the file was not clean UTF-8
+    pub(crate) encoding_warning: Option<EncodingWarning>,
+}
+
+/// An embedding and when it was computed (`ChunkEntry::embedded_at`).
+type TimedEmbedding = (Vec<f32>, Option<u64>);
+
+#[tracing::instrument(name = "index_file", level = "debug", skip_all, fields(file = %file_path.display()))]
+#[allow(clippy::too_many_arguments)]
+fn index_single_file_with_progress(
+    file_path: &Path,
+    repo_root: &Path,
+    settings: &RunSettings,
+    embedder: Option<&mut Box<dyn ck_embed::Embedder>>,
+    detailed_progress: Option<&DetailedProgressCallback>,
+    file_index: usize,
     total_files: usize,
-) -> Result<(IndexEntry, usize, usize)> {
+    cancel: &CancellationToken,
+) -> Result<IndexedFile> {
     // Skip binary files to avoid UTF-8 warnings
     if !is_text_file(file_path) {
-        return Err(anyhow::anyhow!("Binary file, skipping"));
+        return Err(IndexError::BinaryFile);
     }
 
-    // Build chunk cache from old sidecar if it exists (for chunk reuse)
-    let chunk_cache: HashMap<String, Vec<f32>> = if embedder.is_some() {
+    // Chunks of the old sidecar, if it exists (for chunk and summary reuse)
+    let old_chunks = if embedder.is_some() {
         let sidecar_path = get_sidecar_path(repo_root, file_path);
         if sidecar_path.exists() {
-            match load_index_entry(&sidecar_path) {
-                Ok(old_entry) => old_entry
-                    .chunks
-                    .into_iter()
-                    .filter_map(|chunk| {
-                        if let (Some(hash), Some(embedding)) = (chunk.chunk_hash, chunk.embedding) {
-                            Some((hash, embedding))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect(),
-                Err(_) => HashMap::new(),
-            }
+            load_index_entry(&sidecar_path)
+                .map(|old_entry| old_entry.chunks)
+                .unwrap_or_default()
         } else {
-            HashMap::new()
+            Vec::new()
         }
     } else {
-        HashMap::new()
+        Vec::new()
     };
 
-    // Preprocess file (extracts PDFs to cache, returns path to readable content)
-    let content_path = preprocess_file(file_path, repo_root)?;
-    let content = fs::read_to_string(&content_path)?;
+    // Extracts PDFs into the content store; other files are decoded to UTF-8
+    let decoded = read_indexable_content(file_path, repo_root)?;
+    let encoding_warning = decoded.needs_warning().then(|| EncodingWarning {
+        file: file_path.to_path_buf(),
+        encoding: decoded.encoding,
+        lossy: decoded.lossy,
+    });
+    if let Some(warning) = &encoding_warning {
+        tracing::warn!("{}", warning);
+    }
+    let content = decoded.text;
 
     // Always use the ORIGINAL file for hash and metadata
     let hash = compute_file_hash(file_path)?;
@@ -1139,16 +1730,143 @@ fn index_single_file_with_progress(
     let lang = if ck_core::pdf::is_pdf_file(file_path) {
         Some(Language::Pdf)
     } else {
-        ck_core::Language::from_path(file_path)
+        ck_core::Language::detect(file_path, &content)
     };
 
     let model_name = embedder.as_ref().map(|e| e.model_name());
-    let chunks = ck_chunk::chunk_text_with_model(&content, lang, model_name)?;
+    let project_config = &settings.project;
+    let chunks = ck_chunk::chunk_file(
+        file_path,
+        &content,
+        lang,
+        model_name,
+        project_config.chunk_strategy,
+        &settings.chunkers,
+    )
+    .map_err(|e| IndexError::Chunking {
+        file: file_path.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    limits::check_chunk_count(file_path, chunks.len(), &project_config.limits)?;
+    let spans: Vec<(usize, usize)> = chunks
+        .iter()
+        .map(|chunk| (chunk.span.byte_start, chunk.span.byte_end))
+        .collect();
+    let facets = ck_chunk::chunk_facets(&content, lang, &spans);
+
+    // Text handed to the embedder (or the summarizer), with secrets redacted
+    // when the project asks. Sidecars only store spans, so the context line
+    // never shows up in search output. The reuse hash covers the same text, so
+    // toggling `chunk_context` or `redaction`, or moving a file, re-embeds its
+    // chunks.
+    let context_path = project_config
+        .chunk_context
+        .then(|| standard_path.to_string_lossy().replace('\\', "/"));
+    // Secrets are found in the whole file, so one a chunk boundary splits is
+    // redacted in both chunks
+    let secrets = project_config
+        .redaction
+        .redactor()?
+        .map(|redactor| (redactor.find(&content), redactor));
+    let model_config = model_name.and_then(|name| {
+        settings
+            .registry
+            .resolve(Some(name))
+            .ok()
+            .map(|(_, config)| config)
+    });
+    let redacted = |chunk: &ck_chunk::Chunk| {
+        let span = chunk.span.byte_start..chunk.span.byte_end;
+        match &secrets {
+            Some((secrets, _)) if content.get(span) == Some(chunk.text.as_str()) => {
+                secrets.redact(&chunk.text, chunk.span.byte_start)
+            }
+            Some((_, redactor)) => redactor.redact(&chunk.text),
+            None => std::borrow::Cow::Borrowed(chunk.text.as_str()),
+        }
+        .into_owned()
+    };
+    let with_context = |chunk: &ck_chunk::Chunk, text: String| match &context_path {
+        Some(path) => format!("{}\n{}", chunk.context_line(path), text),
+        None => text,
+    };
+    // The model's document instruction goes before the text actually embedded,
+    // a summary included; the reuse hash covers it too
+    let instruct = |text: String| match &model_config {
+        Some(config) => config.instructed_document(&text),
+        None => text,
+    };
+
+    // Files listed under `summarize` embed an LLM summary of each chunk instead
+    let mut summaries = summarize::FileSummaries::for_file(
+        repo_root,
+        file_path,
+        &project_config.summarize,
+        &old_chunks,
+    );
+    let chunk_cache: HashMap<String, TimedEmbedding> = old_chunks
+        .into_iter()
+        .filter_map(|chunk| {
+            if let (Some(hash), Some(embedding)) = (chunk.chunk_hash, chunk.embedding) {
+                Some((hash, (embedding, chunk.embedded_at)))
+            } else {
+                None
+            }
+        })
+        .collect();
+    // A chunk new to this file may still be in the index elsewhere (a vendored
+    // copy) under the same content hash. The file's own old chunks may have
+    // any dimension and are checked below; other files' only match with the
+    // embedder's
+    let mut shared = dedup::SharedEmbeddings::new(repo_root);
+    let mut cached = |hash: &str, content_hash: Option<&str>, dim: usize| {
+        chunk_cache
+            .get(hash)
+            .cloned()
+            .or_else(|| shared.get(content_hash?, dim))
+    };
+    // The text to embed for a chunk, the reuse hash covering it, and the
+    // content hash its copies share (summarized chunks share nothing)
+    let mut prepare = |chunk: &ck_chunk::Chunk| {
+        let text = redacted(chunk);
+        let leading = &chunk.metadata.leading_trivia;
+        let trailing = &chunk.metadata.trailing_trivia;
+        match summaries.as_mut() {
+            Some(summaries) => {
+                let (input, hash) = summaries.prepare(
+                    with_context(chunk, text),
+                    instruct,
+                    leading,
+                    trailing,
+                    |hash| chunk_cache.contains_key(hash),
+                );
+                (input, hash, None)
+            }
+            None => {
+                let content_hash = context_path
+                    .is_some()
+                    .then(|| compute_chunk_hash(&instruct(text.clone()), leading, trailing));
+                let input = instruct(with_context(chunk, text));
+                let hash = compute_chunk_hash(&input, leading, trailing);
+                let content_hash = content_hash.unwrap_or_else(|| hash.clone());
+                (input, hash, Some(content_hash))
+            }
+        }
+    };
+
+    let embedded_now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)?
+        .as_secs();
 
     // Track chunk reuse statistics
     let mut chunks_reused = 0;
     let mut chunks_embedded = 0;
 
+    // The dummy embedder's zeros are by design, not a failure
+    let expected_dim = embedder
+        .as_ref()
+        .filter(|embedder| embedder.id() != "dummy")
+        .map(|embedder| embedder.dim());
     let chunk_entries: Vec<ChunkEntry> = if let Some(embedder) = embedder {
         let total_chunks = chunks.len();
         let file_name = file_path
@@ -1167,8 +1885,8 @@ fn index_single_file_with_progress(
 
             let mut chunk_entries = Vec::new();
             for (chunk_index, chunk) in chunks.into_iter().enumerate() {
-                if INTERRUPTED.load(Ordering::SeqCst) {
-                    return Err(anyhow::anyhow!(INDEX_INTERRUPTED_MSG));
+                if cancel.is_cancelled() {
+                    return Err(IndexError::Interrupted);
                 }
                 // Report progress before processing chunk
                 callback(EmbeddingProgress {
@@ -1182,19 +1900,17 @@ fn index_single_file_with_progress(
 
                 // Compute chunk hash for cache lookup or storage
                 // Include trivia so that doc comment changes invalidate the cache
-                let chunk_hash = compute_chunk_hash(
-                    &chunk.text,
-                    &chunk.metadata.leading_trivia,
-                    &chunk.metadata.trailing_trivia,
-                );
+                let (input, chunk_hash, content_hash) = prepare(&chunk);
 
                 // Check cache first, but validate dimension matches current embedder
                 let expected_dim = embedder.dim();
-                let embedding = if let Some(cached_embedding) = chunk_cache.get(&chunk_hash) {
+                let (embedding, embedded_at) = if let Some((cached_embedding, cached_at)) =
+                    cached(&chunk_hash, content_hash.as_deref(), expected_dim)
+                {
                     if cached_embedding.len() == expected_dim {
                         // Dimension matches, safe to reuse
                         chunks_reused += 1;
-                        cached_embedding.clone()
+                        (cached_embedding, cached_at)
                     } else {
                         // Dimension mismatch, re-embed (model changed)
                         chunks_embedded += 1;
@@ -1204,26 +1920,26 @@ fn index_single_file_with_progress(
                             cached_embedding.len(),
                             expected_dim
                         );
-                        let embeddings = embedder.embed(std::slice::from_ref(&chunk.text))?;
-                        embeddings.into_iter().next().ok_or_else(|| {
-                            anyhow::anyhow!(
-                                "Embedder returned empty results for chunk {} in file {:?}. This may indicate an issue with the embedding model or chunk content.",
-                                chunk_index,
-                                file_path
-                            )
-                        })?
+                        let embeddings = embedder.embed(std::slice::from_ref(&input))?;
+                        let embedding = embeddings.into_iter().next().ok_or_else(|| {
+                            IndexError::EmptyEmbedding {
+                                file: file_path.to_path_buf(),
+                                chunk: chunk_index,
+                            }
+                        })?;
+                        (embedding, Some(embedded_now))
                     }
                 } else {
                     // No cache hit, compute embedding
                     chunks_embedded += 1;
-                    let embeddings = embedder.embed(std::slice::from_ref(&chunk.text))?;
-                    embeddings.into_iter().next().ok_or_else(|| {
-                        anyhow::anyhow!(
-                            "Embedder returned empty results for chunk {} in file {:?}. This may indicate an issue with the embedding model or chunk content.",
-                            chunk_index,
-                            file_path
-                        )
-                    })?
+                    let embeddings = embedder.embed(std::slice::from_ref(&input))?;
+                    let embedding = embeddings.into_iter().next().ok_or_else(|| {
+                        IndexError::EmptyEmbedding {
+                            file: file_path.to_path_buf(),
+                            chunk: chunk_index,
+                        }
+                    })?;
+                    (embedding, Some(embedded_now))
                 };
 
                 let chunk_type_str = match chunk.chunk_type {
@@ -1256,12 +1972,22 @@ fn index_single_file_with_progress(
                     embedding: Some(embedding),
                     chunk_type: chunk_type_str,
                     breadcrumb,
+                    symbol: chunk.metadata.symbol.clone(),
+                    language: lang,
                     ancestry,
                     byte_length: Some(chunk.metadata.byte_length),
                     estimated_tokens: Some(chunk.metadata.estimated_tokens),
                     leading_trivia,
                     trailing_trivia,
                     chunk_hash: Some(chunk_hash),
+                    content_hash,
+                    fingerprint: Some(simhash(&chunk.text)),
+                    alias_of: None,
+                    aliases: Vec::new(),
+                    embedded_at,
+                    metadata: BTreeMap::new(),
+                    facets: None,
+                    summary: None,
                 });
             }
             chunk_entries
@@ -1270,20 +1996,28 @@ fn index_single_file_with_progress(
             // First, check which chunks have cached embeddings with dimension validation
             let expected_dim = embedder.dim();
             let mut chunks_to_embed = Vec::new();
-            let mut chunk_results: Vec<(ck_chunk::Chunk, String, Option<Vec<f32>>)> = Vec::new();
+            let mut chunk_results: Vec<(
+                ck_chunk::Chunk,
+                String,
+                Option<String>,
+                Option<TimedEmbedding>,
+            )> = Vec::new();
 
             for chunk in chunks {
                 // Include trivia so that doc comment changes invalidate the cache
-                let chunk_hash = compute_chunk_hash(
-                    &chunk.text,
-                    &chunk.metadata.leading_trivia,
-                    &chunk.metadata.trailing_trivia,
-                );
-                if let Some(cached_embedding) = chunk_cache.get(&chunk_hash) {
+                let (input, chunk_hash, content_hash) = prepare(&chunk);
+                if let Some((cached_embedding, cached_at)) =
+                    cached(&chunk_hash, content_hash.as_deref(), expected_dim)
+                {
                     if cached_embedding.len() == expected_dim {
                         // Dimension matches, safe to reuse
                         chunks_reused += 1;
-                        chunk_results.push((chunk, chunk_hash, Some(cached_embedding.clone())));
+                        chunk_results.push((
+                            chunk,
+                            chunk_hash,
+                            content_hash,
+                            Some((cached_embedding, cached_at)),
+                        ));
                     } else {
                         // Dimension mismatch, need to re-embed
                         tracing::warn!(
@@ -1292,13 +2026,13 @@ fn index_single_file_with_progress(
                             cached_embedding.len(),
                             expected_dim
                         );
-                        chunks_to_embed.push((chunk.text.clone(), chunk_results.len()));
-                        chunk_results.push((chunk, chunk_hash, None));
+                        chunks_to_embed.push((input, chunk_results.len()));
+                        chunk_results.push((chunk, chunk_hash, content_hash, None));
                     }
                 } else {
                     // No cache hit, need to embed
-                    chunks_to_embed.push((chunk.text.clone(), chunk_results.len()));
-                    chunk_results.push((chunk, chunk_hash, None));
+                    chunks_to_embed.push((input, chunk_results.len()));
+                    chunk_results.push((chunk, chunk_hash, content_hash, None));
                 }
             }
 
@@ -1318,26 +2052,26 @@ fn index_single_file_with_progress(
                 let embeddings = embedder.embed(&texts)?;
 
                 if embeddings.len() != chunks_to_embed.len() {
-                    return Err(anyhow::anyhow!(
-                        "Embedder returned {} embeddings for {} chunks in file {:?}. Expected equal counts.",
-                        embeddings.len(),
-                        chunks_to_embed.len(),
-                        file_path
-                    ));
+                    return Err(IndexError::EmbeddingCount {
+                        file: file_path.to_path_buf(),
+                        expected: chunks_to_embed.len(),
+                        actual: embeddings.len(),
+                    });
                 }
 
                 chunks_embedded += embeddings.len();
 
                 // Fill in the computed embeddings
                 for ((_, result_idx), embedding) in chunks_to_embed.into_iter().zip(embeddings) {
-                    chunk_results[result_idx].2 = Some(embedding);
+                    chunk_results[result_idx].3 = Some((embedding, Some(embedded_now)));
                 }
             }
 
             chunk_results
                 .into_iter()
-                .map(|(chunk, chunk_hash, embedding)| {
-                    let embedding = embedding.expect("All chunks should have embeddings by now");
+                .map(|(chunk, chunk_hash, content_hash, embedding)| {
+                    let (embedding, embedded_at) =
+                        embedding.expect("All chunks should have embeddings by now");
                     let chunk_type_str = match chunk.chunk_type {
                         ck_chunk::ChunkType::Function => Some("function".to_string()),
                         ck_chunk::ChunkType::Class => Some("class".to_string()),
@@ -1366,12 +2100,22 @@ fn index_single_file_with_progress(
                         embedding: Some(embedding),
                         chunk_type: chunk_type_str,
                         breadcrumb,
+                        symbol: chunk.metadata.symbol.clone(),
+                        language: lang,
                         ancestry,
                         byte_length: Some(chunk.metadata.byte_length),
                         estimated_tokens: Some(chunk.metadata.estimated_tokens),
                         leading_trivia,
                         trailing_trivia,
                         chunk_hash: Some(chunk_hash),
+                        content_hash,
+                        fingerprint: Some(simhash(&chunk.text)),
+                        alias_of: None,
+                        aliases: Vec::new(),
+                        embedded_at,
+                        metadata: BTreeMap::new(),
+                        facets: None,
+                        summary: None,
                     }
                 })
                 .collect()
@@ -1409,6 +2153,8 @@ fn index_single_file_with_progress(
                     embedding: None,
                     chunk_type: chunk_type_str,
                     breadcrumb,
+                    symbol: chunk.metadata.symbol.clone(),
+                    language: lang,
                     ancestry,
                     byte_length: Some(chunk.metadata.byte_length),
                     estimated_tokens: Some(chunk.metadata.estimated_tokens),
@@ -1419,19 +2165,91 @@ fn index_single_file_with_progress(
                         &chunk.metadata.leading_trivia,
                         &chunk.metadata.trailing_trivia,
                     )),
+                    content_hash: None,
+                    fingerprint: Some(simhash(&chunk.text)),
+                    alias_of: None,
+                    aliases: Vec::new(),
+                    embedded_at: None,
+                    metadata: BTreeMap::new(),
+                    facets: None,
+                    summary: None,
                 }
             })
             .collect()
     };
 
-    Ok((
-        IndexEntry {
+    let mut chunk_entries = chunk_entries;
+    // Degenerate vectors are kept, for `ck --verify --reembed` to replace
+    let chunks_degenerate = expected_dim.map_or(0, |dim| {
+        chunk_entries
+            .iter()
+            .filter_map(|entry| entry.embedding.as_deref())
+            .filter(|embedding| ck_embed::vector_anomaly(embedding, dim, None).is_some())
+            .count()
+    });
+    if chunks_degenerate > 0 {
+        tracing::warn!(
+            "{} of {} chunks in {:?} got degenerate embeddings (all zeros, NaN or the wrong length)",
+            chunks_degenerate,
+            chunk_entries.len(),
+            file_path
+        );
+    }
+    for (entry, facets) in chunk_entries.iter_mut().zip(facets) {
+        entry.facets = Some(facets);
+    }
+    if let Some(summaries) = &summaries {
+        for entry in &mut chunk_entries {
+            entry.summary = entry
+                .chunk_hash
+                .as_ref()
+                .and_then(|hash| summaries.written.get(hash).cloned());
+        }
+    }
+    metadata::attach(
+        repo_root,
+        file_path,
+        settings.metadata_command.as_deref(),
+        &mut chunk_entries,
+    );
+    if project_config.link_generated
+        && let Some(source) = metadata::generated_from(&content)
+    {
+        for entry in &mut chunk_entries {
+            entry
+                .metadata
+                .insert(GENERATED_FROM.to_string(), source.clone());
+        }
+    }
+    if project_config.submodules
+        && let Some((submodule, commit)) = submodules::submodule_of(repo_root, file_path)
+    {
+        for entry in &mut chunk_entries {
+            entry
+                .metadata
+                .insert(SUBMODULE.to_string(), submodule.clone());
+            if let Some(commit) = &commit {
+                entry
+                    .metadata
+                    .insert(SUBMODULE_COMMIT.to_string(), commit.clone());
+            }
+        }
+    }
+
+    if chunks_embedded > 0 {
+        dedup::remember(repo_root, &standard_path, &chunk_entries);
+    }
+
+    Ok(IndexedFile {
+        entry: IndexEntry {
             metadata: file_metadata,
             chunks: chunk_entries,
         },
         chunks_reused,
         chunks_embedded,
-    ))
+        chunks_degenerate,
+        encoding_warning,
+    })
 }
 
 fn load_or_create_manifest(path: &Path) -> Result<IndexManifest> {
@@ -1442,6 +2260,9 @@ fn load_or_create_manifest(path: &Path) -> Result<IndexManifest> {
         IndexManifest::default()
     };
 
+    // Files finished since the last checkpoint, if a run was interrupted
+    journal::replay(path, &mut manifest)?;
+
     // Ensure chunk_hash_version is set to v2 if not already set
     // This handles manifests created before the field existed
     if manifest.chunk_hash_version.is_none() {
@@ -1451,9 +2272,47 @@ fn load_or_create_manifest(path: &Path) -> Result<IndexManifest> {
     Ok(manifest)
 }
 
+/// Forget every file recorded under an older sidecar layout so the next pass
+/// re-indexes (and rewrites) them instead of failing to decode their sidecars.
+fn upgrade_stale_sidecars(manifest: &mut IndexManifest) {
+    if manifest.sidecar_version < SIDECAR_FORMAT_VERSION {
+        if !manifest.files.is_empty() {
+            tracing::info!(
+                "Index sidecars use format v{} (current v{}); rebuilding",
+                manifest.sidecar_version,
+                SIDECAR_FORMAT_VERSION
+            );
+            manifest.files.clear();
+        }
+        manifest.sidecar_version = SIDECAR_FORMAT_VERSION;
+    }
+}
+
+/// Re-link near-duplicate chunks after sidecars were added, changed, or removed.
+/// Rewritten sidecars change search results, so the generation advances too.
+fn refresh_duplicates(
+    repo_root: &Path,
+    manifest: &mut IndexManifest,
+    manifest_path: &Path,
+) -> Result<()> {
+    if manifest.embedding_model.is_none() {
+        return Ok(());
+    }
+    if deduplicate_index(repo_root, manifest)? > 0 {
+        manifest.touch();
+        save_manifest(manifest_path, manifest)?;
+    }
+    Ok(())
+}
+
+/// Rewrite manifest keys in the standard `./relative` form. Keys that name the
+/// same file under different spellings (separators, `..`, or case where the
+/// file system ignores it) collapse into one, keeping the newest entry.
 fn normalize_manifest_paths(manifest: &mut IndexManifest, repo_root: &Path) {
     let original_entries = std::mem::take(&mut manifest.files);
-    let mut normalized = HashMap::with_capacity(original_entries.len());
+    let mut normalized: HashMap<PathBuf, FileMetadata> =
+        HashMap::with_capacity(original_entries.len());
+    let mut spellings: HashMap<String, PathBuf> = HashMap::new();
 
     for (key, mut metadata) in original_entries {
         let standard_key = if key.is_absolute() {
@@ -1470,6 +2329,16 @@ fn normalize_manifest_paths(manifest: &mut IndexManifest, repo_root: &Path) {
         };
         metadata.path = path_utils::to_manifest_path(&metadata_standard);
 
+        let identity = ck_core::paths::identity_key(&standard_key);
+        if let Some(existing) = spellings.get(&identity) {
+            if normalized[existing].last_modified >= metadata.last_modified {
+                tracing::debug!("Dropping duplicate manifest entry {:?}", key);
+                continue;
+            }
+            tracing::debug!("Dropping duplicate manifest entry {:?}", existing);
+            normalized.remove(existing);
+        }
+        spellings.insert(identity, manifest_key.clone());
         normalized.insert(manifest_key, metadata);
     }
 
@@ -1478,11 +2347,16 @@ fn normalize_manifest_paths(manifest: &mut IndexManifest, repo_root: &Path) {
 
 fn save_manifest(path: &Path, manifest: &IndexManifest) -> Result<()> {
     let data = serde_json::to_vec_pretty(manifest)?;
-    atomic_write(path, &data)
+    atomic_write(path, &data)?;
+    // The manifest now includes everything journaled before it
+    journal::clear(path)?;
+    generation::committed(path, manifest.generation);
+    Ok(())
 }
 
 fn save_index_entry(path: &Path, entry: &IndexEntry) -> Result<()> {
-    let data = bincode::serialize(entry)?;
+    generation::retire(path)?;
+    let data = encryption::seal(encryption::index_dir_of(path), bincode::serialize(entry)?)?;
     atomic_write(path, &data)
 }
 
@@ -1494,16 +2368,19 @@ fn atomic_write(path: &Path, data: &[u8]) -> Result<()> {
     tmp.write_all(data)?;
     tmp.as_file().sync_all()?;
 
-    if path.exists() {
-        fs::remove_file(path)?;
-    }
-
+    // Replaces any existing file atomically, so readers never find it missing
     tmp.persist(path)?;
     Ok(())
 }
 
+/// Remove a sidecar, keeping its version for searches pinned before.
+fn remove_sidecar(path: &Path) -> std::io::Result<()> {
+    generation::retire(path)?;
+    fs::remove_file(path)
+}
+
 pub fn load_index_entry(path: &Path) -> Result<IndexEntry> {
-    let data = fs::read(path)?;
+    let data = encryption::unseal(encryption::index_dir_of(path), fs::read(path)?)?;
     Ok(bincode::deserialize(&data)?)
 }
 
@@ -1527,51 +2404,45 @@ fn find_repo_root(path: &Path) -> Result<PathBuf> {
 }
 
 /// Check if content needs re-extraction
-fn should_reextract(source_path: &Path, cache_path: &Path) -> Result<bool> {
-    if !cache_path.exists() {
+fn should_reextract(source_path: &Path, repo_root: &Path) -> Result<bool> {
+    let Some(cache_modified) = content::content_modified(repo_root, source_path) else {
         return Ok(true);
-    }
+    };
 
     let source_modified = fs::metadata(source_path)?.modified()?;
-    let cache_modified = fs::metadata(cache_path)?.modified()?;
 
     Ok(source_modified > cache_modified)
 }
 
 /// Extract text content from a PDF file
 fn extract_pdf_text(path: &Path) -> Result<String> {
-    pdf_extract::extract_text(path)
-        .map_err(|e| anyhow::anyhow!("Failed to extract text from PDF {}: {}", path.display(), e))
+    pdf_extract::extract_text(path).map_err(|e| IndexError::PdfExtract {
+        file: path.to_path_buf(),
+        message: e.to_string(),
+    })
 }
 
-/// Preprocess a file if needed, returning path to readable content
-/// For regular files: returns the original path (no preprocessing)
-/// For PDFs: extracts text to cache, returns cache path
-fn preprocess_file(file_path: &Path, repo_root: &Path) -> Result<PathBuf> {
+/// Read a file's indexable content
+/// For regular files: the file itself, decoded to UTF-8 (see `ck_core::encoding`)
+/// For PDFs: the extracted text, extracting it into the content store first if needed
+fn read_indexable_content(file_path: &Path, repo_root: &Path) -> Result<DecodedText> {
     if ck_core::pdf::is_pdf_file(file_path) {
-        let cache_path = ck_core::pdf::get_content_cache_path(repo_root, file_path);
-
         // Check if re-extraction needed
-        if should_reextract(file_path, &cache_path)? {
-            tracing::debug!(
-                "Extracting PDF content from {:?} to {:?}",
-                file_path,
-                cache_path
-            );
+        let text = if should_reextract(file_path, repo_root)? {
+            tracing::debug!("Extracting PDF content from {:?}", file_path);
             let extracted_text = extract_pdf_text(file_path)?;
-
-            // Ensure cache directory exists
-            if let Some(parent) = cache_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-
-            // Write extracted text
-            fs::write(&cache_path, extracted_text)?;
-        }
-
-        Ok(cache_path) // Return path to extracted text
+            content::write_content(repo_root, file_path, &extracted_text)?;
+            extracted_text
+        } else {
+            content::read_content(repo_root, file_path)?
+        };
+        Ok(DecodedText {
+            text,
+            encoding: TextEncoding::Utf8,
+            lossy: false,
+        })
     } else {
-        Ok(file_path.to_path_buf()) // Return original path for regular files
+        Ok(ck_core::encoding::read_text(file_path)?)
     }
 }
 
@@ -1581,7 +2452,7 @@ fn is_text_file(path: &Path) -> bool {
         return true;
     }
 
-    // Use NUL byte heuristic like ripgrep - read first 8KB and check for NUL bytes
+    // NUL byte heuristic like ripgrep on the first 8KB, letting UTF-16 through
     const BUFFER_SIZE: usize = 8192;
 
     match std::fs::File::open(path) {
@@ -1594,8 +2465,7 @@ fn is_text_file(path: &Path) -> bool {
                         return true;
                     }
 
-                    // Check for NUL bytes in the read portion
-                    !buffer[..bytes_read].contains(&0)
+                    ck_core::encoding::looks_like_text(&buffer[..bytes_read])
                 }
                 Err(_) => false, // If we can't read, assume binary
             }
@@ -1652,6 +2522,16 @@ pub struct CleanupStats {
     pub orphaned_sidecars_removed: usize,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompactStats {
+    pub entries_removed: usize,
+    pub sidecars_removed: usize,
+    pub temp_files_removed: usize,
+    pub sidecars_relinked: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct IndexStats {
     pub total_files: usize,
@@ -1673,6 +2553,38 @@ pub struct UpdateStats {
     pub orphaned_files_removed: usize,
     pub chunks_reused: usize,
     pub chunks_embedded: usize,
+    /// Chunks embedded as all zeros, NaN or the wrong length; see
+    /// `ck --verify`
+    #[serde(default)]
+    pub chunks_degenerate: usize,
+    /// Files skipped by the `limits` rules of `.ckconfig.json`
+    pub files_skipped_too_large: usize,
+    pub files_skipped_by_extension: usize,
+    pub files_skipped_too_many_chunks: usize,
+    /// Symlinks not traversed because `follow_symlinks` was off
+    pub symlinks_skipped: usize,
+    /// Files indexed from text that was not clean UTF-8
+    #[serde(default)]
+    pub encoding_warnings: Vec<EncodingWarning>,
+}
+
+/// A file that was transcoded, or had invalid bytes replaced, to be indexed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodingWarning {
+    pub file: PathBuf,
+    pub encoding: TextEncoding,
+    /// Some bytes were invalid and became U+FFFD
+    pub lossy: bool,
+}
+
+impl std::fmt::Display for EncodingWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} read as {}", self.file.display(), self.encoding)?;
+        if self.lossy {
+            write!(f, ", invalid bytes replaced")?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -1697,7 +2609,7 @@ mod tests {
             "test-empty-results"
         }
 
-        fn embed(&mut self, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        fn embed(&mut self, _texts: &[String]) -> ck_embed::Result<Vec<Vec<f32>>> {
             // Always return empty vector to trigger the panic scenario
             Ok(Vec::new())
         }
@@ -1719,7 +2631,7 @@ mod tests {
             "test-mismatched-count"
         }
 
-        fn embed(&mut self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        fn embed(&mut self, texts: &[String]) -> ck_embed::Result<Vec<Vec<f32>>> {
             // Always return one less embedding than requested
             if texts.is_empty() {
                 Ok(Vec::new())
@@ -1769,10 +2681,12 @@ mod tests {
         let result = index_single_file_with_progress(
             &test_file,
             test_path,
+            &RunSettings::load(test_path),
             Some(&mut empty_embedder),
             Some(&dummy_callback),
             0,
             1,
+            &CancellationToken::new(),
         );
 
         assert!(result.is_err());
@@ -1837,6 +2751,272 @@ mod tests {
         }
     }
 
+    /// Test embedder that records every text it is asked to embed
+    struct RecordingEmbedder(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl ck_embed::Embedder for RecordingEmbedder {
+        fn id(&self) -> &'static str {
+            "recording-test"
+        }
+
+        fn dim(&self) -> usize {
+            4
+        }
+
+        fn model_name(&self) -> &str {
+            "test-recording"
+        }
+
+        fn embed(&mut self, texts: &[String]) -> ck_embed::Result<Vec<Vec<f32>>> {
+            self.0.lock().unwrap().extend(texts.iter().cloned());
+            Ok(vec![vec![1.0; self.dim()]; texts.len()])
+        }
+    }
+
+    #[test]
+    fn test_shards_embed_in_parallel_and_drop_independently() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let index_dir = root.join(".ck");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        let files = vec![
+            root.join("README.md"),
+            root.join("src/lib.rs"),
+            root.join("docs/guide.md"),
+        ];
+        for file in &files {
+            fs::write(file, "fn shared() {}\n").unwrap();
+        }
+
+        assert_eq!(shard_of(Path::new("src/nested/lib.rs")), "src");
+        assert_eq!(shard_of(Path::new("README.md")), ROOT_SHARD);
+        assert_eq!(shard::worker_count(8, &files, root), 3);
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut embedders: Vec<Box<dyn ck_embed::Embedder>> = (0..2)
+            .map(|_| Box::new(RecordingEmbedder(seen.clone())) as Box<dyn ck_embed::Embedder>)
+            .collect();
+        let mut manifest = IndexManifest::default();
+        let cancel = CancellationToken::new();
+        shard::embed_shards(
+            &files,
+            root,
+            &RunSettings::load(root),
+            &mut embedders,
+            None,
+            None,
+            &cancel,
+            |file, result| {
+                let entry = result?.entry;
+                save_index_entry(&get_sidecar_path(root, &file), &entry)?;
+                manifest
+                    .files
+                    .insert(entry.metadata.path.clone(), entry.metadata);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(manifest.files.len(), 3);
+        save_manifest(&index_dir.join("manifest.json"), &manifest).unwrap();
+
+        let shards: Vec<String> = list_shards(&index_dir)
+            .unwrap()
+            .into_iter()
+            .map(|shard| shard.name)
+            .collect();
+        assert_eq!(shards, [ROOT_SHARD, "docs", "src"]);
+
+        // Dropping one shard leaves the others searchable
+        assert_eq!(drop_shard(root, "src").unwrap(), 1);
+        assert!(!get_sidecar_path(root, &files[1]).exists());
+        assert!(get_sidecar_path(root, &files[2]).exists());
+        let manifest = load_or_create_manifest(&index_dir.join("manifest.json")).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+    }
+
+    #[test]
+    fn test_reused_embeddings_keep_their_timestamp() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        let test_file = test_path.join("lib.rs");
+        fs::write(&test_file, "fn first() {}\n\nfn second() {}\n").unwrap();
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut embedder: Box<dyn ck_embed::Embedder> = Box::new(RecordingEmbedder(seen.clone()));
+        let mut entry = index_single_file(&test_file, test_path, Some(&mut embedder)).unwrap();
+        assert!(entry.chunks.iter().all(|c| c.embedded_at.is_some()));
+
+        // Backdate the stored timestamps; a rerun that reuses the embeddings keeps them
+        for chunk in &mut entry.chunks {
+            chunk.embedded_at = Some(42);
+        }
+        save_index_entry(&get_sidecar_path(test_path, &test_file), &entry).unwrap();
+        seen.lock().unwrap().clear();
+        let entry = index_single_file(&test_file, test_path, Some(&mut embedder)).unwrap();
+        assert!(seen.lock().unwrap().is_empty());
+        assert!(entry.chunks.iter().all(|c| c.embedded_at == Some(42)));
+    }
+
+    #[test]
+    fn test_chunk_context_prepended_to_embedding_input() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        fs::create_dir_all(test_path.join("src")).unwrap();
+        let test_file = test_path.join("src").join("calc.rs");
+        fs::write(
+            &test_file,
+            "struct Calculator;\n\nimpl Calculator {\n    fn add(&self, a: i32, b: i32) -> i32 {\n        a + b\n    }\n}\n",
+        )
+        .unwrap();
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut embedder: Box<dyn ck_embed::Embedder> = Box::new(RecordingEmbedder(seen.clone()));
+        let entry = index_single_file(&test_file, test_path, Some(&mut embedder)).unwrap();
+        let with_context = entry
+            .chunks
+            .iter()
+            .map(|c| c.chunk_hash.clone())
+            .collect::<Vec<_>>();
+        {
+            let seen = seen.lock().unwrap();
+            assert!(seen.iter().all(|text| text.starts_with("src/calc.rs")));
+            assert!(
+                seen.iter()
+                    .any(|text| text.starts_with("src/calc.rs :: Calculator :: fn add\n"))
+            );
+        }
+
+        // Disabling enrichment embeds the raw chunk text and changes the reuse hash
+        ck_models::ProjectConfig {
+            chunk_context: false,
+            ..Default::default()
+        }
+        .save(&test_path.join(ck_models::PROJECT_CONFIG_FILE))
+        .unwrap();
+        seen.lock().unwrap().clear();
+        let entry = index_single_file(&test_file, test_path, Some(&mut embedder)).unwrap();
+        assert!(
+            seen.lock()
+                .unwrap()
+                .iter()
+                .all(|text| !text.starts_with("src/calc.rs"))
+        );
+        let without_context = entry
+            .chunks
+            .iter()
+            .map(|c| c.chunk_hash.clone())
+            .collect::<Vec<_>>();
+        assert_ne!(with_context, without_context);
+    }
+
+    #[test]
+    fn test_summarized_files_embed_their_summary() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        fs::create_dir_all(test_path.join("data")).unwrap();
+        let table = test_path.join("data").join("rates.csv");
+        fs::write(&table, "currency,rate\nEUR,0.92\nGBP,0.79\n").unwrap();
+        let code = test_path.join("lib.rs");
+        fs::write(&code, "fn convert() {}\n").unwrap();
+        ck_models::ProjectConfig {
+            summarize: ck_models::SummarizeConfig {
+                paths: vec!["data/**".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .save(&test_path.join(ck_models::PROJECT_CONFIG_FILE))
+        .unwrap();
+
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        set_summarizer(move |_, text| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(format!("Exchange rates table ({} bytes)", text.len()))
+        });
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut embedder: Box<dyn ck_embed::Embedder> = Box::new(RecordingEmbedder(seen.clone()));
+        let entry = index_single_file(&table, test_path, Some(&mut embedder)).unwrap();
+        assert!(calls.load(std::sync::atomic::Ordering::SeqCst) > 0);
+        assert!(
+            seen.lock()
+                .unwrap()
+                .iter()
+                .all(|text| text.starts_with("Exchange rates table"))
+        );
+        // The chunk keeps pointing at the table's own lines
+        assert_eq!(entry.chunks[0].span.line_start, 1);
+        assert!(
+            entry.chunks[0]
+                .summary
+                .as_deref()
+                .is_some_and(|summary| summary.starts_with("Exchange rates table"))
+        );
+
+        // An unchanged file reuses its summaries and embeddings
+        save_index_entry(&get_sidecar_path(test_path, &table), &entry).unwrap();
+        calls.store(0, std::sync::atomic::Ordering::SeqCst);
+        seen.lock().unwrap().clear();
+        let again = index_single_file(&table, test_path, Some(&mut embedder)).unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert!(seen.lock().unwrap().is_empty());
+        assert_eq!(again.chunks[0].summary, entry.chunks[0].summary);
+
+        // Files not listed are embedded as they are
+        let entry = index_single_file(&code, test_path, Some(&mut embedder)).unwrap();
+        assert!(entry.chunks.iter().all(|chunk| chunk.summary.is_none()));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_deduplicate_index_links_vendored_copies() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let code = "pub fn checksum(data: &[u8]) -> u32 {\n    let mut hash: u32 = 2166136261;\n    for byte in data {\n        hash ^= *byte as u32;\n        hash = hash.wrapping_mul(16777619);\n    }\n    hash\n}\n";
+        let embedded = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut embedder: Box<dyn ck_embed::Embedder> =
+            Box::new(RecordingEmbedder(embedded.clone()));
+        let mut manifest = IndexManifest::default();
+        for dir in ["src", "vendor/lib"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+            let file = root.join(dir).join("hash.rs");
+            fs::write(&file, code).unwrap();
+            let entry = index_single_file(&file, root, Some(&mut embedder)).unwrap();
+            save_index_entry(&get_sidecar_path(root, &file), &entry).unwrap();
+            manifest
+                .files
+                .insert(entry.metadata.path.clone(), entry.metadata.clone());
+        }
+        // The vendored copy reused the first copy's embedding
+        assert_eq!(embedded.lock().unwrap().len(), 1);
+
+        assert_eq!(deduplicate_index(root, &manifest).unwrap(), 2);
+        let canonical =
+            load_index_entry(&get_sidecar_path(root, &root.join("src/hash.rs"))).unwrap();
+        let alias =
+            load_index_entry(&get_sidecar_path(root, &root.join("vendor/lib/hash.rs"))).unwrap();
+        assert_eq!(canonical.chunks[0].aliases.len(), 1);
+        assert_eq!(
+            canonical.chunks[0].aliases[0].path,
+            PathBuf::from("vendor/lib/hash.rs")
+        );
+        assert_eq!(alias.chunks[0].alias_of, Some(PathBuf::from("src/hash.rs")));
+        assert!(alias.chunks[0].embedding.is_some());
+
+        // Nothing changed, nothing rewritten
+        assert_eq!(deduplicate_index(root, &manifest).unwrap(), 0);
+        assert!(root.join(".ck").join(dedup::HASH_INDEX_FILE).exists());
+
+        // Removing the canonical copy promotes the vendored one
+        fs::remove_file(get_sidecar_path(root, &root.join("src/hash.rs"))).unwrap();
+        assert_eq!(deduplicate_index(root, &manifest).unwrap(), 1);
+        let alias =
+            load_index_entry(&get_sidecar_path(root, &root.join("vendor/lib/hash.rs"))).unwrap();
+        assert_eq!(alias.chunks[0].alias_of, None);
+    }
+
     #[tokio::test]
     async fn test_smart_update_index() {
         let temp_dir = TempDir::new().unwrap();
@@ -1849,6 +3029,8 @@ mod tests {
             respect_gitignore: true,
             use_ckignore: true,
             exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
         };
 
         // First index
@@ -1883,6 +3065,171 @@ mod tests {
         assert_eq!(stats4.files_indexed, 1);
     }
 
+    #[tokio::test]
+    async fn test_updates_keep_indexing_the_listed_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        fs::write(test_path.join("listed.txt"), "listed content").unwrap();
+        fs::write(test_path.join("unlisted.txt"), "unlisted content").unwrap();
+
+        let walked = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        let listed = ck_core::FileCollectionOptions {
+            files_from: Some(vec![test_path.join("listed.txt")]),
+            ..walked.clone()
+        };
+        let stats = smart_update_index(test_path, false, &listed).await.unwrap();
+        assert_eq!(stats.files_added, 1);
+
+        // An update that is not given the list, like the one before a search
+        fs::write(test_path.join("listed.txt"), "changed content").unwrap();
+        let stats = smart_update_index(test_path, false, &walked).await.unwrap();
+        assert_eq!(stats.files_modified, 1);
+        assert_eq!(stats.files_added, 0);
+
+        // A forced rebuild too
+        smart_update_index(test_path, true, &walked).await.unwrap();
+        let manifest = load_or_create_manifest(&test_path.join(".ck/manifest.json")).unwrap();
+        assert_eq!(manifest.files.len(), 1);
+
+        // Cleaning forgets the list
+        clean_index(test_path).unwrap();
+        let stats = smart_update_index(test_path, false, &walked).await.unwrap();
+        assert_eq!(stats.files_added, 2);
+    }
+
+    #[tokio::test]
+    async fn test_limits_skip_and_drop_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        fs::write(test_path.join("dump.sql"), "insert into t values (1);").unwrap();
+        fs::write(test_path.join("big.txt"), "x".repeat(4096)).unwrap();
+        let lines: String = (0..400).map(|i| format!("line {i}\n\n")).collect();
+        fs::write(test_path.join("long.txt"), lines).unwrap();
+        fs::write(test_path.join("small.txt"), "small").unwrap();
+
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        let stats = smart_update_index(test_path, false, &file_options)
+            .await
+            .unwrap();
+        assert_eq!(stats.files_indexed, 4);
+
+        ck_models::ProjectConfig {
+            limits: ck_models::IndexLimits {
+                max_file_size: Some(3500),
+                max_chunks_per_file: Some(1),
+                extensions: [("SQL".to_string(), false)].into(),
+            },
+            ..Default::default()
+        }
+        .save(&test_path.join(ck_models::PROJECT_CONFIG_FILE))
+        .unwrap();
+        fs::write(test_path.join("long.txt"), "changed\n".repeat(400)).unwrap();
+
+        let stats = smart_update_index(test_path, false, &file_options)
+            .await
+            .unwrap();
+        assert_eq!(stats.files_skipped_by_extension, 1);
+        assert_eq!(stats.files_skipped_too_large, 1);
+        assert_eq!(stats.files_skipped_too_many_chunks, 1);
+        assert_eq!(stats.orphaned_files_removed, 3);
+
+        let manifest = load_or_create_manifest(&test_path.join(".ck/manifest.json")).unwrap();
+        let indexed: Vec<_> = manifest.files.keys().cloned().collect();
+        assert_eq!(indexed, vec![PathBuf::from("./small.txt")]);
+        assert!(!get_sidecar_path(test_path, &test_path.join("dump.sql")).exists());
+    }
+
+    #[tokio::test]
+    async fn test_non_utf8_files_are_indexed_with_warnings() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        let utf16: Vec<u8> = "fn main() {}\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        fs::write(
+            test_path.join("wide.rs"),
+            [b"\xFF\xFE".as_slice(), &utf16].concat(),
+        )
+        .unwrap();
+        fs::write(test_path.join("legacy.txt"), b"caf\xE9 cr\xE8me\n").unwrap();
+        fs::write(test_path.join("damaged.txt"), b"na\xC3\xAFve \xFF\n").unwrap();
+        fs::write(test_path.join("clean.txt"), "plain\n").unwrap();
+
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        let stats = smart_update_index(test_path, false, &file_options)
+            .await
+            .unwrap();
+        assert_eq!(stats.files_indexed, 4);
+
+        let mut warnings: Vec<_> = stats
+            .encoding_warnings
+            .iter()
+            .map(|warning| {
+                let name = warning.file.file_name().unwrap().to_string_lossy();
+                (name.into_owned(), warning.encoding, warning.lossy)
+            })
+            .collect();
+        warnings.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            warnings,
+            vec![
+                ("damaged.txt".to_string(), TextEncoding::Utf8, true),
+                ("legacy.txt".to_string(), TextEncoding::Latin1, false),
+                ("wide.rs".to_string(), TextEncoding::Utf16Le, false),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_recorded_or_followed_once() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/a.txt"), "hello").unwrap();
+        symlink(root.join("src"), root.join("vendor")).unwrap();
+        symlink(root, root.join("src/loop")).unwrap();
+        symlink(root.join("src/a.txt"), root.join("alias.txt")).unwrap();
+
+        let mut options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        let (files, symlinks_skipped) = walk_files(root, &options).unwrap();
+        assert_eq!(files, vec![root.join("src/a.txt")]);
+        assert_eq!(symlinks_skipped, 3);
+
+        // The cycle is cut and the three routes to a.txt collapse to the shortest
+        options.follow_symlinks = true;
+        let (files, symlinks_skipped) = walk_files(root, &options).unwrap();
+        assert_eq!(files, vec![root.join("alias.txt")]);
+        assert_eq!(symlinks_skipped, 0);
+    }
+
     #[test]
     fn test_cleanup_index() {
         let temp_dir = TempDir::new().unwrap();
@@ -1911,6 +3258,8 @@ mod tests {
             respect_gitignore: true,
             use_ckignore: true,
             exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
         };
         let stats = cleanup_index(test_path, &file_options).unwrap();
         assert_eq!(stats.orphaned_entries_removed, 1);
@@ -1920,6 +3269,159 @@ mod tests {
         assert_eq!(updated_manifest.files.len(), 0);
     }
 
+    #[test]
+    fn test_metric_is_fixed_by_the_first_embeddings() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        let config = ck_models::ProjectConfig {
+            metric: ck_models::SimilarityMetric::Dot,
+            ..Default::default()
+        };
+        config
+            .save(&test_path.join(ck_models::PROJECT_CONFIG_FILE))
+            .unwrap();
+        let (_, model) = ck_models::ModelRegistry::default().resolve(None).unwrap();
+
+        // A new index takes the project's metric and embeds unnormalized
+        let mut manifest = IndexManifest::default();
+        let embedding = fix_metric(&mut manifest, test_path, model.clone());
+        assert_eq!(manifest.metric, ck_models::SimilarityMetric::Dot);
+        assert!(!embedding.normalize);
+
+        // An index that already has embeddings keeps its own
+        let mut manifest = IndexManifest {
+            embedding_model: Some(model.name.clone()),
+            ..Default::default()
+        };
+        let embedding = fix_metric(&mut manifest, test_path, model);
+        assert_eq!(manifest.metric, ck_models::SimilarityMetric::Cosine);
+        assert!(embedding.normalize);
+    }
+
+    #[test]
+    fn test_moved_submodule_is_reindexed() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::write(root.join(".ckconfig.json"), r#"{"submodules": true}"#).unwrap();
+        fs::write(
+            root.join(".gitmodules"),
+            "[submodule \"lib\"]\n\tpath = vendor/lib\n",
+        )
+        .unwrap();
+        let modules = root.join(".git/modules/lib");
+        fs::create_dir_all(&modules).unwrap();
+        fs::write(modules.join("HEAD"), "abc123\n").unwrap();
+        fs::create_dir_all(root.join("vendor/lib")).unwrap();
+        fs::write(
+            root.join("vendor/lib/.git"),
+            "gitdir: ../../.git/modules/lib\n",
+        )
+        .unwrap();
+
+        let entry = |path: &str| {
+            let path = PathBuf::from(path);
+            let metadata = FileMetadata {
+                path: path.clone(),
+                hash: "abc".to_string(),
+                last_modified: 0,
+                size: 3,
+            };
+            (path, metadata)
+        };
+        let mut manifest = IndexManifest::default();
+        manifest
+            .files
+            .extend([entry("./vendor/lib/lib.rs"), entry("./src/main.rs")]);
+        manifest
+            .submodule_commits
+            .insert("vendor/lib".to_string(), "abc123".to_string());
+        refresh_moved_submodules(&mut manifest, root);
+        assert_eq!(manifest.files.len(), 2);
+
+        fs::write(modules.join("HEAD"), "def456\n").unwrap();
+        refresh_moved_submodules(&mut manifest, root);
+        assert_eq!(manifest.files.len(), 1);
+        assert!(manifest.files.contains_key(Path::new("./src/main.rs")));
+        assert_eq!(manifest.submodule_commits["vendor/lib"], "def456");
+    }
+
+    #[test]
+    fn test_new_document_instruction_reembeds() {
+        let (_, mut model) = ck_models::ModelRegistry::default().resolve(None).unwrap();
+        let file = FileMetadata {
+            path: PathBuf::from("src/lib.rs"),
+            hash: "abc".to_string(),
+            last_modified: 0,
+            size: 3,
+        };
+        let mut manifest = IndexManifest {
+            embedding_model: Some(model.name.clone()),
+            files: HashMap::from([(file.path.clone(), file.clone())]),
+            ..Default::default()
+        };
+        reembed_on_new_instruction(&mut manifest, &model);
+        assert_eq!(manifest.files.len(), 1);
+
+        model.document_instruction = Some("passage".to_string());
+        reembed_on_new_instruction(&mut manifest, &model);
+        assert!(manifest.files.is_empty());
+        assert_eq!(manifest.document_instruction.as_deref(), Some("passage"));
+
+        manifest.files.insert(file.path.clone(), file);
+        reembed_on_new_instruction(&mut manifest, &model);
+        assert_eq!(manifest.files.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_compact_index_reclaims_deleted_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_path = temp_dir.path();
+        fs::write(test_path.join("kept.txt"), "kept content").unwrap();
+        fs::write(test_path.join("deleted.txt"), "deleted content").unwrap();
+
+        let file_options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        smart_update_index(test_path, false, &file_options)
+            .await
+            .unwrap();
+
+        // Updates leave the deleted file's entry and sidecar in place
+        fs::remove_file(test_path.join("deleted.txt")).unwrap();
+        smart_update_index(test_path, false, &file_options)
+            .await
+            .unwrap();
+        let deleted_sidecar = get_sidecar_path(test_path, &test_path.join("deleted.txt"));
+        assert!(deleted_sidecar.exists());
+
+        // A killed run left a temporary file behind; a fresh one may still be in use
+        let index_dir = test_path.join(".ck");
+        let stale = index_dir.join(".tmpAbC123");
+        fs::write(&stale, vec![0u8; 4096]).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(SystemTime::now() - std::time::Duration::from_secs(3600))
+            .unwrap();
+        let fresh = index_dir.join(".tmpXyZ789");
+        fs::write(&fresh, b"in progress").unwrap();
+
+        let stats = compact_index(test_path, &file_options, None).unwrap();
+        assert_eq!(stats.entries_removed, 1);
+        assert_eq!(stats.sidecars_removed, 1);
+        assert_eq!(stats.temp_files_removed, 1);
+        assert!(stats.bytes_after < stats.bytes_before);
+        assert!(!deleted_sidecar.exists() && !stale.exists() && fresh.exists());
+
+        let manifest = load_or_create_manifest(&index_dir.join("manifest.json")).unwrap();
+        assert_eq!(manifest.files.len(), 1);
+    }
+
     #[test]
     fn test_get_index_stats() {
         let temp_dir = TempDir::new().unwrap();
@@ -1951,6 +3453,82 @@ mod tests {
         assert_eq!(stats.total_files, 1);
     }
 
+    #[test]
+    fn test_journal_resumes_interrupted_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_dir = temp_dir.path().join(".ck");
+        fs::create_dir_all(&index_dir).unwrap();
+        let manifest_path = index_dir.join("manifest.json");
+        let file = |name: &str| FileMetadata {
+            path: PathBuf::from(name),
+            hash: format!("{name}_hash"),
+            last_modified: 0,
+            size: 1,
+        };
+
+        let mut manifest = IndexManifest::default();
+        let mut journal = Journal::new(&manifest_path);
+        journal.checkpoint(&manifest).unwrap();
+        journal.record(&mut manifest, file("a.rs")).unwrap();
+        journal.record(&mut manifest, file("b.rs")).unwrap();
+        drop(journal);
+
+        // A run killed mid-write leaves a torn last line
+        let journal_file = index_dir.join(JOURNAL_FILE);
+        let mut torn = fs::OpenOptions::new()
+            .append(true)
+            .open(&journal_file)
+            .unwrap();
+        torn.write_all(b"{\"generation\": 9").unwrap();
+        drop(torn);
+
+        // The manifest on disk predates both files; loading replays them
+        assert_eq!(read_index_generation(temp_dir.path()), Some(2));
+        let resumed = load_or_create_manifest(&manifest_path).unwrap();
+        assert_eq!(resumed.files.len(), 2);
+        assert_eq!(resumed.generation, 2);
+
+        // Saving folds the journal into the manifest
+        save_manifest(&manifest_path, &resumed).unwrap();
+        assert!(!journal_file.exists());
+        assert_eq!(
+            load_or_create_manifest(&manifest_path).unwrap().files.len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_manifest_keys_collapse_to_one_spelling() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let entry = |path: &str, last_modified: u64| {
+            (
+                PathBuf::from(path),
+                FileMetadata {
+                    path: PathBuf::from(path),
+                    hash: last_modified.to_string(),
+                    last_modified,
+                    size: 1,
+                },
+            )
+        };
+        let mut manifest = IndexManifest::default();
+        manifest.files.extend([
+            entry("./src/a.rs", 1),
+            entry("./src/../src/a.rs", 2),
+            entry(&root.join("src/b.rs").to_string_lossy(), 1),
+        ]);
+
+        normalize_manifest_paths(&mut manifest, root);
+        let mut keys: Vec<_> = manifest.files.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![PathBuf::from("./src/a.rs"), PathBuf::from("./src/b.rs")]
+        );
+        assert_eq!(manifest.files[Path::new("./src/a.rs")].last_modified, 2);
+    }
+
     #[test]
     fn test_sidecar_to_original_path() {
         let temp_dir = TempDir::new().unwrap();
@@ -2059,6 +3637,8 @@ mod tests {
             respect_gitignore: true,
             use_ckignore: false,
             exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
         };
         let files = collect_files(test_path, &options_respect).unwrap();
         assert_eq!(
@@ -2073,6 +3653,8 @@ mod tests {
             respect_gitignore: false,
             use_ckignore: false,
             exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
         };
         let files = collect_files(test_path, &options_no_ignore).unwrap();
         assert_eq!(
@@ -2103,6 +3685,8 @@ mod tests {
             respect_gitignore: false,
             use_ckignore: true,
             exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
         };
 
         let files = collect_files(test_path, &options).unwrap();
@@ -2135,6 +3719,8 @@ mod tests {
             respect_gitignore: false,
             use_ckignore: false,
             exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
         };
 
         let files_all = collect_files(test_path, &options_both_disabled).unwrap();
@@ -2223,17 +3809,15 @@ mod cleanup_validation {
         let sidecar_path =
             path_utils::get_sidecar_path_for_standard_path(index_dir, &standard_path);
         if sidecar_path.exists() {
-            fs::remove_file(&sidecar_path)?;
+            super::remove_sidecar(&sidecar_path)?;
             stats.orphaned_sidecars_removed += 1;
         }
 
         // Remove content cache for PDFs
         if ck_core::pdf::is_pdf_file(&standard_path) {
             let absolute_path = repo_root.join(&standard_path);
-            let cache_path = ck_core::pdf::get_content_cache_path(repo_root, &absolute_path);
-            if cache_path.exists() {
-                fs::remove_file(&cache_path)?;
-                tracing::debug!("Removed orphaned content cache: {:?}", cache_path);
+            if content::remove_content(repo_root, &absolute_path)? {
+                tracing::debug!("Removed orphaned content cache for {:?}", absolute_path);
             }
         }
 
@@ -2253,7 +3837,10 @@ mod cleanup_validation {
             return Ok(());
         }
 
-        for entry in WalkDir::new(index_dir) {
+        for entry in WalkDir::new(index_dir)
+            .into_iter()
+            .filter_entry(|entry| !is_snapshot_store(entry))
+        {
             let entry = entry?;
             if entry.file_type().is_file() {
                 let sidecar_path = entry.path();
@@ -2267,7 +3854,7 @@ mod cleanup_validation {
                     if !standard_existing_files.contains(&standard_path)
                         || !manifest.files.contains_key(&manifest_path)
                     {
-                        fs::remove_file(sidecar_path)?;
+                        super::remove_sidecar(sidecar_path)?;
                         stats.orphaned_sidecars_removed += 1;
                     }
                 }
@@ -2288,24 +3875,27 @@ mod cleanup_validation {
 mod path_utils {
     use super::*;
 
-    /// Convert an absolute path to a standardized relative path from repo root
+    /// Convert an absolute path to a standardized relative path from repo root,
+    /// however either is spelled (see [`ck_core::paths::relative_to`])
     pub fn to_standard_path(absolute_path: &Path, repo_root: &Path) -> PathBuf {
-        if let Ok(relative) = absolute_path.strip_prefix(repo_root) {
-            relative.to_path_buf()
-        } else {
-            absolute_path.to_path_buf()
-        }
+        ck_core::paths::relative_to(absolute_path, repo_root)
+            .unwrap_or_else(|| absolute_path.to_path_buf())
     }
 
-    /// Convert a standardized path to a manifest path (with "./" prefix for compatibility)
+    /// Convert a standardized path to a manifest path (with "./" prefix for compatibility).
+    /// Relative paths use `/` separators on every platform, so manifests match
+    /// across operating systems.
     pub fn to_manifest_path(standard_path: &Path) -> PathBuf {
-        PathBuf::from(".").join(standard_path)
+        if standard_path.is_absolute() {
+            return standard_path.to_path_buf();
+        }
+        PathBuf::from(format!("./{}", ck_core::paths::to_slash(standard_path)))
     }
 
     /// Convert a manifest path (with "./" prefix) to a standardized path
     pub fn from_manifest_path(manifest_path: &Path) -> PathBuf {
         if let Ok(relative) = manifest_path.strip_prefix(".") {
-            relative.to_path_buf()
+            ck_core::paths::normalize_lexically(relative)
         } else {
             manifest_path.to_path_buf()
         }
@@ -0,0 +1,107 @@
+//! The `limits` rules of `.ckconfig.json`: files `ck --index` skips because
+//! they are too large, have a disabled extension, or chunk into too many
+//! pieces.
+
+use ck_models::IndexLimits;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{IndexError, Result};
+
+/// Files left out of an indexing run, with counts by rule.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Skipped {
+    pub files: Vec<PathBuf>,
+    pub too_large: usize,
+    pub by_extension: usize,
+}
+
+/// Drop the files `limits` rules out before they are read.
+pub(crate) fn retain_indexable(
+    files: Vec<PathBuf>,
+    limits: &IndexLimits,
+) -> (Vec<PathBuf>, Skipped) {
+    let mut skipped = Skipped::default();
+    if *limits == IndexLimits::default() {
+        return (files, skipped);
+    }
+    let mut kept = Vec::with_capacity(files.len());
+    for file in files {
+        if !limits.extension_enabled(&file) {
+            skipped.by_extension += 1;
+        } else if limits
+            .max_file_size
+            .is_some_and(|max| fs::metadata(&file).is_ok_and(|metadata| metadata.len() > max))
+        {
+            tracing::debug!("Skipping {:?}: larger than max_file_size", file);
+            skipped.too_large += 1;
+        } else {
+            kept.push(file);
+            continue;
+        }
+        skipped.files.push(file);
+    }
+    (kept, skipped)
+}
+
+/// Fail with [`IndexError::TooManyChunks`] when `file` chunked into more than
+/// `limits` allows.
+pub(crate) fn check_chunk_count(file: &Path, chunks: usize, limits: &IndexLimits) -> Result<()> {
+    match limits.max_chunks_per_file {
+        Some(limit) if chunks > limit => Err(IndexError::TooManyChunks {
+            file: file.to_path_buf(),
+            chunks,
+            limit,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_files_over_the_limits_are_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let dump = temp_dir.path().join("dump.SQL");
+        let big = temp_dir.path().join("big.rs");
+        let small = temp_dir.path().join("small.rs");
+        fs::write(&dump, "insert into t values (1);").unwrap();
+        fs::write(&big, "x".repeat(2048)).unwrap();
+        fs::write(&small, "fn main() {}").unwrap();
+        let files = vec![dump, big, small.clone()];
+
+        let (kept, skipped) = retain_indexable(files.clone(), &IndexLimits::default());
+        assert_eq!(kept, files);
+        assert_eq!(skipped, Skipped::default());
+
+        let limits = IndexLimits {
+            max_file_size: Some(1024),
+            extensions: [("sql".to_string(), false), ("rs".to_string(), true)].into(),
+            ..Default::default()
+        };
+        let files_skipped = files[..2].to_vec();
+        let (kept, skipped) = retain_indexable(files, &limits);
+        assert_eq!(kept, vec![small.clone()]);
+        assert_eq!(
+            skipped,
+            Skipped {
+                files: files_skipped,
+                too_large: 1,
+                by_extension: 1
+            }
+        );
+
+        let limits = IndexLimits {
+            max_chunks_per_file: Some(2),
+            ..Default::default()
+        };
+        assert!(check_chunk_count(&small, 2, &limits).is_ok());
+        assert!(matches!(
+            check_chunk_count(&small, 3, &limits),
+            Err(IndexError::TooManyChunks { limit: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,416 @@
+//! Custom key/value metadata attached to chunks at index time (service name,
+//! team, criticality tier), for `--meta KEY=VALUE` filters and JSON output.
+//! Two sources, merged in this order:
+//!
+//! - `.ckmeta.json` files: a flat JSON object whose pairs apply to every file
+//!   in its directory and below. A nearer file overrides a farther one's keys.
+//! - `"metadata_command"` in the user's `config.json`, or in `.ckconfig.json`
+//!   with `CK_TRUST_PROJECT=1`: run through the shell from the project root
+//!   for each file indexed, with `CK_FILE` set to the file's
+//!   relative path and a description of the file and its chunks on stdin:
+//!   `{"path": ..., "language": ..., "chunks": [{"line_start", "line_end",
+//!   "kind", "symbol"}]}`. It prints `{"file": {...}, "chunks": [{...}]}`,
+//!   with `chunks` in the order given; both parts are optional. A command
+//!   that fails, times out or prints anything else is reported, and the file
+//!   keeps its `.ckmeta.json` pairs alone.
+//!
+//! Values are strings; numbers and booleans are stored as their JSON text.
+//! Metadata is read when a file is indexed, so files pick up changes to it
+//! the next time they change, or when the index is rebuilt.
+
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+use super::{ChunkEntry, IndexEntry};
+
+/// Per-directory metadata file.
+pub const METADATA_FILE: &str = ".ckmeta.json";
+
+/// Key under which a generated file's chunks record the schema it was
+/// generated from, with `link_generated`.
+pub const GENERATED_FROM: &str = "generated_from";
+
+/// How long `metadata_command` may run for one file.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The `metadata_command` to run for files under `project`: the user's, or
+/// the project's when it is trusted.
+pub(crate) fn command_for(project: &ck_models::ProjectConfig) -> Option<String> {
+    if project.metadata_command.is_some() && !ck_models::project_trusted() {
+        static WARNED: std::sync::Once = std::sync::Once::new();
+        WARNED.call_once(|| {
+            tracing::warn!(
+                "Ignoring the metadata_command in {}: project commands run only with {}=1",
+                ck_models::PROJECT_CONFIG_FILE,
+                ck_models::TRUST_PROJECT_ENV
+            )
+        });
+    }
+    project.trusted().metadata_command
+}
+
+pub type Metadata = BTreeMap<String, String>;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CommandOutput {
+    file: Map<String, Value>,
+    chunks: Vec<Map<String, Value>>,
+}
+
+/// Set each chunk's metadata from the `.ckmeta.json` files above `file_path`
+/// and the project's `metadata_command`.
+pub(crate) fn attach(
+    repo_root: &Path,
+    file_path: &Path,
+    command: Option<&str>,
+    chunks: &mut [ChunkEntry],
+) {
+    let inherited = inherited(repo_root, file_path);
+    let output = command
+        .and_then(
+            |command| match run_command(command, repo_root, file_path, chunks) {
+                Ok(output) => Some(output),
+                Err(e) => {
+                    tracing::warn!("metadata_command '{}' {} for {:?}", command, e, file_path);
+                    None
+                }
+            },
+        )
+        .unwrap_or_default();
+
+    let mut file_metadata = inherited;
+    file_metadata.extend(strings(output.file));
+    let mut per_chunk = output.chunks.into_iter().map(strings);
+    for chunk in chunks {
+        let mut metadata = file_metadata.clone();
+        metadata.extend(per_chunk.next().unwrap_or_default());
+        chunk.metadata = metadata;
+    }
+}
+
+/// The schema a generated file's header names, as protoc and its plugins
+/// write it: `// source: api/user.proto` (or `#` for Python) near a
+/// "generated" or "DO NOT EDIT" notice. Thrift and GraphQL schemas count too.
+pub(crate) fn generated_from(content: &str) -> Option<String> {
+    let header: Vec<&str> = content.lines().take(40).collect();
+    let generated = header.iter().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.contains("generated") || line.contains("do not edit")
+    });
+    if !generated {
+        return None;
+    }
+    header.iter().find_map(|line| {
+        let (comment, source) = line.split_once("source:")?;
+        if !comment.trim_start().starts_with(['/', '#', '*']) {
+            return None;
+        }
+        let source = source.split_whitespace().next()?;
+        [".proto", ".thrift", ".graphql", ".gql"]
+            .iter()
+            .any(|extension| source.ends_with(extension))
+            .then(|| source.to_string())
+    })
+}
+
+/// The pairs of every `.ckmeta.json` from `repo_root` down to `file_path`'s
+/// directory, nearer files last.
+fn inherited(repo_root: &Path, file_path: &Path) -> Metadata {
+    let mut metadata = Metadata::new();
+    let Some(dir) = file_path.parent() else {
+        return metadata;
+    };
+    let mut dirs: Vec<&Path> = dir
+        .ancestors()
+        .take_while(|ancestor| ancestor.starts_with(repo_root))
+        .collect();
+    dirs.reverse();
+    for dir in dirs {
+        let path = dir.join(METADATA_FILE);
+        let Ok(data) = std::fs::read(&path) else {
+            continue;
+        };
+        match serde_json::from_slice::<Map<String, Value>>(&data) {
+            Ok(pairs) => metadata.extend(strings(pairs)),
+            Err(e) => tracing::warn!("Ignoring {:?}: {}", path, e),
+        }
+    }
+    metadata
+}
+
+/// The string, number and boolean values of `pairs`, as strings.
+fn strings(pairs: Map<String, Value>) -> Metadata {
+    pairs
+        .into_iter()
+        .filter_map(|(key, value)| match value {
+            Value::String(value) => Some((key, value)),
+            Value::Number(_) | Value::Bool(_) => Some((key, value.to_string())),
+            _ => {
+                tracing::debug!(
+                    "Ignoring metadata '{}': not a string, number or boolean",
+                    key
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+fn run_command(
+    command: &str,
+    repo_root: &Path,
+    file_path: &Path,
+    chunks: &[ChunkEntry],
+) -> Result<CommandOutput, String> {
+    let relative = ck_core::paths::relative_to(file_path, repo_root)
+        .unwrap_or_else(|| file_path.to_path_buf());
+    let relative = ck_core::paths::to_slash(&relative);
+    let input = json!({
+        "path": relative,
+        "language": chunks.first().and_then(|chunk| chunk.language).map(|lang| lang.to_string()),
+        "chunks": chunks
+            .iter()
+            .map(|chunk| json!({
+                "line_start": chunk.span.line_start,
+                "line_end": chunk.span.line_end,
+                "kind": chunk.chunk_type,
+                "symbol": chunk.symbol,
+            }))
+            .collect::<Vec<_>>(),
+    })
+    .to_string();
+
+    let mut command = ck_core::shell::command(command);
+    command.current_dir(repo_root).env("CK_FILE", &relative);
+    let output = run_with_timeout(command, input, COMMAND_TIMEOUT)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{}: {}", output.status, stderr.trim()));
+    }
+    if output.stdout.iter().all(u8::is_ascii_whitespace) {
+        return Ok(CommandOutput::default());
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("printed invalid output: {e}"))
+}
+
+/// Run `command` with `input` on its stdin, killing it once `timeout` has
+/// passed.
+pub(crate) fn run_with_timeout(
+    mut command: Command,
+    input: String,
+    timeout: Duration,
+) -> Result<Output, String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not start: {e}"))?;
+
+    // Feed stdin and drain the pipes on their own threads so a command that
+    // ignores its input or prints a lot cannot deadlock against us
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = std::thread::spawn(move || {
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as _));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(5)),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {}s", timeout.as_secs()));
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+    let _ = writer.join();
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// The metadata of the chunk covering `line` in `entry`: the smallest chunk
+/// containing it, or outside every chunk, the pairs all chunks share.
+pub fn metadata_at(entry: &IndexEntry, line: usize) -> Metadata {
+    let covering = entry
+        .chunks
+        .iter()
+        .filter(|chunk| chunk.span.line_start <= line && line <= chunk.span.line_end)
+        .min_by_key(|chunk| chunk.span.line_end - chunk.span.line_start);
+    if let Some(chunk) = covering {
+        return chunk.metadata.clone();
+    }
+    let mut chunks = entry.chunks.iter();
+    let Some(first) = chunks.next() else {
+        return Metadata::new();
+    };
+    let mut shared = first.metadata.clone();
+    for chunk in chunks {
+        shared.retain(|key, value| chunk.metadata.get(key) == Some(value));
+    }
+    shared
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::Span;
+    use tempfile::TempDir;
+
+    fn chunk(line_start: usize, line_end: usize, symbol: &str) -> ChunkEntry {
+        ChunkEntry {
+            span: Span {
+                byte_start: 0,
+                byte_end: 0,
+                line_start,
+                line_end,
+                col_start: 0,
+                col_end: 0,
+            },
+            embedding: None,
+            chunk_type: Some("function".to_string()),
+            breadcrumb: None,
+            symbol: Some(symbol.to_string()),
+            language: None,
+            ancestry: None,
+            byte_length: None,
+            estimated_tokens: None,
+            leading_trivia: None,
+            trailing_trivia: None,
+            chunk_hash: None,
+            content_hash: None,
+            fingerprint: None,
+            alias_of: None,
+            aliases: Vec::new(),
+            embedded_at: None,
+            metadata: Metadata::new(),
+            facets: None,
+            summary: None,
+        }
+    }
+
+    #[test]
+    fn test_nearer_metadata_files_override() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("services/payments")).unwrap();
+        std::fs::write(
+            root.join(METADATA_FILE),
+            r#"{"team": "platform", "tier": 3}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("services/payments").join(METADATA_FILE),
+            r#"{"team": "payments", "pci": true}"#,
+        )
+        .unwrap();
+        let file = root.join("services/payments/charge.rs");
+
+        let mut chunks = vec![chunk(1, 10, "charge")];
+        attach(root, &file, None, &mut chunks);
+        let expected: Metadata = [("pci", "true"), ("team", "payments"), ("tier", "3")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(chunks[0].metadata, expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_metadata_command_sets_file_and_chunk_pairs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let file = root.join("src/lib.rs");
+        let command = r#"cat > /dev/null; echo "{\"file\": {\"path\": \"$CK_FILE\"}, \"chunks\": [{\"tier\": 1}]}""#;
+
+        let mut chunks = vec![chunk(1, 10, "charge"), chunk(12, 20, "refund")];
+        attach(root, &file, Some(command), &mut chunks);
+        assert_eq!(chunks[0].metadata["path"], "src/lib.rs");
+        assert_eq!(chunks[0].metadata["tier"], "1");
+        assert_eq!(chunks[1].metadata["path"], "src/lib.rs");
+        assert!(!chunks[1].metadata.contains_key("tier"));
+
+        let entry = IndexEntry {
+            metadata: crate::FileMetadata {
+                path: file.clone(),
+                hash: String::new(),
+                last_modified: 0,
+                size: 0,
+            },
+            chunks,
+        };
+        assert_eq!(metadata_at(&entry, 5)["tier"], "1");
+        assert_eq!(metadata_at(&entry, 11).len(), 1);
+
+        let mut failing = vec![chunk(1, 10, "charge")];
+        attach(root, &file, Some("exit 3"), &mut failing);
+        assert!(failing[0].metadata.is_empty());
+    }
+
+    #[test]
+    fn test_project_metadata_command_needs_trust() {
+        let project = ck_models::ProjectConfig {
+            metadata_command: Some("touch pwned".to_string()),
+            ..Default::default()
+        };
+        let user = ck_models::UserConfig::default();
+        assert_eq!(
+            project.trusted_with(user.clone(), false).metadata_command,
+            None
+        );
+        assert_eq!(
+            project.trusted_with(user, true).metadata_command.as_deref(),
+            Some("touch pwned")
+        );
+
+        let own = ck_models::UserConfig {
+            metadata_command: Some("./meta.sh".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            project.trusted_with(own, true).metadata_command.as_deref(),
+            Some("./meta.sh")
+        );
+    }
+
+    #[test]
+    fn test_generated_files_name_their_schema() {
+        let go = "// Code generated by protoc-gen-go. DO NOT EDIT.\n// versions:\n// \tprotoc v4.25.1\n// source: api/user/v1/user.proto\n\npackage userv1\n";
+        assert_eq!(
+            generated_from(go).as_deref(),
+            Some("api/user/v1/user.proto")
+        );
+        let python = "# -*- coding: utf-8 -*-\n# Generated by the protocol buffer compiler.  DO NOT EDIT!\n# source: user.proto\n";
+        assert_eq!(generated_from(python).as_deref(), Some("user.proto"));
+
+        // A hand-written file mentioning a schema is not generated from it
+        assert_eq!(generated_from("// source: api/user.proto\n"), None);
+        assert_eq!(
+            generated_from("// generated docs\nlet source: &str = \"a.proto\";\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! `ck --index --dry-run`: walk, filter and chunk like a build, but embed
+//! nothing, so ignore rules and limits can be tuned before a long run.
+
+use ck_models::ModelConfig;
+use rayon::prelude::*;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use super::cost::{chunk_file, is_embedded, total_tokens};
+use super::drift::duration;
+use super::{Result, RunSettings, find_repo_root, limits, load_or_create_manifest, walk_files};
+
+/// Files listed as the largest in a plan.
+const LARGEST_FILES: usize = 10;
+
+/// Chunks embedded to time the model when the plan has that many.
+const TIMING_SAMPLE: usize = 32;
+
+/// A file the build would embed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub chunks: usize,
+    pub tokens: u64,
+}
+
+/// What building the index would process, without embedding anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexPlan {
+    pub model: String,
+    /// Files new or changed since the last build, which would be embedded
+    pub files: usize,
+    /// Files already embedded, unchanged, by this model
+    pub files_current: usize,
+    pub chunks: usize,
+    pub tokens: u64,
+    pub skipped_too_large: usize,
+    pub skipped_by_extension: usize,
+    pub skipped_too_many_chunks: usize,
+    /// Files that are binary or could not be read
+    pub unreadable: usize,
+    /// Embedding time at the model's measured speed; `None` when it was not
+    /// measured (priced endpoints, or a model that failed to load)
+    pub seconds: Option<u64>,
+    /// USD, for priced endpoints
+    pub cost: Option<f64>,
+    /// The files with the most tokens, largest first
+    pub largest: Vec<PlannedFile>,
+}
+
+impl fmt::Display for IndexPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Would embed {} files ({} chunks, ~{} tokens) with {}; {} files already current",
+            self.files, self.chunks, self.tokens, self.model, self.files_current
+        )?;
+        for (count, rule) in [
+            (self.skipped_too_large, "over max_file_size"),
+            (self.skipped_by_extension, "with a disabled extension"),
+            (self.skipped_too_many_chunks, "over max_chunks_per_file"),
+            (self.unreadable, "binary or unreadable"),
+        ] {
+            if count > 0 {
+                writeln!(f, "  {} files skipped {}", count, rule)?;
+            }
+        }
+        writeln!(f, "  Estimated time: {}", duration(self.seconds))?;
+        if let Some(cost) = self.cost {
+            writeln!(f, "  Estimated cost: ${:.2}", cost)?;
+        }
+        if !self.largest.is_empty() {
+            writeln!(f, "Largest files:")?;
+            for file in &self.largest {
+                writeln!(
+                    f,
+                    "  {:>8} tokens  {:>5} chunks  {:>10} bytes  {}",
+                    file.tokens,
+                    file.chunks,
+                    file.bytes,
+                    file.path.display()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Plan indexing `path` with `config`: count what a build would embed, time
+/// the model on a sample of the chunks, and list the largest files.
+pub fn plan_index(
+    path: &Path,
+    options: &ck_core::FileCollectionOptions,
+    config: &ModelConfig,
+) -> Result<IndexPlan> {
+    let repo_root = find_repo_root(path)?;
+    let manifest = load_or_create_manifest(&path.join(".ck").join("manifest.json"))?;
+    let settings = RunSettings::load(&repo_root);
+    let (walked, _) = walk_files(path, options)?;
+    let (files, skipped) = limits::retain_indexable(walked, &settings.project.limits);
+
+    let (current, pending): (Vec<PathBuf>, Vec<PathBuf>) = files
+        .into_par_iter()
+        .partition(|file| is_embedded(&manifest, &config.name, &repo_root, file));
+    let chunked: Vec<(PathBuf, Option<Vec<ck_chunk::Chunk>>)> = pending
+        .into_par_iter()
+        .map(|file| {
+            let chunks = chunk_file(&file, &config.name, &settings);
+            (file, chunks)
+        })
+        .collect();
+
+    let mut planned = Vec::new();
+    let mut sample = Vec::new();
+    let (mut unreadable, mut too_many_chunks) = (0, 0);
+    for (file, chunks) in chunked {
+        let Some(chunks) = chunks else {
+            unreadable += 1;
+            continue;
+        };
+        if limits::check_chunk_count(&file, chunks.len(), &settings.project.limits).is_err() {
+            too_many_chunks += 1;
+            continue;
+        }
+        sample.extend(
+            chunks
+                .iter()
+                .take(TIMING_SAMPLE - sample.len().min(TIMING_SAMPLE))
+                .map(|chunk| chunk.text.clone()),
+        );
+        planned.push(PlannedFile {
+            bytes: std::fs::metadata(&file).map_or(0, |metadata| metadata.len()),
+            chunks: chunks.len(),
+            tokens: total_tokens(&chunks),
+            path: file,
+        });
+    }
+
+    let chunks: usize = planned.iter().map(|file| file.chunks).sum();
+    let tokens: u64 = planned.iter().map(|file| file.tokens).sum();
+    let seconds = if config.remote.cost_per_million_tokens.is_some() {
+        None
+    } else {
+        seconds_per_chunk(config, &sample)
+            .map(|per_chunk| (per_chunk * chunks as f64).round() as u64)
+    };
+    let files = planned.len();
+    planned.sort_by_key(|file| std::cmp::Reverse(file.tokens));
+    planned.truncate(LARGEST_FILES);
+
+    Ok(IndexPlan {
+        model: config.name.clone(),
+        files,
+        files_current: current.len(),
+        chunks,
+        tokens,
+        skipped_too_large: skipped.too_large,
+        skipped_by_extension: skipped.by_extension,
+        skipped_too_many_chunks: too_many_chunks,
+        unreadable,
+        seconds,
+        cost: config.remote.cost_of(tokens),
+        largest: planned,
+    })
+}
+
+/// Time the model on `sample`, after one warm-up call so loading and the
+/// first (slowest) inference are not counted.
+fn seconds_per_chunk(config: &ModelConfig, sample: &[String]) -> Option<f64> {
+    let (warm_up, timed) = sample.split_first()?;
+    if timed.is_empty() {
+        return None;
+    }
+    let mut embedder = match ck_embed::create_embedder_for_config(config, None) {
+        Ok(embedder) => embedder,
+        Err(e) => {
+            tracing::warn!("Cannot time {}: {}", config.name, e);
+            return None;
+        }
+    };
+    embedder.embed(std::slice::from_ref(warm_up)).ok()?;
+    let started = Instant::now();
+    embedder.embed(timed).ok()?;
+    Some(started.elapsed().as_secs_f64() / timed.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_counts_without_writing_an_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let long = (0..400)
+            .map(|i| format!("fn handler_{i}() -> u32 {{ {i} }}\n"))
+            .collect::<String>();
+        std::fs::write(temp_dir.path().join("handlers.rs"), &long).unwrap();
+        std::fs::write(temp_dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        let options = ck_core::FileCollectionOptions {
+            respect_gitignore: true,
+            use_ckignore: true,
+            exclude_patterns: vec![],
+            follow_symlinks: false,
+            files_from: None,
+        };
+        let mut config = ck_models::ModelRegistry::default()
+            .get_default_model()
+            .unwrap()
+            .clone();
+        config.remote.cost_per_million_tokens = Some(1.0);
+
+        let plan = plan_index(temp_dir.path(), &options, &config).unwrap();
+        assert_eq!(plan.files, 2);
+        assert_eq!(plan.files_current, 0);
+        assert!(plan.chunks >= 2);
+        assert_eq!(plan.largest[0].path.file_name().unwrap(), "handlers.rs");
+        assert!(plan.largest[0].tokens > plan.largest[1].tokens);
+        assert!(plan.seconds.is_none());
+        assert!(plan.cost.is_some());
+        assert!(!temp_dir.path().join(".ck").exists());
+    }
+}
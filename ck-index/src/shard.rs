@@ -0,0 +1,208 @@
+//! Top-level directories as index shards.
+//!
+//! Sidecars mirror the repository layout under `.ck`, so the sidecars of each
+//! top-level directory already sit apart from the others. Treating those
+//! directories as shards lets `ck --index` embed several at once
+//! (`shard_workers` in `.ckconfig.json`), lets one be rebuilt without touching
+//! the rest, and lets semantic search score them in parallel before merging.
+//! Files directly in the repository root form the [`ROOT_SHARD`].
+
+use crate::{
+    DetailedProgressCallback, IndexedFile, ProgressCallback, Result, RunSettings, generation,
+    index_single_file_with_progress, load_or_create_manifest, normalize_manifest_paths, path_utils,
+    remove_empty_dirs, remove_sidecar, save_manifest, snapshot,
+};
+use ck_core::CancellationToken;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, mpsc};
+use walkdir::WalkDir;
+
+/// Shard holding the files directly in the repository root.
+pub const ROOT_SHARD: &str = ".";
+
+/// Outcome of indexing one file.
+pub(crate) type FileResult = Result<IndexedFile>;
+
+/// The shard of a path relative to the repository root.
+pub fn shard_of(relative_path: &Path) -> String {
+    let mut components = relative_path
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir));
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(first)), Some(_)) => first.to_string_lossy().into_owned(),
+        _ => ROOT_SHARD.to_string(),
+    }
+}
+
+/// One shard of an index directory.
+#[derive(Debug, Clone)]
+pub struct Shard {
+    pub name: String,
+    dir: PathBuf,
+}
+
+impl Shard {
+    /// Sidecar files in this shard, found by walking its directory.
+    pub fn sidecars(&self) -> impl Iterator<Item = PathBuf> + use<> {
+        let depth = if self.name == ROOT_SHARD {
+            1
+        } else {
+            usize::MAX
+        };
+        WalkDir::new(&self.dir)
+            .min_depth(1)
+            .max_depth(depth)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry.file_type().is_file()
+                    && entry.path().extension().and_then(|s| s.to_str()) == Some("ck")
+            })
+            .map(|entry| entry.into_path())
+    }
+}
+
+/// Every shard under `index_dir`: the root shard, then each top-level directory
+/// other than the snapshot store. Directories holding no sidecars (such as the
+/// lexical index) yield none.
+pub fn list_shards(index_dir: &Path) -> Result<Vec<Shard>> {
+    let mut shards = vec![Shard {
+        name: ROOT_SHARD.to_string(),
+        dir: index_dir.to_path_buf(),
+    }];
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(index_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() && !snapshot::is_reserved_dir(&entry.file_name()) {
+            dirs.push(Shard {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                dir: entry.path(),
+            });
+        }
+    }
+    dirs.sort_by(|a, b| a.name.cmp(&b.name));
+    shards.extend(dirs);
+    Ok(shards)
+}
+
+/// Remove one shard's sidecars and manifest entries so the next `ck --index`
+/// re-indexes just that shard. Returns the number of files dropped.
+pub fn drop_shard(repo_root: &Path, name: &str) -> Result<usize> {
+    let index_dir = repo_root.join(".ck");
+    let manifest_path = index_dir.join("manifest.json");
+    if !manifest_path.exists() {
+        return Ok(0);
+    }
+    let _writing = generation::begin_write(repo_root);
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    normalize_manifest_paths(&mut manifest, repo_root);
+
+    let before = manifest.files.len();
+    manifest
+        .files
+        .retain(|path, _| shard_of(&path_utils::from_manifest_path(path)) != name);
+    let dropped = before - manifest.files.len();
+
+    if let Some(shard) = list_shards(&index_dir)?
+        .into_iter()
+        .find(|shard| shard.name == name)
+    {
+        for sidecar in shard.sidecars() {
+            remove_sidecar(&sidecar)?;
+        }
+    }
+    remove_empty_dirs(&index_dir)?;
+
+    manifest.touch();
+    save_manifest(&manifest_path, &manifest)?;
+    Ok(dropped)
+}
+
+/// Group files by shard, smallest shard first, so workers popping from the end
+/// start on the largest shards.
+fn group_by_shard(files: &[PathBuf], repo_root: &Path) -> Vec<Vec<PathBuf>> {
+    let mut shards: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for file in files {
+        shards
+            .entry(shard_of(&path_utils::to_standard_path(file, repo_root)))
+            .or_default()
+            .push(file.clone());
+    }
+    let mut shards: Vec<Vec<PathBuf>> = shards.into_values().collect();
+    shards.sort_by_key(Vec::len);
+    shards
+}
+
+/// Shard workers to embed `files` with: the configured count, but no more than
+/// there are shards to hand out.
+pub(crate) fn worker_count(configured: usize, files: &[PathBuf], repo_root: &Path) -> usize {
+    configured.clamp(1, group_by_shard(files, repo_root).len().max(1))
+}
+
+/// Embed `files` with one embedder per worker, each taking whole shards in turn.
+/// Results reach `on_result` on the calling thread as they finish, so sidecars
+/// and the journal are only ever written from one place. Stops early when
+/// `cancel` is cancelled or `on_result` fails.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn embed_shards(
+    files: &[PathBuf],
+    repo_root: &Path,
+    settings: &RunSettings,
+    embedders: &mut [Box<dyn ck_embed::Embedder>],
+    progress_callback: Option<&ProgressCallback>,
+    detailed_progress_callback: Option<&DetailedProgressCallback>,
+    cancel: &CancellationToken,
+    mut on_result: impl FnMut(PathBuf, FileResult) -> Result<()>,
+) -> Result<()> {
+    let queue = Mutex::new(group_by_shard(files, repo_root));
+    let started = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for embedder in embedders.iter_mut() {
+            let tx = tx.clone();
+            let (queue, started) = (&queue, &started);
+            scope.spawn(move || {
+                loop {
+                    let Some(shard) = queue.lock().unwrap_or_else(|e| e.into_inner()).pop() else {
+                        return;
+                    };
+                    for file_path in shard {
+                        if cancel.is_cancelled() {
+                            return;
+                        }
+                        if let Some(callback) = progress_callback
+                            && let Some(file_name) = file_path.file_name()
+                        {
+                            callback(&file_name.to_string_lossy());
+                        }
+                        let file_index = started.fetch_add(1, Ordering::SeqCst);
+                        let result = index_single_file_with_progress(
+                            &file_path,
+                            repo_root,
+                            settings,
+                            Some(&mut *embedder),
+                            detailed_progress_callback,
+                            file_index,
+                            files.len(),
+                            cancel,
+                        );
+                        if tx.send((file_path, result)).is_err() {
+                            // The receiver stopped on an error
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        for (file_path, result) in rx {
+            on_result(file_path, result)?;
+        }
+        Ok(())
+    })
+}
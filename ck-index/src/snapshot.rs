@@ -0,0 +1,443 @@
+//! Snapshots: earlier generations of the index, kept for `--as-of`.
+//!
+//! With `"snapshots": N` in `.ckconfig.json`, each index update that advances
+//! the generation leaves a copy of the result under
+//! `.ck/.snapshots/<generation>`, and the oldest beyond N are pruned. A
+//! snapshot is laid out like a repository with its own index: its `.ck` holds
+//! the manifest and sidecars, and beside it sit the indexed files as they were
+//! indexed, so every search mode runs against it unchanged. Sidecars are hard
+//! links, which stay intact because updates replace sidecars rather than
+//! rewriting them in place. Sources are stored once per content hash under
+//! `.snapshots/objects` and linked into each snapshot holding them, so a new
+//! snapshot costs little more than the files changed since the last one.
+//! Derived artifacts (HNSW graph, file vectors, lexical index) are left out;
+//! searches of a snapshot scan its sidecars instead. Encrypted indexes are not
+//! snapshotted, since their sources would be stored unencrypted.
+
+use crate::file_list::FILE_LIST;
+use crate::{
+    IndexError, IndexManifest, Result, content, encryption, load_or_create_manifest, path_utils,
+    save_manifest,
+};
+use ck_core::{compute_file_hash, get_sidecar_path};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory under `.ck` holding the snapshots. Hidden, so it can never be
+/// mistaken for the shard of a top-level directory of the repository.
+pub const SNAPSHOTS_DIR: &str = ".snapshots";
+
+/// Content-addressed sources shared by the snapshots.
+const OBJECTS_DIR: &str = "objects";
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// One kept generation of the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub generation: u64,
+    /// When the generation was written (Unix seconds)
+    pub updated: u64,
+    pub files: usize,
+    /// Searchable root: the snapshot's `.ck` and its sources
+    pub root: PathBuf,
+}
+
+/// Whether a walk of `.ck` has reached the snapshot store, whose sidecars
+/// belong to other generations, or the history store, an index of its own.
+pub fn is_snapshot_store(entry: &walkdir::DirEntry) -> bool {
+    entry.depth() == 1 && is_reserved_dir(entry.file_name())
+}
+
+/// Whether `name`, a directory directly under `.ck`, is one of ck's own
+/// stores rather than a shard.
+pub(crate) fn is_reserved_dir(name: &std::ffi::OsStr) -> bool {
+    name == SNAPSHOTS_DIR || name == crate::HISTORY_DIR
+}
+
+fn store_dir(repo_root: &Path) -> PathBuf {
+    repo_root.join(".ck").join(SNAPSHOTS_DIR)
+}
+
+fn read_manifest(snapshot_root: &Path) -> Result<IndexManifest> {
+    let data = fs::read(snapshot_root.join(".ck").join("manifest.json"))?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// The snapshots kept for the index at `repo_root`, oldest first.
+pub fn list_snapshots(repo_root: &Path) -> Result<Vec<Snapshot>> {
+    let store = store_dir(repo_root);
+    if !store.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(&store)? {
+        let entry = entry?;
+        let Some(generation) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let root = entry.path();
+        let Ok(manifest) = read_manifest(&root) else {
+            continue;
+        };
+        snapshots.push(Snapshot {
+            generation,
+            updated: manifest.updated,
+            files: manifest.files.len(),
+            root,
+        });
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.generation);
+    Ok(snapshots)
+}
+
+/// The snapshot `as_of` names: a generation number, or the newest snapshot
+/// written at or before a UTC date (`YYYY-MM-DD`, through the end of that day)
+/// or time (`YYYY-MM-DDTHH:MM[:SS]`).
+pub fn resolve_snapshot(repo_root: &Path, as_of: &str) -> Result<Snapshot> {
+    let snapshots = list_snapshots(repo_root)?;
+    if snapshots.is_empty() {
+        return Err(IndexError::Snapshot(
+            "No snapshots of this index; set \"snapshots\" in .ckconfig.json to keep earlier generations".to_string(),
+        ));
+    }
+    let available = || {
+        snapshots
+            .iter()
+            .map(|snapshot| snapshot.generation.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if let Ok(generation) = as_of.trim().parse::<u64>() {
+        return snapshots
+            .iter()
+            .find(|snapshot| snapshot.generation == generation)
+            .cloned()
+            .ok_or_else(|| {
+                IndexError::Snapshot(format!(
+                    "No snapshot of generation {}; kept generations: {}",
+                    generation,
+                    available()
+                ))
+            });
+    }
+    let Some(cutoff) = parse_timestamp(as_of.trim()) else {
+        return Err(IndexError::Snapshot(format!(
+            "Invalid --as-of value '{}'; expected a generation, YYYY-MM-DD or YYYY-MM-DDTHH:MM[:SS] (UTC)",
+            as_of
+        )));
+    };
+    snapshots
+        .iter()
+        .rev()
+        .find(|snapshot| snapshot.updated <= cutoff)
+        .cloned()
+        .ok_or_else(|| {
+            IndexError::Snapshot(format!(
+                "No snapshot was written by {}; kept generations: {}",
+                as_of,
+                available()
+            ))
+        })
+}
+
+/// Unix seconds for a UTC `YYYY-MM-DD` (its last second) or
+/// `YYYY-MM-DD[T ]HH:MM[:SS][Z]`.
+fn parse_timestamp(value: &str) -> Option<u64> {
+    let (date, time) = match value.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time.trim_end_matches('Z'))),
+        None => (value, None),
+    };
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    let seconds = match time {
+        None => SECONDS_PER_DAY - 1,
+        Some(time) => {
+            let mut parts = time.splitn(3, ':');
+            let hours: u64 = parts.next()?.parse().ok()?;
+            let minutes: u64 = parts.next()?.parse().ok()?;
+            let seconds: u64 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+            if hours > 23 || minutes > 59 || seconds > 59 {
+                return None;
+            }
+            hours * 3600 + minutes * 60 + seconds
+        }
+    };
+    Some(days * SECONDS_PER_DAY + seconds)
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date (Howard Hinnant's
+/// `days_from_civil`).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Hard-link `from` to `to`, copying when the filesystem cannot link.
+fn link_or_copy(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::hard_link(from, to).is_err() {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+/// Keep the index's current generation as a snapshot, then prune all but the
+/// newest `keep`. Does nothing when `keep` is 0, the generation is already
+/// kept, or the index is encrypted.
+pub(crate) fn take_snapshot(repo_root: &Path, keep: usize) -> Result<()> {
+    let manifest_path = repo_root.join(".ck").join("manifest.json");
+    if keep == 0 || !manifest_path.exists() {
+        return Ok(());
+    }
+    if encryption::is_encrypted(repo_root) {
+        tracing::debug!("Not snapshotting an encrypted index");
+        return Ok(());
+    }
+    let manifest = load_or_create_manifest(&manifest_path)?;
+    let store = store_dir(repo_root);
+    let target = store.join(manifest.generation.to_string());
+    if target.exists() {
+        return Ok(());
+    }
+
+    // Assemble beside the target, so an interrupted snapshot is never listed
+    let staging = store.join(format!("{}.partial", manifest.generation));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    let objects = store.join(OBJECTS_DIR);
+    fs::create_dir_all(&objects)?;
+    let mut kept = manifest.clone();
+    for (key, metadata) in &manifest.files {
+        let relative = path_utils::from_manifest_path(key);
+        let source = repo_root.join(&relative);
+        let sidecar = get_sidecar_path(repo_root, &source);
+        if relative.is_absolute() || !sidecar.exists() {
+            kept.files.remove(key);
+            continue;
+        }
+        let object = objects.join(&metadata.hash);
+        if !object.exists() {
+            // A file edited since it was indexed no longer matches its sidecar
+            if compute_file_hash(&source).ok().as_ref() != Some(&metadata.hash) {
+                kept.files.remove(key);
+                continue;
+            }
+            let partial = objects.join(format!("{}.partial", metadata.hash));
+            fs::copy(&source, &partial)?;
+            fs::rename(&partial, &object)?;
+        }
+        let snapshot_source = staging.join(&relative);
+        link_or_copy(&object, &snapshot_source)?;
+        link_or_copy(&sidecar, &get_sidecar_path(&staging, &snapshot_source))?;
+        // Extracted text is rewritten in place, so it is copied rather than linked
+        for (stored, copy) in [
+            (
+                content::content_path(repo_root, &source),
+                content::content_path(&staging, &snapshot_source),
+            ),
+            (
+                ck_core::pdf::get_content_cache_path(repo_root, &source),
+                ck_core::pdf::get_content_cache_path(&staging, &snapshot_source),
+            ),
+        ] {
+            if stored.exists() {
+                fs::create_dir_all(copy.parent().unwrap_or(&staging))?;
+                fs::copy(&stored, &copy)?;
+            }
+        }
+    }
+    let dictionary = repo_root.join(".ck").join(content::CONTENT_DICTIONARY_FILE);
+    if dictionary.exists() {
+        fs::create_dir_all(staging.join(".ck"))?;
+        fs::copy(
+            &dictionary,
+            staging.join(".ck").join(content::CONTENT_DICTIONARY_FILE),
+        )?;
+    }
+    save_manifest(&staging.join(".ck").join("manifest.json"), &kept)?;
+    fs::rename(&staging, &target)?;
+    tracing::debug!(
+        "Kept generation {} as a snapshot of {} files",
+        manifest.generation,
+        kept.files.len()
+    );
+
+    prune(repo_root, keep)
+}
+
+/// Remove all but the newest `keep` snapshots, then the sources only they held.
+fn prune(repo_root: &Path, keep: usize) -> Result<()> {
+    let snapshots = list_snapshots(repo_root)?;
+    let excess = snapshots.len().saturating_sub(keep);
+    for snapshot in &snapshots[..excess] {
+        fs::remove_dir_all(&snapshot.root)?;
+    }
+    if excess == 0 {
+        return Ok(());
+    }
+
+    let mut referenced = HashSet::new();
+    for snapshot in &snapshots[excess..] {
+        let manifest = read_manifest(&snapshot.root)?;
+        referenced.extend(manifest.files.into_values().map(|metadata| metadata.hash));
+    }
+    for entry in fs::read_dir(store_dir(repo_root).join(OBJECTS_DIR))? {
+        let entry = entry?;
+        if !referenced.contains(entry.file_name().to_string_lossy().as_ref()) {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Empty the index at `repo_root` for a rebuild, keeping its snapshots and
+/// the file list it was built from. The new manifest continues from the
+/// newest snapshot's generation, so the rebuilt index cannot reuse a
+/// generation a snapshot already stands for.
+pub(crate) fn clear_keeping_snapshots(repo_root: &Path) -> Result<()> {
+    let index_dir = repo_root.join(".ck");
+    if !index_dir.exists() {
+        return Ok(());
+    }
+    let newest = list_snapshots(repo_root)?.last().map(|s| s.generation);
+    for entry in fs::read_dir(&index_dir)? {
+        let entry = entry?;
+        if entry.file_name() == SNAPSHOTS_DIR || entry.file_name() == FILE_LIST {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    if let Some(generation) = newest {
+        let manifest = IndexManifest {
+            generation,
+            ..Default::default()
+        };
+        save_manifest(&index_dir.join("manifest.json"), &manifest)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_core::FileMetadata;
+    use tempfile::TempDir;
+
+    /// Index `files` as the next generation by hand: sources, empty sidecars
+    /// and a manifest, the way an update leaves them.
+    fn write_generation(root: &Path, files: &[(&str, &str)]) -> u64 {
+        let manifest_path = root.join(".ck").join("manifest.json");
+        let mut manifest = if manifest_path.exists() {
+            load_or_create_manifest(&manifest_path).unwrap()
+        } else {
+            IndexManifest::default()
+        };
+        manifest.files.clear();
+        for (name, text) in files {
+            let source = root.join(name);
+            fs::write(&source, text).unwrap();
+            let metadata = FileMetadata {
+                path: PathBuf::from(format!("./{}", name)),
+                hash: compute_file_hash(&source).unwrap(),
+                last_modified: 0,
+                size: text.len() as u64,
+            };
+            let entry = crate::IndexEntry {
+                metadata: metadata.clone(),
+                chunks: Vec::new(),
+            };
+            crate::save_index_entry(&get_sidecar_path(root, &source), &entry).unwrap();
+            manifest.files.insert(metadata.path.clone(), metadata);
+        }
+        manifest.touch();
+        save_manifest(&manifest_path, &manifest).unwrap();
+        manifest.generation
+    }
+
+    #[test]
+    fn test_snapshots_keep_sources_and_prune_oldest() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let first = write_generation(root, &[("a.txt", "old"), ("b.txt", "same")]);
+        take_snapshot(root, 2).unwrap();
+        let second = write_generation(root, &[("a.txt", "new"), ("b.txt", "same")]);
+        take_snapshot(root, 2).unwrap();
+
+        let snapshots = list_snapshots(root).unwrap();
+        assert_eq!(
+            snapshots.iter().map(|s| s.generation).collect::<Vec<_>>(),
+            vec![first, second]
+        );
+        let old = resolve_snapshot(root, &first.to_string()).unwrap();
+        assert_eq!(fs::read_to_string(old.root.join("a.txt")).unwrap(), "old");
+        assert!(get_sidecar_path(&old.root, &old.root.join("b.txt")).exists());
+
+        // A third generation pushes out the first, and the source only it held
+        write_generation(root, &[("b.txt", "same")]);
+        take_snapshot(root, 2).unwrap();
+        assert_eq!(list_snapshots(root).unwrap().len(), 2);
+        assert!(resolve_snapshot(root, &first.to_string()).is_err());
+        let objects: Vec<_> = fs::read_dir(store_dir(root).join(OBJECTS_DIR))
+            .unwrap()
+            .collect();
+        assert_eq!(objects.len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_snapshot_by_date() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write_generation(root, &[("a.txt", "text")]);
+        take_snapshot(root, 1).unwrap();
+
+        assert!(resolve_snapshot(root, "1999-12-31").is_err());
+        assert!(resolve_snapshot(root, "2999-01-01T00:00").is_ok());
+        assert!(resolve_snapshot(root, "yesterday").is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("1970-01-02"), Some(2 * SECONDS_PER_DAY - 1));
+        assert_eq!(parse_timestamp("2024-03-01 12:30"), Some(1_709_296_200));
+        assert_eq!(parse_timestamp("2024-13-01"), None);
+    }
+
+    #[test]
+    fn test_rebuild_keeps_snapshots_and_generation() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let generation = write_generation(root, &[("a.txt", "text")]);
+        take_snapshot(root, 3).unwrap();
+
+        clear_keeping_snapshots(root).unwrap();
+        assert_eq!(list_snapshots(root).unwrap().len(), 1);
+        assert!(!get_sidecar_path(root, &root.join("a.txt")).exists());
+        assert_eq!(crate::read_index_generation(root), Some(generation));
+    }
+}
@@ -0,0 +1,136 @@
+//! Git submodules. A checked-out submodule is a directory holding a `.git`
+//! file (a linked worktree looks the same). Walks skip them unless
+//! `"submodules": true` is set in `.ckconfig.json`; with it, their files are
+//! indexed and each chunk records the submodule's path and checked-out
+//! commit as metadata, for `--meta submodule=vendor/lib` filters and JSON
+//! output. The commit is read from the submodule's git directory, without
+//! running git.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Metadata key holding the path of the submodule a chunk's file is in,
+/// relative to the project root.
+pub const SUBMODULE: &str = "submodule";
+
+/// Metadata key holding the commit checked out in that submodule.
+pub const SUBMODULE_COMMIT: &str = "submodule_commit";
+
+/// Whether a walk has reached a submodule (or nested worktree) below its root.
+pub(crate) fn is_submodule(entry: &ignore::DirEntry) -> bool {
+    entry.depth() > 0
+        && entry.file_type().is_some_and(|ft| ft.is_dir())
+        && entry.path().join(".git").is_file()
+}
+
+/// The innermost submodule holding `file_path` below `repo_root`: its
+/// relative path, and its checked-out commit when that can be read.
+pub(crate) fn submodule_of(repo_root: &Path, file_path: &Path) -> Option<(String, Option<String>)> {
+    let mut dir = file_path.parent()?;
+    while dir.starts_with(repo_root) && dir != repo_root {
+        let dot_git = dir.join(".git");
+        if dot_git.is_file() {
+            let relative = ck_core::paths::relative_to(dir, repo_root)?;
+            let commit = git_dir(&dot_git).and_then(|git_dir| head_commit(&git_dir));
+            return Some((ck_core::paths::to_slash(&relative), commit));
+        }
+        dir = dir.parent()?;
+    }
+    None
+}
+
+/// The commit checked out in each submodule `.gitmodules` lists, by path.
+pub(crate) fn checked_out(repo_root: &Path) -> BTreeMap<String, String> {
+    let Ok(gitmodules) = fs::read_to_string(repo_root.join(".gitmodules")) else {
+        return BTreeMap::new();
+    };
+    gitmodules
+        .lines()
+        .filter_map(|line| {
+            let path = line
+                .trim()
+                .strip_prefix("path")?
+                .trim_start()
+                .strip_prefix('=')?;
+            let path = path.trim();
+            let commit = git_dir(&repo_root.join(path).join(".git"))
+                .and_then(|git_dir| head_commit(&git_dir))?;
+            Some((path.to_string(), commit))
+        })
+        .collect()
+}
+
+/// The directory a `.git` file points at (`gitdir: ../.git/modules/lib`).
+fn git_dir(dot_git: &Path) -> Option<PathBuf> {
+    let text = fs::read_to_string(dot_git).ok()?;
+    let target = text.lines().next()?.strip_prefix("gitdir:")?.trim();
+    Some(dot_git.parent()?.join(target))
+}
+
+/// The commit `HEAD` of `git_dir` names, following one symbolic ref through
+/// loose and packed refs.
+fn head_commit(git_dir: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let Some(reference) = head.strip_prefix("ref:").map(str::trim) else {
+        return Some(head.to_string());
+    };
+    if let Ok(commit) = fs::read_to_string(git_dir.join(reference)) {
+        return Some(commit.trim().to_string());
+    }
+    let packed = fs::read_to_string(git_dir.join("packed-refs")).ok()?;
+    packed.lines().find_map(|line| {
+        let (commit, name) = line.split_once(' ')?;
+        (name == reference).then(|| commit.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_submodule_of_reads_checked_out_commit() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let modules = root.join(".git/modules/vendor/lib");
+        fs::create_dir_all(modules.join("refs/heads")).unwrap();
+        fs::write(modules.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(
+            modules.join("packed-refs"),
+            "# pack-refs\nabc123 refs/heads/main\n",
+        )
+        .unwrap();
+        let submodule = root.join("vendor/lib");
+        fs::create_dir_all(submodule.join("src")).unwrap();
+        fs::write(
+            submodule.join(".git"),
+            "gitdir: ../../.git/modules/vendor/lib\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            submodule_of(root, &submodule.join("src/lib.rs")),
+            Some(("vendor/lib".to_string(), Some("abc123".to_string())))
+        );
+        assert_eq!(submodule_of(root, &root.join("src/main.rs")), None);
+
+        fs::write(
+            root.join(".gitmodules"),
+            "[submodule \"lib\"]\n\tpath = vendor/lib\n\turl = https://example.com/lib.git\n",
+        )
+        .unwrap();
+        assert_eq!(
+            checked_out(root),
+            BTreeMap::from([("vendor/lib".to_string(), "abc123".to_string())])
+        );
+
+        // A loose ref wins over a packed one, and a detached HEAD is the commit
+        fs::write(modules.join("refs/heads/main"), "def456\n").unwrap();
+        assert_eq!(head_commit(&modules).as_deref(), Some("def456"));
+        fs::write(modules.join("HEAD"), "0123abcd\n").unwrap();
+        assert_eq!(head_commit(&modules).as_deref(), Some("0123abcd"));
+    }
+}
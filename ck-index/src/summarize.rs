@@ -0,0 +1,178 @@
+//! Summarize-then-embed for opaque files: `"summarize": {"paths": [...]}` in
+//! `.ckconfig.json`. Each chunk of a matching file is sent to the LLM and the
+//! reply is embedded in place of the chunk text. The chunk keeps its own span,
+//! so results point at the real lines, and stores the summary it was embedded
+//! from. Unchanged chunks reuse their summary with their embedding.
+//!
+//! ck-index does not talk to LLMs itself; the search engine registers a
+//! client with [`set_summarizer`]. Without one, or after a failed request,
+//! the rest of the file is embedded as it is and summarized on a later update.
+
+use ck_core::compute_chunk_hash;
+use ck_models::SummarizeConfig;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use super::ChunkEntry;
+
+/// Writes a summary of a chunk's text for the project at the given root.
+pub type Summarizer = Arc<dyn Fn(&Path, &str) -> std::result::Result<String, String> + Send + Sync>;
+
+static SUMMARIZER: RwLock<Option<Summarizer>> = RwLock::new(None);
+
+/// Hashed ahead of a summarized chunk's text, so turning summaries on or off
+/// for a file re-embeds its chunks.
+const HASH_MARKER: &str = "\0summary\0";
+
+/// Summarize the chunks of the files `"summarize"` lists with `summarizer`,
+/// replacing any summarizer set before.
+pub fn set_summarizer<F>(summarizer: F)
+where
+    F: Fn(&Path, &str) -> std::result::Result<String, String> + Send + Sync + 'static,
+{
+    *SUMMARIZER.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(summarizer));
+}
+
+/// Summaries for the chunks of one file being indexed.
+pub(crate) struct FileSummaries<'a> {
+    repo_root: &'a Path,
+    file_path: &'a Path,
+    summarizer: Summarizer,
+    max_input_bytes: usize,
+    /// Summaries in the file's previous sidecar, by chunk hash
+    previous: HashMap<String, String>,
+    /// Summaries of this run, by chunk hash
+    pub(crate) written: HashMap<String, String>,
+    failed: bool,
+}
+
+impl<'a> FileSummaries<'a> {
+    /// Summaries for `file_path` when `config` lists it and a summarizer is
+    /// registered; `old_chunks` are its chunks from the previous index.
+    pub(crate) fn for_file(
+        repo_root: &'a Path,
+        file_path: &'a Path,
+        config: &SummarizeConfig,
+        old_chunks: &[ChunkEntry],
+    ) -> Option<Self> {
+        if !is_listed(repo_root, file_path, &config.paths) {
+            return None;
+        }
+        let Some(summarizer) = SUMMARIZER.read().unwrap_or_else(|e| e.into_inner()).clone() else {
+            tracing::warn!(
+                "{:?} is listed under \"summarize\" but no summarizer is available; embedding it as is",
+                file_path
+            );
+            return None;
+        };
+        let previous = old_chunks
+            .iter()
+            .filter_map(|chunk| Some((chunk.chunk_hash.clone()?, chunk.summary.clone()?)))
+            .collect();
+        Some(Self {
+            repo_root,
+            file_path,
+            summarizer,
+            max_input_bytes: config.max_input_bytes,
+            previous,
+            written: HashMap::new(),
+            failed: false,
+        })
+    }
+
+    /// The text to embed for a chunk whose plain embedding input is `input`,
+    /// and its hash: the chunk's summary when one is cached (`is_cached`) or
+    /// can be written, otherwise `input` itself. Either is passed through
+    /// `instruct` (the model's document instruction) last, and the hash covers
+    /// it, so the LLM never sees the instruction.
+    pub(crate) fn prepare(
+        &mut self,
+        input: String,
+        instruct: impl Fn(String) -> String,
+        leading_trivia: &[String],
+        trailing_trivia: &[String],
+        is_cached: impl Fn(&str) -> bool,
+    ) -> (String, String) {
+        let hash = compute_chunk_hash(
+            &instruct(format!("{HASH_MARKER}{input}")),
+            leading_trivia,
+            trailing_trivia,
+        );
+        let summary = match self.previous.get(&hash) {
+            Some(summary) if is_cached(&hash) => Some(summary.clone()),
+            _ if self.failed => None,
+            _ => match (self.summarizer)(self.repo_root, truncate(&input, self.max_input_bytes)) {
+                Ok(summary) if !summary.trim().is_empty() => Some(summary.trim().to_string()),
+                Ok(_) => None,
+                Err(e) => {
+                    tracing::warn!(
+                        "Could not summarize a chunk of {:?}, embedding the file as is: {}",
+                        self.file_path,
+                        e
+                    );
+                    self.failed = true;
+                    None
+                }
+            },
+        };
+        match summary {
+            Some(summary) => {
+                self.written.insert(hash.clone(), summary.clone());
+                (instruct(summary), hash)
+            }
+            None => {
+                let input = instruct(input);
+                let hash = compute_chunk_hash(&input, leading_trivia, trailing_trivia);
+                (input, hash)
+            }
+        }
+    }
+}
+
+/// Whether `file_path` matches one of the `paths` globs.
+fn is_listed(repo_root: &Path, file_path: &Path, paths: &[String]) -> bool {
+    if paths.is_empty() {
+        return false;
+    }
+    let mut builder = ignore::overrides::OverrideBuilder::new(repo_root);
+    for pattern in paths {
+        if let Err(e) = builder.add(pattern) {
+            tracing::warn!("Ignoring \"summarize\" path '{}': {}", pattern, e);
+        }
+    }
+    builder
+        .build()
+        .is_ok_and(|globs| globs.matched(file_path, false).is_whitelist())
+}
+
+/// At most `max_bytes` of `text`, cut at a character boundary.
+fn truncate(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listed_paths_and_truncation() {
+        let root = Path::new("/repo");
+        let paths = vec!["gen/**".to_string(), "*.csv".to_string()];
+        assert!(is_listed(root, Path::new("/repo/gen/api.pb.go"), &paths));
+        assert!(is_listed(root, Path::new("/repo/data/rates.csv"), &paths));
+        assert!(!is_listed(root, Path::new("/repo/src/main.rs"), &paths));
+        assert!(!is_listed(root, Path::new("/repo/gen/api.pb.go"), &[]));
+
+        assert_eq!(truncate("héllo", 2), "h");
+        assert_eq!(truncate("héllo", 3), "hé");
+        assert_eq!(truncate("hi", 16), "hi");
+    }
+}
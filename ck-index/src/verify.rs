@@ -0,0 +1,239 @@
+//! `ck --verify`: find chunks whose embeddings came out degenerate (all
+//! zeros, NaN, the wrong length, or no different from the model's output on
+//! empty input) and, with `--reembed`, embed them again. Such vectors are
+//! stored as they came, so a failure during a build is not lost but can be
+//! found and repaired without rebuilding the whole index.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use super::{
+    IndexManifest, Result, drift, encryption, generation, get_sidecar_path, index_single_file,
+    legacy_model_config, load_index_entry, load_or_create_manifest, normalize_manifest_paths,
+    path_utils, save_index_entry, save_manifest,
+};
+
+/// A chunk with a degenerate embedding.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlaggedChunk {
+    /// Relative to the index root
+    pub path: String,
+    pub line_start: usize,
+    pub line_end: usize,
+    pub anomaly: ck_embed::VectorAnomaly,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerifyReport {
+    /// Embedded chunks checked
+    pub chunks: usize,
+    /// Whether chunks were compared with the model's embedding of empty
+    /// input; false when the model could not be loaded
+    pub padding_checked: bool,
+    /// Ordered by path and line
+    pub flagged: Vec<FlaggedChunk>,
+    /// Files re-embedded with `reembed`
+    pub files_reembedded: usize,
+    /// Flagged chunks still degenerate after re-embedding
+    pub remaining: usize,
+}
+
+/// Check every embedding of the index rooted at `path`. With `reembed`, the
+/// files holding flagged chunks are indexed again, with those chunks
+/// embedded afresh rather than taken from the chunk cache.
+pub fn verify_index(path: &Path, reembed: bool) -> Result<VerifyReport> {
+    let index_dir = path.join(".ck");
+    let manifest_path = index_dir.join("manifest.json");
+    let mut manifest = load_or_create_manifest(&manifest_path)?;
+    normalize_manifest_paths(&mut manifest, path);
+    encryption::prepare(path, &manifest)?;
+
+    let model_registry = ck_models::ModelRegistry::for_project(path);
+    let config = if let Some(existing) = manifest.embedding_model.as_deref() {
+        match model_registry.resolve(Some(existing)) {
+            Ok((_, config)) => config,
+            Err(_) => legacy_model_config(existing, manifest.embedding_dimensions),
+        }
+    } else {
+        model_registry.resolve(None)?.1
+    };
+    let embedder = match ck_embed::create_embedder_for_config(&config, None) {
+        Ok(embedder) => Some(embedder),
+        Err(e) if !reembed => {
+            tracing::warn!("Cannot load '{}' to check for padding: {}", config.name, e);
+            None
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let dim = manifest
+        .embedding_dimensions
+        .or_else(|| embedder.as_ref().map(|embedder| embedder.dim()))
+        .unwrap_or(config.dimensions);
+    verify_with(path, manifest, embedder, dim, reembed)
+}
+
+/// [`verify_index`] with the index's `manifest`, its embedder (`None` when it
+/// could not be loaded) and its dimensions.
+fn verify_with(
+    path: &Path,
+    mut manifest: IndexManifest,
+    mut embedder: Option<Box<dyn ck_embed::Embedder>>,
+    dim: usize,
+    reembed: bool,
+) -> Result<VerifyReport> {
+    let padding = embedder.as_mut().and_then(|embedder| {
+        embedder
+            .embed(&[String::new()])
+            .ok()
+            .and_then(|embeddings| embeddings.into_iter().next())
+    });
+
+    let mut report = VerifyReport {
+        padding_checked: padding.is_some(),
+        ..Default::default()
+    };
+    let mut files: Vec<PathBuf> = manifest.files.keys().cloned().collect();
+    files.sort();
+    let mut to_reembed = Vec::new();
+    for key in files {
+        let relative = path_utils::from_manifest_path(&key);
+        let file = path.join(&relative);
+        let sidecar = get_sidecar_path(path, &file);
+        let Ok(mut entry) = load_index_entry(&sidecar) else {
+            continue;
+        };
+        let mut flagged = 0;
+        for chunk in &mut entry.chunks {
+            let Some(embedding) = &chunk.embedding else {
+                continue;
+            };
+            report.chunks += 1;
+            let Some(anomaly) = ck_embed::vector_anomaly(embedding, dim, padding.as_deref()) else {
+                continue;
+            };
+            report.flagged.push(FlaggedChunk {
+                path: ck_core::paths::to_slash(&relative),
+                line_start: chunk.span.line_start,
+                line_end: chunk.span.line_end,
+                anomaly,
+            });
+            // Without its hash the chunk misses the cache and is embedded again
+            chunk.embedding = None;
+            chunk.chunk_hash = None;
+            flagged += 1;
+        }
+        if flagged > 0 {
+            to_reembed.push((file, sidecar, entry));
+        }
+    }
+
+    let Some(embedder) = embedder.as_mut().filter(|_| reembed) else {
+        report.remaining = report.flagged.len();
+        return Ok(report);
+    };
+    if to_reembed.is_empty() {
+        return Ok(report);
+    }
+    let _writing = generation::begin_write(path);
+    drift::check_manifest(path, &manifest)?;
+    for (file, sidecar, entry) in to_reembed {
+        save_index_entry(&sidecar, &entry)?;
+        let entry = index_single_file(&file, path, Some(&mut *embedder))?;
+        report.remaining += entry
+            .chunks
+            .iter()
+            .filter_map(|chunk| chunk.embedding.as_deref())
+            .filter(|embedding| {
+                ck_embed::vector_anomaly(embedding, dim, padding.as_deref()).is_some()
+            })
+            .count();
+        save_index_entry(&sidecar, &entry)?;
+        manifest
+            .files
+            .insert(entry.metadata.path.clone(), entry.metadata);
+        report.files_reembedded += 1;
+    }
+    manifest.touch();
+    save_manifest(&path.join(".ck").join("manifest.json"), &manifest)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Embeds a text as its length followed by constants, so that chunks
+    /// differ from the embedding of empty input
+    struct StubEmbedder;
+
+    impl ck_embed::Embedder for StubEmbedder {
+        fn id(&self) -> &'static str {
+            "stub"
+        }
+
+        fn dim(&self) -> usize {
+            4
+        }
+
+        fn model_name(&self) -> &str {
+            "stub"
+        }
+
+        fn embed(&mut self, texts: &[String]) -> ck_embed::Result<Vec<Vec<f32>>> {
+            Ok(texts
+                .iter()
+                .map(|text| vec![text.len() as f32 + 1.0, 1.0, 2.0, 3.0])
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_verify_flags_degenerate_embeddings() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let mut embedder: Box<dyn ck_embed::Embedder> = Box::new(StubEmbedder);
+        let mut manifest = IndexManifest {
+            embedding_model: Some("stub".to_string()),
+            embedding_dimensions: Some(4),
+            ..Default::default()
+        };
+        for (name, code) in [
+            ("a.rs", "fn alpha() -> u32 {\n    1\n}\n"),
+            ("b.rs", "fn beta() -> u32 {\n    2\n}\n"),
+        ] {
+            let file = root.join(name);
+            fs::write(&file, code).unwrap();
+            let entry = index_single_file(&file, root, Some(&mut embedder)).unwrap();
+            save_index_entry(&get_sidecar_path(root, &file), &entry).unwrap();
+            manifest
+                .files
+                .insert(entry.metadata.path.clone(), entry.metadata);
+        }
+        save_manifest(&root.join(".ck/manifest.json"), &manifest).unwrap();
+
+        // Give b.rs a broken vector
+        let sidecar = get_sidecar_path(root, &root.join("b.rs"));
+        let mut entry = load_index_entry(&sidecar).unwrap();
+        entry.chunks[0].embedding = Some(vec![f32::NAN; 4]);
+        save_index_entry(&sidecar, &entry).unwrap();
+
+        let report = verify_with(root, manifest.clone(), Some(embedder), 4, false).unwrap();
+        assert!(report.padding_checked);
+        assert!(report.chunks >= 2);
+        assert_eq!(report.flagged.len(), 1);
+        assert_eq!(report.flagged[0].path, "b.rs");
+        assert_eq!(
+            report.flagged[0].anomaly,
+            ck_embed::VectorAnomaly::NonFinite
+        );
+        assert_eq!(report.remaining, 1);
+
+        let report = verify_with(root, manifest, Some(Box::new(StubEmbedder)), 4, true).unwrap();
+        assert_eq!(report.files_reembedded, 1);
+        assert_eq!(report.remaining, 0);
+        let entry = load_index_entry(&sidecar).unwrap();
+        assert!(entry.chunks[0].embedding.as_ref().unwrap()[0].is_finite());
+    }
+}
@@ -0,0 +1,29 @@
+use thiserror::Error;
+
+/// Failures from the model registries and project configuration.
+#[derive(Debug, Error)]
+pub enum ModelError {
+    #[error("Unknown model '{name}'. Available models: {available}")]
+    ModelNotFound { name: String, available: String },
+
+    #[error("Unknown rerank model '{name}'. Available models: {available}")]
+    RerankModelNotFound { name: String, available: String },
+
+    #[error("No default model configured in registry")]
+    NoDefaultModel,
+
+    #[error("No default reranking model configured")]
+    NoDefaultRerankModel,
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid configuration: {0}")]
+    Config(#[from] serde_json::Error),
+
+    /// A config file that does not load, located at the offending key.
+    #[error("Invalid configuration: {0}")]
+    InvalidFile(Box<crate::ConfigIssue>),
+}
+
+pub type Result<T> = std::result::Result<T, ModelError>;
@@ -1,15 +1,351 @@
-use anyhow::{Result, anyhow};
+use ck_core::redact::RedactionConfig;
+use ck_core::{ChunkStrategy, ScoreTransform};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+mod error;
+mod validate;
+pub use error::{ModelError, Result};
+pub use validate::{ConfigIssue, Severity, validate_file};
+
+/// File name of the shareable per-project configuration, discovered by walking
+/// up from the search path (it lives next to `.ckignore`, not inside `.ck/`).
+pub const PROJECT_CONFIG_FILE: &str = ".ckconfig.json";
+
+/// Default embedding model for projects with `multilingual` enabled.
+pub const MULTILINGUAL_MODEL: &str = "multilingual-e5";
+
+/// Per-user registry of embedding models added with `ck --add-model`, stored in
+/// the ck config directory and merged over the built-in models.
+pub const USER_MODELS_FILE: &str = "models.json";
+
+/// Per-user settings ([`UserConfig`]) in the ck config directory.
+pub const USER_CONFIG_FILE: &str = "config.json";
+
+/// Set to `1` to let a project's `.ckconfig.json` run its `query_rewriter`,
+/// `metadata_command` and `history.command`, and choose the `llm` queries and API keys go to.
+/// Off by default, since anyone who can commit to a repository can write
+/// that file.
+pub const TRUST_PROJECT_ENV: &str = "CK_TRUST_PROJECT";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
     pub name: String,
     pub provider: String,
+    /// Embedding width; `0` (or omitted) means detect it from the model when the
+    /// embedder loads
+    #[serde(default)]
     pub dimensions: usize,
     pub max_tokens: usize,
+    /// Which part of an input longer than `max_tokens` is embedded
+    #[serde(default)]
+    pub truncation: Truncation,
     pub description: String,
+    /// Where a user-registered `onnx` model's files come from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onnx: Option<OnnxSource>,
+    /// Scale embeddings to unit length. Set from the index's metric (see
+    /// [`SimilarityMetric::normalizes`]) rather than read from the registry.
+    #[serde(skip, default = "normalize_by_default")]
+    pub normalize: bool,
+    /// Fail when the model returns embeddings of another width than
+    /// `dimensions`; `false` pads or cuts them to fit instead
+    #[serde(
+        default = "strict_dimensions_by_default",
+        skip_serializing_if = "is_strict_dimensions"
+    )]
+    pub strict_dimensions: bool,
+    /// Task description put before queries (not documents) for
+    /// instruction-tuned models, e.g. `search_query` or `Represent this
+    /// sentence for searching relevant passages`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_instruction: Option<String>,
+    /// Task description put before documents (chunks) at index time, e.g.
+    /// `passage` for E5 models or `search_document` for nomic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_instruction: Option<String>,
+    /// Transport settings for models served over HTTP (`openai` endpoints,
+    /// or the `llama-server` ck starts)
+    #[serde(default, skip_serializing_if = "RemoteConfig::is_unset")]
+    pub remote: RemoteConfig,
+}
+
+fn normalize_by_default() -> bool {
+    true
+}
+
+fn strict_dimensions_by_default() -> bool {
+    true
+}
+
+fn is_strict_dimensions(strict: &bool) -> bool {
+    *strict
+}
+
+impl ModelConfig {
+    /// `query` as this model embeds it: after `instruction` when one is given,
+    /// else after the model's own `query_instruction`, as `<instruction>: <query>`.
+    /// An empty instruction embeds the query bare.
+    pub fn instructed_query(&self, query: &str, instruction: Option<&str>) -> String {
+        let instruction = instruction
+            .or(self.query_instruction.as_deref())
+            .map(|instruction| instruction.trim().trim_end_matches(':').trim_end())
+            .unwrap_or_default();
+        if instruction.is_empty() {
+            query.to_string()
+        } else {
+            format!("{}: {}", instruction, query)
+        }
+    }
+
+    /// `text` as this model embeds a document: after `document_instruction`,
+    /// when there is one, as `<instruction>: <text>`.
+    pub fn instructed_document(&self, text: &str) -> String {
+        match self
+            .document_instruction
+            .as_deref()
+            .map(|instruction| instruction.trim().trim_end_matches(':').trim_end())
+        {
+            Some(instruction) if !instruction.is_empty() => format!("{}: {}", instruction, text),
+            _ => text.to_string(),
+        }
+    }
+
+    /// This model configured for an index compared with `metric`.
+    pub fn for_metric(self, metric: SimilarityMetric) -> Self {
+        Self {
+            normalize: metric.normalizes(),
+            ..self
+        }
+    }
+}
+
+/// How embedding requests reach an HTTP endpoint. Inputs are sent in batches
+/// of `batch_size`, at most `max_concurrency` requests at a time per endpoint
+/// across the whole process. Requests failing with a network error, 408, 429
+/// or 5xx are retried with exponential backoff, waiting out `Retry-After` and
+/// exhausted `x-ratelimit-*` budgets first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// Base URL of an `openai` provider's endpoint, such as
+    /// `https://api.openai.com` or a `llama-server` already running; requests
+    /// go to its `/v1/embeddings`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub batch_size: usize,
+    pub max_concurrency: usize,
+    pub max_retries: u32,
+    /// Request body compression; the endpoint must accept the encoding
+    pub compression: Compression,
+    /// Environment variable holding a bearer token, never the token itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    /// Price in USD per million input tokens; set for paid endpoints so index
+    /// builds estimate their cost first and record what they spent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost_per_million_tokens: Option<f64>,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            batch_size: 64,
+            max_concurrency: 4,
+            max_retries: 5,
+            compression: Compression::None,
+            api_key_env: None,
+            cost_per_million_tokens: None,
+        }
+    }
+}
+
+impl RemoteConfig {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// USD for `tokens` input tokens, when the endpoint is priced.
+    pub fn cost_of(&self, tokens: u64) -> Option<f64> {
+        self.cost_per_million_tokens
+            .map(|price| tokens as f64 * price / 1_000_000.0)
+    }
+}
+
+/// `Content-Encoding` of request bodies sent to an HTTP embedder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Model and tokenizer files for the `onnx` provider. Each is a local path or a
+/// Hugging Face reference `owner/repo[/file]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OnnxSource {
+    pub model: String,
+    /// Defaults to `tokenizer.json` beside a local model, or in the model's repo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokenizer: Option<String>,
+    /// Unset follows the model's sentence-transformers `1_Pooling/config.json`,
+    /// and mean pooling without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pooling: Option<Pooling>,
+}
+
+/// How per-token hidden states become one embedding, for graphs that output
+/// `[batch, tokens, dims]`. Graphs with a pooled `[batch, dims]` output ignore it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pooling {
+    /// Average over non-padding tokens
+    #[default]
+    Mean,
+    /// First token (`[CLS]`)
+    Cls,
+}
+
+impl std::str::FromStr for Pooling {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mean" => Ok(Self::Mean),
+            "cls" => Ok(Self::Cls),
+            other => Err(format!("Unknown pooling '{other}'; use mean or cls")),
+        }
+    }
+}
+
+impl std::fmt::Display for Pooling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Mean => "mean",
+            Self::Cls => "cls",
+        })
+    }
+}
+
+/// Which tokens of an over-long input an embedding model sees. Leading and
+/// trailing special tokens are kept either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Truncation {
+    /// Keep the start and drop the end
+    #[default]
+    Head,
+    /// Keep the end and drop the start
+    Tail,
+    /// Keep both ends and drop the middle
+    Middle,
+}
+
+impl std::str::FromStr for Truncation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "head" => Ok(Self::Head),
+            "tail" => Ok(Self::Tail),
+            "middle" => Ok(Self::Middle),
+            other => Err(format!(
+                "Unknown truncation '{other}'; use head, tail or middle"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Truncation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Head => "head",
+            Self::Tail => "tail",
+            Self::Middle => "middle",
+        })
+    }
+}
+
+/// How semantic search compares embeddings, fixed when an index is created.
+/// Only `cosine` stores unit-length vectors; the others keep the magnitudes the
+/// model produces, which some models (and Matryoshka-truncated embeddings) rank
+/// better with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SimilarityMetric {
+    /// Angle between the vectors; magnitudes are ignored
+    #[default]
+    Cosine,
+    /// Raw inner product
+    Dot,
+    /// Straight-line distance, scored as `1 / (1 + distance)`
+    Euclidean,
+}
+
+impl SimilarityMetric {
+    /// Whether embeddings for this metric are scaled to unit length.
+    pub fn normalizes(self) -> bool {
+        self == Self::Cosine
+    }
+
+    /// Similarity of `a` and `b`, higher is closer. Vectors of different
+    /// widths score 0.
+    pub fn similarity(self, a: &[f32], b: &[f32]) -> f32 {
+        if a.len() != b.len() {
+            return 0.0;
+        }
+        let dot = || a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+        match self {
+            Self::Cosine => {
+                let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+                if norm_a == 0.0 || norm_b == 0.0 {
+                    0.0
+                } else {
+                    dot() / (norm_a * norm_b)
+                }
+            }
+            Self::Dot => dot(),
+            Self::Euclidean => {
+                let distance = a
+                    .iter()
+                    .zip(b)
+                    .map(|(x, y)| (x - y) * (x - y))
+                    .sum::<f32>()
+                    .sqrt();
+                1.0 / (1.0 + distance)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for SimilarityMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cosine" => Ok(Self::Cosine),
+            "dot" | "inner-product" => Ok(Self::Dot),
+            "euclidean" | "l2" => Ok(Self::Euclidean),
+            other => Err(format!(
+                "Unknown metric '{other}'; use cosine, dot or euclidean"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for SimilarityMetric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Cosine => "cosine",
+            Self::Dot => "dot",
+            Self::Euclidean => "euclidean",
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,7 +365,14 @@ impl Default for ModelRegistry {
                 provider: "fastembed".to_string(),
                 dimensions: 384,
                 max_tokens: 512,
+                truncation: Truncation::Head,
                 description: "Small, fast English embedding model".to_string(),
+                onnx: None,
+                normalize: true,
+                strict_dimensions: true,
+                query_instruction: None,
+                document_instruction: None,
+                remote: RemoteConfig::default(),
             },
         );
 
@@ -40,7 +383,14 @@ impl Default for ModelRegistry {
                 provider: "fastembed".to_string(),
                 dimensions: 384,
                 max_tokens: 256,
+                truncation: Truncation::Head,
                 description: "Lightweight English embedding model".to_string(),
+                onnx: None,
+                normalize: true,
+                strict_dimensions: true,
+                query_instruction: None,
+                document_instruction: None,
+                remote: RemoteConfig::default(),
             },
         );
 
@@ -52,8 +402,15 @@ impl Default for ModelRegistry {
                 provider: "fastembed".to_string(),
                 dimensions: 768,
                 max_tokens: 8192,
+                truncation: Truncation::Head,
                 description: "High-quality English embedding model with large context window"
                     .to_string(),
+                onnx: None,
+                normalize: true,
+                strict_dimensions: true,
+                query_instruction: None,
+                document_instruction: None,
+                remote: RemoteConfig::default(),
             },
         );
 
@@ -64,8 +421,15 @@ impl Default for ModelRegistry {
                 provider: "fastembed".to_string(),
                 dimensions: 768,
                 max_tokens: 8192,
+                truncation: Truncation::Head,
                 description: "Code-specific embedding model optimized for programming tasks"
                     .to_string(),
+                onnx: None,
+                normalize: true,
+                strict_dimensions: true,
+                query_instruction: None,
+                document_instruction: None,
+                remote: RemoteConfig::default(),
             },
         );
 
@@ -76,7 +440,53 @@ impl Default for ModelRegistry {
                 provider: "mixedbread".to_string(),
                 dimensions: 384,
                 max_tokens: 4096,
+                truncation: Truncation::Head,
                 description: "Mixedbread xsmall embedding model (4k context, 384 dims) optimized for local semantic search".to_string(),
+                onnx: None,
+                normalize: true,
+                strict_dimensions: true,
+                query_instruction: None,
+                document_instruction: None,
+                remote: RemoteConfig::default(),
+            },
+        );
+
+        models.insert(
+            "bge-m3".to_string(),
+            ModelConfig {
+                name: "BAAI/bge-m3".to_string(),
+                provider: "fastembed".to_string(),
+                dimensions: 1024,
+                max_tokens: 8192,
+                truncation: Truncation::Head,
+                description: "Multilingual model covering 100+ languages with an 8K context window"
+                    .to_string(),
+                onnx: None,
+                normalize: true,
+                strict_dimensions: true,
+                query_instruction: None,
+                document_instruction: None,
+                remote: RemoteConfig::default(),
+            },
+        );
+
+        models.insert(
+            MULTILINGUAL_MODEL.to_string(),
+            ModelConfig {
+                name: "intfloat/multilingual-e5-small".to_string(),
+                provider: "fastembed".to_string(),
+                dimensions: 384,
+                max_tokens: 512,
+                truncation: Truncation::Head,
+                description:
+                    "Small multilingual E5 model; the default for projects with multilingual enabled"
+                        .to_string(),
+                onnx: None,
+                normalize: true,
+                strict_dimensions: true,
+                query_instruction: Some("query".to_string()),
+                document_instruction: Some("passage".to_string()),
+                remote: RemoteConfig::default(),
             },
         );
 
@@ -106,13 +516,12 @@ impl ModelRegistry {
     pub fn resolve(&self, requested: Option<&str>) -> Result<(String, ModelConfig)> {
         match requested {
             Some(name) => {
-                let (alias, config) = self.resolve_alias_or_name(name).ok_or_else(|| {
-                    anyhow!(
-                        "Unknown model '{}'. Available models: {}",
-                        name,
-                        self.format_available_models()
-                    )
-                })?;
+                let (alias, config) =
+                    self.resolve_alias_or_name(name)
+                        .ok_or_else(|| ModelError::ModelNotFound {
+                            name: name.to_string(),
+                            available: self.format_available_models(),
+                        })?;
                 Ok((alias, config.clone()))
             }
             None => {
@@ -120,7 +529,7 @@ impl ModelRegistry {
                 let config = self
                     .get_default_model()
                     .cloned()
-                    .ok_or_else(|| anyhow!("No default model configured in registry"))?;
+                    .ok_or(ModelError::NoDefaultModel)?;
                 Ok((alias, config))
             }
         }
@@ -135,7 +544,7 @@ impl ModelRegistry {
     pub fn load(path: &Path) -> Result<Self> {
         if path.exists() {
             let data = std::fs::read_to_string(path)?;
-            Ok(serde_json::from_str(&data)?)
+            validate::parse(path, &data)
         } else {
             Ok(Self::default())
         }
@@ -147,6 +556,40 @@ impl ModelRegistry {
         Ok(())
     }
 
+    /// Built-in models plus those in the user registry. A missing or unreadable
+    /// user registry leaves just the built-ins; user entries never replace them.
+    pub fn with_user_models() -> Self {
+        let mut registry = Self::default();
+        for (alias, config) in UserModels::for_user().models {
+            registry.models.entry(alias).or_insert(config);
+        }
+        registry
+    }
+
+    /// [`Self::with_user_models`], defaulting to the `model` of the project
+    /// governing `path`, or to [`MULTILINGUAL_MODEL`] when it has `multilingual`
+    /// enabled instead. The project's `truncation`, when set, replaces every
+    /// model's own.
+    pub fn for_project(path: &Path) -> Self {
+        let mut registry = Self::with_user_models();
+        let project = ProjectConfig::for_path(path);
+        if let Some(truncation) = project.truncation {
+            for config in registry.models.values_mut() {
+                config.truncation = truncation;
+            }
+        }
+        if let Some((alias, _)) = project
+            .model
+            .as_deref()
+            .and_then(|model| registry.resolve_alias_or_name(model))
+        {
+            registry.default_model = alias;
+        } else if project.multilingual {
+            registry.default_model = MULTILINGUAL_MODEL.to_string();
+        }
+        registry
+    }
+
     pub fn get_model(&self, name: &str) -> Option<&ModelConfig> {
         self.models.get(name)
     }
@@ -156,11 +599,123 @@ impl ModelRegistry {
     }
 }
 
+/// Models registered by the user, keyed by alias.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserModels {
+    pub models: BTreeMap<String, ModelConfig>,
+    /// Chunker plugins (`plugin:/path/to/program`) by file extension or
+    /// language name, replacing ck's own splitting for those files. Kept
+    /// here rather than in `.ckconfig.json`, so that a repository cannot
+    /// name a program for ck to run.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub chunkers: BTreeMap<String, String>,
+}
+
+impl UserModels {
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let data = std::fs::read_to_string(path)?;
+            validate::parse(path, &data)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// The user's registry; empty when the file is missing or unreadable.
+    pub fn for_user() -> Self {
+        user_models_path()
+            .and_then(|path| Self::load(&path).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// The ck config directory: `$XDG_CONFIG_HOME/ck`, falling back to
+/// `~/.config/ck` and then `%APPDATA%\ck`.
+fn user_config_dir() -> Option<PathBuf> {
+    let base = if let Some(config_home) = std::env::var_os("XDG_CONFIG_HOME") {
+        PathBuf::from(config_home)
+    } else if let Some(home) = std::env::var_os("HOME") {
+        PathBuf::from(home).join(".config")
+    } else {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    };
+    Some(base.join("ck"))
+}
+
+/// Location of the user model registry: `$XDG_CONFIG_HOME/ck/models.json`.
+pub fn user_models_path() -> Option<PathBuf> {
+    Some(user_config_dir()?.join(USER_MODELS_FILE))
+}
+
+/// Location of the user settings: `$XDG_CONFIG_HOME/ck/config.json`.
+pub fn user_config_path() -> Option<PathBuf> {
+    Some(user_config_dir()?.join(USER_CONFIG_FILE))
+}
+
+/// Whether [`TRUST_PROJECT_ENV`] is set to `1`.
+pub fn project_trusted() -> bool {
+    std::env::var(TRUST_PROJECT_ENV).is_ok_and(|value| value.trim() == "1")
+}
+
+/// Settings that run programs, kept per user so that cloning a repository
+/// and searching it never runs anything the repository chose. The same keys
+/// in a project's `.ckconfig.json` apply only with [`TRUST_PROJECT_ENV`] set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_rewriter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_command: Option<String>,
+    #[serde(skip_serializing_if = "LlmConfig::is_unset")]
+    pub llm: LlmConfig,
+}
+
+impl UserConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let data = std::fs::read_to_string(path)?;
+            validate::parse(path, &data)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// The user's settings; none when the file is missing or unreadable.
+    pub fn for_user() -> Self {
+        user_config_path()
+            .and_then(|path| Self::load(&path).ok())
+            .unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RerankModelConfig {
     pub name: String,
     pub provider: String,
     pub description: String,
+    /// How raw logits become scores
+    #[serde(default)]
+    pub score_transform: ScoreTransform,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -181,6 +736,7 @@ impl Default for RerankModelRegistry {
                 description:
                     "Jina Turbo reranker (default) tuned for English code + text relevance"
                         .to_string(),
+                score_transform: ScoreTransform::Sigmoid,
             },
         );
 
@@ -190,6 +746,7 @@ impl Default for RerankModelRegistry {
                 name: "BAAI/bge-reranker-base".to_string(),
                 provider: "fastembed".to_string(),
                 description: "BGE reranker base model for multilingual use cases".to_string(),
+                score_transform: ScoreTransform::Sigmoid,
             },
         );
 
@@ -200,6 +757,7 @@ impl Default for RerankModelRegistry {
                 provider: "mixedbread".to_string(),
                 description: "Mixedbread xsmall reranker (quantized) optimized for local inference"
                     .to_string(),
+                score_transform: ScoreTransform::Sigmoid,
             },
         );
 
@@ -230,11 +788,10 @@ impl RerankModelRegistry {
         match requested {
             Some(name) => {
                 let (alias, config) = self.resolve_alias_or_name(name).ok_or_else(|| {
-                    anyhow!(
-                        "Unknown rerank model '{}'. Available models: {}",
-                        name,
-                        self.format_available_models()
-                    )
+                    ModelError::RerankModelNotFound {
+                        name: name.to_string(),
+                        available: self.format_available_models(),
+                    }
                 })?;
                 Ok((alias, config.clone()))
             }
@@ -244,7 +801,7 @@ impl RerankModelRegistry {
                     .models
                     .get(&self.default_model)
                     .cloned()
-                    .ok_or_else(|| anyhow!("No default reranking model configured"))?;
+                    .ok_or(ModelError::NoDefaultRerankModel)?;
                 Ok((alias, config))
             }
         }
@@ -258,29 +815,458 @@ impl RerankModelRegistry {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ProjectConfig {
-    pub model: String,
+    /// Embedding model for this project. An index built with another model is
+    /// refused, not mixed into, until it is rebuilt with `ck --switch-model`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Which part of an over-long chunk is embedded, overriding the
+    /// `truncation` of whichever model the project uses. Applies to files
+    /// indexed after a change.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncation: Option<Truncation>,
     pub chunk_size: usize,
     pub chunk_overlap: usize,
     pub index_backend: String,
+    /// Metric new indexes compare embeddings with; an existing index keeps the
+    /// one recorded in its manifest until it is rebuilt.
+    pub metric: SimilarityMetric,
+    /// Prepend a `path :: scope :: symbol` line to each chunk before embedding.
+    pub chunk_context: bool,
+    /// How files are split into chunks; applies to files indexed after a change.
+    pub chunk_strategy: ChunkStrategy,
+    /// Tag chunks of generated code with the schema their header names
+    /// (`// source: api/user.proto`), and fold their hits into the schema's.
+    pub link_generated: bool,
+    /// Index git submodules checked out under the project, tagging their
+    /// chunks with the submodule's path and commit; otherwise walks skip them.
+    pub submodules: bool,
+    /// Non-English code and docs: new indexes default to a multilingual embedder,
+    /// and lexical search splits CJK text into characters instead of dropping
+    /// long unspaced runs.
+    pub multilingual: bool,
+    /// Shards (top-level directories) `ck --index` embeds at once, each with its
+    /// own model instance. Raising it trades memory for throughput on monorepos.
+    pub shard_workers: usize,
+    /// HNSW graph parameters, used when `index_backend` is `hnsw`.
+    pub hnsw: HnswConfig,
+    /// Post-retrieval score adjustments for ranked search modes.
+    pub boosts: RankingBoosts,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub presets: BTreeMap<String, QueryPreset>,
+    /// Count searches and index updates in `.ck/usage.json` for `ck --stats`.
+    /// Local only; nothing is sent anywhere.
+    pub usage_stats: bool,
+    /// Shell command the query is piped through before it is embedded (query
+    /// on stdin, embedding text on stdout), e.g. a script that expands it
+    /// with an LLM. Keyword matching keeps the query as typed. Run only with
+    /// [`TRUST_PROJECT_ENV`] set; see [`Self::trusted`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_rewriter: Option<String>,
+    /// Shell command run for each file indexed that prints key/value metadata
+    /// for the file and its chunks (see `.ckmeta.json` for static pairs). Run
+    /// only with [`TRUST_PROJECT_ENV`] set; see [`Self::trusted`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_command: Option<String>,
+    /// Chat model for `--hyde` and `--ask`. Used only with
+    /// [`TRUST_PROJECT_ENV`] set; see [`Self::trusted`].
+    #[serde(skip_serializing_if = "LlmConfig::is_unset")]
+    pub llm: LlmConfig,
+    #[serde(skip_serializing_if = "HydeConfig::is_unset")]
+    pub hyde: HydeConfig,
+    /// Store each file's mean chunk vector at `ck --index`, for `--top-files`
+    /// and `--by-file`. Without it they are averaged at query time.
+    pub file_vectors: bool,
+    /// Files `ck --index` leaves out: too large, too many chunks, or a
+    /// disabled extension.
+    #[serde(skip_serializing_if = "IndexLimits::is_unset")]
+    pub limits: IndexLimits,
+    /// Earlier index generations kept under `.ck/.snapshots` for `--as-of`;
+    /// 0 keeps none.
+    pub snapshots: usize,
+    /// Replace credentials in chunk text before it is embedded or stored in
+    /// the lexical index, and in snippets before they are printed.
+    #[serde(skip_serializing_if = "RedactionConfig::is_unset")]
+    pub redaction: RedactionConfig,
+    /// Files embedded through an LLM summary of each chunk instead of the
+    /// chunk itself, for text that embeds poorly. Needs `llm`.
+    #[serde(skip_serializing_if = "SummarizeConfig::is_unset")]
+    pub summarize: SummarizeConfig,
+    /// What `ck --index-history` indexes for `ck --history` searches. Its
+    /// `command` runs only with [`TRUST_PROJECT_ENV`] set.
+    #[serde(skip_serializing_if = "HistoryConfig::is_unset")]
+    pub history: HistoryConfig,
+    /// Maintenance `ck --serve` runs on a cron schedule while no requests
+    /// are coming in.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduledTask>,
+}
+
+/// Parameters of the HNSW graph semantic search uses on large indexes. `m` and
+/// `ef_construction` shape the graph and take effect at the next `ck --index`;
+/// `ef_search` and `oversample` apply to every query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HnswConfig {
+    pub m: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+    /// Graph candidates fetched per requested result and re-scored exactly,
+    /// so approximate ranking near the cutoff cannot drop a better chunk.
+    pub oversample: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+            oversample: 4,
+        }
+    }
+}
+
+/// The chat model behind `--hyde` and `--ask`. `endpoint` is the base URL of
+/// an OpenAI-compatible API (the part before `/chat/completions`); the API key
+/// is read from the environment variable named by `api_key_env`, never from
+/// this file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub api_key_env: String,
+}
+
+impl LlmConfig {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether both an endpoint and a model are configured.
+    pub fn is_configured(&self) -> bool {
+        self.endpoint.is_some() && self.model.is_some()
+    }
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            model: None,
+            api_key_env: "CK_LLM_API_KEY".to_string(),
+        }
+    }
+}
+
+/// How `--hyde` folds the drafted snippet into the query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HydeConfig {
+    /// Share of the hypothetical snippet's vector in the query vector; the
+    /// raw query keeps the rest.
+    pub weight: f32,
+}
+
+impl HydeConfig {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for HydeConfig {
+    fn default() -> Self {
+        Self { weight: 0.5 }
+    }
+}
+
+/// Opaque files (generated code, data tables) whose chunks are embedded as
+/// an LLM-written summary. Search results still point at the chunk's own
+/// lines; only the vector comes from the summary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SummarizeConfig {
+    /// Gitignore-style globs, relative to the project root, of the files to
+    /// summarize.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// Bytes of each chunk sent to the LLM; the rest is cut off.
+    pub max_input_bytes: usize,
+}
+
+impl SummarizeConfig {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for SummarizeConfig {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            max_input_bytes: 16 * 1024,
+        }
+    }
+}
+
+/// Project history searched with `ck --history`: commit messages, and
+/// pull request or issue text from a provider command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Most recent commits indexed.
+    pub max_commits: usize,
+    /// Shell command printing documents as a JSON array or JSON lines of
+    /// objects with `id` (or `number`), `title`, `body` and an optional
+    /// `url`, such as `gh pr list --state all --json number,title,body,url`.
+    /// Run only with [`TRUST_PROJECT_ENV`] set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+impl HistoryConfig {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_commits: 5000,
+            command: None,
+        }
+    }
+}
+
+/// A maintenance task and the times it is due, as a five-field cron
+/// expression (`minute hour day-of-month month day-of-week`, local time) or
+/// one of `@hourly`, `@daily`, `@weekly` and `@monthly`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledTask {
+    pub cron: String,
+    pub task: MaintenanceTask,
+}
+
+/// Work a long-running `ck --serve` can do on its own index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceTask {
+    /// Incremental `ck --index` and rebuild of the ANN index
+    Reindex,
+    /// `ck --compact`
+    Compact,
+    /// Drop cached search results and expired result sessions
+    PruneCache,
+}
+
+impl std::fmt::Display for MaintenanceTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Reindex => "reindex",
+            Self::Compact => "compact",
+            Self::PruneCache => "prune_cache",
+        })
+    }
+}
+
+/// Caps that keep oversized or unwanted files out of the semantic index, so
+/// one generated dump cannot dominate it. Regex and lexical search read files
+/// directly and still find skipped ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexLimits {
+    /// Skip files larger than this many bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<u64>,
+    /// Skip files that chunk into more than this many chunks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_chunks_per_file: Option<usize>,
+    /// Extensions (without the dot, any case) mapped to whether their files
+    /// are indexed; `false` skips them. Unlisted extensions are indexed.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub extensions: BTreeMap<String, bool>,
+}
+
+impl IndexLimits {
+    fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether `path`'s extension is left enabled.
+    pub fn extension_enabled(&self, path: &Path) -> bool {
+        let Some(extension) = path.extension().map(|e| e.to_string_lossy()) else {
+            return true;
+        };
+        self.extensions
+            .iter()
+            .find(|(listed, _)| {
+                listed
+                    .trim_start_matches('.')
+                    .eq_ignore_ascii_case(&extension)
+            })
+            .is_none_or(|(_, enabled)| *enabled)
+    }
+}
+
+/// Score multipliers applied after retrieval in ranked modes (semantic, lexical,
+/// hybrid, symbol). Regex results are unranked and left alone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankingBoosts {
+    /// Extra weight for a file modified just now, halving every
+    /// `recency_half_life_days`: `score *= 1 + weight * 0.5^(age / half_life)`.
+    /// `0.0` disables recency boosting.
+    pub recency_weight: f32,
+    pub recency_half_life_days: f32,
+    /// Glob rules matched against paths relative to the project root; every
+    /// matching rule multiplies the score by its weight.
+    pub paths: Vec<PathBoost>,
+    /// Rules matched against a result's git blame authors (case-insensitive
+    /// substring of name or email); results owned by a matching author are
+    /// multiplied by its weight. Non-empty rules turn on blame for every search.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<OwnerBoost>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathBoost {
+    pub glob: String,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OwnerBoost {
+    pub owner: String,
+    pub weight: f32,
+}
+
+impl PathBoost {
+    fn new(glob: &str, weight: f32) -> Self {
+        Self {
+            glob: glob.to_string(),
+            weight,
+        }
+    }
+}
+
+impl Default for RankingBoosts {
+    fn default() -> Self {
+        Self {
+            recency_weight: 0.0,
+            recency_half_life_days: 30.0,
+            paths: vec![
+                PathBoost::new("**/test/**", 0.9),
+                PathBoost::new("**/tests/**", 0.9),
+                PathBoost::new("**/vendor/**", 0.7),
+                PathBoost::new("**/third_party/**", 0.7),
+            ],
+            owners: Vec::new(),
+        }
+    }
+}
+
+/// A saved search, invoked as `ck @name`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryPreset {
+    pub query: String,
+    /// One of `regex`, `lex`, `sem`, `hybrid`, `sym`; `None` keeps the CLI default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Language names as accepted by `--lang`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f32>,
+    pub rerank: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_scores: Option<ScoreTransform>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_top: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_budget_ms: Option<u64>,
+    pub case_insensitive: bool,
+    pub whole_word: bool,
+    pub fixed_string: bool,
 }
 
 impl Default for ProjectConfig {
     fn default() -> Self {
         Self {
-            model: "bge-small".to_string(),
+            model: None,
+            truncation: None,
             chunk_size: 512,
             chunk_overlap: 128,
             index_backend: "hnsw".to_string(),
+            metric: SimilarityMetric::default(),
+            chunk_context: true,
+            chunk_strategy: ChunkStrategy::default(),
+            link_generated: false,
+            submodules: false,
+            multilingual: false,
+            shard_workers: 1,
+            hnsw: HnswConfig::default(),
+            boosts: RankingBoosts::default(),
+            presets: BTreeMap::new(),
+            usage_stats: true,
+            query_rewriter: None,
+            metadata_command: None,
+            llm: LlmConfig::default(),
+            hyde: HydeConfig::default(),
+            file_vectors: false,
+            limits: IndexLimits::default(),
+            snapshots: 0,
+            redaction: RedactionConfig::default(),
+            summarize: SummarizeConfig::default(),
+            history: HistoryConfig::default(),
+            schedule: Vec::new(),
         }
     }
 }
 
 impl ProjectConfig {
+    /// The commands this project may run: [`Self::trusted_with`] the
+    /// user's settings and [`project_trusted`].
+    pub fn trusted(&self) -> UserConfig {
+        self.trusted_with(UserConfig::for_user(), project_trusted())
+    }
+
+    /// The user's own settings, each filled in from this project's when it
+    /// is `trusted`.
+    pub fn trusted_with(&self, user: UserConfig, trusted: bool) -> UserConfig {
+        let project = |value: &Option<String>| value.clone().filter(|_| trusted);
+        UserConfig {
+            query_rewriter: user
+                .query_rewriter
+                .or_else(|| project(&self.query_rewriter)),
+            metadata_command: user
+                .metadata_command
+                .or_else(|| project(&self.metadata_command)),
+            llm: if user.llm.is_unset() && trusted {
+                self.llm.clone()
+            } else {
+                user.llm
+            },
+        }
+    }
+
     pub fn load(path: &Path) -> Result<Self> {
         if path.exists() {
             let data = std::fs::read_to_string(path)?;
-            Ok(serde_json::from_str(&data)?)
+            validate::parse(path, &data)
         } else {
             Ok(Self::default())
         }
@@ -291,4 +1277,48 @@ impl ProjectConfig {
         std::fs::write(path, data)?;
         Ok(())
     }
+
+    /// Config governing `start`: the nearest `.ckconfig.json` above it, or the
+    /// defaults when there is none or it cannot be parsed.
+    pub fn for_path(start: &Path) -> Self {
+        Self::find(start)
+            .and_then(|path| Self::load(&path).ok())
+            .unwrap_or_default()
+    }
+
+    /// Walk up from `start` to the nearest `.ckconfig.json`.
+    pub fn find(start: &Path) -> Option<PathBuf> {
+        let start = start.canonicalize().unwrap_or_else(|_| start.to_path_buf());
+        let mut current = if start.is_file() {
+            start.parent()?
+        } else {
+            &start
+        };
+        loop {
+            let candidate = current.join(PROJECT_CONFIG_FILE);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+            current = current.parent()?;
+        }
+    }
+
+    /// Where the config for `start` should be read from or written to: an existing
+    /// `.ckconfig.json` above it, else the nearest directory holding `.ck` or `.git`,
+    /// else `start` itself.
+    pub fn locate(start: &Path) -> PathBuf {
+        if let Some(existing) = Self::find(start) {
+            return existing;
+        }
+        let start = start.canonicalize().unwrap_or_else(|_| start.to_path_buf());
+        let base = if start.is_file() {
+            start.parent().unwrap_or(&start)
+        } else {
+            &start
+        };
+        base.ancestors()
+            .find(|dir| dir.join(".ck").is_dir() || dir.join(".git").exists())
+            .unwrap_or(base)
+            .join(PROJECT_CONFIG_FILE)
+    }
 }
@@ -0,0 +1,367 @@
+//! Checks of `.ckconfig.json` and the model registries that point at the
+//! offending key: its file, line and column, and its path in the document
+//! (`hnsw.ef_search`, `models.mini.pooling`).
+//!
+//! The config types themselves are the schema. A type error, invalid enum
+//! value or missing field is serde's message placed at the key whose value
+//! it arose in. A key is unknown when the types ignore it, found by
+//! replacing its value with one no field accepts and seeing the document
+//! still load. Unknown keys are warnings, since they are usually typos but
+//! may belong to a newer ck.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+
+/// How much a [`ConfigIssue`] matters: errors stop the file from loading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One problem in a config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub file: PathBuf,
+    /// 1-based, like editors count
+    pub line: usize,
+    pub column: usize,
+    /// Path of the key in the document, when the problem is inside one
+    pub key: Option<String>,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: ", self.file.display(), self.line, self.column)?;
+        if let Some(key) = &self.key {
+            write!(f, "{key}: ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Problems in the config file at `path` read as a `T`, errors first. A
+/// missing file has none.
+pub fn validate_file<T: DeserializeOwned>(path: &Path) -> Result<Vec<ConfigIssue>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(path)?;
+    Ok(validate::<T>(path, &text))
+}
+
+/// Load the config file `text`, read from `path`, as a `T`, placing any
+/// error at the key it arose in.
+pub(crate) fn parse<T: DeserializeOwned>(path: &Path, text: &str) -> Result<T> {
+    serde_json::from_str(text)
+        .map_err(|error| crate::ModelError::InvalidFile(Box::new(locate(path, text, &error))))
+}
+
+fn validate<T: DeserializeOwned>(path: &Path, text: &str) -> Vec<ConfigIssue> {
+    if let Err(error) = serde_json::from_str::<T>(text) {
+        return vec![locate(path, text, &error)];
+    }
+    let Ok(root) = serde_json::from_str::<Value>(text) else {
+        return Vec::new();
+    };
+    let keys = Scanner::new(text).keys();
+    let mut unknown: Vec<&Key> = Vec::new();
+    for key in &keys {
+        if key.is_element || unknown.iter().any(|parent| key.is_below(parent)) {
+            continue;
+        }
+        let mut probe = root.clone();
+        if let Some(value) = probe.pointer_mut(&key.pointer) {
+            *value = serde_json::json!([[[]]]);
+        }
+        if serde_json::from_value::<T>(probe).is_ok() {
+            unknown.push(key);
+        }
+    }
+    unknown
+        .into_iter()
+        .map(|key| {
+            let (line, column) = position(text, key.key_offset);
+            ConfigIssue {
+                file: path.to_path_buf(),
+                line,
+                column,
+                key: Some(key.name.clone()),
+                message: "unknown key, ignored".to_string(),
+                severity: Severity::Warning,
+            }
+        })
+        .collect()
+}
+
+/// `error` from reading `text` as an issue at the innermost key around it.
+fn locate(path: &Path, text: &str, error: &serde_json::Error) -> ConfigIssue {
+    let message = error.to_string();
+    let message = match message.rfind(" at line ") {
+        Some(end) => message[..end].to_string(),
+        None => message,
+    };
+    let (line, column) = (error.line().max(1), error.column().max(1));
+    let key = if error.is_data() {
+        let offset = offset(text, line, column);
+        // Reported at the closing brace, which can touch the last value
+        let in_container = message.starts_with("missing field");
+        Scanner::new(text)
+            .keys()
+            .into_iter()
+            .filter(|key| key.value.0 <= offset && offset <= key.value.1)
+            .filter(|key| {
+                !in_container || matches!(text.as_bytes().get(key.value.0), Some(b'{' | b'['))
+            })
+            .min_by_key(|key| key.value.1 - key.value.0)
+            .map(|key| key.name)
+    } else {
+        None
+    };
+    ConfigIssue {
+        file: path.to_path_buf(),
+        line,
+        column,
+        key,
+        message,
+        severity: Severity::Error,
+    }
+}
+
+/// Byte offset of a 1-based line and column.
+fn offset(text: &str, line: usize, column: usize) -> usize {
+    let start: usize = text
+        .split_inclusive('\n')
+        .take(line - 1)
+        .map(str::len)
+        .sum();
+    (start + column - 1).min(text.len())
+}
+
+/// 1-based line and column of a byte offset.
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = offset - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+    (line, column)
+}
+
+/// A key or array element of a JSON document.
+struct Key {
+    /// Dotted path for messages, with `[i]` for array elements
+    name: String,
+    /// JSON pointer, for replacing the value
+    pointer: String,
+    is_element: bool,
+    key_offset: usize,
+    /// Byte range of the value, closing bracket included
+    value: (usize, usize),
+}
+
+impl Key {
+    fn is_below(&self, parent: &Key) -> bool {
+        self.pointer
+            .strip_prefix(&parent.pointer)
+            .is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+/// Finds where each key of a well-formed JSON document sits, which
+/// `serde_json::Value` does not keep.
+struct Scanner<'a> {
+    text: &'a [u8],
+    pos: usize,
+    keys: Vec<Key>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text: text.as_bytes(),
+            pos: 0,
+            keys: Vec::new(),
+        }
+    }
+
+    fn keys(mut self) -> Vec<Key> {
+        self.value("", "");
+        self.keys
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self, name: &str, pointer: &str) {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(name, pointer),
+            Some(b'[') => self.array(name, pointer),
+            Some(b'"') => {
+                self.string();
+            }
+            Some(_) => {
+                while self
+                    .peek()
+                    .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+                {
+                    self.pos += 1;
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Skip a string, returning its contents.
+    fn string(&mut self) -> String {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'\\' => self.pos += 1,
+                b'"' => break,
+                _ => {}
+            }
+        }
+        let raw = &self.text[start..self.pos.min(self.text.len())];
+        serde_json::from_slice(raw).unwrap_or_default()
+    }
+
+    fn object(&mut self, name: &str, pointer: &str) {
+        self.pos += 1;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b'"') => {}
+                Some(b'}') => {
+                    self.pos += 1;
+                    return;
+                }
+                Some(b',') => {
+                    self.pos += 1;
+                    continue;
+                }
+                _ => return,
+            }
+            let key_offset = self.pos;
+            let key = self.string();
+            self.skip_whitespace();
+            if self.peek() == Some(b':') {
+                self.pos += 1;
+            }
+            let child_name = if name.is_empty() {
+                key.clone()
+            } else {
+                format!("{name}.{key}")
+            };
+            let child_pointer = format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1"));
+            self.member(child_name, child_pointer, false, key_offset);
+        }
+    }
+
+    fn array(&mut self, name: &str, pointer: &str) {
+        self.pos += 1;
+        let mut index = 0;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b']') => {
+                    self.pos += 1;
+                    return;
+                }
+                Some(b',') => {
+                    self.pos += 1;
+                    continue;
+                }
+                Some(b'}') | None => return,
+                Some(_) => {}
+            }
+            let offset = self.pos;
+            self.member(
+                format!("{name}[{index}]"),
+                format!("{pointer}/{index}"),
+                true,
+                offset,
+            );
+            index += 1;
+        }
+    }
+
+    fn member(&mut self, name: String, pointer: String, is_element: bool, key_offset: usize) {
+        self.skip_whitespace();
+        let start = self.pos;
+        let slot = self.keys.len();
+        self.keys.push(Key {
+            name: name.clone(),
+            pointer: pointer.clone(),
+            is_element,
+            key_offset,
+            value: (start, start),
+        });
+        self.value(&name, &pointer);
+        self.keys[slot].value.1 = self.pos.max(start + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ProjectConfig, UserModels};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_issues_point_at_the_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = temp_dir.path().join(crate::PROJECT_CONFIG_FILE);
+
+        std::fs::write(
+            &config,
+            "{\n  \"snapshots\": 2,\n  \"hnsw\": {\n    \"ef_serch\": 80\n  },\n  \"metric\": \"cosine\"\n}\n",
+        )
+        .unwrap();
+        let issues = validate_file::<ProjectConfig>(&config).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Warning);
+        assert_eq!(issues[0].key.as_deref(), Some("hnsw.ef_serch"));
+        assert_eq!((issues[0].line, issues[0].column), (4, 5));
+
+        std::fs::write(
+            &config,
+            "{\n  \"snapshots\": 2,\n  \"metric\": \"cosin\"\n}\n",
+        )
+        .unwrap();
+        let issues = validate_file::<ProjectConfig>(&config).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(issues[0].key.as_deref(), Some("metric"));
+        assert_eq!(issues[0].line, 3);
+        assert!(issues[0].message.contains("cosin"), "{}", issues[0]);
+
+        let error = ProjectConfig::load(&config).unwrap_err().to_string();
+        assert!(error.contains(":3:"), "{error}");
+
+        let models = temp_dir.path().join(crate::USER_MODELS_FILE);
+        std::fs::write(
+            &models,
+            "{\n  \"models\": {\n    \"mini\": {\"name\": \"mini-lm\", \"provider\": \"onnx\"}\n  }\n}\n",
+        )
+        .unwrap();
+        let issues = validate_file::<UserModels>(&models).unwrap();
+        assert_eq!(issues[0].key.as_deref(), Some("models.mini"));
+        assert_eq!(issues[0].line, 3);
+        assert!(issues[0].message.contains("missing field `max_tokens`"));
+    }
+}
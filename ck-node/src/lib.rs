@@ -0,0 +1,138 @@
+//! Node.js bindings over the `ck_engine::CkIndex` library API, so VS Code
+//! extensions and JS agent frameworks can search in-process instead of spawning
+//! `ck` and parsing its output.
+//!
+//! ```js
+//! const { CkIndex } = require('ck-search-node');
+//! const index = CkIndex.open('/path/to/repo');
+//! const hits = await index.search('retry with backoff', { mode: 'semantic', topK: 5 });
+//! ```
+
+use ck_core::Language;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+fn to_napi(err: anyhow::Error) -> Error {
+    Error::from_reason(err.to_string())
+}
+
+/// Options for `CkIndex.search`; every field is optional.
+#[napi(object)]
+#[derive(Default)]
+pub struct SearchOptions {
+    /// `semantic` (default), `lexical`, `hybrid`, `regex` or `symbol`
+    pub mode: Option<String>,
+    pub top_k: Option<u32>,
+    pub threshold: Option<f64>,
+    /// Language names, aliases or extensions (`rust`, `py`, `ts`, ...)
+    pub languages: Option<Vec<String>>,
+    /// File or directory relative to the index root
+    pub scope: Option<String>,
+    pub rerank: Option<bool>,
+}
+
+#[napi(object)]
+pub struct Hit {
+    pub path: String,
+    pub line_start: u32,
+    pub line_end: u32,
+    pub score: f64,
+    pub snippet: String,
+    pub language: Option<String>,
+    pub symbol: Option<String>,
+    /// Other `path:line` locations of near-identical copies
+    pub duplicates: Vec<String>,
+}
+
+impl From<ck_engine::Hit> for Hit {
+    fn from(hit: ck_engine::Hit) -> Self {
+        Self {
+            path: hit.path.display().to_string(),
+            line_start: hit.span.line_start as u32,
+            line_end: hit.span.line_end as u32,
+            score: hit.score as f64,
+            snippet: hit.snippet,
+            language: hit.language.map(|l| l.to_string()),
+            symbol: hit.symbol,
+            duplicates: hit
+                .duplicates
+                .iter()
+                .map(|d| format!("{}:{}", d.file.display(), d.span.line_start))
+                .collect(),
+        }
+    }
+}
+
+fn build_query(text: String, options: SearchOptions) -> Result<ck_engine::Query> {
+    let mut query = match options.mode.as_deref().unwrap_or("semantic") {
+        "semantic" | "sem" => ck_engine::Query::semantic(text),
+        "lexical" | "lex" => ck_engine::Query::lexical(text),
+        "hybrid" => ck_engine::Query::hybrid(text),
+        "regex" => ck_engine::Query::regex(text),
+        "symbol" | "sym" => ck_engine::Query::symbol(text),
+        other => {
+            return Err(Error::new(
+                Status::InvalidArg,
+                format!(
+                    "Unknown mode '{}'. Expected semantic, lexical, hybrid, regex or symbol",
+                    other
+                ),
+            ));
+        }
+    };
+    if let Some(top_k) = options.top_k {
+        query = query.top_k(top_k as usize);
+    }
+    if let Some(threshold) = options.threshold {
+        query = query.threshold(threshold as f32);
+    }
+    for name in options.languages.unwrap_or_default() {
+        let language: Language = name
+            .parse()
+            .map_err(|e: String| Error::new(Status::InvalidArg, e))?;
+        query = query.language(language);
+    }
+    if let Some(scope) = options.scope {
+        query = query.scope(scope);
+    }
+    if let Some(rerank) = options.rerank {
+        query = query.rerank(rerank);
+    }
+    Ok(query)
+}
+
+#[napi]
+pub struct CkIndex {
+    inner: ck_engine::CkIndex,
+}
+
+#[napi]
+impl CkIndex {
+    /// Open the tree at `root`; the index is built on first search or `update()`.
+    #[napi(factory)]
+    pub fn open(root: String) -> Result<Self> {
+        let inner = ck_engine::CkIndex::open(root).map_err(to_napi)?;
+        Ok(Self { inner })
+    }
+
+    #[napi(getter)]
+    pub fn root(&self) -> String {
+        self.inner.root().display().to_string()
+    }
+
+    /// Bring the index up to date, computing embeddings unless `embeddings` is false.
+    #[napi]
+    pub async fn update(&self, embeddings: Option<bool>) -> Result<()> {
+        self.inner
+            .update(embeddings.unwrap_or(true))
+            .await
+            .map_err(to_napi)
+    }
+
+    #[napi]
+    pub async fn search(&self, query: String, options: Option<SearchOptions>) -> Result<Vec<Hit>> {
+        let query = build_query(query, options.unwrap_or_default())?;
+        let hits = self.inner.search(query).await.map_err(to_napi)?;
+        Ok(hits.into_iter().map(Hit::from).collect())
+    }
+}
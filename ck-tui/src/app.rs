@@ -60,6 +60,7 @@ impl TuiApp {
                 status_message: "Ready. Type to search...".to_string(),
                 search_path,
                 selected_files: Default::default(),
+                feedback: Default::default(),
                 search_history: if !query.is_empty() {
                     vec![query]
                 } else {
@@ -166,6 +167,18 @@ impl TuiApp {
                         // Ctrl+Space: Toggle multi-select
                         self.toggle_select();
                     }
+                    KeyCode::Char('p') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        // Ctrl+P: Pin result as relevant and re-run the query
+                        self.toggle_pin();
+                    }
+                    KeyCode::Char('b') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        // Ctrl+B: Ban result as irrelevant and re-run the query
+                        self.toggle_ban();
+                    }
+                    KeyCode::Char('x') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        // Ctrl+X: Clear pins and bans
+                        self.clear_feedback();
+                    }
                     KeyCode::Tab => {
                         self.cycle_mode();
                         self.trigger_search();
@@ -269,6 +282,7 @@ impl TuiApp {
             SearchMode::Regex => SearchMode::Hybrid,
             SearchMode::Hybrid => SearchMode::Semantic,
             SearchMode::Lexical => SearchMode::Semantic, // Skip lexical for now
+            SearchMode::Symbol => SearchMode::Semantic,
         };
         self.state.status_message = format!("Switched to {:?} mode", self.state.mode);
         self.save_config();
@@ -329,6 +343,41 @@ impl TuiApp {
         }
     }
 
+    fn toggle_pin(&mut self) {
+        let Some(result) = self.state.results.get(self.state.selected_idx) else {
+            return;
+        };
+        let label = format!("{}:{}", result.file.display(), result.span.line_start);
+        self.state.status_message = if self.state.feedback.toggle_pin(result) {
+            format!("Pinned {}", label)
+        } else {
+            format!("Unpinned {}", label)
+        };
+        self.trigger_search();
+    }
+
+    fn toggle_ban(&mut self) {
+        let Some(result) = self.state.results.get(self.state.selected_idx) else {
+            return;
+        };
+        let label = format!("{}:{}", result.file.display(), result.span.line_start);
+        self.state.status_message = if self.state.feedback.toggle_ban(result) {
+            format!("Banned {}", label)
+        } else {
+            format!("Unbanned {}", label)
+        };
+        self.trigger_search();
+    }
+
+    fn clear_feedback(&mut self) {
+        if self.state.feedback.is_empty() {
+            return;
+        }
+        self.state.feedback.clear();
+        self.state.status_message = "Cleared pins and bans".to_string();
+        self.trigger_search();
+    }
+
     fn history_previous(&mut self) {
         if self.state.search_history.is_empty() {
             return;
@@ -541,6 +590,7 @@ impl TuiApp {
             SearchMode::Hybrid => None,
             SearchMode::Regex => None,
             SearchMode::Lexical => None,
+            SearchMode::Symbol => None,
         };
 
         // Use the centralized pattern builder from ck-core
@@ -560,6 +610,7 @@ impl TuiApp {
             whole_word: false,
             fixed_string: false,
             line_numbers: true,
+            column: false,
             context_lines: 0,
             before_context_lines: 0,
             after_context_lines: 0,
@@ -569,6 +620,7 @@ impl TuiApp {
             no_snippet: false,
             reindex: false,
             show_scores: true,
+            explain: false,
             show_filenames: true,
             files_with_matches: false,
             files_without_matches: false,
@@ -579,7 +631,25 @@ impl TuiApp {
             full_section: false,
             rerank: false,
             rerank_model: None,
+            rerank_scores: None,
+            rerank_top: None,
+            rerank_budget: None,
+            ann_oversample: None,
             embedding_model: None,
+            languages: Vec::new(),
+            feedback: self.state.feedback.clone(),
+            hyde: false,
+            top_files: None,
+            by_file: false,
+            score_normalization: ck_core::ScoreNormalization::Rank,
+            follow_symlinks: false,
+            as_of: None,
+            blame: false,
+            owner: None,
+            meta: Vec::new(),
+            prefer: None,
+            cancel: None,
+            instruction: None,
         };
 
         let progress_tx = self.progress_tx.clone();
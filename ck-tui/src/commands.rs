@@ -57,6 +57,8 @@ fn show_help(state: &mut TuiState) {
         "  Ctrl+F           - Toggle snippet/full file view".to_string(),
         "  Ctrl+D           - Show chunk metadata (debug)".to_string(),
         "  Ctrl+Space       - Multi-select files".to_string(),
+        "  Ctrl+P / Ctrl+B  - Pin / ban result (steers the query)".to_string(),
+        "  Ctrl+X           - Clear pins and bans".to_string(),
         "  Ctrl+Up/Down     - Navigate search history".to_string(),
         "  Up/Down          - Navigate results".to_string(),
         "  PgUp/PgDn        - Scroll preview".to_string(),
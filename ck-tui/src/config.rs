@@ -30,6 +30,7 @@ mod search_mode_serde {
             SearchMode::Regex => "regex",
             SearchMode::Hybrid => "hybrid",
             SearchMode::Lexical => "lexical",
+            SearchMode::Symbol => "symbol",
         };
         serializer.serialize_str(s)
     }
@@ -44,6 +45,7 @@ mod search_mode_serde {
             "regex" => SearchMode::Regex,
             "hybrid" => SearchMode::Hybrid,
             "lexical" => SearchMode::Lexical,
+            "symbol" => SearchMode::Symbol,
             _ => SearchMode::Semantic, // Default fallback
         })
     }
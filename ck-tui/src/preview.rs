@@ -21,26 +21,32 @@ pub fn load_preview_lines(
     let repo_root = find_repo_root(&resolved_path);
     let is_pdf = pdf::is_pdf_file(&resolved_path);
 
-    let (_content, lines) = if is_pdf {
+    let content = if is_pdf {
         let root = repo_root.clone().ok_or_else(|| {
             "PDF preview unavailable (missing .ck index). Run `ck --index .` first.".to_string()
         })?;
 
-        let cache_path = pdf::get_content_cache_path(&root, &resolved_path);
-        let content = fs::read_to_string(&cache_path).map_err(|err| {
+        ck_index::read_content(&root, &resolved_path).map_err(|err| {
             format!(
                 "PDF preview unavailable ({}). Run `ck --index .` to generate cache.",
                 err
             )
-        })?;
-        let lines: Vec<String> = content.lines().map(|line| line.to_string()).collect();
-        (content, lines)
+        })?
     } else {
-        let content = fs::read_to_string(&resolved_path)
-            .map_err(|err| format!("Could not read {}: {}", resolved_path.display(), err))?;
-        let lines: Vec<String> = content.lines().map(|line| line.to_string()).collect();
-        (content, lines)
+        ck_core::encoding::read_text(&resolved_path)
+            .map(|decoded| decoded.text)
+            .map_err(|err| format!("Could not read {}: {}", resolved_path.display(), err))?
+    };
+    // Redacted as search output is; line breaks are kept, so spans still fit
+    let redactor = ck_models::ProjectConfig::for_path(&resolved_path)
+        .redaction
+        .redactor()
+        .map_err(|err| err.to_string())?;
+    let content = match redactor {
+        Some(redactor) => redactor.redact(&content).into_owned(),
+        None => content,
     };
+    let lines: Vec<String> = content.lines().map(|line| line.to_string()).collect();
 
     // Use live chunking instead of cached index data (same approach as --dump-chunks)
     let chunk_spans = if is_pdf {
@@ -22,6 +22,7 @@ pub fn draw_query_input(f: &mut Frame, area: Rect, state: &TuiState) {
             SearchMode::Regex => "[REG]",
             SearchMode::Hybrid => "[HYB]",
             SearchMode::Lexical => "[LEX]",
+            SearchMode::Symbol => "[SYM]",
         };
         (
             format!(
@@ -46,7 +47,12 @@ pub fn draw_results_list(f: &mut Frame, area: Rect, state: &TuiState, list_state
         .map(|(idx, result)| {
             let score_color = score_to_color(result.score);
             let is_selected = state.selected_files.contains(&result.file);
-            let prefix = if is_selected { "✓ " } else { "  " };
+            let prefix = match (state.feedback.is_pinned(result), is_selected) {
+                (true, true) => "★✓",
+                (true, false) => "★ ",
+                (false, true) => "✓ ",
+                (false, false) => "  ",
+            };
             let content = format!(
                 "{}[{:.3}] {}:{}",
                 prefix,
@@ -109,7 +115,7 @@ pub fn draw_preview(f: &mut Frame, area: Rect, state: &TuiState) {
 }
 
 pub fn draw_status_bar(f: &mut Frame, area: Rect, state: &TuiState) {
-    let help_text = " ↑↓: Nav | Tab: Mode | ^V: View | ^Space: Select | Enter: Open | ^↑↓: History | Esc/q: Quit ";
+    let help_text = " ↑↓: Nav | Tab: Mode | ^V: View | ^Space: Select | ^P/^B: Pin/Ban | Enter: Open | ^↑↓: History | Esc/q: Quit ";
 
     let mut status_spans = vec![Span::styled(
         state.status_message.clone(),
@@ -174,6 +180,18 @@ pub fn draw_status_bar(f: &mut Frame, area: Rect, state: &TuiState) {
         ));
     }
 
+    if !state.feedback.is_empty() {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled(
+            format!(
+                "{} pinned, {} banned",
+                state.feedback.pinned.len(),
+                state.feedback.banned.len()
+            ),
+            Style::default().fg(COLOR_YELLOW),
+        ));
+    }
+
     let index_info = if let Some(stats) = state.index_stats.as_ref() {
         format!(
             "Index: {} files, {} chunks",
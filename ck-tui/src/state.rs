@@ -1,5 +1,6 @@
 use crate::chunks::IndexedChunkMeta;
 use crate::config::PreviewMode;
+use ck_core::RelevanceFeedback;
 use ck_core::SearchMode;
 use ck_core::SearchResult;
 use ck_index::IndexStats;
@@ -21,6 +22,7 @@ pub struct TuiState {
     pub status_message: String,
     pub search_path: PathBuf,
     pub selected_files: HashSet<PathBuf>, // For multi-select
+    pub feedback: RelevanceFeedback,      // Pinned/banned hits steering the query
     pub search_history: Vec<String>,      // Search history
     pub history_index: usize,             // Current position in history
     pub command_mode: bool,               // true when query starts with /
@@ -0,0 +1,122 @@
+//! Browser bindings over `ck_ann::SearchBundle`, so a static docs site can offer
+//! semantic search over a bundle exported with `ck --export-bundle`.
+//!
+//! ```js
+//! import init, { WebIndex } from './pkg/ck_wasm.js';
+//! await init();
+//! const bytes = new Uint8Array(await (await fetch('/ck.bundle')).arrayBuffer());
+//! const index = WebIndex.fromBytes(bytes);
+//! const hits = index.search(await embed(query), 10); // Float32Array from index.model
+//! ```
+//!
+//! The query embedding is computed by the page (for example with transformers.js
+//! running the model named by `index.model`), so nothing here loads a model.
+
+use ck_ann::SearchBundle;
+use wasm_bindgen::prelude::*;
+
+fn to_js(err: anyhow::Error) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// One search hit.
+#[wasm_bindgen(getter_with_clone)]
+pub struct WebHit {
+    /// Path relative to the indexed tree
+    pub path: String,
+    #[wasm_bindgen(js_name = lineStart)]
+    pub line_start: u32,
+    #[wasm_bindgen(js_name = lineEnd)]
+    pub line_end: u32,
+    pub score: f32,
+    pub snippet: String,
+    pub language: Option<String>,
+    pub symbol: Option<String>,
+}
+
+#[wasm_bindgen]
+pub struct WebIndex {
+    bundle: SearchBundle,
+}
+
+#[wasm_bindgen]
+impl WebIndex {
+    /// Load a bundle written by `ck --export-bundle`.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WebIndex, JsError> {
+        let bundle = SearchBundle::from_bytes(bytes).map_err(to_js)?;
+        Ok(Self { bundle })
+    }
+
+    /// Embedding model the bundle was built with; queries must use the same one.
+    #[wasm_bindgen(getter)]
+    pub fn model(&self) -> String {
+        self.bundle.model().to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dimensions(&self) -> usize {
+        self.bundle.dimensions()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.bundle.len()
+    }
+
+    /// The `top_k` chunks closest to `query` (an embedding from `model`), best first.
+    pub fn search(&self, query: &[f32], top_k: usize) -> Result<Vec<WebHit>, JsError> {
+        let hits = self.bundle.search(query, top_k).map_err(to_js)?;
+        Ok(hits
+            .into_iter()
+            .map(|hit| WebHit {
+                path: hit.chunk.path.clone(),
+                line_start: hit.chunk.line_start as u32,
+                line_end: hit.chunk.line_end as u32,
+                score: hit.score,
+                snippet: hit.chunk.snippet.clone(),
+                language: hit.chunk.language.clone(),
+                symbol: hit.chunk.symbol.clone(),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ck_ann::BundleChunk;
+
+    #[test]
+    fn test_web_index_loads_and_searches_a_bundle() {
+        let chunk = |path: &str| BundleChunk {
+            path: path.to_string(),
+            line_start: 3,
+            line_end: 5,
+            language: Some("rust".to_string()),
+            symbol: Some("retry".to_string()),
+            snippet: "fn retry() {}".to_string(),
+        };
+        let bytes = SearchBundle::new(
+            "BAAI/bge-small-en-v1.5",
+            vec![
+                (chunk("src/retry.rs"), vec![1.0, 0.0]),
+                (chunk("src/auth.rs"), vec![0.0, 1.0]),
+            ],
+        )
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+
+        let index = WebIndex::from_bytes(&bytes).unwrap_or_else(|_| panic!("bundle did not load"));
+        assert_eq!(index.model(), "BAAI/bge-small-en-v1.5");
+        assert_eq!((index.dimensions(), index.length()), (2, 2));
+        let hits = index
+            .search(&[0.9, 0.1], 1)
+            .unwrap_or_else(|_| panic!("search failed"));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "src/retry.rs");
+        assert_eq!((hits[0].line_start, hits[0].line_end), (3, 5));
+        assert_eq!(hits[0].symbol.as_deref(), Some("retry"));
+    }
+}